@@ -9,24 +9,34 @@ use embassy_stm32::usart::{Config, Uart};
 use embassy_stm32::{bind_interrupts, peripherals, usart};
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::signal::Signal;
-use embassy_time::Timer;
+use embassy_time::{Duration, Instant, Timer};
 use panic_halt as _;
-use static_cell::StaticCell;
+use static_cell::{ConstStaticCell, StaticCell};
 
 use ushell_dispatcher::{generate_commands_dispatcher, generate_shortcuts_dispatcher};
 use ushell_usercode::commands as uc;
 use ushell_usercode::shortcuts as us;
 
+use ushell2::history::DuplicatePolicy;
+use ushell2::input::key_reader::Key;
+use ushell2::jobs::{AsyncCommand, Job, JobError};
 use ushell2::runner::{run_shell, AsyncReader, ShellConfig};
-use ushell2::{log_info, log_simple};
-use ushell2::logger::{init_logger, LogLevel, LoggerConfig};
+use ushell2::xmodem::{self, Protocol};
+use ushell2::{log_error, log_info, log_simple};
+use ushell2::logger::{attach_log_ring, init_logger, set_timestamp_source, LogLevel, LoggerConfig};
 
 use uart_hal::{
     uart_flush, uart_write,
-    uart_rx_task,
-    GLOBAL_UART_RX, GLOBAL_UART_TX, UART_RX_CHANNEL,
+    uart_store_config, set_baudrate, baudrate,
+    set_serial_format, serial_format, SerialFormat,
+    selftest_tx, selftest_loopback, uart_stats, SelftestError,
+    GLOBAL_UART_TX,
     UartWriter,
 };
+#[cfg(not(feature = "dma-rx"))]
+use uart_hal::{uart_rx_task, GLOBAL_UART_RX, UART_RX_CHANNEL};
+#[cfg(feature = "dma-rx")]
+use uart_hal::{DmaRxReader, GLOBAL_DMA_RX};
 
 // ============================================================================
 // Shell Configuration Constants
@@ -38,6 +48,7 @@ pub const MAX_INPUT_LEN: usize = 128;
 pub const MAX_HEXSTR_LEN: usize = 64;
 pub const MAX_HISTORY_CAPACITY: usize = 256;
 pub const MAX_ERROR_BUFFER_SIZE: usize = 32;
+pub const LOG_RING_SIZE: usize = 1024;
 
 // ============================================================================
 // Shell Dispatcher Code Generation
@@ -50,9 +61,42 @@ generate_commands_dispatcher! {
     path = "../ushell/ushell_usercode/src/commands.cfg"
 }
 
+/// Privilege level of the console. The console is not protected, so it runs
+/// everything; with a login, return the user's level instead.
+fn session_level() -> u8 {
+    commands::LEVEL_ADMIN
+}
+
+/// Runs a command line with the console's privilege level.
+fn dispatch_command<'a>(
+    line: &'a str,
+    error_buffer: &'a mut heapless::String<MAX_ERROR_BUFFER_SIZE>,
+) -> Result<(), &'a str> {
+    commands::dispatch(line, session_level(), error_buffer)
+}
+
+/// Argument completion: the application's own completers, then the `s[a|b]`
+/// keywords of commands.cfg.
+fn complete_arg(command: &str, arg_index: usize, word: &str, offer: &mut dyn FnMut(&str)) {
+    uc::complete_arg(command, arg_index, word, offer);
+    commands::complete_arg(command, arg_index, word, offer);
+}
+
+/// Regions of the STM32F411 the `md`/`mw*`/`mfill` built-ins may touch: the SRAM,
+/// the GPIO ports and, read-only, the flash. Other peripherals are left out: reading
+/// some registers has side effects (a read of the USART data register clears RXNE and
+/// loses shell input).
+fn mem_access(addr: usize, len: usize, write: bool) -> bool {
+    let within = |start: usize, size: usize| addr >= start && addr + len <= start + size;
+    within(0x2000_0000, 128 * 1024)
+        || within(0x4002_0000, 0x2000)
+        || (!write && within(0x0800_0000, 512 * 1024))
+}
+
 generate_shortcuts_dispatcher! {
     mod shortcuts;
     error_buffer_size = crate::MAX_ERROR_BUFFER_SIZE;
+    command_dispatcher = crate::dispatch_command;
     path = "../ushell/ushell_usercode/src/shortcuts.cfg"
 }
 
@@ -69,6 +113,9 @@ bind_interrupts!(struct Irqs {
 
 static UART_WRITER: StaticCell<UartWriter> = StaticCell::new();
 
+// Recent log lines, read back with `log show`.
+static LOG_RING: ConstStaticCell<[u8; LOG_RING_SIZE]> = ConstStaticCell::new([0; LOG_RING_SIZE]);
+
 // Signal sent from `main` to `shell_task` once hardware is fully configured.
 static SYSTEM_READY: Signal<CriticalSectionRawMutex, ()> = Signal::new();
 
@@ -101,8 +148,17 @@ async fn main(spawner: Spawner) {
     // These statics are only written once.
     unsafe {
         *GLOBAL_UART_TX.tx.get() = Some(tx);
-        *GLOBAL_UART_RX.rx.get() = Some(rx);
+        #[cfg(not(feature = "dma-rx"))]
+        {
+            *GLOBAL_UART_RX.rx.get() = Some(rx);
+        }
+        // Circular DMA on DMA1_CH5 instead of polling RX (for high baud rates)
+        #[cfg(feature = "dma-rx")]
+        {
+            *GLOBAL_DMA_RX.reader.get() = Some(DmaRxReader::start(rx, p.DMA1_CH5));
+        }
     }
+    uart_store_config(config);
 
     // Initialize the logger using a safely-allocated static UartWriter.
     // UART_WRITER.init() panics if called more than once, which is what we want.
@@ -114,6 +170,8 @@ async fn main(spawner: Spawner) {
         },
         writer,
     );
+    attach_log_ring(LOG_RING.take());
+    set_timestamp_source(ushell2::clock::now_ms);
 
     log_simple!("System initialized");
     log_simple!("UART configured with async shell (nb_read)");
@@ -123,6 +181,7 @@ async fn main(spawner: Spawner) {
     spawner
         .spawn(blink_led(p.PC13))
         .expect("Failed to spawn blink_led");
+    #[cfg(not(feature = "dma-rx"))]
     spawner
         .spawn(uart_rx_task())
         .expect("Failed to spawn uart_rx_task");
@@ -144,19 +203,131 @@ async fn main(spawner: Spawner) {
 async fn blink_led(pin: peripherals::PC13) {
     let mut led = Output::new(pin, Level::High, Speed::Low);
 
-    log_info!("LED task started");
+    log_info!(target: "led", "LED task started");
 
     loop {
         led.set_high();
-        log_info!("LED ON");
+        log_info!(target: "led", "LED ON");
         Timer::after_millis(500).await;
 
         led.set_low();
-        log_info!("LED OFF");
+        log_info!(target: "led", "LED OFF");
         Timer::after_millis(500).await;
     }
 }
 
+// ============================================================================
+// Console Commands
+// Commands that need the UART live here rather than in ushell_usercode.
+// ============================================================================
+
+/// `baud <rate>`: switches the console UART to another rate.
+pub fn baud(rate: u32) {
+    let old = baudrate().unwrap_or(0);
+    // Still readable at the old rate: the terminal has to follow
+    log_info!("baud | switching {} -> {}, reconnect the terminal", old, rate);
+    match set_baudrate(rate) {
+        Ok(()) => log_simple!("Console at {} baud", rate),
+        Err(e) => log_error!("baud | {}", e.message()),
+    }
+}
+
+/// `serial config <format>`: switches the console UART to another frame (`8N1`, `7E1`, ...).
+pub fn serial(what: &str, format: &str) {
+    if what != "config" {
+        log_error!("serial | usage: serial config <8N1|7E1|...>");
+        return;
+    }
+    let Some(new) = SerialFormat::parse(format) else {
+        log_error!("serial | bad format: {}", format);
+        return;
+    };
+    let (old, new_name) = (serial_format().unwrap_or_default().to_bytes(), new.to_bytes());
+    fn name(bytes: &[u8; 3]) -> &str {
+        core::str::from_utf8(bytes).unwrap_or("?")
+    }
+    log_info!(
+        "serial | switching {} -> {}, reconnect the terminal",
+        name(&old),
+        name(&new_name)
+    );
+    match set_serial_format(new) {
+        Ok(()) => log_simple!("Console at {}", name(&new_name)),
+        Err(e) => log_error!("serial | {}", e.message()),
+    }
+}
+
+/// Pattern length of `uart selftest`.
+const SELFTEST_LEN: usize = 256;
+
+/// `uart selftest [loopback]`: production test of the console UART. `loopback`
+/// additionally checks the echo of every byte with TX jumpered to RX.
+pub fn uart(args: &str) {
+    let mut words = args.split_whitespace();
+    let loopback = match (words.next(), words.next(), words.next()) {
+        (Some("selftest"), None, None) => false,
+        (Some("selftest"), Some("loopback"), None) => true,
+        _ => {
+            log_error!("uart | usage: uart selftest [loopback]");
+            return;
+        }
+    };
+
+    let mut result = selftest_tx(SELFTEST_LEN);
+    if result.is_ok() && loopback {
+        result = selftest_loopback(SELFTEST_LEN, Duration::from_millis(10));
+    }
+    let stats = uart_stats();
+    log_info!(
+        "uart | tx {} bytes ({} errors), rx {} bytes ({} errors), rx queue high-water {}",
+        stats.tx_bytes,
+        stats.tx_errors,
+        stats.rx_bytes,
+        stats.rx_errors,
+        stats.rx_high_water
+    );
+    match result {
+        Ok(()) => log_info!("uart | selftest passed"),
+        Err(e @ SelftestError::TxErrors { errors }) => {
+            log_error!("uart | {}: {} errors", e.message(), errors)
+        }
+        Err(e @ SelftestError::TxTooFast { elapsed_us, min_us }) => {
+            log_error!("uart | {}: {} us, at least {} us expected", e.message(), elapsed_us, min_us)
+        }
+        Err(e @ (SelftestError::NoEcho { index } | SelftestError::Mismatch { index, .. })) => {
+            log_error!("uart | {} at byte {}", e.message(), index)
+        }
+        Err(e) => log_error!("uart | {}", e.message()),
+    }
+}
+
+/// `recv <name>`: receives a file over XMODEM (128 or 1024-byte blocks). The blocks are
+/// only counted: `recv_block` is where an image would be written to flash.
+pub fn recv(name: &str) {
+    match xmodem::start(Protocol::Xmodem, recv_block) {
+        Ok(()) => log_info!("recv | {}: start the XMODEM upload", name),
+        Err(e) => log_error!("recv | {}", e.message()),
+    }
+}
+
+/// Data blocks of `recv`, in order.
+fn recv_block(_offset: u32, _data: &[u8]) -> bool {
+    true
+}
+
+/// `sleep <ms>`: a background job that ends after `ms` milliseconds; the prompt stays
+/// usable meanwhile (`jobs`, `kill <id>`).
+fn sleep(args: &str) -> Result<Job, JobError> {
+    let ms: u64 = args.parse().map_err(|_| JobError::BadArgs("usage: sleep <ms>"))?;
+    Job::new(async move {
+        Timer::after_millis(ms).await;
+        Ok(())
+    })
+}
+
+/// Commands run as background jobs (see `ushell2::jobs`).
+const ASYNC_COMMANDS: &[(&str, AsyncCommand)] = &[("sleep", sleep)];
+
 // ============================================================================
 // Shell Processing Task
 // ============================================================================
@@ -170,23 +341,73 @@ async fn shell_task() {
     log_simple!("Starting async shell...");
     log_simple!("Type '##' for available commands");
 
+    // Non-blocking: try to pull a byte from the RX channel
+    #[cfg(not(feature = "dma-rx"))]
+    let try_read = || UART_RX_CHANNEL.try_receive().ok();
+    // ... or straight from the DMA buffer
+    #[cfg(feature = "dma-rx")]
+    let try_read = {
+        // Safety: set once in `main` before this task was spawned; only taken here.
+        let mut dma_rx = unsafe { (*GLOBAL_DMA_RX.reader.get()).take() };
+        move || dma_rx.as_mut().and_then(DmaRxReader::read_byte)
+    };
+
     let reader = AsyncReader::new(
-        // Non-blocking: try to pull a byte from the RX channel
-        || UART_RX_CHANNEL.try_receive().ok(),
+        try_read,
         // Yield for 50 µs between empty reads to avoid busy-looping
         || Timer::after_micros(50),
         // Yield to the executor after 100 consecutive empty reads —
         // a good balance between latency and cooperative scheduling
         100,
-    );
+    )
+    // Single time base for watch, streams and command budgets
+    .with_clock(|| Instant::now().as_millis());
 
     let config = ShellConfig {
         get_commands: commands::get_commands,
         get_datatypes: commands::get_datatypes,
         get_shortcuts: shortcuts::get_shortcuts,
         is_shortcut: shortcuts::is_supported_shortcut,
-        command_dispatcher: commands::dispatch,
+        is_nohistory: commands::is_nohistory,
+        history_duplicates: DuplicatePolicy::MoveToFront,
+        // Uptime; an RTC's Unix time would show dates in `history -T`
+        history_clock: Some(|| (ushell2::clock::now_ms() / 1000) as u32),
+        now_ms: Some(ushell2::clock::now_ms),
+        complete_arg: Some(complete_arg),
+        completions: Some(&uc::COMPLETIONS),
+        live_validation: true,
+        ghost_completion: true,
+        history_completion: true,
+        snippets: uc::SNIPPETS,
+        snippet_key: Some(Key::F(2)),
+        command_dispatcher: dispatch_command,
         shortcut_dispatcher: shortcuts::dispatch,
+        privilege_level: Some(session_level),
+        async_commands: ASYNC_COMMANDS,
+        rollback_dispatcher: Some(commands::rollback),
+        // Some(ushell2::output::itm_write) mirrors the session to ITM port 0 (SWO)
+        mirror_write: None,
+        // Commands ask for input (join, wipe), so they run in the shell task
+        submit: None,
+        is_busy: None,
+        time_budget: None,
+        on_command_timeout: None,
+        describe_command: Some(commands::describe_command),
+        on_mouse: None,
+        storage: None,
+        mem_access: Some(mem_access),
+        history_store: None,
+        // Help listings stop at `--more--` after each screen of a 24-line terminal
+        terminal_rows: Some(24),
+        // Lines longer than an 80-column terminal scroll horizontally
+        terminal_cols: Some(80),
+        // Replaced by the real size if the terminal answers the probe
+        probe_terminal_size: true,
+        bracketed_paste: true,
+        // Some(&ushell2::input::bindings::VI) for vi-style editing
+        key_bindings: None,
+        // Some(&ushell2::theme::PLAIN) for a terminal without colors
+        theme: None,
         prompt: PROMPT,
     };
 
@@ -7,18 +7,25 @@
 //   - uart_write / uart_flush helpers for shell TX closures
 //   - uart_rx_task: async task that feeds a byte channel from nb_read()
 //   - UART_RX_CHANNEL: the shared channel between RX task and shell reader
+//   - set_baudrate / set_serial_format: runtime reconfiguration of the console UART
+//   - uart_stats / selftest_tx / selftest_loopback: traffic counters and self-tests
+//   - DmaRxReader: interrupt-free RX from a circular DMA buffer (alternative to
+//     uart_rx_task)
 
 #![no_std]
 
-use core::cell::UnsafeCell;
+use core::cell::{Cell, UnsafeCell};
 use core::option::Option::{self, None, Some};
-use core::result::Result::Ok;
+use core::result::Result::{self, Err, Ok};
+use core::sync::atomic::{compiler_fence, AtomicBool, AtomicU32, Ordering};
 
-use embassy_stm32::{peripherals};
+use embassy_stm32::peripherals;
+use embassy_stm32::usart::{Config, ConfigError, DataBits, StopBits};
+pub use embassy_stm32::usart::Parity;
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
 use embassy_sync::channel::Channel;
-use embassy_time::Timer;
-use nb;
+use embassy_time::{Duration, Instant, Timer};
 
 // ============================================================================
 // Global Storage
@@ -59,6 +66,22 @@ pub static GLOBAL_UART_RX: GlobalUartRx = GlobalUartRx {
 /// Fed by `uart_rx_task`, consumed by the shell's `AsyncReader`.
 pub static UART_RX_CHANNEL: Channel<CriticalSectionRawMutex, u8, 1024> = Channel::new();
 
+/// Configuration the UART was set up with, needed to change a single setting later.
+/// Stored by `uart_store_config`.
+static UART_CONFIG: Mutex<CriticalSectionRawMutex, Cell<Option<Config>>> =
+    Mutex::const_new(CriticalSectionRawMutex::new(), Cell::new(None));
+
+/// Set while the UART is reconfigured or self-tested; `uart_rx_task` leaves the
+/// receiver alone.
+static RX_PAUSED: AtomicBool = AtomicBool::new(false);
+
+// Traffic counters, see `uart_stats`
+static TX_BYTES: AtomicU32 = AtomicU32::new(0);
+static TX_ERRORS: AtomicU32 = AtomicU32::new(0);
+static RX_BYTES: AtomicU32 = AtomicU32::new(0);
+static RX_ERRORS: AtomicU32 = AtomicU32::new(0);
+static RX_HIGH_WATER: AtomicU32 = AtomicU32::new(0);
+
 // ============================================================================
 // UartWriter — core::fmt::Write over blocking TX
 // ============================================================================
@@ -79,11 +102,7 @@ impl UartWriter {
     }
 
     fn write_bytes_internal(&mut self, bytes: &[u8]) {
-        unsafe {
-            if let Some(tx) = (*GLOBAL_UART_TX.tx.get()).as_mut() {
-                let _ = tx.blocking_write(bytes);
-            }
-        }
+        uart_write(bytes);
     }
 }
 
@@ -102,7 +121,10 @@ impl core::fmt::Write for UartWriter {
 pub fn uart_write(bytes: &[u8]) {
     unsafe {
         if let Some(tx) = (*GLOBAL_UART_TX.tx.get()).as_mut() {
-            let _ = tx.blocking_write(bytes);
+            match tx.blocking_write(bytes) {
+                Ok(()) => TX_BYTES.fetch_add(bytes.len() as u32, Ordering::Relaxed),
+                Err(_) => TX_ERRORS.fetch_add(1, Ordering::Relaxed),
+            };
         }
     }
 }
@@ -116,6 +138,487 @@ pub fn uart_flush() {
     }
 }
 
+// ============================================================================
+// Runtime Reconfiguration
+// ============================================================================
+
+/// Reasons `set_baudrate` / `set_serial_format` can fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReconfigureError {
+    /// The UART was not set up (`GLOBAL_UART_TX` / `uart_store_config`).
+    NotInitialized,
+    /// The rate cannot be reached from the peripheral clock.
+    TooLow,
+    TooHigh,
+    /// The USART has no such frame (7 data bits need a parity bit).
+    UnsupportedFormat,
+}
+
+impl ReconfigureError {
+    pub fn message(&self) -> &'static str {
+        match self {
+            ReconfigureError::NotInitialized => "UART not initialized",
+            ReconfigureError::TooLow => "baud rate too low",
+            ReconfigureError::TooHigh => "baud rate too high",
+            ReconfigureError::UnsupportedFormat => "unsupported format (7N1, 7N2)",
+        }
+    }
+}
+
+/// Character frame: data bits, parity, stop bits, written as usual (`8N1`, `7E1`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerialFormat {
+    /// 7 or 8.
+    pub data_bits: u8,
+    pub parity: Parity,
+    /// 1 or 2.
+    pub stop_bits: u8,
+}
+
+impl SerialFormat {
+    pub const DEFAULT: SerialFormat = SerialFormat {
+        data_bits: 8,
+        parity: Parity::ParityNone,
+        stop_bits: 1,
+    };
+
+    /// Parses the `<data><N|E|O><stop>` notation, case-insensitive (`7e1`).
+    pub fn parse(text: &str) -> Option<SerialFormat> {
+        let &[data, parity, stop] = text.as_bytes() else {
+            return None;
+        };
+        Some(SerialFormat {
+            data_bits: match data {
+                b'7' => 7,
+                b'8' => 8,
+                _ => return None,
+            },
+            parity: match parity.to_ascii_uppercase() {
+                b'N' => Parity::ParityNone,
+                b'E' => Parity::ParityEven,
+                b'O' => Parity::ParityOdd,
+                _ => return None,
+            },
+            stop_bits: match stop {
+                b'1' => 1,
+                b'2' => 2,
+                _ => return None,
+            },
+        })
+    }
+
+    /// The notation `parse` accepts, e.g. `b"7E1"`.
+    pub fn to_bytes(&self) -> [u8; 3] {
+        let parity = match self.parity {
+            Parity::ParityNone => b'N',
+            Parity::ParityEven => b'E',
+            Parity::ParityOdd => b'O',
+        };
+        [b'0' + self.data_bits, parity, b'0' + self.stop_bits]
+    }
+
+    /// Reads the format back from a driver configuration.
+    fn of(config: &Config) -> SerialFormat {
+        // The USART word length includes the parity bit
+        let word_bits = match config.data_bits {
+            DataBits::DataBits8 => 8,
+            DataBits::DataBits9 => 9,
+        };
+        let parity_bits = if config.parity == Parity::ParityNone { 0 } else { 1 };
+        SerialFormat {
+            data_bits: word_bits - parity_bits,
+            parity: config.parity,
+            stop_bits: if config.stop_bits == StopBits::STOP2 { 2 } else { 1 },
+        }
+    }
+
+    /// Writes the format into a driver configuration.
+    fn apply(&self, config: &mut Config) -> Result<(), ReconfigureError> {
+        let parity_bits = if self.parity == Parity::ParityNone { 0 } else { 1 };
+        config.data_bits = match self.data_bits + parity_bits {
+            8 => DataBits::DataBits8,
+            9 => DataBits::DataBits9,
+            _ => return Err(ReconfigureError::UnsupportedFormat),
+        };
+        config.parity = self.parity;
+        config.stop_bits = match self.stop_bits {
+            2 => StopBits::STOP2,
+            _ => StopBits::STOP1,
+        };
+        Ok(())
+    }
+}
+
+impl Default for SerialFormat {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Records the configuration USART2 was created with. Call it from `main` next to
+/// storing the TX/RX halves; `set_baudrate` / `set_serial_format` change one setting
+/// and keep the rest.
+pub fn uart_store_config(config: Config) {
+    UART_CONFIG.lock(|cell| cell.set(Some(config)));
+}
+
+/// Current baud rate, if the configuration was stored.
+pub fn baudrate() -> Option<u32> {
+    UART_CONFIG.lock(|cell| cell.get()).map(|config| config.baudrate)
+}
+
+/// Current character frame, if the configuration was stored.
+pub fn serial_format() -> Option<SerialFormat> {
+    UART_CONFIG.lock(|cell| cell.get()).map(|config| SerialFormat::of(&config))
+}
+
+/// Switches USART2 to `bps`. On error the old rate stays in effect.
+pub fn set_baudrate(bps: u32) -> Result<(), ReconfigureError> {
+    reconfigure(|config| {
+        config.baudrate = bps;
+        Ok(())
+    })
+}
+
+/// Switches USART2 to another frame, e.g. `7E1` for legacy equipment. On error the
+/// old format stays in effect.
+pub fn set_serial_format(format: SerialFormat) -> Result<(), ReconfigureError> {
+    reconfigure(|config| format.apply(config))
+}
+
+/// Applies `change` to the stored configuration and reprograms USART2: waits until all
+/// pending TX bytes are on the wire, pauses `uart_rx_task`, and drops bytes received
+/// meanwhile (they were sampled with the wrong settings).
+fn reconfigure(
+    change: impl FnOnce(&mut Config) -> Result<(), ReconfigureError>,
+) -> Result<(), ReconfigureError> {
+    let mut config = UART_CONFIG
+        .lock(|cell| cell.get())
+        .ok_or(ReconfigureError::NotInitialized)?;
+    change(&mut config)?;
+
+    // Safety: same single-owner access as `uart_write` (shell task only).
+    let tx = unsafe { (*GLOBAL_UART_TX.tx.get()).as_mut() }
+        .ok_or(ReconfigureError::NotInitialized)?;
+    let _ = tx.blocking_flush();
+
+    RX_PAUSED.store(true, Ordering::Release);
+    let result = tx.set_config(&config).map_err(|e| match e {
+        ConfigError::BaudrateTooLow => ReconfigureError::TooLow,
+        ConfigError::BaudrateTooHigh => ReconfigureError::TooHigh,
+        _ => ReconfigureError::NotInitialized,
+    });
+    if result.is_ok() {
+        UART_CONFIG.lock(|cell| cell.set(Some(config)));
+    }
+    while UART_RX_CHANNEL.try_receive().is_ok() {}
+    RX_PAUSED.store(false, Ordering::Release);
+    result
+}
+
+// ============================================================================
+// Statistics and Self-Test
+// ============================================================================
+
+/// Traffic counters since start-up (or `uart_reset_stats`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct UartStats {
+    /// Bytes written by `uart_write`.
+    pub tx_bytes: u32,
+    /// Failed `uart_write` calls.
+    pub tx_errors: u32,
+    /// Bytes read by `uart_rx_task`.
+    pub rx_bytes: u32,
+    /// Framing, noise, parity and overrun errors seen by `uart_rx_task`.
+    pub rx_errors: u32,
+    /// Most bytes waiting in `UART_RX_CHANNEL` at once.
+    pub rx_high_water: u32,
+}
+
+pub fn uart_stats() -> UartStats {
+    UartStats {
+        tx_bytes: TX_BYTES.load(Ordering::Relaxed),
+        tx_errors: TX_ERRORS.load(Ordering::Relaxed),
+        rx_bytes: RX_BYTES.load(Ordering::Relaxed),
+        rx_errors: RX_ERRORS.load(Ordering::Relaxed),
+        rx_high_water: RX_HIGH_WATER.load(Ordering::Relaxed),
+    }
+}
+
+pub fn uart_reset_stats() {
+    for counter in [&TX_BYTES, &TX_ERRORS, &RX_BYTES, &RX_ERRORS, &RX_HIGH_WATER] {
+        counter.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Reasons a self-test fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelftestError {
+    /// The UART was not set up (`GLOBAL_UART_TX`).
+    NotInitialized,
+    /// Writing the pattern failed `errors` times.
+    TxErrors { errors: u32 },
+    /// The transmitter did not finish sending the pattern.
+    TxNotDrained,
+    /// The pattern drained in `elapsed_us`, faster than its `min_us` on the wire.
+    TxTooFast { elapsed_us: u64, min_us: u64 },
+    /// Nothing came back for the byte at `index` (TX not jumpered to RX?).
+    NoEcho { index: usize },
+    /// A different byte came back.
+    Mismatch { index: usize, sent: u8, received: u8 },
+}
+
+impl SelftestError {
+    pub fn message(&self) -> &'static str {
+        match self {
+            SelftestError::NotInitialized => "UART not initialized",
+            SelftestError::TxErrors { .. } => "TX write failed",
+            SelftestError::TxNotDrained => "TX did not drain",
+            SelftestError::TxTooFast { .. } => "TX drained faster than the baud rate allows",
+            SelftestError::NoEcho { .. } => "no echo (is TX jumpered to RX?)",
+            SelftestError::Mismatch { .. } => "echo differs from the pattern",
+        }
+    }
+}
+
+/// Byte `index` of the self-test pattern: printable ASCII, so the test line is
+/// readable on the console.
+pub fn selftest_pattern(index: usize) -> u8 {
+    b' ' + (index % 95) as u8
+}
+
+/// Sends `len` pattern bytes (and a line break) through `uart_write` and checks that
+/// the transmitter drained: the USART reports transmission complete, and no sooner
+/// than the bytes take on the wire at the configured baud rate.
+pub fn selftest_tx(len: usize) -> Result<(), SelftestError> {
+    // Safety: same single-owner access as `uart_write` (shell task only).
+    let tx = unsafe { (*GLOBAL_UART_TX.tx.get()).as_mut() }
+        .ok_or(SelftestError::NotInitialized)?;
+    // Time only the pattern, not output still queued ahead of it
+    let _ = tx.blocking_flush();
+    let errors_before = TX_ERRORS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let mut chunk = [0u8; 32];
+    let mut sent = 0;
+    while sent < len {
+        let n = (len - sent).min(chunk.len());
+        for (i, byte) in chunk[..n].iter_mut().enumerate() {
+            *byte = selftest_pattern(sent + i);
+        }
+        uart_write(&chunk[..n]);
+        sent += n;
+    }
+    uart_write(b"\r\n");
+
+    let errors = TX_ERRORS.load(Ordering::Relaxed).wrapping_sub(errors_before);
+    if errors != 0 {
+        return Err(SelftestError::TxErrors { errors });
+    }
+    // Safety: as above; `uart_write` is done with it.
+    let tx = unsafe { (*GLOBAL_UART_TX.tx.get()).as_mut() }
+        .ok_or(SelftestError::NotInitialized)?;
+    if tx.blocking_flush().is_err() || !embassy_stm32::pac::USART2.sr().read().tc() {
+        return Err(SelftestError::TxNotDrained);
+    }
+    let elapsed_us = start.elapsed().as_micros();
+
+    // At least 10 bits a byte (start, 8 data, stop); the first byte may already
+    // be in the shift register when the clock starts
+    let bps = baudrate().unwrap_or(115_200).max(1) as u64;
+    let min_us = (len as u64 + 1) * 10 * 1_000_000 / bps;
+    if elapsed_us < min_us {
+        return Err(SelftestError::TxTooFast { elapsed_us, min_us });
+    }
+    Ok(())
+}
+
+/// External loopback test, with TX jumpered to RX: sends `len` pattern bytes one at a
+/// time and reads each echo straight from the USART while `uart_rx_task` is paused.
+/// Waits up to `timeout` for every echo.
+pub fn selftest_loopback(len: usize, timeout: Duration) -> Result<(), SelftestError> {
+    // Safety: same single-owner access as `uart_write` (shell task only).
+    let tx = unsafe { (*GLOBAL_UART_TX.tx.get()).as_mut() }
+        .ok_or(SelftestError::NotInitialized)?;
+    let _ = tx.blocking_flush();
+
+    RX_PAUSED.store(true, Ordering::Release);
+    let usart = embassy_stm32::pac::USART2;
+    // With `DmaRxReader` running, the DMA would take the echo first
+    let dma_rx = usart.cr3().read().dmar();
+    usart.cr3().modify(|w| w.set_dmar(false));
+    // Drop a byte left in the data register (also clears overrun)
+    let _ = usart.sr().read();
+    let _ = usart.dr().read();
+
+    let mut result = Ok(());
+    for index in 0..len {
+        let sent = selftest_pattern(index);
+        if tx.blocking_write(&[sent]).is_err() || tx.blocking_flush().is_err() {
+            result = Err(SelftestError::TxNotDrained);
+            break;
+        }
+        let deadline = Instant::now() + timeout;
+        while !usart.sr().read().rxne() && Instant::now() < deadline {}
+        if !usart.sr().read().rxne() {
+            result = Err(SelftestError::NoEcho { index });
+            break;
+        }
+        let received = usart.dr().read().dr() as u8;
+        if received != sent {
+            result = Err(SelftestError::Mismatch {
+                index,
+                sent,
+                received,
+            });
+            break;
+        }
+    }
+    usart.cr3().modify(|w| w.set_dmar(dma_rx));
+    while UART_RX_CHANNEL.try_receive().is_ok() {}
+    RX_PAUSED.store(false, Ordering::Release);
+    result
+}
+
+// ============================================================================
+// DMA RX Backend
+//
+// USART2 RX feeds a circular buffer through DMA1 stream 5 (channel 4) without any
+// per-byte interrupt. The write position is derived from the stream's remaining
+// transfer count; `DmaRxReader` hands out what lies between its read position and
+// the write position. Use it instead of `uart_rx_task` at high baud rates.
+// ============================================================================
+
+/// Size of the circular DMA buffer. It has to hold all bytes arriving between two
+/// reads; older bytes are overwritten otherwise.
+pub const DMA_RX_BUF_SIZE: usize = 256;
+
+const DMA_RX_STREAM: usize = 5;
+const DMA_RX_CHANNEL: u8 = 4;
+
+struct DmaRxBuffer(UnsafeCell<[u8; DMA_RX_BUF_SIZE]>);
+
+unsafe impl Sync for DmaRxBuffer {}
+
+static DMA_RX_BUF: DmaRxBuffer = DmaRxBuffer(UnsafeCell::new([0; DMA_RX_BUF_SIZE]));
+
+/// Reads USART2 through the circular DMA buffer. Keeps the RX half alive: dropping it
+/// would disable the USART.
+pub struct DmaRxReader {
+    _rx: embassy_stm32::usart::UartRx<'static, peripherals::USART2, embassy_stm32::dma::NoDma>,
+    _dma: peripherals::DMA1_CH5,
+    read_pos: usize,
+}
+
+pub struct GlobalDmaRx {
+    pub reader: UnsafeCell<Option<DmaRxReader>>,
+}
+
+unsafe impl Sync for GlobalDmaRx {}
+
+/// Set by `main` with the `dma-rx` backend, taken by the shell task.
+pub static GLOBAL_DMA_RX: GlobalDmaRx = GlobalDmaRx {
+    reader: UnsafeCell::new(None),
+};
+
+/// DMA write position in the circular buffer.
+pub fn dma_rx_write_pos() -> usize {
+    let remaining = embassy_stm32::pac::DMA1
+        .st(DMA_RX_STREAM)
+        .ndtr()
+        .read()
+        .ndt() as usize;
+    // NDTR reloads to the buffer size right after reaching 0
+    (DMA_RX_BUF_SIZE - remaining) % DMA_RX_BUF_SIZE
+}
+
+impl DmaRxReader {
+    /// Starts circular reception into the DMA buffer. `rx` is the RX half of USART2
+    /// (created with `NoDma`), `dma` the stream wired to USART2 RX.
+    pub fn start(
+        rx: embassy_stm32::usart::UartRx<'static, peripherals::USART2, embassy_stm32::dma::NoDma>,
+        dma: peripherals::DMA1_CH5,
+    ) -> Self {
+        use embassy_stm32::pac;
+        use embassy_stm32::pac::dma::vals;
+
+        pac::RCC.ahb1enr().modify(|w| w.set_dma1en(true));
+        let stream = pac::DMA1.st(DMA_RX_STREAM);
+        stream.cr().modify(|w| w.set_en(false));
+        while stream.cr().read().en() {}
+        // Stale stream 5 flags (high flag register, position 1)
+        pac::DMA1.ifcr(1).write(|w| {
+            w.set_tcif(1, true);
+            w.set_htif(1, true);
+            w.set_teif(1, true);
+            w.set_dmeif(1, true);
+            w.set_feif(1, true);
+        });
+
+        stream.par().write_value(pac::USART2.dr().as_ptr() as u32);
+        stream.m0ar().write_value(DMA_RX_BUF.0.get() as u32);
+        stream.ndtr().write(|w| w.set_ndt(DMA_RX_BUF_SIZE as u16));
+        stream.cr().write(|w| {
+            w.set_chsel(DMA_RX_CHANNEL);
+            w.set_dir(vals::Dir::PERIPHERALTOMEMORY);
+            w.set_psize(vals::Size::BITS8);
+            w.set_msize(vals::Size::BITS8);
+            w.set_minc(true);
+            w.set_circ(true);
+            w.set_pl(vals::Pl::HIGH);
+        });
+        compiler_fence(Ordering::SeqCst);
+        stream.cr().modify(|w| w.set_en(true));
+
+        // Bytes go to the DMA only: no RXNE interrupt
+        pac::USART2.cr1().modify(|w| w.set_rxneie(false));
+        pac::USART2.cr3().modify(|w| w.set_dmar(true));
+
+        Self {
+            _rx: rx,
+            _dma: dma,
+            read_pos: dma_rx_write_pos(),
+        }
+    }
+
+    /// Bytes received since the previous call, as one contiguous slice; empty if there
+    /// are none. Data wrapping around the end of the buffer comes in two calls.
+    pub fn read(&mut self) -> &[u8] {
+        let write_pos = dma_rx_write_pos();
+        compiler_fence(Ordering::SeqCst);
+        let end = if write_pos >= self.read_pos {
+            write_pos
+        } else {
+            DMA_RX_BUF_SIZE
+        };
+        let start = self.read_pos;
+        self.read_pos = end % DMA_RX_BUF_SIZE;
+
+        let pending = (write_pos + DMA_RX_BUF_SIZE - start) % DMA_RX_BUF_SIZE;
+        RX_BYTES.fetch_add((end - start) as u32, Ordering::Relaxed);
+        RX_HIGH_WATER.fetch_max(pending as u32, Ordering::Relaxed);
+        // Safety: the DMA is writing at `write_pos` and beyond, not in `start..end`.
+        unsafe { core::slice::from_raw_parts((DMA_RX_BUF.0.get() as *const u8).add(start), end - start) }
+    }
+
+    /// Next received byte, if any. Fits `AsyncReader`'s read function.
+    pub fn read_byte(&mut self) -> Option<u8> {
+        let write_pos = dma_rx_write_pos();
+        if write_pos == self.read_pos {
+            return None;
+        }
+        compiler_fence(Ordering::SeqCst);
+        // Safety: the byte at `read_pos` is complete, the DMA is past it.
+        let byte = unsafe { (DMA_RX_BUF.0.get() as *const u8).add(self.read_pos).read_volatile() };
+        self.read_pos = (self.read_pos + 1) % DMA_RX_BUF_SIZE;
+        RX_BYTES.fetch_add(1, Ordering::Relaxed);
+        RX_HIGH_WATER.fetch_max(
+            ((write_pos + DMA_RX_BUF_SIZE - self.read_pos) % DMA_RX_BUF_SIZE) as u32 + 1,
+            Ordering::Relaxed,
+        );
+        Some(byte)
+    }
+}
+
 // ============================================================================
 // UART RX Task
 //
@@ -133,10 +636,16 @@ pub async fn uart_rx_task() {
 
     if let Some(mut rx) = rx {
         loop {
+            if RX_PAUSED.load(Ordering::Acquire) {
+                Timer::after_micros(100).await;
+                continue;
+            }
             match rx.nb_read() {
                 Ok(byte) => {
                     // Got a byte — push to channel immediately, no delay
+                    RX_BYTES.fetch_add(1, Ordering::Relaxed);
                     let _ = UART_RX_CHANNEL.send(byte).await;
+                    RX_HIGH_WATER.fetch_max(UART_RX_CHANNEL.len() as u32, Ordering::Relaxed);
                 }
                 Err(nb::Error::WouldBlock) => {
                     // No data — yield so other tasks can run
@@ -144,6 +653,7 @@ pub async fn uart_rx_task() {
                 }
                 Err(nb::Error::Other(_)) => {
                     // RX error — brief back-off
+                    RX_ERRORS.fetch_add(1, Ordering::Relaxed);
                     Timer::after_millis(10).await;
                 }
             }
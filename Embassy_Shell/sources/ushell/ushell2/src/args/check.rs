@@ -0,0 +1,381 @@
+//! Checking typed arguments against a descriptor.
+//!
+//! Used by the live validation of the input line (`InputParser::set_live_validation`):
+//! the arguments typed so far are checked the way the generated dispatcher would parse
+//! them, so a bad value is reported before Enter is pressed. Problems are named after the
+//! dispatcher's errors (`BadUnsigned`, `OutOfRange`, ...).
+//!
+//! Range bounds are checked when they are numeric literals (`D(1..=1024)`); bounds
+//! given as constants are left to the dispatcher. Optional arguments (`[D=100]`) may be
+//! left out: the line is complete once the mandatory ones are typed. Named arguments
+//! (`D:addr`) may also be typed as `--addr=value`.
+
+use super::{ArgErrorKind, FromToken};
+use crate::tokens::Tokenizer;
+
+/// The first argument the dispatcher would reject.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArgProblem {
+    /// 0-based index of the argument.
+    pub index: usize,
+    /// Name of the dispatch error, e.g. `"BadUnsigned"`.
+    pub error: &'static str,
+}
+
+/// Result of checking the arguments typed so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgCheck {
+    /// All arguments typed so far are valid (or still being typed), more are expected.
+    Partial,
+    /// All arguments are present and valid.
+    Complete,
+    /// An argument is invalid, or there are too many.
+    Invalid(ArgProblem),
+}
+
+/// Checks the arguments in `args` (the line after the command name) against
+/// `descriptor`. The word being typed (not followed by a space) is not reported as
+/// invalid until it is finished, but completes the line if it is valid.
+pub fn check_args(descriptor: &str, args: &str) -> ArgCheck {
+    if Specs(descriptor).any(|spec| spec.name.is_some()) {
+        return check_named(descriptor, args);
+    }
+    let mut tokens = Tokenizer::new(args);
+    let mut specs = Specs(descriptor);
+    let mut index = 0;
+    loop {
+        let spec = specs.next();
+        // The rest of the line takes everything
+        if spec.is_some_and(|spec| spec.ch == 'r') {
+            return if tokens.rest().trim().is_empty() {
+                ArgCheck::Partial
+            } else {
+                ArgCheck::Complete
+            };
+        }
+        let Some(token) = tokens.next() else {
+            // Optional arguments only come last
+            return if spec.is_none_or(|spec| spec.optional) {
+                ArgCheck::Complete
+            } else {
+                ArgCheck::Partial
+            };
+        };
+        let typing = tokens.offset() == args.len();
+        let Some(spec) = spec else {
+            return ArgCheck::Invalid(ArgProblem {
+                index,
+                error: "WrongArity",
+            });
+        };
+        match check_token(spec.ch, spec.range, token) {
+            Err(_) if typing => return ArgCheck::Partial,
+            Err(error) => return ArgCheck::Invalid(ArgProblem { index, error }),
+            Ok(()) if typing && specs.next().is_none_or(|spec| spec.optional) => {
+                return ArgCheck::Complete
+            }
+            Ok(()) if typing => return ArgCheck::Partial,
+            Ok(()) => index += 1,
+        }
+    }
+}
+
+/// `check_args` for a descriptor with named arguments: a `--name=value` token is checked
+/// against the argument of that name, the other tokens against the arguments not given
+/// by name, in order. A problem with a named argument is reported at its descriptor index.
+fn check_named(descriptor: &str, args: &str) -> ArgCheck {
+    let position = |name: &str| Specs(descriptor).position(|spec| spec.name == Some(name));
+    // Arguments given by name anywhere on the line (bit per descriptor index)
+    let mut named: u64 = 0;
+    for (name, _) in Tokenizer::new(args).filter_map(by_name) {
+        if let Some(i) = position(name) {
+            named |= 1 << i;
+        }
+    }
+    let mut positional = Specs(descriptor)
+        .enumerate()
+        .filter(|&(i, _)| named & (1 << i) == 0);
+    let mut seen: u64 = 0;
+    let mut tokens = Tokenizer::new(args);
+    let mut count = 0;
+    while let Some(token) = tokens.next() {
+        let typing = tokens.offset() == args.len();
+        let (index, spec, value) = match by_name(token) {
+            Some((name, value)) => match position(name) {
+                Some(i) if seen & (1 << i) == 0 => {
+                    seen |= 1 << i;
+                    let value = value
+                        .strip_prefix('"')
+                        .map_or(value, |v| v.strip_suffix('"').unwrap_or(v));
+                    (i, Specs(descriptor).nth(i), value)
+                }
+                _ if typing => return ArgCheck::Partial,
+                _ => {
+                    return ArgCheck::Invalid(ArgProblem {
+                        index: count,
+                        error: "BadName",
+                    })
+                }
+            },
+            None => match positional.next() {
+                Some((i, spec)) => (i, Some(spec), token),
+                None => {
+                    return ArgCheck::Invalid(ArgProblem {
+                        index: count,
+                        error: "WrongArity",
+                    })
+                }
+            },
+        };
+        let Some(spec) = spec else {
+            return ArgCheck::Partial;
+        };
+        match check_token(spec.ch, spec.range, value) {
+            Err(_) if typing => return ArgCheck::Partial,
+            Err(error) => return ArgCheck::Invalid(ArgProblem { index, error }),
+            Ok(()) => count += 1,
+        }
+    }
+    // Complete once every mandatory argument is given
+    if positional.all(|(_, spec)| spec.optional) {
+        ArgCheck::Complete
+    } else {
+        ArgCheck::Partial
+    }
+}
+
+/// Name and value of a `--name=value` token; `--name` alone is `true`.
+fn by_name(token: &str) -> Option<(&str, &str)> {
+    let named = token.strip_prefix("--")?;
+    Some(named.split_once('=').unwrap_or((named, "true")))
+}
+
+/// One argument of a descriptor.
+#[derive(Clone, Copy)]
+pub(crate) struct Spec<'a> {
+    /// Type character.
+    pub(crate) ch: char,
+    /// Text of the range, if any.
+    pub(crate) range: Option<&'a str>,
+    /// `[D(1..=9)=5]`, or a flag (named `t`).
+    pub(crate) optional: bool,
+    /// Text of the default of a `[T=value]` argument.
+    pub(crate) default: Option<&'a str>,
+    /// `D:addr`: given as `--addr=value`.
+    pub(crate) name: Option<&'a str>,
+}
+
+/// The arguments of a descriptor. `v` has none.
+pub(crate) struct Specs<'a>(pub(crate) &'a str);
+
+impl<'a> Iterator for Specs<'a> {
+    type Item = Spec<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let text = self.0.trim_start();
+        if let Some((optional, after)) = text.strip_prefix('[').and_then(|t| t.split_once(']')) {
+            self.0 = after;
+            // The default follows the range, which may hold `..=`
+            let arg_end = optional.rfind(')').map_or(0, |end| end + 1);
+            let (arg, default) = match optional[arg_end..].split_once('=') {
+                Some((range_end, default)) => {
+                    (&optional[..arg_end + range_end.len()], Some(default.trim()))
+                }
+                None => (optional, None),
+            };
+            let spec = Specs(arg).next()?;
+            return Some(Spec {
+                optional: true,
+                default,
+                ..spec
+            });
+        }
+        let mut chars = text.chars();
+        let ch = chars.next()?;
+        let mut rest = chars.as_str();
+        if ch == 'v' {
+            self.0 = rest;
+            return self.next();
+        }
+        let mut range = None;
+        if let Some((text, after)) = rest.strip_prefix('(').and_then(|r| r.split_once(')')) {
+            range = Some(text);
+            rest = after;
+        }
+        let mut name = None;
+        if let Some(named) = rest.strip_prefix(':') {
+            let end = named
+                .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '-')))
+                .unwrap_or(named.len());
+            name = Some(&named[..end]);
+            rest = &named[end..];
+        }
+        self.0 = rest;
+        Some(Spec {
+            ch,
+            range,
+            optional: ch == 't' && name.is_some(),
+            default: None,
+            name,
+        })
+    }
+}
+
+fn check_token(ch: char, range: Option<&str>, token: &str) -> Result<(), &'static str> {
+    let kind = match ch {
+        'B' => u8::from_token(token).err(),
+        'W' => u16::from_token(token).err(),
+        'D' => u32::from_token(token).err(),
+        'Q' => u64::from_token(token).err(),
+        'X' => u128::from_token(token).err(),
+        'Z' => usize::from_token(token).err(),
+        'b' => i8::from_token(token).err(),
+        'w' => i16::from_token(token).err(),
+        'd' => i32::from_token(token).err(),
+        'q' => i64::from_token(token).err(),
+        'x' => i128::from_token(token).err(),
+        'z' => isize::from_token(token).err(),
+        'f' => f32::from_token(token).err(),
+        'F' => f64::from_token(token).err(),
+        't' => bool::from_token(token).err(),
+        'c' => char::from_token(token).err(),
+        'h' => {
+            let hex = !token.is_empty()
+                && token.len().is_multiple_of(2)
+                && token.bytes().all(|b| b.is_ascii_hexdigit());
+            return if hex { Ok(()) } else { Err("BadHexStr") };
+        }
+        _ => None,
+    };
+    if let Some(kind) = kind {
+        return Err(error_name(kind));
+    }
+    match range {
+        Some(range) if !in_range(range, token) => Err("OutOfRange"),
+        _ => Ok(()),
+    }
+}
+
+const fn error_name(kind: ArgErrorKind) -> &'static str {
+    match kind {
+        ArgErrorKind::WrongArity => "WrongArity",
+        ArgErrorKind::BadBool => "BadBool",
+        ArgErrorKind::BadChar => "BadChar",
+        ArgErrorKind::BadUnsigned => "BadUnsigned",
+        ArgErrorKind::BadSigned => "BadSigned",
+        ArgErrorKind::BadFloat => "BadFloat",
+    }
+}
+
+/// Whether a valid numeric token lies within `lo..hi` / `lo..=hi`; `true` when the
+/// bounds are not numeric literals.
+fn in_range(range: &str, token: &str) -> bool {
+    let (lo, hi, inclusive) = match range.split_once("..=") {
+        Some((lo, hi)) => (lo.trim(), hi.trim(), true),
+        None => match range.split_once("..") {
+            Some((lo, hi)) => (lo.trim(), hi.trim(), false),
+            None => return true,
+        },
+    };
+    if let Ok(value) = i128::from_token(token) {
+        let bound = |b: &str| i128::from_token(b.trim_start_matches('+')).ok();
+        return compare(value, lo, hi, inclusive, bound);
+    }
+    if let Ok(value) = f64::from_token(token) {
+        return compare(value, lo, hi, inclusive, |b| f64::from_token(b).ok());
+    }
+    true
+}
+
+fn compare<T: PartialOrd>(
+    value: T,
+    lo: &str,
+    hi: &str,
+    inclusive: bool,
+    bound: impl Fn(&str) -> Option<T>,
+) -> bool {
+    // A bound that is not a literal cannot be checked here
+    let (lo, hi) = match (lo.is_empty(), hi.is_empty()) {
+        (false, _) if bound(lo).is_none() => return true,
+        (_, false) if bound(hi).is_none() => return true,
+        _ => (bound(lo), bound(hi)),
+    };
+    lo.is_none_or(|lo| value >= lo)
+        && hi.is_none_or(|hi| if inclusive { value <= hi } else { value < hi })
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problem(index: usize, error: &'static str) -> ArgCheck {
+        ArgCheck::Invalid(ArgProblem { index, error })
+    }
+
+    #[test]
+    fn test_check_args() {
+        assert_eq!(check_args("Bt", ""), ArgCheck::Partial);
+        // The word being typed is not judged
+        assert_eq!(check_args("Bt", "300"), ArgCheck::Partial);
+        assert_eq!(check_args("Bt", "300 "), problem(0, "BadUnsigned"));
+        assert_eq!(check_args("Bt", "3 yes "), problem(1, "BadBool"));
+        assert_eq!(check_args("Bt", "3 y"), ArgCheck::Partial);
+        assert_eq!(check_args("Bt", "3 1"), ArgCheck::Complete);
+        assert_eq!(check_args("Bt", "3 1 "), ArgCheck::Complete);
+        assert_eq!(check_args("Bt", "3 1 4"), problem(2, "WrongArity"));
+        assert_eq!(check_args("v", "x"), problem(0, "WrongArity"));
+        assert_eq!(check_args("v", ""), ArgCheck::Complete);
+        assert_eq!(check_args("sh", "\"a b\" 0a1 "), problem(1, "BadHexStr"));
+        assert_eq!(check_args("Br", "1 "), ArgCheck::Partial);
+        assert_eq!(check_args("Br", "1 any 300 text"), ArgCheck::Complete);
+    }
+
+    #[test]
+    fn test_ranges() {
+        assert_eq!(check_args("D(1..=1024)B", "1024 "), ArgCheck::Partial);
+        assert_eq!(check_args("D(1..=1024)B", "0 "), problem(0, "OutOfRange"));
+        assert_eq!(check_args("d(-5..5)", "5 "), problem(0, "OutOfRange"));
+        assert_eq!(check_args("d(-5..5)", "-5 "), ArgCheck::Complete);
+        assert_eq!(check_args("f(0.0..=1.0)", "1.5 "), problem(0, "OutOfRange"));
+        assert_eq!(check_args("B(..10)", "0x0a "), problem(0, "OutOfRange"));
+        // Bounds that are not literals are left to the dispatcher
+        assert_eq!(check_args("D(1..=MAX)", "0 "), ArgCheck::Complete);
+    }
+
+    #[test]
+    fn test_optional_arguments() {
+        let desc = "B[D(1..=1024)=100][t=false]";
+        assert_eq!(check_args(desc, ""), ArgCheck::Partial);
+        assert_eq!(check_args(desc, "3"), ArgCheck::Complete);
+        assert_eq!(check_args(desc, "3 "), ArgCheck::Complete);
+        assert_eq!(check_args(desc, "3 0 "), problem(1, "OutOfRange"));
+        assert_eq!(check_args(desc, "3 1024 x "), problem(2, "BadBool"));
+        assert_eq!(check_args(desc, "3 1024 1"), ArgCheck::Complete);
+        assert_eq!(check_args(desc, "3 1024 1 2"), problem(3, "WrongArity"));
+        assert_eq!(check_args("[D=5]", ""), ArgCheck::Complete);
+    }
+
+    #[test]
+    fn test_named_arguments() {
+        let desc = "D:addr D(1..=64):len t:verbose";
+        assert_eq!(check_args(desc, "--len=8 "), ArgCheck::Partial);
+        assert_eq!(check_args(desc, "--len=8 16"), ArgCheck::Complete);
+        assert_eq!(check_args(desc, "--len=8 16 --verbose"), ArgCheck::Complete);
+        assert_eq!(check_args(desc, "--verbose 16 8"), ArgCheck::Complete);
+        assert_eq!(check_args(desc, "--len=99 "), problem(1, "OutOfRange"));
+        assert_eq!(check_args(desc, "16 --addr=1 "), ArgCheck::Complete);
+        assert_eq!(
+            check_args(desc, "--addr=1 --addr=2 "),
+            problem(1, "BadName")
+        );
+        assert_eq!(check_args(desc, "--size=1 "), problem(0, "BadName"));
+        // Names and values being typed are not judged
+        assert_eq!(check_args(desc, "--ver"), ArgCheck::Partial);
+        assert_eq!(check_args(desc, "--len=9"), ArgCheck::Partial);
+        assert_eq!(check_args(desc, "1 2 true 4 "), problem(3, "WrongArity"));
+    }
+}
@@ -0,0 +1,201 @@
+//! Typed command arguments.
+//!
+//! `#[derive(ShellArgs)]` (from `ushell_dispatcher`) maps the fields of a struct to a
+//! descriptor and parses them from tokens, so a command takes one struct instead of
+//! many positional parameters:
+//!
+//! ```ignore
+//! #[derive(ShellArgs)]
+//! pub struct PwmArgs {
+//!     channel: u8,
+//!     duty: u16,
+//!     frequency: u32,
+//! }
+//!
+//! pub fn pwm(args: PwmArgs) { ... }
+//! ```
+//!
+//! ```text
+//! S{crate::pwm::PwmArgs} : crate::pwm::pwm "set a PWM output",
+//! ```
+//!
+//! Fields are parsed like the descriptor characters of their types (`0x`/`0o`/`0b`
+//! integers, `1|true|0|false` booleans, ...). Handlers can also call `from_tokens`
+//! directly on tokens of their own (see `crate::tokens::Tokenizer`).
+//!
+//! `check_args` checks typed arguments against a descriptor without running anything
+//! (see `check`).
+
+pub mod check;
+
+pub use check::{check_args, ArgCheck, ArgProblem};
+
+/// Why an argument was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgErrorKind {
+    /// The number of tokens differs from `ShellArgs::ARITY`.
+    WrongArity,
+    BadBool,
+    BadChar,
+    BadUnsigned,
+    BadSigned,
+    BadFloat,
+}
+
+/// An argument that failed to parse: its 0-based `index`, or for `WrongArity` the
+/// number of arguments expected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArgError {
+    pub index: u8,
+    pub kind: ArgErrorKind,
+}
+
+/// A struct parsed from command arguments, implemented by `#[derive(ShellArgs)]`.
+pub trait ShellArgs<'a>: Sized {
+    /// Descriptor of the fields, in order (e.g. `"BWD"`), shown in command listings.
+    const DESCRIPTOR: &'static str;
+    /// Number of fields, hence of arguments.
+    const ARITY: usize;
+
+    /// Parses exactly `ARITY` tokens into the fields.
+    fn from_tokens(tokens: &[&'a str]) -> Result<Self, ArgError>;
+}
+
+/// A field type of a `ShellArgs` struct.
+pub trait FromToken<'a>: Sized {
+    fn from_token(token: &'a str) -> Result<Self, ArgErrorKind>;
+}
+
+macro_rules! int_from_token {
+    ($kind:ident: $($ty:ty),*) => {$(
+        impl FromToken<'_> for $ty {
+            fn from_token(token: &str) -> Result<Self, ArgErrorKind> {
+                let token = token.trim();
+                let parsed = if let Some(digits) = token.strip_prefix("0x") {
+                    <$ty>::from_str_radix(digits, 16)
+                } else if let Some(digits) = token.strip_prefix("0o") {
+                    <$ty>::from_str_radix(digits, 8)
+                } else if let Some(digits) = token.strip_prefix("0b") {
+                    <$ty>::from_str_radix(digits, 2)
+                } else {
+                    token.parse::<$ty>()
+                };
+                parsed.map_err(|_| ArgErrorKind::$kind)
+            }
+        }
+    )*};
+}
+
+int_from_token!(BadUnsigned: u8, u16, u32, u64, u128, usize);
+int_from_token!(BadSigned: i8, i16, i32, i64, i128, isize);
+
+impl FromToken<'_> for f32 {
+    fn from_token(token: &str) -> Result<Self, ArgErrorKind> {
+        token.parse().map_err(|_| ArgErrorKind::BadFloat)
+    }
+}
+
+impl FromToken<'_> for f64 {
+    fn from_token(token: &str) -> Result<Self, ArgErrorKind> {
+        token.parse().map_err(|_| ArgErrorKind::BadFloat)
+    }
+}
+
+impl FromToken<'_> for bool {
+    fn from_token(token: &str) -> Result<Self, ArgErrorKind> {
+        match token {
+            "1" | "true" | "True" | "TRUE" => Ok(true),
+            "0" | "false" | "False" | "FALSE" => Ok(false),
+            _ => Err(ArgErrorKind::BadBool),
+        }
+    }
+}
+
+impl FromToken<'_> for char {
+    fn from_token(token: &str) -> Result<Self, ArgErrorKind> {
+        let mut chars = token.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            _ => Err(ArgErrorKind::BadChar),
+        }
+    }
+}
+
+impl<'a> FromToken<'a> for &'a str {
+    fn from_token(token: &'a str) -> Result<Self, ArgErrorKind> {
+        Ok(token)
+    }
+}
+
+/// Parses field `index` of a `ShellArgs` struct (used by the derived `from_tokens`).
+pub fn field<'a, T: FromToken<'a>>(tokens: &[&'a str], index: u8) -> Result<T, ArgError> {
+    T::from_token(tokens[index as usize]).map_err(|kind| ArgError { index, kind })
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What `#[derive(ShellArgs)]` generates for `struct Pwm<'a> { channel: u8, mode: &'a str, on: bool }`.
+    #[derive(Debug, PartialEq)]
+    struct Pwm<'a> {
+        channel: u8,
+        mode: &'a str,
+        on: bool,
+    }
+
+    impl<'a> ShellArgs<'a> for Pwm<'a> {
+        const DESCRIPTOR: &'static str = "Bst";
+        const ARITY: usize = 3;
+
+        fn from_tokens(tokens: &[&'a str]) -> Result<Self, ArgError> {
+            if tokens.len() != Self::ARITY {
+                return Err(ArgError {
+                    index: Self::ARITY as u8,
+                    kind: ArgErrorKind::WrongArity,
+                });
+            }
+            Ok(Self {
+                channel: field(tokens, 0)?,
+                mode: field(tokens, 1)?,
+                on: field(tokens, 2)?,
+            })
+        }
+    }
+
+    #[test]
+    fn test_fields_parse_like_descriptors() {
+        assert_eq!(u8::from_token("0x1f"), Ok(31));
+        assert_eq!(u16::from_token("0b101"), Ok(5));
+        assert_eq!(i32::from_token("-12"), Ok(-12));
+        assert_eq!(u8::from_token("256"), Err(ArgErrorKind::BadUnsigned));
+        assert_eq!(i8::from_token("x"), Err(ArgErrorKind::BadSigned));
+        assert_eq!(f32::from_token("1.5"), Ok(1.5));
+        assert_eq!(bool::from_token("TRUE"), Ok(true));
+        assert_eq!(char::from_token("ab"), Err(ArgErrorKind::BadChar));
+
+        assert_eq!(
+            Pwm::from_tokens(&["2", "fast", "1"]),
+            Ok(Pwm {
+                channel: 2,
+                mode: "fast",
+                on: true
+            })
+        );
+        assert_eq!(
+            Pwm::from_tokens(&["2", "fast", "maybe"]),
+            Err(ArgError {
+                index: 2,
+                kind: ArgErrorKind::BadBool
+            })
+        );
+        assert_eq!(
+            Pwm::from_tokens(&["2"]).unwrap_err().kind,
+            ArgErrorKind::WrongArity
+        );
+    }
+}
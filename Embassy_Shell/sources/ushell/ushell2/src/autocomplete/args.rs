@@ -0,0 +1,160 @@
+//! Argument completion.
+//!
+//! Once the command name is complete, Tab asks the application's `ArgCompleter` for
+//! candidates for the word before the cursor:
+//! - no candidate: bell
+//! - one candidate: the word is replaced by it, followed by a space (unless the
+//!   candidate ends with `.` or `/`, so completion can continue inside a path)
+//! - several candidates: the word is extended to their longest common prefix, or the
+//!   candidates are listed if it cannot be extended
+//!
+//! Hierarchical values such as settings keys (`net.wifi.ssid`) are completed one
+//! segment at a time with `complete_path`:
+//!
+//! ```ignore
+//! fn settings_children(parent: &str, emit: &mut dyn FnMut(&str)) {
+//!     let names: &[&str] = match parent {
+//!         "" => &["net", "log"],
+//!         "net" => &["wifi", "eth"],
+//!         "net.wifi" => &["ssid", "channel"],
+//!         _ => &[],
+//!     };
+//!     names.iter().for_each(|n| emit(n));
+//! }
+//!
+//! fn complete_arg(command: &str, arg_index: usize, word: &str, offer: &mut dyn FnMut(&str)) {
+//!     if command == "set" && arg_index == 0 {
+//!         complete_path(settings_children, word, offer);
+//!     }
+//! }
+//! ```
+
+use crate::heapless::String;
+
+/// Offers completion candidates for an argument.
+///
+/// Called with the command name, the index of the argument being completed (0 for the
+/// first argument) and the word typed so far. Candidates are passed to `offer` as the
+/// complete word; candidates not starting with the typed word are ignored.
+pub type ArgCompleter =
+    fn(command: &str, arg_index: usize, word: &str, offer: &mut dyn FnMut(&str));
+
+/// Lists the children of a node of a dot-separated hierarchy by passing each child
+/// segment name to `emit`. `parent` is `""` for the root.
+pub type ChildrenFn = fn(parent: &str, emit: &mut dyn FnMut(&str));
+
+/// Longest path `complete_path` can build, in bytes.
+pub const PATH_MAX_LEN: usize = 64;
+
+/// Offers the completions of `word` in a dot-separated hierarchy: the children of the
+/// path before the last `.` are offered as full paths, branches with a trailing `.`.
+pub fn complete_path(children: ChildrenFn, word: &str, offer: &mut dyn FnMut(&str)) {
+    let parent = word.rsplit_once('.').map_or("", |(parent, _)| parent);
+    children(parent, &mut |child| {
+        let mut path: String<PATH_MAX_LEN> = String::new();
+        if !parent.is_empty() && (path.push_str(parent).is_err() || path.push('.').is_err()) {
+            return;
+        }
+        if path.push_str(child).is_err() {
+            return;
+        }
+        let mut is_branch = false;
+        children(path.as_str(), &mut |_| is_branch = true);
+        if is_branch && path.push('.').is_err() {
+            return;
+        }
+        offer(path.as_str());
+    });
+}
+
+/// Reduces the candidates offered for `word` to their longest common prefix.
+pub(crate) struct CommonPrefix<'w, const N: usize> {
+    word: &'w str,
+    pub(crate) common: String<N>,
+    pub(crate) matches: usize,
+}
+
+impl<'w, const N: usize> CommonPrefix<'w, N> {
+    pub(crate) fn new(word: &'w str) -> Self {
+        Self {
+            word,
+            common: String::new(),
+            matches: 0,
+        }
+    }
+
+    pub(crate) fn offer(&mut self, candidate: &str) {
+        if !candidate.starts_with(self.word) {
+            return;
+        }
+        if self.matches == 0 {
+            self.common.clear();
+            let _ = self.common.push_str(candidate);
+        } else {
+            let shared = self
+                .common
+                .char_indices()
+                .zip(candidate.chars())
+                .find(|((_, a), b)| a != b)
+                .map_or(self.common.len().min(candidate.len()), |((i, _), _)| i);
+            self.common.truncate(shared);
+        }
+        self.matches += 1;
+    }
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use std::string::String as StdString;
+    use std::vec::Vec as StdVec;
+
+    fn settings_children(parent: &str, emit: &mut dyn FnMut(&str)) {
+        let names: &[&str] = match parent {
+            "" => &["net", "log"],
+            "net" => &["wifi", "eth"],
+            "net.wifi" => &["ssid", "channel"],
+            _ => &[],
+        };
+        names.iter().for_each(|n| emit(n));
+    }
+
+    fn offered(word: &str) -> StdVec<StdString> {
+        let mut out = StdVec::new();
+        complete_path(settings_children, word, &mut |c| {
+            if c.starts_with(word) {
+                out.push(StdString::from(c));
+            }
+        });
+        out
+    }
+
+    #[test]
+    fn test_complete_path_segments() {
+        assert_eq!(offered(""), ["net.", "log"]);
+        assert_eq!(offered("n"), ["net."]);
+        assert_eq!(offered("net."), ["net.wifi.", "net.eth"]);
+        assert_eq!(offered("net.wifi.c"), ["net.wifi.channel"]);
+        assert!(offered("net.x.").is_empty());
+    }
+
+    #[test]
+    fn test_common_prefix() {
+        let mut prefix = CommonPrefix::<16>::new("s");
+        prefix.offer("status");
+        prefix.offer("stack");
+        prefix.offer("reset");
+        assert_eq!(prefix.matches, 2);
+        assert_eq!(prefix.common.as_str(), "sta");
+
+        let mut prefix = CommonPrefix::<16>::new("");
+        prefix.offer("ab");
+        prefix.offer("a");
+        assert_eq!(prefix.common.as_str(), "a");
+    }
+}
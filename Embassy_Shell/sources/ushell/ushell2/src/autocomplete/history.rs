@@ -0,0 +1,149 @@
+//! Completion of whole command lines from the history.
+//!
+//! The command table completes names only; the history knows the lines that were run,
+//! arguments included. With history completion on (see
+//! `InputParser::set_history_completion`), Up on a typed line walks back through the
+//! earlier lines starting with it instead of through the whole history, and Down walks
+//! forward again, back to the typed text:
+//!
+//! ```text
+//! >> led
+//! (Up)    >> led 1 blink 250
+//! (Up)    >> led 0
+//! (Down)  >> led 1 blink 250
+//! (Down)  >> led
+//! ```
+//!
+//! Tab offers the same lines once arguments are typed (`led 1` + Tab), and ghost
+//! completion shows the rest of the newest one. Entries are compared in place (see
+//! `History::iter`); only the line shown is copied.
+
+use crate::heapless::String;
+use crate::history::{History, HistoryEntry};
+
+/// A walk through the history lines starting with a typed prefix.
+#[derive(Debug, Default)]
+pub struct HistoryCompletion<const IML: usize> {
+    /// The typed text, shown again past the newest match.
+    prefix: String<IML>,
+    /// Index of the history entry shown, `None` while the typed text is shown.
+    shown: Option<usize>,
+    active: bool,
+}
+
+impl<const IML: usize> HistoryCompletion<IML> {
+    pub const fn new() -> Self {
+        Self {
+            prefix: String::new(),
+            shown: None,
+            active: false,
+        }
+    }
+
+    /// Whether a walk is in progress.
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Starts a walk through the lines starting with `prefix`, unless one is in progress.
+    pub fn begin(&mut self, prefix: &str) {
+        if self.active {
+            return;
+        }
+        self.prefix.clear();
+        let _ = self.prefix.push_str(prefix);
+        self.shown = None;
+        self.active = true;
+    }
+
+    /// Ends the walk, leaving the line as it is (the line was edited or run).
+    pub fn reset(&mut self) {
+        self.active = false;
+        self.shown = None;
+    }
+
+    /// The next older line starting with the prefix; `None` past the oldest, where the
+    /// walk stays.
+    pub fn older<const HTC: usize>(&mut self, history: &History<HTC>) -> Option<String<IML>> {
+        let end = self.shown.unwrap_or(history.len());
+        let (index, line) = history
+            .iter()
+            .enumerate()
+            .take(end)
+            .rev()
+            .find(|(_, entry)| entry.len() > self.prefix.len() && entry.starts_with(&self.prefix))
+            .and_then(|(index, entry)| Some((index, entry.to_line()?)))?;
+        self.shown = Some(index);
+        Some(line)
+    }
+
+    /// The next newer line starting with the prefix, or the prefix itself past the
+    /// newest; `None` if the prefix is shown already.
+    pub fn newer<const HTC: usize>(&mut self, history: &History<HTC>) -> Option<String<IML>> {
+        let shown = self.shown?;
+        let newer = history
+            .iter()
+            .enumerate()
+            .skip(shown + 1)
+            .find(|(_, entry)| entry.len() > self.prefix.len() && entry.starts_with(&self.prefix))
+            .and_then(|(index, entry)| Some((index, entry.to_line()?)));
+        match newer {
+            Some((index, line)) => {
+                self.shown = Some(index);
+                Some(line)
+            }
+            None => {
+                self.shown = None;
+                Some(self.prefix.clone())
+            }
+        }
+    }
+}
+
+/// The newest line of `history` starting with `prefix` and longer than it.
+pub fn newest_match<'h, const HTC: usize>(
+    history: &'h History<HTC>,
+    prefix: &str,
+) -> Option<HistoryEntry<'h>> {
+    history
+        .iter()
+        .rev()
+        .find(|entry| entry.len() > prefix.len() && entry.starts_with(prefix))
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(all(test, not(feature = "no-history")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_walk_prefixed_lines() {
+        let mut history: History<128> = History::new();
+        for line in ["led 0", "reset", "led 1 blink 250", "led"] {
+            history.push(line);
+        }
+
+        let mut walk: HistoryCompletion<32> = HistoryCompletion::new();
+        walk.begin("led");
+        assert_eq!(walk.older(&history).unwrap(), "led 1 blink 250");
+        assert_eq!(walk.older(&history).unwrap(), "led 0");
+        assert_eq!(walk.older(&history), None);
+        assert_eq!(walk.newer(&history).unwrap(), "led 1 blink 250");
+        assert_eq!(walk.newer(&history).unwrap(), "led");
+        assert_eq!(walk.newer(&history), None);
+
+        // Already walking: the prefix is kept
+        walk.begin("led 1");
+        assert_eq!(walk.older(&history).unwrap(), "led 1 blink 250");
+        assert!(walk.is_active());
+        walk.reset();
+        assert!(!walk.is_active());
+
+        let newest = newest_match(&history, "led ").unwrap();
+        assert_eq!(newest.to_line::<32>().unwrap(), "led 1 blink 250");
+        assert!(newest_match(&history, "reset").is_none());
+    }
+}
@@ -1,5 +1,13 @@
 use crate::heapless::{String, Vec};
 
+pub mod args;
+pub mod history;
+pub mod registry;
+
+pub use args::{complete_path, ArgCompleter, ChildrenFn};
+pub use history::HistoryCompletion;
+pub use registry::{CompletionRegistry, CompletionSource};
+
 /// Autocomplete struct for managing and filtering command candidates.
 /// Optimized to only load candidates after the first character is entered.
 ///
@@ -7,6 +15,7 @@ use crate::heapless::{String, Vec};
 /// - `NAC`: Autocomplete Number of Candidates (can now be MAX_COMMANDS_PER_LETTER)
 /// - `FNL`: Function Name Length
 ///
+#[cfg(not(feature = "no-autocomplete"))]
 pub struct Autocomplete<'a, const NAC: usize, const FNL: usize> {
     /// Current candidates (subset based on first character).
     candidates: Vec<&'a str, NAC>,
@@ -20,6 +29,7 @@ pub struct Autocomplete<'a, const NAC: usize, const FNL: usize> {
     first_char_loaded: Option<char>,
 }
 
+#[cfg(not(feature = "no-autocomplete"))]
 impl<'a, const NAC: usize, const FNL: usize> Default for Autocomplete<'a, NAC, FNL> {
     fn default() -> Self {
         Self {
@@ -32,6 +42,7 @@ impl<'a, const NAC: usize, const FNL: usize> Default for Autocomplete<'a, NAC, F
     }
 }
 
+#[cfg(not(feature = "no-autocomplete"))]
 impl<'a, const NAC: usize, const FNL: usize> Autocomplete<'a, NAC, FNL> {
     /// Creates a new empty Autocomplete instance.
     /// Candidates are loaded lazily when the first character is typed.
@@ -190,9 +201,57 @@ impl<'a, const NAC: usize, const FNL: usize> Autocomplete<'a, NAC, FNL> {
     }
 }
 
+// ==================== DISABLED AUTOCOMPLETE =======================
+
+/// Zero-sized stand-in for `Autocomplete` used when the `no-autocomplete` feature is enabled.
+///
+/// Keeps the same API (and the `NAC`/`FNL` parameters) so `InputParser` and `ShellConfig`
+/// are unchanged, but never loads or suggests any candidate.
+///
+#[cfg(feature = "no-autocomplete")]
+pub struct Autocomplete<'a, const NAC: usize, const FNL: usize> {
+    _marker: core::marker::PhantomData<&'a str>,
+}
+
+#[cfg(feature = "no-autocomplete")]
+impl<'a, const NAC: usize, const FNL: usize> Default for Autocomplete<'a, NAC, FNL> {
+    fn default() -> Self {
+        Self {
+            _marker: core::marker::PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "no-autocomplete")]
+impl<'a, const NAC: usize, const FNL: usize> Autocomplete<'a, NAC, FNL> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update_input<F>(&mut self, _new_input: &str, _get_candidates: F)
+    where
+        F: FnOnce(char) -> &'a [&'a str],
+    {
+    }
+
+    pub fn cycle_forward(&mut self) {}
+
+    pub fn cycle_backward(&mut self) {}
+
+    pub fn current_input(&self) -> &str {
+        ""
+    }
+
+    pub fn filtered_candidates(&self) -> &[&'a str] {
+        &[]
+    }
+
+    pub fn reset(&mut self) {}
+}
+
 // ==================== TESTS =======================
 
-#[cfg(test)]
+#[cfg(all(test, not(feature = "no-autocomplete")))]
 mod tests {
     use super::*;
     use heapless::{String, Vec};
@@ -0,0 +1,164 @@
+//! Runtime completion values.
+//!
+//! Command handlers can publish values found at runtime as completion candidates for an
+//! argument of another command, e.g. the addresses found by `i2c scan` for the first
+//! argument of `i2c read`:
+//!
+//! ```ignore
+//! pub static COMPLETIONS: CompletionRegistry<4, 8, 6> = CompletionRegistry::new();
+//!
+//! pub fn scan() {
+//!     COMPLETIONS.clear("i2c read", 0);
+//!     for addr in probe_bus() {
+//!         let mut text: String<6> = String::new();
+//!         let _ = write!(text, "0x{:02x}", addr);
+//!         COMPLETIONS.add("i2c read", 0, &text);
+//!     }
+//! }
+//! ```
+//!
+//! The registry is handed to the shell (`ShellConfig::completions`) and consulted by
+//! the argument completion after the application's `ArgCompleter`.
+
+use core::cell::RefCell;
+use critical_section::Mutex;
+
+use crate::heapless::{String, Vec};
+
+/// Source of completion candidates consulted next to the `ArgCompleter`.
+pub trait CompletionSource: Sync {
+    /// Passes the candidates for argument `arg_index` of `command` to `offer`.
+    fn offer_values(&self, command: &str, arg_index: usize, offer: &mut dyn FnMut(&str));
+}
+
+struct Values<const V: usize, const L: usize> {
+    command: &'static str,
+    arg_index: u8,
+    values: Vec<String<L>, V>,
+}
+
+/// Completion values keyed by command name and argument index, for at most `N`
+/// arguments of `V` values of up to `L` bytes each.
+pub struct CompletionRegistry<const N: usize, const V: usize, const L: usize> {
+    entries: Mutex<RefCell<Vec<Values<V, L>, N>>>,
+}
+
+impl<const N: usize, const V: usize, const L: usize> CompletionRegistry<N, V, L> {
+    pub const fn new() -> Self {
+        Self {
+            entries: Mutex::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    /// Adds a candidate for argument `arg_index` of `command` (a full command name such
+    /// as `"i2c read"`). Returns `false` if the value is too long or the registry is full;
+    /// a value already present is accepted once.
+    pub fn add(&self, command: &'static str, arg_index: u8, value: &str) -> bool {
+        let Ok(value) = String::try_from(value) else {
+            return false;
+        };
+        critical_section::with(|cs| {
+            let mut entries = self.entries.borrow_ref_mut(cs);
+            let index = match entries
+                .iter()
+                .position(|e| e.command == command && e.arg_index == arg_index)
+            {
+                Some(index) => index,
+                None => {
+                    let entry = Values {
+                        command,
+                        arg_index,
+                        values: Vec::new(),
+                    };
+                    if entries.push(entry).is_err() {
+                        return false;
+                    }
+                    entries.len() - 1
+                }
+            };
+            let values = &mut entries[index].values;
+            values.contains(&value) || values.push(value).is_ok()
+        })
+    }
+
+    /// Replaces the candidates of an argument; returns `false` if not all of them fit.
+    pub fn set(&self, command: &'static str, arg_index: u8, values: &[&str]) -> bool {
+        self.clear(command, arg_index);
+        let mut fits = true;
+        for value in values {
+            fits &= self.add(command, arg_index, value);
+        }
+        fits
+    }
+
+    /// Removes the candidates of an argument.
+    pub fn clear(&self, command: &str, arg_index: u8) {
+        critical_section::with(|cs| {
+            self.entries
+                .borrow_ref_mut(cs)
+                .retain(|e| !(e.command == command && e.arg_index == arg_index));
+        });
+    }
+}
+
+impl<const N: usize, const V: usize, const L: usize> Default for CompletionRegistry<N, V, L> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize, const V: usize, const L: usize> CompletionSource
+    for CompletionRegistry<N, V, L>
+{
+    fn offer_values(&self, command: &str, arg_index: usize, offer: &mut dyn FnMut(&str)) {
+        // Copied out so `offer` runs outside the critical section
+        let values = critical_section::with(|cs| {
+            self.entries
+                .borrow_ref(cs)
+                .iter()
+                .find(|e| e.command == command && e.arg_index as usize == arg_index)
+                .map(|e| e.values.clone())
+        });
+        for value in values.iter().flatten() {
+            offer(value);
+        }
+    }
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use std::string::String as StdString;
+    use std::vec::Vec as StdVec;
+
+    fn offered(registry: &dyn CompletionSource, command: &str, arg: usize) -> StdVec<StdString> {
+        let mut out = StdVec::new();
+        registry.offer_values(command, arg, &mut |v| out.push(StdString::from(v)));
+        out
+    }
+
+    #[test]
+    fn test_registry() {
+        let registry = CompletionRegistry::<2, 3, 4>::new();
+        assert!(registry.add("i2c read", 0, "0x50"));
+        assert!(registry.add("i2c read", 0, "0x68"));
+        assert!(registry.add("i2c read", 0, "0x50"));
+        assert!(!registry.add("i2c read", 0, "0x100"));
+        assert_eq!(offered(&registry, "i2c read", 0), ["0x50", "0x68"]);
+        assert!(offered(&registry, "i2c read", 1).is_empty());
+
+        assert!(registry.set("spi cs", 0, &["0", "1"]));
+        assert!(!registry.add("gpio read", 0, "1"));
+        assert!(!registry.set("i2c read", 0, &["a", "b", "c", "d"]));
+        assert_eq!(offered(&registry, "i2c read", 0), ["a", "b", "c"]);
+
+        registry.clear("spi cs", 0);
+        assert!(offered(&registry, "spi cs", 0).is_empty());
+        assert!(registry.add("gpio read", 0, "1"));
+    }
+}
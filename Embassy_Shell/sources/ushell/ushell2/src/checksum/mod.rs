@@ -0,0 +1,269 @@
+//! Checksums for verifying payloads: CRC-16 (XMODEM), CRC-32 and an 8-bit sum.
+//!
+//! The CRC tables are built at compile time (`CRC16_TABLE`, `CRC32_TABLE`), so they sit in
+//! flash and cost no startup time. Handlers checking a received payload call the
+//! functions directly, or go through `Checksum` when the algorithm is chosen at runtime:
+//!
+//! ```ignore
+//! use ushell2::checksum::{self, Checksum};
+//!
+//! if checksum::crc32(&frame[..len]) != expected {
+//!     log_error!("frame | bad CRC");
+//! }
+//! let ok = Checksum::Crc16.verify(payload, u32::from(crc));
+//! ```
+//!
+//! Data arriving in pieces is checked with `crc16_update` and `crc32_update`, starting
+//! from 0 and passing the previous result on:
+//!
+//! ```ignore
+//! let mut crc = 0;
+//! for block in blocks {
+//!     crc = checksum::crc32_update(crc, block);
+//! }
+//! ```
+//!
+//! The `crc16`, `crc32` and `sum8 <hex>` built-ins show the checksum of bytes given in
+//! hex (spaces between the bytes are allowed):
+//!
+//! ```text
+//! >> crc32 313233343536373839
+//! crc32: 0xCBF43926 (9 bytes)
+//! >> sum8 01 02 FF
+//! sum8: 0x02 (3 bytes)
+//! ```
+
+/// CRC-16/XMODEM (CCITT polynomial 0x1021, initial value 0, not reflected).
+const CRC16_POLY: u16 = 0x1021;
+
+/// CRC-32 of zlib and Ethernet (polynomial 0x04C11DB7, reflected).
+const CRC32_POLY: u32 = 0xEDB8_8320;
+
+/// CRC-16 of each byte value, most significant bit first.
+pub const CRC16_TABLE: [u16; 256] = crc16_table();
+
+/// CRC-32 of each byte value, least significant bit first.
+pub const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc16_table() -> [u16; 256] {
+    let mut table = [0u16; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut crc = (index as u16) << 8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ CRC16_POLY
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+    table
+}
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut crc = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ CRC32_POLY
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+    table
+}
+
+/// CRC-16/XMODEM of `data`; `crc16(b"123456789")` is 0x31C3.
+pub fn crc16(data: &[u8]) -> u16 {
+    crc16_update(0, data)
+}
+
+/// Continues the CRC-16 `crc` of the bytes before `data`.
+pub fn crc16_update(crc: u16, data: &[u8]) -> u16 {
+    data.iter().fold(crc, |crc, &byte| {
+        (crc << 8) ^ CRC16_TABLE[usize::from((crc >> 8) as u8 ^ byte)]
+    })
+}
+
+/// CRC-32 of `data`; `crc32(b"123456789")` is 0xCBF43926.
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32_update(0, data)
+}
+
+/// Continues the CRC-32 `crc` of the bytes before `data` (0 for none).
+pub fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    !data.iter().fold(!crc, |crc, &byte| {
+        (crc >> 8) ^ CRC32_TABLE[usize::from(crc as u8 ^ byte)]
+    })
+}
+
+/// The bytes of `data` added up, modulo 256.
+pub fn sum8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+}
+
+/// A checksum chosen at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checksum {
+    Crc16,
+    Crc32,
+    Sum8,
+}
+
+impl Checksum {
+    /// The checksum called `name` (`crc16`, `crc32`, `sum8`).
+    pub fn find(name: &str) -> Option<Self> {
+        match name {
+            "crc16" => Some(Checksum::Crc16),
+            "crc32" => Some(Checksum::Crc32),
+            "sum8" => Some(Checksum::Sum8),
+            _ => None,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Checksum::Crc16 => "crc16",
+            Checksum::Crc32 => "crc32",
+            Checksum::Sum8 => "sum8",
+        }
+    }
+
+    /// Hex digits of a formatted value.
+    pub const fn digits(self) -> usize {
+        match self {
+            Checksum::Crc16 => 4,
+            Checksum::Crc32 => 8,
+            Checksum::Sum8 => 2,
+        }
+    }
+
+    /// The checksum of `data`, widened to `u32`.
+    pub fn compute(self, data: &[u8]) -> u32 {
+        self.update(0, data)
+    }
+
+    /// Continues the checksum `value` of the bytes before `data` (0 for none).
+    pub fn update(self, value: u32, data: &[u8]) -> u32 {
+        match self {
+            Checksum::Crc16 => u32::from(crc16_update(value as u16, data)),
+            Checksum::Crc32 => crc32_update(value, data),
+            Checksum::Sum8 => u32::from((value as u8).wrapping_add(sum8(data))),
+        }
+    }
+
+    /// Whether `expected` is the checksum of `data`.
+    pub fn verify(self, data: &[u8], expected: u32) -> bool {
+        self.compute(data) == expected
+    }
+
+    /// The checksum of the bytes written in hex in `text`, and their count. Whitespace
+    /// between the bytes is skipped; an optional `0x` prefix is allowed.
+    pub fn of_hex(self, text: &str) -> Result<(u32, usize), ChecksumError> {
+        let mut value = 0;
+        let mut count = 0;
+        for word in text.split_whitespace() {
+            let digits = word.strip_prefix("0x").unwrap_or(word).as_bytes();
+            if digits.is_empty() {
+                return Err(ChecksumError::BadHex);
+            }
+            if !digits.len().is_multiple_of(2) {
+                return Err(ChecksumError::OddDigits);
+            }
+            for pair in digits.chunks_exact(2) {
+                let byte = (hex_digit(pair[0])? << 4) | hex_digit(pair[1])?;
+                value = self.update(value, &[byte]);
+                count += 1;
+            }
+        }
+        if count == 0 {
+            return Err(ChecksumError::Empty);
+        }
+        Ok((value, count))
+    }
+}
+
+fn hex_digit(c: u8) -> Result<u8, ChecksumError> {
+    (c as char)
+        .to_digit(16)
+        .map(|digit| digit as u8)
+        .ok_or(ChecksumError::BadHex)
+}
+
+/// Why a hex string has no checksum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumError {
+    /// No bytes given.
+    Empty,
+    /// A character is not a hex digit.
+    BadHex,
+    /// A group of digits does not make whole bytes.
+    OddDigits,
+}
+
+impl ChecksumError {
+    pub const fn message(&self) -> &'static str {
+        match self {
+            ChecksumError::Empty => "no bytes given",
+            ChecksumError::BadHex => "not a hex digit",
+            ChecksumError::OddDigits => "odd number of hex digits",
+        }
+    }
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_values() {
+        assert_eq!(crc16(b"123456789"), 0x31C3);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(sum8(b"123456789"), 0xDD);
+        assert_eq!(crc16(b""), 0);
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(CRC32_TABLE[1], 0x7707_3096);
+        assert_eq!(CRC16_TABLE[1], 0x1021);
+    }
+
+    #[test]
+    fn test_incremental() {
+        let (head, tail) = b"123456789".split_at(4);
+        assert_eq!(crc16_update(crc16(head), tail), 0x31C3);
+        assert_eq!(crc32_update(crc32(head), tail), 0xCBF4_3926);
+        for checksum in [Checksum::Crc16, Checksum::Crc32, Checksum::Sum8] {
+            let split = checksum.update(checksum.compute(head), tail);
+            assert_eq!(split, checksum.compute(b"123456789"));
+        }
+        assert!(Checksum::Crc32.verify(b"123456789", 0xCBF4_3926));
+        assert!(!Checksum::Crc16.verify(b"123456780", 0x31C3));
+    }
+
+    #[test]
+    fn test_of_hex() {
+        let crc32 = Checksum::find("crc32").unwrap();
+        assert_eq!(crc32.of_hex("313233343536373839"), Ok((0xCBF4_3926, 9)));
+        assert_eq!(crc32.of_hex("0x3132 33 343536373839"), Ok((0xCBF4_3926, 9)));
+        assert_eq!(Checksum::Sum8.of_hex("01 02 ff"), Ok((0x02, 3)));
+        assert_eq!(crc32.of_hex("31 2"), Err(ChecksumError::OddDigits));
+        assert_eq!(crc32.of_hex("3G"), Err(ChecksumError::BadHex));
+        assert_eq!(crc32.of_hex("  "), Err(ChecksumError::Empty));
+        assert!(Checksum::find("md5").is_none());
+    }
+}
@@ -0,0 +1,89 @@
+//! Shell time base.
+//!
+//! Time-based features (`watch`, streams, command time budgets) read a millisecond
+//! clock. Instead of wiring a hardware timer to each of them, the application advances
+//! this one clock and hands `now_ms` to the shell:
+//!
+//! ```ignore
+//! // SysTick (or any periodic timer) at 1 kHz
+//! #[exception]
+//! fn SysTick() {
+//!     ushell2::clock::tick(1);
+//! }
+//!
+//! let config = ShellConfig {
+//!     now_ms: Some(ushell2::clock::now_ms),
+//!     // ...
+//! };
+//! ```
+//!
+//! With the `async` feature, `AsyncReader::with_clock` keeps it in step with the
+//! executor's time driver (e.g. `|| Instant::now().as_millis()`) instead.
+
+use core::cell::Cell;
+use critical_section::Mutex;
+
+#[cfg(test)]
+extern crate std;
+
+/// Milliseconds counted by `tick`, and the last time seen by `sync`.
+static CLOCK: Mutex<Cell<(u64, Option<u64>)>> = Mutex::new(Cell::new((0, None)));
+
+/// Advances the clock by `elapsed_ms`.
+pub fn tick(elapsed_ms: u32) {
+    critical_section::with(|cs| {
+        let clock = CLOCK.borrow(cs);
+        let (now, last) = clock.get();
+        clock.set((now.saturating_add(elapsed_ms as u64), last));
+    });
+}
+
+/// Advances the clock by the time elapsed on `source` (a free-running millisecond
+/// counter) since the previous call; the first call only records the start.
+pub fn sync(source_ms: u64) {
+    critical_section::with(|cs| {
+        let clock = CLOCK.borrow(cs);
+        let (now, last) = clock.get();
+        let elapsed = last.map_or(0, |last| source_ms.saturating_sub(last));
+        clock.set((now.saturating_add(elapsed), Some(source_ms)));
+    });
+}
+
+/// Milliseconds since start-up, as counted by `tick` and `sync`.
+pub fn now_ms() -> u64 {
+    critical_section::with(|cs| CLOCK.borrow(cs).get().0)
+}
+
+/// Serializes tests that use the global clock.
+#[cfg(test)]
+pub(crate) static TEST_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tick_and_sync() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        critical_section::with(|cs| CLOCK.borrow(cs).set((0, None)));
+
+        tick(5);
+        tick(10);
+        assert_eq!(now_ms(), 15);
+
+        sync(1_000);
+        assert_eq!(now_ms(), 15);
+        sync(1_020);
+        tick(1);
+        assert_eq!(now_ms(), 36);
+
+        // A source going backwards does not move the clock back
+        sync(900);
+        sync(905);
+        assert_eq!(now_ms(), 41);
+    }
+}
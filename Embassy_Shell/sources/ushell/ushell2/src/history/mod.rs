@@ -8,21 +8,158 @@ const HISTORY_FILENAME: &str = ".hist";
 use std::fmt::Write;
 
 use crate::heapless::String;
+#[cfg(not(feature = "no-history"))]
+use crate::storage::HistoryOp;
+use crate::storage::{HistoryStore, StorageError};
 use core::default::Default;
 
 const METADATA_SIZE: usize = 4; // 2 bytes leading + 2 bytes trailing length
 
+/// Flag of a pinned entry in its leading length.
+const PINNED: u16 = 0x8000;
+/// Flag of an entry with a timestamp, in both lengths; entries are shorter than 16 KiB.
+const STAMPED: u16 = 0x4000;
+const FLAGS: u16 = PINNED | STAMPED;
+/// Size of the timestamp following the leading length of a stamped entry.
+const STAMP_SIZE: usize = 4;
+
+/// What `History::push` does with a line already in the history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicatePolicy {
+    /// The line is not added again; the earlier entry keeps its place.
+    #[default]
+    RejectDuplicates,
+    /// The earlier entry is moved to the newest place (keeping its pin), so the order
+    /// is the order the lines were last run in.
+    MoveToFront,
+    /// The line is added again.
+    AllowAll,
+}
+
+/// How the timestamps of entries are shown (see `History::show_with_times`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeFormat {
+    /// The age of the entry: `42s ago`, `5m07s ago`, `3h12m ago`, `2d05h ago`.
+    Relative,
+    /// The time itself: a Unix time (2001 on) as a UTC date, `2026-10-15 12:03:04`,
+    /// smaller values as an uptime, `02:03:04` or `1d 02:03:04`.
+    Absolute,
+}
+
+/// Displays the timestamp of an entry in a `TimeFormat`; `now` is the clock's time,
+/// which relative times are counted back from.
+#[derive(Debug, Clone, Copy)]
+pub struct EntryTime {
+    pub timestamp: u32,
+    pub now: u32,
+    pub format: TimeFormat,
+}
+
+/// Unix times from here on are shown as dates (2001-09-09).
+const UNIX_TIME_MIN: u32 = 1_000_000_000;
+
+impl core::fmt::Display for EntryTime {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let seconds = match self.format {
+            TimeFormat::Relative => self.now.saturating_sub(self.timestamp),
+            TimeFormat::Absolute => self.timestamp,
+        };
+        let (days, rest) = (seconds / 86400, seconds % 86400);
+        let (hours, minutes, secs) = (rest / 3600, rest / 60 % 60, rest % 60);
+        match self.format {
+            TimeFormat::Relative if days > 0 => write!(f, "{}d{:02}h ago", days, hours),
+            TimeFormat::Relative if hours > 0 => write!(f, "{}h{:02}m ago", hours, minutes),
+            TimeFormat::Relative if minutes > 0 => write!(f, "{}m{:02}s ago", minutes, secs),
+            TimeFormat::Relative => write!(f, "{}s ago", secs),
+            TimeFormat::Absolute if seconds >= UNIX_TIME_MIN => {
+                let (year, month, day) = civil_from_days(days);
+                write!(
+                    f,
+                    "{}-{:02}-{:02} {:02}:{:02}:{:02}",
+                    year, month, day, hours, minutes, secs
+                )
+            }
+            TimeFormat::Absolute if days > 0 => {
+                write!(f, "{}d {:02}:{:02}:{:02}", days, hours, minutes, secs)
+            }
+            TimeFormat::Absolute => write!(f, "{:02}:{:02}:{:02}", hours, minutes, secs),
+        }
+    }
+}
+
+/// The date of a day counted from 1970-01-01 (proleptic Gregorian calendar).
+fn civil_from_days(days: u32) -> (u32, u32, u32) {
+    // Days from 0000-03-01, in 400-year eras of 146097 days
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u32::from(month <= 2);
+    (year, month, day)
+}
+
+/// An entry of the history, borrowed from its buffer without copying: in two parts
+/// when it wraps around the end of the buffer.
+#[derive(Debug, Clone, Copy)]
+pub struct HistoryEntry<'h> {
+    head: &'h [u8],
+    tail: &'h [u8],
+    timestamp: Option<u32>,
+}
+
+impl<'h> HistoryEntry<'h> {
+    /// Length in bytes.
+    pub fn len(&self) -> usize {
+        self.head.len() + self.tail.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// When the entry was pushed, if the history has a clock (see `History::set_clock`).
+    pub fn timestamp(&self) -> Option<u32> {
+        self.timestamp
+    }
+
+    /// The bytes of the entry.
+    pub fn bytes(&self) -> impl Iterator<Item = u8> + 'h {
+        self.head.iter().chain(self.tail).copied()
+    }
+
+    /// Whether the entry starts with `prefix`.
+    pub fn starts_with(&self, prefix: &str) -> bool {
+        prefix.len() <= self.len() && self.bytes().zip(prefix.bytes()).all(|(a, b)| a == b)
+    }
+
+    /// Copies the entry; `None` if it is longer than `N` bytes.
+    pub fn to_line<const N: usize>(&self) -> Option<String<N>> {
+        let mut bytes: heapless::Vec<u8, N> = heapless::Vec::new();
+        bytes.extend_from_slice(self.head).ok()?;
+        bytes.extend_from_slice(self.tail).ok()?;
+        String::from_utf8(bytes).ok()
+    }
+}
+
 /// A fixed-size, circular history buffer for storing strings.
 ///
 /// Uses embedded metadata design
 /// - Each entry: [len_hi][len_lo][data...][len_hi][len_lo]
 /// - METADATA_SIZE = 4 bytes (2 for leading length, 2 for trailing length)
+/// - The top bit of the leading length marks a pinned entry
+/// - The next bit, in both lengths, marks an entry stamped by the clock (see
+///   `set_clock`), whose 4-byte big-endian timestamp follows the leading length
 /// - Enables bidirectional traversal
 /// - Single circular buffer, no separate metadata array
 ///
 /// Generic parameters:
 /// - `HTC`: History Total Capacity (bytes in buffer)
 ///
+#[cfg(not(feature = "no-history"))]
 pub struct History<const HTC: usize> {
     /// Circular buffer containing all history entries with embedded metadata
     data: [u8; HTC],
@@ -34,10 +171,17 @@ pub struct History<const HTC: usize> {
     entry_size: usize,
     /// Current navigation index (for up/down arrow keys)
     current_index: usize,
+    /// Storage the entries are kept in across resets, see `set_store`
+    store: Option<HistoryStore>,
+    /// What pushing a line already stored does
+    duplicates: DuplicatePolicy,
+    /// Source of the timestamps of new entries, see `set_clock`
+    clock: Option<fn() -> u32>,
 }
 
 /// Default
 ///
+#[cfg(not(feature = "no-history"))]
 impl<const HTC: usize> Default for History<HTC> {
     /// Returns a new, empty history buffer.
     fn default() -> Self {
@@ -47,6 +191,7 @@ impl<const HTC: usize> Default for History<HTC> {
 
 /// Implement History
 ///
+#[cfg(not(feature = "no-history"))]
 impl<const HTC: usize> History<HTC> {
     /// Creates a new, empty history buffer.
     pub fn new() -> Self {
@@ -56,6 +201,9 @@ impl<const HTC: usize> History<HTC> {
             entry_oldest: 0,
             entry_size: 0,
             current_index: 0,
+            store: None,
+            duplicates: DuplicatePolicy::RejectDuplicates,
+            clock: None,
         };
         #[cfg(feature = "history-persistence")]
         let instance = {
@@ -66,23 +214,66 @@ impl<const HTC: usize> History<HTC> {
         instance
     }
 
+    /// Keeps the history in `store` (e.g. a `FlashHistory`, see `crate::storage`): loads
+    /// the lines stored there, then appends every added entry and clears it with the
+    /// history. Returns the number of lines loaded.
+    ///
+    /// A failed append leaves the entry in RAM only; the storage is not retried.
+    pub fn set_store(&mut self, store: HistoryStore) -> Result<usize, StorageError> {
+        self.store = None;
+        // The stored lines have no time
+        let loaded = store(HistoryOp::Load(&mut |line| {
+            self.push_stamped(line, None);
+        }));
+        self.store = Some(store);
+        loaded
+    }
+
+    /// Stamps the entries pushed from now on with `now`, a 32-bit time in seconds:
+    /// the uptime, or the Unix time of an RTC (see `write_time`). A stamp takes 4 bytes
+    /// of the buffer.
+    pub fn set_clock(&mut self, now: fn() -> u32) {
+        self.clock = Some(now);
+    }
+
+    /// Sets what pushing a line already in the history does (by default it is rejected).
+    pub fn set_duplicate_policy(&mut self, policy: DuplicatePolicy) {
+        self.duplicates = policy;
+    }
+
+    pub fn duplicate_policy(&self) -> DuplicatePolicy {
+        self.duplicates
+    }
+
     /// Pushes a new string into the history.
     /// - Trims whitespace.
-    /// - Rejects if entry is too large or a duplicate of any existing entry.
-    /// - Removes oldest entries if needed to make space.
-    /// - Returns `true` if the entry was added, `false` otherwise.
+    /// - Rejects if entry is too large, or handles a duplicate of an existing entry as
+    ///   the `DuplicatePolicy` says.
+    /// - Removes the oldest entries that are not pinned if needed to make space.
+    /// - Appends it to the store, if one was set.
+    /// - Returns `true` if the entry was added (or moved), `false` otherwise.
+    ///
+    /// The entry is stamped with the time of the clock, if one was set.
     ///
     pub fn push(&mut self, s: &str) -> bool {
+        let timestamp = self.clock.map(|now| now());
+        self.push_stamped(s, timestamp)
+    }
+
+    /// Pushes a new string into the history with the given timestamp (see `push`).
+    ///
+    pub fn push_stamped(&mut self, s: &str, timestamp: Option<u32>) -> bool {
         let trimmed = s.trim();
         let bytes = trimmed.as_bytes();
         let len = bytes.len();
 
-        // Reject if empty or too large for u16 length field
-        if len == 0 || len > 65535 {
+        // Reject if empty or too large for the length field (its top bits are flags)
+        if len == 0 || len > !FLAGS as usize {
             return false;
         }
 
-        let needed = Self::entry_total_size(len as u16);
+        let header = len as u16 | if timestamp.is_some() { STAMPED } else { 0 };
+        let needed = Self::entry_total_size(header);
 
         // Check if entry can possibly fit in buffer
         if needed > HTC {
@@ -90,19 +281,25 @@ impl<const HTC: usize> History<HTC> {
         }
 
         // Check for duplicates in ENTIRE history
-        // If found anywhere, reject the new entry
-        if self.entry_size > 0 && self.is_duplicate(bytes, len) {
-            return false;
+        let mut pinned = false;
+        if self.duplicates != DuplicatePolicy::AllowAll {
+            if let Some(index) = self.find_duplicate(bytes) {
+                if self.duplicates == DuplicatePolicy::RejectDuplicates {
+                    return false;
+                }
+                pinned = self.is_pinned(index);
+                self.remove(index);
+            }
         }
 
-        // Remove oldest entries until we have enough space
+        // Remove the oldest unpinned entries until we have enough space
         let mut used = self.calculate_used_space();
-        while self.entry_size > 0 && (HTC - used) < needed {
-            let oldest_len = self.read_length_at(self.entry_oldest);
-            let oldest_size = Self::entry_total_size(oldest_len);
-
-            self.remove_oldest_entry();
-            used -= oldest_size;
+        while (HTC - used) < needed {
+            let Some(index) = (0..self.entry_size).find(|&index| !self.is_pinned(index)) else {
+                break;
+            };
+            used -= Self::entry_total_size(self.read_header_at(self.position_of(index)));
+            self.remove(index);
         }
 
         // Double-check we have space
@@ -113,10 +310,18 @@ impl<const HTC: usize> History<HTC> {
         // Write entry with embedded metadata: [len_hi][len_lo][data...][len_hi][len_lo]
         let mut write_pos = self.data_head;
 
-        // Write leading length (2 bytes, big-endian)
-        self.write_length_at(write_pos, len as u16);
+        // Write leading length (2 bytes, big-endian), flagged if pinned
+        self.write_length_at(write_pos, header | if pinned { PINNED } else { 0 });
         write_pos = (write_pos + 2) % HTC;
 
+        // Write timestamp (4 bytes, big-endian)
+        if let Some(timestamp) = timestamp {
+            for byte in timestamp.to_be_bytes() {
+                self.data[write_pos] = byte;
+                write_pos = (write_pos + 1) % HTC;
+            }
+        }
+
         // Write data
         for &byte in bytes {
             self.data[write_pos] = byte;
@@ -124,7 +329,7 @@ impl<const HTC: usize> History<HTC> {
         }
 
         // Write trailing length (2 bytes) - enables backward traversal
-        self.write_length_at(write_pos, len as u16);
+        self.write_length_at(write_pos, header);
         write_pos = (write_pos + 2) % HTC;
 
         // Update head position and counts
@@ -135,20 +340,24 @@ impl<const HTC: usize> History<HTC> {
         #[cfg(feature = "history-persistence")]
         self.append_to_file(HISTORY_FILENAME, trimmed);
 
+        if let Some(store) = self.store {
+            let _ = store(HistoryOp::Append(trimmed));
+        }
+
         true
     }
 
-    /// Checks if the given bytes match any existing entry
+    /// Returns the index of the entry matching the given bytes, if any
     #[inline]
-    fn is_duplicate(&self, bytes: &[u8], len: usize) -> bool {
+    fn find_duplicate(&self, bytes: &[u8]) -> Option<usize> {
         let mut pos = self.entry_oldest;
 
-        for _ in 0..self.entry_size {
+        for index in 0..self.entry_size {
             let entry_len = self.read_length_at(pos);
 
-            if entry_len as usize == len {
+            if entry_len as usize == bytes.len() {
                 // Lengths match, compare data
-                let data_pos = (pos + 2) % HTC;
+                let data_pos = self.data_pos(pos);
 
                 let is_match = bytes
                     .iter()
@@ -156,14 +365,58 @@ impl<const HTC: usize> History<HTC> {
                     .all(|(j, &ch)| self.data[(data_pos + j) % HTC] == ch);
 
                 if is_match {
-                    return true; // Duplicate found
+                    return Some(index); // Duplicate found
                 }
             }
 
             pos = self.find_next_entry_pos(pos);
         }
 
-        false
+        None
+    }
+
+    /// Pins the entry at `index`: it is never evicted to make room for new entries
+    /// (`clear` still removes it). Pins are not kept in the store. Returns `false` if
+    /// the index is out of bounds.
+    pub fn pin(&mut self, index: usize) -> bool {
+        self.set_pinned(index, true)
+    }
+
+    /// Unpins the entry at `index`; returns `false` if the index is out of bounds.
+    pub fn unpin(&mut self, index: usize) -> bool {
+        self.set_pinned(index, false)
+    }
+
+    /// Returns `true` if the entry at `index` is pinned.
+    pub fn is_pinned(&self, index: usize) -> bool {
+        index < self.entry_size && self.data[self.position_of(index)] & 0x80 != 0
+    }
+
+    /// Removes the entry at `index` (0 = oldest); returns `false` if the index is out
+    /// of bounds. The store is not changed.
+    pub fn remove(&mut self, index: usize) -> bool {
+        if index >= self.entry_size {
+            return false;
+        }
+        if index == 0 {
+            self.remove_oldest_entry();
+            return true;
+        }
+
+        // Move the newer entries back over the removed one
+        let pos = self.position_of(index);
+        let size = Self::entry_total_size(self.read_header_at(pos));
+        let end = (pos + size) % HTC;
+        let count = (self.data_head + HTC - end) % HTC;
+        for i in 0..count {
+            self.data[(pos + i) % HTC] = self.data[(end + i) % HTC];
+        }
+        self.data_head = (self.data_head + HTC - size) % HTC;
+        self.entry_size -= 1;
+        if self.current_index >= self.entry_size {
+            self.current_index = self.entry_size - 1;
+        }
+        true
     }
 
     /// Moves to the previous entry position and calls the provided function with its data.
@@ -218,6 +471,17 @@ impl<const HTC: usize> History<HTC> {
         self.for_each_byte(self.current_index, f).is_some()
     }
 
+    /// Iterates over the entries without copying them, oldest first (`rev()` for newest
+    /// first); the index of an entry is its position, see `enumerate()`.
+    pub fn iter(&self) -> Iter<'_, HTC> {
+        Iter {
+            history: self,
+            front: self.entry_oldest,
+            back: self.data_head,
+            remaining: self.entry_size,
+        }
+    }
+
     /// Sets the current index to the given value, if valid.
     ///
     pub fn set_index(&mut self, index: usize) {
@@ -315,7 +579,7 @@ impl<const HTC: usize> History<HTC> {
         }
 
         let len = self.read_length_at(pos) as usize;
-        let data_pos = (pos + 2) % HTC;
+        let data_pos = self.data_pos(pos);
 
         for i in 0..len {
             let byte = self.data[(data_pos + i) % HTC];
@@ -327,6 +591,19 @@ impl<const HTC: usize> History<HTC> {
         Some(len)
     }
 
+    /// Returns the timestamp of the entry at `index`, if it was stamped (see `set_clock`).
+    pub fn timestamp(&self, index: usize) -> Option<u32> {
+        if index >= self.entry_size {
+            return None;
+        }
+        self.timestamp_at(self.position_of(index))
+    }
+
+    /// Returns the time of the clock, if one was set.
+    pub fn now(&self) -> Option<u32> {
+        self.clock.map(|now| now())
+    }
+
     /// Prints all entries and free space info using the provided writer.
     /// This is a zero-allocation method that writes directly to the output.
     ///
@@ -342,7 +619,16 @@ impl<const HTC: usize> History<HTC> {
     ///     writer.write_fmt(args);
     /// });
     /// ```
-    pub fn show<F>(&self, mut write_fn: F)
+    pub fn show<F>(&self, write_fn: F)
+    where
+        F: FnMut(core::fmt::Arguments),
+    {
+        self.show_with_times(None, write_fn);
+    }
+
+    /// Like `show`, with the time of each stamped entry before it in `times` (relative
+    /// times are counted from the clock's time).
+    pub fn show_with_times<F>(&self, times: Option<TimeFormat>, mut write_fn: F)
     where
         F: FnMut(core::fmt::Arguments),
     {
@@ -351,10 +637,20 @@ impl<const HTC: usize> History<HTC> {
             return;
         }
 
-        // Iterate through entries manually without allocation
+        // Iterate through entries manually without allocation, pinned ones starred
         let mut pos = self.entry_oldest;
         for idx in 0..self.entry_size {
-            write_fn(format_args!("[{}] ", idx));
+            let pin = if self.is_pinned(idx) { "*" } else { "" };
+            write_fn(format_args!("[{}]{} ", idx, pin));
+            if let (Some(format), Some(timestamp)) = (times, self.timestamp_at(pos)) {
+                let now = self.now().unwrap_or(timestamp);
+                let time = EntryTime {
+                    timestamp,
+                    now,
+                    format,
+                };
+                write_fn(format_args!("{}  ", time));
+            }
 
             // Stream the entry byte-by-byte
             self.for_each_byte_at_pos(pos, |byte| {
@@ -370,13 +666,17 @@ impl<const HTC: usize> History<HTC> {
         write_fn(format_args!("Free: {} bytes\n", free_bytes));
     }
 
-    /// Clears all entries from history.
+    /// Clears all entries from history, and from the store if one was set.
     ///
     pub fn clear(&mut self) {
         self.data_head = 0;
         self.entry_oldest = 0;
         self.entry_size = 0;
         self.current_index = 0;
+
+        if let Some(store) = self.store {
+            let _ = store(HistoryOp::Clear);
+        }
     }
 
     // ==================== PRIVATE HELPERS ====================
@@ -399,7 +699,7 @@ impl<const HTC: usize> History<HTC> {
         buffer_len: usize,
     ) -> usize {
         let len = self.read_length_at(pos) as usize;
-        let data_pos = (pos + 2) % HTC;
+        let data_pos = self.data_pos(pos);
 
         let bytes_to_copy = len.min(buffer_len);
         for (i, byte) in buffer.iter_mut().enumerate().take(bytes_to_copy) {
@@ -418,7 +718,7 @@ impl<const HTC: usize> History<HTC> {
         F: FnMut(u8) -> bool,
     {
         let len = self.read_length_at(pos) as usize;
-        let data_pos = (pos + 2) % HTC;
+        let data_pos = self.data_pos(pos);
 
         for i in 0..len {
             let byte = self.data[(data_pos + i) % HTC];
@@ -430,15 +730,90 @@ impl<const HTC: usize> History<HTC> {
         len
     }
 
-    /// Reads a u16 length value (big-endian) at the given position.
+    /// The entry starting at `pos`.
+    fn entry_at(&self, pos: usize) -> HistoryEntry<'_> {
+        let len = self.read_length_at(pos) as usize;
+        let start = self.data_pos(pos);
+        if start + len <= HTC {
+            HistoryEntry {
+                head: &self.data[start..start + len],
+                tail: &[],
+                timestamp: self.timestamp_at(pos),
+            }
+        } else {
+            HistoryEntry {
+                head: &self.data[start..],
+                tail: &self.data[..start + len - HTC],
+                timestamp: self.timestamp_at(pos),
+            }
+        }
+    }
+
+    /// Reads a u16 length value (big-endian) at the given position, with its flags.
     ///    
     #[inline]
-    fn read_length_at(&self, pos: usize) -> u16 {
+    fn read_header_at(&self, pos: usize) -> u16 {
         let hi = self.data[pos] as u16;
         let lo = self.data[(pos + 1) % HTC] as u16;
         (hi << 8) | lo
     }
 
+    /// Reads a u16 length value (big-endian) at the given position, without the flags.
+    ///    
+    #[inline]
+    fn read_length_at(&self, pos: usize) -> u16 {
+        self.read_header_at(pos) & !FLAGS
+    }
+
+    /// Returns the position of the data of the entry starting at `pos`.
+    ///
+    #[inline]
+    fn data_pos(&self, pos: usize) -> usize {
+        if self.read_header_at(pos) & STAMPED != 0 {
+            (pos + 2 + STAMP_SIZE) % HTC
+        } else {
+            (pos + 2) % HTC
+        }
+    }
+
+    /// Reads the timestamp of the entry starting at `pos`, if stamped.
+    ///
+    fn timestamp_at(&self, pos: usize) -> Option<u32> {
+        if self.read_header_at(pos) & STAMPED == 0 {
+            return None;
+        }
+        let mut bytes = [0; STAMP_SIZE];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = self.data[(pos + 2 + i) % HTC];
+        }
+        Some(u32::from_be_bytes(bytes))
+    }
+
+    /// Returns the position of the entry at `index`, which must be in bounds.
+    ///
+    fn position_of(&self, index: usize) -> usize {
+        let mut pos = self.entry_oldest;
+        for _ in 0..index {
+            pos = self.find_next_entry_pos(pos);
+        }
+        pos
+    }
+
+    /// Sets or clears the pin flag of the entry at `index`.
+    ///
+    fn set_pinned(&mut self, index: usize, pinned: bool) -> bool {
+        if index >= self.entry_size {
+            return false;
+        }
+        let pos = self.position_of(index);
+        if pinned {
+            self.data[pos] |= 0x80;
+        } else {
+            self.data[pos] &= 0x7F;
+        }
+        true
+    }
+
     /// Writes a u16 length value (big-endian) at the given position.
     ///    
     #[inline]
@@ -447,11 +822,12 @@ impl<const HTC: usize> History<HTC> {
         self.data[(pos + 1) % HTC] = (len & 0xFF) as u8;
     }
 
-    /// Returns the total size of an entry (data + metadata).
+    /// Returns the total size of an entry (data + metadata) from its length and flags.
     ///    
     #[inline]
-    const fn entry_total_size(data_len: u16) -> usize {
-        data_len as usize + METADATA_SIZE
+    const fn entry_total_size(header: u16) -> usize {
+        let stamp = if header & STAMPED != 0 { STAMP_SIZE } else { 0 };
+        (header & !FLAGS) as usize + METADATA_SIZE + stamp
     }
 
     /// Removes the oldest entry from the buffer.
@@ -461,8 +837,8 @@ impl<const HTC: usize> History<HTC> {
             return;
         }
 
-        let len = self.read_length_at(self.entry_oldest);
-        let size = Self::entry_total_size(len);
+        let header = self.read_header_at(self.entry_oldest);
+        let size = Self::entry_total_size(header);
 
         // Move oldest pointer forward
         self.entry_oldest = (self.entry_oldest + size) % HTC;
@@ -489,8 +865,8 @@ impl<const HTC: usize> History<HTC> {
         let mut pos = self.entry_oldest;
 
         for _ in 0..self.entry_size {
-            let len = self.read_length_at(pos);
-            total += Self::entry_total_size(len);
+            let header = self.read_header_at(pos);
+            total += Self::entry_total_size(header);
             pos = self.find_next_entry_pos(pos);
         }
 
@@ -501,8 +877,8 @@ impl<const HTC: usize> History<HTC> {
     ///    
     #[inline]
     fn find_next_entry_pos(&self, pos: usize) -> usize {
-        let len = self.read_length_at(pos);
-        let size = Self::entry_total_size(len);
+        let header = self.read_header_at(pos);
+        let size = Self::entry_total_size(header);
         (pos + size) % HTC
     }
 
@@ -532,3 +908,316 @@ impl<const HTC: usize> History<HTC> {
         }
     }
 }
+
+/// Iterator over the entries of a `History`, see `History::iter`.
+#[cfg(not(feature = "no-history"))]
+pub struct Iter<'h, const HTC: usize> {
+    history: &'h History<HTC>,
+    /// Start of the next entry from the front
+    front: usize,
+    /// End of the next entry from the back
+    back: usize,
+    remaining: usize,
+}
+
+#[cfg(not(feature = "no-history"))]
+impl<'h, const HTC: usize> Iterator for Iter<'h, HTC> {
+    type Item = HistoryEntry<'h>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let entry = self.history.entry_at(self.front);
+        self.front = self.history.find_next_entry_pos(self.front);
+        Some(entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+#[cfg(not(feature = "no-history"))]
+impl<const HTC: usize> DoubleEndedIterator for Iter<'_, HTC> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        // The trailing length ends the entry
+        let header = self.history.read_header_at((self.back + HTC - 2) % HTC);
+        self.back = (self.back + HTC - History::<HTC>::entry_total_size(header)) % HTC;
+        Some(self.history.entry_at(self.back))
+    }
+}
+
+#[cfg(not(feature = "no-history"))]
+impl<const HTC: usize> ExactSizeIterator for Iter<'_, HTC> {}
+
+// ==================== DISABLED HISTORY =======================
+
+/// Zero-sized stand-in for `History` used when the `no-history` feature is enabled.
+///
+/// Keeps the same API (and the `HTC` parameter) so `InputParser` and `ShellConfig`
+/// are unchanged, but stores nothing: every push is rejected and navigation finds nothing.
+///
+#[cfg(feature = "no-history")]
+pub struct History<const HTC: usize>;
+
+#[cfg(feature = "no-history")]
+impl<const HTC: usize> Default for History<HTC> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "no-history")]
+impl<const HTC: usize> History<HTC> {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn set_store(&mut self, _store: HistoryStore) -> Result<usize, StorageError> {
+        Ok(0)
+    }
+
+    pub fn set_duplicate_policy(&mut self, _policy: DuplicatePolicy) {}
+
+    pub fn duplicate_policy(&self) -> DuplicatePolicy {
+        DuplicatePolicy::RejectDuplicates
+    }
+
+    pub fn set_clock(&mut self, _now: fn() -> u32) {}
+
+    pub fn push(&mut self, _s: &str) -> bool {
+        false
+    }
+
+    pub fn push_stamped(&mut self, _s: &str, _timestamp: Option<u32>) -> bool {
+        false
+    }
+
+    pub fn pin(&mut self, _index: usize) -> bool {
+        false
+    }
+
+    pub fn unpin(&mut self, _index: usize) -> bool {
+        false
+    }
+
+    pub fn is_pinned(&self, _index: usize) -> bool {
+        false
+    }
+
+    pub fn remove(&mut self, _index: usize) -> bool {
+        false
+    }
+
+    pub fn get_prev_entry<F>(&mut self, _f: F) -> bool
+    where
+        F: FnMut(u8) -> bool,
+    {
+        false
+    }
+
+    pub fn get_next_entry<F>(&mut self, _f: F) -> bool
+    where
+        F: FnMut(u8) -> bool,
+    {
+        false
+    }
+
+    pub fn set_index(&mut self, _index: usize) {}
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        0
+    }
+
+    pub fn get_free_space(&self) -> usize {
+        0
+    }
+
+    pub fn get_into_buffer(&self, _index: usize, _buffer: &mut [u8]) -> Option<usize> {
+        None
+    }
+
+    pub fn for_each_byte<F>(&self, _index: usize, _f: F) -> Option<usize>
+    where
+        F: FnMut(u8) -> bool,
+    {
+        None
+    }
+
+    pub fn iter(&self) -> core::iter::Empty<HistoryEntry<'_>> {
+        core::iter::empty()
+    }
+
+    pub fn timestamp(&self, _index: usize) -> Option<u32> {
+        None
+    }
+
+    pub fn now(&self) -> Option<u32> {
+        None
+    }
+
+    pub fn show<F>(&self, write_fn: F)
+    where
+        F: FnMut(core::fmt::Arguments),
+    {
+        self.show_with_times(None, write_fn);
+    }
+
+    pub fn show_with_times<F>(&self, _times: Option<TimeFormat>, mut write_fn: F)
+    where
+        F: FnMut(core::fmt::Arguments),
+    {
+        write_fn(format_args!("History is disabled.\n"));
+    }
+
+    pub fn clear(&mut self) {}
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(all(test, not(feature = "no-history")))]
+mod tests {
+    extern crate std;
+    use super::*;
+    use std::vec::Vec as StdVec;
+
+    fn lines<'h>(entries: impl Iterator<Item = HistoryEntry<'h>>) -> StdVec<String<16>> {
+        entries.map(|entry| entry.to_line().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_iter_wrapping_entries() {
+        // 24 bytes: the third entry evicts the first and wraps around the end
+        let mut history: History<24> = History::new();
+        history.push("led 1");
+        history.push("led 0");
+        history.push("reset x");
+        assert_eq!(lines(history.iter()), ["led 0", "reset x"]);
+        assert_eq!(lines(history.iter().rev()), ["reset x", "led 0"]);
+
+        let newest = history.iter().next_back().unwrap();
+        assert!(newest.starts_with("reset"));
+        assert!(!newest.starts_with("reset xy"));
+        assert_eq!(newest.len(), 7);
+        assert!(newest.to_line::<4>().is_none());
+
+        let mut iter = history.iter();
+        assert_eq!(iter.len(), 2);
+        assert!(iter.next_back().is_some() && iter.next().is_some());
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_duplicate_policies() {
+        let mut history: History<64> = History::new();
+        history.push("led 1");
+        history.push("reset");
+        assert!(!history.push("led 1"));
+        assert_eq!(lines(history.iter()), ["led 1", "reset"]);
+
+        history.set_duplicate_policy(DuplicatePolicy::MoveToFront);
+        assert!(history.push("led 1"));
+        assert_eq!(lines(history.iter()), ["reset", "led 1"]);
+
+        history.set_duplicate_policy(DuplicatePolicy::AllowAll);
+        assert!(history.push("reset"));
+        assert_eq!(lines(history.iter()), ["reset", "led 1", "reset"]);
+    }
+
+    #[test]
+    fn test_pinned_entries_survive_eviction() {
+        // Room for three 5-byte entries
+        let mut history: History<27> = History::new();
+        history.push("led 1");
+        history.push("led 2");
+        history.push("led 3");
+        assert!(history.pin(0));
+        assert!(!history.pin(3));
+
+        history.push("led 4");
+        history.push("led 5");
+        assert_eq!(lines(history.iter()), ["led 1", "led 4", "led 5"]);
+        assert!(history.is_pinned(0) && !history.is_pinned(1));
+        assert_eq!(lines(history.iter().rev()), ["led 5", "led 4", "led 1"]);
+
+        // A moved entry keeps its pin
+        history.set_duplicate_policy(DuplicatePolicy::MoveToFront);
+        history.push("led 1");
+        assert_eq!(lines(history.iter()), ["led 4", "led 5", "led 1"]);
+        assert!(history.is_pinned(2));
+
+        // Nothing to evict
+        history.pin(0);
+        history.pin(1);
+        assert!(!history.push("led 6"));
+        assert!(history.unpin(0));
+        assert!(history.push("led 6"));
+        assert_eq!(lines(history.iter()), ["led 5", "led 1", "led 6"]);
+    }
+
+    #[test]
+    fn test_stamped_entries() {
+        let mut history: History<64> = History::new();
+        history.push("led 1");
+        history.set_clock(|| 3700);
+        history.push("led 2");
+        history.push_stamped("led 3", Some(3600 - 42));
+        assert_eq!(history.timestamp(0), None);
+        assert_eq!(history.timestamp(1), Some(3700));
+        assert_eq!(lines(history.iter().rev()), ["led 3", "led 2", "led 1"]);
+        assert_eq!(history.iter().nth(2).unwrap().timestamp(), Some(3558));
+
+        let mut out = std::string::String::new();
+        history.show_with_times(Some(TimeFormat::Relative), |args| {
+            out.push_str(&std::format!("{}", args))
+        });
+        assert!(out.starts_with("[0] led 1\n[1] 0s ago  led 2\n[2] 2m22s ago  led 3\n"));
+
+        // The stamp is 4 more bytes: 64 - (5 + 4) - 2 * (5 + 8)
+        assert_eq!(history.get_free_space(), 29);
+        history.remove(1);
+        assert_eq!(lines(history.iter()), ["led 1", "led 3"]);
+        assert_eq!(history.timestamp(1), Some(3558));
+    }
+
+    #[test]
+    fn test_entry_time_formats() {
+        let time = |timestamp, now, format| {
+            std::format!(
+                "{}",
+                EntryTime {
+                    timestamp,
+                    now,
+                    format
+                }
+            )
+        };
+        assert_eq!(time(100, 190_000, TimeFormat::Relative), "2d04h ago");
+        assert_eq!(time(100, 4000, TimeFormat::Relative), "1h05m ago");
+        assert_eq!(time(4000, 100, TimeFormat::Relative), "0s ago");
+        assert_eq!(time(93_784, 0, TimeFormat::Absolute), "1d 02:03:04");
+        assert_eq!(time(7384, 0, TimeFormat::Absolute), "02:03:04");
+        assert_eq!(
+            time(1_792_065_784, 0, TimeFormat::Absolute),
+            "2026-10-15 12:03:04"
+        );
+        assert_eq!(
+            time(1_709_210_096, 0, TimeFormat::Absolute),
+            "2024-02-29 12:34:56"
+        );
+    }
+}
@@ -0,0 +1,199 @@
+//! Key bindings of the edit line.
+//!
+//! `KeyBindings` maps the keys decoded by the key reader (see `keymap` for the byte
+//! sequences of each terminal) to editor actions: moving the cursor, walking the
+//! history, killing text, completing. The default is `EMACS`, readline's bindings;
+//! `VI` adds a normal mode entered with Esc. A `ShellConfig` selects the bindings with
+//! `key_bindings`, or the `InputParser` with `set_key_bindings`.
+//!
+//! Custom bindings list only what they change and fall back to a `base`:
+//!
+//! ```ignore
+//! // Tab is reserved by the tooling: complete with Ctrl+N only
+//! static MY_BINDINGS: KeyBindings = KeyBindings {
+//!     name: "no-tab",
+//!     insert: &[(Key::Tab, Action::Ignore), (Key::F(1), Action::Complete)],
+//!     normal: &[],
+//!     base: Some(&EMACS),
+//! };
+//! ```
+//!
+//! A printable character bound to nothing is inserted (in vi's normal mode it rings the
+//! bell); any other unbound key is ignored. Snippets, the pager and the history search
+//! read their keys before the bindings.
+
+use crate::input::key_reader::Key;
+
+/// What a key does on the edit line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Runs the line.
+    Submit,
+    /// Completes the command or argument, or cycles to the next completion.
+    Complete,
+    /// Cycles to the previous completion.
+    CompleteBack,
+    HistoryPrev,
+    HistoryNext,
+    CursorLeft,
+    CursorRight,
+    LineStart,
+    LineEnd,
+    /// Deletes the character before the cursor.
+    DeleteBack,
+    /// Deletes the character under the cursor.
+    DeleteChar,
+    /// Deletes the word before the cursor.
+    DeleteWordBack,
+    /// Deletes from the start of the line to the cursor.
+    KillToStart,
+    /// Deletes from the cursor to the end of the line.
+    KillToEnd,
+    /// Swaps the characters around the cursor.
+    Transpose,
+    /// Clears the whole line.
+    ClearLine,
+    /// Starts the reverse history search.
+    Search,
+    /// Does nothing: unbinds a key of the `base` bindings.
+    Ignore,
+    /// Leaves insert mode for the normal mode (vi).
+    NormalMode,
+    /// Back to insert mode at the cursor (vi `i`).
+    Insert,
+    /// Back to insert mode after the cursor (vi `a`).
+    InsertAfter,
+    /// Back to insert mode at the start of the line (vi `I`).
+    InsertAtStart,
+    /// Back to insert mode at the end of the line (vi `A`).
+    InsertAtEnd,
+}
+
+/// Keys and the actions they run.
+#[derive(Debug)]
+pub struct KeyBindings {
+    pub name: &'static str,
+    /// `(key, action)` pairs of the edit line (of insert mode for modal bindings).
+    pub insert: &'static [(Key, Action)],
+    /// `(key, action)` pairs of the normal mode, entered with `Action::NormalMode`.
+    pub normal: &'static [(Key, Action)],
+    /// Bindings looked up for the keys not bound here.
+    pub base: Option<&'static KeyBindings>,
+}
+
+impl KeyBindings {
+    /// The action bound to `key`, in normal mode if `normal`.
+    pub fn action(&self, key: Key, normal: bool) -> Option<Action> {
+        let table = if normal { self.normal } else { self.insert };
+        table
+            .iter()
+            .find(|&&(bound, _)| bound == key)
+            .map(|&(_, action)| action)
+            .or_else(|| self.base?.action(key, normal))
+    }
+}
+
+/// readline's bindings, and the keys of the edit line before bindings were configurable.
+pub static EMACS: KeyBindings = KeyBindings {
+    name: "emacs",
+    insert: &[
+        (Key::Enter, Action::Submit),
+        (Key::Tab, Action::Complete),
+        (Key::ShiftTab, Action::CompleteBack),
+        (Key::CtrlN, Action::Complete),
+        (Key::CtrlP, Action::CompleteBack),
+        (Key::ArrowUp, Action::HistoryPrev),
+        (Key::ArrowDown, Action::HistoryNext),
+        (Key::ArrowLeft, Action::CursorLeft),
+        (Key::CtrlB, Action::CursorLeft),
+        (Key::ArrowRight, Action::CursorRight),
+        (Key::CtrlF, Action::CursorRight),
+        (Key::Home, Action::LineStart),
+        (Key::CtrlA, Action::LineStart),
+        (Key::End, Action::LineEnd),
+        (Key::CtrlE, Action::LineEnd),
+        (Key::Backspace, Action::DeleteBack),
+        (Key::Delete, Action::DeleteChar),
+        (Key::AltBackspace, Action::DeleteWordBack),
+        (Key::CtrlW, Action::DeleteWordBack),
+        (Key::CtrlU, Action::KillToStart),
+        (Key::CtrlK, Action::KillToEnd),
+        (Key::CtrlT, Action::Transpose),
+        (Key::CtrlD, Action::ClearLine),
+        (Key::CtrlR, Action::Search),
+    ],
+    normal: &[],
+    base: None,
+};
+
+/// vi bindings: the edit line starts in insert mode (the `EMACS` keys), Esc switches to
+/// normal mode.
+pub static VI: KeyBindings = KeyBindings {
+    name: "vi",
+    insert: &[(Key::Escape, Action::NormalMode)],
+    normal: &[
+        (Key::Enter, Action::Submit),
+        (Key::Char('h'), Action::CursorLeft),
+        (Key::Char('l'), Action::CursorRight),
+        (Key::Char(' '), Action::CursorRight),
+        (Key::Char('0'), Action::LineStart),
+        (Key::Char('^'), Action::LineStart),
+        (Key::Char('$'), Action::LineEnd),
+        (Key::Char('k'), Action::HistoryPrev),
+        (Key::Char('j'), Action::HistoryNext),
+        (Key::Char('x'), Action::DeleteChar),
+        (Key::Char('X'), Action::DeleteBack),
+        (Key::Char('D'), Action::KillToEnd),
+        (Key::Char('S'), Action::ClearLine),
+        (Key::Char('/'), Action::Search),
+        (Key::Char('i'), Action::Insert),
+        (Key::Char('a'), Action::InsertAfter),
+        (Key::Char('I'), Action::InsertAtStart),
+        (Key::Char('A'), Action::InsertAtEnd),
+        (Key::Escape, Action::Ignore),
+        (Key::ArrowUp, Action::HistoryPrev),
+        (Key::ArrowDown, Action::HistoryNext),
+        (Key::ArrowLeft, Action::CursorLeft),
+        (Key::ArrowRight, Action::CursorRight),
+        (Key::Home, Action::LineStart),
+        (Key::End, Action::LineEnd),
+        (Key::Backspace, Action::CursorLeft),
+        (Key::Delete, Action::DeleteChar),
+        (Key::Tab, Action::Complete),
+    ],
+    base: Some(&EMACS),
+};
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static NO_TAB: KeyBindings = KeyBindings {
+        name: "no-tab",
+        insert: &[(Key::Tab, Action::Ignore), (Key::F(1), Action::Complete)],
+        normal: &[],
+        base: Some(&EMACS),
+    };
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(EMACS.action(Key::CtrlA, false), Some(Action::LineStart));
+        assert_eq!(EMACS.action(Key::Char('a'), false), None);
+        assert_eq!(EMACS.action(Key::CtrlA, true), None);
+
+        // Insert mode of vi falls back to emacs
+        assert_eq!(VI.action(Key::CtrlK, false), Some(Action::KillToEnd));
+        assert_eq!(VI.action(Key::Escape, false), Some(Action::NormalMode));
+        assert_eq!(VI.action(Key::Char('x'), false), None);
+        assert_eq!(VI.action(Key::Char('x'), true), Some(Action::DeleteChar));
+        assert_eq!(VI.action(Key::Char('q'), true), None);
+
+        assert_eq!(NO_TAB.action(Key::Tab, false), Some(Action::Ignore));
+        assert_eq!(NO_TAB.action(Key::F(1), false), Some(Action::Complete));
+        assert_eq!(NO_TAB.action(Key::Enter, false), Some(Action::Submit));
+    }
+}
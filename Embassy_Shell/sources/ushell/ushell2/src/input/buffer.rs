@@ -336,6 +336,95 @@ impl<const IML: usize> InputBuffer<IML> {
         self.length = self.cursor_pos;
     }
 
+    /// Swaps the character before the cursor with the one under it and moves the cursor
+    /// right (Ctrl-T). At the end of the line the last two characters are swapped.
+    ///
+    /// Returns `false` if there is nothing to swap (cursor at start or fewer than 2 chars).
+    ///
+    /// # Example
+    /// ```
+    /// let mut buf: InputBuffer<8> = InputBuffer::new();
+    /// buf.overwrite("ab");
+    /// assert!(buf.transpose());
+    /// assert_eq!(buf.to_string().as_str(), "ba");
+    /// ```
+    pub fn transpose(&mut self) -> bool {
+        if self.cursor_pos == 0 || self.length < 2 {
+            return false;
+        }
+        let right = self.cursor_pos.min(self.length - 1);
+        self.buffer.swap(right - 1, right);
+        self.cursor_pos = right + 1;
+        true
+    }
+
+    /// Deletes the word before the cursor, including the spaces between it and the
+    /// cursor (Alt-Backspace).
+    ///
+    /// Returns `false` if the cursor is at the start of the buffer.
+    ///
+    /// # Example
+    /// ```
+    /// let mut buf: InputBuffer<16> = InputBuffer::new();
+    /// buf.overwrite("led on ");
+    /// assert!(buf.delete_word_before());
+    /// assert_eq!(buf.to_string().as_str(), "led ");
+    /// ```
+    pub fn delete_word_before(&mut self) -> bool {
+        if self.cursor_pos == 0 {
+            return false;
+        }
+        let mut start = self.cursor_pos;
+        while start > 0 && self.buffer[start - 1] == ' ' {
+            start -= 1;
+        }
+        while start > 0 && self.buffer[start - 1] != ' ' {
+            start -= 1;
+        }
+
+        let removed = self.cursor_pos - start;
+        for i in self.cursor_pos..self.length {
+            self.buffer[i - removed] = self.buffer[i];
+        }
+        for i in self.length - removed..self.length {
+            self.buffer[i] = '\0';
+        }
+        self.length -= removed;
+        self.cursor_pos = start;
+        true
+    }
+
+    /// Inserts `template` at the cursor, leaving the cursor where the first occurrence of
+    /// `marker` was (the marker itself is not inserted). Without a marker the cursor ends
+    /// up after the inserted text.
+    ///
+    /// Returns `false` and leaves the buffer unchanged if the text does not fit.
+    ///
+    /// # Example
+    /// ```
+    /// let mut buf: InputBuffer<32> = InputBuffer::new();
+    /// assert!(buf.insert_template("i2c write 0x50 <cursor>", "<cursor>"));
+    /// assert_eq!(buf.cursor(), 15);
+    /// ```
+    pub fn insert_template(&mut self, template: &str, marker: &str) -> bool {
+        let (before, after) = match template.find(marker).filter(|_| !marker.is_empty()) {
+            Some(pos) => (&template[..pos], &template[pos + marker.len()..]),
+            None => (template, ""),
+        };
+        if self.length + before.chars().count() + after.chars().count() > IML {
+            return false;
+        }
+        before.chars().for_each(|ch| {
+            self.insert(ch);
+        });
+        let cursor = self.cursor_pos;
+        after.chars().for_each(|ch| {
+            self.insert(ch);
+        });
+        self.cursor_pos = cursor;
+        true
+    }
+
     /// Returns the current length of the buffer.
     ///
     /// # Example
@@ -877,4 +966,86 @@ mod tests {
         assert_eq!(buf.cursor(), 0);
         assert_eq!(buf.to_string().as_str(), "");
     }
+
+    // ============================================================================
+    // Template Insertion
+    // ============================================================================
+
+    #[test]
+    fn test_insert_template_places_cursor_at_marker() {
+        let mut buf: InputBuffer<32> = InputBuffer::new();
+        buf.overwrite("x  y");
+        buf.move_home();
+        buf.move_right();
+        buf.move_right();
+        assert!(buf.insert_template("read <cursor> 16", "<cursor>"));
+        assert_eq!(buf.to_string().as_str(), "x read  16 y");
+        assert_eq!(buf.cursor(), 7);
+    }
+
+    #[test]
+    fn test_insert_template_without_marker() {
+        let mut buf: InputBuffer<16> = InputBuffer::new();
+        assert!(buf.insert_template("reset", "<cursor>"));
+        assert_eq!(buf.to_string().as_str(), "reset");
+        assert_eq!(buf.cursor(), 5);
+    }
+
+    #[test]
+    fn test_insert_template_too_long() {
+        let mut buf: InputBuffer<8> = InputBuffer::new();
+        buf.overwrite("ab");
+        assert!(!buf.insert_template("0123456<cursor>", "<cursor>"));
+        assert_eq!(buf.to_string().as_str(), "ab");
+        assert!(buf.insert_template("012<cursor>345", "<cursor>"));
+        assert_eq!(buf.len(), 8);
+    }
+
+    // ============================================================================
+    // Transpose / Delete Word
+    // ============================================================================
+
+    #[test]
+    fn test_transpose() {
+        let mut buf: InputBuffer<8> = InputBuffer::new();
+        buf.overwrite("abc");
+        buf.move_home();
+        assert!(!buf.transpose());
+        buf.move_right();
+        assert!(buf.transpose());
+        assert_eq!(buf.to_string().as_str(), "bac");
+        assert_eq!(buf.cursor(), 2);
+
+        // At the end the last two characters are swapped, repeatedly
+        buf.move_end();
+        assert!(buf.transpose());
+        assert_eq!(buf.to_string().as_str(), "bca");
+        assert!(buf.transpose());
+        assert_eq!(buf.to_string().as_str(), "bac");
+        assert_eq!(buf.cursor(), 3);
+    }
+
+    #[test]
+    fn test_transpose_single_char() {
+        let mut buf: InputBuffer<8> = InputBuffer::new();
+        buf.overwrite("a");
+        assert!(!buf.transpose());
+        assert_eq!(buf.to_string().as_str(), "a");
+    }
+
+    #[test]
+    fn test_delete_word_before() {
+        let mut buf: InputBuffer<32> = InputBuffer::new();
+        buf.overwrite("set net.ssid  lab");
+        for _ in 0..3 {
+            buf.move_left();
+        }
+        assert!(buf.delete_word_before());
+        assert_eq!(buf.to_string().as_str(), "set lab");
+        assert_eq!(buf.cursor(), 4);
+        assert!(buf.delete_word_before());
+        assert_eq!(buf.to_string().as_str(), "lab");
+        assert!(!buf.delete_word_before());
+        assert_eq!(buf.len(), 3);
+    }
 }
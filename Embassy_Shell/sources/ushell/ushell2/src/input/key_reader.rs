@@ -24,16 +24,62 @@ pub enum Key {
     ShiftTab,
 
     // Control sequences
+    CtrlA,
+    CtrlB,
+    CtrlE,
+    CtrlF,
+    CtrlW,
     CtrlU,
     CtrlK,
     CtrlD,
     CtrlN,
     CtrlP,
+    CtrlT,
+    CtrlR,
+    CtrlG,
+    AltBackspace,
+
+    /// Esc. Byte streams (serial, Unix terminals) report it for Esc pressed twice: a
+    /// single ESC starts an escape sequence and cannot be told from one without a timeout.
+    Escape,
+
+    /// Start and end of pasted text (`ESC [ 200 ~` / `ESC [ 201 ~`), sent by terminals
+    /// once the application enabled bracketed paste (`ESC [ ? 2004 h`).
+    PasteStart,
+    PasteEnd,
+
+    // Function keys F1..F12
+    F(u8),
 
     // Printable character
     Char(char),
+
+    /// SGR mouse report (`ESC [ < button ; x ; y M|m`), sent by terminals once the
+    /// application enabled mouse reporting (`ESC [ ? 1000 ; 1006 h`). `button` is the raw
+    /// SGR code (0..=2 buttons, 64/65 wheel, +32 motion, +4/8/16 modifiers), `x` and `y`
+    /// are 1-based, `press` is `false` for a release.
+    Mouse {
+        button: u8,
+        x: u16,
+        y: u16,
+        press: bool,
+    },
+
+    /// Cursor position report (`ESC [ row ; col R`), the terminal's reply to a Device
+    /// Status Report request (`ESC [ 6 n`); 1-based. Sent after moving the cursor to the
+    /// far corner (see `TERMINAL_SIZE_PROBE`) it gives the size of the terminal.
+    CursorReport {
+        row: u16,
+        col: u16,
+    },
 }
 
+/// Asks the terminal for its size: saves the cursor, moves it as far right and down as
+/// the terminal allows, requests its position (`ESC [ 6 n`) and restores it. The
+/// terminal replies with a `Key::CursorReport` of its rows and columns; terminals
+/// without DSR support ignore it.
+pub const TERMINAL_SIZE_PROBE: &str = "\x1B7\x1B[999;999H\x1B[6n\x1B8";
+
 /// ============= TRAIT-BASED INTERFACE FOR EMBEDDED =============
 ///
 pub trait KeyReader {
@@ -64,6 +110,8 @@ pub mod platform {
     const LEFT_CTRL_PRESSED: u32 = 0x0008;
     const RIGHT_CTRL_PRESSED: u32 = 0x0004;
     const SHIFT_PRESSED: u32 = 0x0010;
+    const LEFT_ALT_PRESSED: u32 = 0x0002;
+    const RIGHT_ALT_PRESSED: u32 = 0x0001;
 
     pub fn read_key() -> io::Result<Key> {
         unsafe {
@@ -92,14 +140,28 @@ pub mod platform {
                         & (LEFT_CTRL_PRESSED | RIGHT_CTRL_PRESSED))
                         != 0;
                     let shift = (key_event.dwControlKeyState & SHIFT_PRESSED) != 0;
+                    let alt =
+                        (key_event.dwControlKeyState & (LEFT_ALT_PRESSED | RIGHT_ALT_PRESSED)) != 0;
+
+                    if alt && vkey == 0x08 {
+                        return Ok(Key::AltBackspace);
+                    }
 
                     if ctrl {
                         match vkey {
+                            0x41 => return Ok(Key::CtrlA),
+                            0x42 => return Ok(Key::CtrlB),
+                            0x45 => return Ok(Key::CtrlE),
+                            0x46 => return Ok(Key::CtrlF),
+                            0x57 => return Ok(Key::CtrlW),
                             0x55 => return Ok(Key::CtrlU),
                             0x4B => return Ok(Key::CtrlK),
                             0x44 => return Ok(Key::CtrlD),
                             0x4E => return Ok(Key::CtrlN),
                             0x50 => return Ok(Key::CtrlP),
+                            0x54 => return Ok(Key::CtrlT),
+                            0x52 => return Ok(Key::CtrlR),
+                            0x47 => return Ok(Key::CtrlG),
                             _ => {}
                         }
                     }
@@ -117,6 +179,7 @@ pub mod platform {
                         0x08 => return Ok(Key::Backspace),
                         0x09 => return Ok(if shift { Key::ShiftTab } else { Key::Tab }),
                         0x0D => return Ok(Key::Enter),
+                        0x1B => return Ok(Key::Escape),
                         _ => {}
                     }
 
@@ -147,6 +210,12 @@ pub mod platform {
                 b'\x1B' => {
                     // Handle escape sequences
                     if let Some(Ok(b2)) = bytes.next() {
+                        if b2 == b'\x7F' || b2 == b'\x08' {
+                            return Ok(Key::AltBackspace);
+                        }
+                        if b2 == b'\x1B' {
+                            return Ok(Key::Escape);
+                        }
                         if b2 == b'[' {
                             if let Some(Ok(b3)) = bytes.next() {
                                 return Ok(match b3 {
@@ -157,11 +226,25 @@ pub mod platform {
                                     b'H' => Key::Home,
                                     b'F' => Key::End,
                                     b'Z' => Key::ShiftTab,
-                                    b'1' | b'2' | b'3' | b'4' | b'5' | b'6' => {
+                                    // Bracketed paste markers: ESC [ 2 0 0 ~ / ESC [ 2 0 1 ~
+                                    b'2' => match bytes.next() {
+                                        Some(Ok(b'0')) => match bytes.next() {
+                                            Some(Ok(b'0')) => {
+                                                let _ = bytes.next();
+                                                Key::PasteStart
+                                            }
+                                            Some(Ok(b'1')) => {
+                                                let _ = bytes.next();
+                                                Key::PasteEnd
+                                            }
+                                            _ => Key::F(9),
+                                        },
+                                        _ => Key::Insert,
+                                    },
+                                    b'1' | b'3' | b'4' | b'5' | b'6' => {
                                         let _ = bytes.next();
                                         match b3 {
                                             b'1' => Key::Home,
-                                            b'2' => Key::Insert,
                                             b'3' => Key::Delete,
                                             b'4' => Key::End,
                                             b'5' => Key::PageUp,
@@ -175,11 +258,19 @@ pub mod platform {
                         }
                     }
                 }
+                b'\x01' => return Ok(Key::CtrlA),
+                b'\x02' => return Ok(Key::CtrlB),
+                b'\x05' => return Ok(Key::CtrlE),
+                b'\x06' => return Ok(Key::CtrlF),
+                b'\x17' => return Ok(Key::CtrlW),
                 b'\x15' => return Ok(Key::CtrlU),
                 b'\x0B' => return Ok(Key::CtrlK),
                 b'\x04' => return Ok(Key::CtrlD),
                 b'\x0E' => return Ok(Key::CtrlN),
                 b'\x10' => return Ok(Key::CtrlP),
+                b'\x14' => return Ok(Key::CtrlT),
+                b'\x12' => return Ok(Key::CtrlR),
+                b'\x07' => return Ok(Key::CtrlG),
                 b'\r' | b'\n' => return Ok(Key::Enter),
                 b'\t' => return Ok(Key::Tab),
                 b'\x7F' | b'\x08' => return Ok(Key::Backspace),
@@ -196,12 +287,17 @@ pub mod platform {
 #[cfg(not(feature = "hosted"))]
 pub mod embedded {
     use super::Key;
+    use crate::input::keymap::{self, KeyMap, Translation};
     use heapless::Vec;
 
     /// Simple VT100/ANSI escape sequence parser for embedded
+    ///
+    /// Sequences of the keymap (see `crate::input::keymap`) are translated first.
     pub struct AnsiKeyParser {
-        escape_buffer: Vec<u8, 8>,
+        // Long enough for SGR mouse reports with 4-digit coordinates
+        escape_buffer: Vec<u8, 20>,
         in_escape: bool,
+        keymap: Option<&'static KeyMap>,
     }
 
     impl Default for AnsiKeyParser {
@@ -215,15 +311,38 @@ pub mod embedded {
             Self {
                 escape_buffer: Vec::new(),
                 in_escape: false,
+                keymap: None,
+            }
+        }
+
+        /// Parser always using `keymap` instead of the globally selected one.
+        pub const fn with_keymap(keymap: &'static KeyMap) -> Self {
+            Self {
+                escape_buffer: Vec::new(),
+                in_escape: false,
+                keymap: Some(keymap),
+            }
+        }
+
+        fn translate(&self, bytes: &[u8]) -> Translation {
+            match self.keymap {
+                Some(keymap) => keymap.translate(bytes),
+                None => keymap::keymap().translate(bytes),
             }
         }
 
         /// Parse a single byte and return a Key if complete
         #[inline]
         pub fn parse_byte(&mut self, byte: u8) -> Option<Key> {
+            if !self.in_escape && byte != 0x1B {
+                if let Translation::Key(key) = self.translate(&[byte]) {
+                    return Some(key);
+                }
+            }
+
             match byte {
-                // Escape sequence start
-                0x1B => {
+                // Escape sequence start; a second ESC right after the first is Esc
+                0x1B if !self.in_escape || self.escape_buffer.len() > 1 => {
                     self.in_escape = true;
                     self.escape_buffer.clear();
                     let _ = self.escape_buffer.push(byte);
@@ -237,11 +356,19 @@ pub mod embedded {
                 }
 
                 // Control characters
+                0x01 => Some(Key::CtrlA), // Ctrl+A
+                0x02 => Some(Key::CtrlB), // Ctrl+B
+                0x05 => Some(Key::CtrlE), // Ctrl+E
+                0x06 => Some(Key::CtrlF), // Ctrl+F
+                0x17 => Some(Key::CtrlW), // Ctrl+W
                 0x15 => Some(Key::CtrlU), // Ctrl+U
                 0x0B => Some(Key::CtrlK), // Ctrl+K
                 0x04 => Some(Key::CtrlD), // Ctrl+D
                 0x0E => Some(Key::CtrlN), // Ctrl+N
                 0x10 => Some(Key::CtrlP), // Ctrl+P
+                0x14 => Some(Key::CtrlT), // Ctrl+T
+                0x12 => Some(Key::CtrlR), // Ctrl+R
+                0x07 => Some(Key::CtrlG), // Ctrl+G
                 b'\r' | b'\n' => Some(Key::Enter),
                 b'\t' => Some(Key::Tab),
                 0x7F | 0x08 => Some(Key::Backspace),
@@ -257,50 +384,148 @@ pub mod embedded {
         fn try_complete_escape(&mut self) -> Option<Key> {
             let buf = &self.escape_buffer[..];
 
-            // Common VT100 sequences: ESC [ X
-            if buf.len() >= 3 && buf[0] == 0x1B && buf[1] == b'[' {
-                let result = match buf[2] {
-                    b'A' => Some(Key::ArrowUp),
-                    b'B' => Some(Key::ArrowDown),
-                    b'C' => Some(Key::ArrowRight),
-                    b'D' => Some(Key::ArrowLeft),
-                    b'H' => Some(Key::Home),
-                    b'F' => Some(Key::End),
-                    b'Z' => Some(Key::ShiftTab),
+            let result = match self.translate(buf) {
+                Translation::Key(key) => Some(key),
+                Translation::Partial if !self.escape_buffer.is_full() => return None,
+                _ => self.decode_escape()?,
+            };
+
+            // Complete sequence, known or not
+            self.in_escape = false;
+            self.escape_buffer.clear();
+            result
+        }
+
+        /// Default decoding of the escape buffer: `None` while incomplete, `Some(None)`
+        /// for complete sequences that are not keys.
+        #[inline]
+        fn decode_escape(&self) -> Option<Option<Key>> {
+            let buf = &self.escape_buffer[..];
+
+            let result = match buf {
+                // SGR mouse report: ESC [ < button ; x ; y M|m
+                [0x1B, b'[', b'<', rest @ ..] => match rest.last() {
+                    Some(b'M' | b'm') => decode_mouse(rest),
+                    Some(b'0'..=b'9' | b';') | None if !self.escape_buffer.is_full() => {
+                        return None; // Wait for more bytes
+                    }
+                    _ => None,
+                },
+
+                // Common VT100 sequences: ESC [ X
+                [0x1B, b'[', third, rest @ ..] => match (*third, rest) {
+                    (b'A', _) => Some(Key::ArrowUp),
+                    (b'B', _) => Some(Key::ArrowDown),
+                    (b'C', _) => Some(Key::ArrowRight),
+                    (b'D', _) => Some(Key::ArrowLeft),
+                    (b'H', _) => Some(Key::Home),
+                    (b'F', _) => Some(Key::End),
+                    (b'Z', _) => Some(Key::ShiftTab),
 
                     // Extended sequences: ESC [ N ~
-                    b'1' | b'2' | b'3' | b'4' | b'5' | b'6' => {
-                        if buf.len() >= 4 && buf[3] == b'~' {
-                            match buf[2] {
-                                b'1' => Some(Key::Home),
-                                b'2' => Some(Key::Insert),
-                                b'3' => Some(Key::Delete),
-                                b'4' => Some(Key::End),
-                                b'5' => Some(Key::PageUp),
-                                b'6' => Some(Key::PageDown),
-                                _ => None,
-                            }
-                        } else {
-                            None // Wait for more bytes
-                        }
+                    (b'1'..=b'6', [b'~']) => match third {
+                        b'1' => Some(Key::Home),
+                        b'2' => Some(Key::Insert),
+                        b'3' => Some(Key::Delete),
+                        b'4' => Some(Key::End),
+                        b'5' => Some(Key::PageUp),
+                        b'6' => Some(Key::PageDown),
+                        _ => None,
+                    },
+
+                    // Bracketed paste markers: ESC [ 2 0 0 ~ / ESC [ 2 0 1 ~
+                    (b'2', [b'0', b'0', b'~']) => Some(Key::PasteStart),
+                    (b'2', [b'0', b'1', b'~']) => Some(Key::PasteEnd),
+
+                    // Function keys: ESC [ N N ~
+                    (b'1' | b'2', [second, b'~']) => match (third - b'0') * 10 + (second - b'0') {
+                        code @ 11..=15 => Some(Key::F(code - 10)),
+                        code @ 17..=21 => Some(Key::F(code - 11)),
+                        code @ 23..=24 => Some(Key::F(code - 12)),
+                        _ => None,
+                    },
+
+                    // Cursor position report, the reply to `ESC [ 6 n`: ESC [ row ; col R
+                    (b'0'..=b'9', [.., b'R']) => decode_cursor_report(&buf[2..buf.len() - 1]),
+
+                    // Numeric parameters so far
+                    (b'0'..=b'9', _) if is_parameters(rest) && !self.escape_buffer.is_full() => {
+                        return None; // Wait for more bytes
                     }
-                    _ => Some(Key::Char(buf[2] as char)),
-                };
 
-                if result.is_some() {
-                    self.in_escape = false;
-                    self.escape_buffer.clear();
-                }
-                result
-            } else if buf.len() >= 4 {
+                    // Parameters cut by a printable byte (`ESC [ 1 ; a`): drop them, keep it
+                    (b'0'..=b'9', [.., b';', last]) if last.is_ascii_graphic() => {
+                        Some(Key::Char(*last as char))
+                    }
+
+                    // Unsupported ESC [ N ... sequence: drop it
+                    (b'0'..=b'9', _) => None,
+                    _ => Some(Key::Char(*third as char)),
+                },
+
+                // Alt+Backspace: ESC DEL (or ESC BS)
+                [0x1B, 0x7F | 0x08] => Some(Key::AltBackspace),
+
+                // Esc twice
+                [0x1B, 0x1B] => Some(Key::Escape),
+
+                // SS3 sequences: ESC O X (F1..F4, Home/End on some terminals)
+                [0x1B, b'O', third] => match third {
+                    b'P'..=b'S' => Some(Key::F(third - b'P' + 1)),
+                    b'H' => Some(Key::Home),
+                    b'F' => Some(Key::End),
+                    _ => None,
+                },
+
                 // Escape sequence too long, reset
-                self.in_escape = false;
-                self.escape_buffer.clear();
-                None
-            } else {
-                None // Wait for more bytes
+                _ if buf.len() >= 4 => None,
+                _ => return None, // Wait for more bytes
+            };
+            Some(result)
+        }
+    }
+
+    /// Decodes `button ; x ; y M|m`, the part of an SGR mouse report after `ESC [ <`.
+    fn decode_mouse(report: &[u8]) -> Option<Key> {
+        let (&last, fields) = report.split_last()?;
+        let [button, x, y] = parse_fields(fields)?;
+        Some(Key::Mouse {
+            button: button.min(u8::MAX as u16) as u8,
+            x,
+            y,
+            press: last == b'M',
+        })
+    }
+
+    /// Decodes `row ; col`, the parameters of a cursor position report.
+    fn decode_cursor_report(params: &[u8]) -> Option<Key> {
+        let [row, col] = parse_fields(params)?;
+        Some(Key::CursorReport { row, col })
+    }
+
+    /// Parses exactly `N` decimal fields separated by `;`.
+    fn parse_fields<const N: usize>(fields: &[u8]) -> Option<[u16; N]> {
+        let mut values = [0u16; N];
+        let mut count = 0;
+        for field in fields.split(|&b| b == b';') {
+            let value = values.get_mut(count)?;
+            if field.is_empty() {
+                return None;
+            }
+            for &digit in field {
+                if !digit.is_ascii_digit() {
+                    return None;
+                }
+                *value = value.checked_mul(10)?.checked_add((digit - b'0') as u16)?;
             }
+            count += 1;
         }
+        (count == N).then_some(values)
+    }
+
+    /// Whether `bytes` can start the parameters of a sequence (digits and `;`).
+    fn is_parameters(bytes: &[u8]) -> bool {
+        bytes.iter().all(|&b| b.is_ascii_digit() || b == b';')
     }
 }
 
@@ -310,8 +535,10 @@ pub mod embedded {
 
 #[cfg(test)]
 mod tests {
+    extern crate std;
     use super::*;
     use core::matches;
+    use std::format;
 
     #[test]
     fn test_key_enum_debug() {
@@ -344,7 +571,22 @@ mod tests {
             Key::CtrlD,
             Key::CtrlN,
             Key::CtrlP,
+            Key::CtrlT,
+            Key::CtrlR,
+            Key::CtrlG,
+            Key::AltBackspace,
+            Key::Escape,
+            Key::PasteStart,
+            Key::PasteEnd,
+            Key::F(1),
             Key::Char('x'),
+            Key::Mouse {
+                button: 0,
+                x: 1,
+                y: 1,
+                press: true,
+            },
+            Key::CursorReport { row: 24, col: 80 },
         ];
     }
 
@@ -363,11 +605,19 @@ mod tests {
     fn test_ansi_parser_control_keys() {
         let mut parser = embedded::AnsiKeyParser::new();
 
+        assert_eq!(parser.parse_byte(0x01), Some(Key::CtrlA));
+        assert_eq!(parser.parse_byte(0x02), Some(Key::CtrlB));
+        assert_eq!(parser.parse_byte(0x05), Some(Key::CtrlE));
+        assert_eq!(parser.parse_byte(0x06), Some(Key::CtrlF));
+        assert_eq!(parser.parse_byte(0x17), Some(Key::CtrlW));
         assert_eq!(parser.parse_byte(0x15), Some(Key::CtrlU));
         assert_eq!(parser.parse_byte(0x0B), Some(Key::CtrlK));
         assert_eq!(parser.parse_byte(0x04), Some(Key::CtrlD));
         assert_eq!(parser.parse_byte(0x0E), Some(Key::CtrlN));
         assert_eq!(parser.parse_byte(0x10), Some(Key::CtrlP));
+        assert_eq!(parser.parse_byte(0x14), Some(Key::CtrlT));
+        assert_eq!(parser.parse_byte(0x12), Some(Key::CtrlR));
+        assert_eq!(parser.parse_byte(0x07), Some(Key::CtrlG));
         assert_eq!(parser.parse_byte(b'\r'), Some(Key::Enter));
         assert_eq!(parser.parse_byte(b'\t'), Some(Key::Tab));
     }
@@ -388,6 +638,98 @@ mod tests {
         assert_eq!(parser.parse_byte(b'B'), Some(Key::ArrowDown));
     }
 
+    #[cfg(not(feature = "hosted"))]
+    #[test]
+    fn test_ansi_parser_alt_backspace() {
+        let mut parser = embedded::AnsiKeyParser::new();
+
+        for second in [0x7F, 0x08] {
+            assert_eq!(parser.parse_byte(0x1B), None);
+            assert_eq!(parser.parse_byte(second), Some(Key::AltBackspace));
+        }
+        assert_eq!(parser.parse_byte(0x7F), Some(Key::Backspace));
+
+        assert_eq!(parser.parse_byte(0x1B), None);
+        assert_eq!(parser.parse_byte(0x1B), Some(Key::Escape));
+    }
+
+    #[cfg(not(feature = "hosted"))]
+    #[test]
+    fn test_ansi_parser_keymap() {
+        use crate::input::keymap::{MINICOM, PUTTY};
+
+        let mut parser = embedded::AnsiKeyParser::with_keymap(&MINICOM);
+        assert_eq!(parser.parse_byte(0x7F), Some(Key::Delete));
+        assert_eq!(parser.parse_byte(0x08), Some(Key::Backspace));
+
+        // Keymap sequences and default ones side by side
+        let mut parser = embedded::AnsiKeyParser::with_keymap(&PUTTY);
+        let keys: std::vec::Vec<Key> = b"\x1b[[B\x1b[A\x1b[7~\x1b[4~"
+            .iter()
+            .filter_map(|&b| parser.parse_byte(b))
+            .collect();
+        assert_eq!(keys, [Key::F(2), Key::ArrowUp, Key::Home, Key::End]);
+    }
+
+    #[cfg(not(feature = "hosted"))]
+    #[test]
+    fn test_ansi_parser_sgr_mouse() {
+        let mut parser = embedded::AnsiKeyParser::new();
+        let keys: std::vec::Vec<Key> = b"\x1b[<0;12;5M\x1b[<64;1200;300m\x1b[<1;2Mx\x1b[<0;1;1;1Ma"
+            .iter()
+            .filter_map(|&b| parser.parse_byte(b))
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                Key::Mouse {
+                    button: 0,
+                    x: 12,
+                    y: 5,
+                    press: true
+                },
+                Key::Mouse {
+                    button: 64,
+                    x: 1200,
+                    y: 300,
+                    press: false
+                },
+                // Malformed reports are dropped
+                Key::Char('x'),
+                Key::Char('a'),
+            ]
+        );
+    }
+
+    #[cfg(not(feature = "hosted"))]
+    #[test]
+    fn test_ansi_parser_cursor_report() {
+        let mut parser = embedded::AnsiKeyParser::new();
+        let keys: std::vec::Vec<Key> = b"\x1b[24;80R\x1b[5;132R\x1b[999;1Rx\x1b[1;2;3R\x1b[7~a"
+            .iter()
+            .filter_map(|&b| parser.parse_byte(b))
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                Key::CursorReport { row: 24, col: 80 },
+                Key::CursorReport { row: 5, col: 132 },
+                Key::CursorReport { row: 999, col: 1 },
+                Key::Char('x'),
+                // Malformed reports and unknown sequences are dropped
+                Key::Char('a'),
+            ]
+        );
+        // Extended keys still decode
+        assert_eq!(
+            b"\x1b[3~\x1b[200~"
+                .iter()
+                .filter_map(|&b| parser.parse_byte(b))
+                .collect::<std::vec::Vec<Key>>(),
+            [Key::Delete, Key::PasteStart]
+        );
+    }
+
     #[cfg(not(feature = "hosted"))]
     #[test]
     fn test_ansi_parser_delete_key() {
@@ -431,4 +773,56 @@ mod tests {
         assert!(!is_arrow_key(&Key::Enter));
         assert!(!is_arrow_key(&Key::Char('a')));
     }
+
+    #[cfg(not(feature = "hosted"))]
+    #[test]
+    fn test_ansi_parser_function_keys() {
+        let mut parser = embedded::AnsiKeyParser::new();
+        let mut feed = |bytes: &[u8]| {
+            let mut key = None;
+            for &b in bytes {
+                key = parser.parse_byte(b);
+            }
+            key
+        };
+
+        // SS3 form: F1..F4
+        assert_eq!(feed(b"\x1bOP"), Some(Key::F(1)));
+        assert_eq!(feed(b"\x1bOQ"), Some(Key::F(2)));
+        assert_eq!(feed(b"\x1bOS"), Some(Key::F(4)));
+
+        // CSI form: ESC [ N N ~
+        assert_eq!(feed(b"\x1b[12~"), Some(Key::F(2)));
+        assert_eq!(feed(b"\x1b[15~"), Some(Key::F(5)));
+        assert_eq!(feed(b"\x1b[17~"), Some(Key::F(6)));
+        assert_eq!(feed(b"\x1b[21~"), Some(Key::F(10)));
+        assert_eq!(feed(b"\x1b[24~"), Some(Key::F(12)));
+
+        // Unsupported sequences are dropped instead of stalling the parser
+        assert_eq!(feed(b"\x1b[16~"), None);
+        assert_eq!(feed(b"a"), Some(Key::Char('a')));
+        assert_eq!(feed(b"\x1b[1;"), None);
+        assert_eq!(feed(b"a"), Some(Key::Char('a')));
+    }
+
+    #[cfg(not(feature = "hosted"))]
+    #[test]
+    fn test_ansi_parser_bracketed_paste() {
+        let mut parser = embedded::AnsiKeyParser::new();
+        let mut keys = std::vec::Vec::new();
+        for &b in b"\x1b[200~ab\r\x1b[201~\x1b[20~" {
+            keys.extend(parser.parse_byte(b));
+        }
+        assert_eq!(
+            keys,
+            [
+                Key::PasteStart,
+                Key::Char('a'),
+                Key::Char('b'),
+                Key::Enter,
+                Key::PasteEnd,
+                Key::F(9),
+            ]
+        );
+    }
 }
@@ -0,0 +1,165 @@
+//! Terminal keymap quirks.
+//!
+//! Terminal programs disagree on the bytes sent for some keys: minicom sends BS (0x08)
+//! for Backspace and DEL (0x7F) for Delete, rxvt-style terminals send `ESC [ 7 ~` for
+//! Home, screen in application cursor mode sends `ESC O A` for Up, ... A `KeyMap` lists
+//! byte sequences that `AnsiKeyParser` translates before its built-in decoding. What the
+//! keys then do on the edit line is set by `bindings`.
+//!
+//! The active keymap is global and selected at runtime with `set_keymap` or the
+//! `term keymap <name>` built-in:
+//!
+//! ```text
+//! >> term keymap minicom
+//! ```
+//!
+//! Custom keymaps are plain statics:
+//!
+//! ```ignore
+//! static MY_TERM: KeyMap = KeyMap {
+//!     name: "myterm",
+//!     entries: &[(b"\x1b[7~", Key::Home), (b"\x1b[8~", Key::End)],
+//! };
+//! keymap::set_keymap(&MY_TERM);
+//! ```
+
+use core::cell::Cell;
+use critical_section::Mutex;
+
+use crate::input::key_reader::Key;
+
+#[cfg(test)]
+extern crate std;
+
+/// Byte sequences translated to keys ahead of the default decoding.
+#[derive(Debug)]
+pub struct KeyMap {
+    pub name: &'static str,
+    /// `(bytes, key)` pairs; a sequence starts with ESC or is a single byte.
+    pub entries: &'static [(&'static [u8], Key)],
+}
+
+/// Result of looking up received bytes in a `KeyMap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Translation {
+    /// The bytes are a complete sequence of the keymap.
+    Key(Key),
+    /// The bytes start a sequence of the keymap; more are needed.
+    Partial,
+    /// Not in the keymap; use the default decoding.
+    None,
+}
+
+impl KeyMap {
+    pub fn translate(&self, bytes: &[u8]) -> Translation {
+        let mut partial = false;
+        for &(seq, key) in self.entries {
+            if seq == bytes {
+                return Translation::Key(key);
+            }
+            partial |= seq.len() > bytes.len() && seq.starts_with(bytes);
+        }
+        if partial {
+            Translation::Partial
+        } else {
+            Translation::None
+        }
+    }
+}
+
+/// xterm / VT220 behaviour, as decoded by `AnsiKeyParser` itself.
+pub static DEFAULT: KeyMap = KeyMap {
+    name: "default",
+    entries: &[],
+};
+
+/// PuTTY with the "Linux console" function keys or "rxvt" Home/End.
+pub static PUTTY: KeyMap = KeyMap {
+    name: "putty",
+    entries: &[
+        (b"\x1b[[A", Key::F(1)),
+        (b"\x1b[[B", Key::F(2)),
+        (b"\x1b[[C", Key::F(3)),
+        (b"\x1b[[D", Key::F(4)),
+        (b"\x1b[[E", Key::F(5)),
+        (b"\x1b[7~", Key::Home),
+        (b"\x1b[8~", Key::End),
+        (b"\x1bOw", Key::End),
+    ],
+};
+
+/// minicom: Backspace sends BS, Delete sends DEL.
+pub static MINICOM: KeyMap = KeyMap {
+    name: "minicom",
+    entries: &[(b"\x7f", Key::Delete)],
+};
+
+/// picocom passes the bytes of the hosting terminal; covers rxvt Home/End.
+pub static PICOCOM: KeyMap = KeyMap {
+    name: "picocom",
+    entries: &[(b"\x1b[7~", Key::Home), (b"\x1b[8~", Key::End)],
+};
+
+/// GNU screen (and tmux) in application cursor mode.
+pub static SCREEN: KeyMap = KeyMap {
+    name: "screen",
+    entries: &[
+        (b"\x1bOA", Key::ArrowUp),
+        (b"\x1bOB", Key::ArrowDown),
+        (b"\x1bOC", Key::ArrowRight),
+        (b"\x1bOD", Key::ArrowLeft),
+    ],
+};
+
+/// Keymaps selectable by name with `term keymap <name>`.
+pub static PRESETS: [&KeyMap; 5] = [&DEFAULT, &PUTTY, &MINICOM, &PICOCOM, &SCREEN];
+
+static ACTIVE: Mutex<Cell<&'static KeyMap>> = Mutex::new(Cell::new(&DEFAULT));
+
+/// Returns the preset called `name`.
+pub fn find(name: &str) -> Option<&'static KeyMap> {
+    PRESETS.iter().copied().find(|keymap| keymap.name == name)
+}
+
+/// Selects the keymap used by `AnsiKeyParser`.
+pub fn set_keymap(keymap: &'static KeyMap) {
+    critical_section::with(|cs| ACTIVE.borrow(cs).set(keymap));
+}
+
+/// Returns the active keymap.
+pub fn keymap() -> &'static KeyMap {
+    critical_section::with(|cs| ACTIVE.borrow(cs).get())
+}
+
+/// Serializes tests that change the active keymap.
+#[cfg(test)]
+pub(crate) static TEST_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate() {
+        assert_eq!(MINICOM.translate(b"\x7f"), Translation::Key(Key::Delete));
+        assert_eq!(MINICOM.translate(b"\x08"), Translation::None);
+        assert_eq!(PUTTY.translate(b"\x1b["), Translation::Partial);
+        assert_eq!(PUTTY.translate(b"\x1b[["), Translation::Partial);
+        assert_eq!(PUTTY.translate(b"\x1b[[C"), Translation::Key(Key::F(3)));
+        assert_eq!(PUTTY.translate(b"\x1b[A"), Translation::None);
+        assert_eq!(DEFAULT.translate(b"\x1b"), Translation::None);
+    }
+
+    #[test]
+    fn test_select_preset() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        assert!(find("vt52").is_none());
+        set_keymap(find("screen").unwrap());
+        assert_eq!(keymap().name, "screen");
+        set_keymap(&DEFAULT);
+    }
+}
@@ -1,4 +1,6 @@
+pub mod bindings;
 pub mod buffer;
 pub mod key_reader;
+pub mod keymap;
 pub mod parser;
 pub mod renderer;
@@ -19,12 +19,29 @@ use core::fmt::Write;
 use core::iter::Iterator;
 use core::option::Option::{self, None, Some};
 
-use crate::autocomplete::Autocomplete;
-use crate::history::History;
+use crate::alias::{AliasTable, ALIAS_MAX};
+use crate::args::{check_args, ArgCheck};
+use crate::autocomplete::args::CommonPrefix;
+use crate::autocomplete::history::newest_match;
+use crate::autocomplete::{ArgCompleter, Autocomplete, CompletionSource, HistoryCompletion};
+use crate::checksum::Checksum;
+use crate::history::{DuplicatePolicy, EntryTime, History, TimeFormat};
+use crate::input::bindings::{self, Action, KeyBindings};
 use crate::input::buffer::InputBuffer;
-use crate::input::key_reader::Key;
-use crate::input::renderer::DisplayRenderer;
-use crate::logger::UnifiedWriter;
+use crate::input::key_reader::{Key, TERMINAL_SIZE_PROBE};
+use crate::input::keymap;
+use crate::input::renderer::{self, DisplayRenderer};
+use crate::interact::{self, ExecOutcome, Pending};
+use crate::logger::{self, LogLevel, UnifiedWriter};
+use crate::memtools::{self, MemAccess};
+use crate::notice;
+use crate::output::{self, Column, JsonWriter, OutputFormat, TableStyle, TableWriter};
+use crate::prompt;
+use crate::storage::{HistoryStore, StorageControl, StorageError, StorageOp};
+use crate::stream::{self, StreamKey};
+use crate::theme::{self, Theme};
+use crate::tokens::Tokenizer;
+use crate::watch::MemWatch;
 
 // Import StdWriter for hosted builds
 #[cfg(feature = "hosted")]
@@ -46,6 +63,30 @@ use crate::input::renderer::StdWriter;
 /// - `history`: Command history manager (heap-allocated or stack-based depending on feature flags).
 /// - `buffer`: Input buffer for editing and cursor movement (heap-allocated or stack-based depending on feature flags).
 /// - `prompt`: Static prompt string displayed to the user.
+/// - `pending_input`: Input requested by the last command and the line to run again once it is collected.
+/// - `skip_history`: Predicate for lines that must not be stored in history (e.g. `@nohistory` commands).
+/// - `clock`: Millisecond clock used by periodic built-ins such as `watch`.
+/// - `watch`: The running `watch mem`, if any.
+/// - `arg_completer`: Completion provider for arguments (see `autocomplete::args`).
+/// - `completions`: Values registered at runtime, offered after `arg_completer`'s
+///   (see `autocomplete::registry`).
+/// - `snippets`: Named command line templates expanded by `snippet_key`.
+/// - `snippet_key`: Key expanding the snippet name before the cursor.
+/// - `describe`: Source of the command table listed by the `describe` and `apropos` built-ins.
+/// - `mouse`: Receives mouse reports; without it they are dropped.
+/// - `storage`: Runs the `storage` built-in on the application's persistent storage.
+/// - `live_validation`: Whether typed arguments are checked on every keystroke.
+/// - `ghost_completion`: Whether command completions are shown as ghost text instead of
+///   being typed in.
+/// - `history_completion`: Whether typed lines are completed from the history as well.
+/// - `history_walk`: The walk through the history lines starting with the typed text.
+/// - `shown_hint`: Whether the validation hint is on screen.
+/// - `paging`: The help listing being paged and the line its next page starts at.
+/// - `search`: The reverse history search in progress (Ctrl-R), if any.
+/// - `pasting`: Whether a bracketed paste is being received.
+/// - `aliases`: The aliases defined with the `alias` built-in (see `crate::alias`).
+/// - `bindings`: The actions of the keys (see `input::bindings`).
+/// - `normal_mode`: Whether modal bindings (vi) are in normal mode.
 ///
 pub struct InputParser<
     'a,
@@ -62,6 +103,7 @@ pub struct InputParser<
     autocomplete: Autocomplete<'a, NAC, FNL>,
 
     // Temporary buffer for passing commands to autocomplete (sized with NAC)
+    #[cfg(not(feature = "no-autocomplete"))]
     temp_commands: Vec<&'a str, NAC>,
 
     #[cfg(feature = "heap-history")]
@@ -75,8 +117,139 @@ pub struct InputParser<
     buffer: InputBuffer<IML>,
 
     prompt: &'static str,
+
+    pending_input: Option<(Pending, String<IML>)>,
+
+    skip_history: fn(&str) -> bool,
+
+    clock: Option<fn() -> u64>,
+    watch: Option<MemWatch>,
+
+    arg_completer: Option<ArgCompleter>,
+    completions: Option<&'static dyn CompletionSource>,
+
+    snippets: &'static [(&'static str, &'static str)],
+    snippet_key: Option<Key>,
+
+    busy: Option<fn() -> bool>,
+    shown_busy: bool,
+
+    describe: Option<CommandDescriber>,
+
+    mouse: Option<MouseHandler>,
+
+    storage: Option<StorageControl>,
+
+    mem_access: Option<MemAccess>,
+
+    privilege_level: Option<fn() -> u8>,
+
+    live_validation: bool,
+    shown_hint: bool,
+
+    ghost_completion: bool,
+
+    history_completion: bool,
+    history_walk: HistoryCompletion<IML>,
+
+    paging: Option<(String<IML>, usize)>,
+
+    search: Option<HistorySearch<IML>>,
+
+    pasting: bool,
+
+    aliases: AliasTable<IML, ALIAS_MAX>,
+
+    bindings: &'static KeyBindings,
+    normal_mode: bool,
+
+    // A size probe was sent and not answered yet
+    probing_size: bool,
+}
+
+/// State of a reverse history search (Ctrl-R); the match is kept in the input buffer.
+struct HistorySearch<const IML: usize> {
+    /// Text searched for.
+    query: String<IML>,
+    /// History index of the match shown, `None` before the first one.
+    found: Option<usize>,
+    /// No entry older than `found` contains `query`.
+    failed: bool,
+    /// The line being edited when the search started, restored when it is aborted.
+    saved: String<IML>,
+}
+
+/// Receives mouse reports (`Key::Mouse`): button code, column, row (1-based), press.
+pub type MouseHandler = fn(button: u8, x: u16, y: u16, press: bool);
+
+/// Returns `(name, descriptor, arity, help, level)` of the command at an index, `None`
+/// past the last one (generated `describe_command`); `level` is the privilege level the
+/// command needs.
+pub type CommandDescriber = fn(usize) -> Option<(&'static str, &'static str, u8, &'static str, u8)>;
+
+/// Marks where the cursor is placed when a snippet is expanded.
+pub const SNIPPET_CURSOR: &str = "<cursor>";
+
+/// Written before the prompt while deferred commands are running or queued.
+pub const BUSY_MARKER: &str = "[busy] ";
+
+/// The shell built-ins, with the privilege level each needs: 0, 1 and 2 as the
+/// generated `LEVEL_USER`, `LEVEL_OPERATOR` and `LEVEL_ADMIN`. A command of the
+/// application named like one would never run (`ShellConfig::validate` rejects it).
+/// `set` is left out: only `set` alone and `set <NAME> ...` are the built-in.
+pub const BUILTINS: &[(&str, u8)] = &[
+    ("abort", 0),
+    ("alias", 0),
+    ("apropos", 0),
+    ("begin", 0),
+    ("commit", 0),
+    ("crc16", 0),
+    ("crc32", 0),
+    ("describe", 0),
+    ("echo", 0),
+    ("format", 0),
+    ("history", 0),
+    ("jobs", 0),
+    ("kill", 1),
+    ("log", 0),
+    ("logcolor", 0),
+    ("loglevel", 0),
+    #[cfg(feature = "mem-tools")]
+    ("md", 1),
+    #[cfg(feature = "mem-tools")]
+    ("mfill", 2),
+    #[cfg(feature = "mem-tools")]
+    ("mw16", 2),
+    #[cfg(feature = "mem-tools")]
+    ("mw32", 2),
+    #[cfg(feature = "mem-tools")]
+    ("mw8", 2),
+    ("prompt", 0),
+    ("record", 0),
+    ("replay", 0),
+    ("shell", 0),
+    ("storage", 2),
+    ("sum8", 0),
+    ("term", 0),
+    ("theme", 0),
+    ("unalias", 0),
+    ("unset", 0),
+    ("watch", 0),
+];
+
+/// The privilege level the built-in `name` needs, `None` if there is no such built-in.
+pub fn builtin_level(name: &str) -> Option<u8> {
+    BUILTINS
+        .iter()
+        .find(|&&(builtin, _)| builtin == name)
+        .map(|&(_, level)| level)
 }
 
+const TERM_SIZE_USAGE: &str = "usage: term size [<cols> <rows>|probe]\r\n";
+
+/// Narrowest args column of a help listing on a narrow terminal.
+const TABLE_MIN_ARGS_WIDTH: usize = 8;
+
 impl<
         'a,
         W: UnifiedWriter,
@@ -123,7 +296,7 @@ impl<
 
         let log_writer = renderer.writer_mut();
         log_writer.write_str("Shell started (try ###)\n\r");
-        log_writer.write_str(prompt);
+        log_writer.write_str(&prompt::expand(prompt));
 
         Self {
             renderer,
@@ -131,15 +304,271 @@ impl<
             shell_datatypes,
             shell_shortcuts,
             autocomplete: Autocomplete::<'a, NAC, FNL>::new(),
+            #[cfg(not(feature = "no-autocomplete"))]
             temp_commands: Vec::new(),
             history,
             buffer,
             prompt,
+            pending_input: None,
+            skip_history: |_| false,
+            clock: None,
+            watch: None,
+            arg_completer: None,
+            completions: None,
+            snippets: &[],
+            snippet_key: None,
+            busy: None,
+            shown_busy: false,
+            describe: None,
+            mouse: None,
+            storage: None,
+            mem_access: None,
+            privilege_level: None,
+            live_validation: false,
+            ghost_completion: false,
+            history_completion: false,
+            history_walk: HistoryCompletion::new(),
+            shown_hint: false,
+            paging: None,
+            search: None,
+            pasting: false,
+            aliases: AliasTable::new(),
+            bindings: &bindings::EMACS,
+            normal_mode: false,
+            probing_size: false,
+        }
+    }
+
+    /// Sets the terminal height: help listings (`#`, `##`, `## <group>`) longer than a
+    /// screen stop after `rows - 1` lines at a `--more--` prompt. Space or PageDown
+    /// shows the next page, Enter or Down the next line, `q` ends the listing.
+    pub fn set_page_rows(&mut self, rows: u16) {
+        self.renderer.set_page_rows(Some(rows.max(2)));
+    }
+
+    /// Sets the terminal width: an input line longer than the width left by the prompt
+    /// scrolls horizontally with the cursor instead of wrapping, so lines up to `IML`
+    /// stay editable on a narrow terminal. The `args` column of the help listings is
+    /// narrowed so that their rows fit.
+    pub fn set_terminal_columns(&mut self, columns: u16) {
+        self.renderer.set_columns(Some(columns));
+    }
+
+    /// Sets both terminal dimensions (see `set_terminal_columns` and `set_page_rows`),
+    /// as the terminal's reply to `probe_terminal_size` does.
+    pub fn set_terminal_size(&mut self, columns: u16, rows: u16) {
+        self.set_terminal_columns(columns);
+        self.set_page_rows(rows);
+    }
+
+    /// Asks the terminal for its size (see `key_reader::TERMINAL_SIZE_PROBE`). The reply
+    /// arrives as a key, a `Key::CursorReport`, and sets the size with
+    /// `set_terminal_size`; a terminal that does not answer keeps the size set before.
+    /// Reports that were not asked for (xterm's Shift+F3 sends `ESC [ 1 ; 2 R`) are
+    /// ignored.
+    pub fn probe_terminal_size(&mut self) {
+        self.probing_size = true;
+        let writer = self.renderer.writer_mut();
+        writer.write_str(TERMINAL_SIZE_PROBE);
+        writer.flush();
+    }
+
+    /// Sets the colors of the prompt, hints, help headers and suggestions, and of the
+    /// errors reported by the runner (see `crate::theme`).
+    pub fn set_theme(&mut self, theme: &'static Theme) {
+        self.renderer.set_theme(theme);
+        theme::set_theme(theme);
+    }
+
+    /// Sets the actions of the keys on the edit line (see `input::bindings`), e.g.
+    /// `&bindings::VI`. The default is `bindings::EMACS`.
+    pub fn set_key_bindings(&mut self, bindings: &'static KeyBindings) {
+        self.bindings = bindings;
+        self.normal_mode = false;
+    }
+
+    /// Sets the predicate telling whether deferred commands are running or queued
+    /// (see `queue`); while it returns `true` the prompt starts with `BUSY_MARKER`.
+    /// Typing is accepted either way.
+    pub fn set_busy_indicator(&mut self, is_busy: fn() -> bool) {
+        self.busy = Some(is_busy);
+    }
+
+    /// Registers snippets as `(name, template)` pairs: typing a name and pressing `key`
+    /// replaces the name with its template, the cursor placed at `SNIPPET_CURSOR`
+    /// (e.g. `("i2cw", "i2c write 0x50 <cursor>")` with `Key::F(2)`).
+    pub fn set_snippets(&mut self, snippets: &'static [(&'static str, &'static str)], key: Key) {
+        self.snippets = snippets;
+        self.snippet_key = Some(key);
+    }
+
+    /// Sets the provider used by Tab to complete arguments once the command name is
+    /// complete (see `autocomplete::args`).
+    pub fn set_arg_completer(&mut self, completer: ArgCompleter) {
+        self.arg_completer = Some(completer);
+    }
+
+    /// Sets the runtime completion values offered for arguments (see
+    /// `autocomplete::registry`), in addition to those of the `ArgCompleter`.
+    pub fn set_completions(&mut self, completions: &'static dyn CompletionSource) {
+        self.completions = Some(completions);
+    }
+
+    /// Enables checking the arguments against the command's descriptor while they are
+    /// typed (see `args::check`): a finished argument the dispatcher would reject shows
+    /// a red `✗ argN: <error>` hint at the right of the line, a complete valid line a
+    /// green `✓`. Off by default.
+    pub fn set_live_validation(&mut self, enabled: bool) {
+        self.live_validation = enabled;
+    }
+
+    /// Shows the completion of the command name being typed as ghost text, fish style:
+    /// the rest of the first matching command follows the cursor in the theme's
+    /// suggestion color, and Right arrow or Tab accepts it. The typed text is left as is
+    /// (without it, a unique match or the common prefix is typed in at once). Off by
+    /// default; needs a theme with a suggestion color.
+    pub fn set_ghost_completion(&mut self, enabled: bool) {
+        self.ghost_completion = enabled;
+    }
+
+    /// Completes whole command lines from the history (see `autocomplete::history`): Up
+    /// and Down on a typed line walk through the earlier lines starting with it, Tab
+    /// after the arguments offers them too, and ghost completion shows the newest one
+    /// before the command names. Up on an empty line walks the whole history as usual.
+    /// Off by default.
+    pub fn set_history_completion(&mut self, enabled: bool) {
+        self.history_completion = enabled;
+    }
+
+    /// Sets the millisecond clock used by periodic built-ins (`watch`).
+    /// Without a clock these built-ins report an error.
+    pub fn set_clock(&mut self, now_ms: fn() -> u64) {
+        self.clock = Some(now_ms);
+    }
+
+    /// Sets the source of the command table listed by the `describe` and `apropos`
+    /// built-ins, typically the generated `describe_command`.
+    pub fn set_describer(&mut self, describe: CommandDescriber) {
+        self.describe = Some(describe);
+    }
+
+    /// Passes mouse reports to `handler` instead of dropping them, e.g. for a command
+    /// drawing a TUI that enabled mouse reporting. They never reach the edit line.
+    pub fn set_mouse_handler(&mut self, handler: MouseHandler) {
+        self.mouse = Some(handler);
+    }
+
+    /// Asks the terminal to mark pasted text (`ESC [ ? 2004 h`), or to stop it. Marked
+    /// text is inserted verbatim: no autocomplete, no redraw per character, and Tab or
+    /// editing keys in it do nothing; each line ending in it runs the line as Enter does.
+    pub fn set_bracketed_paste(&mut self, enabled: bool) {
+        let mode = if enabled {
+            "\x1B[?2004h"
+        } else {
+            "\x1B[?2004l"
+        };
+        let writer = self.renderer.writer_mut();
+        writer.write_str(mode);
+        writer.flush();
+    }
+
+    /// Allows the memory built-ins of the `mem-tools` feature (`md`, `mw8`, ...) the
+    /// regions `access` accepts (see `crate::memtools`); they refuse all without it.
+    pub fn set_mem_access(&mut self, access: MemAccess) {
+        self.mem_access = Some(access);
+    }
+
+    /// Refuses the built-ins needing more than the privilege level `level` returns (see
+    /// `BUILTINS`), as `dispatch` refuses commands; without it every built-in runs.
+    pub fn set_privilege_level(&mut self, level: fn() -> u8) {
+        self.privilege_level = Some(level);
+    }
+
+    /// Enables the `storage [info|mount|format]` built-in on the application's
+    /// persistent storage (see `crate::storage`).
+    pub fn set_storage_control(&mut self, control: StorageControl) {
+        self.storage = Some(control);
+    }
+
+    /// Keeps the history in the application's storage across resets (see
+    /// `History::set_store`); returns the number of lines loaded from it.
+    pub fn set_history_store(&mut self, store: HistoryStore) -> Result<usize, StorageError> {
+        self.history.set_store(store)
+    }
+
+    /// Sets the predicate deciding which command lines are kept out of history,
+    /// typically the generated `is_nohistory`. Lines starting with a space are never
+    /// stored regardless of it.
+    pub fn set_history_filter(&mut self, skip_history: fn(&str) -> bool) {
+        self.skip_history = skip_history;
+    }
+
+    /// Sets what running a line already in the history does (see `DuplicatePolicy`).
+    pub fn set_history_duplicates(&mut self, policy: DuplicatePolicy) {
+        self.history.set_duplicate_policy(policy);
+    }
+
+    /// Stamps the lines added to the history with `now`, in seconds (see
+    /// `History::set_clock`), shown by `history -t` and `history -T`.
+    pub fn set_history_clock(&mut self, now: fn() -> u32) {
+        self.history.set_clock(now);
+    }
+
+    /// Lists the commands as a `command | args` table sized to the longest entries; with
+    /// a `group`, only its subcommands (`gpio set`, `gpio read`, ...). With a terminal
+    /// width, the args column is narrowed (and long args cut) so that rows fit.
+    fn write_command_table<X: UnifiedWriter>(
+        writer: &mut X,
+        commands: &[(&str, &str)],
+        group: Option<&str>,
+        columns: Option<u16>,
+    ) {
+        let listed = || {
+            commands
+                .iter()
+                .filter(move |&&(name, _)| group.is_none() || command_group(name) == group)
+        };
+        let (name_width, mut args_width) = listed().fold((0, 0), |(n, a), &(name, args)| {
+            (n.max(name.len()), a.max(args.len()))
+        });
+        if let Some(columns) = columns {
+            // `| ` + name + ` | ` + args + ` |`
+            let used = name_width.max("command".len()) + 7;
+            let room = (columns as usize).saturating_sub(used);
+            args_width = args_width.min(room.max(TABLE_MIN_ARGS_WIDTH));
+        }
+        let columns = [
+            Column::new("command", name_width),
+            Column::new("args", args_width),
+        ];
+        let mut table = TableWriter::new(writer, &columns, TableStyle::Ascii);
+        for &(name, args) in listed() {
+            table.row(&[name, args]);
+        }
+        table.finish();
+    }
+
+    /// Whether `name` is the group of subcommands (`gpio` of `gpio set`).
+    fn is_group(&self, name: &str) -> bool {
+        self.shell_commands
+            .iter()
+            .any(|&(command, _)| command_group(command) == Some(name))
+    }
+
+    /// Lists the shortcuts (given as `a | b | c`) as a one-column table.
+    fn write_shortcut_table<X: UnifiedWriter>(writer: &mut X, shortcuts: &str) {
+        let keys = || shortcuts.split(" | ").filter(|k| !k.is_empty());
+        let width = keys().map(str::len).max().unwrap_or(0);
+        let columns = [Column::new("shortcut", width)];
+        let mut table = TableWriter::new(writer, &columns, TableStyle::Ascii);
+        for key in keys() {
+            table.row(&[key]);
         }
+        table.finish();
     }
 
     /// Helper function: write a number directly to the writer without allocation
-    fn write_number(writer: &mut W, mut num: usize) {
+    fn write_number<X: UnifiedWriter + ?Sized>(writer: &mut X, mut num: usize) {
         let mut digits = [0u8; 20];
         let mut digit_count = 0;
 
@@ -160,14 +589,185 @@ impl<
         }
     }
 
+    #[cfg(not(feature = "no-autocomplete"))]
     fn buffer_to_autocomplete_input(&self) -> String<FNL> {
         self.buffer.chars().take(FNL).collect()
     }
 
     fn render_buffer(&mut self) {
+        let hint = if self.live_validation {
+            self.validation_hint()
+        } else {
+            String::new()
+        };
+        self.render_line(&hint);
+    }
+
+    fn render_line(&mut self, hint: &str) {
         let buf_str = self.buffer.to_string();
         let cursor_pos = self.buffer.cursor().min(self.buffer.len());
-        self.renderer.render(self.prompt, &buf_str, cursor_pos);
+        let marker = if self.shown_busy { BUSY_MARKER } else { "" };
+        let prompt = prompt::expand(self.prompt);
+        let ghost = self.ghost_suggestion();
+        let ghost = ghost.as_ref().map_or("", |(rest, _)| rest.as_str());
+        self.renderer
+            .render_suggested(marker, &prompt, &buf_str, ghost, cursor_pos, hint);
+        self.shown_hint = !hint.is_empty();
+    }
+
+    /// Ghost text of ghost completion: the rest of the newest history line starting with
+    /// the line (with history completion), else of the first command starting with it,
+    /// and whether that command is the only one. `None` unless the cursor ends a line
+    /// that is the start of one of them.
+    fn ghost_suggestion(&self) -> Option<(String<IML>, bool)> {
+        if !self.ghost_completion
+            || self.renderer.theme().suggestion.is_empty()
+            || self.buffer.is_empty()
+            || self.buffer.cursor() < self.buffer.len()
+        {
+            return None;
+        }
+        let line = self.buffer.to_string();
+        if self.history_completion {
+            if let Some(entry) = newest_match(&self.history, &line) {
+                let full: String<IML> = entry.to_line()?;
+                return Some((full[line.len()..].try_into().ok()?, false));
+            }
+        }
+        let mut matches = self
+            .shell_commands
+            .iter()
+            .map(|&(name, _)| name)
+            .filter(|name| name.len() > line.len() && name.starts_with(line.as_str()));
+        let first = matches.next()?;
+        Some((
+            first[line.len()..].try_into().ok()?,
+            matches.next().is_none(),
+        ))
+    }
+
+    /// Appends the ghost text to the line (and a space if the command is the only
+    /// match); returns `false` if none is shown.
+    fn accept_ghost(&mut self) -> bool {
+        let Some((rest, unique)) = self.ghost_suggestion() else {
+            return false;
+        };
+        let mut line = self.buffer.to_string();
+        let _ = line.push_str(&rest);
+        if unique {
+            let _ = line.push(' ');
+        }
+        self.buffer.overwrite(&line);
+        #[cfg(not(feature = "no-autocomplete"))]
+        self.update_autocomplete_after_delete();
+        self.render_buffer();
+        true
+    }
+
+    /// Hint shown by live validation: the first argument the dispatcher would reject,
+    /// a check mark once the line is complete, else nothing.
+    fn validation_hint(&self) -> String<40> {
+        let mut hint = String::new();
+        let line = self.buffer.to_string();
+        let Some((descriptor, args_start)) = self.resolve_command(&line) else {
+            return hint;
+        };
+        let theme = self.renderer.theme();
+        match check_args(descriptor, &line[args_start..]) {
+            ArgCheck::Partial => {}
+            ArgCheck::Complete => Theme::paint(&mut hint, theme.ok, "✓"),
+            ArgCheck::Invalid(problem) => {
+                let _ = hint.push_str(theme.error);
+                let _ = write!(hint, "✗ arg{}: {}", problem.index + 1, problem.error);
+                if !theme.error.is_empty() {
+                    let _ = hint.push_str(theme::RESET);
+                }
+            }
+        }
+        hint
+    }
+
+    /// Descriptor of the command named at the start of `line` (one token, or two for a
+    /// group subcommand) and the offset of its arguments. `None` while the name is
+    /// being typed or if it is not a command.
+    fn resolve_command(&self, line: &str) -> Option<(&'a str, usize)> {
+        let mut tokens = Tokenizer::new(line);
+        let first = tokens.next()?;
+        if tokens.offset() == line.len() {
+            return None;
+        }
+        if let Some(&(_, descriptor)) = self.shell_commands.iter().find(|&&(n, _)| n == first) {
+            return Some((descriptor, tokens.offset()));
+        }
+        let verb = tokens.next()?;
+        if tokens.offset() == line.len() {
+            return None;
+        }
+        self.shell_commands
+            .iter()
+            .find(|&&(name, _)| name.split_once(' ') == Some((first, verb)))
+            .map(|&(_, descriptor)| (descriptor, tokens.offset()))
+    }
+
+    /// While a stream runs, keys control it instead of editing the line; the prompt comes
+    /// back when it ends.
+    fn route_to_stream(&mut self, key: Option<Key>) {
+        let running = match key.map(stream::handle_key) {
+            Some(StreamKey::Paused) => {
+                let writer = self.renderer.writer_mut();
+                writer.write_str("-- paused --\r\n");
+                true
+            }
+            Some(StreamKey::Stopped) => false,
+            Some(StreamKey::Resumed | StreamKey::Ignored) | None => {
+                stream::poll(self.clock.map(|now_ms| now_ms()))
+            }
+        };
+        if !running {
+            self.render_buffer();
+        }
+    }
+
+    /// Prints the messages queued with `notice::println_above` in place of the edit line,
+    /// then draws the prompt and the input again.
+    fn print_notices(&mut self) {
+        let mut printed = false;
+        while let Some(msg) = notice::take() {
+            let writer = self.renderer.writer_mut();
+            if !printed {
+                writer.write_str("\r\x1B[K");
+                printed = true;
+            }
+            writer.write_str(&msg);
+            writer.write_str("\r\n");
+        }
+        if printed {
+            self.render_buffer();
+        }
+    }
+
+    /// Writes the lines queued in the logger's deferred mode (see
+    /// `logger::set_deferred_logging`) in place of the edit line, then draws it again.
+    fn print_deferred_logs(&mut self) {
+        if !logger::has_deferred_logs() {
+            return;
+        }
+        let writer = self.renderer.writer_mut();
+        writer.write_str("\r\x1B[K");
+        writer.flush();
+        logger::drain_logs();
+        self.render_buffer();
+    }
+
+    /// Redraws the line when the busy state changed since it was last shown.
+    fn update_busy_marker(&mut self) {
+        let busy = self.busy.is_some_and(|is_busy| is_busy());
+        if busy != self.shown_busy {
+            self.shown_busy = busy;
+            if self.pending_input.is_none() {
+                self.render_buffer();
+            }
+        }
     }
 
     /// Handles a single character input from the user.
@@ -182,50 +782,63 @@ impl<
     ///
     /// Finally, renders the updated buffer and prompt to the display.
     ///
+    /// With the `no-autocomplete` feature the character is only inserted.
+    ///
     pub fn handle_char(&mut self, ch: char) {
+        self.history_walk.reset();
         if self.buffer.insert(ch) {
-            let autocomplete_input: String<FNL> = self.buffer.chars().take(FNL).collect();
-
-            // Collect commands for this first character
-            // We need to provide &'a [&'a str] to the closure, but we're in a method with lifetime 'self
-            // However, the actual command strings are 'static (from shell_commands), so this is safe
-            self.temp_commands.clear();
-            if let Some(first_char) = autocomplete_input.chars().next() {
-                for &(cmd_name, _) in self.shell_commands {
-                    if let Some(first) = cmd_name.chars().next() {
-                        if first == first_char {
-                            let _ = self.temp_commands.push(cmd_name);
-                        }
+            #[cfg(not(feature = "no-autocomplete"))]
+            self.update_autocomplete_after_insert();
+        } else {
+            self.renderer.boundary_marker();
+        }
+
+        self.render_buffer();
+    }
+
+    /// Refreshes the autocomplete state after a character insertion and applies
+    /// the suggestion (if any) to the input buffer.
+    ///
+    #[cfg(not(feature = "no-autocomplete"))]
+    fn update_autocomplete_after_insert(&mut self) {
+        let autocomplete_input: String<FNL> = self.buffer.chars().take(FNL).collect();
+
+        // Collect commands for this first character
+        // We need to provide &'a [&'a str] to the closure, but we're in a method with lifetime 'self
+        // However, the actual command strings are 'static (from shell_commands), so this is safe
+        self.temp_commands.clear();
+        if let Some(first_char) = autocomplete_input.chars().next() {
+            for &(cmd_name, _) in self.shell_commands {
+                if let Some(first) = cmd_name.chars().next() {
+                    if first == first_char {
+                        let _ = self.temp_commands.push(cmd_name);
                     }
                 }
             }
+        }
 
-            // SAFETY: The command strings are 'static (from shell_commands: &'static [...]),
-            // and 'static outlives 'a, so it's safe to transmute the slice lifetime.
-            // We're only extending the lifetime of the slice reference, not the strings themselves.
-            let temp_commands_static: &'a [&'a str] = unsafe {
-                core::mem::transmute::<&[&str], &'a [&'a str]>(self.temp_commands.as_slice())
-            };
+        // SAFETY: The command strings are 'static (from shell_commands: &'static [...]),
+        // and 'static outlives 'a, so it's safe to transmute the slice lifetime.
+        // We're only extending the lifetime of the slice reference, not the strings themselves.
+        let temp_commands_static: &'a [&'a str] = unsafe {
+            core::mem::transmute::<&[&str], &'a [&'a str]>(self.temp_commands.as_slice())
+        };
 
-            self.autocomplete
-                .update_input(&autocomplete_input, |_| temp_commands_static);
+        self.autocomplete
+            .update_input(&autocomplete_input, |_| temp_commands_static);
 
-            let suggestion = self.autocomplete.current_input();
+        let suggestion = self.autocomplete.current_input();
 
-            if suggestion != autocomplete_input.as_str() {
-                let mut new_buf = String::<IML>::new();
-                let _ = new_buf.push_str(suggestion);
+        // Ghost completion shows the suggestion instead
+        if suggestion != autocomplete_input.as_str() && !self.ghost_completion {
+            let mut new_buf = String::<IML>::new();
+            let _ = new_buf.push_str(suggestion);
 
-                for c in self.buffer.chars().skip(FNL) {
-                    let _ = new_buf.push(c);
-                }
-                self.buffer.overwrite(&new_buf);
+            for c in self.buffer.chars().skip(FNL) {
+                let _ = new_buf.push(c);
             }
-        } else {
-            self.renderer.boundary_marker();
+            self.buffer.overwrite(&new_buf);
         }
-
-        self.render_buffer();
     }
 
     /// Handles the backspace key event within the input buffer.
@@ -241,27 +854,20 @@ impl<
     ///
     pub fn handle_backspace(&mut self) {
         if self.buffer.backspace() {
-            let autocomplete_input = self.buffer_to_autocomplete_input();
-
-            // Collect commands for this first character
-            self.temp_commands.clear();
-            if let Some(first_char) = autocomplete_input.chars().next() {
-                for &(cmd_name, _) in self.shell_commands {
-                    if let Some(first) = cmd_name.chars().next() {
-                        if first == first_char {
-                            let _ = self.temp_commands.push(cmd_name);
-                        }
-                    }
-                }
-            }
+            #[cfg(not(feature = "no-autocomplete"))]
+            self.update_autocomplete_after_delete();
+        } else {
+            self.renderer.bell();
+        }
 
-            // SAFETY: Same justification as handle_char - commands are 'static
-            let temp_commands_static: &'a [&'a str] = unsafe {
-                core::mem::transmute::<&[&str], &'a [&'a str]>(self.temp_commands.as_slice())
-            };
+        self.render_buffer();
+    }
 
-            self.autocomplete
-                .update_input(&autocomplete_input, |_| temp_commands_static);
+    /// Deletes the word before the cursor (Alt+Backspace).
+    pub fn handle_delete_word(&mut self) {
+        if self.buffer.delete_word_before() {
+            #[cfg(not(feature = "no-autocomplete"))]
+            self.update_autocomplete_after_delete();
         } else {
             self.renderer.bell();
         }
@@ -269,6 +875,33 @@ impl<
         self.render_buffer();
     }
 
+    /// Refreshes the autocomplete state after a character was removed.
+    ///
+    #[cfg(not(feature = "no-autocomplete"))]
+    fn update_autocomplete_after_delete(&mut self) {
+        let autocomplete_input = self.buffer_to_autocomplete_input();
+
+        // Collect commands for this first character
+        self.temp_commands.clear();
+        if let Some(first_char) = autocomplete_input.chars().next() {
+            for &(cmd_name, _) in self.shell_commands {
+                if let Some(first) = cmd_name.chars().next() {
+                    if first == first_char {
+                        let _ = self.temp_commands.push(cmd_name);
+                    }
+                }
+            }
+        }
+
+        // SAFETY: Same justification as handle_char - commands are 'static
+        let temp_commands_static: &'a [&'a str] = unsafe {
+            core::mem::transmute::<&[&str], &'a [&'a str]>(self.temp_commands.as_slice())
+        };
+
+        self.autocomplete
+            .update_input(&autocomplete_input, |_| temp_commands_static);
+    }
+
     /// Handles the tab key event to cycle through autocomplete suggestions.
     ///
     /// If `reverse` is `true`, triggers reverse cycling (Shift+Tab); otherwise, cycles forward.
@@ -279,7 +912,32 @@ impl<
     ///
     /// Overwrites the buffer with the new input and re-renders the prompt and buffer display.
     ///
+    /// No-op when built with the `no-autocomplete` feature.
+    ///
     pub fn handle_tab(&mut self, reverse: bool) {
+        if cfg!(feature = "no-autocomplete") {
+            return;
+        }
+        if !reverse && self.accept_ghost() {
+            return;
+        }
+
+        if (self.arg_completer.is_some() || self.completions.is_some())
+            && self.buffer.cursor() == self.buffer.len()
+            && self.complete_argument()
+        {
+            return;
+        }
+
+        // Past the command name, the history has the lines with arguments
+        if !reverse
+            && self.history_completion
+            && (self.history_walk.is_active() || self.buffer.to_string().contains(' '))
+        {
+            self.handle_up();
+            return;
+        }
+
         if reverse {
             self.autocomplete.cycle_backward();
         } else {
@@ -298,13 +956,128 @@ impl<
         self.render_buffer();
     }
 
+    /// Completes the last word of the line with the `ArgCompleter` and the runtime
+    /// completions if it is an argument (i.e. a command name precedes it). Returns
+    /// `false` if the line has no argument.
+    fn complete_argument(&mut self) -> bool {
+        let line = self.buffer.to_string();
+        let word_start = line.rfind(' ').map_or(0, |i| i + 1);
+        let mut tokens = line[..word_start].split_whitespace();
+        let Some(mut command) = tokens.next() else {
+            return false;
+        };
+        // The subcommand of a group is part of the command name (`gpio set`)
+        if self.is_group(command) {
+            let Some(verb) = tokens.next() else {
+                // Completing the subcommand itself: left to the command autocomplete
+                return false;
+            };
+            let Some(&(name, _)) = self
+                .shell_commands
+                .iter()
+                .find(|&&(name, _)| name.split_once(' ') == Some((command, verb)))
+            else {
+                self.renderer.bell();
+                return true;
+            };
+            command = name;
+        }
+        let arg_index = tokens.count();
+        let word = &line[word_start..];
+        let (arg_completer, completions) = (self.arg_completer, self.completions);
+        let completer = |offer: &mut dyn FnMut(&str)| {
+            if let Some(completer) = arg_completer {
+                completer(command, arg_index, word, offer);
+            }
+            if let Some(completions) = completions {
+                completions.offer_values(command, arg_index, offer);
+            }
+        };
+
+        let mut prefix = CommonPrefix::<IML>::new(word);
+        completer(&mut |candidate| prefix.offer(candidate));
+
+        if prefix.matches == 0 {
+            self.renderer.bell();
+        } else if prefix.matches == 1 || prefix.common.len() > word.len() {
+            let mut new_buf: String<IML> = String::new();
+            let _ = new_buf.push_str(&line[..word_start]);
+            let _ = new_buf.push_str(&prefix.common);
+            if prefix.matches == 1 && !prefix.common.ends_with(['.', '/']) {
+                let _ = new_buf.push(' ');
+            }
+            self.buffer.overwrite(&new_buf);
+        } else {
+            // Ambiguous and nothing to add: list the candidates
+            let writer = self.renderer.writer_mut();
+            writer.write_str("\r\n");
+            completer(&mut |candidate| {
+                if candidate.starts_with(word) {
+                    writer.write_str(candidate);
+                    writer.write_str("  ");
+                }
+            });
+            writer.write_str("\r\n");
+        }
+        self.render_buffer();
+        true
+    }
+
+    /// Replaces the snippet name before the cursor with its template. Rings the bell if
+    /// the word is not a snippet name or the expanded line would not fit.
+    pub fn handle_snippet(&mut self) {
+        let cursor = self.buffer.cursor();
+        let chars = self.buffer.as_chars();
+        let word_start = chars[..cursor]
+            .iter()
+            .rposition(|&c| c == ' ')
+            .map_or(0, |i| i + 1);
+        let name: String<IML> = chars[word_start..cursor].iter().collect();
+        let template = self
+            .snippets
+            .iter()
+            .find(|&&(snippet, _)| !name.is_empty() && snippet == name.as_str())
+            .map(|&(_, template)| template);
+
+        let Some(template) = template else {
+            self.renderer.bell();
+            return;
+        };
+
+        for _ in name.chars() {
+            self.buffer.backspace();
+        }
+        if !self.buffer.insert_template(template, SNIPPET_CURSOR) {
+            // Does not fit: put the name back
+            name.chars().for_each(|ch| {
+                self.buffer.insert(ch);
+            });
+            self.renderer.bell();
+            return;
+        }
+        self.render_buffer();
+    }
+
     /// Handles the up arrow key event to navigate backward through command history.
     ///
-    /// - Retrieves the previous command from history.
+    /// - Retrieves the previous command from history (with history completion, the
+    ///   previous one starting with the typed line).
     /// - Overwrites the input buffer with the retrieved command.
     /// - Re-renders the prompt and buffer display to reflect the new input.
     ///
+    /// No-op when built with the `no-history` feature.
+    ///
     pub fn handle_up(&mut self) {
+        if cfg!(feature = "no-history") {
+            return;
+        }
+        if self.history_completion && (self.history_walk.is_active() || !self.buffer.is_empty()) {
+            self.history_walk.begin(&self.buffer.to_string());
+            let older = self.history_walk.older(&self.history);
+            self.show_walked(older);
+            return;
+        }
+
         self.buffer.clear();
         let found = self
             .history
@@ -321,7 +1094,18 @@ impl<
     /// - Overwrites the input buffer with the retrieved command (or clears it if at the end).
     /// - Re-renders the prompt and buffer display to reflect the new input.
     ///
+    /// No-op when built with the `no-history` feature.
+    ///
     pub fn handle_down(&mut self) {
+        if cfg!(feature = "no-history") {
+            return;
+        }
+        if self.history_walk.is_active() {
+            let newer = self.history_walk.newer(&self.history);
+            self.show_walked(newer);
+            return;
+        }
+
         self.buffer.clear();
         let found = self
             .history
@@ -332,6 +1116,15 @@ impl<
         self.render_buffer();
     }
 
+    /// Shows the line a history completion walk moved to, or rings the bell at its end.
+    fn show_walked(&mut self, line: Option<String<IML>>) {
+        match line {
+            Some(line) => self.buffer.overwrite(&line),
+            None => self.renderer.bell(),
+        }
+        self.render_buffer();
+    }
+
     /// Handles the left arrow key event to move the cursor one position to the left.
     ///
     /// - Moves the cursor left in the input buffer.
@@ -344,10 +1137,14 @@ impl<
 
     /// Handles the right arrow key event to move the cursor one position to the right.
     ///
-    /// - Moves the cursor right in the input buffer.
+    /// - Moves the cursor right in the input buffer, or at its end accepts the ghost
+    ///   text of ghost completion.
     /// - Re-renders the prompt and buffer display to reflect the new cursor position.
     ///
     pub fn handle_right(&mut self) {
+        if self.accept_ghost() {
+            return;
+        }
         self.buffer.move_right();
         self.render_buffer();
     }
@@ -393,9 +1190,13 @@ impl<
     /// - `#q` - Quit/exit the shell.
     /// - `#` - List available commands.
     /// - `##` - List all (commands + shortcuts + arg types).
+    /// - `## <group>` - List the subcommands of a group (`## gpio`).
     /// - `#l` - Show command history.
     /// - `#c` - Clear command history.
     /// - `#N` - Execute command from history at index N.
+    /// - `#p N` / `#u N` - Pin / unpin history entry N (see `History::pin`).
+    ///
+    /// The command listings are paged if a terminal height is set (`set_page_rows`).
     ///
     pub fn handle_hashtag(&mut self, stripped: &str) -> (bool, Option<String<IML>>) {
         let writer = self.renderer.writer_mut();
@@ -403,61 +1204,72 @@ impl<
             "q" => {
                 return (false, None);
             }
-            "" => {
-                writer.write_str("Available commands:\n\r");
-                for &(name, desc) in self.shell_commands {
-                    writer.write_str("  ");
-                    writer.write_str(name);
-                    writer.write_str(": ");
-                    writer.write_str(desc);
-                    writer.write_str("\n\r");
+            "" | "#" if output::output_format() == OutputFormat::Json => {
+                let mut json = JsonWriter::new(writer);
+                json.begin_object();
+                json.key("commands").begin_array();
+                for &(name, args) in self.shell_commands {
+                    json.begin_object();
+                    json.key("name").str(name);
+                    json.key("args").str(args);
+                    json.end_object();
                 }
-            }
-            "#" => {
-                writer.write_str("Available commands:\n\r");
-                for &(name, desc) in self.shell_commands {
-                    writer.write_str("  ");
-                    writer.write_str(name);
-                    writer.write_str(": ");
-                    writer.write_str(desc);
-                    writer.write_str("\n\r");
+                json.end_array();
+                if stripped == "#" {
+                    json.key("datatypes").str(self.shell_datatypes);
+                    json.key("shortcuts").begin_array();
+                    for key in self.shell_shortcuts.split(" | ").filter(|k| !k.is_empty()) {
+                        json.str(key);
+                    }
+                    json.end_array();
                 }
-                writer.write_str("\n\rArgument types:\n\r");
-                writer.write_str(self.shell_datatypes);
-                writer.write_str("\n\r\n\rShortcuts:\n\r");
-                writer.write_str(self.shell_shortcuts);
-                writer.write_str("\n\r");
+                json.end_object();
+                writer.write_str("\r\n");
             }
-            "l" => {
-                if self.history.is_empty() {
-                    writer.write_str("History is empty.\n\r");
-                } else {
-                    // Iterate through history entries
-                    for idx in 0..self.history.len() {
-                        writer.write_str("[");
-                        Self::write_number(writer, idx);
-                        writer.write_str("] ");
-
-                        // Stream the entry byte-by-byte
-                        self.history.for_each_byte(idx, |byte| {
-                            writer.write_bytes(&[byte]);
-                            true
-                        });
-
-                        writer.write_str("\n\r");
-                    }
-
-                    // Write free space info
-                    writer.write_str("Free: ");
-                    Self::write_number(writer, self.history.get_free_space());
-                    writer.write_str(" bytes\n\r");
+            "" | "#" => self.show_help(stripped),
+            "l" if output::output_format() == OutputFormat::Json => {
+                let mut json = JsonWriter::new(writer);
+                json.begin_object();
+                json.key("history").begin_array();
+                for idx in 0..self.history.len() {
+                    let mut entry: Vec<u8, IML> = Vec::new();
+                    self.history
+                        .for_each_byte(idx, |byte| entry.push(byte).is_ok());
+                    json.str(core::str::from_utf8(&entry).unwrap_or(""));
                 }
+                json.end_array();
+                json.key("free").u64(self.history.get_free_space() as u64);
+                json.end_object();
+                writer.write_str("\r\n");
                 writer.flush();
             }
+            "l" => self.write_history(None),
             "c" => {
                 self.history.clear();
                 writer.write_str("History cleared.\n\r");
             }
+            _ if stripped.starts_with("p ") || stripped.starts_with("u ") => {
+                let done = match stripped[2..].trim().parse::<usize>() {
+                    Ok(index) if stripped.starts_with('p') => self.history.pin(index),
+                    Ok(index) => self.history.unpin(index),
+                    Err(_) => false,
+                };
+                if !done {
+                    writer.write_str("Invalid history index.\n\r");
+                }
+            }
+            _ if stripped.starts_with("# ") => {
+                let group = stripped[2..].trim();
+                if self
+                    .shell_commands
+                    .iter()
+                    .any(|&(name, _)| command_group(name) == Some(group))
+                {
+                    self.show_help(stripped);
+                } else {
+                    writer.write_str("Unknown group.\n\r");
+                }
+            }
             _ => {
                 // Try to parse as a number for history command execution
                 if let Ok(index) = stripped.parse::<usize>() {
@@ -483,13 +1295,279 @@ impl<
         (true, None)
     }
 
-    /// Clears the entire input buffer and resets autocomplete state.
-    ///
-    /// - Clears the buffer content.
-    /// - Resets the autocomplete engine to an empty input.
+    /// Lists the history (`#l`), with the time of each stamped entry in `times` (the
+    /// `history -t` / `-T` built-in).
+    fn write_history(&mut self, times: Option<TimeFormat>) {
+        let writer = self.renderer.writer_mut();
+        if self.history.is_empty() {
+            writer.write_str("History is empty.\n\r");
+        } else {
+            // Iterate through history entries
+            for idx in 0..self.history.len() {
+                writer.write_str("[");
+                Self::write_number(writer, idx);
+                writer.write_str(if self.history.is_pinned(idx) {
+                    "]* "
+                } else {
+                    "] "
+                });
+                if let (Some(format), Some(timestamp)) = (times, self.history.timestamp(idx)) {
+                    let time = EntryTime {
+                        timestamp,
+                        now: self.history.now().unwrap_or(timestamp),
+                        format,
+                    };
+                    let mut text: String<24> = String::new();
+                    let _ = write!(text, "{}  ", time);
+                    writer.write_str(&text);
+                }
+
+                // Stream the entry byte-by-byte
+                self.history.for_each_byte(idx, |byte| {
+                    writer.write_bytes(&[byte]);
+                    true
+                });
+
+                writer.write_str("\n\r");
+            }
+
+            // Write free space info
+            writer.write_str("Free: ");
+            Self::write_number(writer, self.history.get_free_space());
+            writer.write_str(" bytes\n\r");
+        }
+        writer.flush();
+    }
+
+    /// Writes a help listing: `""` the commands, `"#"` commands, argument types and
+    /// shortcuts, `"# <group>"` the subcommands of a group.
+    fn write_help<X: UnifiedWriter>(
+        writer: &mut X,
+        which: &str,
+        commands: &[(&str, &str)],
+        datatypes: &str,
+        shortcuts: &str,
+        columns: Option<u16>,
+        header: &str,
+    ) {
+        if let Some(group) = which.strip_prefix("# ") {
+            let group = group.trim();
+            writer.write_str(header);
+            writer.write_str("Commands of ");
+            writer.write_str(group);
+            writer.write_str(":");
+            if !header.is_empty() {
+                writer.write_str(theme::RESET);
+            }
+            writer.write_str("\n\r");
+            Self::write_command_table(writer, commands, Some(group), columns);
+            return;
+        }
+        Theme::paint(writer, header, "Available commands:");
+        writer.write_str("\n\r");
+        Self::write_command_table(writer, commands, None, columns);
+        if which == "#" {
+            writer.write_str("\n\r");
+            Theme::paint(writer, header, "Argument types:");
+            writer.write_str("\n\r");
+            writer.write_str(datatypes);
+            writer.write_str("\n\r\n\r");
+            Theme::paint(writer, header, "Shortcuts:");
+            writer.write_str("\n\r");
+            Self::write_shortcut_table(writer, shortcuts);
+        }
+    }
+
+    /// Writes a help listing (see `write_help`), its first page only if paging is on.
+    fn show_help(&mut self, which: &str) {
+        match self.renderer.page_rows() {
+            Some(rows) => self.page_help(which, 0, rows as usize - 1),
+            None => {
+                let columns = self.renderer.columns();
+                let header = self.renderer.theme().header;
+                Self::write_help(
+                    self.renderer.writer_mut(),
+                    which,
+                    self.shell_commands,
+                    self.shell_datatypes,
+                    self.shell_shortcuts,
+                    columns,
+                    header,
+                )
+            }
+        }
+    }
+
+    /// Writes `lines` lines of a help listing from line `skip` on; keeps paging if
+    /// more is left.
+    fn page_help(&mut self, which: &str, skip: usize, lines: usize) {
+        let (commands, datatypes, shortcuts, columns, header) = (
+            self.shell_commands,
+            self.shell_datatypes,
+            self.shell_shortcuts,
+            self.renderer.columns(),
+            self.renderer.theme().header,
+        );
+        let next = self.renderer.page(skip, lines, |writer| {
+            Self::write_help(
+                writer, which, commands, datatypes, shortcuts, columns, header,
+            )
+        });
+        self.paging = next.and_then(|next| Some((String::try_from(which).ok()?, next)));
+    }
+
+    /// Handles a key at the `--more--` prompt of a paged listing.
+    fn handle_pager_key(&mut self, key: Key) {
+        let Some((which, next)) = self.paging.take() else {
+            return;
+        };
+        let rows = self.renderer.page_rows().unwrap_or(2) as usize;
+        let lines = match key {
+            Key::Char(' ') | Key::PageDown => rows - 1,
+            Key::Enter | Key::ArrowDown => 1,
+            Key::Char('q') | Key::Char('Q') => 0,
+            _ => {
+                self.paging = Some((which, next));
+                return;
+            }
+        };
+        self.renderer.clear_more();
+        if lines > 0 {
+            self.page_help(&which, next, lines);
+        }
+        if self.paging.is_none() {
+            self.render_buffer();
+        }
+    }
+
+    /// Starts a reverse history search (Ctrl-R): typed characters narrow the search to
+    /// older entries containing them, Ctrl-R goes to the next older match, Enter runs the
+    /// match, Ctrl-G or Esc restores the line, any other key keeps the match for editing.
+    fn start_search(&mut self) {
+        self.search = Some(HistorySearch {
+            query: String::new(),
+            found: None,
+            failed: false,
+            saved: self.buffer.to_string(),
+        });
+        self.render_search();
+    }
+
+    /// Handles a key during a reverse history search. Returns the key to handle as
+    /// usual once the search ended with the match taken into the line.
+    fn handle_search_key(&mut self, key: Key) -> Option<Key> {
+        let search = self.search.as_mut()?;
+        match key {
+            Key::Char(ch) => {
+                if search.query.push(ch).is_err() {
+                    self.renderer.bell();
+                    return None;
+                }
+                // The current match may still do
+                let end = search.found.map_or(self.history.len(), |index| index + 1);
+                self.search_older(end);
+            }
+            Key::CtrlR => {
+                let end = search.found.unwrap_or(self.history.len());
+                if search.failed {
+                    self.renderer.bell();
+                } else {
+                    self.search_older(end);
+                }
+            }
+            Key::Backspace => {
+                search.query.pop();
+                search.failed = false;
+                if search.query.is_empty() {
+                    search.found = None;
+                    let saved = search.saved.clone();
+                    self.buffer.overwrite(&saved);
+                } else {
+                    self.search_older(self.history.len());
+                }
+            }
+            Key::CtrlG | Key::Escape => {
+                if let Some(search) = self.search.take() {
+                    self.buffer.overwrite(&search.saved);
+                }
+                self.render_buffer();
+                return None;
+            }
+            _ => {
+                self.search = None;
+                self.render_buffer();
+                return Some(key);
+            }
+        }
+        self.render_search();
+        None
+    }
+
+    /// Handles a key of pasted text (between `Key::PasteStart` and `Key::PasteEnd`).
+    /// Characters are inserted without redrawing, the line is drawn at the end of the
+    /// paste; a line ending is returned to be handled as Enter, other keys are dropped.
+    fn handle_paste_key(&mut self, key: Key) -> Option<Key> {
+        match key {
+            Key::PasteStart => self.pasting = true,
+            Key::Char(ch) => {
+                self.buffer.insert(ch);
+            }
+            Key::Enter => {
+                self.render_buffer();
+                return Some(Key::Enter);
+            }
+            Key::PasteEnd => {
+                self.pasting = false;
+                self.render_buffer();
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Shows the newest history entry before index `end` containing the query in the
+    /// input buffer, or marks the search failed.
+    fn search_older(&mut self, end: usize) {
+        let Some(search) = self.search.as_mut() else {
+            return;
+        };
+        let found = (0..end).rev().find_map(|index| {
+            let mut entry: String<IML> = String::new();
+            self.history
+                .for_each_byte(index, |byte| entry.push(byte as char).is_ok());
+            entry
+                .contains(search.query.as_str())
+                .then_some((index, entry))
+        });
+        match found {
+            Some((index, entry)) => {
+                search.found = Some(index);
+                search.failed = false;
+                self.buffer.overwrite(&entry);
+            }
+            None => {
+                search.failed = true;
+                self.renderer.bell();
+            }
+        }
+    }
+
+    fn render_search(&mut self) {
+        if let Some(search) = &self.search {
+            let line = self.buffer.to_string();
+            self.renderer
+                .render_search(&search.query, &line, search.failed);
+        }
+    }
+
+    /// Clears the entire input buffer and resets autocomplete state.
+    ///
+    /// - Clears the buffer content.
+    /// - Resets the autocomplete engine to an empty input.
     /// - Re-renders the prompt and empty buffer.
     ///
     pub fn handle_clear(&mut self) {
+        self.history_walk.reset();
         self.buffer.clear();
         // Empty input - no commands needed
         self.autocomplete.update_input("", |_| &[]);
@@ -499,198 +1577,2493 @@ impl<
     /// Processes the current input when the Enter key is pressed.
     ///
     /// Behavior:
-    /// - Commits the current buffer content to history (unless empty or starts with '#').
+    /// - Commits the current buffer content to history, unless it is empty, starts with
+    ///   '#' or a space, or is rejected by the history filter.
     /// - Clears the buffer.
     /// - Resets autocomplete state.
     /// - Returns the command string for execution.
     ///
     pub fn handle_enter(&mut self) -> String<IML> {
         let cmd = self.buffer.to_string();
-        if !cmd.is_empty() && !cmd.starts_with('#') {
+        if !cmd.is_empty()
+            && !cmd.starts_with('#')
+            && !cmd.starts_with(' ')
+            && !(self.skip_history)(cmd.as_str())
+        {
             self.history.push(cmd.as_str());
         }
+        self.history_walk.reset();
         self.buffer.clear();
         // Empty input - no commands needed
         self.autocomplete.update_input("", |_| &[]);
         cmd
     }
 
-    // =============== NEW GENERIC API (works for both hosted and embedded) ===============
+    /// Whether `cmd` may run: a built-in needing more than the session's privilege level
+    /// is refused with the error `dispatch` gives for a command.
+    fn builtin_allowed(&mut self, cmd: &str) -> bool {
+        let name = cmd.split_whitespace().next().unwrap_or("");
+        match (builtin_level(name), self.privilege_level) {
+            (Some(required), Some(level)) if required > level() => {
+                let mut line: String<40> = String::new();
+                let _ = write!(line, "Error: PermissionDenied(required={})\r\n", required);
+                self.renderer.writer_mut().write_str(&line);
+                false
+            }
+            _ => true,
+        }
+    }
 
-    /// Unified input parsing method that works for both hosted and embedded environments.
-    ///
-    /// This method provides a complete input processing loop with:
-    /// - Full line editing capabilities (arrow keys, home/end, delete, etc.)
-    /// - Autocompletion (Tab/Shift+Tab)
-    /// - Command history (Up/Down arrows)
-    /// - Hashtag command support (#q, ##, #h, #c, #N)
-    /// - Command execution via the provided callback
-    /// - Automatic history management
-    ///
-    /// # Parameters
-    /// - `read_key_fn`: Closure that returns the next key (or None if no key available)
-    /// - `write_output`: Closure for writing output strings (e.g., "\r\n")
-    /// - `exec_command`: Closure for executing parsed commands
-    ///
-    /// # Returns
-    /// - `true` if the shell should continue running
-    /// - `false` if the shell should exit (user typed "#q")
-    ///
-    /// # Hashtag Commands
-    /// Special commands starting with '#':
-    /// - `#q` - Quit/exit the shell
-    /// - `#` - List available commands
-    /// - `##` - List all (commands + shortcuts + arg types)
-    /// - `#l` - Show command history
-    /// - `#c` - Clear command history
-    /// - `#N` - Execute command from history at index N
-    ///
-    /// # Example (Embedded with UART)
-    /// ```no_run
-    /// let mut key_parser = AnsiKeyParser::new();
-    /// let mut pending_key = None;
-    ///
-    /// loop {
-    ///     let should_continue = parser.parse_input(
-    ///         || pending_key.take(),
-    ///         |s: &str| { uart_tx.blocking_write(s.as_bytes()); },
-    ///         |cmd: &String<256>| {
-    ///             // Your command execution logic
-    ///             execute_command(cmd);
-    ///         }
-    ///     );
-    ///     
-    ///     if !should_continue {
-    ///         break;
-    ///     }
-    ///     
-    ///     // Read next byte and parse key
-    ///     if let Ok(byte) = uart_rx.read() {
-    ///         if let Some(key) = key_parser.parse_byte(byte) {
-    ///             pending_key = Some(key);
-    ///         }
-    ///     }
-    /// }
-    /// ```
-    ///
-    /// # Example (Hosted/PC)
-    /// ```no_run
-    /// use crate::input::key_reader::platform::read_key;
-    ///
-    /// loop {
-    ///     if !parser.parse_input(
-    ///         || read_key().ok(),
-    ///         |s| print!("{}", s),
-    ///         |cmd| {
-    ///             println!("Executing: {}", cmd);
-    ///         }
-    ///     ) {
-    ///         println!("Goodbye!");
-    ///         break;
-    ///     }
-    /// }
-    /// ```
-    ///
-    pub fn parse_input<R, O, E>(
-        &mut self,
-        mut read_key_fn: R,
-        mut write_output: O,
-        exec_command: E,
-    ) -> bool
-    where
-        R: FnMut() -> Option<Key>,
-        O: FnMut(&str),
-        E: Fn(&String<IML>),
-    {
-        if let Some(key) = read_key_fn() {
-            match key {
-                Key::Char(ch) => {
-                    self.handle_char(ch);
-                }
-                Key::Backspace => {
-                    self.handle_backspace();
-                }
-                Key::Enter => {
-                    write_output("\r\n");
-                    let cmd = self.handle_enter();
-
-                    if !cmd.is_empty() {
-                        // Handle hashtag commands
-                        if let Some(stripped) = cmd.strip_prefix('#') {
-                            let (continue_running, maybe_history_command) =
-                                self.handle_hashtag(stripped);
-                            if !continue_running {
-                                let writer = self.renderer.writer_mut();
-                                writer.write_str("Shell exited...\n\r");
-                                return false;
-                            }
-                            if let Some(history_command) = maybe_history_command {
-                                exec_command(&history_command);
-                            }
-                        } else {
-                            // Regular command execution
-                            exec_command(&cmd);
+    /// Runs a shell built-in (`watch`, `format`, `shell debug`, ...). Returns `None` if `cmd` is not a
+    /// built-in, otherwise whether the prompt should be shown again.
+    fn run_builtin(&mut self, cmd: &str) -> Option<bool> {
+        let cmd = cmd.trim();
+        let (name, args) = cmd.split_once(' ').unwrap_or((cmd, ""));
+        match name {
+            "watch" => Some(!self.start_watch(args)),
+            "format" => {
+                let writer = self.renderer.writer_mut();
+                match args.trim() {
+                    "" => writer.write_str(match output::output_format() {
+                        OutputFormat::Text => "format: text\r\n",
+                        OutputFormat::Json => "format: json\r\n",
+                    }),
+                    "text" => output::set_output_format(OutputFormat::Text),
+                    "json" => output::set_output_format(OutputFormat::Json),
+                    _ => writer.write_str("usage: format text|json\r\n"),
+                }
+                Some(true)
+            }
+            "prompt" => {
+                self.set_prompt(args.trim());
+                Some(true)
+            }
+            "alias" => {
+                self.alias(args.trim());
+                Some(true)
+            }
+            "unalias" => {
+                let writer = self.renderer.writer_mut();
+                match args.trim() {
+                    "" => writer.write_str("usage: unalias <name>\r\n"),
+                    name => {
+                        if let Err(e) = self.aliases.remove(name) {
+                            writer.write_str(e.message());
+                            writer.write_str("\r\n");
                         }
                     }
-                    self.render_buffer();
-                }
-                Key::Tab => {
-                    self.handle_tab(false);
-                }
-                Key::ShiftTab => {
-                    self.handle_tab(true);
-                }
-                Key::CtrlN => {
-                    // Ctrl+N as alternative to Tab
-                    self.handle_tab(false);
-                }
-                Key::CtrlP => {
-                    // Ctrl+P as alternative to Shift+Tab
-                    self.handle_tab(true);
-                }
-                Key::ArrowUp => {
-                    self.handle_up();
-                }
-                Key::ArrowDown => {
-                    self.handle_down();
-                }
-                Key::ArrowLeft => {
-                    self.handle_left();
                 }
-                Key::ArrowRight => {
-                    self.handle_right();
-                }
-                Key::Home => {
-                    self.handle_home();
-                }
-                Key::End => {
-                    self.handle_end();
+                Some(true)
+            }
+            "term" => {
+                self.term(args.trim());
+                Some(true)
+            }
+            "theme" => {
+                self.theme(args.trim());
+                Some(true)
+            }
+            "crc16" | "crc32" | "sum8" => {
+                let checksum = Checksum::find(name)?;
+                let mut line: String<64> = String::new();
+                let _ = match checksum.of_hex(args) {
+                    Ok((value, count)) => write!(
+                        line,
+                        "{}: {:#0width$X} ({} bytes)\r\n",
+                        name,
+                        value,
+                        count,
+                        width = checksum.digits() + 2
+                    ),
+                    Err(e) => write!(line, "usage: {} <hex bytes> ({})\r\n", name, e.message()),
+                };
+                self.renderer.writer_mut().write_str(&line);
+                Some(true)
+            }
+            "history" => {
+                match args.trim() {
+                    "" => self.write_history(None),
+                    "-t" => self.write_history(Some(TimeFormat::Relative)),
+                    "-T" => self.write_history(Some(TimeFormat::Absolute)),
+                    _ => self
+                        .renderer
+                        .writer_mut()
+                        .write_str("usage: history [-t|-T]\r\n"),
                 }
-                Key::Delete => {
-                    self.handle_delete();
+                Some(true)
+            }
+            "describe" => {
+                self.describe();
+                Some(true)
+            }
+            "apropos" => {
+                self.apropos(args.trim());
+                Some(true)
+            }
+            "log" => {
+                self.log(args.trim());
+                Some(true)
+            }
+            "loglevel" => {
+                self.loglevel(args.trim());
+                Some(true)
+            }
+            "logcolor" => {
+                let writer = self.renderer.writer_mut();
+                match args.trim() {
+                    "" => writer.write_str(match logger::color_entire_line() {
+                        true => "logcolor: on\r\n",
+                        false => "logcolor: off\r\n",
+                    }),
+                    "on" => logger::set_color_entire_line(true),
+                    "off" => logger::set_color_entire_line(false),
+                    _ => writer.write_str("usage: logcolor [on|off]\r\n"),
                 }
-                Key::CtrlU => {
-                    // Delete from cursor to beginning of line
-                    self.buffer.delete_to_start();
-                    self.render_buffer();
+                Some(true)
+            }
+            "storage" => {
+                self.storage(args.trim());
+                Some(true)
+            }
+            #[cfg(feature = "mem-tools")]
+            "md" | "mw8" | "mw16" | "mw32" | "mfill" => {
+                let writer = self.renderer.writer_mut();
+                if let Err(e) = memtools::run(name, args, self.mem_access, writer) {
+                    writer.write_str(e.message());
+                    writer.write_str("\r\n");
                 }
-                Key::CtrlK => {
-                    // Delete from cursor to end of line
-                    self.buffer.delete_to_end();
-                    self.render_buffer();
+                Some(true)
+            }
+            "shell" => {
+                match args.trim() {
+                    "debug" => Self::write_state(
+                        &self.buffer,
+                        &self.history,
+                        &self.autocomplete,
+                        self.pending_input.as_ref().map(|(pending, _)| pending),
+                        self.watch.is_some(),
+                        self.renderer.writer_mut(),
+                    ),
+                    _ => self
+                        .renderer
+                        .writer_mut()
+                        .write_str("usage: shell debug\r\n"),
                 }
-                Key::CtrlD => {
-                    if !self.buffer.is_empty() {
-                        self.buffer.clear();
-                        self.render_buffer();
-                    }
+                Some(true)
+            }
+            // A group alone lists its subcommands, as `## gpio` does
+            _ if args.is_empty() && self.is_group(name) => {
+                let mut which: String<IML> = String::new();
+                let _ = write!(which, "# {}", name);
+                self.show_help(&which);
+                Some(self.paging.is_none())
+            }
+            _ => None,
+        }
+    }
+
+    /// The `prompt` built-in: shows the prompt, or sets it (`prompt "dev> "`; `prompt ""`
+    /// restores the default).
+    fn set_prompt(&mut self, arg: &str) {
+        let writer = self.renderer.writer_mut();
+        if arg.is_empty() {
+            let custom = prompt::custom_prompt();
+            writer.write_str("prompt: \"");
+            writer.write_str(custom.as_deref().unwrap_or(self.prompt));
+            writer.write_str("\"\r\n");
+            return;
+        }
+        let template = arg
+            .strip_prefix('"')
+            .and_then(|quoted| quoted.strip_suffix('"'))
+            .unwrap_or(arg);
+        if let Err(e) = prompt::set_prompt(template) {
+            writer.write_str(e.message());
+            writer.write_str("\r\n");
+        }
+    }
+
+    /// The `alias` built-in: `alias` lists the aliases, `alias <name>` shows one and
+    /// `alias <name> "command"` (or unquoted, `alias <name> command...`) defines one
+    /// (see `crate::alias`).
+    fn alias(&mut self, args: &str) {
+        let writer = self.renderer.writer_mut();
+        let (name, command) = args.split_once(' ').unwrap_or((args, ""));
+        let command = command.trim();
+        if name.is_empty() {
+            for (name, command) in self.aliases.iter() {
+                writer.write_str(name);
+                writer.write_str(" = \"");
+                writer.write_str(command);
+                writer.write_str("\"\r\n");
+            }
+        } else if command.is_empty() {
+            match self.aliases.get(name) {
+                Some(command) => {
+                    writer.write_str(name);
+                    writer.write_str(" = \"");
+                    writer.write_str(command);
+                    writer.write_str("\"\r\n");
                 }
-                // Ignore keys we don't handle
-                Key::Insert | Key::PageUp | Key::PageDown => {
-                    // Ignore these keys
+                None => writer.write_str("alias: no such alias\r\n"),
+            }
+        } else {
+            let command = command
+                .strip_prefix('"')
+                .and_then(|quoted| quoted.strip_suffix('"'))
+                .unwrap_or(command);
+            if let Err(e) = self.aliases.define(name, command) {
+                writer.write_str(e.message());
+                writer.write_str("\r\n");
+            }
+        }
+        writer.flush();
+    }
+
+    /// Replaces a leading alias in `cmd` by its command (see `crate::alias`). Returns
+    /// `None`, after reporting why, if the expanded line does not fit.
+    fn expand_alias(&mut self, cmd: String<IML>) -> Option<String<IML>> {
+        match self.aliases.expand(&cmd) {
+            Ok(expanded) => Some(expanded.unwrap_or(cmd)),
+            Err(e) => {
+                let writer = self.renderer.writer_mut();
+                writer.write_str(e.message());
+                writer.write_str("\r\n");
+                None
+            }
+        }
+    }
+
+    /// The `describe` built-in: one JSON object per line and command, for tools that
+    /// build their UI from the command table:
+    ///
+    /// ```text
+    /// {"name":"led","descriptor":"Bt","arity":2,"help":"set a LED","group":null,"privilege":0}
+    /// ```
+    ///
+    /// `group` is `null` for commands outside a group; `privilege` is the level the
+    /// command needs (`desc@level`, 0 without one).
+    fn describe(&mut self) {
+        let writer = self.renderer.writer_mut();
+        let Some(describe) = self.describe else {
+            writer.write_str("describe: not available\r\n");
+            return;
+        };
+        let mut index = 0;
+        while let Some((name, descriptor, arity, help, level)) = describe(index) {
+            let mut json = JsonWriter::new(writer);
+            json.begin_object();
+            json.key("name").str(name);
+            json.key("descriptor").str(descriptor);
+            json.key("arity").u64(arity as u64);
+            json.key("help").str(help);
+            match command_group(name) {
+                Some(group) => json.key("group").str(group),
+                None => json.key("group").null(),
+            };
+            json.key("privilege").u64(level as u64);
+            json.end_object();
+            writer.write_str("\r\n");
+            index += 1;
+        }
+        writer.flush();
+    }
+
+    /// Dumps the editor state (input line, cursor, history, autocomplete, pending
+    /// request) for diagnosing a shell that seems stuck; also the `shell debug` built-in.
+    ///
+    /// ```text
+    /// input: "led 1" (5 chars, cursor 3)
+    /// history: 4 entries, 120 bytes free
+    /// autocomplete: "le", 2 candidates
+    /// pending: none
+    /// watch: off, stream: off
+    /// ```
+    pub fn debug_state(&self, writer: &mut dyn UnifiedWriter) {
+        Self::write_state(
+            &self.buffer,
+            &self.history,
+            &self.autocomplete,
+            self.pending_input.as_ref().map(|(pending, _)| pending),
+            self.watch.is_some(),
+            writer,
+        );
+    }
+
+    /// Takes the parts separately so `shell debug` can write through the renderer.
+    fn write_state<X: UnifiedWriter + ?Sized>(
+        buffer: &InputBuffer<IML>,
+        history: &History<HTC>,
+        autocomplete: &Autocomplete<'a, NAC, FNL>,
+        pending: Option<&Pending>,
+        watching: bool,
+        writer: &mut X,
+    ) {
+        let mut utf8 = [0u8; 4];
+        writer.write_str("input: \"");
+        for ch in buffer.chars() {
+            writer.write_str(ch.encode_utf8(&mut utf8));
+        }
+        writer.write_str("\" (");
+        Self::write_number(writer, buffer.len());
+        writer.write_str(" chars, cursor ");
+        Self::write_number(writer, buffer.cursor());
+        writer.write_str(")\r\nhistory: ");
+        Self::write_number(writer, history.len());
+        writer.write_str(" entries, ");
+        Self::write_number(writer, history.get_free_space());
+        writer.write_str(" bytes free\r\nautocomplete: \"");
+        writer.write_str(autocomplete.current_input());
+        writer.write_str("\", ");
+        Self::write_number(writer, autocomplete.filtered_candidates().len());
+        writer.write_str(" candidates\r\npending: ");
+        writer.write_str(match pending {
+            None => "none",
+            Some(Pending::Confirm(_)) => "confirm",
+            Some(Pending::HiddenLine(_)) => "hidden line",
+        });
+        writer.write_str(if watching {
+            "\r\nwatch: on"
+        } else {
+            "\r\nwatch: off"
+        });
+        writer.write_str(if stream::is_active() {
+            ", stream: on\r\n"
+        } else {
+            ", stream: off\r\n"
+        });
+        writer.flush();
+    }
+
+    /// The `apropos` built-in: lists the commands whose name, descriptor or help text
+    /// contains `keyword`, ignoring ASCII case.
+    ///
+    /// ```text
+    /// >> apropos led
+    /// led Bt - set a LED
+    /// ```
+    fn apropos(&mut self, keyword: &str) {
+        let writer = self.renderer.writer_mut();
+        let Some(describe) = self.describe else {
+            writer.write_str("apropos: not available\r\n");
+            return;
+        };
+        if keyword.is_empty() {
+            writer.write_str("usage: apropos <keyword>\r\n");
+            return;
+        }
+        let mut index = 0;
+        let mut found = false;
+        while let Some((name, descriptor, _, help, _)) = describe(index) {
+            index += 1;
+            if ![name, descriptor, help]
+                .iter()
+                .any(|field| contains_ignore_case(field, keyword))
+            {
+                continue;
+            }
+            found = true;
+            writer.write_str(name);
+            writer.write_str(" ");
+            writer.write_str(descriptor);
+            if !help.is_empty() {
+                writer.write_str(" - ");
+                writer.write_str(help);
+            }
+            writer.write_str("\r\n");
+        }
+        if !found {
+            writer.write_str("apropos: nothing matches \"");
+            writer.write_str(keyword);
+            writer.write_str("\"\r\n");
+        }
+        writer.flush();
+    }
+
+    /// The `log` built-in over the lines kept by `logger::attach_log_ring`:
+    /// `log show [n] [level]` lists the last `n` of them (all by default), only those
+    /// at least as severe as `level` if given; `log clear` forgets them.
+    ///
+    /// ```text
+    /// >> log show 2 warn
+    /// [ WARN] low voltage
+    /// [ERROR] sensor timeout
+    /// ```
+    fn log(&mut self, args: &str) {
+        let writer = self.renderer.writer_mut();
+        let (verb, args) = args.split_once(' ').unwrap_or((args, ""));
+        let mut count = usize::MAX;
+        let mut min_level = None;
+        let mut valid = matches!(verb, "show" | "clear");
+        for arg in args.split_whitespace() {
+            if let Ok(n) = arg.parse() {
+                count = n;
+            } else if let Some(level) = LogLevel::from_name(arg) {
+                min_level = Some(level);
+            } else {
+                valid = false;
+            }
+        }
+        if !valid || (verb == "clear" && !args.is_empty()) {
+            writer.write_str("usage: log show [n] [level] | log clear\r\n");
+            return;
+        }
+        let shown = logger::with_log_ring(|ring| {
+            if verb == "clear" {
+                ring.clear();
+                return true;
+            }
+            let selected = |&(level, _): &(Option<LogLevel>, &str)| match (level, min_level) {
+                (_, None) => true,
+                (Some(level), Some(min_level)) => level.is_enabled(min_level),
+                (None, Some(_)) => false,
+            };
+            let matching = ring.iter().filter(selected).count();
+            for (level, text) in ring
+                .iter()
+                .filter(selected)
+                .skip(matching.saturating_sub(count))
+            {
+                if let Some(level) = level {
+                    writer.write_str("[");
+                    writer.write_str(level.label());
+                    writer.write_str("] ");
                 }
+                writer.write_str(text);
+                writer.write_str("\r\n");
             }
+            matching > 0
+        });
+        match shown {
+            None => writer.write_str("log: not available\r\n"),
+            Some(false) => writer.write_str("log: empty\r\n"),
+            Some(true) => {}
         }
-        true
+        writer.flush();
+    }
+
+    /// The `loglevel` built-in: `loglevel` shows the logger's minimum level and the
+    /// target filters, `loglevel <level>` sets the minimum level and
+    /// `loglevel <level> <target>` the level of a target (see
+    /// `logger::set_target_level`); `loglevel default <target>` removes its filter.
+    ///
+    /// ```text
+    /// >> loglevel warn led
+    /// >> loglevel
+    /// loglevel: debug
+    ///   led: warn
+    /// ```
+    fn loglevel(&mut self, args: &str) {
+        let writer = self.renderer.writer_mut();
+        let mut args = args.split_whitespace();
+        let (level, target, extra) = (args.next(), args.next(), args.next());
+        let valid = match (level, target, extra) {
+            (None, _, _) => {
+                writer.write_str("loglevel: ");
+                writer.write_str(logger::min_level().name());
+                writer.write_str("\r\n");
+                logger::for_each_target_level(|prefix, level| {
+                    writer.write_str("  ");
+                    writer.write_str(prefix);
+                    writer.write_str(": ");
+                    writer.write_str(level.name());
+                    writer.write_str("\r\n");
+                });
+                true
+            }
+            (Some("default"), Some(target), None) => {
+                logger::clear_target_level(target);
+                true
+            }
+            (Some(level), target, None) => match (LogLevel::from_name(level), target) {
+                (Some(level), None) => {
+                    logger::set_min_level(level);
+                    true
+                }
+                (Some(level), Some(target)) => {
+                    if let Err(e) = logger::set_target_level(target, level) {
+                        writer.write_str("loglevel: ");
+                        writer.write_str(e.message());
+                        writer.write_str("\r\n");
+                    }
+                    true
+                }
+                (None, _) => false,
+            },
+            _ => false,
+        };
+        if !valid {
+            writer.write_str(
+                "usage: loglevel [error|warn|info|debug|verbose|trace [target]|default <target>]\r\n",
+            );
+        }
+        writer.flush();
+    }
+
+    /// The `storage` built-in: `storage [info]`, `storage mount` or `storage format`
+    /// on the storage passed to `set_storage_control`.
+    ///
+    /// ```text
+    /// >> storage
+    /// storage: 12 records, 188/1024 bytes, generation 3
+    /// ```
+    fn storage(&mut self, args: &str) {
+        let writer = self.renderer.writer_mut();
+        let Some(control) = self.storage else {
+            writer.write_str("storage: not available\r\n");
+            return;
+        };
+        let op = match args {
+            "" | "info" => StorageOp::Info,
+            "mount" => StorageOp::Mount,
+            "format" => StorageOp::Format,
+            _ => {
+                writer.write_str("usage: storage [info|mount|format]\r\n");
+                return;
+            }
+        };
+        match control(op) {
+            Ok(info) => {
+                writer.write_str("storage: ");
+                Self::write_number(writer, info.records);
+                writer.write_str(" records, ");
+                Self::write_number(writer, info.used);
+                writer.write_str("/");
+                Self::write_number(writer, info.capacity);
+                writer.write_str(" bytes, generation ");
+                Self::write_number(writer, info.generation as usize);
+                writer.write_str("\r\n");
+            }
+            Err(error) => {
+                writer.write_str("storage: ");
+                writer.write_str(error.message());
+                writer.write_str("\r\n");
+            }
+        }
+        writer.flush();
+    }
+
+    /// The `term` built-in: `term keymap [name]` shows or selects the keymap preset
+    /// (see `crate::input::keymap`); `term size [<cols> <rows>|probe]` shows, sets or
+    /// asks the terminal for its size (see `set_terminal_size`).
+    fn term(&mut self, args: &str) {
+        let mut words = args.split_whitespace();
+        let first = words.next();
+        if first == Some("size") {
+            self.term_size(words.next(), words.next(), words.next());
+            return;
+        }
+        let writer = self.renderer.writer_mut();
+        let (Some("keymap"), name, None) = (first, words.next(), words.next()) else {
+            writer.write_str("usage: term keymap [");
+            for (i, preset) in keymap::PRESETS.iter().enumerate() {
+                if i > 0 {
+                    writer.write_str("|");
+                }
+                writer.write_str(preset.name);
+            }
+            writer.write_str("]\r\n");
+            writer.write_str(TERM_SIZE_USAGE);
+            return;
+        };
+        match name {
+            None => {
+                writer.write_str("keymap: ");
+                writer.write_str(keymap::keymap().name);
+                writer.write_str("\r\n");
+            }
+            Some(name) => match keymap::find(name) {
+                Some(preset) => keymap::set_keymap(preset),
+                None => writer.write_str("term: unknown keymap\r\n"),
+            },
+        }
+    }
+
+    /// The `theme` built-in: `theme [name]` shows or selects the color theme (see
+    /// `crate::theme`).
+    fn theme(&mut self, args: &str) {
+        if let Some(preset) = theme::find(args) {
+            self.set_theme(preset);
+            return;
+        }
+        let current = self.renderer.theme().name;
+        let writer = self.renderer.writer_mut();
+        match args {
+            "" => {
+                writer.write_str("theme: ");
+                writer.write_str(current);
+                writer.write_str("\r\n");
+            }
+            _ => {
+                writer.write_str("usage: theme [");
+                for (i, preset) in theme::PRESETS.iter().enumerate() {
+                    if i > 0 {
+                        writer.write_str("|");
+                    }
+                    writer.write_str(preset.name);
+                }
+                writer.write_str("]\r\n");
+            }
+        }
+    }
+
+    /// `term size`: shows the terminal size, sets it (`<cols> <rows>`) or probes it.
+    fn term_size(&mut self, first: Option<&str>, second: Option<&str>, extra: Option<&str>) {
+        match (first, second, extra) {
+            (None, _, _) => {
+                let (columns, rows) = (self.renderer.columns(), self.renderer.page_rows());
+                let writer = self.renderer.writer_mut();
+                writer.write_str("size: ");
+                match columns {
+                    Some(columns) => Self::write_number(writer, columns as usize),
+                    None => writer.write_str("?"),
+                }
+                writer.write_str("x");
+                match rows {
+                    Some(rows) => Self::write_number(writer, rows as usize),
+                    None => writer.write_str("?"),
+                }
+                writer.write_str("\r\n");
+            }
+            (Some("probe"), None, _) => self.probe_terminal_size(),
+            (Some(columns), Some(rows), None) => match (columns.parse(), rows.parse()) {
+                (Ok(columns @ 1..), Ok(rows @ 1..)) => self.set_terminal_size(columns, rows),
+                _ => self
+                    .renderer
+                    .writer_mut()
+                    .write_str("term: invalid size\r\n"),
+            },
+            _ => self.renderer.writer_mut().write_str(TERM_SIZE_USAGE),
+        }
+    }
+
+    /// Starts the `watch` built-in (see `crate::watch`); returns `true` if it is running.
+    fn start_watch(&mut self, args: &str) -> bool {
+        let writer = self.renderer.writer_mut();
+        if self.clock.is_none() {
+            writer.write_str("watch: no clock configured\r\n");
+            return false;
+        }
+        match MemWatch::parse(args) {
+            Ok(watch) => {
+                writer.write_str("(press any key to stop)\r\n");
+                self.watch = Some(watch);
+                true
+            }
+            Err(e) => {
+                writer.write_str(e.message());
+                writer.write_str("\r\n");
+                false
+            }
+        }
+    }
+
+    /// Runs a command line through `exec_command`.
+    ///
+    /// Returns `true` if the prompt should be shown again, `false` if the command
+    /// asked for input (see `interact`) and the parser switched to collecting it, or
+    /// started a stream (see `crate::stream`).
+    ///
+    fn run_command<E, X>(&mut self, cmd: &String<IML>, exec_command: &E) -> bool
+    where
+        E: Fn(&String<IML>) -> X,
+        X: Into<ExecOutcome>,
+    {
+        match exec_command(cmd).into() {
+            ExecOutcome::Done if stream::is_active() => {
+                let writer = self.renderer.writer_mut();
+                writer.write_str("(Space pauses, q stops)\r\n");
+                false
+            }
+            ExecOutcome::Done => true,
+            ExecOutcome::NeedsInput(pending) => {
+                let writer = self.renderer.writer_mut();
+                match pending {
+                    Pending::Confirm(prompt) => {
+                        if !prompt.is_empty() {
+                            writer.write_str(prompt);
+                            writer.write_str(" ");
+                        }
+                        writer.write_str("Are you sure? [y/N] ");
+                    }
+                    Pending::HiddenLine(prompt) => {
+                        interact::hidden_wipe();
+                        writer.write_str(prompt);
+                    }
+                }
+                writer.flush();
+                self.pending_input = Some((pending, cmd.clone()));
+                false
+            }
+        }
+    }
+
+    /// Handles a key while a command waits for input, then runs the command again.
+    ///
+    /// - Confirmation: `y`/`Y` answers yes, any other key answers no.
+    /// - Hidden line: characters are echoed as `*`, Backspace deletes, Enter completes
+    ///   the line, Ctrl-D cancels (the command is not run again). Other keys are ignored.
+    ///
+    fn handle_pending_key<E, X>(&mut self, key: Key, exec_command: &E)
+    where
+        E: Fn(&String<IML>) -> X,
+        X: Into<ExecOutcome>,
+    {
+        let Some((pending, cmd)) = self.pending_input.take() else {
+            return;
+        };
+        match pending {
+            Pending::Confirm(_) => {
+                let yes = matches!(key, Key::Char('y') | Key::Char('Y'));
+                self.renderer
+                    .writer_mut()
+                    .write_str(if yes { "y\r\n" } else { "n\r\n" });
+                interact::answer_confirm(yes);
+            }
+            Pending::HiddenLine(_) => {
+                let writer = self.renderer.writer_mut();
+                match key {
+                    Key::Enter => {
+                        writer.write_str("\r\n");
+                        interact::hidden_finish();
+                    }
+                    Key::CtrlD => {
+                        interact::hidden_wipe();
+                        writer.write_str("\r\nCancelled.\r\n");
+                        self.render_buffer();
+                        return;
+                    }
+                    Key::Char(ch) => {
+                        if interact::hidden_push(ch) {
+                            writer.write_str("*");
+                        } else {
+                            self.renderer.bell();
+                        }
+                        self.pending_input = Some((pending, cmd));
+                        return;
+                    }
+                    Key::Backspace => {
+                        if interact::hidden_pop() {
+                            writer.write_str("\x08 \x08");
+                        }
+                        self.pending_input = Some((pending, cmd));
+                        return;
+                    }
+                    _ => {
+                        self.pending_input = Some((pending, cmd));
+                        return;
+                    }
+                }
+            }
+        }
+        if self.run_command(&cmd, exec_command) {
+            self.render_buffer();
+        }
+    }
+
+    // =============== NEW GENERIC API (works for both hosted and embedded) ===============
+
+    /// Unified input parsing method that works for both hosted and embedded environments.
+    ///
+    /// This method provides a complete input processing loop with:
+    /// - Full line editing capabilities (arrow keys, home/end, delete, etc.)
+    /// - Autocompletion (Tab/Shift+Tab)
+    /// - Command history (Up/Down arrows)
+    /// - Hashtag command support (#q, ##, #h, #c, #N)
+    /// - Built-ins: `watch mem <addr> <len> <interval_ms>` (needs `set_clock`),
+    ///   `format text|json` (output format of the built-in listings),
+    ///   `prompt ["text"]` (see `crate::prompt`), `term keymap [name]`
+    ///   (see `crate::input::keymap`), `term size [<cols> <rows>|probe]`,
+    ///   `theme [name]` (see `crate::theme`), `alias`/`unalias` (see `crate::alias`) and
+    ///   `history [-t|-T]` (the history with the age or time of each line, see
+    ///   `set_history_clock`), `loglevel [<level> [target]]` and `logcolor [on|off]`
+    ///   (the logger's levels and line coloring, see `crate::logger`), `crc16`, `crc32`
+    ///   and `sum8 <hex bytes>` (see `crate::checksum`); with the
+    ///   `mem-tools` feature `md`, `mw8`/`mw16`/`mw32` and `mfill` (see `crate::memtools`)
+    /// - Command execution via the provided callback
+    /// - Automatic history management
+    ///
+    /// # Parameters
+    /// - `read_key_fn`: Closure that returns the next key (or None if no key available)
+    /// - `write_output`: Closure for writing output strings (e.g., "\r\n")
+    /// - `exec_command`: Closure for executing parsed commands; returns `()` or an `ExecOutcome`
+    ///   (`ExecOutcome::NeedsInput` makes the parser collect the input and run the line again)
+    ///
+    /// # Returns
+    /// - `true` if the shell should continue running
+    /// - `false` if the shell should exit (user typed "#q")
+    ///
+    /// # Hashtag Commands
+    /// Special commands starting with '#':
+    /// - `#q` - Quit/exit the shell
+    /// - `#` - List available commands
+    /// - `##` - List all (commands + shortcuts + arg types)
+    /// - `## <group>` - List the subcommands of a group
+    /// - `#l` - Show command history
+    /// - `#c` - Clear command history
+    /// - `#N` - Execute command from history at index N
+    ///
+    /// # Example (Embedded with UART)
+    /// ```no_run
+    /// let mut key_parser = AnsiKeyParser::new();
+    /// let mut pending_key = None;
+    ///
+    /// loop {
+    ///     let should_continue = parser.parse_input(
+    ///         || pending_key.take(),
+    ///         |s: &str| { uart_tx.blocking_write(s.as_bytes()); },
+    ///         |cmd: &String<256>| {
+    ///             // Your command execution logic
+    ///             execute_command(cmd);
+    ///         }
+    ///     );
+    ///     
+    ///     if !should_continue {
+    ///         break;
+    ///     }
+    ///     
+    ///     // Read next byte and parse key
+    ///     if let Ok(byte) = uart_rx.read() {
+    ///         if let Some(key) = key_parser.parse_byte(byte) {
+    ///             pending_key = Some(key);
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// # Example (Hosted/PC)
+    /// ```no_run
+    /// use crate::input::key_reader::platform::read_key;
+    ///
+    /// loop {
+    ///     if !parser.parse_input(
+    ///         || read_key().ok(),
+    ///         |s| print!("{}", s),
+    ///         |cmd| {
+    ///             println!("Executing: {}", cmd);
+    ///         }
+    ///     ) {
+    ///         println!("Goodbye!");
+    ///         break;
+    ///     }
+    /// }
+    /// ```
+    ///
+    pub fn parse_input<R, O, E, X>(
+        &mut self,
+        mut read_key_fn: R,
+        mut write_output: O,
+        exec_command: E,
+    ) -> bool
+    where
+        R: FnMut() -> Option<Key>,
+        O: FnMut(&str),
+        E: Fn(&String<IML>) -> X,
+        X: Into<ExecOutcome>,
+    {
+        if let Some(watch) = self.watch.as_mut() {
+            if read_key_fn().is_some() {
+                // Any key stops the watch
+                self.watch = None;
+                self.render_buffer();
+            } else if let Some(now_ms) = self.clock {
+                watch.poll(now_ms(), self.renderer.writer_mut());
+            }
+            return true;
+        }
+
+        if stream::is_active() {
+            self.route_to_stream(read_key_fn());
+            return true;
+        }
+
+        if self.paging.is_some() {
+            if let Some(key) = read_key_fn() {
+                self.handle_pager_key(key);
+            }
+            return true;
+        }
+
+        self.update_busy_marker();
+        if self.pending_input.is_none() {
+            self.print_deferred_logs();
+            self.print_notices();
+            if renderer::take_progress_cleared() {
+                self.render_buffer();
+            }
+        }
+
+        if let Some(key) = read_key_fn() {
+            if let Key::Mouse {
+                button,
+                x,
+                y,
+                press,
+            } = key
+            {
+                if let Some(mouse) = self.mouse {
+                    mouse(button, x, y, press);
+                }
+                return true;
+            }
+            if let Key::CursorReport { row, col } = key {
+                if core::mem::take(&mut self.probing_size) {
+                    self.set_terminal_size(col, row);
+                    self.render_buffer();
+                }
+                return true;
+            }
+            if self.pending_input.is_some() {
+                self.handle_pending_key(key, &exec_command);
+                return true;
+            }
+            let key = match self.search {
+                Some(_) => match self.handle_search_key(key) {
+                    Some(key) => key,
+                    None => return true,
+                },
+                None => key,
+            };
+            let key = if self.pasting || key == Key::PasteStart {
+                match self.handle_paste_key(key) {
+                    Some(key) => key,
+                    None => return true,
+                }
+            } else {
+                key
+            };
+            if self.snippet_key == Some(key) {
+                self.handle_snippet();
+                return true;
+            }
+            match self.bindings.action(key, self.normal_mode) {
+                Some(action) => return self.run_action(action, &mut write_output, &exec_command),
+                None => match key {
+                    Key::Char(ch) if !self.normal_mode => self.handle_char(ch),
+                    Key::Char(_) => self.renderer.bell(),
+                    // Unbound keys are ignored
+                    _ => {}
+                },
+            }
+        }
+        true
+    }
+
+    /// Runs the action a key is bound to. Returns `false` if the shell should exit.
+    fn run_action<O, E, X>(
+        &mut self,
+        action: Action,
+        write_output: &mut O,
+        exec_command: &E,
+    ) -> bool
+    where
+        O: FnMut(&str),
+        E: Fn(&String<IML>) -> X,
+        X: Into<ExecOutcome>,
+    {
+        if !matches!(
+            action,
+            Action::HistoryPrev | Action::HistoryNext | Action::Complete
+        ) {
+            self.history_walk.reset();
+        }
+        match action {
+            Action::Submit => {
+                self.normal_mode = false;
+                // The hint is not kept in the scrollback
+                if self.shown_hint {
+                    self.render_line("");
+                }
+                write_output("\r\n");
+                let cmd = self.handle_enter();
+                let mut show_prompt = true;
+
+                if !cmd.is_empty() {
+                    // Handle hashtag commands
+                    if let Some(stripped) = cmd.strip_prefix('#') {
+                        let (continue_running, maybe_history_command) =
+                            self.handle_hashtag(stripped);
+                        if !continue_running {
+                            let writer = self.renderer.writer_mut();
+                            writer.write_str("Shell exited...\n\r");
+                            return false;
+                        }
+                        if let Some(history_command) = maybe_history_command
+                            .and_then(|cmd| self.expand_alias(cmd))
+                            .filter(|cmd| self.builtin_allowed(cmd))
+                        {
+                            show_prompt = self.run_command(&history_command, exec_command);
+                        }
+                        // The prompt follows the last page
+                        show_prompt &= self.paging.is_none();
+                    } else if let Some(cmd) = self
+                        .expand_alias(cmd)
+                        .filter(|cmd| self.builtin_allowed(cmd))
+                    {
+                        if let Some(show) = self.run_builtin(&cmd) {
+                            show_prompt = show;
+                        } else {
+                            // Regular command execution
+                            show_prompt = self.run_command(&cmd, exec_command);
+                        }
+                    }
+                }
+                if show_prompt {
+                    self.render_buffer();
+                }
+            }
+            Action::Complete => self.handle_tab(false),
+            Action::CompleteBack => self.handle_tab(true),
+            Action::HistoryPrev => self.handle_up(),
+            Action::HistoryNext => self.handle_down(),
+            Action::CursorLeft => self.handle_left(),
+            Action::CursorRight => self.handle_right(),
+            Action::LineStart => self.handle_home(),
+            Action::LineEnd => self.handle_end(),
+            Action::DeleteBack => self.handle_backspace(),
+            Action::DeleteChar => self.handle_delete(),
+            Action::DeleteWordBack => self.handle_delete_word(),
+            Action::KillToStart => {
+                self.buffer.delete_to_start();
+                self.render_buffer();
+            }
+            Action::KillToEnd => {
+                self.buffer.delete_to_end();
+                self.render_buffer();
+            }
+            Action::Transpose => {
+                if self.buffer.transpose() {
+                    self.render_buffer();
+                } else {
+                    self.renderer.bell();
+                }
+            }
+            Action::ClearLine => {
+                if !self.buffer.is_empty() {
+                    self.buffer.clear();
+                    self.render_buffer();
+                }
+            }
+            Action::Search => self.start_search(),
+            Action::Ignore => {}
+            Action::NormalMode => {
+                // Like vi, the cursor steps back onto the last typed character
+                self.normal_mode = true;
+                self.handle_left();
+            }
+            Action::Insert => self.normal_mode = false,
+            Action::InsertAfter => {
+                self.normal_mode = false;
+                self.handle_right();
+            }
+            Action::InsertAtStart => {
+                self.normal_mode = false;
+                self.handle_home();
+            }
+            Action::InsertAtEnd => {
+                self.normal_mode = false;
+                self.handle_end();
+            }
+        }
+        true
+    }
+}
+
+/// Case-insensitive (ASCII) substring test; an empty `needle` matches everything.
+pub fn contains_ignore_case(haystack: &str, needle: &str) -> bool {
+    let (haystack, needle) = (haystack.as_bytes(), needle.as_bytes());
+    needle.is_empty()
+        || haystack
+            .windows(needle.len())
+            .any(|window| window.eq_ignore_ascii_case(needle))
+}
+
+/// The group of a subcommand name (`gpio` of `gpio set`), `None` for a plain command.
+pub fn command_group(name: &str) -> Option<&str> {
+    name.split_once(' ').map(|(group, _)| group)
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+
+    type TestParser = InputParser<'static, String<512>, 4, 8, 32, 64>;
+
+    fn enter_line(parser: &mut TestParser, line: &str) {
+        for ch in line.chars() {
+            parser.handle_char(ch);
+        }
+        parser.handle_enter();
+    }
+
+    type Guard = std::sync::MutexGuard<'static, ()>;
+
+    /// Polling the parser runs streams and prints queued notices; tests starting
+    /// streams or queueing notices hold these locks.
+    fn globals_lock() -> (Guard, Guard) {
+        let stream = stream::TEST_LOCK.lock();
+        let notices = notice::TEST_LOCK.lock();
+        (
+            stream.unwrap_or_else(|poisoned| poisoned.into_inner()),
+            notices.unwrap_or_else(|poisoned| poisoned.into_inner()),
+        )
+    }
+
+    fn poll(parser: &mut TestParser, key: Option<Key>) {
+        let _globals = globals_lock();
+        poll_locked(parser, key);
+    }
+
+    fn poll_locked(parser: &mut TestParser, key: Option<Key>) {
+        let mut next = key;
+        parser.parse_input(|| next.take(), |_| {}, |_| {});
+    }
+
+    #[cfg(not(feature = "no-history"))]
+    fn is_login(line: &str) -> bool {
+        line.split_whitespace().next() == Some("login")
+    }
+
+    // ============================================================================
+    // History Filter Tests
+    // ============================================================================
+
+    #[cfg(not(feature = "no-history"))]
+    #[test]
+    fn test_history_stores_regular_lines() {
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+        enter_line(&mut parser, "led 1");
+        enter_line(&mut parser, "#l");
+        enter_line(&mut parser, "");
+        assert_eq!(parser.history.len(), 1);
+    }
+
+    #[test]
+    fn test_history_skips_leading_space() {
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+        enter_line(&mut parser, " led 1");
+        assert_eq!(parser.history.len(), 0);
+    }
+
+    #[cfg(not(feature = "no-history"))]
+    #[test]
+    fn test_history_pin_and_duplicates() {
+        let _guard = FORMAT_LOCK.lock().unwrap();
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+        parser.set_history_duplicates(DuplicatePolicy::MoveToFront);
+        enter_line(&mut parser, "led 1");
+        enter_line(&mut parser, "reset");
+        enter_line(&mut parser, "led 1");
+        parser.handle_hashtag("p 1");
+        parser.renderer.writer_mut().clear();
+        parser.handle_hashtag("l");
+        let out = parser.renderer.writer_mut().as_str();
+        assert!(out.starts_with("[0] reset\n\r[1]* led 1\n\r"), "{:?}", out);
+
+        parser.handle_hashtag("u 1");
+        assert!(!parser.history.is_pinned(1));
+        parser.renderer.writer_mut().clear();
+        parser.handle_hashtag("p 7");
+        assert_eq!(
+            parser.renderer.writer_mut().as_str(),
+            "Invalid history index.\n\r"
+        );
+    }
+
+    // ============================================================================
+    // Listing Tests
+    // ============================================================================
+
+    // The output format is global; listing tests must not run concurrently.
+    static FORMAT_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    #[test]
+    fn test_command_listing_table() {
+        let _guard = FORMAT_LOCK.lock().unwrap();
+        static COMMANDS: &[(&str, &str)] = &[("led", "t"), ("read", "bD")];
+        let mut parser = TestParser::new(String::new(), COMMANDS, "", "a | bb", ">> ");
+        parser.renderer.writer_mut().clear();
+        parser.handle_hashtag("#");
+        let out = parser.renderer.writer_mut().as_str();
+        assert!(out.contains("| command | args |\r\n"));
+        assert!(out.contains("| read    | bD   |\r\n"));
+        assert!(out.contains("| shortcut |\r\n"));
+        assert!(out.contains("| bb       |\r\n"));
+    }
+
+    #[test]
+    fn test_listing_fits_terminal_width() {
+        // As in `test_paged_listing`
+        let _guard = FORMAT_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        output::set_output_format(OutputFormat::Text);
+        static COMMANDS: &[(&str, &str)] = &[("led", "t"), ("read", "bDDDDDDDDDDDDDD")];
+        let mut parser = TestParser::new(String::new(), COMMANDS, "", "", ">> ");
+        parser.set_terminal_columns(22);
+        parser.renderer.writer_mut().clear();
+        parser.handle_hashtag("");
+        let out = parser.renderer.writer_mut().as_str();
+        assert!(out.contains("| read    | bDDDDDD~ |\r\n"), "{}", out);
+        assert!(out.contains("| led     | t        |\r\n"));
+
+        parser.set_terminal_columns(200);
+        parser.renderer.writer_mut().clear();
+        parser.handle_hashtag("");
+        let out = parser.renderer.writer_mut().as_str();
+        assert!(out.contains("| read    | bDDDDDDDDDDDDDD |\r\n"));
+    }
+
+    #[test]
+    fn test_paged_listing() {
+        // A failed listing test may poison the lock and leave JSON selected
+        let _guard = FORMAT_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        output::set_output_format(OutputFormat::Text);
+        static COMMANDS: &[(&str, &str)] = &[("aa", ""), ("bb", ""), ("cc", ""), ("dd", "")];
+        let mut parser = TestParser::new(String::new(), COMMANDS, "", "", ">> ");
+        parser.set_page_rows(4);
+        parser.renderer.writer_mut().clear();
+        parser.handle_hashtag("#");
+        let out = parser.renderer.writer_mut().as_str();
+        assert!(out.ends_with(crate::input::renderer::MORE_PROMPT));
+        assert!(!out.contains("| dd"));
+        assert!(parser.paging.is_some());
+
+        // Other keys are ignored, Enter shows one more line
+        parser.handle_pager_key(Key::Char('x'));
+        assert!(parser.paging.is_some());
+        parser.renderer.writer_mut().clear();
+        parser.handle_pager_key(Key::Enter);
+        assert_eq!(parser.renderer.writer_mut().matches('\n').count(), 1);
+        while parser.paging.is_some() {
+            parser.handle_pager_key(Key::Char(' '));
+        }
+        assert!(parser.renderer.writer_mut().contains("| dd"));
+
+        parser.handle_hashtag("#");
+        assert!(parser.paging.is_some());
+        parser.handle_pager_key(Key::Char('q'));
+        assert!(parser.paging.is_none());
+    }
+
+    #[test]
+    fn test_json_listings() {
+        let _guard = FORMAT_LOCK.lock().unwrap();
+        static COMMANDS: &[(&str, &str)] = &[("led", "t")];
+        let mut parser = TestParser::new(String::new(), COMMANDS, "t=bool", "a | b", ">> ");
+        enter_line(&mut parser, "say \"hi\"");
+        output::set_output_format(OutputFormat::Json);
+
+        parser.renderer.writer_mut().clear();
+        parser.handle_hashtag("#");
+        assert_eq!(
+            parser.renderer.writer_mut().as_str(),
+            "{\"commands\":[{\"name\":\"led\",\"args\":\"t\"}],\
+             \"datatypes\":\"t=bool\",\"shortcuts\":[\"a\",\"b\"]}\r\n"
+        );
+
+        // The history listing, unless history is compiled out
+        #[cfg(not(feature = "no-history"))]
+        {
+            parser.renderer.writer_mut().clear();
+            parser.handle_hashtag("l");
+            assert!(parser
+                .renderer
+                .writer_mut()
+                .starts_with("{\"history\":[\"say \\\"hi\\\"\"],\"free\":"));
+        }
+
+        output::set_output_format(OutputFormat::Text);
+    }
+
+    // ============================================================================
+    // Argument Completion Tests
+    // ============================================================================
+
+    #[cfg(not(feature = "no-autocomplete"))]
+    fn settings_children(parent: &str, emit: &mut dyn FnMut(&str)) {
+        let names: &[&str] = match parent {
+            "" => &["net", "log"],
+            "net" => &["wifi", "eth"],
+            "net.wifi" => &["ssid", "channel", "chan_width"],
+            _ => &[],
+        };
+        names.iter().for_each(|n| emit(n));
+    }
+
+    #[cfg(not(feature = "no-autocomplete"))]
+    fn complete_arg(command: &str, arg_index: usize, word: &str, offer: &mut dyn FnMut(&str)) {
+        if command == "set" && arg_index == 0 {
+            crate::autocomplete::complete_path(settings_children, word, offer);
+        }
+    }
+
+    #[cfg(not(feature = "no-autocomplete"))]
+    fn tab_after(parser: &mut TestParser, line: &str) -> String<32> {
+        parser.buffer.overwrite(line);
+        parser.handle_tab(false);
+        parser.buffer.to_string()
+    }
+
+    #[cfg(not(feature = "no-autocomplete"))]
+    #[test]
+    fn test_hierarchical_argument_completion() {
+        static COMMANDS: &[(&str, &str)] = &[("set", "ss")];
+        let mut parser = TestParser::new(String::new(), COMMANDS, "", "", ">> ");
+        parser.set_arg_completer(complete_arg);
+
+        // One segment per Tab; branches end with '.', leaves with a space
+        assert_eq!(tab_after(&mut parser, "set n").as_str(), "set net.");
+        assert_eq!(
+            tab_after(&mut parser, "set net.w").as_str(),
+            "set net.wifi."
+        );
+        assert_eq!(
+            tab_after(&mut parser, "set net.wifi.s").as_str(),
+            "set net.wifi.ssid "
+        );
+
+        // Ambiguous: extended to the common prefix, then listed
+        assert_eq!(
+            tab_after(&mut parser, "set net.wifi.c").as_str(),
+            "set net.wifi.chan"
+        );
+        parser.renderer.writer_mut().clear();
+        assert_eq!(
+            tab_after(&mut parser, "set net.wifi.chan").as_str(),
+            "set net.wifi.chan"
+        );
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("net.wifi.channel  net.wifi.chan_width"));
+
+        // Nothing offered for other arguments
+        assert_eq!(tab_after(&mut parser, "set log x").as_str(), "set log x");
+
+        // Command names are still completed by the command autocomplete
+        parser.handle_clear();
+        parser.handle_char('s');
+        assert_eq!(parser.buffer.to_string().as_str(), "set ");
+    }
+
+    #[cfg(not(feature = "no-autocomplete"))]
+    #[test]
+    fn test_ghost_completion() {
+        static COMMANDS: &[(&str, &str)] = &[("gpio read", "u"), ("gpio set", "uu"), ("led", "t")];
+        let mut parser = TestParser::new(String::new(), COMMANDS, "", "", ">> ");
+        parser.set_ghost_completion(true);
+
+        // The typed text is kept, the rest of the command shown after it
+        parser.handle_char('l');
+        assert_eq!(parser.buffer.to_string().as_str(), "l");
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .ends_with(">> l\x1B[90med\x1B[0m\x1B[5G"));
+        parser.handle_right();
+        assert_eq!(parser.buffer.to_string().as_str(), "led ");
+
+        // The first of several matches; Tab accepts it without a space
+        parser.handle_clear();
+        parser.handle_char('g');
+        parser.handle_tab(false);
+        assert_eq!(parser.buffer.to_string().as_str(), "gpio read");
+        for _ in 0..4 {
+            parser.handle_backspace();
+        }
+        parser.handle_char('s');
+        parser.handle_tab(false);
+        assert_eq!(parser.buffer.to_string().as_str(), "gpio set ");
+
+        // Not shown away from the end of the line, nor without a suggestion color
+        parser.handle_clear();
+        parser.handle_char('l');
+        parser.handle_left();
+        assert!(parser.ghost_suggestion().is_none());
+        parser.handle_end();
+        parser.renderer.set_theme(&theme::PLAIN);
+        assert!(parser.ghost_suggestion().is_none());
+        parser.handle_right();
+        assert_eq!(parser.buffer.to_string().as_str(), "l");
+    }
+
+    #[cfg(not(any(feature = "no-history", feature = "no-autocomplete")))]
+    #[test]
+    fn test_history_completion() {
+        static COMMANDS: &[(&str, &str)] = &[("led", "t"), ("reset", "")];
+        let mut parser = TestParser::new(String::new(), COMMANDS, "", "", ">> ");
+        parser.set_history_completion(true);
+        for line in ["led 0", "reset", "led 1 blink 250"] {
+            parser.buffer.overwrite(line);
+            parser.handle_enter();
+        }
+
+        // Up and Down walk through the lines starting with the typed text
+        parser.buffer.overwrite("led");
+        parser.handle_up();
+        assert_eq!(parser.buffer.to_string().as_str(), "led 1 blink 250");
+        parser.handle_up();
+        assert_eq!(parser.buffer.to_string().as_str(), "led 0");
+        parser.handle_down();
+        parser.handle_down();
+        assert_eq!(parser.buffer.to_string().as_str(), "led");
+
+        // Tab after the arguments; an edit starts a new walk
+        parser.buffer.overwrite("led 1");
+        parser.handle_tab(false);
+        assert_eq!(parser.buffer.to_string().as_str(), "led 1 blink 250");
+        parser.handle_char('!');
+        parser.handle_tab(false);
+        assert_eq!(parser.buffer.to_string().as_str(), "led 1 blink 250!");
+
+        // An empty line walks the whole history, as without history completion
+        parser.handle_clear();
+        parser.handle_up();
+        assert_eq!(parser.buffer.to_string().as_str(), "reset");
+        assert!(!parser.history_walk.is_active());
+
+        // Ghost text shows the newest line before the command names
+        parser.set_ghost_completion(true);
+        parser.buffer.overwrite("le");
+        assert_eq!(
+            parser.ghost_suggestion().unwrap().0.as_str(),
+            "d 1 blink 250"
+        );
+        parser.handle_right();
+        assert_eq!(parser.buffer.to_string().as_str(), "led 1 blink 250");
+    }
+
+    #[cfg(not(feature = "no-autocomplete"))]
+    #[test]
+    fn test_runtime_completions() {
+        static COMMANDS: &[(&str, &str)] = &[("i2c", "Bs"), ("set", "ss")];
+        static FOUND: crate::autocomplete::CompletionRegistry<2, 4, 4> =
+            crate::autocomplete::CompletionRegistry::new();
+        let mut parser = TestParser::new(String::new(), COMMANDS, "", "", ">> ");
+        parser.set_completions(&FOUND);
+
+        // Nothing registered yet
+        assert_eq!(tab_after(&mut parser, "i2c 0x").as_str(), "i2c 0x");
+
+        // Values registered by a handler, consulted with the `ArgCompleter`'s
+        FOUND.set("i2c", 0, &["0x50", "0x68"]);
+        parser.set_arg_completer(complete_arg);
+        assert_eq!(tab_after(&mut parser, "i2c 0x6").as_str(), "i2c 0x68 ");
+        assert_eq!(tab_after(&mut parser, "i2c 0x").as_str(), "i2c 0x");
+        assert!(parser.renderer.writer_mut().contains("0x50  0x68"));
+        assert_eq!(tab_after(&mut parser, "set n").as_str(), "set net.");
+    }
+
+    #[test]
+    fn test_live_validation() {
+        static COMMANDS: &[(&str, &str)] = &[("io set", "Bt"), ("led", "D(1..=9)")];
+        let mut parser = TestParser::new(String::new(), COMMANDS, "", "", ">> ");
+        let hint_after = |parser: &mut TestParser, line: &str| {
+            parser.buffer.overwrite(line);
+            parser.validation_hint()
+        };
+
+        // Off by default
+        parser.buffer.overwrite("led 0 ");
+        parser.render_buffer();
+        assert!(!parser.shown_hint);
+
+        parser.set_live_validation(true);
+        assert_eq!(hint_after(&mut parser, "led"), "");
+        assert_eq!(hint_after(&mut parser, "led 0"), "");
+        assert_eq!(
+            hint_after(&mut parser, "led 0 "),
+            "\x1B[31m✗ arg1: OutOfRange\x1B[0m"
+        );
+        assert_eq!(hint_after(&mut parser, "led 5"), "\x1B[32m✓\x1B[0m");
+        assert_eq!(
+            hint_after(&mut parser, "io set 300 1"),
+            "\x1B[31m✗ arg1: BadUnsigned\x1B[0m"
+        );
+        assert_eq!(hint_after(&mut parser, "io set 3 "), "");
+        assert_eq!(hint_after(&mut parser, "nope 1 "), "");
+
+        // Shown while typing, cleared from the line on Enter
+        parser.buffer.overwrite("led 0 ");
+        parser.render_buffer();
+        assert!(parser.shown_hint);
+        parser.renderer.writer_mut().clear();
+        let mut keys = [Key::Enter].into_iter();
+        parser.parse_input(|| keys.next(), |_| {}, |_| ExecOutcome::Done);
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .starts_with("\r\x1B[K>> led 0 \x1B["));
+        assert!(!parser.shown_hint);
+    }
+
+    #[test]
+    fn test_subcommands() {
+        let _guard = FORMAT_LOCK.lock().unwrap();
+        static COMMANDS: &[(&str, &str)] = &[("io get", "B"), ("io set", "Bt"), ("set", "ss")];
+        let mut parser = TestParser::new(String::new(), COMMANDS, "", "", ">> ");
+        parser.set_arg_completer(|command, arg_index, _, offer| {
+            if command == "io set" && arg_index == 1 {
+                offer("true");
+            }
+        });
+
+        // The first level completes to the group, the second to the subcommand
+        #[cfg(not(feature = "no-autocomplete"))]
+        {
+            parser.handle_char('i');
+            assert_eq!(parser.buffer.to_string().as_str(), "io ");
+            parser.handle_char('s');
+            assert_eq!(parser.buffer.to_string().as_str(), "io set ");
+
+            // Arguments are completed for the subcommand
+            assert_eq!(
+                tab_after(&mut parser, "io set 1 t").as_str(),
+                "io set 1 true "
+            );
+            assert_eq!(
+                tab_after(&mut parser, "io nope 1 t").as_str(),
+                "io nope 1 t"
+            );
+        }
+
+        parser.renderer.writer_mut().clear();
+        parser.handle_hashtag("# io");
+        let out = parser.renderer.writer_mut().as_str();
+        assert!(out.starts_with("Commands of io:\n\r"));
+        assert!(out.contains("| io set  | Bt   |\r\n"));
+        assert!(!out.contains("| set "));
+
+        // The group alone lists it too; with arguments it is dispatched
+        parser.renderer.writer_mut().clear();
+        assert_eq!(parser.run_builtin(" io "), Some(true));
+        let out = parser.renderer.writer_mut().as_str();
+        assert!(out.starts_with("Commands of io:\n\r"));
+        assert!(out.contains("| io set  | Bt   |\r\n"));
+        assert_eq!(parser.run_builtin("io nope"), None);
+        assert_eq!(parser.run_builtin("led"), None);
+
+        parser.renderer.writer_mut().clear();
+        parser.handle_hashtag("# led");
+        assert_eq!(parser.renderer.writer_mut().as_str(), "Unknown group.\n\r");
+    }
+
+    // ============================================================================
+    // Watch Built-in Tests
+    // ============================================================================
+
+    fn clock() -> u64 {
+        0
+    }
+
+    fn feed_watch(parser: &mut TestParser, key: Option<Key>) -> usize {
+        let mut next = key;
+        let runs = core::cell::Cell::new(0);
+        let _globals = globals_lock();
+        parser.parse_input(|| next.take(), |_| {}, |_| runs.set(runs.get() + 1));
+        runs.get()
+    }
+
+    #[test]
+    fn test_watch_requires_clock() {
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+        enter_line(&mut parser, "");
+        for ch in "watch mem 0 4 10".chars() {
+            feed_watch(&mut parser, Some(Key::Char(ch)));
+        }
+        feed_watch(&mut parser, Some(Key::Enter));
+        assert!(parser.watch.is_none());
+    }
+
+    #[test]
+    fn test_watch_runs_until_key() {
+        static REGION: [u8; 4] = [1, 2, 3, 4];
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+        parser.set_clock(clock);
+
+        let line = std::format!("watch mem {} 4 10", REGION.as_ptr() as usize);
+        for ch in line.chars() {
+            feed_watch(&mut parser, Some(Key::Char(ch)));
+        }
+        assert_eq!(feed_watch(&mut parser, Some(Key::Enter)), 0);
+        assert!(parser.watch.is_some());
+
+        // Polling without keys keeps it running, the next key stops it
+        feed_watch(&mut parser, None);
+        assert!(parser.watch.is_some());
+        assert_eq!(feed_watch(&mut parser, Some(Key::Char('x'))), 0);
+        assert!(parser.watch.is_none());
+        assert!(parser.buffer.to_string().is_empty());
+    }
+
+    #[cfg(not(feature = "no-history"))]
+    #[test]
+    fn test_history_filter() {
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+        parser.set_history_filter(is_login);
+        enter_line(&mut parser, "login admin secret");
+        enter_line(&mut parser, "logout");
+        assert_eq!(parser.history.len(), 1);
+    }
+
+    #[cfg(not(feature = "no-history"))]
+    #[test]
+    fn test_reverse_search() {
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+        for line in ["led on", "read 1 2", "led off"] {
+            enter_line(&mut parser, line);
+        }
+        parser.handle_char('x');
+        let line = |parser: &TestParser| parser.buffer.to_string();
+
+        feed_watch(&mut parser, Some(Key::CtrlR));
+        for ch in "led".chars() {
+            feed_watch(&mut parser, Some(Key::Char(ch)));
+        }
+        assert_eq!(line(&parser), "led off");
+        feed_watch(&mut parser, Some(Key::CtrlR));
+        assert_eq!(line(&parser), "led on");
+
+        // Nothing older: the search fails and keeps the last match
+        parser.renderer.writer_mut().clear();
+        feed_watch(&mut parser, Some(Key::CtrlR));
+        assert_eq!(line(&parser), "led on");
+        let out = parser.renderer.writer_mut().as_str();
+        assert!(out.contains("(failed reverse-i-search)`led': led on"));
+
+        // Backspace searches again from the newest entry, Esc restores the line
+        feed_watch(&mut parser, Some(Key::Backspace));
+        assert_eq!(line(&parser), "led off");
+        feed_watch(&mut parser, Some(Key::Escape));
+        assert!(parser.search.is_none());
+        assert_eq!(line(&parser), "x");
+
+        // Enter runs the match, other keys leave it for editing
+        feed_watch(&mut parser, Some(Key::CtrlR));
+        feed_watch(&mut parser, Some(Key::Char('2')));
+        feed_watch(&mut parser, Some(Key::End));
+        assert!(parser.search.is_none());
+        assert_eq!(line(&parser), "read 1 2");
+        feed_watch(&mut parser, Some(Key::CtrlR));
+        feed_watch(&mut parser, Some(Key::Char('r')));
+        assert_eq!(feed_watch(&mut parser, Some(Key::Enter)), 1);
+        assert!(parser.search.is_none());
+        assert!(line(&parser).is_empty());
+    }
+
+    #[test]
+    fn test_bracketed_paste() {
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+        parser.renderer.writer_mut().clear();
+
+        // Nothing is drawn while characters arrive; Tab does not complete
+        feed_watch(&mut parser, Some(Key::PasteStart));
+        for ch in "ab".chars() {
+            feed_watch(&mut parser, Some(Key::Char(ch)));
+        }
+        feed_watch(&mut parser, Some(Key::Tab));
+        assert!(parser.renderer.writer_mut().is_empty());
+        assert_eq!(parser.buffer.to_string(), "ab");
+
+        // A line ending runs the line, the rest is drawn at the end of the paste
+        assert_eq!(feed_watch(&mut parser, Some(Key::Enter)), 1);
+        feed_watch(&mut parser, Some(Key::Char('c')));
+        parser.renderer.writer_mut().clear();
+        feed_watch(&mut parser, Some(Key::PasteEnd));
+        assert!(!parser.pasting);
+        assert_eq!(parser.buffer.to_string(), "c");
+        assert!(parser.renderer.writer_mut().contains(">> c"));
+    }
+
+    // ============================================================================
+    // Key Bindings Tests
+    // ============================================================================
+
+    fn type_keys(parser: &mut TestParser, keys: &[Key]) -> usize {
+        keys.iter().map(|&key| feed_watch(parser, Some(key))).sum()
+    }
+
+    #[test]
+    fn test_emacs_bindings() {
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+        for ch in "ab".chars() {
+            feed_watch(&mut parser, Some(Key::Char(ch)));
+        }
+        type_keys(&mut parser, &[Key::CtrlA, Key::Char('x'), Key::CtrlE]);
+        type_keys(&mut parser, &[Key::Char(' '), Key::Char('c'), Key::CtrlW]);
+        assert_eq!(parser.buffer.to_string(), "xab ");
+    }
+
+    #[test]
+    fn test_rebound_keys() {
+        static NO_TAB: KeyBindings = KeyBindings {
+            name: "no-tab",
+            insert: &[
+                (Key::Tab, Action::KillToStart),
+                (Key::CtrlA, Action::Ignore),
+            ],
+            normal: &[],
+            base: Some(&bindings::EMACS),
+        };
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+        parser.set_key_bindings(&NO_TAB);
+        for ch in "ab".chars() {
+            feed_watch(&mut parser, Some(Key::Char(ch)));
+        }
+        type_keys(&mut parser, &[Key::CtrlA, Key::Char('c')]);
+        assert_eq!(parser.buffer.to_string(), "abc");
+        type_keys(&mut parser, &[Key::ArrowLeft, Key::Tab]);
+        assert_eq!(parser.buffer.to_string(), "c");
+    }
+
+    #[test]
+    fn test_vi_bindings() {
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+        parser.set_key_bindings(&bindings::VI);
+        for ch in "led 10".chars() {
+            feed_watch(&mut parser, Some(Key::Char(ch)));
+        }
+
+        // Esc steps onto the last character; commands are not inserted
+        type_keys(&mut parser, &[Key::Escape, Key::Char('x')]);
+        assert_eq!(parser.buffer.to_string(), "led 1");
+        type_keys(
+            &mut parser,
+            &[Key::Char('0'), Key::Char('x'), Key::Char('q')],
+        );
+        assert_eq!(parser.buffer.to_string(), "ed 1");
+        assert!(parser.normal_mode);
+
+        type_keys(&mut parser, &[Key::Char('i'), Key::Char('r')]);
+        type_keys(&mut parser, &[Key::Escape, Key::Char('A'), Key::Char('2')]);
+        assert_eq!(parser.buffer.to_string(), "red 12");
+
+        // Enter runs the line from either mode and returns to insert mode
+        assert_eq!(type_keys(&mut parser, &[Key::Escape, Key::Enter]), 1);
+        assert!(!parser.normal_mode);
+        feed_watch(&mut parser, Some(Key::Char('k')));
+        assert_eq!(parser.buffer.to_string(), "k");
+    }
+
+    // ============================================================================
+    // Snippet Tests
+    // ============================================================================
+
+    static SNIPPETS: &[(&str, &str)] = &[
+        ("i2cw", "i2c write 0x50 <cursor>"),
+        ("rd", "read <cursor> 16"),
+        ("long", "0123456789012345678901234567890123456789"),
+    ];
+
+    fn press(parser: &mut TestParser, key: Key) {
+        poll(parser, Some(key));
+    }
+
+    #[test]
+    fn test_snippet_expansion() {
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+        parser.set_snippets(SNIPPETS, Key::F(2));
+
+        parser.buffer.overwrite("i2cw");
+        press(&mut parser, Key::F(2));
+        assert_eq!(parser.buffer.to_string().as_str(), "i2c write 0x50 ");
+        assert_eq!(parser.buffer.cursor(), 15);
+
+        // Expanded after other words, cursor placed inside the template
+        parser.buffer.overwrite("x rd");
+        press(&mut parser, Key::F(2));
+        assert_eq!(parser.buffer.to_string().as_str(), "x read  16");
+        assert_eq!(parser.buffer.cursor(), 7);
+    }
+
+    #[test]
+    fn test_snippet_unknown_or_too_long() {
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+        parser.set_snippets(SNIPPETS, Key::F(2));
+
+        for line in ["nope", "", "x ", "long"] {
+            parser.buffer.overwrite(line);
+            parser.renderer.writer_mut().clear();
+            press(&mut parser, Key::F(2));
+            assert_eq!(parser.buffer.to_string().as_str(), line);
+            assert!(parser.renderer.writer_mut().contains('\x07'));
+        }
+    }
+
+    // ============================================================================
+    // Busy Marker Tests
+    // ============================================================================
+
+    static BUSY: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+    fn is_busy() -> bool {
+        BUSY.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    #[test]
+    fn test_busy_marker_follows_state() {
+        let _guard = crate::prompt::TEST_LOCK.lock().unwrap();
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+        parser.set_busy_indicator(is_busy);
+
+        // Typing is accepted while busy; the marker shows up on the first redraw
+        BUSY.store(true, std::sync::atomic::Ordering::Relaxed);
+        press(&mut parser, Key::Char('a'));
+        assert_eq!(parser.buffer.to_string().as_str(), "a");
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .ends_with("\r\x1B[K[busy] >> a\x1B[12G"));
+
+        // Cleared without waiting for a key
+        BUSY.store(false, std::sync::atomic::Ordering::Relaxed);
+        parser.renderer.writer_mut().clear();
+        poll(&mut parser, None);
+        assert_eq!(parser.renderer.writer_mut().as_str(), "\r\x1B[K>> a\x1B[5G");
+    }
+
+    // ============================================================================
+    // Prompt Built-in Tests
+    // ============================================================================
+
+    fn run_line(parser: &mut TestParser, line: &str) {
+        parser.buffer.overwrite(line);
+        press(parser, Key::Enter);
+    }
+
+    #[test]
+    fn test_prompt_builtin() {
+        let _guard = crate::prompt::TEST_LOCK.lock().unwrap();
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+
+        run_line(&mut parser, "prompt \"dev> \"");
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .ends_with("\r\x1B[Kdev> \x1B[6G"));
+
+        parser.renderer.writer_mut().clear();
+        run_line(&mut parser, "prompt");
+        assert!(parser.renderer.writer_mut().contains("prompt: \"dev> \""));
+
+        run_line(&mut parser, "prompt \"\"");
+        assert!(parser.renderer.writer_mut().ends_with("\r\x1B[K>> \x1B[4G"));
+        assert!(crate::prompt::custom_prompt().is_none());
+    }
+
+    /// Enters `line` and returns the line dispatched to the command handler, if any.
+    fn dispatched(parser: &mut TestParser, line: &str) -> Option<std::string::String> {
+        parser.buffer.overwrite(line);
+        let mut next = Some(Key::Enter);
+        let ran = core::cell::RefCell::new(None);
+        let _globals = globals_lock();
+        parser.parse_input(
+            || next.take(),
+            |_| {},
+            |cmd| *ran.borrow_mut() = Some(cmd.as_str().into()),
+        );
+        ran.into_inner()
+    }
+
+    #[test]
+    fn test_alias_builtin() {
+        let _guard = FORMAT_LOCK.lock().unwrap();
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+
+        assert_eq!(dispatched(&mut parser, "alias st \"status 1\""), None);
+        assert_eq!(dispatched(&mut parser, "alias g gpio set"), None);
+        assert_eq!(dispatched(&mut parser, "st").as_deref(), Some("status 1"));
+        assert_eq!(
+            dispatched(&mut parser, "g 5 1").as_deref(),
+            Some("gpio set 5 1")
+        );
+        assert_eq!(dispatched(&mut parser, "led st").as_deref(), Some("led st"));
+
+        parser.renderer.writer_mut().clear();
+        run_line(&mut parser, "alias");
+        let listing = parser.renderer.writer_mut();
+        assert!(listing.contains("st = \"status 1\"\r\ng = \"gpio set\"\r\n"));
+
+        // Aliases may name built-ins
+        run_line(&mut parser, "alias fj format json");
+        assert_eq!(dispatched(&mut parser, "fj"), None);
+        assert_eq!(output::output_format(), OutputFormat::Json);
+        output::set_output_format(OutputFormat::Text);
+
+        parser.renderer.writer_mut().clear();
+        run_line(&mut parser, "unalias st");
+        run_line(&mut parser, "unalias st");
+        assert!(parser.renderer.writer_mut().contains("no such alias"));
+        assert_eq!(dispatched(&mut parser, "st").as_deref(), Some("st"));
+
+        parser.renderer.writer_mut().clear();
+        run_line(&mut parser, "alias unalias x");
+        assert!(parser.renderer.writer_mut().contains("bad alias name"));
+    }
+
+    fn describe_command(
+        index: usize,
+    ) -> Option<(&'static str, &'static str, u8, &'static str, u8)> {
+        [
+            ("led", "Bt", 2, "set a \"LED\"", 0),
+            ("reset", "v", 0, "", 2),
+            ("gpio set", "Bt", 2, "", 1),
+        ]
+        .get(index)
+        .copied()
+    }
+
+    #[test]
+    fn test_describe_builtin() {
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+        run_line(&mut parser, "describe");
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("describe: not available"));
+
+        parser.set_describer(describe_command);
+        parser.renderer.writer_mut().clear();
+        run_line(&mut parser, "describe");
+        assert!(parser.renderer.writer_mut().contains(
+            "{\"name\":\"led\",\"descriptor\":\"Bt\",\"arity\":2,\"help\":\"set a \\\"LED\\\"\",\"group\":null,\"privilege\":0}\r\n{\"name\":\"reset\",\"descriptor\":\"v\",\"arity\":0,"
+        ));
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("{\"name\":\"gpio set\",\"descriptor\":\"Bt\",\"arity\":2,\"help\":\"\",\"group\":\"gpio\",\"privilege\":1}\r\n"));
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("\"help\":\"\",\"group\":null,\"privilege\":2}"));
+    }
+
+    #[test]
+    fn test_apropos_builtin() {
+        assert!(contains_ignore_case("Set a LED", "led"));
+        assert!(contains_ignore_case("abc", ""));
+        assert!(!contains_ignore_case("le", "led"));
+
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+        run_line(&mut parser, "apropos led");
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("apropos: not available"));
+
+        parser.set_describer(describe_command);
+        parser.renderer.writer_mut().clear();
+        run_line(&mut parser, "apropos LeD");
+        let out = parser.renderer.writer_mut();
+        assert!(out.contains("led Bt - set a \"LED\"\r\n"));
+        assert!(!out.contains("reset"));
+
+        out.clear();
+        run_line(&mut parser, "apropos v");
+        assert!(parser.renderer.writer_mut().contains("reset v\r\n"));
+
+        parser.renderer.writer_mut().clear();
+        run_line(&mut parser, "apropos flash");
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("apropos: nothing matches \"flash\""));
+    }
+
+    #[cfg(feature = "mem-tools")]
+    #[test]
+    fn test_mem_builtins() {
+        static REGION: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+        let mut region = [0u8; 16];
+        let addr = region.as_mut_ptr() as usize;
+        REGION.store(addr, std::sync::atomic::Ordering::Relaxed);
+
+        run_line(&mut parser, &std::format!("md {:#x} 4", addr));
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("mem: access denied\r\n"));
+
+        parser.set_mem_access(|addr, len, _| {
+            let start = REGION.load(std::sync::atomic::Ordering::Relaxed);
+            addr >= start && addr + len <= start + 16
+        });
+        run_line(&mut parser, &std::format!("mfill {:#x} 4 0x5A", addr));
+        run_line(&mut parser, &std::format!("mw16 {:#x} 0x1234", addr + 2));
+        parser.renderer.writer_mut().clear();
+        run_line(&mut parser, &std::format!("md {:#x} 6", addr));
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains(&std::format!(": 5A 5A 34 12 00 00{:32}|ZZ4...|\r\n", "")));
+        run_line(&mut parser, &std::format!("md {:#x} 17", addr));
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("mem: access denied\r\n"));
+        run_line(&mut parser, "mw32 1");
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("usage: mw8|mw16|mw32 <addr> <val>\r\n"));
+        assert_eq!(region[1], 0x5A);
+    }
+
+    #[test]
+    fn test_loglevel_and_logcolor_builtins() {
+        let _guard = logger::TEST_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        logger::tests::init();
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+
+        run_line(&mut parser, "loglevel trace");
+        run_line(&mut parser, "loglevel warn led");
+        run_line(&mut parser, "loglevel");
+        assert_eq!(logger::min_level(), LogLevel::Trace);
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("loglevel: trace\r\n  led: warn\r\n"));
+        assert!(!logger::is_target_enabled("led.blink", LogLevel::Info));
+
+        parser.renderer.writer_mut().clear();
+        run_line(&mut parser, "loglevel default led");
+        assert!(logger::is_target_enabled("led", LogLevel::Info));
+        run_line(&mut parser, "loglevel loud");
+        run_line(&mut parser, "loglevel info a b");
+        assert_eq!(
+            parser
+                .renderer
+                .writer_mut()
+                .matches("usage: loglevel")
+                .count(),
+            2
+        );
+        run_line(&mut parser, "loglevel info a.very.long.target.name");
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("loglevel: bad target prefix\r\n"));
+
+        run_line(&mut parser, "logcolor on");
+        assert!(logger::color_entire_line());
+        run_line(&mut parser, "logcolor");
+        assert!(parser.renderer.writer_mut().contains("logcolor: on\r\n"));
+        run_line(&mut parser, "logcolor off");
+        assert!(!logger::color_entire_line());
+        run_line(&mut parser, "logcolor blue");
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("usage: logcolor [on|off]\r\n"));
+        logger::set_min_level(LogLevel::Info);
+    }
+
+    #[test]
+    fn test_log_builtin() {
+        let _guard = logger::TEST_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+        logger::attach_log_ring(std::boxed::Box::leak(std::boxed::Box::new([0u8; 128])));
+        logger::with_log_ring(|ring| {
+            ring.push(Some(LogLevel::Info), "boot");
+            ring.push(Some(LogLevel::Warn), "low voltage");
+            ring.push(None, "plain");
+            ring.push(Some(LogLevel::Error), "sensor timeout");
+        });
+
+        run_line(&mut parser, "log show 2 warn");
+        let out = parser.renderer.writer_mut();
+        assert!(out.contains("[ WARN] low voltage\r\n[ERROR] sensor timeout\r\n"));
+        assert!(!out.contains("boot"));
+
+        out.clear();
+        run_line(&mut parser, "log show 2");
+        let out = parser.renderer.writer_mut();
+        assert!(out.contains("plain\r\n[ERROR] sensor timeout\r\n"));
+        assert!(!out.contains("voltage"));
+
+        out.clear();
+        run_line(&mut parser, "log show loud");
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("usage: log show [n] [level] | log clear"));
+
+        run_line(&mut parser, "log clear");
+        parser.renderer.writer_mut().clear();
+        run_line(&mut parser, "log show");
+        assert!(parser.renderer.writer_mut().contains("log: empty"));
+    }
+
+    #[test]
+    fn test_storage_builtin() {
+        use crate::storage::StorageInfo;
+
+        fn control(op: StorageOp) -> Result<StorageInfo, StorageError> {
+            match op {
+                StorageOp::Mount => Err(StorageError::NotFormatted),
+                StorageOp::Info | StorageOp::Format => Ok(StorageInfo {
+                    records: 12,
+                    used: 188,
+                    capacity: 1024,
+                    generation: 3,
+                }),
+            }
+        }
+
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+        run_line(&mut parser, "storage");
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("storage: not available"));
+
+        parser.set_storage_control(control);
+        parser.renderer.writer_mut().clear();
+        run_line(&mut parser, "storage");
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("storage: 12 records, 188/1024 bytes, generation 3\r\n"));
+
+        parser.renderer.writer_mut().clear();
+        run_line(&mut parser, "storage mount");
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("storage: not formatted\r\n"));
+
+        parser.renderer.writer_mut().clear();
+        run_line(&mut parser, "storage erase");
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("usage: storage [info|mount|format]"));
+    }
+
+    #[test]
+    fn test_shell_debug_builtin() {
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+        run_line(&mut parser, "reset");
+        parser.buffer.overwrite("led 1");
+        press(&mut parser, Key::ArrowLeft);
+
+        // The stub history of `no-history` stores nothing
+        let history = if cfg!(feature = "no-history") {
+            "0 entries, 0 bytes free"
+        } else {
+            "1 entries, 55 bytes free"
+        };
+        let mut out: String<256> = String::new();
+        parser.debug_state(&mut out);
+        assert_eq!(
+            out.as_str(),
+            std::format!(
+                "input: \"led 1\" (5 chars, cursor 4)\r\nhistory: {}\r\n\
+                 autocomplete: \"\", 0 candidates\r\npending: none\r\nwatch: off, stream: off\r\n",
+                history
+            )
+        );
+
+        parser.buffer.clear();
+        parser.renderer.writer_mut().clear();
+        run_line(&mut parser, "shell debug");
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("input: \"\" (0 chars, cursor 0)"));
+        run_line(&mut parser, "shell");
+        assert!(parser.renderer.writer_mut().contains("usage: shell debug"));
+    }
+
+    #[test]
+    fn test_builtin_privilege() {
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+        // Without a level every built-in runs
+        run_line(&mut parser, "storage");
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("storage: not available"));
+
+        parser.set_privilege_level(|| 1);
+        for line in ["storage format", "  storage"] {
+            parser.renderer.writer_mut().clear();
+            run_line(&mut parser, line);
+            let out = parser.renderer.writer_mut();
+            assert!(out.contains("Error: PermissionDenied(required=2)\r\n"));
+            assert!(!out.contains("storage:"));
+        }
+        parser.renderer.writer_mut().clear();
+        run_line(&mut parser, "shell");
+        assert!(parser.renderer.writer_mut().contains("usage: shell debug"));
+        assert_eq!(builtin_level("kill"), Some(1));
+        assert_eq!(builtin_level("set"), None);
+    }
+
+    static MOUSE_REPORTS: core::sync::atomic::AtomicU32 = core::sync::atomic::AtomicU32::new(0);
+
+    fn on_mouse(button: u8, x: u16, y: u16, press: bool) {
+        assert_eq!((button, x, y, press), (0, 3, 1, true));
+        MOUSE_REPORTS.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+    }
+
+    #[test]
+    fn test_mouse_reports_stay_out_of_line() {
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+        let click = Key::Mouse {
+            button: 0,
+            x: 3,
+            y: 1,
+            press: true,
+        };
+        parser.buffer.overwrite("led");
+        press(&mut parser, click);
+        assert_eq!(parser.buffer.to_string().as_str(), "led");
+
+        parser.set_mouse_handler(on_mouse);
+        press(&mut parser, click);
+        assert_eq!(parser.buffer.to_string().as_str(), "led");
+        assert_eq!(MOUSE_REPORTS.load(core::sync::atomic::Ordering::Relaxed), 1);
+    }
+
+    // ============================================================================
+    // Edit Key Tests
+    // ============================================================================
+
+    #[test]
+    fn test_transpose_and_delete_word_keys() {
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+        parser.buffer.overwrite("led no");
+        press(&mut parser, Key::CtrlT);
+        assert_eq!(parser.buffer.to_string().as_str(), "led on");
+
+        press(&mut parser, Key::AltBackspace);
+        assert_eq!(parser.buffer.to_string().as_str(), "led ");
+        press(&mut parser, Key::AltBackspace);
+        assert!(parser.buffer.is_empty());
+
+        parser.renderer.writer_mut().clear();
+        press(&mut parser, Key::AltBackspace);
+        assert!(parser.renderer.writer_mut().contains('\x07'));
+    }
+
+    // ============================================================================
+    // Term Built-in Tests
+    // ============================================================================
+
+    #[test]
+    fn test_term_keymap_builtin() {
+        let _guard = keymap::TEST_LOCK.lock().unwrap();
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+
+        run_line(&mut parser, "term keymap putty");
+        assert_eq!(keymap::keymap().name, "putty");
+        run_line(&mut parser, "term keymap");
+        assert!(parser.renderer.writer_mut().contains("keymap: putty\r\n"));
+
+        run_line(&mut parser, "term keymap vt52");
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("term: unknown keymap"));
+        assert_eq!(keymap::keymap().name, "putty");
+        run_line(&mut parser, "term");
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("usage: term keymap [default|putty|minicom|picocom|screen]"));
+
+        keymap::set_keymap(&keymap::DEFAULT);
+    }
+
+    #[cfg(not(feature = "no-history"))]
+    #[test]
+    fn test_history_times_builtin() {
+        let _guard = FORMAT_LOCK.lock().unwrap();
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+        parser.history.push("reset");
+        parser.set_history_clock(|| 1000);
+        parser.history.push_stamped("led 1", Some(880));
+
+        run_line(&mut parser, "history -t");
+        let out = parser.renderer.writer_mut().as_str();
+        assert!(
+            out.contains("[0] reset\n\r[1] 2m00s ago  led 1\n\r"),
+            "{:?}",
+            out
+        );
+        assert!(out.contains("[2] 0s ago  history -t\n\r"), "{:?}", out);
+
+        parser.renderer.writer_mut().clear();
+        run_line(&mut parser, "history -T");
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("[1] 00:14:40  led 1\n\r"));
+        run_line(&mut parser, "history -x");
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("usage: history [-t|-T]\r\n"));
+    }
+
+    #[test]
+    fn test_checksum_builtins() {
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+
+        run_line(&mut parser, "crc32 313233343536373839");
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("crc32: 0xCBF43926 (9 bytes)\r\n"));
+        run_line(&mut parser, "crc16 3132 3334 3536373839");
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("crc16: 0x31C3 (9 bytes)\r\n"));
+        run_line(&mut parser, "sum8 01 02 ff");
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("sum8: 0x02 (3 bytes)\r\n"));
+        run_line(&mut parser, "crc32 123");
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("usage: crc32 <hex bytes> (odd number of hex digits)\r\n"));
+    }
+
+    #[test]
+    fn test_theme_builtin() {
+        let _guard = theme::TEST_LOCK.lock().unwrap();
+        let mut parser = TestParser::new(String::new(), &[("led", "t")], "", "", ">> ");
+
+        run_line(&mut parser, "theme bright");
+        assert_eq!(theme::theme().name, "bright");
+        run_line(&mut parser, "theme");
+        assert!(parser.renderer.writer_mut().contains("theme: bright\r\n"));
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("\x1B[1;32m>> \x1B[0m"));
+        parser.renderer.writer_mut().clear();
+        parser.show_help("");
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("\x1B[1;36mAvailable commands:\x1B[0m\n\r"));
+
+        run_line(&mut parser, "theme plain");
+        parser.buffer.overwrite("led 1");
+        assert_eq!(parser.validation_hint().as_str(), "✓");
+        run_line(&mut parser, "theme neon");
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .contains("usage: theme [default|bright|plain]"));
+        assert_eq!(theme::theme().name, "plain");
+
+        theme::set_theme(&theme::DEFAULT);
+    }
+
+    #[test]
+    fn test_term_size_builtin() {
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+
+        run_line(&mut parser, "term size 100 30");
+        assert_eq!(parser.renderer.columns(), Some(100));
+        assert_eq!(parser.renderer.page_rows(), Some(30));
+        run_line(&mut parser, "term size");
+        assert!(parser.renderer.writer_mut().contains("size: 100x30\r\n"));
+
+        feed_watch(&mut parser, Some(Key::CursorReport { row: 1, col: 2 }));
+        assert_eq!(parser.renderer.columns(), Some(100));
+        run_line(&mut parser, "term size probe");
+        assert!(parser.renderer.writer_mut().contains(TERMINAL_SIZE_PROBE));
+        feed_watch(&mut parser, Some(Key::CursorReport { row: 40, col: 120 }));
+        assert_eq!(parser.renderer.columns(), Some(120));
+        assert_eq!(parser.renderer.page_rows(), Some(40));
+        feed_watch(&mut parser, Some(Key::CursorReport { row: 1, col: 2 }));
+        assert_eq!(parser.renderer.columns(), Some(120));
+
+        run_line(&mut parser, "term size 0 5");
+        assert!(parser.renderer.writer_mut().contains("term: invalid size"));
+        run_line(&mut parser, "term size 80");
+        assert!(parser.renderer.writer_mut().contains(TERM_SIZE_USAGE));
+        assert_eq!(parser.renderer.columns(), Some(120));
+    }
+
+    // ============================================================================
+    // Notice Tests
+    // ============================================================================
+
+    #[test]
+    fn test_println_above_keeps_input() {
+        let _prompt = crate::prompt::TEST_LOCK.lock().unwrap();
+        let _globals = globals_lock();
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+        parser.buffer.overwrite("led o");
+
+        assert!(notice::println_above("alert: 81 C"));
+        assert!(notice::println_above("alert: 82 C"));
+        parser.renderer.writer_mut().clear();
+        poll_locked(&mut parser, None);
+        assert_eq!(
+            parser.renderer.writer_mut().as_str(),
+            "\r\x1B[Kalert: 81 C\r\nalert: 82 C\r\n\r\x1B[K>> led o\x1B[9G"
+        );
+        assert_eq!(parser.buffer.to_string().as_str(), "led o");
+    }
+
+    // ============================================================================
+    // Stream Tests
+    // ============================================================================
+
+    fn sample() -> bool {
+        true
+    }
+
+    fn start_stream(_: &String<32>) {
+        stream::start(sample, 0).unwrap();
+    }
+
+    #[test]
+    fn test_stream_takes_keys() {
+        let _prompt = crate::prompt::TEST_LOCK.lock().unwrap();
+        let _globals = globals_lock();
+        stream::stop();
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+
+        parser.buffer.overwrite("adcwatch 100");
+        let mut next = Some(Key::Enter);
+        parser.parse_input(|| next.take(), |_| {}, start_stream);
+        assert!(parser
+            .renderer
+            .writer_mut()
+            .ends_with("(Space pauses, q stops)\r\n"));
+
+        // Keys go to the stream, not to the line
+        poll_locked(&mut parser, Some(Key::Char('a')));
+        poll_locked(&mut parser, Some(Key::Char(' ')));
+        assert!(parser.renderer.writer_mut().ends_with("-- paused --\r\n"));
+        assert!(parser.buffer.is_empty());
+
+        parser.renderer.writer_mut().clear();
+        poll_locked(&mut parser, Some(Key::Char('q')));
+        assert!(!stream::is_active());
+        assert_eq!(parser.renderer.writer_mut().as_str(), "\r\x1B[K>> \x1B[4G");
     }
 }
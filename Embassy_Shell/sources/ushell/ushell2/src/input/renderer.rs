@@ -1,9 +1,12 @@
 use core::fmt::Write;
 use core::ops::FnMut;
+use core::sync::atomic::{AtomicBool, Ordering};
 
 /// Import and re-export the unified writer from logger
 ///
 use crate::logger::UnifiedWriter;
+use crate::output::display_width;
+use crate::theme::{self, Theme};
 
 /// Standard library writer (for hosted platforms)
 ///
@@ -71,17 +74,147 @@ where
     }
 }
 
+/// Shown below a page of output while more is left (see `DisplayRenderer::page`).
+pub const MORE_PROMPT: &str = "\r\x1B[7m--more--\x1B[0m";
+
+/// Starts the line of a reverse history search (see `DisplayRenderer::render_search`).
+pub const SEARCH_PROMPT: &str = "(reverse-i-search)`";
+
+/// Starts the line of a reverse history search that found nothing.
+pub const FAILED_SEARCH_PROMPT: &str = "(failed reverse-i-search)`";
+
+/// Writer passing lines `skip..end` of the output to the wrapped writer and dropping
+/// the others; notes whether output past `end` was dropped.
+pub struct PagedWriter<'w, W: UnifiedWriter> {
+    writer: &'w mut W,
+    skip: usize,
+    end: usize,
+    line: usize,
+    more: bool,
+}
+
+impl<W: UnifiedWriter> UnifiedWriter for PagedWriter<'_, W> {
+    fn write_str(&mut self, s: &str) {
+        self.write_bytes(s.as_bytes());
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        let mut rest = bytes;
+        while !rest.is_empty() {
+            let (chunk, newline) = match rest.iter().position(|&b| b == b'\n') {
+                Some(i) => (&rest[..=i], true),
+                None => (rest, false),
+            };
+            rest = &rest[chunk.len()..];
+            // Lines end with "\n\r": a leading carriage return belongs to the line before
+            let cr = chunk.iter().take_while(|&&b| b == b'\r').count();
+            if self.line > self.skip && self.line <= self.end {
+                self.writer.write_bytes(&chunk[..cr]);
+            }
+            let text = &chunk[cr..];
+            if self.line >= self.end {
+                self.more |= !text.is_empty();
+            } else if self.line >= self.skip {
+                self.writer.write_bytes(text);
+            }
+            if newline {
+                self.line += 1;
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        self.writer.flush();
+    }
+}
+
 /// DisplayRenderer: handles terminal output
 /// Generic over the writer type to support both std and no_std environments
 ///
 pub struct DisplayRenderer<W: UnifiedWriter> {
     writer: W,
+    page_rows: Option<u16>,
+    columns: Option<u16>,
+    /// First character of the input shown while a long line is scrolled.
+    scroll: usize,
+    theme: &'static Theme,
 }
 
 impl<W: UnifiedWriter> DisplayRenderer<W> {
     /// Create a new DisplayRenderer with the given writer
     pub fn new(writer: W) -> Self {
-        Self { writer }
+        Self {
+            writer,
+            page_rows: None,
+            columns: None,
+            scroll: 0,
+            theme: &theme::DEFAULT,
+        }
+    }
+
+    /// Sets the terminal height in lines used to page long output; `None` (the
+    /// default) writes it at once.
+    pub fn set_page_rows(&mut self, rows: Option<u16>) {
+        self.page_rows = rows;
+    }
+
+    /// The terminal height set with `set_page_rows`.
+    pub fn page_rows(&self) -> Option<u16> {
+        self.page_rows
+    }
+
+    /// Sets the terminal width in columns: an input line that does not fit next to the
+    /// prompt scrolls horizontally with the cursor. `None` (the default) writes the line
+    /// whole and lets the terminal wrap it.
+    pub fn set_columns(&mut self, columns: Option<u16>) {
+        self.columns = columns;
+    }
+
+    /// The terminal width set with `set_columns`.
+    pub fn columns(&self) -> Option<u16> {
+        self.columns
+    }
+
+    /// Sets the colors of the prompt and the boundary marker (see `crate::theme`).
+    pub fn set_theme(&mut self, theme: &'static Theme) {
+        self.theme = theme;
+    }
+
+    /// The theme set with `set_theme`, `theme::DEFAULT` by default.
+    pub fn theme(&self) -> &'static Theme {
+        self.theme
+    }
+
+    /// Writes `lines` lines of the output of `write`, starting at line `skip`, and
+    /// `MORE_PROMPT` if output was left out. `write` produces the whole output each
+    /// time, so it must write the same text on every call.
+    ///
+    /// Returns the line to continue from, `None` once everything was shown.
+    pub fn page(
+        &mut self,
+        skip: usize,
+        lines: usize,
+        write: impl FnOnce(&mut PagedWriter<'_, W>),
+    ) -> Option<usize> {
+        let mut paged = PagedWriter {
+            writer: &mut self.writer,
+            skip,
+            end: skip + lines,
+            line: 0,
+            more: false,
+        };
+        write(&mut paged);
+        let more = paged.more;
+        if more {
+            self.writer.write_str(MORE_PROMPT);
+        }
+        self.writer.flush();
+        more.then_some(skip + lines)
+    }
+
+    /// Removes `MORE_PROMPT` from the screen.
+    pub fn clear_more(&mut self) {
+        self.writer.write_str("\r\x1B[K");
     }
 
     /// Provides mutable access to the underlying writer
@@ -103,21 +236,162 @@ impl<W: UnifiedWriter> DisplayRenderer<W> {
     ///
     /// - Clears the current line.
     /// - Prints the prompt followed by the content.
-    /// - Moves the cursor to the correct position based on `cursor_pos`.
+    /// - Moves the cursor to the correct position based on `cursor_pos` (in chars);
+    ///   escape sequences in the prompt, such as colors, take no columns.
     /// - Ensures cursor position does not exceed content length.
     /// - Flushes output to apply changes immediately.
     ///
     pub fn render(&mut self, prompt: &str, content: &str, cursor_pos: usize) {
-        let safe_cursor_pos = cursor_pos.min(content.len());
+        self.render_marked("", prompt, content, cursor_pos);
+    }
+
+    /// Same as `render`, with `marker` (e.g. a busy indicator) written before the prompt.
+    pub fn render_marked(&mut self, marker: &str, prompt: &str, content: &str, cursor_pos: usize) {
+        self.render_hinted(marker, prompt, content, cursor_pos, "");
+    }
 
-        // Clear line and write prompt + content
+    /// Same as `render_marked`, with `hint` right-aligned on the line (at the right edge
+    /// of the terminal, whatever its width); an empty hint leaves the line as is.
+    ///
+    /// With a terminal width (`set_columns`), only the part of a long line around the
+    /// cursor is shown, `<` and `>` marking the cut ends.
+    pub fn render_hinted(
+        &mut self,
+        marker: &str,
+        prompt: &str,
+        content: &str,
+        cursor_pos: usize,
+        hint: &str,
+    ) {
+        self.render_suggested(marker, prompt, content, "", cursor_pos, hint);
+    }
+
+    /// Same as `render_hinted`, with `suggestion` written after the content in the
+    /// theme's suggestion color (ghost text: shown, not part of the line). It is cut to
+    /// the terminal width, and left out while a long line is scrolled.
+    pub fn render_suggested(
+        &mut self,
+        marker: &str,
+        prompt: &str,
+        content: &str,
+        suggestion: &str,
+        cursor_pos: usize,
+        hint: &str,
+    ) {
+        let safe_cursor_pos = cursor_pos.min(content.chars().count());
+
+        let prefix = display_width(marker) + display_width(prompt);
+        let (cut_left, content, cut_right, safe_cursor_pos) =
+            self.viewport(prefix, content, safe_cursor_pos);
+
+        // Clear line and write marker + prompt + content
         self.writer.write_str("\r\x1B[K");
-        self.writer.write_str(prompt);
+        self.writer.write_str(marker);
+        Theme::paint(&mut self.writer, self.theme.prompt, prompt);
+        if cut_left {
+            self.writer.write_str("<");
+        }
         self.writer.write_str(content);
+        if cut_right {
+            self.writer.write_str(">");
+        }
+        if !suggestion.is_empty() && !cut_left && !cut_right {
+            let shown = match self.columns {
+                Some(columns) => {
+                    let room =
+                        (columns as usize).saturating_sub(prefix + content.chars().count() + 1);
+                    let end = suggestion
+                        .char_indices()
+                        .nth(room)
+                        .map_or(suggestion.len(), |(i, _)| i);
+                    &suggestion[..end]
+                }
+                None => suggestion,
+            };
+            Theme::paint(&mut self.writer, self.theme.suggestion, shown);
+        }
+
+        if !hint.is_empty() {
+            // To the last column, then back by the width of the hint
+            use core::fmt::Write as FmtWrite;
+            let mut buf = heapless::String::<16>::new();
+            let _ = write!(&mut buf, "\x1B[999C\x1B[{}D", display_width(hint));
+            self.writer.write_str(buf.as_str());
+            self.writer.write_str(hint);
+        }
 
         // Position cursor
-        let cursor_position = prompt.len() + safe_cursor_pos + 1;
-        self.write_cursor_position(cursor_position);
+        self.write_cursor_position(prefix + safe_cursor_pos + 1);
+
+        self.writer.flush();
+    }
+
+    /// The part of `content` shown after `prefix` columns: whether `<` precedes it, the
+    /// text, whether `>` follows it, and the cursor column within it. The scroll position
+    /// only moves to keep the cursor shown, off the marks unless at an end of the line;
+    /// the last column of the terminal is left for the cursor at the end of the line.
+    fn viewport<'c>(
+        &mut self,
+        prefix: usize,
+        content: &'c str,
+        cursor: usize,
+    ) -> (bool, &'c str, bool, usize) {
+        let len = content.chars().count();
+        let room = match self.columns.map(usize::from) {
+            // At least a character between the marks
+            Some(columns) if prefix + len + 1 > columns && columns > prefix + 3 => {
+                columns - prefix - 1
+            }
+            _ => {
+                self.scroll = 0;
+                return (false, content, false, cursor);
+            }
+        };
+        if self.scroll > 0 && cursor <= self.scroll {
+            self.scroll = cursor.saturating_sub(1);
+        }
+        if cursor + 2 > self.scroll + room {
+            self.scroll = cursor + 2 - room;
+        }
+        self.scroll = self.scroll.min(len + 1 - room);
+
+        let start = self.scroll;
+        let cut_left = start > 0;
+        let cut_right = start + room < len;
+        let byte = |chars: usize| {
+            content
+                .char_indices()
+                .nth(chars)
+                .map_or(content.len(), |(i, _)| i)
+        };
+        let first = start + cut_left as usize;
+        let end = (start + room - cut_right as usize).min(len);
+        (
+            cut_left,
+            &content[byte(first)..byte(end)],
+            cut_right,
+            cursor - start,
+        )
+    }
+
+    /// Renders the line of a reverse history search, readline style:
+    /// ``(reverse-i-search)`query': match``, with the cursor at the start of `query`
+    /// within the match. `failed` tells that no entry contains `query`.
+    pub fn render_search(&mut self, query: &str, found: &str, failed: bool) {
+        let label = if failed {
+            FAILED_SEARCH_PROMPT
+        } else {
+            SEARCH_PROMPT
+        };
+        self.writer.write_str("\r\x1B[K");
+        self.writer.write_str(label);
+        self.writer.write_str(query);
+        self.writer.write_str("': ");
+        self.writer.write_str(found);
+
+        let offset = found.find(query).map_or(0, |i| display_width(&found[..i]));
+        let prefix = display_width(label) + display_width(query) + 3;
+        self.write_cursor_position(prefix + offset + 1);
 
         self.writer.flush();
     }
@@ -141,19 +415,195 @@ impl<W: UnifiedWriter> DisplayRenderer<W> {
         self.writer.flush();
     }
 
-    /// Prints a red boundary marker in the terminal.
+    /// Prints a boundary marker in the terminal.
     ///
-    /// - Displays a red pipe character.
+    /// - Displays a pipe character in the theme's error color.
     /// - Moves the cursor back two positions.
     /// - Flushes output to apply changes immediately.
     /// - Can be used to visually separate sections or indicate limits.
     ///
     pub fn boundary_marker(&mut self) {
-        self.writer.write_str("\x1B[31m|\x1B[0m\x1B[1D \x1B[1D");
+        Theme::paint(&mut self.writer, self.theme.error, "|");
+        self.writer.write_str("\x1B[1D \x1B[1D");
         self.writer.flush();
     }
 }
 
+/// Width in characters of the bar drawn by `ProgressReporter::progress`.
+pub const PROGRESS_BAR_WIDTH: usize = 20;
+
+/// Frames of the spinner drawn by `ProgressReporter::spin`.
+const SPINNER: [&str; 4] = ["|", "/", "-", "\\"];
+
+/// Set when a progress line is cleared, until the shell draws the edit line again.
+static PROGRESS_CLEARED: AtomicBool = AtomicBool::new(false);
+
+/// Progress of a long-running handler, drawn in place on a single line.
+///
+/// Each update erases the line and draws `label [#####-----]  50%` (or `label |` for a
+/// spinner) through the logger's writer, so the bar also works from a task while the
+/// user is typing. `finish` (or dropping the reporter) clears the line; on its next poll
+/// the shell draws the prompt and the input being edited again.
+///
+/// ```ignore
+/// use ushell2::ProgressReporter;
+///
+/// pub fn flash_erase() {
+///     let mut progress = ProgressReporter::new("erase");
+///     for sector in 0..SECTORS {
+///         flash::erase_sector(sector);
+///         progress.progress(((sector + 1) * 100 / SECTORS) as u8);
+///     }
+///     progress.finish();
+///     log_info!("erase | {} sectors", SECTORS);
+/// }
+/// ```
+///
+/// Log lines written while a bar is shown start after it: finish the bar first.
+pub struct ProgressReporter {
+    label: &'static str,
+    percent: Option<u8>,
+    frame: usize,
+    shown: bool,
+}
+
+impl ProgressReporter {
+    pub const fn new(label: &'static str) -> Self {
+        Self {
+            label,
+            percent: None,
+            frame: 0,
+            shown: false,
+        }
+    }
+
+    /// Draws the bar filled to `percent` (at most 100); the same value is not drawn twice.
+    pub fn progress(&mut self, percent: u8) {
+        let percent = percent.min(100);
+        if self.shown && self.percent == Some(percent) {
+            return;
+        }
+        self.percent = Some(percent);
+        let filled = PROGRESS_BAR_WIDTH * percent as usize / 100;
+        let mut value = heapless::String::<8>::new();
+        let _ = write!(&mut value, "] {:>3}%", percent);
+        self.draw(|writer| {
+            writer.write_str(" [");
+            for i in 0..PROGRESS_BAR_WIDTH {
+                writer.write_str(if i < filled { "#" } else { "-" });
+            }
+            writer.write_str(&value);
+        });
+    }
+
+    /// Advances the spinner by one frame, for work of unknown length.
+    pub fn spin(&mut self) {
+        let frame = SPINNER[self.frame % SPINNER.len()];
+        self.frame += 1;
+        self.percent = None;
+        self.draw(|writer| {
+            writer.write_str(" ");
+            writer.write_str(frame);
+        });
+    }
+
+    /// Clears the line.
+    pub fn finish(mut self) {
+        self.clear();
+    }
+
+    fn draw(&mut self, body: impl FnOnce(&mut dyn UnifiedWriter)) {
+        let label = self.label;
+        with_progress_writer(|writer| {
+            writer.write_str("\r\x1B[K");
+            writer.write_str(label);
+            body(writer);
+            writer.flush();
+        });
+        self.shown = true;
+    }
+
+    fn clear(&mut self) {
+        if !self.shown {
+            return;
+        }
+        self.shown = false;
+        with_progress_writer(|writer| {
+            writer.write_str("\r\x1B[K");
+            writer.flush();
+        });
+        PROGRESS_CLEARED.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for ProgressReporter {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+/// Whether a progress line was cleared since the last call (called by the shell, which
+/// then draws the edit line again).
+pub fn take_progress_cleared() -> bool {
+    PROGRESS_CLEARED.swap(false, Ordering::Relaxed)
+}
+
+#[cfg(not(feature = "hosted"))]
+fn with_progress_writer(f: impl FnOnce(&mut dyn UnifiedWriter)) {
+    let _ = crate::logger::with_global_writer(f);
+}
+
+#[cfg(feature = "hosted")]
+fn with_progress_writer(f: impl FnOnce(&mut dyn UnifiedWriter)) {
+    f(&mut StdWriter);
+}
+
+/// Bytes shown per `hexdump` row.
+pub const HEXDUMP_ROW_LEN: usize = 16;
+
+/// A `hexdump` row: address, colon, the bytes, the ASCII column and the line end.
+const HEXDUMP_LINE_MAX: usize = 2 * core::mem::size_of::<usize>() + 4 * HEXDUMP_ROW_LEN + 8;
+
+/// Writes `data` as `xxd`-style rows of 16 bytes: the address of the row (counted from
+/// `base_addr`), the bytes in hex and the bytes as ASCII, `.` for the unprintable ones.
+///
+/// ```text
+/// 20000000: 48 65 6C 6C 6F 0D 0A 00 00 00 00 00 00 00 00 00  |Hello...........|
+/// 20000010: FF FF                                            |..|
+/// ```
+///
+/// Each row is written and flushed on its own, so a dump of any length goes through
+/// writers with a small transmit buffer.
+pub fn hexdump<W: UnifiedWriter + ?Sized>(writer: &mut W, base_addr: usize, data: &[u8]) {
+    for (row, chunk) in data.chunks(HEXDUMP_ROW_LEN).enumerate() {
+        let mut line = heapless::String::<HEXDUMP_LINE_MAX>::new();
+        let _ = write!(
+            line,
+            "{:0width$X}:",
+            base_addr.wrapping_add(row * HEXDUMP_ROW_LEN),
+            width = 2 * core::mem::size_of::<usize>()
+        );
+        for byte in chunk {
+            let _ = write!(line, " {:02X}", byte);
+        }
+        for _ in chunk.len()..HEXDUMP_ROW_LEN {
+            let _ = line.push_str("   ");
+        }
+        let _ = line.push_str("  |");
+        for &byte in chunk {
+            let shown = if byte.is_ascii_graphic() || byte == b' ' {
+                byte as char
+            } else {
+                '.'
+            };
+            let _ = line.push(shown);
+        }
+        let _ = line.push_str("|\r\n");
+        writer.write_str(&line);
+        writer.flush();
+    }
+}
+
 // Convenience type aliases
 #[cfg(feature = "hosted")]
 pub type StdDisplayRenderer = DisplayRenderer<StdWriter>;
@@ -189,6 +639,7 @@ pub type CallbackDisplayRenderer<W, F> = DisplayRenderer<CallbackWriter<W, F>>;
 
 #[cfg(test)]
 mod tests {
+    extern crate std;
     use super::*;
     use core::str;
 
@@ -224,6 +675,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_hexdump() {
+        let mut writer = MockWriter::new();
+        hexdump(&mut writer, 0x2000_0010, b"Hello\r\n\0 0123456789");
+        let width = 2 * core::mem::size_of::<usize>();
+        let expected = std::format!(
+            "{:0w$X}: 48 65 6C 6C 6F 0D 0A 00 20 30 31 32 33 34 35 36  |Hello... 0123456|\r\n\
+             {:0w$X}: 37 38 39{:41}|789|\r\n",
+            0x2000_0010,
+            0x2000_0020,
+            "",
+            w = width
+        );
+        assert_eq!(writer.as_str(), expected);
+
+        writer.buffer.clear();
+        hexdump(&mut writer, 0, &[]);
+        assert_eq!(writer.as_str(), "");
+    }
+
     #[test]
     fn test_render_clears_and_positions() {
         let mut renderer = DisplayRenderer::new(MockWriter::new());
@@ -235,6 +706,83 @@ mod tests {
         assert!(output.contains("Hello")); // Content
     }
 
+    #[test]
+    fn test_right_aligned_hint() {
+        let mut renderer = DisplayRenderer::new(MockWriter::new());
+        renderer.render_hinted("", ">", "led x", 5, "\x1B[31m✗ arg1\x1B[0m");
+        assert_eq!(
+            renderer.writer.as_str(),
+            "\r\x1B[K>led x\x1B[999C\x1B[6D\x1B[31m✗ arg1\x1B[0m\x1B[7G"
+        );
+    }
+
+    #[test]
+    fn test_colored_prompt_and_wide_input() {
+        let mut renderer = DisplayRenderer::new(MockWriter::new());
+        renderer.render("\x1B[32mdev\x1B[0m> ", "héllo", 5);
+        assert!(renderer.writer.as_str().ends_with("\x1B[11G"));
+    }
+
+    #[test]
+    fn test_theme() {
+        let mut renderer = DisplayRenderer::new(MockWriter::new());
+        renderer.set_theme(&theme::BRIGHT);
+        renderer.render(">> ", "led", 3);
+        assert_eq!(
+            renderer.writer.as_str(),
+            "\r\x1B[K\x1B[1;32m>> \x1B[0mled\x1B[7G"
+        );
+
+        renderer.writer.buffer.clear();
+        renderer.set_theme(&theme::PLAIN);
+        renderer.boundary_marker();
+        assert_eq!(renderer.writer.as_str(), "|\x1B[1D \x1B[1D");
+    }
+
+    #[test]
+    fn test_suggestion() {
+        let mut renderer = DisplayRenderer::new(MockWriter::new());
+        renderer.render_suggested("", ">", "le", "d ", 2, "");
+        assert_eq!(
+            renderer.writer.as_str(),
+            "\r\x1B[K>le\x1B[90md \x1B[0m\x1B[4G"
+        );
+
+        // Cut to the width of the terminal
+        renderer.writer.buffer.clear();
+        renderer.set_columns(Some(6));
+        renderer.render_suggested("", ">", "le", "d on", 2, "");
+        assert_eq!(
+            renderer.writer.as_str(),
+            "\r\x1B[K>le\x1B[90md \x1B[0m\x1B[4G"
+        );
+    }
+
+    #[test]
+    fn test_horizontal_scroll() {
+        let line = "0123456789abcdef";
+        let mut renderer = DisplayRenderer::new(MockWriter::new());
+        renderer.set_columns(Some(12));
+        let mut render = |cursor| {
+            renderer.writer.buffer.clear();
+            renderer.render(">", line, cursor);
+            std::string::String::from(renderer.writer.as_str())
+        };
+        // The end of the line, cut on the left
+        assert_eq!(render(16), "\r\x1B[K><89abcdef\x1B[11G");
+        assert_eq!(render(9), "\r\x1B[K><89abcdef\x1B[4G");
+        // Scrolled only as far as needed, the cursor kept off the marks
+        assert_eq!(render(8), "\r\x1B[K><89abcdef\x1B[3G");
+        assert_eq!(render(7), "\r\x1B[K><789abcdef\x1B[3G");
+        assert_eq!(render(0), "\r\x1B[K>012345678>\x1B[2G");
+        assert_eq!(render(9), "\r\x1B[K><23456789>\x1B[10G");
+
+        // A line that fits is written whole
+        renderer.writer.buffer.clear();
+        renderer.render(">", "0123456789", 10);
+        assert_eq!(renderer.writer.as_str(), "\r\x1B[K>0123456789\x1B[12G");
+    }
+
     #[test]
     fn test_bell() {
         let mut renderer = DisplayRenderer::new(MockWriter::new());
@@ -265,6 +813,44 @@ mod tests {
         assert!(output.contains("Hi"));
     }
 
+    #[test]
+    fn test_render_search() {
+        let mut renderer = DisplayRenderer::new(MockWriter::new());
+        renderer.render_search("ad", "read 1", false);
+        // Cursor on the match: 19 + 2 + 3 columns, then "re"
+        assert_eq!(
+            renderer.writer.as_str(),
+            "\r\x1B[K(reverse-i-search)`ad': read 1\x1B[27G"
+        );
+
+        let mut renderer = DisplayRenderer::new(MockWriter::new());
+        renderer.render_search("x", "read 1", true);
+        assert!(renderer
+            .writer
+            .as_str()
+            .starts_with("\r\x1B[K(failed reverse-i-search)`x': read 1"));
+    }
+
+    #[test]
+    fn test_page() {
+        let text = "one\n\rtwo\n\rthree\n\r";
+        let mut renderer = DisplayRenderer::new(MockWriter::new());
+        assert_eq!(renderer.page(0, 2, |w| w.write_str(text)), Some(2));
+        assert_eq!(
+            renderer.writer.as_str(),
+            "one\n\rtwo\n\r\r\x1B[7m--more--\x1B[0m"
+        );
+
+        let mut renderer = DisplayRenderer::new(MockWriter::new());
+        assert_eq!(renderer.page(2, 2, |w| w.write_str(text)), None);
+        assert_eq!(renderer.writer.as_str(), "three\n\r");
+
+        // Exactly one page: nothing left
+        let mut renderer = DisplayRenderer::new(MockWriter::new());
+        assert_eq!(renderer.page(0, 3, |w| w.write_str(text)), None);
+        assert_eq!(renderer.writer.as_str(), text);
+    }
+
     #[cfg(not(feature = "hosted"))]
     #[test]
     fn test_callback_writer() {
@@ -288,4 +874,38 @@ mod tests {
         assert_eq!(buffer.as_slice(), b"\x07");
         assert!(flush_called);
     }
+
+    #[cfg(not(feature = "hosted"))]
+    #[test]
+    fn test_progress_reporter() {
+        use crate::logger::{self, tests::written};
+        let _guard = logger::TEST_LOCK.lock().unwrap();
+        logger::tests::init();
+        take_progress_cleared();
+
+        let mut progress = ProgressReporter::new("erase");
+        progress.progress(0);
+        progress.progress(50);
+        progress.progress(50);
+        progress.progress(250);
+        assert_eq!(
+            written(),
+            "\r\x1B[Kerase [--------------------]   0%\
+             \r\x1B[Kerase [##########----------]  50%\
+             \r\x1B[Kerase [####################] 100%"
+        );
+        assert!(!take_progress_cleared());
+
+        logger::tests::init();
+        progress.spin();
+        progress.spin();
+        progress.finish();
+        assert_eq!(written(), "\r\x1B[Kerase |\r\x1B[Kerase /\r\x1B[K");
+        assert!(take_progress_cleared());
+        assert!(!take_progress_cleared());
+
+        // Nothing shown, nothing to clear
+        drop(ProgressReporter::new("idle"));
+        assert!(!take_progress_cleared());
+    }
 }
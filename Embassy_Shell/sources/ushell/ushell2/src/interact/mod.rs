@@ -0,0 +1,409 @@
+//! Interactive requests from command handlers.
+//!
+//! Handlers are called synchronously by the shell, which owns the input stream, so a
+//! handler cannot block waiting for a key. Instead it records a request and returns;
+//! the shell collects the answer and then runs the same command line again, at which
+//! point the handler receives the answer.
+//!
+//! ```ignore
+//! pub fn wipe() {
+//!     if !ushell2::confirm("All settings will be erased.") {
+//!         return; // first call (request recorded), or the user answered "n"
+//!     }
+//!     settings::erase();
+//! }
+//! ```
+//!
+//! ```text
+//! >> wipe
+//! All settings will be erased. Are you sure? [y/N] y
+//! ```
+//!
+//! Secrets are requested the same way with `read_hidden_line`; the typed text is echoed
+//! as `*`, kept out of the input buffer and history, and wiped once the handler read it.
+//!
+//! ```ignore
+//! pub fn wifi_join(ssid: &str) {
+//!     let mut password: heapless::String<32> = heapless::String::new();
+//!     if !ushell2::read_hidden_line("Password: ", &mut password) {
+//!         return;
+//!     }
+//!     wifi::join(ssid, &password);
+//! }
+//! ```
+
+use core::cell::{Cell, RefCell};
+use critical_section::Mutex;
+use heapless::String;
+
+/// Maximum length in bytes of a line read with `read_hidden_line`.
+pub const HIDDEN_LINE_MAX: usize = 64;
+
+/// Input the shell has to collect before a command can be completed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pending {
+    /// Ask a yes/no question; the text is shown before `Are you sure? [y/N]`.
+    Confirm(&'static str),
+    /// Read one line without echoing it; the text is shown as the prompt.
+    HiddenLine(&'static str),
+}
+
+/// Result of executing one command line, as reported to the input parser.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExecOutcome {
+    /// The command ran to completion.
+    Done,
+    /// The command asked for input; the parser collects it and runs the line again.
+    NeedsInput(Pending),
+}
+
+impl From<()> for ExecOutcome {
+    fn from(_: ()) -> Self {
+        ExecOutcome::Done
+    }
+}
+
+#[derive(Clone, Copy)]
+enum State {
+    Idle,
+    Requested(Pending),
+    Confirmed(bool),
+    HiddenLineReady,
+}
+
+static STATE: Mutex<Cell<State>> = Mutex::new(Cell::new(State::Idle));
+
+/// Storage for a hidden line; zeroed on `wipe` rather than just truncated.
+struct HiddenBuf {
+    bytes: [u8; HIDDEN_LINE_MAX],
+    len: usize,
+}
+
+impl HiddenBuf {
+    const fn new() -> Self {
+        Self {
+            bytes: [0; HIDDEN_LINE_MAX],
+            len: 0,
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.bytes[..self.len]).unwrap_or("")
+    }
+
+    fn wipe(&mut self) {
+        self.bytes = [0; HIDDEN_LINE_MAX];
+        self.len = 0;
+    }
+}
+
+static HIDDEN: Mutex<RefCell<HiddenBuf>> = Mutex::new(RefCell::new(HiddenBuf::new()));
+
+/// Ask the user to confirm the running command.
+///
+/// On the first invocation this records the request and returns `false`; the handler
+/// should return without side effects. After the user answers, the shell runs the same
+/// command line again and `confirm` returns the answer (`true` only for `y`/`Y`).
+pub fn confirm(prompt: &'static str) -> bool {
+    critical_section::with(|cs| {
+        let state = STATE.borrow(cs);
+        match state.get() {
+            State::Confirmed(answer) => {
+                state.set(State::Idle);
+                answer
+            }
+            _ => {
+                state.set(State::Requested(Pending::Confirm(prompt)));
+                false
+            }
+        }
+    })
+}
+
+/// Read a line from the user without echoing it (e.g. a password).
+///
+/// Works like `confirm`: the first invocation records the request and returns `false`.
+/// Once the user pressed Enter the shell runs the same command line again; `out` then
+/// receives the line (truncated to its capacity) and `true` is returned. The shell's
+/// copy is wiped afterwards. At most `HIDDEN_LINE_MAX` bytes are collected.
+pub fn read_hidden_line<const N: usize>(prompt: &'static str, out: &mut String<N>) -> bool {
+    critical_section::with(|cs| {
+        let state = STATE.borrow(cs);
+        match state.get() {
+            State::HiddenLineReady => {
+                state.set(State::Idle);
+                out.clear();
+                for ch in HIDDEN.borrow_ref(cs).as_str().chars() {
+                    if out.push(ch).is_err() {
+                        break;
+                    }
+                }
+                HIDDEN.borrow_ref_mut(cs).wipe();
+                true
+            }
+            _ => {
+                state.set(State::Requested(Pending::HiddenLine(prompt)));
+                false
+            }
+        }
+    })
+}
+
+/// Append a character to the hidden line; `false` if it does not fit.
+pub(crate) fn hidden_push(ch: char) -> bool {
+    critical_section::with(|cs| {
+        let mut hidden = HIDDEN.borrow_ref_mut(cs);
+        let len = hidden.len;
+        let width = ch.len_utf8();
+        if len + width > HIDDEN_LINE_MAX {
+            return false;
+        }
+        ch.encode_utf8(&mut hidden.bytes[len..len + width]);
+        hidden.len += width;
+        true
+    })
+}
+
+/// Remove the last character of the hidden line; `false` if it was empty.
+pub(crate) fn hidden_pop() -> bool {
+    critical_section::with(|cs| {
+        let mut hidden = HIDDEN.borrow_ref_mut(cs);
+        let Some(ch) = hidden.as_str().chars().next_back() else {
+            return false;
+        };
+        let len = hidden.len;
+        let new_len = len - ch.len_utf8();
+        hidden.bytes[new_len..len].fill(0);
+        hidden.len = new_len;
+        true
+    })
+}
+
+/// Mark the hidden line as complete before the command is run again.
+pub(crate) fn hidden_finish() {
+    critical_section::with(|cs| STATE.borrow(cs).set(State::HiddenLineReady));
+}
+
+/// Wipe the hidden line (new request, cancellation, or an unread line).
+pub(crate) fn hidden_wipe() {
+    critical_section::with(|cs| HIDDEN.borrow_ref_mut(cs).wipe());
+}
+
+/// Store the user's answer before the command is run again.
+pub(crate) fn answer_confirm(answer: bool) {
+    critical_section::with(|cs| STATE.borrow(cs).set(State::Confirmed(answer)));
+}
+
+/// Take the request recorded by the last handler, if any, and reset the state
+/// (an answer or hidden line the handler did not consume is dropped as well).
+pub(crate) fn take_request() -> Option<Pending> {
+    critical_section::with(|cs| {
+        HIDDEN.borrow_ref_mut(cs).wipe();
+        match STATE.borrow(cs).replace(State::Idle) {
+            State::Requested(pending) => Some(pending),
+            _ => None,
+        }
+    })
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::input::key_reader::Key;
+    use crate::input::parser::InputParser;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use heapless::String;
+
+    // The state is global; tests touching it must not run concurrently.
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    #[test]
+    fn test_confirm_round_trip() {
+        let _guard = LOCK.lock().unwrap();
+
+        // Nothing requested yet
+        let _ = take_request();
+        assert_eq!(take_request(), None);
+
+        // First call records the request
+        assert!(!confirm("Erase?"));
+        assert_eq!(take_request(), Some(Pending::Confirm("Erase?")));
+        assert_eq!(take_request(), None);
+
+        // Re-run after "y"
+        answer_confirm(true);
+        assert!(confirm("Erase?"));
+        assert_eq!(take_request(), None);
+
+        // Re-run after "n"
+        answer_confirm(false);
+        assert!(!confirm("Erase?"));
+        assert_eq!(take_request(), None);
+
+        // Unconsumed answer does not leak into the next command
+        answer_confirm(true);
+        assert_eq!(take_request(), None);
+        assert!(!confirm("Again?"));
+        assert_eq!(take_request(), Some(Pending::Confirm("Again?")));
+    }
+
+    // ============================================================================
+    // Parser Integration Tests
+    // ============================================================================
+
+    type TestParser = InputParser<'static, String<512>, 4, 8, 32, 64>;
+
+    static WIPED: AtomicUsize = AtomicUsize::new(0);
+
+    fn wipe() {
+        if confirm("Settings will be erased.") {
+            WIPED.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    // Mirrors the runner's `exec`: run the handler, then report a pending request.
+    fn exec(_cmd: &String<32>) -> ExecOutcome {
+        wipe();
+        match take_request() {
+            Some(pending) => ExecOutcome::NeedsInput(pending),
+            None => ExecOutcome::Done,
+        }
+    }
+
+    fn feed(parser: &mut TestParser, keys: &[Key]) {
+        for &key in keys {
+            let mut next = Some(key);
+            parser.parse_input(|| next.take(), |_| {}, exec);
+        }
+    }
+
+    fn typed(line: &str) -> std::vec::Vec<Key> {
+        let mut keys: std::vec::Vec<Key> = line.chars().map(Key::Char).collect();
+        keys.push(Key::Enter);
+        keys
+    }
+
+    #[test]
+    fn test_parser_confirmation_yes_and_no() {
+        let _guard = LOCK.lock().unwrap();
+        let _ = take_request();
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+        WIPED.store(0, Ordering::SeqCst);
+
+        feed(&mut parser, &typed("wipe"));
+        assert_eq!(WIPED.load(Ordering::SeqCst), 0);
+        feed(&mut parser, &[Key::Char('y')]);
+        assert_eq!(WIPED.load(Ordering::SeqCst), 1);
+
+        feed(&mut parser, &typed("wipe"));
+        feed(&mut parser, &[Key::Char('n')]);
+        assert_eq!(WIPED.load(Ordering::SeqCst), 1);
+
+        // Any other key, Enter included, answers no
+        feed(&mut parser, &typed("wipe"));
+        feed(&mut parser, &[Key::Enter]);
+        assert_eq!(WIPED.load(Ordering::SeqCst), 1);
+        assert_eq!(take_request(), None);
+    }
+
+    #[test]
+    fn test_hidden_line_round_trip() {
+        let _guard = LOCK.lock().unwrap();
+        let _ = take_request();
+        let mut out: String<8> = String::new();
+
+        assert!(!read_hidden_line("Password: ", &mut out));
+        assert_eq!(take_request(), Some(Pending::HiddenLine("Password: ")));
+
+        for ch in "sécret!".chars() {
+            assert!(hidden_push(ch));
+        }
+        assert!(hidden_pop());
+        hidden_finish();
+        assert!(read_hidden_line("Password: ", &mut out));
+        // "sécret" is 7 bytes and fits
+        assert_eq!(out.as_str(), "sécret");
+
+        // The shell's copy is wiped after reading
+        critical_section::with(|cs| {
+            let hidden = HIDDEN.borrow_ref(cs);
+            assert_eq!(hidden.len, 0);
+            assert!(hidden.bytes.iter().all(|&b| b == 0));
+        });
+        assert_eq!(take_request(), None);
+    }
+
+    #[test]
+    fn test_hidden_line_limits() {
+        let _guard = LOCK.lock().unwrap();
+        let _ = take_request();
+        assert!(!hidden_pop());
+        for _ in 0..HIDDEN_LINE_MAX {
+            assert!(hidden_push('x'));
+        }
+        assert!(!hidden_push('x'));
+
+        // Truncated to the capacity of `out`
+        hidden_finish();
+        let mut out: String<4> = String::new();
+        assert!(read_hidden_line("", &mut out));
+        assert_eq!(out.as_str(), "xxxx");
+
+        // An unread line is wiped by the next command
+        assert!(hidden_push('a'));
+        hidden_finish();
+        assert_eq!(take_request(), None);
+        assert!(!read_hidden_line("", &mut out));
+        let _ = take_request();
+    }
+
+    static JOINED: std::sync::Mutex<std::string::String> =
+        std::sync::Mutex::new(std::string::String::new());
+
+    fn exec_join(_cmd: &String<32>) -> ExecOutcome {
+        let mut password: String<16> = String::new();
+        if read_hidden_line("Password: ", &mut password) {
+            *JOINED.lock().unwrap() = std::string::String::from(password.as_str());
+        }
+        match take_request() {
+            Some(pending) => ExecOutcome::NeedsInput(pending),
+            None => ExecOutcome::Done,
+        }
+    }
+
+    #[test]
+    fn test_parser_hidden_line() {
+        let _guard = LOCK.lock().unwrap();
+        let _ = take_request();
+        let mut parser = TestParser::new(String::new(), &[], "", "", ">> ");
+        let mut feed_join = |keys: &[Key]| {
+            for &key in keys {
+                let mut next = Some(key);
+                parser.parse_input(|| next.take(), |_| {}, exec_join);
+            }
+        };
+
+        feed_join(&typed("join"));
+        feed_join(&[Key::Char('a'), Key::Char('b'), Key::Char('x')]);
+        feed_join(&[Key::Backspace, Key::Char('c'), Key::Enter]);
+        assert_eq!(JOINED.lock().unwrap().as_str(), "abc");
+
+        // Ctrl-D cancels without running the command again
+        JOINED.lock().unwrap().clear();
+        feed_join(&typed("join"));
+        feed_join(&[Key::Char('z'), Key::CtrlD]);
+        feed_join(&[Key::Enter]);
+        assert_eq!(JOINED.lock().unwrap().as_str(), "");
+        assert_eq!(take_request(), None);
+    }
+
+    #[test]
+    fn test_exec_outcome_from_unit() {
+        assert_eq!(ExecOutcome::from(()), ExecOutcome::Done);
+    }
+}
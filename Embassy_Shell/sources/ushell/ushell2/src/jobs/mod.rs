@@ -0,0 +1,406 @@
+//! Background jobs for long-running async commands.
+//!
+//! A command that takes seconds (a flash erase, a sensor sweep) freezes the shell when
+//! its handler blocks. Listed in `ShellConfig::async_commands` instead, its handler
+//! returns a future wrapped in a `Job`; the shell spawns it on a small fixed table and
+//! polls it while it keeps reading keys, so the prompt comes back at once.
+//!
+//! ```ignore
+//! fn erase(args: &str) -> Result<Job, JobError> {
+//!     let sector: u8 = args.parse().map_err(|_| JobError::BadArgs("usage: erase <sector>"))?;
+//!     Job::new(async move {
+//!         FLASH.lock().await.erase_sector(sector).await.map_err(|_| "erase failed")
+//!     })
+//! }
+//!
+//! const ASYNC_COMMANDS: &[(&str, AsyncCommand)] = &[("erase", erase)];
+//! ```
+//!
+//! ```text
+//! >> erase 3
+//! [ INFO] [1] erase 3
+//! >> erase 4
+//! [ INFO] [2] erase 4
+//! >> kill 1
+//! >> jobs
+//! [ INFO] [2] erase 4
+//! [2] erase 4: done          (printed above the edit line, see `notice`)
+//! ```
+//!
+//! Futures are stored in place, without allocation: a future larger than
+//! `JOB_FUTURE_SIZE` is refused. Jobs are polled inside a critical section, so each
+//! poll must be short: a job awaits between chunks of work rather than blocking. Output
+//! of a running job should go through `notice::println_above`, not the log, which would
+//! write into the middle of the edit line.
+//!
+//! A job whose command has a time budget (`ShellConfig::time_budget`) is stopped once it
+//! runs longer, reported as timed out and passed to `ShellConfig::on_command_timeout`.
+
+use core::cell::RefCell;
+use core::fmt::Write;
+use core::future::Future;
+use core::mem::{align_of, size_of, MaybeUninit};
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use critical_section::Mutex;
+use heapless::String;
+
+use crate::notice;
+
+/// Number of jobs that can run at the same time.
+pub const JOB_MAX: usize = 4;
+
+/// Maximum size in bytes of a job's future.
+pub const JOB_FUTURE_SIZE: usize = 256;
+
+/// Maximum length in bytes of a job name (the command line, cut).
+pub const JOB_NAME_MAX: usize = 24;
+
+/// What a job's future resolves to.
+pub type JobResult = Result<(), &'static str>;
+
+/// Handler of an async command: takes the arguments of the line and returns the job
+/// to run (see `Job::new`).
+pub type AsyncCommand = fn(args: &str) -> Result<Job, JobError>;
+
+/// Why a job was not started or not found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobError {
+    /// The future is larger than `JOB_FUTURE_SIZE`, or more strictly aligned than 8 bytes.
+    TooLarge,
+    /// `JOB_MAX` jobs are running.
+    Full,
+    /// No job with that id.
+    Unknown,
+    /// The command's arguments are wrong.
+    BadArgs(&'static str),
+}
+
+impl JobError {
+    pub const fn message(&self) -> &'static str {
+        match self {
+            JobError::TooLarge => "job future too large",
+            JobError::Full => "too many jobs (kill one first)",
+            JobError::Unknown => "no such job",
+            JobError::BadArgs(usage) => usage,
+        }
+    }
+}
+
+#[repr(C, align(8))]
+struct Storage([MaybeUninit<u8>; JOB_FUTURE_SIZE]);
+
+/// A future stored in place, ready to be spawned.
+pub struct Job {
+    storage: Storage,
+    poll: unsafe fn(*mut u8, &mut Context<'_>) -> Poll<JobResult>,
+    drop: unsafe fn(*mut u8),
+}
+
+impl Job {
+    /// Wraps `future`; fails if it does not fit `JOB_FUTURE_SIZE`.
+    pub fn new<F>(future: F) -> Result<Self, JobError>
+    where
+        F: Future<Output = JobResult> + Send + 'static,
+    {
+        if size_of::<F>() > JOB_FUTURE_SIZE || align_of::<F>() > align_of::<Storage>() {
+            return Err(JobError::TooLarge);
+        }
+        let mut storage = Storage([MaybeUninit::uninit(); JOB_FUTURE_SIZE]);
+        // Safety: the storage is large and aligned enough for `F` (checked above)
+        unsafe { storage.0.as_mut_ptr().cast::<F>().write(future) };
+        Ok(Self {
+            storage,
+            poll: poll_future::<F>,
+            drop: drop_future::<F>,
+        })
+    }
+
+    /// Polls the future. Once polled, the job must stay where it is until dropped: only
+    /// jobs in the static table are polled.
+    fn poll(&mut self, cx: &mut Context<'_>) -> Poll<JobResult> {
+        // Safety: the storage holds the future `poll` was made for
+        unsafe { (self.poll)(self.storage.0.as_mut_ptr().cast(), cx) }
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        // Safety: the storage holds the future `drop` was made for, dropped only here
+        unsafe { (self.drop)(self.storage.0.as_mut_ptr().cast()) }
+    }
+}
+
+unsafe fn poll_future<F: Future<Output = JobResult>>(
+    ptr: *mut u8,
+    cx: &mut Context<'_>,
+) -> Poll<JobResult> {
+    // The future is not moved once polled (see `Job::poll`)
+    Pin::new_unchecked(&mut *ptr.cast::<F>()).poll(cx)
+}
+
+unsafe fn drop_future<F>(ptr: *mut u8) {
+    ptr.cast::<F>().drop_in_place();
+}
+
+struct Entry {
+    id: u32,
+    name: String<JOB_NAME_MAX>,
+    job: Job,
+    started_ms: u64,
+    budget_ms: Option<u32>,
+}
+
+struct Table {
+    // Entries are never moved: a job is polled where it was spawned
+    slots: [Option<Entry>; JOB_MAX],
+    next_id: u32,
+}
+
+static JOBS: Mutex<RefCell<Table>> = Mutex::new(RefCell::new(Table {
+    slots: [const { None }; JOB_MAX],
+    next_id: 1,
+}));
+
+/// Starts `job` under `name` (cut to `JOB_NAME_MAX`) at `started_ms` and returns its id.
+/// With a `budget_ms`, `poll` stops the job once it has run longer than that.
+pub fn spawn(
+    name: &str,
+    job: Job,
+    started_ms: u64,
+    budget_ms: Option<u32>,
+) -> Result<u32, JobError> {
+    let mut short: String<JOB_NAME_MAX> = String::new();
+    for ch in name.chars() {
+        if short.push(ch).is_err() {
+            break;
+        }
+    }
+    critical_section::with(|cs| {
+        let mut table = JOBS.borrow_ref_mut(cs);
+        let table = &mut *table;
+        let slot = table
+            .slots
+            .iter_mut()
+            .find(|slot| slot.is_none())
+            .ok_or(JobError::Full)?;
+        let id = table.next_id;
+        table.next_id = table.next_id.wrapping_add(1).max(1);
+        *slot = Some(Entry {
+            id,
+            name: short,
+            job,
+            started_ms,
+            budget_ms,
+        });
+        Ok(id)
+    })
+}
+
+/// Stops the job `id`, dropping its future.
+pub fn kill(id: u32) -> Result<(), JobError> {
+    critical_section::with(|cs| {
+        JOBS.borrow_ref_mut(cs)
+            .slots
+            .iter_mut()
+            .find(|slot| slot.as_ref().is_some_and(|entry| entry.id == id))
+            .ok_or(JobError::Unknown)?
+            .take();
+        Ok(())
+    })
+}
+
+/// Calls `f` with the id and name of each running job, oldest first.
+pub fn for_each(mut f: impl FnMut(u32, &str)) {
+    critical_section::with(|cs| {
+        let table = JOBS.borrow_ref(cs);
+        let mut entries: heapless::Vec<&Entry, JOB_MAX> = table.slots.iter().flatten().collect();
+        entries.sort_unstable_by_key(|entry| entry.id);
+        for entry in entries {
+            f(entry.id, &entry.name);
+        }
+    });
+}
+
+/// The number of running jobs.
+pub fn count() -> usize {
+    critical_section::with(|cs| JOBS.borrow_ref(cs).slots.iter().flatten().count())
+}
+
+/// Polls every job once at `now_ms` (called by the shell). Finished jobs are removed and
+/// reported above the edit line. A job past its budget is dropped instead, reported as
+/// timed out and passed to `on_timeout` with its command name and run time.
+pub fn poll(cx: &mut Context<'_>, now_ms: u64, on_timeout: Option<fn(&str, u64)>) {
+    let mut timed_out: heapless::Vec<(String<JOB_NAME_MAX>, u64), JOB_MAX> = heapless::Vec::new();
+    critical_section::with(|cs| {
+        for slot in JOBS.borrow_ref_mut(cs).slots.iter_mut() {
+            let Some(entry) = slot else {
+                continue;
+            };
+            let elapsed = now_ms.saturating_sub(entry.started_ms);
+            if let Some(budget) = entry.budget_ms.filter(|&budget| elapsed > budget as u64) {
+                let mut msg: String<{ notice::NOTICE_MAX_LEN }> = String::new();
+                let _ = write!(
+                    msg,
+                    "[{}] {}: Error: timed out after {} ms (budget {} ms)",
+                    entry.id, entry.name, elapsed, budget
+                );
+                notice::println_above(&msg);
+                let _ = timed_out.push((entry.name.clone(), elapsed));
+                *slot = None;
+                continue;
+            }
+            let Poll::Ready(result) = entry.job.poll(cx) else {
+                continue;
+            };
+            let mut msg: String<{ notice::NOTICE_MAX_LEN }> = String::new();
+            let _ = match result {
+                Ok(()) => write!(msg, "[{}] {}: done", entry.id, entry.name),
+                Err(e) => write!(msg, "[{}] {}: Error: {}", entry.id, entry.name, e),
+            };
+            notice::println_above(&msg);
+            *slot = None;
+        }
+    });
+    // Outside the critical section: the callback may take its time
+    if let Some(on_timeout) = on_timeout {
+        for (name, elapsed) in &timed_out {
+            on_timeout(name.split_whitespace().next().unwrap_or(""), *elapsed);
+        }
+    }
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+pub(crate) mod tests {
+    extern crate std;
+    use super::*;
+    use core::task::Waker;
+    use std::vec::Vec as StdVec;
+
+    // Tests share the global table
+    pub(crate) static TEST_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    pub(crate) fn reset() {
+        critical_section::with(|cs| {
+            let mut table = JOBS.borrow_ref_mut(cs);
+            table.slots = [const { None }; JOB_MAX];
+            table.next_id = 1;
+        });
+        while notice::take().is_some() {}
+    }
+
+    pub(crate) fn poll_once() {
+        poll(&mut Context::from_waker(Waker::noop()), 0, None);
+    }
+
+    /// Ready with `result` on its `polls`-th poll.
+    pub(crate) struct Countdown {
+        pub(crate) polls: u32,
+        pub(crate) result: JobResult,
+    }
+
+    impl Future for Countdown {
+        type Output = JobResult;
+
+        fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<JobResult> {
+            self.polls -= 1;
+            if self.polls == 0 {
+                Poll::Ready(self.result)
+            } else {
+                Poll::Pending
+            }
+        }
+    }
+
+    fn listed() -> StdVec<std::string::String> {
+        let mut jobs = StdVec::new();
+        for_each(|id, name| jobs.push(std::format!("{} {}", id, name)));
+        jobs
+    }
+
+    #[test]
+    fn test_spawn_poll_kill() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        let _notices = notice::TEST_LOCK.lock().unwrap();
+        reset();
+
+        let short = Countdown {
+            polls: 1,
+            result: Ok(()),
+        };
+        let failing = Countdown {
+            polls: 2,
+            result: Err("erase failed"),
+        };
+        let endless = Countdown {
+            polls: u32::MAX,
+            result: Ok(()),
+        };
+        assert_eq!(spawn("blink", Job::new(short).unwrap(), 0, None), Ok(1));
+        assert_eq!(spawn("erase 3", Job::new(failing).unwrap(), 0, None), Ok(2));
+        assert_eq!(spawn("sweep", Job::new(endless).unwrap(), 0, None), Ok(3));
+        assert_eq!(listed(), ["1 blink", "2 erase 3", "3 sweep"]);
+
+        poll_once();
+        assert_eq!(notice::take().unwrap(), "[1] blink: done");
+        assert_eq!(listed(), ["2 erase 3", "3 sweep"]);
+        poll_once();
+        assert_eq!(notice::take().unwrap(), "[2] erase 3: Error: erase failed");
+        assert_eq!(notice::take(), None);
+
+        assert_eq!(kill(3), Ok(()));
+        assert_eq!(kill(3), Err(JobError::Unknown));
+        assert_eq!(count(), 0);
+    }
+
+    #[test]
+    fn test_bounds() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        reset();
+
+        let big = [0u8; JOB_FUTURE_SIZE + 1];
+        let too_large = async move { big.iter().map(|_| Ok(())).next().unwrap() };
+        assert!(matches!(Job::new(too_large), Err(JobError::TooLarge)));
+
+        for n in 0..JOB_MAX {
+            let endless = Countdown {
+                polls: u32::MAX,
+                result: Ok(()),
+            };
+            assert!(
+                spawn("sweep", Job::new(endless).unwrap(), 0, None).is_ok(),
+                "{}",
+                n
+            );
+        }
+        let extra = Countdown {
+            polls: 1,
+            result: Ok(()),
+        };
+        assert_eq!(
+            spawn("extra", Job::new(extra).unwrap(), 0, None),
+            Err(JobError::Full)
+        );
+        // Dropped in place
+        assert_eq!(kill(2), Ok(()));
+        assert_eq!(count(), JOB_MAX - 1);
+
+        let long = "x".repeat(JOB_NAME_MAX + 8);
+        let job = Countdown {
+            polls: 1,
+            result: Ok(()),
+        };
+        let id = spawn(&long, Job::new(job).unwrap(), 0, None).unwrap();
+        let mut name_len = 0;
+        for_each(|job_id, name| {
+            if job_id == id {
+                name_len = name.len();
+            }
+        });
+        assert_eq!(name_len, JOB_NAME_MAX);
+    }
+}
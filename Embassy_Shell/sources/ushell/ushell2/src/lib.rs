@@ -10,22 +10,49 @@ pub extern crate heapless;
 ))]
 extern crate std;
 
+pub mod alias;
+pub mod args;
 pub mod autocomplete;
+pub mod checksum;
+pub mod clock;
 pub mod history;
 pub mod input;
+pub mod interact;
+pub mod jobs;
 pub mod logger;
+pub mod memtools;
+pub mod notice;
+pub mod output;
+pub mod pipe;
+pub mod prompt;
+pub mod queue;
+pub mod record;
 pub mod runner;
+pub mod storage;
+pub mod stream;
 pub mod terminal;
+pub mod theme;
+pub mod tokens;
+pub mod transaction;
+pub mod vars;
+pub mod watch;
+pub mod xmodem;
 
 // Re-export commonly used types for easier importing
+pub use input::renderer::{hexdump, ProgressReporter};
 pub use input::parser::InputParser;
+pub use interact::{confirm, read_hidden_line};
+pub use notice::println_above;
+pub use logger::{begin_log_capture, end_log_capture};
 pub use terminal::RawMode;
 
 // Re-export items needed by logging macros
 // (macros use $crate:: which refers to the crate root)
 pub use logger::{
     get_buffer_size,
+    is_target_enabled,
     log_with_level, 
+    log_with_target,
     log_simple_message,
     LogLevel,
     FmtWrite,
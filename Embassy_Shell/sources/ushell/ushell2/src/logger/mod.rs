@@ -69,6 +69,33 @@ impl LogLevel {
         }
     }
 
+    /// Parses a level name (`error`, `warn`, `info`, `debug`, `verbose`, `trace`),
+    /// ignoring ASCII case.
+    pub fn from_name(name: &str) -> Option<LogLevel> {
+        [
+            LogLevel::Error,
+            LogLevel::Warn,
+            LogLevel::Info,
+            LogLevel::Debug,
+            LogLevel::Verbose,
+            LogLevel::Trace,
+        ]
+        .into_iter()
+        .find(|level| level.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Lowercase name, as accepted by `from_name`.
+    pub const fn name(&self) -> &'static str {
+        match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Verbose => "verbose",
+            LogLevel::Trace => "trace",
+        }
+    }
+
     /// Allows early exit before string formatting
     #[inline]
     pub const fn is_enabled(&self, min_level: LogLevel) -> bool {
@@ -168,8 +195,7 @@ impl Default for LoggerConfig {
 // Buffer size configuration - stored globally
 // ============================================================================
 
-#[cfg(not(feature = "hosted"))]
-use core::sync::atomic::{AtomicUsize, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, AtomicUsize, Ordering};
 
 #[cfg(not(feature = "hosted"))]
 static BUFFER_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_BUFFER_SIZE);
@@ -186,6 +212,154 @@ pub fn get_buffer_size() -> usize {
     BUFFER_SIZE.load(Ordering::Relaxed)
 }
 
+// ============================================================================
+// Timestamp prefix
+// ============================================================================
+
+/// Bytes the `[012345.678] ` prefix adds to a line, on top of the message and its
+/// level label (one more per extra digit of seconds, after 11.5 days of uptime).
+pub const TIMESTAMP_PREFIX_LEN: usize = 13;
+
+/// Uptime in milliseconds.
+type TimestampSource = fn() -> u64;
+
+static TIMESTAMP_SOURCE: critical_section::Mutex<core::cell::Cell<Option<TimestampSource>>> =
+    critical_section::Mutex::new(core::cell::Cell::new(None));
+
+/// Prefixes each line logged with a level with the uptime returned by `source`, in
+/// milliseconds:
+///
+/// ```text
+/// [000042.137] [ INFO] sensor ready
+/// ```
+///
+/// The prefix is written ahead of the message, so it takes nothing from the buffer the
+/// macros format into (see `set_buffer_size`); a writer receives `TIMESTAMP_PREFIX_LEN`
+/// more bytes per line. `log_simple!` lines, captured lines and the log ring are not
+/// stamped. Deferred lines keep the time they were logged at. `source` may be called
+/// from interrupts, like the macros.
+pub fn set_timestamp_source(source: fn() -> u64) {
+    critical_section::with(|cs| TIMESTAMP_SOURCE.borrow(cs).set(Some(source)));
+}
+
+/// Stops stamping lines.
+pub fn clear_timestamp_source() {
+    critical_section::with(|cs| TIMESTAMP_SOURCE.borrow(cs).set(None));
+}
+
+/// The time of a line logged now, `None` without a timestamp source.
+fn line_timestamp() -> Option<u64> {
+    critical_section::with(|cs| TIMESTAMP_SOURCE.borrow(cs).get()).map(|source| source())
+}
+
+/// Displays the `[012345.678] ` prefix of a line logged at `.0` milliseconds.
+struct TimestampPrefix(u64);
+
+impl fmt::Display for TimestampPrefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{:06}.{:03}] ", self.0 / 1000, self.0 % 1000)
+    }
+}
+
+// ============================================================================
+// Per-target level filters
+// ============================================================================
+
+/// Number of target filters `set_target_level` can hold.
+pub const LOG_TARGET_FILTERS: usize = 8;
+
+/// Longest target prefix of a filter, in bytes.
+pub const LOG_TARGET_MAX_LEN: usize = 16;
+
+/// Why a target filter was not set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetFilterError {
+    /// `LOG_TARGET_FILTERS` filters are set.
+    Full,
+    /// The prefix is empty or longer than `LOG_TARGET_MAX_LEN`.
+    BadPrefix,
+}
+
+impl TargetFilterError {
+    pub const fn message(&self) -> &'static str {
+        match self {
+            TargetFilterError::Full => "too many target filters",
+            TargetFilterError::BadPrefix => "bad target prefix",
+        }
+    }
+}
+
+type TargetFilters =
+    heapless::Vec<(heapless::String<LOG_TARGET_MAX_LEN>, LogLevel), LOG_TARGET_FILTERS>;
+
+static TARGET_LEVELS: critical_section::Mutex<core::cell::RefCell<TargetFilters>> =
+    critical_section::Mutex::new(core::cell::RefCell::new(heapless::Vec::new()));
+
+/// Sets the minimum level of the lines logged with a target starting with `prefix`
+/// (`log_info!(target: "led", ...)`), instead of the logger's `min_level`: `"led"`
+/// covers `led` and `led.blink`. The longest matching prefix wins; lines without a
+/// target follow `min_level` only.
+///
+/// ```ignore
+/// set_min_level(LogLevel::Trace);
+/// set_target_level("led", LogLevel::Warn); // silence the LED task
+/// ```
+pub fn set_target_level(prefix: &str, level: LogLevel) -> Result<(), TargetFilterError> {
+    let key = heapless::String::try_from(prefix)
+        .ok()
+        .filter(|key: &heapless::String<LOG_TARGET_MAX_LEN>| !key.is_empty())
+        .ok_or(TargetFilterError::BadPrefix)?;
+    critical_section::with(|cs| {
+        let mut filters = TARGET_LEVELS.borrow_ref_mut(cs);
+        match filters.iter_mut().find(|(known, _)| *known == key) {
+            Some(filter) => filter.1 = level,
+            None => filters
+                .push((key, level))
+                .map_err(|_| TargetFilterError::Full)?,
+        }
+        Ok(())
+    })
+}
+
+/// Removes the filter of `prefix`; returns `false` if there is none.
+pub fn clear_target_level(prefix: &str) -> bool {
+    critical_section::with(|cs| {
+        let mut filters = TARGET_LEVELS.borrow_ref_mut(cs);
+        let found = filters.iter().position(|(known, _)| known == prefix);
+        found.map(|index| filters.remove(index)).is_some()
+    })
+}
+
+/// Calls `f` with the prefix and level of each target filter, in the order they were set.
+pub fn for_each_target_level(mut f: impl FnMut(&str, LogLevel)) {
+    critical_section::with(|cs| {
+        for (prefix, level) in TARGET_LEVELS.borrow_ref(cs).iter() {
+            f(prefix, *level);
+        }
+    });
+}
+
+/// The level set for the longest prefix of `target`, if any.
+fn target_filter(target: &str) -> Option<LogLevel> {
+    critical_section::with(|cs| {
+        TARGET_LEVELS
+            .borrow_ref(cs)
+            .iter()
+            .filter(|(prefix, _)| target.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|&(_, level)| level)
+    })
+}
+
+/// Whether a line of `level` logged with `target` passes the filters: checked by the
+/// macros before formatting.
+pub fn is_target_enabled(target: &str, level: LogLevel) -> bool {
+    match target_filter(target) {
+        Some(min_level) => level.is_enabled(min_level),
+        None => level.is_enabled(min_level()),
+    }
+}
+
 // ============================================================================
 // For hosted environments (std) - use a global static logger
 // ============================================================================
@@ -211,7 +385,18 @@ impl GlobalLogger {
         if !level.is_enabled(self.config.min_level) {
             return;
         }
+        self.emit(line_timestamp(), level, message);
+    }
 
+    fn emit(&self, timestamp: Option<u64>, level: LogLevel, message: &str) {
+        record_log(Some(level), message);
+        if capture_log(Some(level), message) {
+            return;
+        }
+
+        if let Some(ms) = timestamp {
+            print!("{}", TimestampPrefix(ms));
+        }
         if self.config.color_entire_line {
             println!("{}[{}] {}{}", level.color(), level.label(), message, RESET);
         } else {
@@ -221,6 +406,10 @@ impl GlobalLogger {
 
     #[inline]
     fn log_simple(&self, message: &str) {
+        record_log(None, message);
+        if capture_log(None, message) {
+            return;
+        }
         println!("{}", message);
     }
 }
@@ -241,6 +430,16 @@ pub fn set_color_entire_line(enabled: bool) {
     }
 }
 
+#[cfg(feature = "hosted")]
+pub fn color_entire_line() -> bool {
+    match unsafe { &GLOBAL_LOGGER } {
+        Some(logger) => logger
+            .lock()
+            .is_ok_and(|guard| guard.config.color_entire_line),
+        None => false,
+    }
+}
+
 #[cfg(feature = "hosted")]
 pub fn set_min_level(level: LogLevel) {
     if let Some(logger) = unsafe { &GLOBAL_LOGGER } {
@@ -250,8 +449,24 @@ pub fn set_min_level(level: LogLevel) {
     }
 }
 
+#[cfg(feature = "hosted")]
+pub fn min_level() -> LogLevel {
+    match unsafe { &GLOBAL_LOGGER } {
+        Some(logger) => logger
+            .lock()
+            .map_or(LogLevel::Info, |guard| guard.config.min_level),
+        None => LogLevel::Info,
+    }
+}
+
 #[cfg(feature = "hosted")]
 pub fn log_with_level(level: LogLevel, message: &str) {
+    if is_deferred_logging() {
+        if level.is_enabled(min_level()) {
+            defer_log(Some(level), message);
+        }
+        return;
+    }
     if let Some(logger) = unsafe { &GLOBAL_LOGGER } {
         if let Ok(guard) = logger.lock() {
             guard.log(level, message);
@@ -259,12 +474,42 @@ pub fn log_with_level(level: LogLevel, message: &str) {
     }
 }
 
+#[cfg(feature = "hosted")]
+pub fn log_with_target(target: &str, level: LogLevel, message: &str) {
+    if !is_target_enabled(target, level) {
+        return;
+    }
+    if is_deferred_logging() {
+        defer_log(Some(level), message);
+        return;
+    }
+    if let Some(logger) = unsafe { &GLOBAL_LOGGER } {
+        if let Ok(guard) = logger.lock() {
+            guard.emit(line_timestamp(), level, message);
+        }
+    }
+}
+
 #[cfg(feature = "hosted")]
 #[inline]
 pub fn log_simple_message(message: &str) {
+    if is_deferred_logging() {
+        defer_log(None, message);
+        return;
+    }
+    emit_now(None, None, message);
+}
+
+/// Writes a line at once, past the filters and the deferred mode; `timestamp` is the
+/// time it was logged at, if stamped.
+#[cfg(feature = "hosted")]
+fn emit_now(timestamp: Option<u64>, level: Option<LogLevel>, message: &str) {
     if let Some(logger) = unsafe { &GLOBAL_LOGGER } {
         if let Ok(guard) = logger.lock() {
-            guard.log_simple(message);
+            match level {
+                Some(level) => guard.emit(timestamp, level, message),
+                None => guard.log_simple(message),
+            }
         }
     }
 }
@@ -273,30 +518,74 @@ pub fn log_simple_message(message: &str) {
 // For no_std environments - use a global logger with writer
 // ============================================================================
 
+/// Most writers the logger fans its lines out to (see `init_logger_with_sinks`).
+#[cfg(not(feature = "hosted"))]
+pub const LOG_SINKS: usize = 4;
+
+/// A writer of the logger and the least severe level of the lines it receives.
+#[cfg(not(feature = "hosted"))]
+pub struct LogSink {
+    pub writer: &'static mut dyn LogWriter,
+    pub min_level: LogLevel,
+}
+
+#[cfg(not(feature = "hosted"))]
+impl LogSink {
+    pub fn new(writer: &'static mut dyn LogWriter, min_level: LogLevel) -> Self {
+        Self { writer, min_level }
+    }
+}
+
 #[cfg(not(feature = "hosted"))]
 struct GlobalLoggerWrapper {
     config: LoggerConfig,
-    writer: &'static mut dyn LogWriter,
+    /// Never empty; the first one is the shell's writer.
+    sinks: heapless::Vec<LogSink, LOG_SINKS>,
 }
 
 #[cfg(not(feature = "hosted"))]
 impl GlobalLoggerWrapper {
-    fn new(config: LoggerConfig, writer: &'static mut dyn LogWriter) -> Self {
-        Self { config, writer }
+    fn new(config: LoggerConfig, sinks: impl IntoIterator<Item = LogSink>) -> Self {
+        Self {
+            config,
+            sinks: sinks.into_iter().collect(),
+        }
     }
 
     fn log(&mut self, level: LogLevel, message: &str) {
         if !level.is_enabled(self.config.min_level) {
             return;
         }
+        self.emit(line_timestamp(), level, message);
+    }
+
+    fn emit(&mut self, timestamp: Option<u64>, level: LogLevel, message: &str) {
+        record_log(Some(level), message);
+        if capture_log(Some(level), message) {
+            return;
+        }
 
-        self.writer
-            .write_log(level, message, self.config.color_entire_line);
+        for sink in self.sinks.iter_mut() {
+            if !level.is_enabled(sink.min_level) {
+                continue;
+            }
+            if let Some(ms) = timestamp {
+                let _ = write!(sink.writer, "{}", TimestampPrefix(ms));
+            }
+            sink.writer
+                .write_log(level, message, self.config.color_entire_line);
+        }
     }
 
     #[inline]
     fn log_simple(&mut self, message: &str) {
-        self.writer.write_simple(message);
+        record_log(None, message);
+        if capture_log(None, message) {
+            return;
+        }
+        for sink in self.sinks.iter_mut() {
+            sink.writer.write_simple(message);
+        }
     }
 }
 
@@ -305,11 +594,52 @@ static GLOBAL_LOGGER: Mutex<RefCell<Option<GlobalLoggerWrapper>>> = Mutex::new(R
 
 #[cfg(not(feature = "hosted"))]
 pub fn init_logger(config: LoggerConfig, writer: &'static mut dyn LogWriter) {
+    init_logger_with_sinks(config, [LogSink::new(writer, LogLevel::Trace)]);
+}
+
+/// Installs a logger writing each line to every sink whose `min_level` it passes; lines
+/// of `log_simple!` go to all of them. The first sink is the shell's writer (see
+/// `with_global_writer`). Lines below `config.min_level` are dropped before any sink,
+/// so it has to be at least as verbose as the most verbose sink:
+///
+/// ```ignore
+/// // Readable logs on the shell UART, everything on ITM for offline analysis
+/// init_logger_with_sinks(
+///     LoggerConfig { color_entire_line: false, min_level: LogLevel::Trace },
+///     [
+///         LogSink::new(UART_WRITER.init(UartWriter::new()), LogLevel::Info),
+///         LogSink::new(ITM_WRITER.init(ItmWriter::new(0)), LogLevel::Trace),
+///     ],
+/// );
+/// ```
+///
+/// `K` is checked at compile time to be `1..=LOG_SINKS`.
+#[cfg(not(feature = "hosted"))]
+pub fn init_logger_with_sinks<const K: usize>(config: LoggerConfig, sinks: [LogSink; K]) {
+    const {
+        assert!(
+            K >= 1 && K <= LOG_SINKS,
+            "init_logger takes 1..=LOG_SINKS sinks"
+        )
+    };
     critical_section::with(|cs| {
-        *GLOBAL_LOGGER.borrow_ref_mut(cs) = Some(GlobalLoggerWrapper::new(config, writer));
+        *GLOBAL_LOGGER.borrow_ref_mut(cs) = Some(GlobalLoggerWrapper::new(config, sinks));
     });
 }
 
+/// Changes the level of the sink at `index` (in the order passed to
+/// `init_logger_with_sinks`); returns `false` if there is none.
+#[cfg(not(feature = "hosted"))]
+pub fn set_sink_level(index: usize, level: LogLevel) -> bool {
+    critical_section::with(|cs| {
+        let mut logger = GLOBAL_LOGGER.borrow_ref_mut(cs);
+        let sink = logger
+            .as_mut()
+            .and_then(|logger| logger.sinks.get_mut(index));
+        sink.map(|sink| sink.min_level = level).is_some()
+    })
+}
+
 #[cfg(not(feature = "hosted"))]
 pub fn set_color_entire_line(enabled: bool) {
     critical_section::with(|cs| {
@@ -319,6 +649,17 @@ pub fn set_color_entire_line(enabled: bool) {
     });
 }
 
+/// Whether whole lines are colored by level, not only their label.
+#[cfg(not(feature = "hosted"))]
+pub fn color_entire_line() -> bool {
+    critical_section::with(|cs| {
+        GLOBAL_LOGGER
+            .borrow_ref(cs)
+            .as_ref()
+            .is_some_and(|logger| logger.config.color_entire_line)
+    })
+}
+
 #[cfg(not(feature = "hosted"))]
 pub fn set_min_level(level: LogLevel) {
     critical_section::with(|cs| {
@@ -328,8 +669,25 @@ pub fn set_min_level(level: LogLevel) {
     });
 }
 
+/// The logger's minimum level (`Info` before `init_logger`).
+#[cfg(not(feature = "hosted"))]
+pub fn min_level() -> LogLevel {
+    critical_section::with(|cs| {
+        GLOBAL_LOGGER
+            .borrow_ref(cs)
+            .as_ref()
+            .map_or(LogLevel::Info, |logger| logger.config.min_level)
+    })
+}
+
 #[cfg(not(feature = "hosted"))]
 pub fn log_with_level(level: LogLevel, message: &str) {
+    if is_deferred_logging() {
+        if level.is_enabled(min_level()) {
+            defer_log(Some(level), message);
+        }
+        return;
+    }
     critical_section::with(|cs| {
         if let Some(logger) = GLOBAL_LOGGER.borrow_ref_mut(cs).as_mut() {
             logger.log(level, message);
@@ -337,20 +695,399 @@ pub fn log_with_level(level: LogLevel, message: &str) {
     });
 }
 
+/// Logs a line of `target` that passes the target filters (see `set_target_level`).
+#[cfg(not(feature = "hosted"))]
+pub fn log_with_target(target: &str, level: LogLevel, message: &str) {
+    if !is_target_enabled(target, level) {
+        return;
+    }
+    if is_deferred_logging() {
+        defer_log(Some(level), message);
+        return;
+    }
+    critical_section::with(|cs| {
+        if let Some(logger) = GLOBAL_LOGGER.borrow_ref_mut(cs).as_mut() {
+            logger.emit(line_timestamp(), level, message);
+        }
+    });
+}
+
 #[cfg(not(feature = "hosted"))]
 #[inline]
 pub fn log_simple_message(message: &str) {
+    if is_deferred_logging() {
+        defer_log(None, message);
+        return;
+    }
+    emit_now(None, None, message);
+}
+
+/// Writes a line at once, past the filters and the deferred mode; `timestamp` is the
+/// time it was logged at, if stamped.
+#[cfg(not(feature = "hosted"))]
+fn emit_now(timestamp: Option<u64>, level: Option<LogLevel>, message: &str) {
     critical_section::with(|cs| {
         if let Some(logger) = GLOBAL_LOGGER.borrow_ref_mut(cs).as_mut() {
-            logger.log_simple(message);
+            match level {
+                Some(level) => logger.emit(timestamp, level, message),
+                None => logger.log_simple(message),
+            }
         }
     });
 }
 
+// ============================================================================
+// Deferred logging (lines queued by ISRs, written by `drain_logs`)
+// ============================================================================
+
+/// Number of lines the deferred mode holds until they are drained.
+pub const DEFERRED_LOG_SLOTS: usize = 16;
+
+/// Longest deferred message; longer ones are cut at a char boundary.
+pub const DEFERRED_LOG_MESSAGE: usize = 80;
+
+const SLOT_FREE: u8 = 0;
+const SLOT_WRITING: u8 = 1;
+const SLOT_READY: u8 = 2;
+
+/// A queued line: its ticket (the order lines were logged in), time, level and text.
+struct DeferredRecord {
+    ticket: u32,
+    timestamp: Option<u64>,
+    level: Option<LogLevel>,
+    len: usize,
+    text: [u8; DEFERRED_LOG_MESSAGE],
+}
+
+/// A slot of the deferred queue, owned by whoever moved its state away from free.
+struct DeferredSlot {
+    state: AtomicU8,
+    record: core::cell::UnsafeCell<DeferredRecord>,
+}
+
+// Safety: the record is only accessed by the producer that set the slot to writing,
+// or by the single drainer once the slot is ready
+unsafe impl Sync for DeferredSlot {}
+
+impl DeferredSlot {
+    const fn new() -> Self {
+        Self {
+            state: AtomicU8::new(SLOT_FREE),
+            record: core::cell::UnsafeCell::new(DeferredRecord {
+                ticket: 0,
+                timestamp: None,
+                level: None,
+                len: 0,
+                text: [0; DEFERRED_LOG_MESSAGE],
+            }),
+        }
+    }
+}
+
+static DEFERRED: AtomicBool = AtomicBool::new(false);
+static DEFERRED_SLOTS: [DeferredSlot; DEFERRED_LOG_SLOTS] =
+    [const { DeferredSlot::new() }; DEFERRED_LOG_SLOTS];
+static DEFERRED_TICKET: AtomicU32 = AtomicU32::new(0);
+static DEFERRED_DROPPED: AtomicU32 = AtomicU32::new(0);
+static DRAINING: AtomicBool = AtomicBool::new(false);
+
+/// Switches the deferred mode: log macros format their line on the caller's stack and
+/// only copy it into a lock-free queue, without taking the logger's critical section or
+/// waiting for the writer, so logging from a high-priority ISR does not hold up the UART
+/// interrupt. The lines are written by `drain_logs`, which the shell calls on every poll
+/// (an idle task can call it too).
+///
+/// Filters are applied when a line is logged, the log ring and captures are fed when it
+/// is drained. Lines logged while the queue is full are dropped and counted. Switching
+/// the mode off drains the lines left.
+pub fn set_deferred_logging(enabled: bool) {
+    DEFERRED.store(enabled, Ordering::Relaxed);
+    if !enabled {
+        drain_logs();
+    }
+}
+
+/// Whether the deferred mode is on (see `set_deferred_logging`).
+pub fn is_deferred_logging() -> bool {
+    DEFERRED.load(Ordering::Relaxed)
+}
+
+/// Whether deferred lines are waiting to be drained.
+pub fn has_deferred_logs() -> bool {
+    DEFERRED_SLOTS
+        .iter()
+        .any(|slot| slot.state.load(Ordering::Acquire) == SLOT_READY)
+        || DEFERRED_DROPPED.load(Ordering::Relaxed) > 0
+}
+
+/// Queues a line; returns `false` if its slot is taken (the line is dropped).
+fn defer_log(level: Option<LogLevel>, message: &str) -> bool {
+    let ticket = DEFERRED_TICKET.fetch_add(1, Ordering::Relaxed);
+    let slot = &DEFERRED_SLOTS[ticket as usize % DEFERRED_LOG_SLOTS];
+    if slot
+        .state
+        .compare_exchange(
+            SLOT_FREE,
+            SLOT_WRITING,
+            Ordering::Acquire,
+            Ordering::Relaxed,
+        )
+        .is_err()
+    {
+        DEFERRED_DROPPED.fetch_add(1, Ordering::Relaxed);
+        return false;
+    }
+    // Safety: setting the slot to writing made this call its only user
+    let record = unsafe { &mut *slot.record.get() };
+    let mut cut = message.len().min(DEFERRED_LOG_MESSAGE);
+    while !message.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    record.ticket = ticket;
+    // Stamped when logged, not when drained
+    record.timestamp = level.and_then(|_| line_timestamp());
+    record.level = level;
+    record.len = cut;
+    record.text[..cut].copy_from_slice(&message.as_bytes()[..cut]);
+    slot.state.store(SLOT_READY, Ordering::Release);
+    true
+}
+
+/// Writes the deferred lines, oldest first, then a warning with the number of lines
+/// dropped since the last drain, if any. Returns the number of lines written. Does
+/// nothing when called while another call is draining.
+pub fn drain_logs() -> usize {
+    if DRAINING.swap(true, Ordering::Acquire) {
+        return 0;
+    }
+    let mut count = 0;
+    loop {
+        // The ready line logged the longest ago
+        let now = DEFERRED_TICKET.load(Ordering::Relaxed);
+        let oldest = DEFERRED_SLOTS
+            .iter()
+            .filter(|slot| slot.state.load(Ordering::Acquire) == SLOT_READY)
+            // Safety: a ready slot is only accessed by the drainer
+            .max_by_key(|slot| now.wrapping_sub(unsafe { (*slot.record.get()).ticket }));
+        let Some(slot) = oldest else {
+            break;
+        };
+        let mut text: heapless::String<DEFERRED_LOG_MESSAGE> = heapless::String::new();
+        // Safety: as above; the slot is freed once the line is copied
+        let (timestamp, level) = unsafe {
+            let record = &*slot.record.get();
+            let _ = text.push_str(core::str::from_utf8(&record.text[..record.len]).unwrap_or(""));
+            (record.timestamp, record.level)
+        };
+        slot.state.store(SLOT_FREE, Ordering::Release);
+        emit_now(timestamp, level, &text);
+        count += 1;
+    }
+    let dropped = DEFERRED_DROPPED.swap(0, Ordering::Relaxed);
+    if dropped > 0 {
+        let mut text: heapless::String<40> = heapless::String::new();
+        let _ = write!(text, "{} deferred log lines dropped", dropped);
+        emit_now(line_timestamp(), Some(LogLevel::Warn), &text);
+    }
+    DRAINING.store(false, Ordering::Release);
+    count
+}
+
+// ============================================================================
+// Scoped log capture (used by protocol mode)
+// ============================================================================
+
+/// Capacity in bytes of the buffer collecting log lines during a capture.
+pub const LOG_CAPTURE_SIZE: usize = 256;
+
+struct LogCapture {
+    text: heapless::String<LOG_CAPTURE_SIZE>,
+    truncated: bool,
+}
+
+static LOG_CAPTURE: critical_section::Mutex<core::cell::RefCell<Option<LogCapture>>> =
+    critical_section::Mutex::new(core::cell::RefCell::new(None));
+
+/// Start capturing: until `end_log_capture`, log lines that pass the level filter are
+/// collected (without colors, one per `\n`) instead of being written. A capture that is
+/// already active is restarted.
+pub fn begin_log_capture() {
+    critical_section::with(|cs| {
+        *LOG_CAPTURE.borrow_ref_mut(cs) = Some(LogCapture {
+            text: heapless::String::new(),
+            truncated: false,
+        });
+    });
+}
+
+/// Stop capturing and pass the collected lines to `f`, together with a flag telling
+/// whether lines were dropped because the buffer was full. Without an active capture
+/// `f` receives `("", false)`. Deferred lines are drained into the capture first.
+pub fn end_log_capture<R>(f: impl FnOnce(&str, bool) -> R) -> R {
+    // Deferred lines of the captured command belong to the capture
+    drain_logs();
+    match critical_section::with(|cs| LOG_CAPTURE.borrow_ref_mut(cs).take()) {
+        Some(capture) => f(capture.text.as_str(), capture.truncated),
+        None => f("", false),
+    }
+}
+
+/// Whether a capture is active: log lines are collected instead of written.
+pub fn is_log_capture_active() -> bool {
+    critical_section::with(|cs| LOG_CAPTURE.borrow_ref(cs).is_some())
+}
+
+/// Append one line to the active capture; returns `false` if no capture is active.
+/// Lines that do not fit entirely are dropped.
+fn capture_log(level: Option<LogLevel>, message: &str) -> bool {
+    critical_section::with(|cs| {
+        let mut capture = LOG_CAPTURE.borrow_ref_mut(cs);
+        let Some(capture) = capture.as_mut() else {
+            return false;
+        };
+        let checkpoint = capture.text.len();
+        let fits = match level {
+            Some(level) => writeln!(capture.text, "[{}] {}", level.label(), message),
+            None => writeln!(capture.text, "{}", message),
+        }
+        .is_ok();
+        if !fits {
+            capture.text.truncate(checkpoint);
+            capture.truncated = true;
+        }
+        true
+    })
+}
+
+// ============================================================================
+// Ring log sink (read back by the `log show` built-in)
+// ============================================================================
+
+/// Longest message kept by a `RingLogSink`; longer ones are cut at a char boundary.
+pub const RING_LOG_MAX_MESSAGE: usize = 255;
+
+/// Level byte of lines logged with `log_simple!`.
+const RING_LOG_SIMPLE: u8 = 0xFF;
+
+/// Keeps the most recent log lines in a caller-provided buffer, dropping the oldest
+/// ones when it is full. Each line takes its length plus 2 bytes: `[level][len][text]`.
+pub struct RingLogSink<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> RingLogSink<'a> {
+    pub fn new(buffer: &'a mut [u8]) -> Self {
+        Self { buffer, len: 0 }
+    }
+
+    /// Appends a line (`level` is `None` for `log_simple!` lines), evicting the oldest
+    /// ones to make room.
+    pub fn push(&mut self, level: Option<LogLevel>, message: &str) {
+        let mut cut = message
+            .len()
+            .min(RING_LOG_MAX_MESSAGE)
+            .min(self.buffer.len().saturating_sub(2));
+        while !message.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        let size = 2 + cut;
+        if size > self.buffer.len() {
+            return;
+        }
+        while self.len + size > self.buffer.len() {
+            let oldest = 2 + self.buffer[1] as usize;
+            self.buffer.copy_within(oldest..self.len, 0);
+            self.len -= oldest;
+        }
+        self.buffer[self.len] = level.map_or(RING_LOG_SIMPLE, |level| level as u8);
+        self.buffer[self.len + 1] = cut as u8;
+        self.buffer[self.len + 2..self.len + size].copy_from_slice(&message.as_bytes()[..cut]);
+        self.len += size;
+    }
+
+    /// Forgets all lines.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Number of lines kept.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The kept lines, oldest first.
+    pub fn iter(&self) -> RingLogIter<'_> {
+        RingLogIter {
+            data: &self.buffer[..self.len],
+        }
+    }
+}
+
+/// Iterator over the lines of a `RingLogSink`, see `RingLogSink::iter`.
+pub struct RingLogIter<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for RingLogIter<'a> {
+    type Item = (Option<LogLevel>, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let [level, len, rest @ ..] = self.data else {
+            return None;
+        };
+        let (text, rest) = rest.split_at(*len as usize);
+        self.data = rest;
+        let level = match level {
+            0 => Some(LogLevel::Error),
+            1 => Some(LogLevel::Warn),
+            2 => Some(LogLevel::Info),
+            3 => Some(LogLevel::Debug),
+            4 => Some(LogLevel::Verbose),
+            5 => Some(LogLevel::Trace),
+            _ => None,
+        };
+        // `push` only cuts at char boundaries
+        Some((level, core::str::from_utf8(text).unwrap_or("")))
+    }
+}
+
+static LOG_RING: critical_section::Mutex<core::cell::RefCell<Option<RingLogSink<'static>>>> =
+    critical_section::Mutex::new(core::cell::RefCell::new(None));
+
+/// Keeps a copy of every log line that passes the level filter in `buffer`, for the
+/// `log show` built-in. Replaces a previously attached buffer.
+pub fn attach_log_ring(buffer: &'static mut [u8]) {
+    critical_section::with(|cs| {
+        *LOG_RING.borrow_ref_mut(cs) = Some(RingLogSink::new(buffer));
+    });
+}
+
+/// Runs `f` on the attached ring, `None` if there is none. `f` must not log.
+pub fn with_log_ring<R>(f: impl FnOnce(&mut RingLogSink<'static>) -> R) -> Option<R> {
+    critical_section::with(|cs| LOG_RING.borrow_ref_mut(cs).as_mut().map(f))
+}
+
+fn record_log(level: Option<LogLevel>, message: &str) {
+    with_log_ring(|ring| ring.push(level, message));
+}
+
+#[cfg(test)]
+extern crate std;
+
+/// Serializes tests that use the global logger, capture or ring.
+#[cfg(test)]
+pub(crate) static TEST_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
 // ============================================================================
 // Get a reference to the global writer for shell use
 // ============================================================================
 
+/// Runs `f` on the writer of the logger's first sink.
 #[cfg(not(feature = "hosted"))]
 pub fn with_global_writer<F, R>(f: F) -> Option<R>
 where
@@ -358,7 +1095,7 @@ where
 {
     critical_section::with(|cs| {
         if let Some(logger) = GLOBAL_LOGGER.borrow_ref_mut(cs).as_mut() {
-            Some(f(logger.writer))
+            Some(f(&mut *logger.sinks[0].writer))
         } else {
             None
         }
@@ -457,6 +1194,12 @@ pub const fn select_buffer_size(size: usize) -> usize {
 #[doc(hidden)]
 #[macro_export]
 macro_rules! __log_with_size {
+    (target: $target:expr, $level:expr, $size:literal, $($arg:tt)*) => {{
+        use $crate::FmtWrite as _;
+        let mut msg_buf = $crate::heapless::String::<$size>::new();
+        let _ = ::core::write!(&mut msg_buf, $($arg)*);
+        $crate::log_with_target($target, $level, msg_buf.as_str());
+    }};
     ($level:expr, $size:literal, $($arg:tt)*) => {{
         use $crate::FmtWrite as _;
         let mut msg_buf = $crate::heapless::String::<$size>::new();
@@ -479,6 +1222,29 @@ macro_rules! __log_simple_with_size {
 
 #[macro_export]
 macro_rules! log {
+    (target: $target:expr, $level:expr, $($arg:tt)*) => {{
+        let (target, level) = ($target, $level);
+        // Filtered out before formatting
+        if $crate::is_target_enabled(target, level) {
+            #[cfg(not(feature = "hosted"))]
+            {
+                let size = $crate::get_buffer_size();
+                match size {
+                    0..=64 => $crate::__log_with_size!(target: target, level, 64, $($arg)*),
+                    65..=128 => $crate::__log_with_size!(target: target, level, 128, $($arg)*),
+                    129..=256 => $crate::__log_with_size!(target: target, level, 256, $($arg)*),
+                    257..=512 => $crate::__log_with_size!(target: target, level, 512, $($arg)*),
+                    513..=1024 => $crate::__log_with_size!(target: target, level, 1024, $($arg)*),
+                    1025..=2048 => $crate::__log_with_size!(target: target, level, 2048, $($arg)*),
+                    _ => $crate::__log_with_size!(target: target, level, 4096, $($arg)*),
+                }
+            }
+            #[cfg(feature = "hosted")]
+            {
+                $crate::log_with_target(target, level, &format!($($arg)*));
+            }
+        }
+    }};
     ($level:expr, $($arg:tt)*) => {{
         #[cfg(not(feature = "hosted"))]
         {
@@ -520,6 +1286,9 @@ macro_rules! log_with_buffer_size {
 
 #[macro_export]
 macro_rules! log_error {
+    (target: $target:expr, $($arg:tt)*) => {
+        $crate::log!(target: $target, $crate::LogLevel::Error, $($arg)*)
+    };
     ($($arg:tt)*) => {
         $crate::log!($crate::LogLevel::Error, $($arg)*)
     };
@@ -527,6 +1296,9 @@ macro_rules! log_error {
 
 #[macro_export]
 macro_rules! log_warn {
+    (target: $target:expr, $($arg:tt)*) => {
+        $crate::log!(target: $target, $crate::LogLevel::Warn, $($arg)*)
+    };
     ($($arg:tt)*) => {
         $crate::log!($crate::LogLevel::Warn, $($arg)*)
     };
@@ -534,6 +1306,9 @@ macro_rules! log_warn {
 
 #[macro_export]
 macro_rules! log_info {
+    (target: $target:expr, $($arg:tt)*) => {
+        $crate::log!(target: $target, $crate::LogLevel::Info, $($arg)*)
+    };
     ($($arg:tt)*) => {
         $crate::log!($crate::LogLevel::Info, $($arg)*)
     };
@@ -541,6 +1316,9 @@ macro_rules! log_info {
 
 #[macro_export]
 macro_rules! log_debug {
+    (target: $target:expr, $($arg:tt)*) => {
+        $crate::log!(target: $target, $crate::LogLevel::Debug, $($arg)*)
+    };
     ($($arg:tt)*) => {
         $crate::log!($crate::LogLevel::Debug, $($arg)*)
     };
@@ -548,6 +1326,9 @@ macro_rules! log_debug {
 
 #[macro_export]
 macro_rules! log_verbose {
+    (target: $target:expr, $($arg:tt)*) => {
+        $crate::log!(target: $target, $crate::LogLevel::Verbose, $($arg)*)
+    };
     ($($arg:tt)*) => {
         $crate::log!($crate::LogLevel::Verbose, $($arg)*)
     };
@@ -555,6 +1336,9 @@ macro_rules! log_verbose {
 
 #[macro_export]
 macro_rules! log_trace {
+    (target: $target:expr, $($arg:tt)*) => {
+        $crate::log!(target: $target, $crate::LogLevel::Trace, $($arg)*)
+    };
     ($($arg:tt)*) => {
         $crate::log!($crate::LogLevel::Trace, $($arg)*)
     };
@@ -603,3 +1387,296 @@ macro_rules! log_simple_with_buffer_size {
         }
     }};
 }
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(all(test, not(feature = "hosted")))]
+pub(crate) mod tests {
+    extern crate std;
+    use super::*;
+    use std::boxed::Box;
+    use std::string::String;
+    use std::sync::Mutex as StdMutex;
+
+    static WRITTEN: StdMutex<String> = StdMutex::new(String::new());
+
+    struct TestWriter;
+
+    impl fmt::Write for TestWriter {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            WRITTEN.lock().unwrap().push_str(s);
+            Ok(())
+        }
+    }
+
+    /// Installs a logger writing into `written()`, emptied.
+    pub(crate) fn init() {
+        init_logger(LoggerConfig::default(), Box::leak(Box::new(TestWriter)));
+        WRITTEN.lock().unwrap().clear();
+    }
+
+    /// What the logger installed by `init` has written.
+    pub(crate) fn written() -> String {
+        WRITTEN.lock().unwrap().clone()
+    }
+
+    // ============================================================================
+    // Log Capture Tests
+    // ============================================================================
+
+    #[test]
+    fn test_capture_redirects_lines() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        init();
+
+        begin_log_capture();
+        log_with_level(LogLevel::Warn, "low voltage");
+        log_with_level(LogLevel::Debug, "filtered out");
+        log_simple_message("plain");
+        let captured = end_log_capture(|text, truncated| {
+            assert!(!truncated);
+            String::from(text)
+        });
+
+        assert_eq!(captured, "[ WARN] low voltage\nplain\n");
+        assert!(WRITTEN.lock().unwrap().is_empty());
+
+        // Output goes to the writer again once the capture ended
+        log_with_level(LogLevel::Info, "after");
+        assert!(WRITTEN.lock().unwrap().contains("after"));
+    }
+
+    #[test]
+    fn test_capture_drops_lines_that_do_not_fit() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        init();
+
+        begin_log_capture();
+        log_with_level(LogLevel::Error, "first");
+        let long = "x".repeat(LOG_CAPTURE_SIZE);
+        log_with_level(LogLevel::Error, &long);
+        log_with_level(LogLevel::Error, "last");
+        end_log_capture(|text, truncated| {
+            assert!(truncated);
+            assert_eq!(text, "[ERROR] first\n[ERROR] last\n");
+        });
+    }
+
+    // ============================================================================
+    // Ring Log Sink Tests
+    // ============================================================================
+
+    #[test]
+    fn test_ring_evicts_oldest_lines() {
+        let mut buffer = [0u8; 16];
+        let mut ring = RingLogSink::new(&mut buffer);
+        assert!(ring.is_empty());
+
+        ring.push(Some(LogLevel::Warn), "abc");
+        ring.push(None, "defg");
+        ring.push(Some(LogLevel::Error), "hijkl");
+        assert_eq!(ring.len(), 2);
+        assert!(ring
+            .iter()
+            .eq([(None, "defg"), (Some(LogLevel::Error), "hijkl")]));
+
+        // Cut to the buffer, at a char boundary
+        ring.push(Some(LogLevel::Info), "0123456789abcdé");
+        assert!(ring.iter().eq([(Some(LogLevel::Info), "0123456789abcd")]));
+
+        ring.clear();
+        assert_eq!(ring.iter().next(), None);
+    }
+
+    #[test]
+    fn test_ring_records_filtered_lines() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        init();
+
+        attach_log_ring(Box::leak(Box::new([0u8; 64])));
+        log_with_level(LogLevel::Warn, "low voltage");
+        log_with_level(LogLevel::Trace, "filtered out");
+        log_simple_message("plain");
+        let lines = with_log_ring(|ring| {
+            ring.iter()
+                .map(|(level, text)| (level, String::from(text)))
+                .collect::<std::vec::Vec<_>>()
+        });
+        assert_eq!(
+            lines.unwrap(),
+            [
+                (Some(LogLevel::Warn), String::from("low voltage")),
+                (None, String::from("plain"))
+            ]
+        );
+        assert_eq!(LogLevel::from_name("WARN"), Some(LogLevel::Warn));
+        assert_eq!(LogLevel::from_name("loud"), None);
+    }
+
+    // ============================================================================
+    // Target Filter Tests
+    // ============================================================================
+
+    #[test]
+    fn test_target_filters() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        init();
+        set_min_level(LogLevel::Trace);
+        set_target_level("led", LogLevel::Warn).unwrap();
+        set_target_level("led.fast", LogLevel::Error).unwrap();
+
+        crate::log_info!(target: "led", "on");
+        crate::log_warn!(target: "led.blink", "stuck");
+        crate::log_warn!(target: "led.fast", "hidden");
+        crate::log_trace!(target: "uart", "rx {}", 3);
+        crate::log_trace!("untargeted");
+        let out = written();
+        assert!(!out.contains("on") && !out.contains("hidden"), "{}", out);
+        assert!(out.contains("stuck") && out.contains("rx 3") && out.contains("untargeted"));
+
+        assert_eq!(
+            set_target_level("", LogLevel::Info),
+            Err(TargetFilterError::BadPrefix)
+        );
+        let mut filters = std::vec::Vec::new();
+        for_each_target_level(|prefix, level| filters.push((String::from(prefix), level)));
+        assert_eq!(filters.len(), 2);
+        assert!(clear_target_level("led.fast"));
+        assert!(clear_target_level("led"));
+        assert!(!clear_target_level("led"));
+        assert!(is_target_enabled("led", LogLevel::Trace));
+        set_min_level(LogLevel::Info);
+        assert!(!is_target_enabled("led", LogLevel::Trace));
+    }
+
+    #[test]
+    fn test_deferred_lines_wait_for_drain() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        init();
+        set_deferred_logging(true);
+
+        crate::log_warn!("from isr {}", 1);
+        log_with_level(LogLevel::Trace, "filtered out");
+        log_simple_message("plain");
+        assert!(has_deferred_logs());
+        assert!(written().is_empty());
+
+        drain_logs();
+        let out = written();
+        let warn = out.find("from isr 1").unwrap();
+        assert!(warn < out.find("plain").unwrap(), "{}", out);
+        assert!(!out.contains("filtered out"));
+        assert!(!has_deferred_logs());
+
+        // A full queue drops lines and reports how many on the next drain
+        for i in 0..DEFERRED_LOG_SLOTS + 3 {
+            crate::log_info!("line {}", i);
+        }
+        drain_logs();
+        let out = written();
+        assert!(
+            out.contains("line 15") && !out.contains("line 16"),
+            "{}",
+            out
+        );
+        assert!(out.contains("3 deferred log lines dropped"), "{}", out);
+
+        // Long messages are cut, switching off drains the rest
+        log_simple_message(&"é".repeat(DEFERRED_LOG_MESSAGE));
+        set_deferred_logging(false);
+        assert!(!is_deferred_logging());
+        let cut = "é".repeat(DEFERRED_LOG_MESSAGE / 2);
+        let out = written();
+        assert!(out.contains(&cut) && !out.contains(&(cut + "é")), "{}", out);
+    }
+
+    #[test]
+    fn test_timestamp_prefix() {
+        use core::sync::atomic::AtomicU64;
+        static NOW_MS: AtomicU64 = AtomicU64::new(42_137);
+
+        let _guard = TEST_LOCK.lock().unwrap();
+        init();
+        set_timestamp_source(|| NOW_MS.load(Ordering::Relaxed));
+
+        crate::log_warn!("sensor {}", "ready");
+        log_simple_message("plain");
+        assert_eq!(
+            written(),
+            "[000042.137] [\x1B[33m WARN\x1B[0m] sensor ready\r\nplain\r\n"
+        );
+        assert_eq!("[000042.137] ".len(), TIMESTAMP_PREFIX_LEN);
+
+        // Deferred lines keep the time they were logged at
+        set_deferred_logging(true);
+        crate::log_info!("early");
+        NOW_MS.store(1_234_567_890, Ordering::Relaxed);
+        set_deferred_logging(false);
+        assert!(
+            written().contains("[000042.137] [\x1B[32m INFO\x1B[0m] early"),
+            "{}",
+            written()
+        );
+
+        crate::log_error!("late");
+        assert!(written().contains("[1234567.890] "));
+        clear_timestamp_source();
+    }
+
+    #[test]
+    fn test_sinks_filter_by_level() {
+        static TRACE: StdMutex<String> = StdMutex::new(String::new());
+
+        struct TraceWriter;
+
+        impl fmt::Write for TraceWriter {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                TRACE.lock().unwrap().push_str(s);
+                Ok(())
+            }
+        }
+
+        let _guard = TEST_LOCK.lock().unwrap();
+        let config = LoggerConfig {
+            color_entire_line: false,
+            min_level: LogLevel::Trace,
+        };
+        init_logger_with_sinks(
+            config,
+            [
+                LogSink::new(Box::leak(Box::new(TestWriter)), LogLevel::Info),
+                LogSink::new(Box::leak(Box::new(TraceWriter)), LogLevel::Trace),
+            ],
+        );
+        WRITTEN.lock().unwrap().clear();
+        TRACE.lock().unwrap().clear();
+
+        crate::log_info!("booted");
+        crate::log_trace!("irq {}", 7);
+        log_simple_message("plain");
+        let (uart, trace) = (written(), TRACE.lock().unwrap().clone());
+        assert!(uart.contains("booted") && !uart.contains("irq 7") && uart.contains("plain"));
+        assert!(trace.contains("booted") && trace.contains("irq 7") && trace.contains("plain"));
+
+        assert!(set_sink_level(1, LogLevel::Error));
+        assert!(!set_sink_level(2, LogLevel::Error));
+        crate::log_warn!("hot");
+        assert!(written().contains("hot") && !TRACE.lock().unwrap().contains("hot"));
+
+        // The shell writes to the first sink
+        with_global_writer(|writer| writer.write_str("prompt"));
+        assert!(written().ends_with("prompt") && !TRACE.lock().unwrap().contains("prompt"));
+        init();
+    }
+
+    #[test]
+    fn test_end_without_capture() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        end_log_capture(|text, truncated| {
+            assert_eq!(text, "");
+            assert!(!truncated);
+        });
+    }
+}
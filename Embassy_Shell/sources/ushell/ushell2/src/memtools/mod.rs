@@ -0,0 +1,251 @@
+//! Memory inspection built-ins, registered with the `mem-tools` feature.
+//!
+//! `md <addr> <len>` hexdumps a region, `mw8`, `mw16` and `mw32 <addr> <val>` write a
+//! value with an access of that width (peripheral registers often need one) and
+//! `mfill <addr> <len> <byte>` fills a region:
+//!
+//! ```text
+//! >> mw32 0x20000000 0xDEADBEEF
+//! >> md 0x20000000 8
+//! 20000000: EF BE AD DE 00 00 00 00                          |........|
+//! ```
+//!
+//! Every access is first passed to the application's `MemAccess` callback
+//! (`ShellConfig::mem_access`, `InputParser::set_mem_access`), which allows the
+//! regions that are safe to touch, e.g. the RAM and a few peripherals; without one,
+//! all accesses are refused. Numbers are decimal or `0x` hexadecimal.
+
+use crate::input::renderer::{hexdump, HEXDUMP_ROW_LEN};
+use crate::logger::UnifiedWriter;
+use crate::watch::parse_number;
+
+/// Largest region `md` dumps, in bytes.
+pub const MD_MAX_LEN: usize = 1024;
+
+/// Tells whether the `len` bytes at `addr` may be read (`write` false) or written.
+pub type MemAccess = fn(addr: usize, len: usize, write: bool) -> bool;
+
+/// Why a memory built-in did nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemError {
+    /// Wrong argument count; holds the usage of the command.
+    Usage(&'static str),
+    /// An argument is not a number.
+    BadNumber,
+    /// The length is zero, too large or runs past the end of the address space.
+    BadLength,
+    /// The value does not fit the width of the write.
+    BadValue,
+    /// The address is not a multiple of the width of the write.
+    Unaligned,
+    /// The access callback refused the region, or there is none.
+    Denied,
+}
+
+impl MemError {
+    pub const fn message(&self) -> &'static str {
+        match self {
+            MemError::Usage(usage) => usage,
+            MemError::BadNumber => "mem: invalid number",
+            MemError::BadLength => "mem: length must be 1..=1024",
+            MemError::BadValue => "mem: value too large for the width",
+            MemError::Unaligned => "mem: unaligned address",
+            MemError::Denied => "mem: access denied",
+        }
+    }
+}
+
+/// Runs the built-in `name` (`md`, `mw8`, `mw16`, `mw32` or `mfill`) with `args`,
+/// checking the region with `access`.
+pub fn run<W: UnifiedWriter>(
+    name: &str,
+    args: &str,
+    access: Option<MemAccess>,
+    writer: &mut W,
+) -> Result<(), MemError> {
+    let mut it = args.split_whitespace();
+    let args = [it.next(), it.next(), it.next(), it.next()];
+    match name {
+        "md" => {
+            let [Some(addr), Some(len), None, None] = args else {
+                return Err(MemError::Usage("usage: md <addr> <len>"));
+            };
+            let (addr, len) = (number(addr)?, number(len)?);
+            check(access, addr, len, false)?;
+            dump(writer, addr, len);
+            Ok(())
+        }
+        "mw8" | "mw16" | "mw32" => {
+            let [Some(addr), Some(value), None, None] = args else {
+                return Err(MemError::Usage("usage: mw8|mw16|mw32 <addr> <val>"));
+            };
+            let (addr, value) = (number(addr)?, number(value)?);
+            let width = match name {
+                "mw8" => 1,
+                "mw16" => 2,
+                _ => 4,
+            };
+            if width < core::mem::size_of::<usize>() && value >> (width * 8) != 0 {
+                return Err(MemError::BadValue);
+            }
+            if addr % width != 0 {
+                return Err(MemError::Unaligned);
+            }
+            check(access, addr, width, true)?;
+            // The region was allowed by the application; the width is the one asked for
+            unsafe {
+                match width {
+                    1 => core::ptr::write_volatile(addr as *mut u8, value as u8),
+                    2 => core::ptr::write_volatile(addr as *mut u16, value as u16),
+                    _ => core::ptr::write_volatile(addr as *mut u32, value as u32),
+                }
+            }
+            Ok(())
+        }
+        "mfill" => {
+            let [Some(addr), Some(len), Some(byte), None] = args else {
+                return Err(MemError::Usage("usage: mfill <addr> <len> <byte>"));
+            };
+            let (addr, len, byte) = (number(addr)?, number(len)?, number(byte)?);
+            let byte = u8::try_from(byte).map_err(|_| MemError::BadValue)?;
+            check(access, addr, len, true)?;
+            for i in 0..len {
+                // As above
+                unsafe { core::ptr::write_volatile((addr + i) as *mut u8, byte) };
+            }
+            Ok(())
+        }
+        _ => Err(MemError::Usage(
+            "usage: md <addr> <len> | mw8|mw16|mw32 <addr> <val> | mfill <addr> <len> <byte>",
+        )),
+    }
+}
+
+fn number(arg: &str) -> Result<usize, MemError> {
+    parse_number(arg).ok_or(MemError::BadNumber)
+}
+
+/// Checks the length of a region and asks `access` for it.
+fn check(access: Option<MemAccess>, addr: usize, len: usize, write: bool) -> Result<(), MemError> {
+    if len == 0 || len > MD_MAX_LEN || addr.checked_add(len).is_none() {
+        return Err(MemError::BadLength);
+    }
+    match access {
+        Some(allowed) if allowed(addr, len, write) => Ok(()),
+        _ => Err(MemError::Denied),
+    }
+}
+
+/// Hexdumps `len` bytes at `addr`, read byte by byte a row at a time.
+fn dump<W: UnifiedWriter>(writer: &mut W, addr: usize, len: usize) {
+    let mut row = [0u8; HEXDUMP_ROW_LEN];
+    for start in (0..len).step_by(HEXDUMP_ROW_LEN) {
+        let count = (len - start).min(HEXDUMP_ROW_LEN);
+        for (i, byte) in row[..count].iter_mut().enumerate() {
+            // Checked by `check`
+            *byte = unsafe { core::ptr::read_volatile((addr + start + i) as *const u8) };
+        }
+        hexdump(writer, addr + start, &row[..count]);
+    }
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use heapless::String;
+
+    /// Start of the only region the tests allow.
+    static REGION: AtomicUsize = AtomicUsize::new(0);
+    const REGION_LEN: usize = 32;
+
+    fn allow_region(addr: usize, len: usize, _write: bool) -> bool {
+        let start = REGION.load(Ordering::Relaxed);
+        addr >= start && addr + len <= start + REGION_LEN
+    }
+
+    #[test]
+    fn test_dump_write_and_fill() {
+        let mut region = [0u32; REGION_LEN / 4];
+        let addr = region.as_mut_ptr() as usize;
+        REGION.store(addr, Ordering::Relaxed);
+        let access = Some(allow_region as MemAccess);
+        let mut out: String<512> = String::new();
+        let line = |args: &str| std::format!("{:#x} {}", addr, args);
+
+        run("mw32", &line("0xDEADBEEF"), access, &mut out).unwrap();
+        run(
+            "mw8",
+            &std::format!("{:#x} 0x7F", addr + 5),
+            access,
+            &mut out,
+        )
+        .unwrap();
+        run(
+            "mfill",
+            &std::format!("{:#x} 8 0x11", addr + 8),
+            access,
+            &mut out,
+        )
+        .unwrap();
+        assert!(out.is_empty());
+
+        run("md", &line("20"), access, &mut out).unwrap();
+        assert!(
+            out.contains(
+                ": EF BE AD DE 00 7F 00 00 11 11 11 11 11 11 11 11  |................|\r\n"
+            ),
+            "{}",
+            out
+        );
+        assert_eq!(out.matches("\r\n").count(), 2);
+        assert!(out.ends_with(&std::format!(": 00 00 00 00{:38}|....|\r\n", "")));
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut out: String<64> = String::new();
+        let deny = Some((|_, _, _| false) as MemAccess);
+        let allow = Some((|_, _, _| true) as MemAccess);
+        assert_eq!(
+            run("md", "0x1000", allow, &mut out),
+            Err(MemError::Usage("usage: md <addr> <len>"))
+        );
+        assert_eq!(run("md", "0x1000 4", None, &mut out), Err(MemError::Denied));
+        assert_eq!(run("md", "0x1000 4", deny, &mut out), Err(MemError::Denied));
+        assert_eq!(
+            run("md", "0x1000 0", allow, &mut out),
+            Err(MemError::BadLength)
+        );
+        assert_eq!(
+            run("md", "0x1000 2000", allow, &mut out),
+            Err(MemError::BadLength)
+        );
+        assert_eq!(
+            run("md", "0xZZ 4", allow, &mut out),
+            Err(MemError::BadNumber)
+        );
+        assert_eq!(
+            run("mw8", "0x1000 0x100", allow, &mut out),
+            Err(MemError::BadValue)
+        );
+        assert_eq!(
+            run("mw16", "0x1001 1", allow, &mut out),
+            Err(MemError::Unaligned)
+        );
+        assert_eq!(
+            run("mw32", "0x1002 1", deny, &mut out),
+            Err(MemError::Unaligned)
+        );
+        assert_eq!(
+            run("mfill", "0x1000 4 256", allow, &mut out),
+            Err(MemError::BadValue)
+        );
+        assert!(out.is_empty());
+    }
+}
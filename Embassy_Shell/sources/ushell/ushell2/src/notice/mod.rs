@@ -0,0 +1,84 @@
+//! Messages printed above the edit line.
+//!
+//! Application tasks that write to the console directly end up in the middle of the line
+//! being edited. `println_above` hands the message to the shell instead: on its next
+//! poll the shell erases the edit line, prints the pending messages and draws the prompt
+//! and the input again, so the user keeps typing where they were.
+//!
+//! ```ignore
+//! #[embassy_executor::task]
+//! async fn alert_task() {
+//!     loop {
+//!         let temp = SENSOR.wait_over_limit().await;
+//!         let mut msg: heapless::String<32> = heapless::String::new();
+//!         let _ = write!(msg, "temperature alert: {}", temp);
+//!         ushell2::notice::println_above(&msg);
+//!     }
+//! }
+//! ```
+//!
+//! Messages are held while the shell waits for an answer (`confirm`) or runs `watch`.
+
+use core::cell::RefCell;
+use critical_section::Mutex;
+use heapless::{Deque, String};
+
+#[cfg(test)]
+extern crate std;
+
+/// Maximum length in bytes of a message; longer ones are cut.
+pub const NOTICE_MAX_LEN: usize = 96;
+
+/// Maximum number of messages waiting to be printed.
+pub const NOTICE_DEPTH: usize = 4;
+
+static PENDING: Mutex<RefCell<Deque<String<NOTICE_MAX_LEN>, NOTICE_DEPTH>>> =
+    Mutex::new(RefCell::new(Deque::new()));
+
+/// Queues `msg` to be printed above the edit line; returns `false` if `NOTICE_DEPTH`
+/// messages are waiting already (the message is dropped).
+pub fn println_above(msg: &str) -> bool {
+    let mut line = String::new();
+    for ch in msg.chars() {
+        if line.push(ch).is_err() {
+            break;
+        }
+    }
+    critical_section::with(|cs| PENDING.borrow_ref_mut(cs).push_back(line).is_ok())
+}
+
+/// Takes the oldest waiting message (called by the shell).
+pub fn take() -> Option<String<NOTICE_MAX_LEN>> {
+    critical_section::with(|cs| PENDING.borrow_ref_mut(cs).pop_front())
+}
+
+/// Serializes tests that use the global message queue.
+#[cfg(test)]
+pub(crate) static TEST_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queue_and_cut() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        while take().is_some() {}
+
+        let long = "é".repeat(NOTICE_MAX_LEN);
+        assert!(println_above(&long));
+        for _ in 1..NOTICE_DEPTH {
+            assert!(println_above("alert"));
+        }
+        assert!(!println_above("dropped"));
+
+        assert_eq!(take().unwrap().chars().count(), NOTICE_MAX_LEN / 2);
+        while let Some(msg) = take() {
+            assert_eq!(msg.as_str(), "alert");
+        }
+    }
+}
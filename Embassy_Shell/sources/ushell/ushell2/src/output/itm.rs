@@ -0,0 +1,146 @@
+//! Output over the Cortex-M ITM (Instrumentation Trace Macrocell).
+//!
+//! `ItmWriter` sends text to an ITM stimulus port, where a debug probe picks it up over
+//! SWO; no UART or logic analyzer is needed. It implements `core::fmt::Write`, so it is
+//! a `UnifiedWriter` and a `LogWriter` and can be passed to `init_logger`, alone or
+//! combined with the UART writer in a `TeeWriter`.
+//!
+//! ```ignore
+//! static LOG_WRITER: StaticCell<TeeWriter<UartWriter, ItmWriter>> = StaticCell::new();
+//! init_logger(config, LOG_WRITER.init(TeeWriter::new(UartWriter::new(), ItmWriter::new(0))));
+//! ```
+//!
+//! As a sink of its own (see `logger::init_logger_with_sinks`), ITM can receive more
+//! verbose lines than the UART.
+//!
+//! The shell itself keeps reading from its transport and mirrors its output to ITM with
+//! `ShellConfig::mirror_write = Some(itm_write)`.
+//!
+//! Nothing is written while ITM or the port is disabled (`TCR.ITMENA`, `TER`), which is
+//! the case until a debugger enables tracing; writing is then free of side effects.
+
+use core::fmt;
+use core::ptr::{read_volatile, write_volatile};
+
+/// Base address of the ITM register block.
+pub const ITM_BASE: usize = 0xE000_0000;
+
+/// Trace Enable Register offset (one bit per stimulus port 0..=31).
+const TER_OFFSET: usize = 0xE00;
+/// Trace Control Register offset.
+const TCR_OFFSET: usize = 0xE80;
+/// `TCR.ITMENA`: ITM enabled.
+const TCR_ITMENA: u32 = 1;
+/// Reading a stimulus port returns 1 in bit 0 when its FIFO can accept data.
+const STIM_FIFOREADY: u32 = 1;
+
+/// Writes to one ITM stimulus port (0..=31).
+pub struct ItmWriter {
+    base: usize,
+    port: u8,
+}
+
+impl ItmWriter {
+    /// Writer for stimulus `port` (0..=31; port 0 is the usual console channel).
+    pub const fn new(port: u8) -> Self {
+        Self {
+            base: ITM_BASE,
+            port: port & 31,
+        }
+    }
+
+    /// Returns `true` if ITM and this stimulus port are enabled.
+    pub fn is_enabled(&self) -> bool {
+        // Reads of the ITM block have no side effects
+        let (tcr, ter) = unsafe {
+            (
+                read_volatile((self.base + TCR_OFFSET) as *const u32),
+                read_volatile((self.base + TER_OFFSET) as *const u32),
+            )
+        };
+        tcr & TCR_ITMENA != 0 && ter & (1 << self.port) != 0
+    }
+
+    /// Sends `bytes`, one 8-bit stimulus write each, waiting for FIFO space.
+    /// Dropped if the port is disabled.
+    pub fn write_all(&mut self, bytes: &[u8]) {
+        if !self.is_enabled() {
+            return;
+        }
+        let stim = (self.base + 4 * self.port as usize) as *mut u32;
+        for &byte in bytes {
+            unsafe {
+                while read_volatile(stim) & STIM_FIFOREADY == 0 {}
+                write_volatile(stim as *mut u8, byte);
+            }
+        }
+    }
+
+    /// Writer over a simulated register block (tests).
+    #[cfg(test)]
+    fn at(base: usize, port: u8) -> Self {
+        Self { base, port }
+    }
+}
+
+impl fmt::Write for ItmWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_all(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// Sends `bytes` to stimulus port 0; usable as `ShellConfig::mirror_write`.
+pub fn itm_write(bytes: &[u8]) {
+    ItmWriter::new(0).write_all(bytes);
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Write;
+
+    /// Simulated ITM block: stimulus ports, TER and TCR.
+    #[repr(C, align(4))]
+    struct Block([u32; (TCR_OFFSET / 4) + 1]);
+
+    impl Block {
+        fn new(tcr: u32, ter: u32) -> Self {
+            let mut regs = [STIM_FIFOREADY; (TCR_OFFSET / 4) + 1];
+            regs[TER_OFFSET / 4] = ter;
+            regs[TCR_OFFSET / 4] = tcr;
+            Self(regs)
+        }
+
+        fn base(&mut self) -> usize {
+            self.0.as_mut_ptr() as usize
+        }
+    }
+
+    #[test]
+    fn test_disabled_port_is_not_written() {
+        // ITM off, then ITM on but the port disabled
+        for (tcr, ter) in [(0, !0), (TCR_ITMENA, !(1 << 2))] {
+            let mut block = Block::new(tcr, ter);
+            let mut itm = ItmWriter::at(block.base(), 2);
+            assert!(!itm.is_enabled());
+            itm.write_str("x").unwrap();
+            assert_eq!(block.0[2], STIM_FIFOREADY);
+        }
+    }
+
+    #[test]
+    fn test_enabled_port_receives_bytes() {
+        let mut block = Block::new(TCR_ITMENA, 1 << 3);
+        let mut itm = ItmWriter::at(block.base(), 3);
+        assert!(itm.is_enabled());
+        // Odd bytes keep the simulated FIFOREADY bit set
+        itm.write_str("ae").unwrap();
+        assert_eq!(block.0[3] & 0xFF, u32::from(b'e'));
+        assert_eq!(block.0[0], STIM_FIFOREADY);
+    }
+}
@@ -0,0 +1,226 @@
+//! Minimal streaming JSON serializer.
+//!
+//! Writes straight to a `UnifiedWriter`: no allocation, no `core::fmt`. Commas and
+//! string escaping are handled by the writer; the caller is responsible for balancing
+//! `begin_*` / `end_*` calls (nesting is limited to 32 levels).
+//!
+//! ```ignore
+//! use ushell2::output::JsonWriter;
+//!
+//! let mut json = JsonWriter::new(&mut writer);
+//! json.begin_object();
+//! json.key("uptime_ms").u64(12345);
+//! json.key("tasks").begin_array();
+//! json.str("shell").str("blinky");
+//! json.end_array();
+//! json.end_object();
+//! // {"uptime_ms":12345,"tasks":["shell","blinky"]}
+//! ```
+
+use crate::logger::UnifiedWriter;
+
+/// Writes one JSON document to a `UnifiedWriter`.
+pub struct JsonWriter<'w, W: UnifiedWriter> {
+    writer: &'w mut W,
+    depth: u8,
+    /// Bit `n` is set once the container at depth `n` holds an item.
+    has_items: u32,
+    after_key: bool,
+}
+
+impl<'w, W: UnifiedWriter> JsonWriter<'w, W> {
+    pub fn new(writer: &'w mut W) -> Self {
+        Self {
+            writer,
+            depth: 0,
+            has_items: 0,
+            after_key: false,
+        }
+    }
+
+    pub fn begin_object(&mut self) -> &mut Self {
+        self.open("{")
+    }
+
+    pub fn end_object(&mut self) -> &mut Self {
+        self.close("}")
+    }
+
+    pub fn begin_array(&mut self) -> &mut Self {
+        self.open("[")
+    }
+
+    pub fn end_array(&mut self) -> &mut Self {
+        self.close("]")
+    }
+
+    /// Writes an object key; the next call writes its value.
+    pub fn key(&mut self, key: &str) -> &mut Self {
+        self.separator();
+        self.write_escaped(key);
+        self.writer.write_str(":");
+        self.after_key = true;
+        self
+    }
+
+    pub fn str(&mut self, value: &str) -> &mut Self {
+        self.separator();
+        self.write_escaped(value);
+        self
+    }
+
+    pub fn u64(&mut self, value: u64) -> &mut Self {
+        self.separator();
+        write_u64(self.writer, value);
+        self
+    }
+
+    pub fn i64(&mut self, value: i64) -> &mut Self {
+        self.separator();
+        if value < 0 {
+            self.writer.write_str("-");
+        }
+        write_u64(self.writer, value.unsigned_abs());
+        self
+    }
+
+    pub fn bool(&mut self, value: bool) -> &mut Self {
+        self.separator();
+        self.writer.write_str(if value { "true" } else { "false" });
+        self
+    }
+
+    pub fn null(&mut self) -> &mut Self {
+        self.separator();
+        self.writer.write_str("null");
+        self
+    }
+
+    fn open(&mut self, bracket: &str) -> &mut Self {
+        self.separator();
+        self.writer.write_str(bracket);
+        self.depth = self.depth.saturating_add(1).min(31);
+        self.has_items &= !(1 << self.depth);
+        self
+    }
+
+    fn close(&mut self, bracket: &str) -> &mut Self {
+        self.writer.write_str(bracket);
+        self.depth = self.depth.saturating_sub(1);
+        self
+    }
+
+    /// Writes the comma between items, unless the value follows a key.
+    fn separator(&mut self) {
+        if self.after_key {
+            self.after_key = false;
+            return;
+        }
+        let bit = 1 << self.depth;
+        if self.has_items & bit != 0 {
+            self.writer.write_str(",");
+        }
+        self.has_items |= bit;
+    }
+
+    fn write_escaped(&mut self, s: &str) {
+        const HEX: &[u8; 16] = b"0123456789abcdef";
+        self.writer.write_str("\"");
+        let mut start = 0;
+        for (i, byte) in s.bytes().enumerate() {
+            let escape: &[u8] = match byte {
+                b'"' => b"\\\"",
+                b'\\' => b"\\\\",
+                b'\n' => b"\\n",
+                b'\r' => b"\\r",
+                b'\t' => b"\\t",
+                0..=0x1F => &[
+                    b'\\',
+                    b'u',
+                    b'0',
+                    b'0',
+                    HEX[(byte >> 4) as usize],
+                    HEX[(byte & 0xF) as usize],
+                ],
+                _ => continue,
+            };
+            self.writer.write_str(&s[start..i]);
+            self.writer.write_bytes(escape);
+            start = i + 1;
+        }
+        self.writer.write_str(&s[start..]);
+        self.writer.write_str("\"");
+    }
+}
+
+fn write_u64<W: UnifiedWriter>(writer: &mut W, mut value: u64) {
+    let mut buf = [0u8; 20];
+    let mut pos = buf.len();
+    loop {
+        pos -= 1;
+        buf[pos] = b'0' + (value % 10) as u8;
+        value /= 10;
+        if value == 0 {
+            break;
+        }
+    }
+    writer.write_bytes(&buf[pos..]);
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use heapless::String;
+
+    #[test]
+    fn test_nested_document() {
+        let mut out: String<128> = String::new();
+        let mut json = JsonWriter::new(&mut out);
+        json.begin_object();
+        json.key("n").u64(42);
+        json.key("neg").i64(-7);
+        json.key("list").begin_array();
+        json.str("a").bool(true).null();
+        json.begin_object().end_object();
+        json.end_array();
+        json.key("empty").begin_array().end_array();
+        json.end_object();
+        assert_eq!(
+            out.as_str(),
+            r#"{"n":42,"neg":-7,"list":["a",true,null,{}],"empty":[]}"#
+        );
+    }
+
+    #[test]
+    fn test_string_escaping() {
+        let mut out: String<64> = String::new();
+        JsonWriter::new(&mut out).str("a\"b\\c\r\n\x01é");
+        assert_eq!(out.as_str(), r#""a\"b\\c\r\n\u0001é""#);
+    }
+
+    #[test]
+    fn test_number_limits() {
+        let mut out: String<64> = String::new();
+        let mut json = JsonWriter::new(&mut out);
+        json.begin_array()
+            .u64(u64::MAX)
+            .i64(i64::MIN)
+            .u64(0)
+            .end_array();
+        assert_eq!(
+            out.as_str(),
+            "[18446744073709551615,-9223372036854775808,0]"
+        );
+    }
+
+    #[test]
+    fn test_top_level_values_are_separated() {
+        let mut out: String<16> = String::new();
+        JsonWriter::new(&mut out).u64(1).u64(2);
+        assert_eq!(out.as_str(), "1,2");
+    }
+}
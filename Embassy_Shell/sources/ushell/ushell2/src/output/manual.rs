@@ -0,0 +1,248 @@
+//! Markdown command reference.
+//!
+//! Renders the command tables of a generated dispatcher (names, usage, help, groups)
+//! and the shortcut list as a Markdown document, so build tooling can publish a manual
+//! that always matches the firmware. The generated `write_manual` passes its own
+//! `describe_command` and `get_datatypes`; a host-side test or build script renders it
+//! into a `String`:
+//!
+//! ```ignore
+//! let mut manual = std::string::String::new();
+//! commands::write_manual(&mut manual, shortcuts::get_shortcuts()).unwrap();
+//! std::fs::write("docs/CLI.md", manual).unwrap();
+//! ```
+
+use core::fmt::{self, Write};
+
+use crate::args::check::Specs;
+use crate::input::parser::{command_group, CommandDescriber};
+
+/// Writes the complete command reference as Markdown.
+///
+/// Ungrouped commands come first, then one section per `@group`, the descriptor
+/// table (`datatypes`) and the shortcut keys (`" | "`-separated, as returned by the
+/// generated `get_shortcuts`).
+pub fn write_manual<W: Write + ?Sized>(
+    out: &mut W,
+    describe: CommandDescriber,
+    datatypes: &str,
+    shortcuts: &str,
+) -> fmt::Result {
+    out.write_str("# Command Reference\n")?;
+
+    let commands = || (0..).map_while(describe);
+    if commands().any(|(name, ..)| command_group(name).is_none()) {
+        out.write_str("\n## Commands\n\n")?;
+        write_table_header(out)?;
+        for (name, descriptor, _, help, _) in commands() {
+            if command_group(name).is_none() {
+                write_row(out, name, descriptor, help)?;
+            }
+        }
+    }
+
+    // Commands are sorted by name, so the members of a group are adjacent
+    let mut current: Option<&str> = None;
+    for (name, descriptor, _, help, _) in commands() {
+        let Some(group) = command_group(name) else {
+            continue;
+        };
+        if current != Some(group) {
+            write!(out, "\n## `{}` commands\n\n", group)?;
+            write_table_header(out)?;
+            current = Some(group);
+        }
+        write_row(out, name, descriptor, help)?;
+    }
+
+    out.write_str("\n## Argument types\n\n```text\n")?;
+    out.write_str(datatypes.trim_end())?;
+    out.write_str("\n```\n")?;
+
+    let mut keys = shortcuts
+        .split(" | ")
+        .filter(|key| !key.is_empty())
+        .peekable();
+    if keys.peek().is_some() {
+        out.write_str("\n## Shortcuts\n\n")?;
+        for key in keys {
+            writeln!(out, "- `{}`", key)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the usage line of a command: its name, then `<type>` per argument
+/// (`<u32 1..=1024>` for a ranged one, `<text...>` for the rest of the line,
+/// `[<u32>=100]` for an optional one, `--addr=<u32>` for a named one and `[--verbose]`
+/// for a flag).
+pub fn write_usage<W: Write + ?Sized>(out: &mut W, name: &str, descriptor: &str) -> fmt::Result {
+    out.write_str(name)?;
+    for spec in Specs(descriptor) {
+        out.write_str(if spec.optional { " [" } else { " " })?;
+        if let Some(name) = spec.name {
+            write!(out, "--{}", name)?;
+            // A flag takes no value
+            if spec.ch == 't' && spec.default.is_none() {
+                out.write_str("]")?;
+                continue;
+            }
+            out.write_str("=")?;
+        }
+        write!(out, "<{}", type_name(spec.ch))?;
+        if let Some(range) = spec.range {
+            write!(out, " {}", range)?;
+        }
+        out.write_str(">")?;
+        match spec.default {
+            // Named arguments show no default: `--len=<u32>=16` would read as a value
+            Some(default) if spec.name.is_none() => write!(out, "={}]", default)?,
+            Some(_) => out.write_str("]")?,
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+/// Type named by a descriptor character, `?` for an unknown one.
+fn type_name(ch: char) -> &'static str {
+    match ch {
+        'B' => "u8",
+        'W' => "u16",
+        'D' => "u32",
+        'Q' => "u64",
+        'X' => "u128",
+        'b' => "i8",
+        'w' => "i16",
+        'd' => "i32",
+        'q' => "i64",
+        'x' => "i128",
+        'Z' => "usize",
+        'z' => "isize",
+        'f' => "f32",
+        'F' => "f64",
+        't' => "bool",
+        'c' => "char",
+        's' => "str",
+        'h' => "hexstr",
+        'r' => "text...",
+        _ => "?",
+    }
+}
+
+fn write_table_header<W: Write + ?Sized>(out: &mut W) -> fmt::Result {
+    out.write_str("| Usage | Description |\n|---|---|\n")
+}
+
+fn write_row<W: Write + ?Sized>(
+    out: &mut W,
+    name: &str,
+    descriptor: &str,
+    help: &str,
+) -> fmt::Result {
+    out.write_str("| `")?;
+    write_usage(&mut CellEscaper { out, html: false }, name, descriptor)?;
+    out.write_str("` | ")?;
+    CellEscaper { out, html: true }.write_str(help)?;
+    out.write_str(" |\n")
+}
+
+/// Escapes text for a table cell: `|` always (it would end the cell, even inside a
+/// code span), `<`, `>` and `&` outside code spans, line breaks as spaces.
+struct CellEscaper<'w, W: Write + ?Sized> {
+    out: &'w mut W,
+    html: bool,
+}
+
+impl<W: Write + ?Sized> Write for CellEscaper<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut start = 0;
+        for (i, ch) in s.char_indices() {
+            let escaped = match ch {
+                '|' => "\\|",
+                '\r' | '\n' => " ",
+                '<' if self.html => "&lt;",
+                '>' if self.html => "&gt;",
+                '&' if self.html => "&amp;",
+                _ => continue,
+            };
+            self.out.write_str(&s[start..i])?;
+            self.out.write_str(escaped)?;
+            start = i + ch.len_utf8();
+        }
+        self.out.write_str(&s[start..])
+    }
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use std::string::String;
+
+    const COMMANDS: &[(&str, &str, u8, &str, u8)] = &[
+        ("gpio read", "B", 1, "gpio read <pin>", 0),
+        ("gpio set", "Bt", 2, "drive a pin", 0),
+        ("reset", "v", 0, "", 2),
+        ("speed", "D(1..=1024)", 1, "set speed | baud", 0),
+        ("write", "Br", 2, "", 0),
+    ];
+
+    fn describe(index: usize) -> Option<(&'static str, &'static str, u8, &'static str, u8)> {
+        COMMANDS.get(index).copied()
+    }
+
+    #[test]
+    fn test_usage_line() {
+        let mut usage = String::new();
+        write_usage(&mut usage, "speed", "D(1..=1024)t").unwrap();
+        assert_eq!(usage, "speed <u32 1..=1024> <bool>");
+        usage.clear();
+        write_usage(&mut usage, "reset", "v").unwrap();
+        assert_eq!(usage, "reset");
+        usage.clear();
+        write_usage(&mut usage, "blink", "B[D(1..=1024)=100][s=\"on\"]").unwrap();
+        assert_eq!(usage, "blink <u8> [<u32 1..=1024>=100] [<str>=\"on\"]");
+        usage.clear();
+        write_usage(
+            &mut usage,
+            "dump",
+            "D:addr D(1..=64):len [B:retries=3] t:verbose",
+        )
+        .unwrap();
+        assert_eq!(
+            usage,
+            "dump --addr=<u32> --len=<u32 1..=64> [--retries=<u8>] [--verbose]"
+        );
+    }
+
+    #[test]
+    fn test_manual() {
+        let mut manual = String::new();
+        write_manual(&mut manual, describe, "B:u8 | v:void\n", "!+ | #?").unwrap();
+        assert_eq!(
+            manual,
+            "# Command Reference\n\
+             \n## Commands\n\n\
+             | Usage | Description |\n|---|---|\n\
+             | `reset` |  |\n\
+             | `speed <u32 1..=1024>` | set speed \\| baud |\n\
+             | `write <u8> <text...>` |  |\n\
+             \n## `gpio` commands\n\n\
+             | Usage | Description |\n|---|---|\n\
+             | `gpio read <u8>` | gpio read &lt;pin&gt; |\n\
+             | `gpio set <u8> <bool>` | drive a pin |\n\
+             \n## Argument types\n\n```text\nB:u8 | v:void\n```\n\
+             \n## Shortcuts\n\n- `!+`\n- `#?`\n"
+        );
+
+        // No shortcuts section without shortcuts
+        manual.clear();
+        write_manual(&mut manual, describe, "", "").unwrap();
+        assert!(!manual.contains("## Shortcuts"));
+    }
+}
@@ -0,0 +1,278 @@
+//! Output helpers for command handlers.
+//!
+//! Small formatting utilities for constrained builds. Formatting a float with `write!`
+//! pulls the full `core::fmt` float machinery into the image; the helpers here produce
+//! fixed-decimal text with plain integer arithmetic instead.
+//!
+//! ```ignore
+//! use ushell2::output::format_f32;
+//! log_info!("temperature: {} C", format_f32(23.456, 2)); // "temperature: 23.46 C"
+//! ```
+//!
+//! Tabular output is rendered with `TableWriter`, its column widths optionally fitted to
+//! the rows and the terminal width by `TableLayout` (see `table`); machine-readable output
+//! with `JsonWriter` (see `json`). Built-in commands pick one of them according to the
+//! global `OutputFormat` (`format text|json`).
+//!
+//! `ItmWriter` sends output over the Cortex-M ITM stimulus ports (see `itm`); `TeeWriter`
+//! duplicates output to two writers; `StripAnsiWriter` removes color codes for sinks that
+//! are not terminals (see `strip_ansi`).
+//!
+//! `write_manual` renders the command tables of a generated dispatcher as a Markdown
+//! reference (see `manual`).
+
+use core::fmt;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+pub mod itm;
+pub mod json;
+pub mod manual;
+pub mod strip_ansi;
+pub mod table;
+pub mod tee;
+
+pub use itm::{itm_write, ItmWriter};
+pub use json::JsonWriter;
+pub use manual::{write_manual, write_usage};
+pub use strip_ansi::{display_width, StripAnsiWriter};
+pub use table::{Align, Column, TableLayout, TableStyle, TableWriter};
+pub use tee::TeeWriter;
+
+/// Output format of the built-in commands, switched with `format text|json`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable text and tables (default).
+    Text,
+    /// One JSON document per command, for host tooling.
+    Json,
+}
+
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Selects the output format of the built-in commands.
+pub fn set_output_format(format: OutputFormat) {
+    JSON_OUTPUT.store(format == OutputFormat::Json, Ordering::Relaxed);
+}
+
+/// Returns the current output format of the built-in commands.
+pub fn output_format() -> OutputFormat {
+    if JSON_OUTPUT.load(Ordering::Relaxed) {
+        OutputFormat::Json
+    } else {
+        OutputFormat::Text
+    }
+}
+
+/// Maximum number of fractional digits produced by the float helpers.
+pub const MAX_FLOAT_DECIMALS: u8 = 9;
+
+/// Sign + 20 integer digits (`u64::MAX`) + '.' + `MAX_FLOAT_DECIMALS` digits.
+const FLOAT_STR_CAPACITY: usize = 1 + 20 + 1 + MAX_FLOAT_DECIMALS as usize;
+
+/// Stack buffer holding a formatted float.
+///
+/// Implements `Display` by writing the prepared text, so using it in `write!` or the
+/// log macros does not bring in float formatting code.
+#[derive(Clone, Copy)]
+pub struct FloatStr {
+    buf: [u8; FLOAT_STR_CAPACITY],
+    len: u8,
+}
+
+impl FloatStr {
+    const fn empty() -> Self {
+        Self {
+            buf: [0; FLOAT_STR_CAPACITY],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, byte: u8) {
+        if (self.len as usize) < FLOAT_STR_CAPACITY {
+            self.buf[self.len as usize] = byte;
+            self.len += 1;
+        }
+    }
+
+    fn push_str(&mut self, s: &str) {
+        for &b in s.as_bytes() {
+            self.push(b);
+        }
+    }
+
+    /// Append `value` in decimal, left-padded with zeros to at least `min_digits`.
+    fn push_u64(&mut self, mut value: u64, min_digits: u8) {
+        let mut digits = [0u8; 20];
+        let mut n = 0usize;
+        while value > 0 || n < min_digits as usize || n == 0 {
+            digits[n] = b'0' + (value % 10) as u8;
+            value /= 10;
+            n += 1;
+        }
+        while n > 0 {
+            n -= 1;
+            self.push(digits[n]);
+        }
+    }
+
+    /// Returns the formatted text.
+    pub fn as_str(&self) -> &str {
+        // Only ASCII is ever pushed.
+        core::str::from_utf8(&self.buf[..self.len as usize]).unwrap_or("")
+    }
+}
+
+impl Deref for FloatStr {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Display for FloatStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for FloatStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// Generates a formatter for one float type so `f32` values never go through
+// (software emulated, on single precision FPUs) `f64` arithmetic.
+macro_rules! format_float {
+    ($name:ident, $ty:ty) => {
+        /// Format `value` with exactly `decimals` fractional digits (rounded half away
+        /// from zero, capped at `MAX_FLOAT_DECIMALS`), never in scientific notation.
+        ///
+        /// Special values are written as `nan`, `inf` and `-inf`; values whose integer
+        /// part does not fit in a `u64` are written as `ovf` / `-ovf`.
+        pub fn $name(value: $ty, decimals: u8) -> FloatStr {
+            let mut out = FloatStr::empty();
+            if value.is_nan() {
+                out.push_str("nan");
+                return out;
+            }
+            let negative = value < 0.0;
+            let abs = if negative { -value } else { value };
+            if abs.is_infinite() {
+                out.push_str(if negative { "-inf" } else { "inf" });
+                return out;
+            }
+            if abs >= 18_446_744_073_709_551_616.0 {
+                out.push_str(if negative { "-ovf" } else { "ovf" });
+                return out;
+            }
+
+            let decimals = decimals.min(MAX_FLOAT_DECIMALS);
+            let scale = 10u64.pow(decimals as u32);
+            let mut int_part = abs as u64;
+            let frac = abs - int_part as $ty;
+            let mut frac_part = (frac * scale as $ty + 0.5) as u64;
+            if frac_part >= scale {
+                frac_part -= scale;
+                int_part = int_part.saturating_add(1);
+            }
+
+            // No sign when the value rounds to zero.
+            if negative && (int_part != 0 || frac_part != 0) {
+                out.push(b'-');
+            }
+            out.push_u64(int_part, 1);
+            if decimals > 0 {
+                out.push(b'.');
+                out.push_u64(frac_part, decimals);
+            }
+            out
+        }
+    };
+}
+
+format_float!(format_f32, f32);
+format_float!(format_f64, f64);
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ============================================================================
+    // Float Formatting Tests
+    // ============================================================================
+
+    #[test]
+    fn test_format_basic() {
+        assert_eq!(format_f64(1.23456, 2).as_str(), "1.23");
+        assert_eq!(format_f64(1.23456, 4).as_str(), "1.2346");
+        assert_eq!(format_f32(23.456, 1).as_str(), "23.5");
+    }
+
+    #[test]
+    fn test_format_zero_decimals() {
+        assert_eq!(format_f64(2.5, 0).as_str(), "3");
+        assert_eq!(format_f64(2.4, 0).as_str(), "2");
+        assert_eq!(format_f64(0.0, 0).as_str(), "0");
+    }
+
+    #[test]
+    fn test_format_pads_fraction() {
+        assert_eq!(format_f64(1.05, 3).as_str(), "1.050");
+        assert_eq!(format_f64(7.0, 2).as_str(), "7.00");
+        assert_eq!(format_f64(0.001, 3).as_str(), "0.001");
+    }
+
+    #[test]
+    fn test_format_rounding_carries() {
+        assert_eq!(format_f64(9.999, 2).as_str(), "10.00");
+        assert_eq!(format_f64(-0.996, 2).as_str(), "-1.00");
+    }
+
+    #[test]
+    fn test_format_negative() {
+        assert_eq!(format_f64(-12.345, 1).as_str(), "-12.3");
+        assert_eq!(format_f32(-0.5, 1).as_str(), "-0.5");
+    }
+
+    #[test]
+    fn test_format_negative_rounding_to_zero() {
+        assert_eq!(format_f64(-0.0001, 2).as_str(), "0.00");
+        assert_eq!(format_f64(-0.0, 1).as_str(), "0.0");
+    }
+
+    #[test]
+    fn test_format_large_values() {
+        assert_eq!(format_f64(1.0e15, 1).as_str(), "1000000000000000.0");
+        assert_eq!(format_f64(1.0e20, 1).as_str(), "ovf");
+        assert_eq!(format_f64(-1.0e20, 1).as_str(), "-ovf");
+    }
+
+    #[test]
+    fn test_format_special_values() {
+        assert_eq!(format_f32(f32::NAN, 2).as_str(), "nan");
+        assert_eq!(format_f32(f32::INFINITY, 2).as_str(), "inf");
+        assert_eq!(format_f64(f64::NEG_INFINITY, 2).as_str(), "-inf");
+    }
+
+    #[test]
+    fn test_format_decimals_capped() {
+        let s = format_f64(0.5, 20);
+        assert_eq!(s.as_str(), "0.500000000");
+        assert_eq!(s.len(), 2 + MAX_FLOAT_DECIMALS as usize);
+    }
+
+    #[test]
+    fn test_format_display() {
+        use core::fmt::Write;
+        let mut buf: heapless::String<32> = heapless::String::new();
+        write!(buf, "v={}", format_f32(1.25, 2)).unwrap();
+        assert_eq!(buf.as_str(), "v=1.25");
+    }
+}
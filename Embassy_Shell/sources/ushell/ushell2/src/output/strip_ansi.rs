@@ -0,0 +1,161 @@
+//! Writer adapter removing ANSI escape sequences.
+//!
+//! Log lines carry SGR color codes for the terminal. `StripAnsiWriter` removes them (and
+//! any other CSI sequence, e.g. cursor movement) before the text reaches a sink that is
+//! not a terminal, such as a flash log or a protocol channel:
+//!
+//! ```ignore
+//! let writer = TeeWriter::new(UartWriter::new(), StripAnsiWriter::new(FlashLogWriter::new()));
+//! ```
+//!
+//! Sequences split across several writes are recognized; the state is kept between calls.
+//!
+//! `display_width` measures text the same way: escape sequences take no columns.
+
+use core::fmt;
+
+/// Position inside an escape sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Text,
+    /// After ESC.
+    Escape,
+    /// After `ESC [`, until the final byte (`0x40..=0x7E`).
+    Csi,
+}
+
+/// Passes text to `inner` without ANSI escape sequences.
+///
+/// CSI sequences (`ESC [ ... final`) are removed completely; for other escapes the ESC
+/// and the ASCII byte following it are removed.
+pub struct StripAnsiWriter<W: fmt::Write> {
+    inner: W,
+    state: State,
+}
+
+impl<W: fmt::Write> StripAnsiWriter<W> {
+    pub const fn new(inner: W) -> Self {
+        Self {
+            inner,
+            state: State::Text,
+        }
+    }
+
+    /// Returns the wrapped writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: fmt::Write> fmt::Write for StripAnsiWriter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        // Runs of plain text are forwarded as slices; escapes are ASCII, so every cut
+        // falls on a char boundary
+        let mut start = 0;
+        for (i, byte) in s.bytes().enumerate() {
+            match self.state {
+                State::Text => {
+                    if byte == 0x1B {
+                        if start < i {
+                            self.inner.write_str(&s[start..i])?;
+                        }
+                        self.state = State::Escape;
+                    }
+                }
+                State::Escape => {
+                    self.state = if byte == b'[' {
+                        State::Csi
+                    } else {
+                        State::Text
+                    };
+                    // A non-ASCII byte is text, not part of the escape
+                    start = if byte.is_ascii() { i + 1 } else { i };
+                }
+                State::Csi => {
+                    if (0x40..=0x7E).contains(&byte) {
+                        self.state = State::Text;
+                    }
+                    start = i + 1;
+                }
+            }
+        }
+        if self.state == State::Text && start < s.len() {
+            self.inner.write_str(&s[start..])?;
+        }
+        Ok(())
+    }
+}
+
+/// Number of terminal columns `text` occupies: characters outside escape sequences
+/// (SGR colors in a prompt, for instance). Every character counts as one column.
+pub fn display_width(text: &str) -> usize {
+    let mut state = State::Text;
+    let mut width = 0;
+    for ch in text.chars() {
+        state = match state {
+            State::Text if ch == '\x1B' => State::Escape,
+            State::Text => {
+                width += 1;
+                State::Text
+            }
+            State::Escape if ch == '[' => State::Csi,
+            State::Escape if ch.is_ascii() => State::Text,
+            // A non-ASCII char is text, not part of the escape
+            State::Escape => {
+                width += 1;
+                State::Text
+            }
+            State::Csi if ('\x40'..='\x7E').contains(&ch) => State::Text,
+            State::Csi => State::Csi,
+        };
+    }
+    width
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::TeeWriter;
+    use core::fmt::Write;
+    use heapless::String;
+
+    #[test]
+    fn test_sequences_removed() {
+        let mut writer = StripAnsiWriter::new(String::<64>::new());
+        writer
+            .write_str("\x1B[1;31m[ERROR]\x1B[0m fail\r\n\x1B[Kok \x1B7é\x1Bü")
+            .unwrap();
+        assert_eq!(writer.into_inner().as_str(), "[ERROR] fail\r\nok éü");
+    }
+
+    #[test]
+    fn test_sequence_split_across_writes() {
+        let mut writer = StripAnsiWriter::new(String::<32>::new());
+        for part in ["a\x1B", "[3", "2mb", "\x1B[0", "m", "c"] {
+            writer.write_str(part).unwrap();
+        }
+        assert_eq!(writer.into_inner().as_str(), "abc");
+    }
+
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width("\x1B[1;32mdev\x1B[0m> "), 5);
+        assert_eq!(display_width("é>\x1B7 \x1Bü"), 4);
+        assert_eq!(display_width("\x1B[38;5;208"), 0);
+    }
+
+    #[test]
+    fn test_second_sink_of_tee() {
+        let mut tee = TeeWriter::new(
+            String::<32>::new(),
+            StripAnsiWriter::new(String::<32>::new()),
+        );
+        write!(tee, "\x1B[33mwarn\x1B[0m").unwrap();
+        assert_eq!(tee.first().as_str(), "\x1B[33mwarn\x1B[0m");
+        assert_eq!(tee.second().inner.as_str(), "warn");
+    }
+}
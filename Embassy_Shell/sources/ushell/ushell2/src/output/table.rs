@@ -0,0 +1,405 @@
+//! Aligned text tables written row by row, without allocation.
+//!
+//! Column widths are fixed up front (a column is never narrower than its title), so
+//! each row can be written as soon as it is known. Cells longer than their column are
+//! cut and end in `~`.
+//!
+//! ```ignore
+//! use ushell2::output::{Column, TableStyle, TableWriter};
+//!
+//! const COLUMNS: &[Column] = &[Column::new("task", 10), Column::new("stack", 6).right()];
+//! let mut table = TableWriter::new(&mut writer, COLUMNS, TableStyle::Ascii);
+//! table.row(&["shell", "1024"]);
+//! table.row(&["blinky", "256"]);
+//! table.finish();
+//! ```
+//!
+//! ```text
+//! +------------+--------+
+//! | task       |  stack |
+//! +------------+--------+
+//! | shell      |   1024 |
+//! | blinky     |    256 |
+//! +------------+--------+
+//! ```
+//!
+//! When the widths depend on the rows, `TableLayout` measures the cells first and fits
+//! the columns into a width budget (the terminal width), narrowing the widest columns;
+//! the rows are then written as above:
+//!
+//! ```ignore
+//! let mut layout = TableLayout::new([Column::auto("task"), Column::auto("stack").right()]);
+//! for task in TASKS {
+//!     layout.measure(&[task.name, task.stack]);
+//! }
+//! layout.fit(80, TableStyle::Ascii);
+//! let mut table = TableWriter::new(&mut writer, layout.columns(), TableStyle::Ascii);
+//! ```
+
+use crate::logger::UnifiedWriter;
+
+/// Horizontal alignment of the cells of a column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+/// One table column: title, content width in characters and alignment.
+#[derive(Debug, Clone, Copy)]
+pub struct Column<'a> {
+    pub title: &'a str,
+    pub width: usize,
+    pub align: Align,
+}
+
+impl<'a> Column<'a> {
+    /// Left-aligned column `width` characters wide (at least as wide as `title`).
+    pub const fn new(title: &'a str, width: usize) -> Self {
+        Self {
+            title,
+            width,
+            align: Align::Left,
+        }
+    }
+
+    /// Left-aligned column as wide as its title, widened by `TableLayout::measure`.
+    pub const fn auto(title: &'a str) -> Self {
+        Self::new(title, 0)
+    }
+
+    /// Same column, right-aligned.
+    pub const fn right(mut self) -> Self {
+        self.align = Align::Right;
+        self
+    }
+
+    fn content_width(&self) -> usize {
+        self.width.max(self.title.chars().count())
+    }
+}
+
+/// Border characters used around and between cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableStyle {
+    /// `+`, `-` and `|`; safe on any terminal.
+    Ascii,
+    /// Box-drawing characters (`┌─┬─┐`, `│`, ...).
+    Unicode,
+    /// Columns separated by two spaces, header underlined with `-`.
+    Plain,
+}
+
+/// Which horizontal rule to draw.
+#[derive(Clone, Copy)]
+enum Rule {
+    Top,
+    Middle,
+    Bottom,
+}
+
+impl TableStyle {
+    /// (left, fill, junction, right) characters of a horizontal rule.
+    const fn rule(self, rule: Rule) -> (&'static str, &'static str, &'static str, &'static str) {
+        match (self, rule) {
+            (TableStyle::Ascii, _) => ("+-", "-", "-+-", "-+"),
+            (TableStyle::Unicode, Rule::Top) => ("┌─", "─", "─┬─", "─┐"),
+            (TableStyle::Unicode, Rule::Middle) => ("├─", "─", "─┼─", "─┤"),
+            (TableStyle::Unicode, Rule::Bottom) => ("└─", "─", "─┴─", "─┘"),
+            (TableStyle::Plain, _) => ("", "-", "  ", ""),
+        }
+    }
+
+    /// (left, separator, right) around the cells of a row.
+    const fn cells(self) -> (&'static str, &'static str, &'static str) {
+        match self {
+            TableStyle::Ascii => ("| ", " | ", " |"),
+            TableStyle::Unicode => ("│ ", " │ ", " │"),
+            TableStyle::Plain => ("", "  ", ""),
+        }
+    }
+
+    /// Characters taken by the borders and separators of a row of `count` columns.
+    fn overhead(self, count: usize) -> usize {
+        let (left, separator, right) = self.cells();
+        let width = |s: &str| s.chars().count();
+        width(left) + width(right) + width(separator) * count.saturating_sub(1)
+    }
+}
+
+/// Column widths sized from the cells and fitted into a width budget.
+///
+/// `measure` widens the columns to the cells of each row, `fit` then narrows the widest
+/// columns until a row fits the budget; a column never gets narrower than its title, so
+/// a budget too small for the titles is exceeded. Cells cut by `fit` end in `~`.
+#[derive(Debug, Clone, Copy)]
+pub struct TableLayout<'c, const COLS: usize> {
+    columns: [Column<'c>; COLS],
+}
+
+impl<'c, const COLS: usize> TableLayout<'c, COLS> {
+    pub const fn new(columns: [Column<'c>; COLS]) -> Self {
+        Self { columns }
+    }
+
+    /// Widens the columns to fit `cells`. Missing cells are skipped, extra cells ignored.
+    pub fn measure(&mut self, cells: &[&str]) {
+        for (column, cell) in self.columns.iter_mut().zip(cells) {
+            column.width = column.width.max(cell.chars().count());
+        }
+    }
+
+    /// Narrows the widest columns until a row in `style` is at most `budget` characters.
+    pub fn fit(&mut self, budget: usize, style: TableStyle) {
+        let mut total = style.overhead(COLS) + self.width();
+        while total > budget {
+            let widest = self
+                .columns
+                .iter_mut()
+                .filter(|column| column.width > column.title.chars().count())
+                .max_by_key(|column| column.width);
+            let Some(column) = widest else {
+                return;
+            };
+            column.width -= 1;
+            total -= 1;
+        }
+    }
+
+    /// Characters taken by the cells of a row, borders not included.
+    pub fn width(&self) -> usize {
+        self.columns.iter().map(Column::content_width).sum()
+    }
+
+    /// The columns, to pass to `TableWriter::new`.
+    pub fn columns(&self) -> &[Column<'c>] {
+        &self.columns
+    }
+}
+
+/// Writes a table to a `UnifiedWriter` one row at a time.
+///
+/// The header is written before the first row (or by `finish` for an empty table);
+/// `finish` writes the closing border.
+pub struct TableWriter<'w, 'c, W: UnifiedWriter> {
+    writer: &'w mut W,
+    columns: &'c [Column<'c>],
+    style: TableStyle,
+    header_written: bool,
+}
+
+impl<'w, 'c, W: UnifiedWriter> TableWriter<'w, 'c, W> {
+    pub fn new(writer: &'w mut W, columns: &'c [Column<'c>], style: TableStyle) -> Self {
+        Self {
+            writer,
+            columns,
+            style,
+            header_written: false,
+        }
+    }
+
+    /// Writes one row. Missing cells are left blank, extra cells are ignored.
+    pub fn row(&mut self, cells: &[&str]) {
+        self.header();
+        self.write_cells(|i| cells.get(i).copied().unwrap_or(""));
+    }
+
+    /// Writes the closing border (and the header if no row was written).
+    pub fn finish(mut self) {
+        self.header();
+        if self.style != TableStyle::Plain {
+            self.write_rule(Rule::Bottom);
+        }
+        self.writer.flush();
+    }
+
+    fn header(&mut self) {
+        if self.header_written {
+            return;
+        }
+        self.header_written = true;
+        if self.style != TableStyle::Plain {
+            self.write_rule(Rule::Top);
+        }
+        let columns = self.columns;
+        self.write_cells(|i| columns[i].title);
+        self.write_rule(Rule::Middle);
+    }
+
+    fn write_rule(&mut self, rule: Rule) {
+        let (left, fill, junction, right) = self.style.rule(rule);
+        self.writer.write_str(left);
+        for (i, column) in self.columns.iter().enumerate() {
+            if i > 0 {
+                self.writer.write_str(junction);
+            }
+            for _ in 0..column.content_width() {
+                self.writer.write_str(fill);
+            }
+        }
+        self.writer.write_str(right);
+        self.writer.write_str("\r\n");
+    }
+
+    fn write_cells<'s>(&mut self, cell: impl Fn(usize) -> &'s str) {
+        let (left, separator, right) = self.style.cells();
+        self.writer.write_str(left);
+        for (i, column) in self.columns.iter().enumerate() {
+            if i > 0 {
+                self.writer.write_str(separator);
+            }
+            write_cell(self.writer, cell(i), column);
+        }
+        self.writer.write_str(right);
+        self.writer.write_str("\r\n");
+    }
+}
+
+/// Writes `text` padded (or cut, ending in `~`) to the column width.
+fn write_cell<W: UnifiedWriter>(writer: &mut W, text: &str, column: &Column) {
+    let width = column.content_width();
+    let len = text.chars().count();
+    if len > width {
+        let cut = text.char_indices().nth(width - 1).map_or(0, |(i, _)| i);
+        writer.write_str(&text[..cut]);
+        writer.write_str("~");
+        return;
+    }
+    if column.align == Align::Right {
+        pad(writer, width - len);
+    }
+    writer.write_str(text);
+    if column.align == Align::Left {
+        pad(writer, width - len);
+    }
+}
+
+fn pad<W: UnifiedWriter>(writer: &mut W, count: usize) {
+    for _ in 0..count {
+        writer.write_str(" ");
+    }
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use heapless::String;
+
+    const COLUMNS: &[Column] = &[Column::new("task", 6), Column::new("stack", 4).right()];
+
+    #[test]
+    fn test_ascii_table() {
+        let mut out: String<256> = String::new();
+        let mut table = TableWriter::new(&mut out, COLUMNS, TableStyle::Ascii);
+        table.row(&["shell", "1024"]);
+        table.row(&["blinky", "64"]);
+        table.finish();
+        assert_eq!(
+            out.as_str(),
+            "+--------+-------+\r\n\
+             | task   | stack |\r\n\
+             +--------+-------+\r\n\
+             | shell  |  1024 |\r\n\
+             | blinky |    64 |\r\n\
+             +--------+-------+\r\n"
+        );
+    }
+
+    #[test]
+    fn test_unicode_table() {
+        let mut out: String<512> = String::new();
+        let mut table = TableWriter::new(&mut out, COLUMNS, TableStyle::Unicode);
+        table.row(&["shell", "1"]);
+        table.finish();
+        assert_eq!(
+            out.as_str(),
+            "┌────────┬───────┐\r\n\
+             │ task   │ stack │\r\n\
+             ├────────┼───────┤\r\n\
+             │ shell  │     1 │\r\n\
+             └────────┴───────┘\r\n"
+        );
+    }
+
+    #[test]
+    fn test_plain_table() {
+        let mut out: String<256> = String::new();
+        let mut table = TableWriter::new(&mut out, COLUMNS, TableStyle::Plain);
+        table.row(&["shell", "1"]);
+        table.finish();
+        assert_eq!(
+            out.as_str(),
+            "task    stack\r\n\
+             ------  -----\r\n\
+             shell       1\r\n"
+        );
+    }
+
+    #[test]
+    fn test_cells_cut_missing_and_extra() {
+        let mut out: String<256> = String::new();
+        let mut table = TableWriter::new(&mut out, COLUMNS, TableStyle::Plain);
+        table.row(&["überlong_name"]);
+        table.row(&["a", "b", "ignored"]);
+        table.finish();
+        assert_eq!(
+            out.as_str(),
+            "task    stack\r\n\
+             ------  -----\r\n\
+             überl~       \r\n\
+             a           b\r\n"
+        );
+    }
+
+    #[test]
+    fn test_layout_fits_budget() {
+        let rows = [
+            ["shell", "1024", "idle"],
+            ["usb_device_poll", "4096", "waiting on endpoint"],
+        ];
+        let mut layout = TableLayout::new([
+            Column::auto("task"),
+            Column::auto("stack").right(),
+            Column::auto("state"),
+        ]);
+        for row in &rows {
+            layout.measure(row);
+        }
+        assert_eq!(layout.width(), 15 + 5 + 19);
+
+        // 10 characters of borders, 30 left for the cells: the two wide columns shrink
+        layout.fit(40, TableStyle::Ascii);
+        assert_eq!(layout.width(), 30);
+        let mut out: String<512> = String::new();
+        let mut table = TableWriter::new(&mut out, layout.columns(), TableStyle::Ascii);
+        for row in &rows {
+            table.row(row);
+        }
+        table.finish();
+        assert_eq!(
+            out.as_str(),
+            "+---------------+-------+--------------+\r\n\
+             | task          | stack | state        |\r\n\
+             +---------------+-------+--------------+\r\n\
+             | shell         |  1024 | idle         |\r\n\
+             | usb_device_p~ |  4096 | waiting on ~ |\r\n\
+             +---------------+-------+--------------+\r\n"
+        );
+
+        // Titles are never cut, even past the budget
+        layout.fit(0, TableStyle::Plain);
+        assert_eq!(layout.width(), "task".len() + "stack".len() + "state".len());
+    }
+
+    #[test]
+    fn test_empty_table_has_header() {
+        let mut out: String<256> = String::new();
+        TableWriter::new(&mut out, COLUMNS, TableStyle::Ascii).finish();
+        assert_eq!(out.matches("\r\n").count(), 4);
+        assert!(out.contains("| task   | stack |"));
+    }
+}
@@ -0,0 +1,56 @@
+//! Writer duplicating its output to two writers.
+
+use core::fmt;
+
+/// Writes everything to `first`, then to `second` (e.g. UART and ITM).
+///
+/// Implements `core::fmt::Write`, so it is a `UnifiedWriter`, and a `LogWriter` when
+/// both halves are `Send`.
+pub struct TeeWriter<A: fmt::Write, B: fmt::Write> {
+    first: A,
+    second: B,
+}
+
+impl<A: fmt::Write, B: fmt::Write> TeeWriter<A, B> {
+    pub const fn new(first: A, second: B) -> Self {
+        Self { first, second }
+    }
+
+    pub fn first(&self) -> &A {
+        &self.first
+    }
+
+    pub fn second(&self) -> &B {
+        &self.second
+    }
+}
+
+impl<A: fmt::Write, B: fmt::Write> fmt::Write for TeeWriter<A, B> {
+    /// Both writers are always written; the first error is reported.
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let first = self.first.write_str(s);
+        let second = self.second.write_str(s);
+        first.and(second)
+    }
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logger::UnifiedWriter;
+    use heapless::String;
+
+    #[test]
+    fn test_both_writers_receive_output() {
+        let mut tee = TeeWriter::new(String::<16>::new(), String::<4>::new());
+        UnifiedWriter::write_str(&mut tee, "abc");
+        // The second writer overflows; the first one still gets everything
+        assert!(fmt::Write::write_str(&mut tee, "de").is_err());
+        assert_eq!(tee.first.as_str(), "abcde");
+        assert_eq!(tee.second.as_str(), "abc");
+    }
+}
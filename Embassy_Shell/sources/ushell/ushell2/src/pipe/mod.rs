@@ -0,0 +1,228 @@
+//! Output filters for command lines.
+//!
+//! `<command> | <filter> [args]` runs the command with its output captured (see
+//! `logger::begin_log_capture`) and shows the output passed through the filter instead.
+//! Filters can be chained; each one reads the lines of the previous one.
+//!
+//! ```text
+//! >> tasks | grep idle
+//! [ INFO] idle     ready   512
+//! >> dumpmem 0x2000 8 | hex
+//! 0000: 5B 20 49 4E 46 4F 5D 20  |[ INFO] |
+//! >> tasks | grep run | count
+//! 2 lines, 58 bytes
+//! ```
+//!
+//! The filters are the fixed set in `FILTERS`. Captured output is bounded by
+//! `logger::LOG_CAPTURE_SIZE`; what does not fit is dropped before filtering.
+
+use core::fmt::Write;
+use heapless::String;
+
+use crate::logger::LOG_CAPTURE_SIZE;
+
+/// A filter: reads `input` (lines separated by `\n`) and passes its output lines to
+/// `out`. `args` is the rest of the filter's stage, trimmed.
+pub type Filter = fn(args: &str, input: &str, out: &mut dyn FnMut(&str)) -> Result<(), PipeError>;
+
+/// The built-in filters.
+pub const FILTERS: &[(&str, Filter)] = &[("count", count), ("grep", grep), ("hex", hex)];
+
+/// Why a piped line was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipeError {
+    /// An empty stage (`led 1 |`, `a || b`).
+    EmptyStage,
+    /// The stage names no filter of `FILTERS`.
+    UnknownFilter,
+    /// The filter's arguments are wrong.
+    BadArgs(&'static str),
+    /// The output of a stage does not fit the buffer of the next one.
+    Overflow,
+}
+
+impl PipeError {
+    pub const fn message(&self) -> &'static str {
+        match self {
+            PipeError::EmptyStage => "empty pipe stage",
+            PipeError::UnknownFilter => "unknown filter (count, grep, hex)",
+            PipeError::BadArgs(usage) => usage,
+            PipeError::Overflow => "pipe output too long",
+        }
+    }
+}
+
+/// Splits `line` at its first `|` outside double quotes into the command and the filter
+/// stages; `None` if it has no pipe.
+pub fn split(line: &str) -> Option<(&str, &str)> {
+    let mut quoted = false;
+    let bar = line.char_indices().find(|&(_, ch)| {
+        if ch == '"' {
+            quoted = !quoted;
+        }
+        ch == '|' && !quoted
+    })?;
+    Some((line[..bar.0].trim(), &line[bar.0 + 1..]))
+}
+
+/// Checks that every stage of `stages` names a filter, before the command runs.
+pub fn check(stages: &str) -> Result<(), PipeError> {
+    stages
+        .split('|')
+        .try_for_each(|stage| stage_filter(stage).map(|_| ()))
+}
+
+/// Runs `input` through the filter stages and passes the last stage's lines to `out`.
+pub fn run(stages: &str, input: &str, out: &mut dyn FnMut(&str)) -> Result<(), PipeError> {
+    let (stage, rest) = stages.split_once('|').unwrap_or((stages, ""));
+    let (filter, args) = stage_filter(stage)?;
+    if rest.is_empty() {
+        return filter(args, input, out);
+    }
+    let mut next: String<LOG_CAPTURE_SIZE> = String::new();
+    let mut overflow = false;
+    filter(args, input, &mut |line| {
+        overflow |= writeln!(next, "{}", line).is_err();
+    })?;
+    if overflow {
+        return Err(PipeError::Overflow);
+    }
+    run(rest, &next, out)
+}
+
+fn stage_filter(stage: &str) -> Result<(Filter, &str), PipeError> {
+    let stage = stage.trim();
+    if stage.is_empty() {
+        return Err(PipeError::EmptyStage);
+    }
+    let (name, args) = stage.split_once(' ').unwrap_or((stage, ""));
+    FILTERS
+        .iter()
+        .find(|(filter_name, _)| *filter_name == name)
+        .map(|&(_, filter)| (filter, args.trim()))
+        .ok_or(PipeError::UnknownFilter)
+}
+
+/// `count`: the number of lines and bytes.
+fn count(args: &str, input: &str, out: &mut dyn FnMut(&str)) -> Result<(), PipeError> {
+    if !args.is_empty() {
+        return Err(PipeError::BadArgs("usage: count"));
+    }
+    let mut text: String<40> = String::new();
+    let _ = write!(
+        text,
+        "{} lines, {} bytes",
+        input.lines().count(),
+        input.len()
+    );
+    out(&text);
+    Ok(())
+}
+
+/// `grep <text>`: the lines containing `text` (in double quotes if it has spaces).
+fn grep(args: &str, input: &str, out: &mut dyn FnMut(&str)) -> Result<(), PipeError> {
+    let pattern = args
+        .strip_prefix('"')
+        .and_then(|quoted| quoted.strip_suffix('"'))
+        .unwrap_or(args);
+    if pattern.is_empty() {
+        return Err(PipeError::BadArgs("usage: grep <text>"));
+    }
+    input
+        .lines()
+        .filter(|line| line.contains(pattern))
+        .for_each(out);
+    Ok(())
+}
+
+/// `hex`: a hex dump of the bytes, 8 per line with their offset and ASCII.
+fn hex(args: &str, input: &str, out: &mut dyn FnMut(&str)) -> Result<(), PipeError> {
+    if !args.is_empty() {
+        return Err(PipeError::BadArgs("usage: hex"));
+    }
+    for (index, chunk) in input.as_bytes().chunks(8).enumerate() {
+        let mut line: String<48> = String::new();
+        let _ = write!(line, "{:04X}:", index * 8);
+        for byte in chunk {
+            let _ = write!(line, " {:02X}", byte);
+        }
+        for _ in chunk.len()..8 {
+            let _ = line.push_str("   ");
+        }
+        let _ = line.push_str("  |");
+        for &byte in chunk {
+            let shown = if byte.is_ascii_graphic() || byte == b' ' {
+                byte as char
+            } else {
+                '.'
+            };
+            let _ = line.push(shown);
+        }
+        let _ = line.push('|');
+        out(&line);
+    }
+    Ok(())
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use std::string::String as StdString;
+    use std::vec::Vec as StdVec;
+
+    fn filtered(stages: &str, input: &str) -> Result<StdVec<StdString>, PipeError> {
+        let mut lines = StdVec::new();
+        run(stages, input, &mut |line| lines.push(line.into()))?;
+        Ok(lines)
+    }
+
+    #[test]
+    fn test_split_and_check() {
+        assert_eq!(split("led 1"), None);
+        assert_eq!(split("tasks | grep idle"), Some(("tasks", " grep idle")));
+        assert_eq!(
+            split("say \"a|b\" | count"),
+            Some(("say \"a|b\"", " count"))
+        );
+
+        assert_eq!(check(" grep x | count"), Ok(()));
+        assert_eq!(check(" "), Err(PipeError::EmptyStage));
+        assert_eq!(check(" count | "), Err(PipeError::EmptyStage));
+        assert_eq!(check(" sort"), Err(PipeError::UnknownFilter));
+    }
+
+    #[test]
+    fn test_filters() {
+        let input = "idle ready\nled run\nuart run\n";
+        assert_eq!(
+            filtered("grep run", input).unwrap(),
+            ["led run", "uart run"]
+        );
+        assert_eq!(filtered("grep \"d r\"", input).unwrap(), ["led run"]);
+        assert_eq!(filtered("count", input).unwrap(), ["3 lines, 28 bytes"]);
+        assert_eq!(
+            filtered("grep run | count", input).unwrap(),
+            ["2 lines, 17 bytes"]
+        );
+        assert_eq!(
+            filtered("hex", "[ INFO] ok\n").unwrap(),
+            [
+                "0000: 5B 20 49 4E 46 4F 5D 20  |[ INFO] |",
+                "0008: 6F 6B 0A                 |ok.|"
+            ]
+        );
+        assert_eq!(
+            filtered("grep", input),
+            Err(PipeError::BadArgs("usage: grep <text>"))
+        );
+        assert_eq!(
+            filtered("count 2", input),
+            Err(PipeError::BadArgs("usage: count"))
+        );
+    }
+}
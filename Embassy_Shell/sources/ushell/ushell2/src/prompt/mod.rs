@@ -0,0 +1,174 @@
+//! Runtime prompt.
+//!
+//! The prompt given in `ShellConfig::prompt` is the default. It can be replaced at
+//! runtime with the `prompt` built-in or `set_prompt`, e.g. from a login command:
+//!
+//! ```text
+//! >> prompt "dev> "
+//! dev> prompt ""
+//! >>
+//! ```
+//!
+//! A prompt may contain dynamic segments `{name}`, filled on every redraw by the
+//! function registered with `set_segment_provider` (privilege level, session id, ...):
+//!
+//! ```ignore
+//! fn segment(name: &str, out: &mut PromptString) -> bool {
+//!     match name {
+//!         "level" => write!(out, "{}", auth::level()).is_ok(),
+//!         _ => false,
+//!     }
+//! }
+//!
+//! prompt::set_segment_provider(segment);
+//! prompt::set_prompt("[{level}] >> ")?;
+//! ```
+//!
+//! Segments the provider does not know are shown as written.
+
+use core::cell::{Cell, RefCell};
+use critical_section::Mutex;
+use heapless::String;
+
+#[cfg(test)]
+extern crate std;
+
+/// Maximum length in bytes of a prompt template.
+pub const PROMPT_MAX: usize = 32;
+
+/// Maximum length in bytes of an expanded prompt; longer expansions are cut.
+pub const PROMPT_EXPANDED_MAX: usize = 64;
+
+/// Expanded prompt.
+pub type PromptString = String<PROMPT_EXPANDED_MAX>;
+
+/// Writes the value of segment `name` to `out`; returns `false` for unknown segments.
+pub type SegmentProvider = fn(name: &str, out: &mut PromptString) -> bool;
+
+/// Why a prompt was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptError {
+    /// The template is longer than `PROMPT_MAX`.
+    TooLong,
+}
+
+impl PromptError {
+    pub const fn message(&self) -> &'static str {
+        match self {
+            PromptError::TooLong => "prompt too long",
+        }
+    }
+}
+
+// An empty template selects the configured default
+static TEMPLATE: Mutex<RefCell<String<PROMPT_MAX>>> = Mutex::new(RefCell::new(String::new()));
+static SEGMENTS: Mutex<Cell<Option<SegmentProvider>>> = Mutex::new(Cell::new(None));
+
+/// Serializes tests that change the global prompt.
+#[cfg(test)]
+pub(crate) static TEST_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Replaces the prompt; an empty `template` restores the configured default.
+pub fn set_prompt(template: &str) -> Result<(), PromptError> {
+    let mut new = String::new();
+    new.push_str(template).map_err(|_| PromptError::TooLong)?;
+    critical_section::with(|cs| *TEMPLATE.borrow_ref_mut(cs) = new);
+    Ok(())
+}
+
+/// Restores the configured default prompt.
+pub fn reset_prompt() {
+    critical_section::with(|cs| TEMPLATE.borrow_ref_mut(cs).clear());
+}
+
+/// Returns the prompt set at runtime, or `None` if the default is in use.
+pub fn custom_prompt() -> Option<String<PROMPT_MAX>> {
+    let template = critical_section::with(|cs| TEMPLATE.borrow_ref(cs).clone());
+    (!template.is_empty()).then_some(template)
+}
+
+/// Registers the function filling `{name}` segments.
+pub fn set_segment_provider(provider: SegmentProvider) {
+    critical_section::with(|cs| SEGMENTS.borrow(cs).set(Some(provider)));
+}
+
+/// Returns the prompt to display: the runtime prompt or `default`, segments expanded.
+pub fn expand(default: &str) -> PromptString {
+    let custom = custom_prompt();
+    let template = custom.as_deref().unwrap_or(default);
+    let provider = critical_section::with(|cs| SEGMENTS.borrow(cs).get());
+
+    let mut out = PromptString::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        push_cut(&mut out, &rest[..open]);
+        let after = &rest[open + 1..];
+        let Some(close) = after.find('}') else {
+            rest = &rest[open..];
+            break;
+        };
+        let name = &after[..close];
+        let mark = out.len();
+        if !provider.is_some_and(|provider| provider(name, &mut out)) {
+            out.truncate(mark);
+            push_cut(&mut out, &rest[open..open + close + 2]);
+        }
+        rest = &after[close + 1..];
+    }
+    push_cut(&mut out, rest);
+    out
+}
+
+/// Appends as much of `s` as fits, on a char boundary.
+fn push_cut(out: &mut PromptString, s: &str) {
+    for ch in s.chars() {
+        if out.push(ch).is_err() {
+            break;
+        }
+    }
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Write;
+
+    fn segment(name: &str, out: &mut PromptString) -> bool {
+        match name {
+            "level" => write!(out, "{}", 3).is_ok(),
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn test_set_and_reset() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        reset_prompt();
+        assert_eq!(expand(">> ").as_str(), ">> ");
+
+        set_prompt("dev> ").unwrap();
+        assert_eq!(expand(">> ").as_str(), "dev> ");
+        assert_eq!(
+            set_prompt(&"x".repeat(PROMPT_MAX + 1)),
+            Err(PromptError::TooLong)
+        );
+        assert_eq!(custom_prompt().unwrap().as_str(), "dev> ");
+
+        set_prompt("").unwrap();
+        assert!(custom_prompt().is_none());
+        assert_eq!(expand(">> ").as_str(), ">> ");
+    }
+
+    #[test]
+    fn test_segments() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        set_segment_provider(segment);
+        set_prompt("[{level}{sid}] {").unwrap();
+        assert_eq!(expand(">> ").as_str(), "[3{sid}] {");
+        reset_prompt();
+    }
+}
@@ -0,0 +1,255 @@
+//! Deferred command execution.
+//!
+//! By default the shell runs a command inside the same call that processes the Enter
+//! key, so a slow command freezes line editing until it returns. With a command queue
+//! the shell task only edits lines and submits them (`ShellConfig::submit`); a separate,
+//! lower-priority context takes them from the queue and executes them.
+//!
+//! Embassy: the reader task submits, an executor task waits on the queue (run it on a
+//! lower-priority executor, or have the shell task on an `InterruptExecutor`):
+//!
+//! ```ignore
+//! static QUEUE: CommandQueue<MAX_INPUT_LEN, 4> = CommandQueue::new();
+//!
+//! fn submit(line: &str) -> bool {
+//!     QUEUE.push(line).is_ok()
+//! }
+//!
+//! #[embassy_executor::task]
+//! async fn exec_task() {
+//!     ushell2::runner::run_executor(&QUEUE, shell_config()).await;
+//! }
+//! ```
+//!
+//! RTIC: the submit function pends a low-priority software task that drains the queue:
+//!
+//! ```ignore
+//! fn submit(line: &str) -> bool {
+//!     let queued = QUEUE.push(line).is_ok();
+//!     rtic::pend(Interrupt::SWI0);
+//!     queued
+//! }
+//!
+//! #[task(binds = SWI0, priority = 1)]
+//! fn exec(_: exec::Context) {
+//!     while ushell2::runner::exec_queued(&QUEUE, &shell_config()) {}
+//! }
+//! ```
+//!
+//! While a command runs or waits in the queue, the prompt shows a busy marker
+//! (`ShellConfig::is_busy`, e.g. `|| QUEUE.status().is_busy()`). Lines entered while the
+//! queue is full are dropped with `busy: command dropped`. The queue depth is the `N`
+//! parameter of `CommandQueue`; `status()` reports its current use.
+//!
+//! Queued commands cannot ask for input (`confirm`, `read_hidden_line`): the answer
+//! would have to come from the line editor while it keeps running. Such commands are
+//! reported as errors.
+
+use core::cell::RefCell;
+use critical_section::Mutex;
+use heapless::{Deque, String};
+
+/// Why a line was not queued.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueError {
+    /// The queue holds its maximum number of lines.
+    Full,
+    /// The line is longer than the queue's line capacity.
+    TooLong,
+}
+
+/// Snapshot of a `CommandQueue`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueStatus {
+    /// Lines waiting to be executed.
+    pub pending: usize,
+    /// Maximum number of waiting lines (the queue depth).
+    pub capacity: usize,
+    /// A queued command is executing.
+    pub running: bool,
+    /// Lines rejected because the queue was full, since startup.
+    pub dropped: u32,
+}
+
+impl QueueStatus {
+    /// A command is executing or waiting.
+    pub const fn is_busy(&self) -> bool {
+        self.running || self.pending > 0
+    }
+}
+
+struct Inner<const IML: usize, const N: usize> {
+    lines: Deque<String<IML>, N>,
+    running: bool,
+    dropped: u32,
+}
+
+/// Bounded FIFO of command lines, shared between interrupt priorities / tasks.
+///
+/// - `IML`: maximum line length in bytes (normally the shell's input length).
+/// - `N`: maximum number of queued lines.
+pub struct CommandQueue<const IML: usize, const N: usize> {
+    inner: Mutex<RefCell<Inner<IML, N>>>,
+    #[cfg(feature = "async")]
+    waker: Mutex<RefCell<Option<core::task::Waker>>>,
+}
+
+impl<const IML: usize, const N: usize> CommandQueue<IML, N> {
+    pub const fn new() -> Self {
+        Self {
+            inner: Mutex::new(RefCell::new(Inner {
+                lines: Deque::new(),
+                running: false,
+                dropped: 0,
+            })),
+            #[cfg(feature = "async")]
+            waker: Mutex::new(RefCell::new(None)),
+        }
+    }
+
+    /// Appends a line; fails without side effects if the queue is full or the line too long.
+    pub fn push(&self, line: &str) -> Result<(), QueueError> {
+        let mut entry: String<IML> = String::new();
+        entry.push_str(line).map_err(|_| QueueError::TooLong)?;
+        critical_section::with(|cs| {
+            let mut inner = self.inner.borrow_ref_mut(cs);
+            if inner.lines.push_back(entry).is_err() {
+                inner.dropped = inner.dropped.wrapping_add(1);
+                return Err(QueueError::Full);
+            }
+            #[cfg(feature = "async")]
+            if let Some(waker) = self.waker.borrow_ref_mut(cs).take() {
+                waker.wake();
+            }
+            Ok(())
+        })
+    }
+
+    /// Removes and returns the oldest line.
+    pub fn pop(&self) -> Option<String<IML>> {
+        critical_section::with(|cs| self.inner.borrow_ref_mut(cs).lines.pop_front())
+    }
+
+    /// Number of queued lines.
+    pub fn len(&self) -> usize {
+        critical_section::with(|cs| self.inner.borrow_ref(cs).lines.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Maximum number of queued lines.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Current use of the queue.
+    pub fn status(&self) -> QueueStatus {
+        critical_section::with(|cs| {
+            let inner = self.inner.borrow_ref(cs);
+            QueueStatus {
+                pending: inner.lines.len(),
+                capacity: N,
+                running: inner.running,
+                dropped: inner.dropped,
+            }
+        })
+    }
+
+    /// Marks whether a command taken from the queue is executing (set by the runner).
+    pub fn set_running(&self, running: bool) {
+        critical_section::with(|cs| self.inner.borrow_ref_mut(cs).running = running);
+    }
+
+    /// Waits for a line and removes it from the queue.
+    #[cfg(feature = "async")]
+    pub async fn recv(&self) -> String<IML> {
+        core::future::poll_fn(|cx| {
+            critical_section::with(|cs| match self.inner.borrow_ref_mut(cs).lines.pop_front() {
+                Some(line) => core::task::Poll::Ready(line),
+                None => {
+                    *self.waker.borrow_ref_mut(cs) = Some(cx.waker().clone());
+                    core::task::Poll::Pending
+                }
+            })
+        })
+        .await
+    }
+}
+
+impl<const IML: usize, const N: usize> Default for CommandQueue<IML, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fifo_order_and_limits() {
+        let queue: CommandQueue<8, 2> = CommandQueue::new();
+        assert!(queue.is_empty());
+        assert_eq!(queue.push("led on"), Ok(()));
+        assert_eq!(queue.push("led off"), Ok(()));
+        assert_eq!(queue.push("reset"), Err(QueueError::Full));
+        assert_eq!(queue.len(), 2);
+
+        assert_eq!(queue.pop().unwrap().as_str(), "led on");
+        assert_eq!(queue.push("too long line"), Err(QueueError::TooLong));
+        assert_eq!(queue.pop().unwrap().as_str(), "led off");
+        assert!(queue.pop().is_none());
+    }
+
+    #[test]
+    fn test_status() {
+        let queue: CommandQueue<8, 1> = CommandQueue::new();
+        assert_eq!(queue.capacity(), 1);
+        assert!(!queue.status().is_busy());
+
+        queue.push("a").unwrap();
+        assert_eq!(queue.push("b"), Err(QueueError::Full));
+        let status = queue.status();
+        assert_eq!((status.pending, status.dropped), (1, 1));
+        assert!(status.is_busy());
+
+        // Still busy while the taken command runs
+        queue.pop();
+        queue.set_running(true);
+        assert!(queue.status().is_busy());
+        queue.set_running(false);
+        assert_eq!(
+            queue.status(),
+            QueueStatus {
+                pending: 0,
+                capacity: 1,
+                running: false,
+                dropped: 1,
+            }
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_recv_wakes_on_push() {
+        use core::future::Future;
+        use core::task::{Context, Poll, Waker};
+
+        let queue: CommandQueue<8, 2> = CommandQueue::new();
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut recv = core::pin::pin!(queue.recv());
+        assert!(recv.as_mut().poll(&mut cx).is_pending());
+
+        queue.push("status").unwrap();
+        match recv.as_mut().poll(&mut cx) {
+            Poll::Ready(line) => assert_eq!(line.as_str(), "status"),
+            Poll::Pending => panic!("line not received"),
+        }
+    }
+}
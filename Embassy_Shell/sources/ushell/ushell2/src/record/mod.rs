@@ -0,0 +1,268 @@
+//! Command line recording and replay.
+//!
+//! `record start <name>` starts capturing: every command line that runs successfully
+//! afterwards is stored in the named slot until `record stop`. `replay <name>` runs the
+//! stored lines again, in order, stopping at the first error. `record` alone lists the
+//! recorded slots.
+//!
+//! ```text
+//! >> record start bringup
+//! >> gpio set 5 1
+//! >> baud 115200
+//! >> record stop
+//! bringup: 2 lines
+//! >> replay bringup
+//! replay: gpio set 5 1
+//! replay: baud 115200
+//! ```
+//!
+//! Slots are static and bounded; starting a recording under an existing name replaces
+//! it. Lines that do not fit are refused rather than silently left out of the recording.
+//! Recordings live in RAM and are lost on reset.
+
+use core::cell::RefCell;
+use critical_section::Mutex;
+use heapless::{String, Vec};
+
+/// Number of named recordings kept.
+pub const REC_MAX_SLOTS: usize = 2;
+
+/// Maximum number of command lines in one recording.
+pub const REC_MAX_LINES: usize = 8;
+
+/// Maximum length in bytes of a recorded command line.
+pub const REC_LINE_MAX: usize = 64;
+
+/// Maximum length in bytes of a recording name.
+pub const REC_NAME_MAX: usize = 16;
+
+/// The lines of one recording.
+pub type Recording = Vec<String<REC_LINE_MAX>, REC_MAX_LINES>;
+
+/// Why a recording operation was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecError {
+    /// `record start` while recording.
+    AlreadyRecording,
+    /// `record stop` while not recording.
+    NotRecording,
+    /// Empty name or longer than `REC_NAME_MAX`.
+    BadName,
+    /// All `REC_MAX_SLOTS` slots hold other recordings.
+    NoFreeSlot,
+    /// No recording with that name.
+    Unknown,
+    /// The recording holds `REC_MAX_LINES` lines already.
+    Full,
+    /// The line is longer than `REC_LINE_MAX`.
+    LineTooLong,
+}
+
+impl RecError {
+    pub const fn message(&self) -> &'static str {
+        match self {
+            RecError::AlreadyRecording => "already recording (record stop first)",
+            RecError::NotRecording => "not recording",
+            RecError::BadName => "bad recording name",
+            RecError::NoFreeSlot => "no free recording slot",
+            RecError::Unknown => "no such recording",
+            RecError::Full => "recording full (record stop first)",
+            RecError::LineTooLong => "command too long for the recording",
+        }
+    }
+}
+
+struct Slot {
+    name: String<REC_NAME_MAX>,
+    lines: Recording,
+}
+
+struct Recorder {
+    /// Index in `slots` of the recording in progress.
+    active: Option<usize>,
+    slots: Vec<Slot, REC_MAX_SLOTS>,
+}
+
+static RECORDER: Mutex<RefCell<Recorder>> = Mutex::new(RefCell::new(Recorder {
+    active: None,
+    slots: Vec::new(),
+}));
+
+/// Returns `true` while recording.
+pub fn is_recording() -> bool {
+    critical_section::with(|cs| RECORDER.borrow_ref(cs).active.is_some())
+}
+
+/// Starts recording into the slot `name`, replacing a recording of the same name.
+pub fn start(name: &str) -> Result<(), RecError> {
+    let mut slot_name = String::new();
+    if name.is_empty() || slot_name.push_str(name).is_err() {
+        return Err(RecError::BadName);
+    }
+    critical_section::with(|cs| {
+        let mut recorder = RECORDER.borrow_ref_mut(cs);
+        if recorder.active.is_some() {
+            return Err(RecError::AlreadyRecording);
+        }
+        let index = match recorder.slots.iter().position(|slot| slot.name == name) {
+            Some(index) => {
+                recorder.slots[index].lines.clear();
+                index
+            }
+            None => {
+                let slot = Slot {
+                    name: slot_name,
+                    lines: Vec::new(),
+                };
+                recorder
+                    .slots
+                    .push(slot)
+                    .map_err(|_| RecError::NoFreeSlot)?;
+                recorder.slots.len() - 1
+            }
+        };
+        recorder.active = Some(index);
+        Ok(())
+    })
+}
+
+/// Stops recording; returns the name and number of lines of the recording.
+pub fn stop() -> Result<(String<REC_NAME_MAX>, usize), RecError> {
+    critical_section::with(|cs| {
+        let mut recorder = RECORDER.borrow_ref_mut(cs);
+        let index = recorder.active.take().ok_or(RecError::NotRecording)?;
+        let slot = &recorder.slots[index];
+        Ok((slot.name.clone(), slot.lines.len()))
+    })
+}
+
+/// Checks that `line` can be recorded if it runs; always `Ok` while not recording.
+pub fn check_room(line: &str) -> Result<(), RecError> {
+    critical_section::with(|cs| {
+        let recorder = RECORDER.borrow_ref(cs);
+        let Some(index) = recorder.active else {
+            return Ok(());
+        };
+        if line.len() > REC_LINE_MAX {
+            Err(RecError::LineTooLong)
+        } else if recorder.slots[index].lines.is_full() {
+            Err(RecError::Full)
+        } else {
+            Ok(())
+        }
+    })
+}
+
+/// Records a line that ran successfully; ignored while not recording.
+/// Lines refused by `check_room` are dropped.
+pub fn record(line: &str) {
+    critical_section::with(|cs| {
+        let mut recorder = RECORDER.borrow_ref_mut(cs);
+        let Some(index) = recorder.active else {
+            return;
+        };
+        let mut entry = String::new();
+        if entry.push_str(line.trim()).is_ok() {
+            let _ = recorder.slots[index].lines.push(entry);
+        }
+    });
+}
+
+/// Returns a copy of the recording `name`, to be replayed outside the lock.
+pub fn recording(name: &str) -> Result<Recording, RecError> {
+    critical_section::with(|cs| {
+        let recorder = RECORDER.borrow_ref(cs);
+        recorder
+            .slots
+            .iter()
+            .find(|slot| slot.name == name)
+            .map(|slot| slot.lines.clone())
+            .ok_or(RecError::Unknown)
+    })
+}
+
+/// Calls `f` with the name and number of lines of each recording.
+pub fn for_each(mut f: impl FnMut(&str, usize)) {
+    critical_section::with(|cs| {
+        for slot in RECORDER.borrow_ref(cs).slots.iter() {
+            f(&slot.name, slot.lines.len());
+        }
+    });
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use std::vec::Vec as StdVec;
+
+    // Tests share the global recorder
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    fn reset() {
+        critical_section::with(|cs| {
+            let mut recorder = RECORDER.borrow_ref_mut(cs);
+            recorder.active = None;
+            recorder.slots.clear();
+        });
+    }
+
+    #[test]
+    fn test_record_and_replace() {
+        let _guard = LOCK.lock().unwrap();
+        reset();
+
+        record("ignored");
+        assert_eq!(stop(), Err(RecError::NotRecording));
+        assert_eq!(start("boot"), Ok(()));
+        assert!(is_recording());
+        assert_eq!(start("other"), Err(RecError::AlreadyRecording));
+        record("gpio set 5 1 ");
+        record("baud 115200");
+        assert_eq!(stop().unwrap(), (String::try_from("boot").unwrap(), 2));
+        assert!(!is_recording());
+
+        let lines = recording("boot").unwrap();
+        assert_eq!(lines, ["gpio set 5 1", "baud 115200"]);
+        assert_eq!(recording("nope"), Err(RecError::Unknown));
+
+        // Same name: replaced, not a new slot
+        start("boot").unwrap();
+        record("led 1");
+        stop().unwrap();
+        assert_eq!(recording("boot").unwrap(), ["led 1"]);
+
+        let mut listed = StdVec::new();
+        for_each(|name, len| listed.push((std::string::String::from(name), len)));
+        assert_eq!(listed, [("boot".into(), 1)]);
+    }
+
+    #[test]
+    fn test_bounds() {
+        let _guard = LOCK.lock().unwrap();
+        reset();
+
+        assert_eq!(start(""), Err(RecError::BadName));
+        assert_eq!(start(&"n".repeat(REC_NAME_MAX + 1)), Err(RecError::BadName));
+        for n in 0..REC_MAX_SLOTS {
+            start(&std::format!("m{}", n)).unwrap();
+            stop().unwrap();
+        }
+        assert_eq!(start("extra"), Err(RecError::NoFreeSlot));
+
+        start("m0").unwrap();
+        let long = "x".repeat(REC_LINE_MAX + 1);
+        assert_eq!(check_room(&long), Err(RecError::LineTooLong));
+        for _ in 0..REC_MAX_LINES {
+            assert_eq!(check_room("led 1"), Ok(()));
+            record("led 1");
+        }
+        assert_eq!(check_room("led 1"), Err(RecError::Full));
+        assert_eq!(stop().unwrap().1, REC_MAX_LINES);
+        assert_eq!(check_room(&long), Ok(()));
+    }
+}
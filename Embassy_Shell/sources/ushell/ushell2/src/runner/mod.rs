@@ -22,16 +22,41 @@
 extern crate core;
 extern crate heapless;
 
+#[cfg(feature = "async")]
+use core::future::{poll_fn, Future};
+use core::iter::Iterator;
 use core::ops::FnMut;
 use core::option::Option::{self, None, Some};
+#[cfg(feature = "async")]
+use core::pin::pin;
 use core::result::Result::{self, Err, Ok};
+use core::task::Context;
+#[cfg(not(feature = "async"))]
+use core::task::Waker;
 use heapless::String;
 
+use crate::autocomplete::{ArgCompleter, CompletionSource};
+#[cfg(feature = "async")]
+use crate::clock;
+use crate::history::DuplicatePolicy;
+use crate::input::bindings::KeyBindings;
 use crate::input::key_reader::embedded::AnsiKeyParser;
 use crate::input::key_reader::Key;
-use crate::input::parser::InputParser;
-use crate::input::renderer::CallbackWriter;
-use crate::{log_error, log_info};
+use crate::input::parser::{builtin_level, CommandDescriber, InputParser, MouseHandler};
+use crate::interact::{self, ExecOutcome};
+use crate::jobs::{self, AsyncCommand};
+use crate::logger::{self, UnifiedWriter};
+use crate::memtools::MemAccess;
+use crate::pipe;
+use crate::queue::CommandQueue;
+use crate::record;
+use crate::storage::{HistoryStore, StorageControl};
+use crate::theme::{self, Theme};
+use crate::transaction;
+use crate::vars;
+#[cfg(feature = "xmodem")]
+use crate::xmodem::Transfer;
+use crate::{log_error, log_info, log_simple, log_warn};
 
 #[cfg(feature = "hosted")]
 use crate::terminal::RawMode;
@@ -134,6 +159,7 @@ mod async_impl {
         yield_fn: fn() -> Y,
         empty_count: u32,
         yield_threshold: u32,
+        clock: Option<fn() -> u64>,
     }
 
     impl<F, Y> AsyncReader<F, Y>
@@ -167,8 +193,17 @@ mod async_impl {
                 yield_fn,
                 empty_count: 0,
                 yield_threshold,
+                clock: None,
             }
         }
+
+        /// Drives `crate::clock` from `now_ms`, a free-running millisecond counter such
+        /// as `|| Instant::now().as_millis()`, on every read.
+        #[inline]
+        pub const fn with_clock(mut self, now_ms: fn() -> u64) -> Self {
+            self.clock = Some(now_ms);
+            self
+        }
     }
 
     impl<F, Y> UartReader for AsyncReader<F, Y>
@@ -177,6 +212,10 @@ mod async_impl {
         Y: core::future::Future<Output = ()>,
     {
         async fn read_byte(&mut self) -> Option<u8> {
+            if let Some(now_ms) = self.clock {
+                clock::sync(now_ms());
+            }
+
             // Try to read data
             if let Some(byte) = (self.try_read_fn)() {
                 self.empty_count = 0;
@@ -201,16 +240,452 @@ mod async_impl {
 // Shell Configuration
 // ============================================================================
 
+/// Command line dispatcher as generated by the dispatcher macros (`rollback`, or
+/// `dispatch` wrapped with the session's privilege level).
+pub type Dispatcher<const EBS: usize> =
+    for<'a> fn(&'a str, &'a mut String<EBS>) -> Result<(), &'a str>;
+
+#[derive(::core::clone::Clone, ::core::marker::Copy)]
 pub struct ShellConfig<const IML: usize, const EBS: usize> {
     pub get_commands: fn() -> &'static [(&'static str, &'static str)],
     pub get_datatypes: fn() -> &'static str,
     pub get_shortcuts: fn() -> &'static str,
     pub is_shortcut: fn(&str) -> bool,
-    pub command_dispatcher: for<'a> fn(&'a str, &'a mut String<EBS>) -> Result<(), &'a str>,
-    pub shortcut_dispatcher: for<'a> fn(&'a str, &'a mut String<EBS>) -> Result<(), &'a str>,
+    /// Lines for which this returns true are not stored in history (generated `is_nohistory`).
+    pub is_nohistory: fn(&str) -> bool,
+    /// What running a line already in the history does: rejected, moved to the newest
+    /// place or added again (see `DuplicatePolicy`).
+    pub history_duplicates: DuplicatePolicy,
+    /// Time in seconds (uptime, or the Unix time of an RTC) the lines added to the
+    /// history are stamped with, shown by `history -t`; `None` stores no times.
+    pub history_clock: Option<fn() -> u32>,
+    /// Millisecond clock for periodic built-ins such as `watch`, e.g. `clock::now_ms`
+    /// (see `crate::clock`); `None` disables them.
+    pub now_ms: Option<fn() -> u64>,
+    /// Tab completion of arguments (see `autocomplete::args`); `None` completes command names only.
+    pub complete_arg: Option<ArgCompleter>,
+    /// Completion values registered by command handlers at runtime, e.g. a static
+    /// `CompletionRegistry` (see `autocomplete::registry`); `None` disables them.
+    pub completions: Option<&'static dyn CompletionSource>,
+    /// Checks the arguments against the command's descriptor while they are typed and
+    /// shows the first problem at the right of the line (see `InputParser::set_live_validation`).
+    pub live_validation: bool,
+    /// Shows the completion of a command name as ghost text after the cursor, accepted
+    /// with Right arrow or Tab (see `InputParser::set_ghost_completion`).
+    pub ghost_completion: bool,
+    /// Completes typed lines from the history, arguments included, with Up, Down and Tab
+    /// (see `InputParser::set_history_completion`).
+    pub history_completion: bool,
+    /// Snippets as `(name, template)` pairs, expanded by `snippet_key` (see `InputParser::set_snippets`).
+    pub snippets: &'static [(&'static str, &'static str)],
+    /// Key expanding the snippet name before the cursor; `None` disables snippets.
+    pub snippet_key: Option<Key>,
+    pub command_dispatcher: Dispatcher<EBS>,
+    pub shortcut_dispatcher: Dispatcher<EBS>,
+    /// Privilege level of the session, the one `command_dispatcher` passes to `dispatch`:
+    /// built-ins needing more are refused (see `parser::BUILTINS`); `None` runs them all.
+    pub privilege_level: Option<fn() -> u8>,
+    /// Commands run as background jobs, as `(name, handler)` pairs: the handler returns a
+    /// future that the shell polls while it keeps reading keys (see `crate::jobs`).
+    pub async_commands: &'static [(&'static str, AsyncCommand)],
+    /// Runs the rollback callback of a command line (generated `rollback`), used by the
+    /// `abort` built-in (see `transaction`); `None` leaves aborted commands in place.
+    pub rollback_dispatcher: Option<Dispatcher<EBS>>,
+    /// Receives a copy of all shell output, e.g. `output::itm_write` to trace the session
+    /// over SWO while the shell runs on the UART; `None` writes to `write_fn` only.
+    pub mirror_write: Option<fn(&[u8])>,
+    /// Deferred execution (see `queue`): entered lines are passed to this function, which
+    /// queues them for `exec_queued` / `run_executor` and returns `false` if it could not.
+    /// `None` runs each command as soon as it is entered.
+    pub submit: Option<fn(&str) -> bool>,
+    /// Tells whether deferred commands are running or queued, e.g.
+    /// `|| QUEUE.status().is_busy()`; the prompt then shows `parser::BUSY_MARKER`.
+    pub is_busy: Option<fn() -> bool>,
+    /// Time budget in milliseconds of a command, looked up by command name (`None`: no
+    /// budget). A command running longer is reported with a warning, a background job
+    /// (see `async_commands`) is stopped; needs `now_ms`.
+    pub time_budget: Option<fn(&str) -> Option<u32>>,
+    /// Called with the command name and its run time when it exceeded its budget, or
+    /// when its background job was stopped for that.
+    pub on_command_timeout: Option<fn(&str, u64)>,
+    /// Command table listed by the `describe` and searched by the `apropos` built-in
+    /// (generated `describe_command`); `None` disables both.
+    pub describe_command: Option<CommandDescriber>,
+    /// Receives mouse reports (see `Key::Mouse`); `None` drops them.
+    pub on_mouse: Option<MouseHandler>,
+    /// Persistent storage driven by the `storage [info|mount|format]` built-in (see
+    /// `crate::storage`); `None` disables it.
+    pub storage: Option<StorageControl>,
+    /// Regions the memory built-ins of the `mem-tools` feature may read and write (see
+    /// `crate::memtools`); `None` refuses every access.
+    pub mem_access: Option<MemAccess>,
+    /// Storage the command history is kept in across resets, e.g. a `FlashHistory` (see
+    /// `crate::storage::HistoryStore`); `None` keeps it in RAM only.
+    pub history_store: Option<HistoryStore>,
+    /// Terminal height in lines: the `#`, `##` and `## <group>` listings stop at a
+    /// `--more--` prompt after each screenful (see `InputParser::set_page_rows`); `None`
+    /// prints them at once.
+    pub terminal_rows: Option<u16>,
+    /// Terminal width in columns: longer input lines scroll horizontally (see
+    /// `InputParser::set_terminal_columns`); `None` lets the terminal wrap them.
+    pub terminal_cols: Option<u16>,
+    /// Asks the terminal for its size at startup (see `InputParser::probe_terminal_size`);
+    /// a reply replaces `terminal_rows` and `terminal_cols`, which stay in use on
+    /// terminals that do not answer.
+    pub probe_terminal_size: bool,
+    /// Enables bracketed paste on the terminal: pasted text is inserted verbatim instead
+    /// of being typed key by key (see `InputParser::set_bracketed_paste`).
+    pub bracketed_paste: bool,
+    /// Actions of the keys on the edit line, e.g. `&bindings::VI` (see
+    /// `crate::input::bindings`); `None` uses `bindings::EMACS`.
+    pub key_bindings: Option<&'static KeyBindings>,
+    /// Colors of the prompt, errors, hints and help headers, e.g. `&theme::PLAIN` for a
+    /// terminal without colors (see `crate::theme`); `None` uses `theme::DEFAULT`.
+    pub theme: Option<&'static Theme>,
+    /// Default prompt; replaced at runtime with the `prompt` built-in (see `crate::prompt`).
     pub prompt: &'static str,
 }
 
+/// A `ShellConfig` that does not fit the shell it is passed to (see `ShellConfig::validate`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigError {
+    /// `prompt` is empty.
+    EmptyPrompt,
+    /// More commands start with this letter than the `NAC` autocomplete candidates.
+    TooManyCommands(char),
+    /// A command name is longer than `FNL`, so autocomplete cuts it short.
+    NameTooLong(&'static str),
+    /// A command name does not fit in the input line (`IML`).
+    NameExceedsInput(&'static str),
+    /// `EBS` is 0, so dispatch errors cannot be reported.
+    NoErrorBuffer,
+    /// `snippet_key` is set but there are no `snippets`.
+    NoSnippets,
+    /// `time_budget` is set without `now_ms`.
+    BudgetWithoutClock,
+    /// A command is named like a built-in, which runs instead (see `parser::BUILTINS`).
+    BuiltinName(&'static str),
+    /// The `xmodem` feature is enabled without `now_ms` to time the transfers.
+    #[cfg(feature = "xmodem")]
+    XmodemWithoutClock,
+}
+
+impl ConfigError {
+    pub const fn message(&self) -> &'static str {
+        match self {
+            ConfigError::EmptyPrompt => "empty prompt",
+            ConfigError::TooManyCommands(_) => "more commands per letter than NAC",
+            ConfigError::NameTooLong(_) => "command name longer than FNL",
+            ConfigError::NameExceedsInput(_) => "command name longer than the input line",
+            ConfigError::NoErrorBuffer => "error buffer of size 0",
+            ConfigError::NoSnippets => "snippet key without snippets",
+            ConfigError::BudgetWithoutClock => "time budget without clock",
+            ConfigError::BuiltinName(_) => "command named like a built-in",
+            #[cfg(feature = "xmodem")]
+            ConfigError::XmodemWithoutClock => "xmodem without clock",
+        }
+    }
+}
+
+impl<const IML: usize, const EBS: usize> ShellConfig<IML, EBS> {
+    /// Checks the configuration against the shell's `NAC` (autocomplete candidates
+    /// per letter) and `FNL` (function name length); returns the first problem found.
+    ///
+    /// Called when the shell is created if the `debug-assert` feature is enabled, which
+    /// logs the problem instead of leaving it to show up as odd behavior later.
+    pub fn validate<const NAC: usize, const FNL: usize>(&self) -> Result<(), ConfigError> {
+        if self.prompt.is_empty() {
+            return Err(ConfigError::EmptyPrompt);
+        }
+        if EBS == 0 {
+            return Err(ConfigError::NoErrorBuffer);
+        }
+        let commands = (self.get_commands)();
+        for (index, &(name, _)) in commands.iter().enumerate() {
+            if name.len() > FNL {
+                return Err(ConfigError::NameTooLong(name));
+            }
+            if name.len() >= IML {
+                return Err(ConfigError::NameExceedsInput(name));
+            }
+            // The first word decides: a group named like a built-in is shadowed too
+            if builtin_level(name.split(' ').next().unwrap_or(name)).is_some() {
+                return Err(ConfigError::BuiltinName(name));
+            }
+            let Some(first) = name.chars().next() else {
+                continue;
+            };
+            // Counted at the first command of each letter
+            let seen = commands[..index].iter().any(|(n, _)| n.starts_with(first));
+            if !seen
+                && commands
+                    .iter()
+                    .filter(|(n, _)| n.starts_with(first))
+                    .count()
+                    > NAC
+            {
+                return Err(ConfigError::TooManyCommands(first));
+            }
+        }
+        if self.snippet_key.is_some() && self.snippets.is_empty() {
+            return Err(ConfigError::NoSnippets);
+        }
+        if self.time_budget.is_some() && self.now_ms.is_none() {
+            return Err(ConfigError::BudgetWithoutClock);
+        }
+        #[cfg(feature = "xmodem")]
+        if self.now_ms.is_none() {
+            return Err(ConfigError::XmodemWithoutClock);
+        }
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Parser Setup
+// ============================================================================
+
+/// Shell output: `write_fn`, plus a copy of everything to `mirror` (see
+/// `ShellConfig::mirror_write`).
+#[derive(::core::clone::Clone, ::core::marker::Copy)]
+pub struct ShellWriter {
+    write_fn: fn(&[u8]),
+    flush_fn: fn(),
+    mirror: Option<fn(&[u8])>,
+}
+
+impl ShellWriter {
+    pub const fn new(write_fn: fn(&[u8]), flush_fn: fn(), mirror: Option<fn(&[u8])>) -> Self {
+        Self {
+            write_fn,
+            flush_fn,
+            mirror,
+        }
+    }
+}
+
+impl UnifiedWriter for ShellWriter {
+    fn write_str(&mut self, s: &str) {
+        self.write_bytes(s.as_bytes());
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        (self.write_fn)(bytes);
+        if let Some(mirror) = self.mirror {
+            mirror(bytes);
+        }
+    }
+
+    fn flush(&mut self) {
+        (self.flush_fn)();
+    }
+}
+
+/// Creates the input parser with the tables and options of `config`.
+fn new_parser<
+    W: UnifiedWriter,
+    const NAC: usize,
+    const FNL: usize,
+    const IML: usize,
+    const HTC: usize,
+    const EBS: usize,
+>(
+    writer: W,
+    config: &ShellConfig<IML, EBS>,
+) -> InputParser<'static, W, NAC, FNL, IML, HTC> {
+    #[cfg(feature = "debug-assert")]
+    if let Err(err) = config.validate::<NAC, FNL>() {
+        log_error!("shell config: {} ({:?})", err.message(), err);
+    }
+
+    let mut parser = InputParser::new(
+        writer,
+        (config.get_commands)(),
+        (config.get_datatypes)(),
+        (config.get_shortcuts)(),
+        config.prompt,
+    );
+    parser.set_history_filter(config.is_nohistory);
+    parser.set_history_duplicates(config.history_duplicates);
+    if let Some(now) = config.history_clock {
+        parser.set_history_clock(now);
+    }
+    if let Some(now_ms) = config.now_ms {
+        parser.set_clock(now_ms);
+    }
+    if let Some(completer) = config.complete_arg {
+        parser.set_arg_completer(completer);
+    }
+    if let Some(completions) = config.completions {
+        parser.set_completions(completions);
+    }
+    parser.set_live_validation(config.live_validation);
+    parser.set_ghost_completion(config.ghost_completion);
+    parser.set_history_completion(config.history_completion);
+    if let Some(key) = config.snippet_key {
+        parser.set_snippets(config.snippets, key);
+    }
+    if let Some(is_busy) = config.is_busy {
+        parser.set_busy_indicator(is_busy);
+    }
+    if let Some(describe) = config.describe_command {
+        parser.set_describer(describe);
+    }
+    if let Some(on_mouse) = config.on_mouse {
+        parser.set_mouse_handler(on_mouse);
+    }
+    if let Some(storage) = config.storage {
+        parser.set_storage_control(storage);
+    }
+    if let Some(access) = config.mem_access {
+        parser.set_mem_access(access);
+    }
+    if let Some(level) = config.privilege_level {
+        parser.set_privilege_level(level);
+    }
+    if let Some(store) = config.history_store {
+        if let Err(err) = parser.set_history_store(store) {
+            log_warn!("history: {}", err.message());
+        }
+    }
+    if let Some(rows) = config.terminal_rows {
+        parser.set_page_rows(rows);
+    }
+    if let Some(columns) = config.terminal_cols {
+        parser.set_terminal_columns(columns);
+    }
+    if config.probe_terminal_size {
+        parser.probe_terminal_size();
+    }
+    if config.bracketed_paste {
+        parser.set_bracketed_paste(true);
+    }
+    if let Some(bindings) = config.key_bindings {
+        parser.set_key_bindings(bindings);
+    }
+    if let Some(theme) = config.theme {
+        parser.set_theme(theme);
+    }
+
+    parser
+}
+
+// ============================================================================
+// Step-based Async Shell
+// ============================================================================
+
+/// Step-based shell for Embassy: each `step().await` reads one byte from the reader
+/// and processes it, so a task can interleave the shell with other work instead of
+/// handing itself over to `run_shell`.
+///
+/// ```ignore
+/// let mut shell = AsyncShellCtx::<
+///     { commands::MAX_COMMANDS_PER_LETTER },
+///     { commands::MAX_FUNCTION_NAME_LEN },
+///     { MAX_INPUT_LEN },
+///     { MAX_HISTORY_CAPACITY },
+///     { MAX_ERROR_BUFFER_SIZE },
+///     _,
+/// >::new(uart_write, uart_flush, reader, config);
+///
+/// loop {
+///     match select(shell.step(), BUTTON.wait()).await {
+///         Either::First(running) if !running => break,
+///         Either::First(_) => {}
+///         Either::Second(_) => toggle_led(),
+///     }
+/// }
+/// ```
+///
+/// `step` is cancel-safe between bytes: a byte is either processed or still queued in
+/// the reader when the future is dropped while waiting.
+#[cfg(feature = "async")]
+pub struct AsyncShellCtx<
+    const NAC: usize,
+    const FNL: usize,
+    const IML: usize,
+    const HTC: usize,
+    const EBS: usize,
+    R: UartReader,
+> {
+    parser: InputParser<'static, ShellWriter, NAC, FNL, IML, HTC>,
+    writer: ShellWriter,
+    key_parser: AnsiKeyParser,
+    pending_key: Option<Key>,
+    reader: R,
+    config: ShellConfig<IML, EBS>,
+    #[cfg(feature = "xmodem")]
+    transfer: Option<Transfer>,
+}
+
+#[cfg(feature = "async")]
+impl<
+        const NAC: usize,
+        const FNL: usize,
+        const IML: usize,
+        const HTC: usize,
+        const EBS: usize,
+        R: UartReader,
+    > AsyncShellCtx<NAC, FNL, IML, HTC, EBS, R>
+{
+    /// Creates the shell and writes the banner and the prompt.
+    pub fn new(
+        write_fn: fn(&[u8]),
+        flush_fn: fn(),
+        reader: R,
+        config: ShellConfig<IML, EBS>,
+    ) -> Self {
+        let writer = ShellWriter::new(write_fn, flush_fn, config.mirror_write);
+        Self {
+            parser: new_parser(writer, &config),
+            writer,
+            key_parser: AnsiKeyParser::new(),
+            pending_key: None,
+            reader,
+            config,
+            #[cfg(feature = "xmodem")]
+            transfer: None,
+        }
+    }
+
+    /// Waits for the next byte (yielding to the executor, see `AsyncReader`) and
+    /// advances the parser. Returns `false` once the shell was exited (`#q`).
+    pub async fn step(&mut self) -> bool {
+        let byte = if self.config.async_commands.is_empty() {
+            self.reader.read_byte().await
+        } else {
+            // Background jobs run while the shell waits for input
+            let mut read = pin!(self.reader.read_byte());
+            poll_fn(|cx| {
+                poll_jobs(cx, &self.config);
+                read.as_mut().poll(cx)
+            })
+            .await
+        };
+        #[cfg(feature = "xmodem")]
+        if feed_transfer(&mut self.transfer, byte, &mut self.writer, &self.config) {
+            return true;
+        }
+        if let Some(byte) = byte {
+            if let Some(key) = self.key_parser.parse_byte(byte) {
+                self.pending_key = Some(key);
+            }
+        }
+
+        let (writer, pending_key, config) = (&mut self.writer, &mut self.pending_key, &self.config);
+        self.parser.parse_input(
+            || pending_key.take(),
+            |s: &str| writer.write_str(s),
+            |input: &String<IML>| submit_or_exec(input.as_str(), config),
+        )
+    }
+
+    /// The input parser, e.g. to print above the prompt between steps.
+    pub fn parser(&mut self) -> &mut InputParser<'static, ShellWriter, NAC, FNL, IML, HTC> {
+        &mut self.parser
+    }
+}
+
 // ============================================================================
 // Unified Shell Runner
 // ============================================================================
@@ -264,56 +739,12 @@ pub async fn run_shell<
 >(
     write_fn: fn(&[u8]),
     flush_fn: fn(),
-    mut reader: R,
+    reader: R,
     config: ShellConfig<IML, EBS>,
 ) {
-    let writer = CallbackWriter::new(write_fn, flush_fn);
-
-    // Get static data references once before loop instead of calling every iteration
-    let commands = (config.get_commands)();
-    let datatypes = (config.get_datatypes)();
-    let shortcuts = (config.get_shortcuts)();
-
-    let mut parser = InputParser::<CallbackWriter<fn(&[u8]), fn()>, NAC, FNL, IML, HTC>::new(
-        writer,
-        commands,
-        datatypes,
-        shortcuts,
-        config.prompt,
-    );
-
-    let mut key_parser = AnsiKeyParser::new();
-    let mut pending_key: Option<Key> = None;
-
-    loop {
-        // Async read - yields to executor when no data available
-        if let Some(byte) = reader.read_byte().await {
-            if let Some(key) = key_parser.parse_byte(byte) {
-                pending_key = Some(key);
-            }
-        }
-
-        // Process pending key
-        let continue_running = parser.parse_input(
-            || pending_key.take(),
-            |s: &str| {
-                write_fn(s.as_bytes());
-            },
-            |input: &String<IML>| {
-                // Pass input as &str to avoid potential string copies
-                exec::<EBS>(
-                    input.as_str(),
-                    config.is_shortcut,
-                    config.command_dispatcher,
-                    config.shortcut_dispatcher,
-                )
-            },
-        );
-
-        if !continue_running {
-            break;
-        }
-    }
+    let mut shell =
+        AsyncShellCtx::<NAC, FNL, IML, HTC, EBS, R>::new(write_fn, flush_fn, reader, config);
+    while shell.step().await {}
 }
 
 #[cfg(not(feature = "async"))]
@@ -330,27 +761,26 @@ pub fn run_shell<
     mut reader: R,
     config: ShellConfig<IML, EBS>,
 ) {
-    let writer = CallbackWriter::new(write_fn, flush_fn);
-
-    // Get static data references once before loop
-    let commands = (config.get_commands)();
-    let datatypes = (config.get_datatypes)();
-    let shortcuts = (config.get_shortcuts)();
-
-    let mut parser = InputParser::<CallbackWriter<fn(&[u8]), fn()>, NAC, FNL, IML, HTC>::new(
-        writer,
-        commands,
-        datatypes,
-        shortcuts,
-        config.prompt,
-    );
+    let mut writer = ShellWriter::new(write_fn, flush_fn, config.mirror_write);
+    let mut parser = new_parser::<_, NAC, FNL, IML, HTC, EBS>(writer, &config);
 
     let mut key_parser = AnsiKeyParser::new();
     let mut pending_key: Option<Key> = None;
+    #[cfg(feature = "xmodem")]
+    let mut transfer: Option<Transfer> = None;
 
     loop {
+        if !config.async_commands.is_empty() {
+            poll_jobs(&mut Context::from_waker(Waker::noop()), &config);
+        }
+
         // Sync read - polls without yielding
-        if let Some(byte) = reader.read_byte() {
+        let byte = reader.read_byte();
+        #[cfg(feature = "xmodem")]
+        if feed_transfer(&mut transfer, byte, &mut writer, &config) {
+            continue;
+        }
+        if let Some(byte) = byte {
             if let Some(key) = key_parser.parse_byte(byte) {
                 pending_key = Some(key);
             }
@@ -361,16 +791,11 @@ pub fn run_shell<
         let continue_running = parser.parse_input(
             || pending_key.take(),
             |s: &str| {
-                write_fn(s.as_bytes());
+                writer.write_str(s);
             },
             |input: &String<IML>| {
                 // Pass input as &str to avoid potential string copies
-                exec::<EBS>(
-                    input.as_str(),
-                    config.is_shortcut,
-                    config.command_dispatcher,
-                    config.shortcut_dispatcher,
-                )
+                submit_or_exec(input.as_str(), &config)
             },
         );
 
@@ -380,29 +805,463 @@ pub fn run_shell<
     }
 }
 
+/// Passes `byte` to the XMODEM transfer started by the last command (see
+/// `xmodem::start`), if any; returns whether a transfer is running, the line editor
+/// being left alone meanwhile.
+#[cfg(feature = "xmodem")]
+fn feed_transfer<const IML: usize, const EBS: usize>(
+    transfer: &mut Option<Transfer>,
+    byte: Option<u8>,
+    writer: &mut ShellWriter,
+    config: &ShellConfig<IML, EBS>,
+) -> bool {
+    if transfer.is_none() {
+        *transfer = Transfer::take_started(config.now_ms);
+    }
+    let Some(running) = transfer.as_mut() else {
+        return false;
+    };
+    if !running.step(byte, writer) {
+        *transfer = None;
+    }
+    true
+}
+
 // ============================================================================
 // Command Execution
 // ============================================================================
 
+/// Queue the line if deferred execution is configured, otherwise run it now.
+#[inline]
+fn submit_or_exec<const IML: usize, const EBS: usize>(
+    input_str: &str,
+    config: &ShellConfig<IML, EBS>,
+) -> ExecOutcome {
+    match config.submit {
+        Some(submit) => {
+            if !submit(input_str) {
+                log_warn!("busy: command dropped");
+            }
+            ExecOutcome::Done
+        }
+        None => exec_timed(input_str, config),
+    }
+}
+
+/// Deferred execution (see `queue`): runs the oldest queued line, if any, and returns
+/// whether one was run. Call it from the low-priority context until it returns `false`.
+pub fn exec_queued<const IML: usize, const N: usize, const EBS: usize>(
+    queue: &CommandQueue<IML, N>,
+    config: &ShellConfig<IML, EBS>,
+) -> bool {
+    let Some(line) = queue.pop() else {
+        return false;
+    };
+    queue.set_running(true);
+    exec_deferred(line.as_str(), config);
+    queue.set_running(false);
+    true
+}
+
+/// Deferred execution (see `queue`): waits for queued lines and runs them, forever.
+#[cfg(feature = "async")]
+pub async fn run_executor<const IML: usize, const N: usize, const EBS: usize>(
+    queue: &CommandQueue<IML, N>,
+    config: ShellConfig<IML, EBS>,
+) -> ! {
+    loop {
+        let line = queue.recv().await;
+        queue.set_running(true);
+        exec_deferred(line.as_str(), &config);
+        queue.set_running(false);
+    }
+}
+
+/// Run a line taken from the queue; requests for input cannot be answered there.
+fn exec_deferred<const IML: usize, const EBS: usize>(
+    input_str: &str,
+    config: &ShellConfig<IML, EBS>,
+) {
+    let outcome = exec_timed(input_str, config);
+    if let ExecOutcome::NeedsInput(_) = outcome {
+        log_failure("queued commands cannot ask for input");
+    }
+}
+
+/// `exec` with the variables of the line expanded (see `vars`) and the run time of the
+/// command checked against its budget (`ShellConfig::time_budget`); a background job
+/// gets the budget of its command as well (see `jobs::poll`).
+fn exec_timed<const IML: usize, const EBS: usize>(
+    input_str: &str,
+    config: &ShellConfig<IML, EBS>,
+) -> ExecOutcome {
+    let expanded = match vars::expand::<IML>(input_str) {
+        Ok(expanded) => expanded,
+        Err(e) => {
+            log_failure(e.message());
+            return ExecOutcome::Done;
+        }
+    };
+    let input_str = expanded.as_deref().unwrap_or(input_str);
+    let name = input_str.split_whitespace().next().unwrap_or("");
+    // The clock is only read for commands with a budget
+    let budget = config
+        .now_ms
+        .zip(config.time_budget)
+        .and_then(|(now_ms, time_budget)| {
+            time_budget(name).map(|budget| (now_ms, now_ms(), budget))
+        });
+    let outcome = exec::<EBS>(
+        input_str,
+        config.is_shortcut,
+        config.command_dispatcher,
+        config.shortcut_dispatcher,
+        config.rollback_dispatcher,
+        config.async_commands,
+        budget.map(|(_, start, budget)| (start, budget)),
+    );
+    if let Some((now_ms, start, budget)) = budget {
+        let elapsed = now_ms().saturating_sub(start);
+        if elapsed > budget as u64 {
+            log_warn!("{}: took {} ms (budget {} ms)", name, elapsed, budget);
+            if let Some(on_command_timeout) = config.on_command_timeout {
+                on_command_timeout(name, elapsed);
+            }
+        }
+    }
+    outcome
+}
+
+/// Dispatch one command line. If the handler asked for input (e.g. `confirm`), the
+/// request is returned instead of reporting a result; the parser runs the line again later.
+///
+/// Also handles the transaction built-ins `begin`, `commit` and `abort`, the recording
+/// built-ins `record` and `replay`, the variable built-ins `set <NAME>`, `unset` and
+/// `echo` and the job built-ins `jobs` and `kill <id>`; starts the `async_commands` as
+/// background jobs (see `jobs`). Records the commands that succeed while a transaction
+/// or a recording is open. The output of a line piped into filters (`tasks | grep idle`, see `pipe`) is
+/// shown filtered.
 #[inline]
 fn exec<const EBS: usize>(
     input_str: &str,
     is_shortcut: fn(&str) -> bool,
-    command_dispatcher: for<'a> fn(&'a str, &'a mut String<EBS>) -> Result<(), &'a str>,
-    shortcut_dispatcher: for<'a> fn(&'a str, &'a mut String<EBS>) -> Result<(), &'a str>,
-) {
+    command_dispatcher: Dispatcher<EBS>,
+    shortcut_dispatcher: Dispatcher<EBS>,
+    rollback_dispatcher: Option<Dispatcher<EBS>>,
+    async_commands: &[(&'static str, AsyncCommand)],
+    budget: Option<(u64, u32)>,
+) -> ExecOutcome {
     let mut error_buffer: String<EBS> = String::new();
+    let mut dispatched = false;
 
-    let result = if is_shortcut(input_str) {
-        shortcut_dispatcher(input_str, &mut error_buffer)
-    } else {
-        command_dispatcher(input_str, &mut error_buffer)
+    let (input_str, stages) = match pipe::split(input_str) {
+        Some((command, stages)) => {
+            if let Err(e) = pipe::check(stages) {
+                log_failure(e.message());
+                return ExecOutcome::Done;
+            }
+            logger::begin_log_capture();
+            (command, Some(stages))
+        }
+        None => (input_str, None),
+    };
+
+    let line = input_str.trim();
+    let (word, args) = line
+        .split_once(' ')
+        .map_or((line, ""), |(word, args)| (word, args.trim()));
+    let result = match (word, args) {
+        ("begin", "") => transaction::begin().map_err(|e| e.message()),
+        ("commit", "") => transaction::commit().map(|_| ()).map_err(|e| e.message()),
+        ("abort", "") => abort_transaction(rollback_dispatcher),
+        ("record", args) => record_builtin(args),
+        ("replay", name) => replay(name, is_shortcut, command_dispatcher, shortcut_dispatcher),
+        ("set", args) if is_var_assignment(args) => set_var(args),
+        ("unset", name) => vars::unset(name).map_err(|e| e.message()),
+        ("echo", text) => {
+            log_simple!("{}", text);
+            Ok(())
+        }
+        ("jobs", "") => {
+            jobs::for_each(|id, name| log_info!("[{}] {}", id, name));
+            Ok(())
+        }
+        ("kill", id) => kill_job(id),
+        _ => match async_commands.iter().find(|&&(name, _)| name == word) {
+            Some(&(_, command)) => spawn_job(line, command, args, budget),
+            None => match check_room(input_str) {
+                Err(e) => Err(e),
+                Ok(()) => {
+                    dispatched = true;
+                    dispatch_line(
+                        input_str,
+                        is_shortcut,
+                        command_dispatcher,
+                        shortcut_dispatcher,
+                        &mut error_buffer,
+                    )
+                }
+            },
+        },
     };
 
+    let result = match stages {
+        Some(stages) => filter_output(stages, result),
+        None => result,
+    };
+    if let Some(pending) = interact::take_request() {
+        return ExecOutcome::NeedsInput(pending);
+    }
+
     match result {
-        Ok(_) => log_info!("Success"),
-        Err(e) => log_error!("Error: {}", e),
+        Ok(_) => {
+            if dispatched {
+                transaction::record(input_str);
+                record::record(input_str);
+            }
+            log_info!("Success");
+            // The return value of a `desc->T` command
+            if !error_buffer.is_empty() {
+                log_simple!("= {}", error_buffer);
+            }
+        }
+        Err(e) => log_failure(e),
+    }
+    ExecOutcome::Done
+}
+
+/// Reports why a line failed as `Error: <message>`, in the theme's error color unless
+/// the output is captured.
+fn log_failure(message: &str) {
+    let color = theme::theme().error;
+    if color.is_empty() || logger::is_log_capture_active() {
+        log_error!("Error: {}", message);
+    } else {
+        log_error!("{}Error: {}{}", color, message, theme::RESET);
+    }
+}
+
+/// Ends the capture of a piped command and shows its output through the filter
+/// `stages` (see `pipe`).
+fn filter_output<'a>(stages: &str, result: Result<(), &'a str>) -> Result<(), &'a str> {
+    if interact::take_request().is_some() {
+        logger::end_log_capture(|_, _| ());
+        return Err("piped commands cannot ask for input");
+    }
+    let (filtered, truncated) = logger::end_log_capture(|output, truncated| {
+        let filtered = pipe::run(stages, output, &mut |line| log_simple!("{}", line));
+        (filtered, truncated)
+    });
+    if truncated {
+        log_warn!("output truncated before filtering");
     }
+    result.and(filtered.map_err(|e| e.message()))
+}
+
+/// `abort` built-in: closes the transaction and rolls back its commands, newest first.
+fn abort_transaction<const EBS: usize>(
+    rollback_dispatcher: Option<Dispatcher<EBS>>,
+) -> Result<(), &'static str> {
+    transaction::abort(|line| {
+        let Some(rollback) = rollback_dispatcher else {
+            log_warn!("not rolled back: {}", line);
+            return;
+        };
+        let mut error_buffer: String<EBS> = String::new();
+        match rollback(line, &mut error_buffer) {
+            Ok(()) => log_info!("rollback: {}", line),
+            Err(e) => log_warn!("not rolled back: {} ({})", line, e),
+        }
+    })
+    .map(|_| ())
+    .map_err(|e| e.message())
+}
+
+/// Checks that a line can be journaled and recorded if it runs.
+fn check_room(input_str: &str) -> Result<(), &'static str> {
+    transaction::check_room(input_str).map_err(|e| e.message())?;
+    record::check_room(input_str).map_err(|e| e.message())
+}
+
+/// Tells the variable built-in `set` (`set`, `set <NAME> <value>`) from an application
+/// command of the same name, which takes lower-case keys.
+fn is_var_assignment(args: &str) -> bool {
+    args.is_empty() || vars::is_var_name(args.split_whitespace().next().unwrap_or(""))
+}
+
+/// `set` built-in: `set <NAME> <value>` sets a variable (a value in double quotes may
+/// contain spaces), `set` alone lists them (see `vars`).
+fn set_var(args: &str) -> Result<(), &'static str> {
+    if args.is_empty() {
+        vars::for_each(|name, value| log_info!("{}={}", name, value));
+        return Ok(());
+    }
+    let (name, value) = args.split_once(' ').unwrap_or((args, ""));
+    let value = value.trim();
+    let value = value
+        .strip_prefix('"')
+        .and_then(|quoted| quoted.strip_suffix('"'))
+        .unwrap_or(value);
+    vars::set(name, value).map_err(|e| e.message())
+}
+
+/// Starts the async command `command` as a background job named after its `line`. With
+/// a `budget` (start time of the line and budget of the command, in ms), the job is
+/// stopped once it runs longer.
+fn spawn_job(
+    line: &str,
+    command: AsyncCommand,
+    args: &str,
+    budget: Option<(u64, u32)>,
+) -> Result<(), &'static str> {
+    let job = command(args).map_err(|e| e.message())?;
+    let (started_ms, budget_ms) = budget.map_or((0, None), |(start, budget)| (start, Some(budget)));
+    let id = jobs::spawn(line, job, started_ms, budget_ms).map_err(|e| e.message())?;
+    log_info!("[{}] {}", id, line);
+    Ok(())
+}
+
+/// Polls the background jobs, stopping those past their budget (see `jobs::poll`).
+fn poll_jobs<const IML: usize, const EBS: usize>(
+    cx: &mut Context<'_>,
+    config: &ShellConfig<IML, EBS>,
+) {
+    let now_ms = config.now_ms.map_or(0, |now_ms| now_ms());
+    jobs::poll(cx, now_ms, config.on_command_timeout);
+}
+
+/// `kill <id>` built-in: stops a background job (see `jobs`).
+fn kill_job(id: &str) -> Result<(), &'static str> {
+    let id = id.parse().map_err(|_| "usage: kill <id>")?;
+    jobs::kill(id).map_err(|e| e.message())
+}
+
+/// `record` built-in: `record start <name>`, `record stop`, or `record` to list the
+/// recordings (see `record`).
+fn record_builtin(args: &str) -> Result<(), &'static str> {
+    let mut words = args.split_whitespace();
+    match (words.next(), words.next(), words.next()) {
+        (None, _, _) => {
+            record::for_each(|name, len| log_info!("{}: {} lines", name, len));
+            Ok(())
+        }
+        (Some("start"), Some(name), None) => record::start(name).map_err(|e| e.message()),
+        (Some("stop"), None, _) => record::stop()
+            .map(|(name, len)| log_info!("{}: {} lines", name, len))
+            .map_err(|e| e.message()),
+        _ => Err("usage: record [start <name> | stop]"),
+    }
+}
+
+/// `replay` built-in: runs the lines of a recording in order, stopping at the first
+/// error. Replayed lines are journaled and recorded like typed ones.
+fn replay<const EBS: usize>(
+    name: &str,
+    is_shortcut: fn(&str) -> bool,
+    command_dispatcher: Dispatcher<EBS>,
+    shortcut_dispatcher: Dispatcher<EBS>,
+) -> Result<(), &'static str> {
+    let lines = record::recording(name).map_err(|e| e.message())?;
+    for line in lines.iter() {
+        log_info!("replay: {}", line);
+        check_room(line)?;
+        let mut error_buffer: String<EBS> = String::new();
+        let result = dispatch_line(
+            line,
+            is_shortcut,
+            command_dispatcher,
+            shortcut_dispatcher,
+            &mut error_buffer,
+        );
+        if interact::take_request().is_some() {
+            return Err("replayed commands cannot ask for input");
+        }
+        if let Err(e) = result {
+            log_failure(e);
+            return Err("replay stopped");
+        }
+        transaction::record(line);
+        record::record(line);
+    }
+    Ok(())
+}
+
+/// Route a line to the shortcut or the command dispatcher.
+#[inline]
+fn dispatch_line<'a, const EBS: usize>(
+    input_str: &'a str,
+    is_shortcut: fn(&str) -> bool,
+    command_dispatcher: Dispatcher<EBS>,
+    shortcut_dispatcher: Dispatcher<EBS>,
+    error_buffer: &'a mut String<EBS>,
+) -> Result<(), &'a str> {
+    if is_shortcut(input_str) {
+        shortcut_dispatcher(input_str, error_buffer)
+    } else {
+        command_dispatcher(input_str, error_buffer)
+    }
+}
+
+/// Protocol (machine) mode: dispatch one line and write a single framed response.
+///
+/// Log lines emitted while the command runs are captured (see `begin_log_capture`)
+/// and attached as a diagnostics section instead of being interleaved on the wire:
+///
+/// ```text
+/// OK [<value>]            or  ERR <message>
+/// # [ WARN] low voltage       one line per captured log line
+/// # ...                       present if lines were dropped (capture buffer full)
+/// END
+/// ```
+///
+/// Commands that ask for interactive input (`confirm`, `read_hidden_line`) are answered
+/// with `ERR input required`.
+pub fn exec_framed<const EBS: usize>(
+    input_str: &str,
+    is_shortcut: fn(&str) -> bool,
+    command_dispatcher: Dispatcher<EBS>,
+    shortcut_dispatcher: Dispatcher<EBS>,
+    write_fn: fn(&[u8]),
+) {
+    let mut error_buffer: String<EBS> = String::new();
+
+    logger::begin_log_capture();
+    let result = dispatch_line(
+        input_str,
+        is_shortcut,
+        command_dispatcher,
+        shortcut_dispatcher,
+        &mut error_buffer,
+    );
+    let needs_input = interact::take_request().is_some();
+
+    match result {
+        _ if needs_input => write_fn(b"ERR input required\r\n"),
+        Ok(()) => {
+            write_fn(b"OK");
+            if !error_buffer.is_empty() {
+                write_fn(b" ");
+                write_fn(error_buffer.as_bytes());
+            }
+            write_fn(b"\r\n");
+        }
+        Err(e) => {
+            write_fn(b"ERR ");
+            write_fn(e.as_bytes());
+            write_fn(b"\r\n");
+        }
+    }
+    logger::end_log_capture(|diagnostics, truncated| {
+        for line in diagnostics.lines() {
+            write_fn(b"# ");
+            write_fn(line.as_bytes());
+            write_fn(b"\r\n");
+        }
+        if truncated {
+            write_fn(b"# ...\r\n");
+        }
+    });
+    write_fn(b"END\r\n");
 }
 
 // ============================================================================
@@ -414,3 +1273,258 @@ pub use sync_impl::PollingReader as SyncReader;
 
 #[cfg(feature = "async")]
 pub use async_impl::AsyncReader;
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::{assert_eq, assert_ne};
+
+    const COMMANDS: &[(&str, &str)] = &[("gpio read", "B"), ("gpio set", "Bt"), ("led", "t")];
+
+    fn dispatch<'a>(_: &'a str, _: &'a mut String<16>) -> Result<(), &'a str> {
+        Ok(())
+    }
+
+    fn config<const IML: usize>() -> ShellConfig<IML, 16> {
+        ShellConfig {
+            get_commands: || COMMANDS,
+            get_datatypes: || "",
+            get_shortcuts: || "",
+            is_shortcut: |_| false,
+            is_nohistory: |_| false,
+            history_duplicates: DuplicatePolicy::RejectDuplicates,
+            history_clock: None,
+            now_ms: None,
+            complete_arg: None,
+            completions: None,
+            live_validation: false,
+            ghost_completion: false,
+            history_completion: false,
+            snippets: &[],
+            snippet_key: None,
+            command_dispatcher: dispatch,
+            shortcut_dispatcher: dispatch,
+            privilege_level: None,
+            async_commands: &[],
+            rollback_dispatcher: None,
+            mirror_write: None,
+            submit: None,
+            is_busy: None,
+            time_budget: None,
+            on_command_timeout: None,
+            describe_command: None,
+            on_mouse: None,
+            storage: None,
+            mem_access: None,
+            history_store: None,
+            terminal_rows: None,
+            terminal_cols: None,
+            probe_terminal_size: false,
+            bracketed_paste: false,
+            key_bindings: None,
+            theme: None,
+            prompt: ">> ",
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        #[cfg(not(feature = "xmodem"))]
+        assert_eq!(config::<32>().validate::<2, 9>(), Ok(()));
+        #[cfg(feature = "xmodem")]
+        {
+            let mut timed = config::<32>();
+            assert_eq!(
+                timed.validate::<2, 9>(),
+                Err(ConfigError::XmodemWithoutClock)
+            );
+            timed.now_ms = Some(|| 0);
+            assert_eq!(timed.validate::<2, 9>(), Ok(()));
+        }
+        assert_eq!(
+            config::<32>().validate::<1, 9>(),
+            Err(ConfigError::TooManyCommands('g'))
+        );
+        assert_eq!(
+            config::<32>().validate::<2, 8>(),
+            Err(ConfigError::NameTooLong("gpio read"))
+        );
+        assert_eq!(
+            config::<9>().validate::<2, 9>(),
+            Err(ConfigError::NameExceedsInput("gpio read"))
+        );
+
+        let mut bad = config::<32>();
+        bad.prompt = "";
+        assert_eq!(bad.validate::<2, 9>(), Err(ConfigError::EmptyPrompt));
+        bad = config::<32>();
+        bad.snippet_key = Some(Key::Tab);
+        assert_eq!(bad.validate::<2, 9>(), Err(ConfigError::NoSnippets));
+        bad = config::<32>();
+        bad.time_budget = Some(|_| Some(10));
+        assert_eq!(bad.validate::<2, 9>(), Err(ConfigError::BudgetWithoutClock));
+        bad = config::<32>();
+        bad.get_commands = || &[("history", "v")];
+        assert_eq!(
+            bad.validate::<2, 9>(),
+            Err(ConfigError::BuiltinName("history"))
+        );
+        bad.get_commands = || &[("log tail", "v")];
+        assert_eq!(
+            bad.validate::<2, 9>(),
+            Err(ConfigError::BuiltinName("log tail"))
+        );
+        // `set <key> <value>` is left to the application
+        bad.get_commands = || &[("set", "ss")];
+        assert_ne!(bad.validate::<2, 9>(), Err(ConfigError::BuiltinName("set")));
+    }
+
+    extern crate std;
+    static DISPATCHED: std::sync::Mutex<std::vec::Vec<std::string::String>> =
+        std::sync::Mutex::new(std::vec::Vec::new());
+
+    fn recording_dispatch<'a>(line: &'a str, _: &'a mut String<16>) -> Result<(), &'a str> {
+        DISPATCHED
+            .lock()
+            .unwrap()
+            .push(std::string::ToString::to_string(line));
+        Ok(())
+    }
+
+    #[test]
+    fn test_variables() {
+        let _guard = vars::tests::TEST_LOCK.lock().unwrap();
+        vars::tests::reset();
+        let mut config = config::<32>();
+        config.command_dispatcher = recording_dispatch;
+
+        exec_timed("set ADDR 0x50", &config);
+        exec_timed("set MSG \"a b\"", &config);
+        exec_timed("led $ADDR $MSG", &config);
+        // Lower-case keys go to the application's `set`
+        exec_timed("set baud 9600", &config);
+        exec_timed("led $NOPE", &config);
+        exec_timed("unset ADDR", &config);
+        exec_timed("led $ADDR", &config);
+
+        assert_eq!(
+            *DISPATCHED.lock().unwrap(),
+            ["led 0x50 a b", "set baud 9600"]
+        );
+        assert_eq!(vars::get("MSG").unwrap(), "a b");
+    }
+
+    fn countdown(args: &str) -> Result<jobs::Job, jobs::JobError> {
+        let polls = args
+            .parse()
+            .map_err(|_| jobs::JobError::BadArgs("usage: countdown <polls>"))?;
+        jobs::Job::new(jobs::tests::Countdown {
+            polls,
+            result: Ok(()),
+        })
+    }
+
+    #[test]
+    fn test_jobs() {
+        let _guard = jobs::tests::TEST_LOCK.lock().unwrap();
+        let _notices = crate::notice::TEST_LOCK.lock().unwrap();
+        jobs::tests::reset();
+        let mut config = config::<32>();
+        config.async_commands = &[("countdown", countdown)];
+
+        exec_timed("countdown 1", &config);
+        exec_timed("countdown 5", &config);
+        exec_timed("countdown x", &config);
+        assert_eq!(jobs::count(), 2);
+
+        jobs::tests::poll_once();
+        assert_eq!(crate::notice::take().unwrap(), "[1] countdown 1: done");
+        exec_timed("kill 2", &config);
+        assert_eq!(jobs::count(), 0);
+    }
+
+    static TIMEOUTS: std::sync::Mutex<std::vec::Vec<(std::string::String, u64)>> =
+        std::sync::Mutex::new(std::vec::Vec::new());
+
+    fn record_timeout(name: &str, elapsed: u64) {
+        TIMEOUTS
+            .lock()
+            .unwrap()
+            .push((std::string::ToString::to_string(name), elapsed));
+    }
+
+    #[test]
+    fn test_job_budget() {
+        let _guard = jobs::tests::TEST_LOCK.lock().unwrap();
+        let _notices = crate::notice::TEST_LOCK.lock().unwrap();
+        let _clock = crate::clock::TEST_LOCK.lock().unwrap();
+        jobs::tests::reset();
+        TIMEOUTS.lock().unwrap().clear();
+        let mut config = config::<32>();
+        config.async_commands = &[("countdown", countdown)];
+        config.now_ms = Some(crate::clock::now_ms);
+        config.time_budget = Some(|name| (name == "countdown").then_some(50));
+        config.on_command_timeout = Some(record_timeout);
+
+        let start = crate::clock::now_ms();
+        exec_timed("countdown 1000", &config);
+        let waker = core::task::Waker::noop();
+        crate::clock::tick(50);
+        poll_jobs(&mut Context::from_waker(waker), &config);
+        assert_eq!(jobs::count(), 1);
+
+        // Past the budget: stopped, not polled to completion
+        crate::clock::tick(1);
+        poll_jobs(&mut Context::from_waker(waker), &config);
+        assert_eq!(jobs::count(), 0);
+        let elapsed = crate::clock::now_ms() - start;
+        assert_eq!(
+            crate::notice::take().unwrap().as_str(),
+            std::format!(
+                "[1] countdown 1000: Error: timed out after {} ms (budget 50 ms)",
+                elapsed
+            )
+        );
+        assert_eq!(
+            *TIMEOUTS.lock().unwrap(),
+            [(std::string::ToString::to_string("countdown"), elapsed)]
+        );
+    }
+
+    #[cfg(not(feature = "hosted"))]
+    fn logging_dispatch<'a>(_: &'a str, _: &'a mut String<16>) -> Result<(), &'a str> {
+        log_info!("idle ready");
+        log_info!("led run");
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(feature = "hosted"))]
+    fn test_pipe() {
+        let _guard = logger::TEST_LOCK.lock().unwrap();
+        logger::tests::init();
+        let mut config = config::<32>();
+        config.command_dispatcher = logging_dispatch;
+
+        exec_timed("tasks | grep led", &config);
+        let written = logger::tests::written();
+        assert!(written.contains("[ INFO] led run"));
+        assert!(!written.contains("idle"));
+        assert!(written.contains("Success"));
+
+        logger::tests::init();
+        exec_timed("tasks | grep run | count", &config);
+        assert!(logger::tests::written().contains("1 lines, 16 bytes"));
+
+        // Checked before the command runs
+        logger::tests::init();
+        exec_timed("tasks | sort", &config);
+        let written = logger::tests::written();
+        assert!(written.contains("unknown filter"));
+        assert!(!written.contains("led run"));
+    }
+}
@@ -0,0 +1,754 @@
+//! Append-only record log on NOR flash, the backend of `FlashHistory` and `FlashSettings`.
+//!
+//! The region is split into two banks of whole erase units. A bank starts with its
+//! generation number and records are appended after it; the bank with the highest
+//! generation is the active one:
+//!
+//! ```text
+//! bank:   [generation u32][record][record]...[erased]
+//! record: [len u16][kind u8][checksum u8][payload][padding to WRITE_SIZE]
+//! ```
+//!
+//! Nothing is erased while appending. When the active bank is full, the records worth
+//! keeping are copied into the other bank and its header is written last, so a reset
+//! during compaction leaves the old bank in charge. Each erase unit is thus erased once
+//! per bank fill instead of once per write. A record torn by a reset fails its checksum:
+//! the log ends before it and the next append compacts it away.
+//!
+//! Reads go through `ReadNorFlash::read` byte-wise, so the flash must have a `READ_SIZE`
+//! of 1 (memory-mapped NOR such as the STM32 internal flash).
+
+use embedded_storage::nor_flash::NorFlash;
+
+use super::{HistoryStorage, SettingsStorage, StorageError, StorageInfo, StorageOp};
+
+/// Longest record payload: a history line, or a setting's key, a NUL and its value.
+pub const MAX_RECORD_LEN: usize = 128;
+
+const BANK_HEADER_LEN: usize = 4;
+const RECORD_HEADER_LEN: usize = 4;
+/// Largest flash write unit supported.
+const MAX_WRITE_SIZE: usize = 32;
+const RECORD_BUF_LEN: usize = RECORD_HEADER_LEN + MAX_RECORD_LEN + MAX_WRITE_SIZE;
+
+const ERASED_LEN: u16 = 0xFFFF;
+
+const KIND_LINE: u8 = 1;
+const KIND_SET: u8 = 2;
+const KIND_REMOVE: u8 = 3;
+
+/// Which records survive a compaction.
+#[derive(Clone, Copy)]
+enum Retain {
+    /// The newest records, up to half a bank (history).
+    Recent,
+    /// The last value of each key that is still set (settings).
+    LatestPerKey,
+}
+
+/// What was found at a position of a bank.
+enum Slot {
+    Erased,
+    Torn,
+    Record { kind: u8, len: usize, next: u32 },
+}
+
+/// Record log over two banks of a NOR flash region.
+pub struct FlashLog<F: NorFlash> {
+    flash: F,
+    base: u32,
+    bank_size: u32,
+    retain: Retain,
+    active: u32,
+    generation: u32,
+    write_pos: u32,
+    records: usize,
+    mounted: bool,
+    torn: bool,
+}
+
+impl<F: NorFlash> FlashLog<F> {
+    fn new(flash: F, offset: u32, size: u32, retain: Retain) -> Result<Self, StorageError> {
+        let erase_size = F::ERASE_SIZE as u32;
+        let bank_size = size / 2;
+        if F::WRITE_SIZE > MAX_WRITE_SIZE
+            || !offset.is_multiple_of(erase_size)
+            || !bank_size.is_multiple_of(erase_size)
+            || (bank_size as usize) < align::<F>(BANK_HEADER_LEN) + 2 * RECORD_BUF_LEN
+            || offset as usize + size as usize > flash.capacity()
+        {
+            return Err(StorageError::InvalidLayout);
+        }
+        Ok(Self {
+            flash,
+            base: offset,
+            bank_size,
+            retain,
+            active: 0,
+            generation: 0,
+            write_pos: 0,
+            records: 0,
+            mounted: false,
+            torn: false,
+        })
+    }
+
+    /// Finds the active bank and the end of its records, returns how many there are.
+    pub fn mount(&mut self) -> Result<usize, StorageError> {
+        self.mounted = false;
+        let (active, generation) = match (self.read_generation(0)?, self.read_generation(1)?) {
+            (None, None) => return Err(StorageError::NotFormatted),
+            (Some(first), Some(second)) if second > first => (1, second),
+            (Some(first), _) => (0, first),
+            (None, Some(second)) => (1, second),
+        };
+        self.active = active;
+        self.generation = generation;
+        self.write_pos = align::<F>(BANK_HEADER_LEN) as u32;
+        self.records = 0;
+        self.torn = false;
+
+        let mut payload = [0u8; MAX_RECORD_LEN];
+        loop {
+            match self.read_slot(active, self.write_pos, &mut payload)? {
+                Slot::Record { next, .. } => {
+                    self.write_pos = next;
+                    self.records += 1;
+                }
+                Slot::Torn => {
+                    self.torn = true;
+                    break;
+                }
+                Slot::Erased => break,
+            }
+        }
+        self.mounted = true;
+        Ok(self.records)
+    }
+
+    /// Erases both banks and leaves the log mounted and empty.
+    pub fn format(&mut self) -> Result<(), StorageError> {
+        self.erase_bank(0)?;
+        self.erase_bank(1)?;
+        self.write_generation(0, 1)?;
+        self.active = 0;
+        self.generation = 1;
+        self.write_pos = align::<F>(BANK_HEADER_LEN) as u32;
+        self.records = 0;
+        self.torn = false;
+        self.mounted = true;
+        Ok(())
+    }
+
+    /// State of the active bank.
+    pub fn info(&self) -> Result<StorageInfo, StorageError> {
+        if !self.mounted {
+            return Err(StorageError::NotMounted);
+        }
+        Ok(StorageInfo {
+            records: self.records,
+            used: self.write_pos as usize,
+            capacity: self.bank_size as usize,
+            generation: self.generation,
+        })
+    }
+
+    /// Runs a `storage` built-in operation, see `StorageControl`.
+    pub fn control(&mut self, op: StorageOp) -> Result<StorageInfo, StorageError> {
+        match op {
+            StorageOp::Info => {}
+            StorageOp::Mount => {
+                self.mount()?;
+            }
+            StorageOp::Format => self.format()?,
+        }
+        self.info()
+    }
+
+    /// Returns the flash driver.
+    pub fn into_flash(self) -> F {
+        self.flash
+    }
+
+    fn append(&mut self, kind: u8, payload: &[u8]) -> Result<(), StorageError> {
+        if !self.mounted {
+            return Err(StorageError::NotMounted);
+        }
+        if payload.len() > MAX_RECORD_LEN {
+            return Err(StorageError::TooLarge);
+        }
+        let size = align::<F>(RECORD_HEADER_LEN + payload.len()) as u32;
+        if self.torn || self.write_pos + size > self.bank_size {
+            self.compact(size)?;
+            if self.write_pos + size > self.bank_size {
+                return Err(StorageError::Full);
+            }
+        }
+        self.write_pos = self.write_record(self.active, self.write_pos, kind, payload)?;
+        self.records += 1;
+        Ok(())
+    }
+
+    /// Calls `each` with the kind and payload of the records, oldest first.
+    fn for_each(&mut self, mut each: impl FnMut(u8, &[u8])) -> Result<(), StorageError> {
+        if !self.mounted {
+            return Err(StorageError::NotMounted);
+        }
+        let mut payload = [0u8; MAX_RECORD_LEN];
+        let mut pos = align::<F>(BANK_HEADER_LEN) as u32;
+        while pos < self.write_pos {
+            let Slot::Record { kind, len, next } =
+                self.read_slot(self.active, pos, &mut payload)?
+            else {
+                break;
+            };
+            each(kind, &payload[..len]);
+            pos = next;
+        }
+        Ok(())
+    }
+
+    /// Copies the records worth keeping into the other bank and makes it the active one.
+    /// `incoming` is the size of the record about to be appended.
+    fn compact(&mut self, incoming: u32) -> Result<(), StorageError> {
+        let (from, to) = (self.active, 1 - self.active);
+        let header_len = align::<F>(BANK_HEADER_LEN) as u32;
+        // History keeps everything while it fits (a torn record is being dropped),
+        // otherwise the newest half bank.
+        let cutoff = if self.write_pos + incoming <= self.bank_size {
+            0
+        } else {
+            self.write_pos
+                .saturating_sub((self.bank_size - header_len) / 2)
+        };
+
+        self.erase_bank(to)?;
+        let mut payload = [0u8; MAX_RECORD_LEN];
+        let (mut pos, mut write_pos, mut records) = (header_len, header_len, 0);
+        while pos < self.write_pos {
+            let Slot::Record { kind, len, next } = self.read_slot(from, pos, &mut payload)? else {
+                break;
+            };
+            let keep = match self.retain {
+                Retain::Recent => pos >= cutoff,
+                Retain::LatestPerKey => {
+                    kind == KIND_SET && !self.superseded(from, next, key_of(&payload[..len]))?
+                }
+            };
+            if keep {
+                write_pos = self.write_record(to, write_pos, kind, &payload[..len])?;
+                records += 1;
+            }
+            pos = next;
+        }
+        self.write_generation(to, self.generation + 1)?;
+
+        self.active = to;
+        self.generation += 1;
+        self.write_pos = write_pos;
+        self.records = records;
+        self.torn = false;
+        Ok(())
+    }
+
+    /// Whether a setting or removal of `key` follows `pos` in `bank`.
+    fn superseded(&mut self, bank: u32, mut pos: u32, key: &[u8]) -> Result<bool, StorageError> {
+        let mut payload = [0u8; MAX_RECORD_LEN];
+        while pos < self.write_pos {
+            let Slot::Record { kind, len, next } = self.read_slot(bank, pos, &mut payload)? else {
+                break;
+            };
+            if matches!(kind, KIND_SET | KIND_REMOVE) && key_of(&payload[..len]) == key {
+                return Ok(true);
+            }
+            pos = next;
+        }
+        Ok(false)
+    }
+
+    fn bank_addr(&self, bank: u32) -> u32 {
+        self.base + bank * self.bank_size
+    }
+
+    fn erase_bank(&mut self, bank: u32) -> Result<(), StorageError> {
+        let from = self.bank_addr(bank);
+        self.flash
+            .erase(from, from + self.bank_size)
+            .map_err(|_| StorageError::Flash)
+    }
+
+    fn read_generation(&mut self, bank: u32) -> Result<Option<u32>, StorageError> {
+        let mut bytes = [0u8; BANK_HEADER_LEN];
+        self.flash
+            .read(self.bank_addr(bank), &mut bytes)
+            .map_err(|_| StorageError::Flash)?;
+        let generation = u32::from_le_bytes(bytes);
+        Ok((generation != u32::MAX).then_some(generation))
+    }
+
+    fn write_generation(&mut self, bank: u32, generation: u32) -> Result<(), StorageError> {
+        let mut bytes = [0u8; MAX_WRITE_SIZE];
+        bytes[..BANK_HEADER_LEN].copy_from_slice(&generation.to_le_bytes());
+        self.flash
+            .write(self.bank_addr(bank), &bytes[..align::<F>(BANK_HEADER_LEN)])
+            .map_err(|_| StorageError::Flash)
+    }
+
+    fn read_slot(
+        &mut self,
+        bank: u32,
+        pos: u32,
+        payload: &mut [u8; MAX_RECORD_LEN],
+    ) -> Result<Slot, StorageError> {
+        if pos as usize + RECORD_HEADER_LEN > self.bank_size as usize {
+            return Ok(Slot::Erased);
+        }
+        let addr = self.bank_addr(bank) + pos;
+        let mut header = [0u8; RECORD_HEADER_LEN];
+        self.flash
+            .read(addr, &mut header)
+            .map_err(|_| StorageError::Flash)?;
+        let len = u16::from_le_bytes([header[0], header[1]]);
+        if len == ERASED_LEN {
+            return Ok(Slot::Erased);
+        }
+        let len = len as usize;
+        let next = pos + align::<F>(RECORD_HEADER_LEN + len) as u32;
+        if len > MAX_RECORD_LEN || next > self.bank_size {
+            return Ok(Slot::Torn);
+        }
+        self.flash
+            .read(addr + RECORD_HEADER_LEN as u32, &mut payload[..len])
+            .map_err(|_| StorageError::Flash)?;
+        if header[3] != checksum(header[2], &payload[..len]) {
+            return Ok(Slot::Torn);
+        }
+        Ok(Slot::Record {
+            kind: header[2],
+            len,
+            next,
+        })
+    }
+
+    /// Writes a record at `pos` of `bank` and returns the position after it.
+    fn write_record(
+        &mut self,
+        bank: u32,
+        pos: u32,
+        kind: u8,
+        payload: &[u8],
+    ) -> Result<u32, StorageError> {
+        let mut bytes = [0u8; RECORD_BUF_LEN];
+        bytes[..2].copy_from_slice(&(payload.len() as u16).to_le_bytes());
+        bytes[2] = kind;
+        bytes[3] = checksum(kind, payload);
+        bytes[RECORD_HEADER_LEN..RECORD_HEADER_LEN + payload.len()].copy_from_slice(payload);
+        let size = align::<F>(RECORD_HEADER_LEN + payload.len());
+        self.flash
+            .write(self.bank_addr(bank) + pos, &bytes[..size])
+            .map_err(|_| StorageError::Flash)?;
+        Ok(pos + size as u32)
+    }
+}
+
+/// Rounds `len` up to the flash write unit.
+fn align<F: NorFlash>(len: usize) -> usize {
+    let unit = F::WRITE_SIZE.max(1);
+    len.div_ceil(unit) * unit
+}
+
+fn checksum(kind: u8, payload: &[u8]) -> u8 {
+    payload
+        .iter()
+        .fold(kind, |sum, &byte| sum.wrapping_add(byte))
+        ^ 0xA5
+}
+
+/// The key of a setting record: the payload up to the NUL.
+fn key_of(payload: &[u8]) -> &[u8] {
+    payload.split(|&byte| byte == 0).next().unwrap_or(payload)
+}
+
+/// `HistoryStorage` on a flash region. Once the lines fill half of it, the oldest ones
+/// are dropped at the next compaction.
+pub struct FlashHistory<F: NorFlash> {
+    log: FlashLog<F>,
+}
+
+impl<F: NorFlash> FlashHistory<F> {
+    /// Uses `size` bytes of `flash` from `offset`, both multiples of twice `ERASE_SIZE`.
+    /// Call `log().mount()` (or `format()` on first use) before anything else.
+    pub fn new(flash: F, offset: u32, size: u32) -> Result<Self, StorageError> {
+        Ok(Self {
+            log: FlashLog::new(flash, offset, size, Retain::Recent)?,
+        })
+    }
+
+    /// The underlying log, for mounting, formatting and `StorageControl`.
+    pub fn log(&mut self) -> &mut FlashLog<F> {
+        &mut self.log
+    }
+
+    /// Returns the flash driver.
+    pub fn into_flash(self) -> F {
+        self.log.into_flash()
+    }
+}
+
+impl<F: NorFlash> HistoryStorage for FlashHistory<F> {
+    fn load(&mut self, each: &mut dyn FnMut(&str)) -> Result<usize, StorageError> {
+        let mut count = 0;
+        self.log.for_each(|kind, payload| {
+            if kind != KIND_LINE {
+                return;
+            }
+            if let Ok(line) = core::str::from_utf8(payload) {
+                each(line);
+                count += 1;
+            }
+        })?;
+        Ok(count)
+    }
+
+    fn append(&mut self, line: &str) -> Result<(), StorageError> {
+        if line.is_empty() {
+            return Ok(());
+        }
+        self.log.append(KIND_LINE, line.as_bytes())
+    }
+
+    fn clear(&mut self) -> Result<(), StorageError> {
+        self.log.format()
+    }
+}
+
+/// `SettingsStorage` on a flash region. Setting a key to its current value writes
+/// nothing, and compaction keeps only the last value of each key.
+pub struct FlashSettings<F: NorFlash> {
+    log: FlashLog<F>,
+}
+
+impl<F: NorFlash> FlashSettings<F> {
+    /// Uses `size` bytes of `flash` from `offset`, both multiples of twice `ERASE_SIZE`.
+    /// Call `log().mount()` (or `format()` on first use) before anything else.
+    pub fn new(flash: F, offset: u32, size: u32) -> Result<Self, StorageError> {
+        Ok(Self {
+            log: FlashLog::new(flash, offset, size, Retain::LatestPerKey)?,
+        })
+    }
+
+    /// The underlying log, for mounting, formatting and `StorageControl`.
+    pub fn log(&mut self) -> &mut FlashLog<F> {
+        &mut self.log
+    }
+
+    /// Returns the flash driver.
+    pub fn into_flash(self) -> F {
+        self.log.into_flash()
+    }
+}
+
+impl<F: NorFlash> SettingsStorage for FlashSettings<F> {
+    fn get(&mut self, key: &str, value: &mut [u8]) -> Result<Option<usize>, StorageError> {
+        let key = key.as_bytes();
+        let mut found = None;
+        self.log.for_each(|kind, payload| {
+            if key_of(payload) != key {
+                return;
+            }
+            match kind {
+                KIND_SET => {
+                    let stored = &payload[key.len() + 1..];
+                    let len = stored.len().min(value.len());
+                    value[..len].copy_from_slice(&stored[..len]);
+                    found = Some(stored.len());
+                }
+                KIND_REMOVE => found = None,
+                _ => {}
+            }
+        })?;
+        match found {
+            Some(len) if len > value.len() => Err(StorageError::TooLarge),
+            found => Ok(found),
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &[u8]) -> Result<(), StorageError> {
+        let key = key.as_bytes();
+        let len = key.len() + 1 + value.len();
+        if key.is_empty() || key.contains(&0) || len > MAX_RECORD_LEN {
+            return Err(StorageError::TooLarge);
+        }
+        let mut payload = [0u8; MAX_RECORD_LEN];
+        if let Some(current) = self.get_bytes(key, &mut payload)? {
+            if &payload[..current] == value {
+                return Ok(());
+            }
+        }
+        payload[..key.len()].copy_from_slice(key);
+        payload[key.len()] = 0;
+        payload[key.len() + 1..len].copy_from_slice(value);
+        self.log.append(KIND_SET, &payload[..len])
+    }
+
+    fn remove(&mut self, key: &str) -> Result<(), StorageError> {
+        let mut value = [0u8; MAX_RECORD_LEN];
+        if self.get_bytes(key.as_bytes(), &mut value)?.is_none() {
+            return Ok(());
+        }
+        self.log.append(KIND_REMOVE, key.as_bytes())
+    }
+}
+
+impl<F: NorFlash> FlashSettings<F> {
+    fn get_bytes(
+        &mut self,
+        key: &[u8],
+        value: &mut [u8; MAX_RECORD_LEN],
+    ) -> Result<Option<usize>, StorageError> {
+        match core::str::from_utf8(key) {
+            Ok(key) => self.get(key, value),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use embedded_storage::nor_flash::{ErrorType, NorFlashErrorKind, ReadNorFlash};
+    use std::string::{String, ToString};
+    use std::vec::Vec;
+
+    const FLASH_SIZE: usize = 2048;
+
+    /// RAM-backed flash that, like the real thing, refuses to program a byte twice.
+    struct MockFlash {
+        data: [u8; FLASH_SIZE],
+        erased_units: usize,
+        writes: usize,
+    }
+
+    impl MockFlash {
+        fn new() -> Self {
+            Self {
+                data: [0xFF; FLASH_SIZE],
+                erased_units: 0,
+                writes: 0,
+            }
+        }
+    }
+
+    impl ErrorType for MockFlash {
+        type Error = NorFlashErrorKind;
+    }
+
+    impl ReadNorFlash for MockFlash {
+        const READ_SIZE: usize = 1;
+
+        fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+            let offset = offset as usize;
+            bytes.copy_from_slice(&self.data[offset..offset + bytes.len()]);
+            Ok(())
+        }
+
+        fn capacity(&self) -> usize {
+            FLASH_SIZE
+        }
+    }
+
+    impl NorFlash for MockFlash {
+        const WRITE_SIZE: usize = 4;
+        const ERASE_SIZE: usize = 256;
+
+        fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+            assert_eq!(from as usize % Self::ERASE_SIZE, 0);
+            assert_eq!(to as usize % Self::ERASE_SIZE, 0);
+            self.data[from as usize..to as usize].fill(0xFF);
+            self.erased_units += (to - from) as usize / Self::ERASE_SIZE;
+            Ok(())
+        }
+
+        fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+            let offset = offset as usize;
+            assert_eq!(offset % Self::WRITE_SIZE, 0);
+            assert_eq!(bytes.len() % Self::WRITE_SIZE, 0);
+            let target = &mut self.data[offset..offset + bytes.len()];
+            assert!(target.iter().all(|&byte| byte == 0xFF), "programmed twice");
+            target.copy_from_slice(bytes);
+            self.writes += 1;
+            Ok(())
+        }
+    }
+
+    fn history(flash: MockFlash) -> FlashHistory<MockFlash> {
+        FlashHistory::new(flash, 0, FLASH_SIZE as u32).unwrap()
+    }
+
+    fn lines(history: &mut FlashHistory<MockFlash>) -> Vec<String> {
+        let mut lines = Vec::new();
+        history
+            .load(&mut |line| lines.push(line.to_string()))
+            .unwrap();
+        lines
+    }
+
+    #[test]
+    fn test_history_survives_remount() {
+        let mut store = history(MockFlash::new());
+        assert_eq!(store.log().mount(), Err(StorageError::NotFormatted));
+        assert_eq!(store.append("led on"), Err(StorageError::NotMounted));
+
+        store.log().format().unwrap();
+        store.append("led on").unwrap();
+        store.append("baud 9600").unwrap();
+
+        let mut store = history(store.into_flash());
+        assert_eq!(store.log().mount(), Ok(2));
+        assert_eq!(lines(&mut store), ["led on", "baud 9600"]);
+
+        store.clear().unwrap();
+        assert!(lines(&mut store).is_empty());
+        assert_eq!(
+            FlashHistory::new(MockFlash::new(), 128, 1024).err(),
+            Some(StorageError::InvalidLayout)
+        );
+    }
+
+    #[test]
+    fn test_history_compaction_keeps_recent_lines() {
+        let mut store = history(MockFlash::new());
+        store.log().format().unwrap();
+        for i in 0..200 {
+            store.append(&std::format!("cmd {:03}", i)).unwrap();
+        }
+
+        let info = store.log().info().unwrap();
+        assert!(info.generation > 1);
+        let kept = lines(&mut store);
+        assert_eq!(kept.len(), info.records);
+        assert_eq!(kept.last().unwrap(), "cmd 199");
+        let first: usize = kept[0][4..].parse().unwrap();
+        for (i, line) in kept.iter().enumerate() {
+            assert_eq!(*line, std::format!("cmd {:03}", first + i));
+        }
+
+        // Format erases both banks, then one bank per compaction.
+        let generation = info.generation as usize;
+        let mut store = history(store.into_flash());
+        store.log().mount().unwrap();
+        assert_eq!(lines(&mut store), kept);
+        assert_eq!(store.into_flash().erased_units, 8 + 4 * (generation - 1));
+    }
+
+    #[cfg(not(feature = "no-history"))]
+    #[test]
+    fn test_history_syncs_through_store() {
+        use crate::history::History;
+        use crate::storage::HistoryOp;
+
+        static STORE: std::sync::Mutex<Option<FlashHistory<MockFlash>>> =
+            std::sync::Mutex::new(None);
+
+        fn store(op: HistoryOp) -> Result<usize, StorageError> {
+            op.run(
+                STORE
+                    .lock()
+                    .unwrap()
+                    .as_mut()
+                    .ok_or(StorageError::NotMounted)?,
+            )
+        }
+        fn stored() -> Vec<String> {
+            lines(STORE.lock().unwrap().as_mut().unwrap())
+        }
+
+        let mut flash = history(MockFlash::new());
+        flash.log().format().unwrap();
+        flash.append("led on").unwrap();
+        flash.append("baud 9600").unwrap();
+        *STORE.lock().unwrap() = Some(flash);
+
+        // Loading does not write the lines back
+        let mut history = History::<128>::new();
+        assert_eq!(history.set_store(store), Ok(2));
+        assert_eq!(history.len(), 2);
+        assert_eq!(stored().len(), 2);
+
+        // Only entries the history keeps are appended
+        assert!(history.push("gpio read 3"));
+        assert!(!history.push("led on"));
+        assert_eq!(stored(), ["led on", "baud 9600", "gpio read 3"]);
+
+        history.clear();
+        assert!(stored().is_empty());
+    }
+
+    #[test]
+    fn test_settings_keep_latest_values() {
+        let mut store = FlashSettings::new(MockFlash::new(), 0, FLASH_SIZE as u32).unwrap();
+        store.log().format().unwrap();
+        let mut value = [0u8; 16];
+
+        store.set("prompt", b"a> ").unwrap();
+        store.set("baud", b"9600").unwrap();
+        store.set("prompt", b"dev> ").unwrap();
+        assert_eq!(store.get("prompt", &mut value), Ok(Some(5)));
+        assert_eq!(&value[..5], b"dev> ");
+        assert_eq!(
+            store.get("prompt", &mut [0u8; 2]),
+            Err(StorageError::TooLarge)
+        );
+        assert_eq!(store.get("missing", &mut value), Ok(None));
+        assert_eq!(store.set("", b"x"), Err(StorageError::TooLarge));
+
+        store.remove("baud").unwrap();
+        assert_eq!(store.get("baud", &mut value), Ok(None));
+
+        // Rewriting the current value costs no flash write.
+        let records = store.log().info().unwrap().records;
+        store.set("prompt", b"dev> ").unwrap();
+        assert_eq!(store.log().info().unwrap().records, records);
+
+        for i in 0..300 {
+            store
+                .set("level", std::format!("{}", i).as_bytes())
+                .unwrap();
+        }
+        let info = store.log().info().unwrap();
+        assert!(info.generation > 1);
+
+        let mut store = FlashSettings::new(store.into_flash(), 0, FLASH_SIZE as u32).unwrap();
+        store.log().mount().unwrap();
+        assert_eq!(store.get("prompt", &mut value), Ok(Some(5)));
+        assert_eq!(&value[..5], b"dev> ");
+        assert_eq!(store.get("level", &mut value), Ok(Some(3)));
+        assert_eq!(&value[..3], b"299");
+        assert_eq!(store.get("baud", &mut value), Ok(None));
+    }
+
+    #[test]
+    fn test_torn_record_is_dropped() {
+        let mut store = history(MockFlash::new());
+        store.log().format().unwrap();
+        store.append("one").unwrap();
+        store.append("two").unwrap();
+        let end = store.log().info().unwrap().used;
+
+        // A record whose payload never made it to flash.
+        let mut flash = store.into_flash();
+        flash.data[end..end + 4].copy_from_slice(&[5, 0, KIND_LINE, 0x42]);
+
+        let mut store = history(flash);
+        assert_eq!(store.log().mount(), Ok(2));
+        store.append("three").unwrap();
+        assert_eq!(store.log().info().unwrap().generation, 2);
+        assert_eq!(lines(&mut store), ["one", "two", "three"]);
+    }
+}
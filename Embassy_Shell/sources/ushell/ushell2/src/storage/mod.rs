@@ -0,0 +1,148 @@
+//! Persistent storage for the shell.
+//!
+//! `HistoryStorage` and `SettingsStorage` describe what the shell keeps across resets:
+//! command lines and small key/value settings. With the `flash-storage` feature,
+//! `FlashHistory` and `FlashSettings` implement them on any `embedded-storage` NOR flash
+//! (e.g. the `embassy_stm32::flash::Flash` driver over a spare internal flash sector):
+//!
+//! ```ignore
+//! // Last two 128 KiB sectors of an STM32F411RE, offsets relative to the flash base
+//! static HISTORY: Mutex<CriticalSectionRawMutex, RefCell<Option<FlashHistory<Flash<'static, Blocking>>>>> = ...;
+//!
+//! let mut history = FlashHistory::new(Flash::new_blocking(p.FLASH), 0x4_0000, 0x4_0000)?;
+//! if history.log().mount().is_err() {
+//!     history.log().format()?;
+//! }
+//!
+//! fn storage(op: StorageOp) -> Result<StorageInfo, StorageError> {
+//!     HISTORY.lock(|h| h.borrow_mut().as_mut().ok_or(StorageError::NotMounted)?.log().control(op))
+//! }
+//!
+//! let config = ShellConfig {
+//!     storage: Some(storage), // `storage [info|mount|format]` built-in
+//!     history_store: Some(history_store), // see `HistoryStore`
+//!     // ...
+//! };
+//! ```
+//!
+//! With a `HistoryStore`, the shell's `History` loads the stored lines when it is set
+//! up, appends each line it keeps and clears the storage on `#c`.
+
+#[cfg(feature = "flash-storage")]
+mod flash;
+
+#[cfg(feature = "flash-storage")]
+pub use flash::{FlashHistory, FlashLog, FlashSettings, MAX_RECORD_LEN};
+
+/// Storage for command history lines.
+pub trait HistoryStorage {
+    /// Calls `each` with the stored lines, oldest first, and returns how many there were.
+    fn load(&mut self, each: &mut dyn FnMut(&str)) -> Result<usize, StorageError>;
+
+    /// Stores `line` after the existing ones.
+    fn append(&mut self, line: &str) -> Result<(), StorageError>;
+
+    /// Forgets all stored lines.
+    fn clear(&mut self) -> Result<(), StorageError>;
+}
+
+/// A `History` operation on the application's `HistoryStorage` (see `HistoryStore`).
+pub enum HistoryOp<'a> {
+    /// Passes the stored lines to the closure, oldest first.
+    Load(&'a mut dyn FnMut(&str)),
+    /// Stores a line after the existing ones.
+    Append(&'a str),
+    /// Forgets all stored lines.
+    Clear,
+}
+
+impl HistoryOp<'_> {
+    /// Runs the operation on `storage`; returns the number of lines loaded, 0 for the
+    /// other operations.
+    pub fn run(self, storage: &mut dyn HistoryStorage) -> Result<usize, StorageError> {
+        match self {
+            HistoryOp::Load(each) => storage.load(each),
+            HistoryOp::Append(line) => storage.append(line).map(|()| 0),
+            HistoryOp::Clear => storage.clear().map(|()| 0),
+        }
+    }
+}
+
+/// Runs a `History` operation on the application's history storage, which usually
+/// lives in a static mutex:
+///
+/// ```ignore
+/// fn history_store(op: HistoryOp) -> Result<usize, StorageError> {
+///     HISTORY.lock(|h| op.run(h.borrow_mut().as_mut().ok_or(StorageError::NotMounted)?))
+/// }
+/// ```
+pub type HistoryStore = fn(HistoryOp<'_>) -> Result<usize, StorageError>;
+
+/// Storage for small key/value settings (prompt, log level, baud rate, ...).
+pub trait SettingsStorage {
+    /// Copies the value of `key` into `value` and returns its length, `None` when unset.
+    fn get(&mut self, key: &str, value: &mut [u8]) -> Result<Option<usize>, StorageError>;
+
+    /// Sets `key` to `value`.
+    fn set(&mut self, key: &str, value: &[u8]) -> Result<(), StorageError>;
+
+    /// Unsets `key`.
+    fn remove(&mut self, key: &str) -> Result<(), StorageError>;
+}
+
+/// Operations of the `storage` built-in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageOp {
+    /// Reports the state of the mounted storage.
+    Info,
+    /// Scans the storage and makes it usable, fails if it was never formatted.
+    Mount,
+    /// Erases the storage and leaves it mounted and empty.
+    Format,
+}
+
+/// State of a storage, reported by the `storage` built-in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StorageInfo {
+    /// Records in use, including superseded ones not yet compacted away.
+    pub records: usize,
+    /// Bytes written to the active area.
+    pub used: usize,
+    /// Size of the active area.
+    pub capacity: usize,
+    /// Number of times the storage was compacted since it was formatted, starting at 1.
+    pub generation: u32,
+}
+
+/// Runs a `storage` built-in operation on the application's storage.
+pub type StorageControl = fn(StorageOp) -> Result<StorageInfo, StorageError>;
+
+/// Errors reported by the storage implementations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageError {
+    /// The flash driver reported an error.
+    Flash,
+    /// The region is not erase-aligned, too small, or the flash writes in too large units.
+    InvalidLayout,
+    /// The storage holds no valid header, it has to be formatted.
+    NotFormatted,
+    /// The storage was not mounted (or formatted) yet.
+    NotMounted,
+    /// A record is longer than `MAX_RECORD_LEN`, or a key is empty or contains NUL.
+    TooLarge,
+    /// The live records fill the storage even after compaction.
+    Full,
+}
+
+impl StorageError {
+    pub fn message(&self) -> &'static str {
+        match self {
+            StorageError::Flash => "flash error",
+            StorageError::InvalidLayout => "invalid storage layout",
+            StorageError::NotFormatted => "not formatted",
+            StorageError::NotMounted => "not mounted",
+            StorageError::TooLarge => "record too large",
+            StorageError::Full => "storage full",
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! Streaming output.
+//!
+//! A command that prints readings continuously (e.g. `adc watch`) registers a stream
+//! and returns. The shell then calls the stream's sample function every `interval_ms`
+//! instead of showing the prompt, and routes keys to the stream instead of the line
+//! editor: Space pauses and resumes, `q` stops, other keys are ignored.
+//!
+//! ```ignore
+//! fn sample() -> bool {
+//!     log_info!("adc: {}", adc::read());
+//!     true // false ends the stream
+//! }
+//!
+//! pub fn adcwatch(interval_ms: u32) {
+//!     if let Err(e) = ushell2::stream::start(sample, interval_ms) {
+//!         log_error!("{}", e.message());
+//!     }
+//! }
+//! ```
+//!
+//! The shell needs a millisecond clock to pace streams (`ShellConfig::now_ms`); without
+//! one the sample function runs on every poll of the shell.
+
+use core::cell::Cell;
+use critical_section::Mutex;
+
+use crate::input::key_reader::Key;
+use crate::watch::Repeat;
+
+#[cfg(test)]
+extern crate std;
+
+/// Prints one sample; returns `false` to end the stream.
+pub type SampleFn = fn() -> bool;
+
+/// Why a stream was not started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamError {
+    /// Another stream is running.
+    AlreadyActive,
+}
+
+impl StreamError {
+    pub const fn message(&self) -> &'static str {
+        match self {
+            StreamError::AlreadyActive => "stream already running",
+        }
+    }
+}
+
+/// What a key did to the running stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamKey {
+    Paused,
+    Resumed,
+    Stopped,
+    /// The key has no meaning for streams.
+    Ignored,
+}
+
+#[derive(Clone, Copy)]
+struct Stream {
+    sample: SampleFn,
+    repeat: Repeat,
+    paused: bool,
+}
+
+static ACTIVE: Mutex<Cell<Option<Stream>>> = Mutex::new(Cell::new(None));
+
+/// Registers the stream of the running command; the first sample is taken on the next
+/// poll of the shell.
+pub fn start(sample: SampleFn, interval_ms: u32) -> Result<(), StreamError> {
+    critical_section::with(|cs| {
+        let active = ACTIVE.borrow(cs);
+        if active.get().is_some() {
+            return Err(StreamError::AlreadyActive);
+        }
+        active.set(Some(Stream {
+            sample,
+            repeat: Repeat::new(interval_ms),
+            paused: false,
+        }));
+        Ok(())
+    })
+}
+
+/// Ends the stream; returns `false` if none was running.
+pub fn stop() -> bool {
+    critical_section::with(|cs| ACTIVE.borrow(cs).take().is_some())
+}
+
+/// Returns `true` while a stream is registered (running or paused).
+pub fn is_active() -> bool {
+    critical_section::with(|cs| ACTIVE.borrow(cs).get().is_some())
+}
+
+/// Applies a key typed while the stream runs (called by the shell).
+pub fn handle_key(key: Key) -> StreamKey {
+    critical_section::with(|cs| {
+        let active = ACTIVE.borrow(cs);
+        let Some(mut stream) = active.get() else {
+            return StreamKey::Ignored;
+        };
+        match key {
+            Key::Char(' ') => {
+                stream.paused = !stream.paused;
+                active.set(Some(stream));
+                if stream.paused {
+                    StreamKey::Paused
+                } else {
+                    StreamKey::Resumed
+                }
+            }
+            Key::Char('q') => {
+                active.set(None);
+                StreamKey::Stopped
+            }
+            _ => StreamKey::Ignored,
+        }
+    })
+}
+
+/// Takes a sample if one is due (called by the shell); returns `false` once the stream
+/// has ended. `now_ms` is `None` without a clock.
+pub fn poll(now_ms: Option<u64>) -> bool {
+    let due = critical_section::with(|cs| {
+        let active = ACTIVE.borrow(cs);
+        let mut stream = active.get()?;
+        if stream.paused {
+            return Some(None);
+        }
+        let due = now_ms.is_none_or(|now| stream.repeat.due(now));
+        active.set(Some(stream));
+        Some(due.then_some(stream.sample))
+    });
+    match due {
+        None => false,
+        Some(None) => true,
+        // Outside the critical section: the sample prints and may stop the stream
+        Some(Some(sample)) => {
+            if !sample() {
+                stop();
+            }
+            is_active()
+        }
+    }
+}
+
+/// Serializes tests that use the global stream.
+#[cfg(test)]
+pub(crate) static TEST_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::{AtomicU32, Ordering};
+
+    static SAMPLES: AtomicU32 = AtomicU32::new(0);
+
+    fn sample() -> bool {
+        SAMPLES.fetch_add(1, Ordering::Relaxed) < 2
+    }
+
+    #[test]
+    fn test_interval_pause_and_end() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        stop();
+        SAMPLES.store(0, Ordering::Relaxed);
+
+        start(sample, 100).unwrap();
+        assert_eq!(start(sample, 100), Err(StreamError::AlreadyActive));
+        assert!(poll(Some(0)));
+        assert!(poll(Some(50)));
+        assert_eq!(SAMPLES.load(Ordering::Relaxed), 1);
+
+        assert_eq!(handle_key(Key::Char(' ')), StreamKey::Paused);
+        assert!(poll(Some(500)));
+        assert_eq!(SAMPLES.load(Ordering::Relaxed), 1);
+        assert_eq!(handle_key(Key::Char('x')), StreamKey::Ignored);
+        assert_eq!(handle_key(Key::Char(' ')), StreamKey::Resumed);
+
+        // The third sample returns false and ends the stream
+        assert!(poll(Some(500)));
+        assert!(!poll(Some(600)));
+        assert!(!is_active());
+        assert_eq!(SAMPLES.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn test_q_stops() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        stop();
+        start(sample, 0).unwrap();
+        assert_eq!(handle_key(Key::Char('q')), StreamKey::Stopped);
+        assert!(!poll(None));
+        assert_eq!(handle_key(Key::Char(' ')), StreamKey::Ignored);
+    }
+}
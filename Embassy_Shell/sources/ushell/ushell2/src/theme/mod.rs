@@ -0,0 +1,154 @@
+//! Colors of the shell's own output.
+//!
+//! A `Theme` holds the ANSI SGR sequences the shell writes around the prompt, the
+//! errors it reports (`Error: ...`), the live validation hints, the headers of the help
+//! listings and the autocomplete suggestions shown as ghost text. Log labels keep the
+//! colors of their level (see `logger`).
+//!
+//! The active theme is global and selected with `ShellConfig::theme`,
+//! `InputParser::set_theme` or the `theme [name]` built-in at runtime:
+//!
+//! ```text
+//! >> theme bright
+//! >> theme plain
+//! ```
+//!
+//! `PLAIN` writes no colors at all, for dumb terminals and terminal logs; output captured
+//! for a pipe (see `logger::begin_log_capture`) is never colored. Custom themes are plain
+//! statics:
+//!
+//! ```ignore
+//! static MY_THEME: Theme = Theme {
+//!     name: "mine",
+//!     prompt: "\x1B[1;35m",
+//!     error: "\x1B[1;31m",
+//!     ok: "\x1B[32m",
+//!     header: "\x1B[4m",
+//!     suggestion: "\x1B[2m",
+//! };
+//! theme::set_theme(&MY_THEME);
+//! ```
+
+use core::cell::Cell;
+use critical_section::Mutex;
+
+use crate::logger::UnifiedWriter;
+
+#[cfg(test)]
+extern crate std;
+
+/// Ends a colored span.
+pub const RESET: &str = "\x1B[0m";
+
+/// SGR sequences of each kind of output; an empty one leaves the output uncolored.
+#[derive(Debug)]
+pub struct Theme {
+    pub name: &'static str,
+    /// The prompt (not the busy marker).
+    pub prompt: &'static str,
+    /// `Error: ...` lines of failed commands, the `✗` validation hint and the boundary
+    /// marker.
+    pub error: &'static str,
+    /// The `✓` hint of a complete line.
+    pub ok: &'static str,
+    /// Headers of the help listings (`Available commands:`, ...).
+    pub header: &'static str,
+    /// Autocomplete suggestions: the ghost text of ghost completion (see
+    /// `InputParser::set_ghost_completion`), not shown without a color.
+    pub suggestion: &'static str,
+}
+
+impl Theme {
+    /// Writes `text` in `color` (one of the fields), followed by `RESET` if colored.
+    pub fn paint<W: UnifiedWriter + ?Sized>(writer: &mut W, color: &str, text: &str) {
+        if color.is_empty() {
+            writer.write_str(text);
+            return;
+        }
+        writer.write_str(color);
+        writer.write_str(text);
+        writer.write_str(RESET);
+    }
+}
+
+/// Red errors, green complete lines and gray suggestions; prompt and headers uncolored.
+pub static DEFAULT: Theme = Theme {
+    name: "default",
+    prompt: "",
+    error: "\x1B[31m",
+    ok: "\x1B[32m",
+    header: "",
+    suggestion: "\x1B[90m",
+};
+
+/// Colored prompt and errors, bold headers, dim suggestions.
+pub static BRIGHT: Theme = Theme {
+    name: "bright",
+    prompt: "\x1B[1;32m",
+    error: "\x1B[1;31m",
+    ok: "\x1B[32m",
+    header: "\x1B[1;36m",
+    suggestion: "\x1B[2m",
+};
+
+/// No colors.
+pub static PLAIN: Theme = Theme {
+    name: "plain",
+    prompt: "",
+    error: "",
+    ok: "",
+    header: "",
+    suggestion: "",
+};
+
+/// Themes selectable by name with `theme <name>`.
+pub static PRESETS: [&Theme; 3] = [&DEFAULT, &BRIGHT, &PLAIN];
+
+static ACTIVE: Mutex<Cell<&'static Theme>> = Mutex::new(Cell::new(&DEFAULT));
+
+/// Returns the preset called `name`.
+pub fn find(name: &str) -> Option<&'static Theme> {
+    PRESETS.iter().copied().find(|theme| theme.name == name)
+}
+
+/// Selects the theme of the errors reported by the runner. The edit line follows
+/// `InputParser::set_theme`, which also calls this.
+pub fn set_theme(theme: &'static Theme) {
+    critical_section::with(|cs| ACTIVE.borrow(cs).set(theme));
+}
+
+/// Returns the active theme.
+pub fn theme() -> &'static Theme {
+    critical_section::with(|cs| ACTIVE.borrow(cs).get())
+}
+
+/// Serializes tests that change the active theme.
+#[cfg(test)]
+pub(crate) static TEST_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use heapless::String;
+
+    #[test]
+    fn test_paint() {
+        let mut out: String<64> = String::new();
+        Theme::paint(&mut out, BRIGHT.error, "Error: x");
+        Theme::paint(&mut out, PLAIN.error, " Error: y");
+        assert_eq!(out.as_str(), "\x1B[1;31mError: x\x1B[0m Error: y");
+    }
+
+    #[test]
+    fn test_select_preset() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        assert!(find("neon").is_none());
+        set_theme(find("plain").unwrap());
+        assert_eq!(theme().name, "plain");
+        set_theme(&DEFAULT);
+    }
+}
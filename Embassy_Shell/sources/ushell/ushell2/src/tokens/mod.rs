@@ -0,0 +1,175 @@
+//! Command line tokenizer.
+//!
+//! The generated dispatchers split command lines with `Tokenizer`; command handlers
+//! taking a free-form `s` argument can use it to split their own sub-arguments the
+//! same way:
+//!
+//! ```ignore
+//! pub fn i2c(args: &str) {
+//!     let mut tokens = Tokenizer::new(args);
+//!     match (tokens.next(), tokens.next()) {
+//!         (Some("scan"), None) => scan(),
+//!         (Some("read"), Some(addr)) => read(addr, tokens.rest().trim()),
+//!         _ => println!("usage: i2c scan | i2c read <addr> [len]"),
+//!     }
+//! }
+//! ```
+
+/// Iterator over the tokens of a line: runs of characters separated by ASCII spaces or
+/// tabs. A token starting with `"` extends to the closing quote and is yielded without
+/// the quotes; inside it `\"` does not close the token (see `unescape`), and characters
+/// glued to the closing quote are dropped. An unterminated quote extends to the end.
+/// A named argument `--name="..."` is one token too, yielded with its quotes.
+///
+/// ```text
+/// led 1 "hello world" "say \"hi\""   ->   led | 1 | hello world | say \"hi\"
+/// log --msg="a b" --raw               ->   log | --msg="a b" | --raw
+/// ```
+#[derive(Debug, Clone)]
+pub struct Tokenizer<'a> {
+    line: &'a str,
+    pos: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub const fn new(line: &'a str) -> Self {
+        Self { line, pos: 0 }
+    }
+
+    /// Byte offset in the line right after the last token yielded.
+    pub fn offset(&self) -> usize {
+        self.pos
+    }
+
+    /// The part of the line after the last token yielded, untrimmed.
+    pub fn rest(&self) -> &'a str {
+        &self.line[self.pos..]
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let bytes = self.line.as_bytes();
+        let mut i = self.pos;
+        while i < bytes.len() && is_space(bytes[i]) {
+            i += 1;
+        }
+        if i >= bytes.len() {
+            return None;
+        }
+
+        let token = if bytes[i] == b'"' {
+            let start = i + 1;
+            i = skip_quoted(bytes, start);
+            let end = i.min(bytes.len());
+            if i < bytes.len() {
+                i += 1;
+            }
+            while i < bytes.len() && !is_space(bytes[i]) {
+                i += 1;
+            }
+            &self.line[start..end]
+        } else {
+            let start = i;
+            while i < bytes.len() && !is_space(bytes[i]) {
+                // `--name="a b"`: the quoted value belongs to the token
+                if bytes[i] == b'"' && bytes[i - 1] == b'=' && bytes[start..].starts_with(b"--") {
+                    i = (skip_quoted(bytes, i + 1) + 1).min(bytes.len());
+                    continue;
+                }
+                i += 1;
+            }
+            &self.line[start..i]
+        };
+        self.pos = i.min(bytes.len());
+        Some(token)
+    }
+}
+
+/// Index of the `"` closing a quote opened before `start`, or past the end if unterminated.
+fn skip_quoted(bytes: &[u8], start: usize) -> usize {
+    let mut i = start;
+    while i < bytes.len() && bytes[i] != b'"' {
+        i += if bytes[i] == b'\\' { 2 } else { 1 };
+    }
+    i
+}
+
+/// Resolves the escapes of a quoted token into `out`: a backslash keeps the character
+/// after it (`\"` -> `"`, `\\` -> `\`). Returns `None` if `out` is too small.
+pub fn unescape<'b>(token: &str, out: &'b mut [u8]) -> Option<&'b str> {
+    let mut len = 0;
+    let mut escaped = false;
+    for &byte in token.as_bytes() {
+        if byte == b'\\' && !escaped {
+            escaped = true;
+            continue;
+        }
+        escaped = false;
+        *out.get_mut(len)? = byte;
+        len += 1;
+    }
+    // Only ASCII backslashes were removed
+    core::str::from_utf8(&out[..len]).ok()
+}
+
+/// ASCII space or tab.
+#[inline(always)]
+const fn is_space(b: u8) -> bool {
+    b == b' ' || b == b'\t'
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_and_quotes() {
+        let tokens = Tokenizer::new("  led\t1 \"hello world\"x \"unterminated ");
+        assert!(tokens.eq(["led", "1", "hello world", "unterminated "]));
+        assert_eq!(Tokenizer::new(" \t ").next(), None);
+
+        let mut tokens = Tokenizer::new("i2c read 0x50 16");
+        assert_eq!(tokens.next(), Some("i2c"));
+        assert_eq!(tokens.next(), Some("read"));
+        assert_eq!(tokens.offset(), 8);
+        assert_eq!(tokens.rest(), " 0x50 16");
+    }
+
+    #[test]
+    fn test_escaped_quotes() {
+        let mut tokens = Tokenizer::new(r#"echo "say \"hi\"" "a\\" b"#);
+        assert_eq!(tokens.next(), Some("echo"));
+        let quoted = tokens.next().unwrap();
+        assert_eq!(quoted, r#"say \"hi\""#);
+        assert_eq!(tokens.next(), Some(r"a\\"));
+        assert_eq!(tokens.next(), Some("b"));
+
+        let mut out = [0u8; 16];
+        assert_eq!(unescape(quoted, &mut out), Some(r#"say "hi""#));
+        assert_eq!(unescape(r"a\\", &mut out), Some(r"a\"));
+        assert_eq!(unescape(quoted, &mut [0u8; 4]), None);
+
+        // A trailing backslash does not run past the end
+        assert!(Tokenizer::new("\"ab\\").eq(["ab\\"]));
+    }
+
+    #[test]
+    fn test_named_arguments() {
+        let tokens = Tokenizer::new(r#"log --msg="a b" --raw x="c d" --tag="e \"f"#);
+        assert!(tokens.eq([
+            "log",
+            r#"--msg="a b""#,
+            "--raw",
+            r#"x="c"#,
+            r#"d""#,
+            r#"--tag="e \"f"#
+        ]));
+    }
+}
@@ -0,0 +1,211 @@
+//! Multi-command transactions.
+//!
+//! `begin` starts a transaction: every command line that runs successfully afterwards
+//! is recorded in a journal. `commit` keeps the changes and drops the journal; `abort`
+//! undoes them by running the rollback callback of each recorded command, newest first.
+//! Rollback callbacks are declared in the commands cfg with `@rollback=path` and take
+//! the same arguments as the command (see the dispatcher README).
+//!
+//! ```text
+//! >> begin
+//! >> set net.wifi.ssid lab
+//! >> set net.wifi.channel 6
+//! >> abort
+//! rollback: set net.wifi.channel 6
+//! rollback: set net.wifi.ssid lab
+//! ```
+//!
+//! The journal is static and bounded: while a transaction is open, commands that do not
+//! fit in it are refused rather than run without a way back.
+
+use core::cell::RefCell;
+use critical_section::Mutex;
+use heapless::{String, Vec};
+
+/// Maximum number of command lines recorded by one transaction.
+pub const TXN_MAX_COMMANDS: usize = 8;
+
+/// Maximum length in bytes of a recorded command line.
+pub const TXN_LINE_MAX: usize = 128;
+
+/// Why a transaction operation was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxnError {
+    /// `begin` while a transaction is open.
+    AlreadyActive,
+    /// `commit` or `abort` without an open transaction.
+    NotActive,
+    /// The journal holds `TXN_MAX_COMMANDS` lines already.
+    JournalFull,
+    /// The line is longer than `TXN_LINE_MAX`.
+    LineTooLong,
+}
+
+impl TxnError {
+    pub const fn message(&self) -> &'static str {
+        match self {
+            TxnError::AlreadyActive => "transaction already open",
+            TxnError::NotActive => "no open transaction",
+            TxnError::JournalFull => "transaction journal full (commit or abort first)",
+            TxnError::LineTooLong => "command too long for the transaction journal",
+        }
+    }
+}
+
+struct Journal {
+    active: bool,
+    lines: Vec<String<TXN_LINE_MAX>, TXN_MAX_COMMANDS>,
+}
+
+static JOURNAL: Mutex<RefCell<Journal>> = Mutex::new(RefCell::new(Journal {
+    active: false,
+    lines: Vec::new(),
+}));
+
+/// Returns `true` while a transaction is open.
+pub fn is_active() -> bool {
+    critical_section::with(|cs| JOURNAL.borrow_ref(cs).active)
+}
+
+/// Opens a transaction.
+pub fn begin() -> Result<(), TxnError> {
+    critical_section::with(|cs| {
+        let mut journal = JOURNAL.borrow_ref_mut(cs);
+        if journal.active {
+            return Err(TxnError::AlreadyActive);
+        }
+        journal.active = true;
+        journal.lines.clear();
+        Ok(())
+    })
+}
+
+/// Checks that `line` can be recorded if it runs; always `Ok` without a transaction.
+pub fn check_room(line: &str) -> Result<(), TxnError> {
+    critical_section::with(|cs| {
+        let journal = JOURNAL.borrow_ref(cs);
+        if !journal.active {
+            Ok(())
+        } else if line.len() > TXN_LINE_MAX {
+            Err(TxnError::LineTooLong)
+        } else if journal.lines.is_full() {
+            Err(TxnError::JournalFull)
+        } else {
+            Ok(())
+        }
+    })
+}
+
+/// Records a line that ran successfully; ignored without a transaction.
+/// Lines refused by `check_room` are dropped.
+pub fn record(line: &str) {
+    critical_section::with(|cs| {
+        let mut journal = JOURNAL.borrow_ref_mut(cs);
+        if !journal.active {
+            return;
+        }
+        let mut entry = String::new();
+        if entry.push_str(line).is_ok() {
+            let _ = journal.lines.push(entry);
+        }
+    });
+}
+
+/// Closes the transaction keeping its changes; returns the number of recorded lines.
+pub fn commit() -> Result<usize, TxnError> {
+    take_journal().map(|lines| lines.len())
+}
+
+/// Closes the transaction and passes the recorded lines to `undo`, newest first.
+/// Returns the number of recorded lines.
+pub fn abort(mut undo: impl FnMut(&str)) -> Result<usize, TxnError> {
+    // The journal is released before the callbacks run
+    let lines = take_journal()?;
+    for line in lines.iter().rev() {
+        undo(line);
+    }
+    Ok(lines.len())
+}
+
+fn take_journal() -> Result<Vec<String<TXN_LINE_MAX>, TXN_MAX_COMMANDS>, TxnError> {
+    critical_section::with(|cs| {
+        let mut journal = JOURNAL.borrow_ref_mut(cs);
+        if !journal.active {
+            return Err(TxnError::NotActive);
+        }
+        journal.active = false;
+        Ok(core::mem::take(&mut journal.lines))
+    })
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use std::vec::Vec as StdVec;
+
+    // Tests share the global journal
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    fn reset() {
+        let _ = take_journal();
+    }
+
+    #[test]
+    fn test_abort_undoes_in_reverse_order() {
+        let _guard = LOCK.lock().unwrap();
+        reset();
+
+        record("ignored 0");
+        assert_eq!(begin(), Ok(()));
+        assert_eq!(begin(), Err(TxnError::AlreadyActive));
+        record("set a 1");
+        record("set b 2");
+
+        let mut undone = StdVec::new();
+        assert_eq!(
+            abort(|line| undone.push(std::string::String::from(line))),
+            Ok(2)
+        );
+        assert_eq!(undone, ["set b 2", "set a 1"]);
+        assert!(!is_active());
+        assert_eq!(abort(|_| {}), Err(TxnError::NotActive));
+    }
+
+    #[test]
+    fn test_commit_drops_journal() {
+        let _guard = LOCK.lock().unwrap();
+        reset();
+
+        assert_eq!(commit(), Err(TxnError::NotActive));
+        begin().unwrap();
+        record("set a 1");
+        assert_eq!(commit(), Ok(1));
+
+        // A new transaction starts empty
+        begin().unwrap();
+        assert_eq!(abort(|_| panic!("nothing to undo")), Ok(0));
+    }
+
+    #[test]
+    fn test_check_room() {
+        let _guard = LOCK.lock().unwrap();
+        reset();
+
+        let long = "x".repeat(TXN_LINE_MAX + 1);
+        assert_eq!(check_room(&long), Ok(()));
+
+        begin().unwrap();
+        assert_eq!(check_room(&long), Err(TxnError::LineTooLong));
+        for _ in 0..TXN_MAX_COMMANDS {
+            assert_eq!(check_room("set a 1"), Ok(()));
+            record("set a 1");
+        }
+        assert_eq!(check_room("set a 1"), Err(TxnError::JournalFull));
+        assert_eq!(commit(), Ok(TXN_MAX_COMMANDS));
+    }
+}
@@ -0,0 +1,314 @@
+//! Periodic built-in commands.
+//!
+//! `watch mem <addr> <len> <interval_ms>` samples a memory region every `interval_ms`
+//! and hexdumps it in place, showing the bytes that changed since the previous sample
+//! in inverse video. Useful to follow DMA buffers or peripheral registers live; any key
+//! stops the watch.
+//!
+//! ```text
+//! >> watch mem 0x20000000 32 500
+//! 20000000: 01 00 00 00 7F 12 00 00 00 00 00 00 00 00 00 00
+//! 20000010: 00 00 00 00 00 00 00 00 AA 55 00 00 00 00 00 00
+//! ```
+//!
+//! The shell needs a millisecond clock to run watches (`ShellConfig::now_ms`).
+
+use crate::logger::UnifiedWriter;
+
+/// Largest region `watch mem` can sample, in bytes.
+pub const WATCH_MAX_LEN: usize = 128;
+
+/// Bytes shown per hexdump row.
+pub(crate) const ROW_LEN: usize = 16;
+
+const INVERSE: &str = "\x1b[7m";
+const RESET: &str = "\x1b[0m";
+
+/// Fires every `interval_ms` milliseconds of a caller-supplied clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Repeat {
+    interval_ms: u32,
+    next_ms: Option<u64>,
+}
+
+impl Repeat {
+    pub const fn new(interval_ms: u32) -> Self {
+        Self {
+            interval_ms,
+            next_ms: None,
+        }
+    }
+
+    /// Returns `true` if the interval elapsed (always on the first call) and schedules
+    /// the next run relative to `now_ms`.
+    pub fn due(&mut self, now_ms: u64) -> bool {
+        match self.next_ms {
+            Some(next) if now_ms < next => false,
+            _ => {
+                self.next_ms = Some(now_ms.saturating_add(self.interval_ms as u64));
+                true
+            }
+        }
+    }
+}
+
+/// Why the arguments of `watch` were rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchError {
+    /// Wrong subcommand or argument count.
+    Usage,
+    /// An argument is not a number, or the interval is zero.
+    BadNumber,
+    /// The length is zero or larger than `WATCH_MAX_LEN`.
+    BadLength,
+}
+
+impl WatchError {
+    pub const fn message(&self) -> &'static str {
+        match self {
+            WatchError::Usage => "usage: watch mem <addr> <len> <interval_ms>",
+            WatchError::BadNumber => "watch: invalid number",
+            WatchError::BadLength => "watch: length must be 1..=128",
+        }
+    }
+}
+
+/// State of a running `watch mem`.
+pub struct MemWatch {
+    addr: usize,
+    len: usize,
+    repeat: Repeat,
+    prev: [u8; WATCH_MAX_LEN],
+    sampled: bool,
+}
+
+impl MemWatch {
+    /// Parses the arguments following `watch`, e.g. `mem 0x20000000 32 500`.
+    /// Numbers are decimal or `0x` hexadecimal; `_` separators are allowed.
+    pub fn parse(args: &str) -> Result<Self, WatchError> {
+        let mut it = args.split_whitespace();
+        let (Some("mem"), Some(addr), Some(len), Some(interval), None) =
+            (it.next(), it.next(), it.next(), it.next(), it.next())
+        else {
+            return Err(WatchError::Usage);
+        };
+        let addr = parse_number(addr).ok_or(WatchError::BadNumber)?;
+        let len = parse_number(len).ok_or(WatchError::BadNumber)?;
+        let interval = parse_number(interval)
+            .and_then(|n| u32::try_from(n).ok())
+            .filter(|&n| n > 0)
+            .ok_or(WatchError::BadNumber)?;
+        if len == 0 || len > WATCH_MAX_LEN {
+            return Err(WatchError::BadLength);
+        }
+        Ok(Self {
+            addr,
+            len,
+            repeat: Repeat::new(interval),
+            prev: [0; WATCH_MAX_LEN],
+            sampled: false,
+        })
+    }
+
+    /// Takes and prints a sample if the interval elapsed; returns `true` if it did.
+    ///
+    /// The first sample is printed below the command line, later ones overwrite it.
+    pub fn poll<W: UnifiedWriter>(&mut self, now_ms: u64, writer: &mut W) -> bool {
+        if !self.repeat.due(now_ms) {
+            return false;
+        }
+
+        let mut cur = [0u8; WATCH_MAX_LEN];
+        for (i, byte) in cur[..self.len].iter_mut().enumerate() {
+            // The region is chosen by the user of a debug shell; byte-wise volatile reads
+            // are what a debugger's memory view does as well.
+            *byte = unsafe { core::ptr::read_volatile((self.addr + i) as *const u8) };
+        }
+
+        let rows = self.len.div_ceil(ROW_LEN);
+        if self.sampled {
+            // Move back to the first row of the previous sample
+            writer.write_str("\x1b[");
+            write_decimal(writer, rows);
+            writer.write_str("F");
+        }
+        let prev = self.sampled.then_some(&self.prev[..self.len]);
+        write_hex_rows(writer, self.addr, &cur[..self.len], prev);
+        writer.flush();
+
+        self.prev = cur;
+        self.sampled = true;
+        true
+    }
+}
+
+/// Hexdump `data` as rows of 16 bytes prefixed by their address; bytes that differ
+/// from `prev` are shown in inverse video.
+pub(crate) fn write_hex_rows<W: UnifiedWriter>(
+    writer: &mut W,
+    addr: usize,
+    data: &[u8],
+    prev: Option<&[u8]>,
+) {
+    for (row, chunk) in data.chunks(ROW_LEN).enumerate() {
+        let row_addr = addr.wrapping_add(row * ROW_LEN);
+        write_hex(writer, row_addr as u64, core::mem::size_of::<usize>() * 2);
+        writer.write_str(":");
+        for (i, &byte) in chunk.iter().enumerate() {
+            let changed = prev.is_some_and(|p| p[row * ROW_LEN + i] != byte);
+            writer.write_str(" ");
+            if changed {
+                writer.write_str(INVERSE);
+            }
+            write_hex(writer, byte as u64, 2);
+            if changed {
+                writer.write_str(RESET);
+            }
+        }
+        writer.write_str("\r\n");
+    }
+}
+
+fn write_hex<W: UnifiedWriter>(writer: &mut W, value: u64, digits: usize) {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+    let mut buf = [0u8; 16];
+    let digits = digits.min(buf.len());
+    for (i, slot) in buf[..digits].iter_mut().enumerate() {
+        let shift = (digits - 1 - i) * 4;
+        *slot = HEX[((value >> shift) & 0xF) as usize];
+    }
+    writer.write_bytes(&buf[..digits]);
+}
+
+fn write_decimal<W: UnifiedWriter>(writer: &mut W, mut value: usize) {
+    let mut buf = [0u8; 20];
+    let mut pos = buf.len();
+    loop {
+        pos -= 1;
+        buf[pos] = b'0' + (value % 10) as u8;
+        value /= 10;
+        if value == 0 {
+            break;
+        }
+    }
+    writer.write_bytes(&buf[pos..]);
+}
+
+/// Decimal or `0x` hexadecimal, with optional `_` separators.
+pub(crate) fn parse_number(s: &str) -> Option<usize> {
+    let (digits, radix) = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => (hex, 16),
+        None => (s, 10),
+    };
+    let mut value: usize = 0;
+    let mut any = false;
+    for ch in digits.chars() {
+        if ch == '_' {
+            continue;
+        }
+        let d = ch.to_digit(radix)? as usize;
+        value = value.checked_mul(radix as usize)?.checked_add(d)?;
+        any = true;
+    }
+    any.then_some(value)
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use heapless::String;
+
+    // ============================================================================
+    // Repeat Tests
+    // ============================================================================
+
+    #[test]
+    fn test_repeat_due() {
+        let mut repeat = Repeat::new(100);
+        assert!(repeat.due(5));
+        assert!(!repeat.due(50));
+        assert!(!repeat.due(104));
+        assert!(repeat.due(105));
+        // Late polls do not cause bursts
+        assert!(repeat.due(1000));
+        assert!(!repeat.due(1050));
+    }
+
+    // ============================================================================
+    // Argument Parsing Tests
+    // ============================================================================
+
+    #[test]
+    fn test_parse_arguments() {
+        let watch = MemWatch::parse("mem 0x2000_0000 32 500").unwrap();
+        assert_eq!(watch.addr, 0x2000_0000);
+        assert_eq!(watch.len, 32);
+        assert_eq!(watch.repeat, Repeat::new(500));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(MemWatch::parse("").err(), Some(WatchError::Usage));
+        assert_eq!(MemWatch::parse("reg 0 4 10").err(), Some(WatchError::Usage));
+        assert_eq!(MemWatch::parse("mem 0 4").err(), Some(WatchError::Usage));
+        assert_eq!(
+            MemWatch::parse("mem 0 4 10 1").err(),
+            Some(WatchError::Usage)
+        );
+        assert_eq!(
+            MemWatch::parse("mem 0xZZ 4 10").err(),
+            Some(WatchError::BadNumber)
+        );
+        assert_eq!(
+            MemWatch::parse("mem 0x 4 10").err(),
+            Some(WatchError::BadNumber)
+        );
+        assert_eq!(
+            MemWatch::parse("mem 0 4 0").err(),
+            Some(WatchError::BadNumber)
+        );
+        assert_eq!(
+            MemWatch::parse("mem 0 0 10").err(),
+            Some(WatchError::BadLength)
+        );
+        assert_eq!(
+            MemWatch::parse("mem 0 129 10").err(),
+            Some(WatchError::BadLength)
+        );
+    }
+
+    // ============================================================================
+    // Sampling Tests
+    // ============================================================================
+
+    #[test]
+    fn test_poll_highlights_changes() {
+        let mut region = [0u8; 20];
+        region[1] = 0xAB;
+        let addr = region.as_ptr() as usize;
+
+        let mut watch = MemWatch::parse("mem 0 20 100").unwrap();
+        watch.addr = addr;
+
+        let mut out: String<512> = String::new();
+        assert!(watch.poll(0, &mut out));
+        assert!(out.contains(" 00 AB 00"));
+        assert!(!out.contains(INVERSE));
+        assert_eq!(out.matches("\r\n").count(), 2);
+
+        out.clear();
+        assert!(!watch.poll(50, &mut out));
+        assert!(out.is_empty());
+
+        unsafe { core::ptr::write_volatile(region.as_mut_ptr().add(17), 0x5A) };
+        assert!(watch.poll(100, &mut out));
+        // Cursor moved up over both rows, only the changed byte is highlighted
+        assert!(out.starts_with("\x1b[2F"));
+        assert!(out.contains("\x1b[7m5A\x1b[0m"));
+        assert_eq!(out.matches(INVERSE).count(), 1);
+    }
+}
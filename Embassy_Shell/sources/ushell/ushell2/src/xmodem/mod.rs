@@ -0,0 +1,764 @@
+//! XMODEM / YMODEM receiver, to get images onto a board with only a UART.
+//!
+//! A command starts a transfer and returns. The shell then hands the bytes read from the
+//! UART (from its `UartReader`: `PollingReader`, `AsyncReader`, ...) to the receiver
+//! instead of the line editor until the transfer ends; the sink gets the data blocks in
+//! order:
+//!
+//! ```ignore
+//! use ushell2::xmodem::{self, Protocol};
+//!
+//! fn write_block(offset: u32, data: &[u8]) -> bool {
+//!     flash::write(IMAGE_BASE + offset, data).is_ok() // false cancels the transfer
+//! }
+//!
+//! pub fn recv(name: &str) {
+//!     match xmodem::start(Protocol::Xmodem, write_block) {
+//!         Ok(()) => log_info!("recv | {}: start the upload", name),
+//!         Err(e) => log_error!("recv | {}", e.message()),
+//!     }
+//! }
+//! ```
+//!
+//! ```text
+//! >> recv app.bin
+//! [ INFO] recv | app.bin: start the upload
+//! CCC
+//! xmodem: 4096 bytes received
+//! >>
+//! ```
+//!
+//! Blocks of 128 and 1024 bytes (XMODEM-1K) are accepted, checked with CRC-16 (see
+//! `crate::checksum`). With `Protocol::Ymodem` the size sent in the header block cuts
+//! the padding of the last block; the batch holds one file. Log lines are held while the
+//! transfer runs (see `logger::set_deferred_logging`), and the result is printed above
+//! the prompt, after a hexdump of the start of the block when a bad one ended the
+//! transfer. The shell needs a millisecond clock (`ShellConfig::now_ms`) to repeat the
+//! start request and to notice a sender that stopped; without one a started transfer
+//! fails at once with `XmodemError::NoClock`.
+//!
+//! The shell side is behind the `xmodem` feature. `Receiver` is the protocol alone, byte
+//! in and reply bytes out, for shells reading the UART their own way (e.g. from an RTIC
+//! `RxQueueReader`).
+
+#[cfg(feature = "xmodem")]
+use core::cell::Cell;
+#[cfg(feature = "xmodem")]
+use core::fmt::Write;
+#[cfg(feature = "xmodem")]
+use critical_section::Mutex;
+
+use crate::checksum::crc16;
+#[cfg(feature = "xmodem")]
+use crate::input::renderer::{hexdump, HEXDUMP_ROW_LEN};
+#[cfg(feature = "xmodem")]
+use crate::logger::{self, UnifiedWriter};
+#[cfg(feature = "xmodem")]
+use crate::notice::{self, NOTICE_MAX_LEN};
+
+#[cfg(test)]
+extern crate std;
+
+/// Largest data block (XMODEM-1K).
+pub const BLOCK_MAX: usize = 1024;
+
+const SOH: u8 = 0x01;
+const STX: u8 = 0x02;
+const EOT: u8 = 0x04;
+const ACK: u8 = 0x06;
+const NAK: u8 = 0x15;
+const CAN: u8 = 0x18;
+/// Asks the sender for blocks checked with CRC-16.
+const CRC_REQUEST: u8 = b'C';
+
+/// Interval of the start requests, and how many are sent before giving up.
+const START_INTERVAL_MS: u64 = 3000;
+const START_TRIES: u8 = 10;
+/// Silence within a block, and between blocks, after which the block is asked again.
+const BLOCK_TIMEOUT_MS: u64 = 1000;
+const IDLE_TIMEOUT_MS: u64 = 10_000;
+/// Bad or missing blocks in a row before the transfer is cancelled.
+const MAX_ERRORS: u8 = 10;
+
+/// Protocol spoken by the sender.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    /// XMODEM-CRC and XMODEM-1K: data blocks only, the last one padded.
+    Xmodem,
+    /// YMODEM batch of one file: a header block with the name and size first.
+    Ymodem,
+}
+
+/// Why a transfer failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XmodemError {
+    /// No block came in answer to the start requests.
+    NoSender,
+    /// The sender stopped in the middle of the transfer.
+    Timeout,
+    /// The sender cancelled the transfer.
+    Cancelled,
+    /// The sink refused a block; the transfer was cancelled.
+    Refused,
+    /// A block was skipped; the transfer was cancelled.
+    Sequence,
+    /// Too many bad blocks in a row; the transfer was cancelled.
+    TooManyErrors,
+    /// The YMODEM batch holds more than one file.
+    TooManyFiles,
+    /// A transfer was started already by the running command.
+    AlreadyStarted,
+    /// The shell has no millisecond clock to time the transfer with.
+    NoClock,
+}
+
+impl XmodemError {
+    pub const fn message(&self) -> &'static str {
+        match self {
+            XmodemError::NoSender => "no sender",
+            XmodemError::Timeout => "sender stopped",
+            XmodemError::Cancelled => "cancelled by sender",
+            XmodemError::Refused => "block refused",
+            XmodemError::Sequence => "block out of sequence",
+            XmodemError::TooManyErrors => "too many bad blocks",
+            XmodemError::TooManyFiles => "one file per transfer",
+            XmodemError::AlreadyStarted => "transfer already started",
+            XmodemError::NoClock => "no clock (ShellConfig::now_ms)",
+        }
+    }
+}
+
+/// State of the transfer after a byte or a poll.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Receiving,
+    /// The transfer ended, with the number of bytes passed to the sink.
+    Done(Result<u32, XmodemError>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Sending start requests until a block comes.
+    Start,
+    /// Between blocks.
+    Idle,
+    /// Reading a block.
+    Block,
+    Done(Result<u32, XmodemError>),
+}
+
+/// Receiving side of an XMODEM or YMODEM transfer.
+///
+/// `feed` takes the bytes read from the sender, `poll` the passing time; both write the
+/// answers (`ACK`, `NAK`, ...) with `reply` and return whether the transfer ended.
+pub struct Receiver {
+    protocol: Protocol,
+    state: State,
+    /// Block number, its complement, the data and the CRC of the block being read.
+    block: [u8; BLOCK_MAX + 4],
+    /// Data bytes of the block being read (128 or 1024).
+    size: usize,
+    pos: usize,
+    /// Number of the next data block; wraps after 255.
+    expected: u8,
+    /// A YMODEM header (block 0) comes next.
+    header_pending: bool,
+    /// The last block taken was the YMODEM header, so a block 0 sent again repeats it.
+    header_last: bool,
+    received: u32,
+    /// File size from the YMODEM header.
+    file_size: Option<u32>,
+    last_ms: u64,
+    /// Start requests sent.
+    tries: u8,
+    /// Bad or missing blocks in a row.
+    errors: u8,
+    /// `CAN` bytes in a row.
+    cancels: u8,
+    /// The first `EOT` of a YMODEM file was answered.
+    eot: bool,
+    /// The YMODEM file ended: the next header ends the batch.
+    batch_end: bool,
+}
+
+impl Receiver {
+    pub const fn new(protocol: Protocol) -> Self {
+        Self {
+            protocol,
+            state: State::Start,
+            block: [0; BLOCK_MAX + 4],
+            size: 0,
+            pos: 0,
+            expected: 1,
+            header_pending: matches!(protocol, Protocol::Ymodem),
+            header_last: false,
+            received: 0,
+            file_size: None,
+            last_ms: 0,
+            tries: 0,
+            errors: 0,
+            cancels: 0,
+            eot: false,
+            batch_end: false,
+        }
+    }
+
+    /// Bytes passed to the sink so far.
+    pub fn received(&self) -> u32 {
+        self.received
+    }
+
+    /// The block read last, as far as it came: its number, the complement, the data and
+    /// the CRC. Shows what the sender sent when a transfer fails (see
+    /// `input::renderer::hexdump`).
+    pub fn last_block(&self) -> &[u8] {
+        &self.block[..self.pos]
+    }
+
+    /// Sends the start request (at once, then every few seconds until a block comes)
+    /// and asks again for a block the sender is late with. Call it regularly.
+    pub fn poll(&mut self, now_ms: u64, reply: &mut impl FnMut(&[u8])) -> Status {
+        let elapsed = now_ms.saturating_sub(self.last_ms);
+        match self.state {
+            State::Start if self.tries == 0 || elapsed >= START_INTERVAL_MS => {
+                if self.tries == START_TRIES {
+                    // After a YMODEM file, a sender that skips the closing header
+                    self.state = State::Done(match self.batch_end {
+                        true => Ok(self.received),
+                        false => Err(XmodemError::NoSender),
+                    });
+                } else {
+                    self.tries += 1;
+                    self.last_ms = now_ms;
+                    reply(&[CRC_REQUEST]);
+                }
+            }
+            State::Block if elapsed >= BLOCK_TIMEOUT_MS => {
+                self.reject(XmodemError::Timeout, now_ms, reply)
+            }
+            State::Idle if elapsed >= IDLE_TIMEOUT_MS => {
+                self.reject(XmodemError::Timeout, now_ms, reply)
+            }
+            _ => {}
+        }
+        self.status()
+    }
+
+    /// Takes one byte from the sender; `sink` gets each new data block with its offset in
+    /// the file and returns `false` to cancel the transfer.
+    pub fn feed(
+        &mut self,
+        byte: u8,
+        now_ms: u64,
+        sink: &mut impl FnMut(u32, &[u8]) -> bool,
+        reply: &mut impl FnMut(&[u8]),
+    ) -> Status {
+        self.last_ms = now_ms;
+        match self.state {
+            State::Block => {
+                self.block[self.pos] = byte;
+                self.pos += 1;
+                if self.pos == self.size + 4 {
+                    self.end_block(now_ms, sink, reply);
+                }
+            }
+            State::Start | State::Idle => {
+                if byte != CAN {
+                    self.cancels = 0;
+                }
+                match byte {
+                    SOH | STX => {
+                        self.size = if byte == SOH { 128 } else { BLOCK_MAX };
+                        self.pos = 0;
+                        self.state = State::Block;
+                    }
+                    EOT => self.end_file(reply),
+                    CAN => {
+                        self.cancels += 1;
+                        if self.cancels == 2 {
+                            self.state = State::Done(Err(XmodemError::Cancelled));
+                        }
+                    }
+                    // Line noise, or the sender's terminal echoing the requests
+                    _ => {}
+                }
+            }
+            State::Done(_) => {}
+        }
+        self.status()
+    }
+
+    fn status(&self) -> Status {
+        match self.state {
+            State::Done(result) => Status::Done(result),
+            _ => Status::Receiving,
+        }
+    }
+
+    fn end_block(
+        &mut self,
+        now_ms: u64,
+        sink: &mut impl FnMut(u32, &[u8]) -> bool,
+        reply: &mut impl FnMut(&[u8]),
+    ) {
+        self.state = State::Idle;
+        let number = self.block[0];
+        let data = &self.block[2..2 + self.size];
+        let crc = u16::from_be_bytes([self.block[self.size + 2], self.block[self.size + 3]]);
+        if self.block[1] != !number || crc16(data) != crc {
+            self.reject(XmodemError::TooManyErrors, now_ms, reply);
+            return;
+        }
+        self.errors = 0;
+
+        if self.header_pending {
+            if number == 0 {
+                self.header(reply);
+            } else {
+                self.cancel(XmodemError::Sequence, reply);
+            }
+            return;
+        }
+        if number == self.expected.wrapping_sub(1) {
+            // Sent again after a lost ACK
+            match self.header_last {
+                true => reply(&[ACK, CRC_REQUEST]),
+                false => reply(&[ACK]),
+            }
+            return;
+        }
+        if number != self.expected {
+            self.cancel(XmodemError::Sequence, reply);
+            return;
+        }
+
+        let left = self.file_size.map_or(self.size, |size| {
+            size.saturating_sub(self.received) as usize
+        });
+        let len = self.size.min(left);
+        if len > 0 && !sink(self.received, &self.block[2..2 + len]) {
+            self.cancel(XmodemError::Refused, reply);
+            return;
+        }
+        self.received += len as u32;
+        self.expected = self.expected.wrapping_add(1);
+        self.header_last = false;
+        reply(&[ACK]);
+    }
+
+    /// YMODEM block 0: `name NUL size ...` of the file, or an empty name ending the batch.
+    fn header(&mut self, reply: &mut impl FnMut(&[u8])) {
+        let data = &self.block[2..2 + self.size];
+        let name_len = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+        if name_len == 0 {
+            reply(&[ACK]);
+            self.state = State::Done(Ok(self.received));
+            return;
+        }
+        if self.batch_end {
+            self.cancel(XmodemError::TooManyFiles, reply);
+            return;
+        }
+        let digits = data.get(name_len + 1..).unwrap_or(&[]);
+        let digits = &digits[..digits.iter().take_while(|b| b.is_ascii_digit()).count()];
+        self.file_size = (!digits.is_empty()).then(|| {
+            digits.iter().fold(0u32, |size, &b| {
+                size.saturating_mul(10).saturating_add((b - b'0') as u32)
+            })
+        });
+        self.expected = 1;
+        self.header_pending = false;
+        self.header_last = true;
+        reply(&[ACK, CRC_REQUEST]);
+    }
+
+    fn end_file(&mut self, reply: &mut impl FnMut(&[u8])) {
+        if self.protocol == Protocol::Xmodem {
+            reply(&[ACK]);
+            self.state = State::Done(Ok(self.received));
+            return;
+        }
+        if !self.eot {
+            // A YMODEM sender repeats the EOT after a NAK
+            self.eot = true;
+            reply(&[NAK]);
+            return;
+        }
+        reply(&[ACK]);
+        // Ask for the next header: the empty one closing the batch
+        self.batch_end = true;
+        self.header_pending = true;
+        self.tries = 0;
+        self.state = State::Start;
+    }
+
+    /// Asks for the block again; too many errors in a row cancel the transfer with `error`.
+    fn reject(&mut self, error: XmodemError, now_ms: u64, reply: &mut impl FnMut(&[u8])) {
+        self.errors += 1;
+        if self.errors > MAX_ERRORS {
+            self.cancel(error, reply);
+            return;
+        }
+        self.state = State::Idle;
+        self.last_ms = now_ms;
+        reply(&[NAK]);
+    }
+
+    fn cancel(&mut self, error: XmodemError, reply: &mut impl FnMut(&[u8])) {
+        reply(&[CAN, CAN, CAN]);
+        self.state = State::Done(Err(error));
+    }
+}
+
+// ============================================================================
+// Transfers run by the shell
+// ============================================================================
+
+/// Receives the data blocks in order: their offset in the file and their data (the
+/// padding of the last XMODEM block included); returns `false` to cancel the transfer.
+#[cfg(feature = "xmodem")]
+pub type BlockSink = fn(offset: u32, data: &[u8]) -> bool;
+
+/// Bytes of the bad block shown when a transfer fails on it.
+#[cfg(feature = "xmodem")]
+const BAD_BLOCK_DUMP_LEN: usize = 2 * HEXDUMP_ROW_LEN;
+
+#[cfg(feature = "xmodem")]
+static STARTED: Mutex<Cell<Option<(Protocol, BlockSink)>>> = Mutex::new(Cell::new(None));
+
+/// Starts a transfer once the running command returns: the shell sends the start
+/// requests and passes the blocks to `sink`.
+#[cfg(feature = "xmodem")]
+pub fn start(protocol: Protocol, sink: BlockSink) -> Result<(), XmodemError> {
+    critical_section::with(|cs| {
+        let started = STARTED.borrow(cs);
+        if started.get().is_some() {
+            return Err(XmodemError::AlreadyStarted);
+        }
+        started.set(Some((protocol, sink)));
+        Ok(())
+    })
+}
+
+/// A transfer run by the shell in place of the line editor.
+#[cfg(feature = "xmodem")]
+pub(crate) struct Transfer {
+    receiver: Receiver,
+    sink: BlockSink,
+    now_ms: fn() -> u64,
+    /// Deferred logging was on before the transfer.
+    deferred: bool,
+}
+
+#[cfg(feature = "xmodem")]
+impl Transfer {
+    /// The transfer started by the last command, if any, timed with `now_ms`; log lines
+    /// are held from now on. Without a clock the transfer fails with `NoClock` at once,
+    /// reported above the prompt: the timeouts would never expire.
+    pub(crate) fn take_started(now_ms: Option<fn() -> u64>) -> Option<Self> {
+        let (protocol, sink) = critical_section::with(|cs| STARTED.borrow(cs).take())?;
+        let Some(now_ms) = now_ms else {
+            let mut msg = heapless::String::<NOTICE_MAX_LEN>::new();
+            let _ = write!(msg, "xmodem: {}", XmodemError::NoClock.message());
+            notice::println_above(&msg);
+            return None;
+        };
+        let deferred = logger::is_deferred_logging();
+        logger::set_deferred_logging(true);
+        Some(Self {
+            receiver: Receiver::new(protocol),
+            sink,
+            now_ms,
+            deferred,
+        })
+    }
+
+    /// Advances the transfer with `byte`, if one was read, answering through `writer`.
+    /// Returns `false` once the transfer ended: the held log lines are written and the
+    /// result is queued above the prompt (see `notice`).
+    pub(crate) fn step<W: UnifiedWriter>(&mut self, byte: Option<u8>, writer: &mut W) -> bool {
+        let now_ms = (self.now_ms)();
+        let mut reply = |bytes: &[u8]| {
+            writer.write_bytes(bytes);
+            writer.flush();
+        };
+        let mut status = self.receiver.poll(now_ms, &mut reply);
+        if let Some(byte) = byte {
+            status = self.receiver.feed(byte, now_ms, &mut self.sink, &mut reply);
+        }
+        let Status::Done(result) = status else {
+            return true;
+        };
+
+        // Remove the start requests from the line
+        writer.write_str("\r\x1B[K");
+        if let Err(XmodemError::Sequence | XmodemError::TooManyErrors) = result {
+            // The start of the block that ended the transfer
+            let block = self.receiver.last_block();
+            hexdump(writer, 0, &block[..block.len().min(BAD_BLOCK_DUMP_LEN)]);
+        }
+        logger::set_deferred_logging(self.deferred);
+        let mut msg = heapless::String::<NOTICE_MAX_LEN>::new();
+        let _ = match result {
+            Ok(received) => write!(msg, "xmodem: {} bytes received", received),
+            Err(e) => write!(msg, "xmodem: {}", e.message()),
+        };
+        notice::println_above(&msg);
+        false
+    }
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    /// Block `number` of `size` bytes holding `data`, padded with `0x1A`.
+    fn block(number: u8, size: usize, data: &[u8]) -> Vec<u8> {
+        let mut payload = data.to_vec();
+        payload.resize(size, 0x1A);
+        let mut bytes = std::vec![if size == 128 { SOH } else { STX }, number, !number];
+        bytes.extend_from_slice(&payload);
+        bytes.extend_from_slice(&crc16(&payload).to_be_bytes());
+        bytes
+    }
+
+    /// Feeds `bytes`; returns the replies, the last status and appends the data to `file`.
+    fn feed(receiver: &mut Receiver, bytes: &[u8], file: &mut Vec<u8>) -> (Vec<u8>, Status) {
+        let mut replies = Vec::new();
+        let mut status = Status::Receiving;
+        for &byte in bytes {
+            status = receiver.feed(
+                byte,
+                0,
+                &mut |offset, data| {
+                    assert_eq!(offset as usize, file.len());
+                    file.extend_from_slice(data);
+                    true
+                },
+                &mut |reply| replies.extend_from_slice(reply),
+            );
+        }
+        (replies, status)
+    }
+
+    #[test]
+    fn test_crc16() {
+        assert_eq!(crc16(b"123456789"), 0x31C3);
+    }
+
+    #[test]
+    fn test_xmodem_blocks() {
+        let mut receiver = Receiver::new(Protocol::Xmodem);
+        let mut replies = Vec::new();
+        receiver.poll(0, &mut |reply| replies.extend_from_slice(reply));
+        receiver.poll(1000, &mut |reply| replies.extend_from_slice(reply));
+        receiver.poll(3000, &mut |reply| replies.extend_from_slice(reply));
+        assert_eq!(replies, b"CC");
+
+        let mut file = Vec::new();
+        let (replies, _) = feed(&mut receiver, &block(1, 128, b"first"), &mut file);
+        assert_eq!(replies, [ACK]);
+
+        // Bad CRC, then the block again; a repeated block is acknowledged, not stored
+        let mut bad = block(2, 1024, b"second");
+        bad[10] ^= 0xFF;
+        let (replies, _) = feed(&mut receiver, &bad, &mut file);
+        assert_eq!(replies, [NAK]);
+        let (replies, _) = feed(&mut receiver, &block(2, 1024, b"second"), &mut file);
+        assert_eq!(replies, [ACK]);
+        let (replies, _) = feed(&mut receiver, &block(2, 1024, b"second"), &mut file);
+        assert_eq!(replies, [ACK]);
+
+        let (replies, status) = feed(&mut receiver, &[EOT], &mut file);
+        assert_eq!(replies, [ACK]);
+        assert_eq!(status, Status::Done(Ok(128 + 1024)));
+        assert_eq!(file.len(), 128 + 1024);
+        assert!(file.starts_with(b"first\x1A"));
+        assert_eq!(&file[128..134], b"second");
+    }
+
+    #[test]
+    fn test_ymodem_file() {
+        let mut receiver = Receiver::new(Protocol::Ymodem);
+        let mut file = Vec::new();
+        let (replies, _) = feed(
+            &mut receiver,
+            &block(0, 128, b"app.bin\x00130 0"),
+            &mut file,
+        );
+        assert_eq!(replies, [ACK, CRC_REQUEST]);
+        feed(&mut receiver, &block(1, 128, &[0x55; 128]), &mut file);
+        feed(&mut receiver, &block(2, 128, b"ab"), &mut file);
+
+        let (replies, _) = feed(&mut receiver, &[EOT, EOT], &mut file);
+        assert_eq!(replies, [NAK, ACK]);
+        let mut requests = Vec::new();
+        receiver.poll(0, &mut |reply| requests.extend_from_slice(reply));
+        assert_eq!(requests, [CRC_REQUEST]);
+        let (replies, status) = feed(&mut receiver, &block(0, 128, &[0; 128]), &mut file);
+        assert_eq!(replies, [ACK]);
+        // The padding past the size of the header is cut
+        assert_eq!(status, Status::Done(Ok(130)));
+        assert_eq!(&file[126..], [0x55, 0x55, b'a', b'b']);
+    }
+
+    #[test]
+    fn test_ymodem_block_numbers_wrap() {
+        const BLOCKS: usize = 300;
+        let size = BLOCKS * 128 - 5;
+        let mut receiver = Receiver::new(Protocol::Ymodem);
+        let mut file = Vec::new();
+        let header = std::format!("big.bin\x00{}", size);
+        feed(&mut receiver, &block(0, 128, header.as_bytes()), &mut file);
+
+        let data: Vec<u8> = (0..BLOCKS * 128).map(|i| (i * 7 + i / 128) as u8).collect();
+        for (index, chunk) in data.chunks(128).enumerate() {
+            let number = (index + 1) as u8;
+            let (replies, status) = feed(&mut receiver, &block(number, 128, chunk), &mut file);
+            assert_eq!(replies, [ACK], "block {}", index + 1);
+            assert_eq!(status, Status::Receiving);
+            if index == 255 {
+                // Block 256 (number 0) sent again after a lost ACK is no header
+                let (replies, _) = feed(&mut receiver, &block(number, 128, chunk), &mut file);
+                assert_eq!(replies, [ACK]);
+            }
+        }
+        assert_eq!(receiver.file_size, Some(size as u32));
+        assert_eq!(receiver.received(), size as u32);
+        assert_eq!(file, data[..size]);
+    }
+
+    #[test]
+    fn test_failures() {
+        let mut file = Vec::new();
+
+        let mut receiver = Receiver::new(Protocol::Xmodem);
+        let (_, status) = feed(&mut receiver, &[CAN, CAN], &mut file);
+        assert_eq!(status, Status::Done(Err(XmodemError::Cancelled)));
+
+        let mut receiver = Receiver::new(Protocol::Xmodem);
+        let (replies, status) = feed(&mut receiver, &block(3, 128, b""), &mut file);
+        assert_eq!(replies, [CAN, CAN, CAN]);
+        assert_eq!(status, Status::Done(Err(XmodemError::Sequence)));
+
+        let mut receiver = Receiver::new(Protocol::Xmodem);
+        let mut status = Status::Receiving;
+        for now in (0..=START_TRIES as u64).map(|i| i * START_INTERVAL_MS) {
+            status = receiver.poll(now, &mut |_| {});
+        }
+        assert_eq!(status, Status::Done(Err(XmodemError::NoSender)));
+
+        // A block cut short is asked again, until the sender is given up
+        let mut receiver = Receiver::new(Protocol::Xmodem);
+        feed(&mut receiver, &[SOH, 1, !1], &mut file);
+        let mut naks = 0;
+        let mut now = 0;
+        while receiver.poll(now, &mut |reply| naks += reply.ends_with(&[NAK]) as u32)
+            == Status::Receiving
+        {
+            now += BLOCK_TIMEOUT_MS;
+        }
+        assert_eq!(naks, MAX_ERRORS as u32);
+        assert_eq!(
+            receiver.poll(now, &mut |_| {}),
+            Status::Done(Err(XmodemError::Timeout))
+        );
+
+        let mut receiver = Receiver::new(Protocol::Xmodem);
+        let mut replies = Vec::new();
+        let status = block(1, 128, b"")
+            .into_iter()
+            .map(|byte| {
+                receiver.feed(byte, 0, &mut |_, _| false, &mut |reply| {
+                    replies.extend_from_slice(reply)
+                })
+            })
+            .last();
+        assert_eq!(status, Some(Status::Done(Err(XmodemError::Refused))));
+        assert_eq!(replies, [CAN, CAN, CAN]);
+    }
+
+    #[cfg(feature = "xmodem")]
+    #[test]
+    fn test_transfer_reports_above_prompt() {
+        let _guard = logger::TEST_LOCK.lock().unwrap();
+        let _notices = notice::TEST_LOCK.lock().unwrap();
+        logger::tests::init();
+        while notice::take().is_some() {}
+
+        fn sink(_: u32, _: &[u8]) -> bool {
+            true
+        }
+        assert!(Transfer::take_started(Some(|| 0)).is_none());
+        start(Protocol::Xmodem, sink).unwrap();
+        assert_eq!(
+            start(Protocol::Xmodem, sink),
+            Err(XmodemError::AlreadyStarted)
+        );
+        let mut transfer = Transfer::take_started(Some(|| 0)).unwrap();
+        assert!(logger::is_deferred_logging());
+
+        let mut out: heapless::String<64> = heapless::String::new();
+        assert!(transfer.step(None, &mut out));
+        for byte in block(1, 128, b"x") {
+            assert!(transfer.step(Some(byte), &mut out));
+        }
+        assert!(!transfer.step(Some(EOT), &mut out));
+        assert_eq!(out.as_bytes(), b"C\x06\x06\r\x1B[K");
+        assert!(!logger::is_deferred_logging());
+        assert_eq!(notice::take().unwrap(), "xmodem: 128 bytes received");
+    }
+
+    #[cfg(feature = "xmodem")]
+    #[test]
+    fn test_transfer_dumps_bad_block() {
+        let _guard = logger::TEST_LOCK.lock().unwrap();
+        let _notices = notice::TEST_LOCK.lock().unwrap();
+        logger::tests::init();
+        while notice::take().is_some() {}
+
+        fn sink(_: u32, _: &[u8]) -> bool {
+            true
+        }
+        start(Protocol::Xmodem, sink).unwrap();
+        let mut transfer = Transfer::take_started(Some(|| 0)).unwrap();
+        let mut out: heapless::String<512> = heapless::String::new();
+        let bytes = block(2, 128, b"hello");
+        let (last, head) = bytes.split_last().unwrap();
+        for &byte in head {
+            assert!(transfer.step(Some(byte), &mut out));
+        }
+        assert!(!transfer.step(Some(*last), &mut out));
+        assert!(out
+            .contains(" 02 FD 68 65 6C 6C 6F 1A 1A 1A 1A 1A 1A 1A 1A 1A  |..hello.........|\r\n"));
+        assert_eq!(out.matches("\r\n").count(), 2);
+        assert_eq!(notice::take().unwrap(), "xmodem: block out of sequence");
+    }
+
+    #[cfg(feature = "xmodem")]
+    #[test]
+    fn test_transfer_needs_clock() {
+        let _guard = logger::TEST_LOCK.lock().unwrap();
+        let _notices = notice::TEST_LOCK.lock().unwrap();
+        logger::tests::init();
+        while notice::take().is_some() {}
+
+        fn sink(_: u32, _: &[u8]) -> bool {
+            true
+        }
+        start(Protocol::Xmodem, sink).unwrap();
+        assert!(Transfer::take_started(None).is_none());
+        assert!(!logger::is_deferred_logging());
+        assert_eq!(
+            notice::take().unwrap(),
+            "xmodem: no clock (ShellConfig::now_ms)"
+        );
+        // Taken: the next command may start one
+        assert!(Transfer::take_started(Some(|| 0)).is_none());
+    }
+}
@@ -0,0 +1,184 @@
+//! # ShellArgs Derive Macro
+//!
+//! `#[derive(ShellArgs)]` implements `ushell2::args::ShellArgs` for a struct whose fields
+//! are descriptor types, so a command handler can take one typed struct:
+//!
+//! ```rust
+//! #[derive(ShellArgs)]
+//! pub struct LedArgs<'a> {
+//!     pin: u8,
+//!     mode: &'a str,
+//!     on: bool,
+//! }
+//! ```
+//!
+//! generates `DESCRIPTOR = "Bst"`, `ARITY = 3` and a `from_tokens` parsing the fields in
+//! declaration order. In the commands file the struct replaces the descriptor:
+//! `S{crate::led::LedArgs} : crate::led::set`.
+//!
+//! Supported field types: `u8`..`u128`, `i8`..`i128`, `usize`, `isize`, `f32`, `f64`,
+//! `bool`, `char` and `&str`. Named and tuple structs may have at most one lifetime
+//! parameter (for `&str` fields) and no type parameters.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{Data, DeriveInput, Fields, GenericParam, Result, Type, parse_macro_input};
+
+/// Descriptor character of a field type, as used in the commands file.
+fn descriptor_char(ty: &Type) -> Option<char> {
+    match ty {
+        Type::Reference(reference) if reference.mutability.is_none() => match &*reference.elem {
+            Type::Path(path) if path.path.is_ident("str") => Some('s'),
+            _ => None,
+        },
+        Type::Path(path) if path.qself.is_none() => {
+            let ident = path.path.get_ident()?.to_string();
+            Some(match ident.as_str() {
+                "u8" => 'B',
+                "u16" => 'W',
+                "u32" => 'D',
+                "u64" => 'Q',
+                "u128" => 'X',
+                "i8" => 'b',
+                "i16" => 'w',
+                "i32" => 'd',
+                "i64" => 'q',
+                "i128" => 'x',
+                "usize" => 'Z',
+                "isize" => 'z',
+                "f32" => 'f',
+                "f64" => 'F',
+                "bool" => 't',
+                "char" => 'c',
+                _ => return None,
+            })
+        }
+        _ => None,
+    }
+}
+
+fn derive_shell_args(input: DeriveInput) -> Result<TokenStream2> {
+    let name = &input.ident;
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "ShellArgs can only be derived for structs",
+        ));
+    };
+
+    // At most one lifetime, which becomes the lifetime of the tokens.
+    let mut lifetime = None;
+    for param in &input.generics.params {
+        match param {
+            GenericParam::Lifetime(def) if lifetime.is_none() => {
+                lifetime = Some(def.lifetime.clone())
+            }
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    param,
+                    "ShellArgs supports a single lifetime parameter only",
+                ));
+            }
+        }
+    }
+
+    let mut descriptor = String::new();
+    let mut inits = Vec::new();
+    for (index, field) in data.fields.iter().enumerate() {
+        let ch = descriptor_char(&field.ty).ok_or_else(|| {
+            syn::Error::new_spanned(
+                &field.ty,
+                "unsupported ShellArgs field type, expected an integer, float, bool, char or &str",
+            )
+        })?;
+        descriptor.push(ch);
+        let index = index as u8;
+        let value = quote! { ::ushell2::args::field(tokens, #index)? };
+        inits.push(match &field.ident {
+            Some(ident) => quote! { #ident: #value },
+            None => value,
+        });
+    }
+    let arity = descriptor.len();
+    if arity > u8::MAX as usize {
+        return Err(syn::Error::new_spanned(name, "ShellArgs: too many fields"));
+    }
+    if descriptor.is_empty() {
+        descriptor.push('v');
+    }
+
+    let construct = match &data.fields {
+        Fields::Named(_) => quote! { Self { #( #inits ),* } },
+        Fields::Unnamed(_) => quote! { Self( #( #inits ),* ) },
+        Fields::Unit => quote! { Self },
+    };
+    let (impl_lifetime, self_ty) = match &lifetime {
+        Some(lt) => (quote! { #lt }, quote! { #name<#lt> }),
+        None => (quote! { '__tokens }, quote! { #name }),
+    };
+
+    Ok(quote! {
+        impl<#impl_lifetime> ::ushell2::args::ShellArgs<#impl_lifetime> for #self_ty {
+            const DESCRIPTOR: &'static str = #descriptor;
+            const ARITY: usize = #arity;
+
+            fn from_tokens(tokens: &[&#impl_lifetime str]) -> ::core::result::Result<Self, ::ushell2::args::ArgError> {
+                if tokens.len() != #arity {
+                    return ::core::result::Result::Err(::ushell2::args::ArgError {
+                        index: #arity as u8,
+                        kind: ::ushell2::args::ArgErrorKind::WrongArity,
+                    });
+                }
+                ::core::result::Result::Ok(#construct)
+            }
+        }
+    })
+}
+
+pub fn generate_shell_args(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    derive_shell_args(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn derive(input: TokenStream2) -> Result<String> {
+        derive_shell_args(syn::parse2(input).unwrap()).map(|code| code.to_string())
+    }
+
+    #[test]
+    fn test_descriptor_from_fields() {
+        let code = derive(quote! {
+            struct LedArgs<'a> { pin: u8, mode: &'a str, on: bool, level: f32 }
+        })
+        .unwrap();
+        assert!(code.contains("const DESCRIPTOR : & 'static str = \"Bstf\""));
+        assert!(code.contains("const ARITY : usize = 4usize"));
+        assert!(code.contains("ShellArgs < 'a > for LedArgs < 'a >"));
+        assert!(code.contains("mode : :: ushell2 :: args :: field (tokens , 1u8) ?"));
+
+        let code = derive(quote! { struct Pair(i16, char); }).unwrap();
+        assert!(code.contains("\"wc\""));
+        assert!(code.contains("Self (:: ushell2 :: args :: field (tokens , 0u8) ?"));
+
+        let code = derive(quote! { struct Nothing; }).unwrap();
+        assert!(code.contains("\"v\""));
+    }
+
+    #[test]
+    fn test_unsupported_inputs() {
+        let err = derive(quote! { struct Bad { data: Vec<u8> } }).unwrap_err();
+        assert!(err.to_string().contains("unsupported ShellArgs field type"));
+        assert!(derive(quote! { struct Bad<T> { value: T } }).is_err());
+        assert!(derive(quote! { enum Bad { A } }).is_err());
+    }
+}
@@ -25,36 +25,42 @@
 //! +------+-------+   +------+------+   +------+------+   +------+------+   +------+------+
 //! | Z    | usize |   | F    | f64  |   | c    | char |   | t    | bool |   | v    | void |
 //! +------+-------+   +------+------+   +------+------+   +------+------+   +------+------+
-//! | z    | isize |   | f    | f32  |   | s    | &str |   | h    | &[u8]|
-//!+------+-------+   +------+------+   +------+------+   +------+------+
+//! | z    | isize |   | f    | f32  |   | s    | &str |   | h    | &[u8]|   | r    | &str |
+//! +------+-------+   +------+------+   +------+------+   +------+------+   +------+------+
 //!
 //! Examples:
 //! - "DdFsb" => arguments: u32, i32, f64, &str, i8
 //! - "t"     => argument: bool
 //! - "v"     => argument: void
+//! - "Dr"    => arguments: u32, rest of the line as &str (last position only, not split)
+//! - "S{path::Args}" => argument: one `path::Args` struct deriving `ShellArgs`, parsed from its fields
+//! - "B->W"  => argument: u8, returns u16 (printed by the shell after success)
+//! - "D[D=100]" => arguments: u32, u32 which is 100 when left out (optional arguments come last)
+//! - "D:addr t:verbose" => arguments: u32 also given as `--addr=5`, bool flag `--verbose`
 //!
 //! ## Macro Input Format
 //! - DSL: `generate_commands_dispatcher!(mod m; \"dFs: path::to::f1 path::to::f2, t: path::to::f3\");`
 //!
 //! * Tokenization splits a command line into tokens, respecting **double quotes** for `&str`.
-//! * `dispatch(line, error_buffer)` parses the function name + arguments, checks **arity**, parses into a stack
-//!   `CallCtx`, and invokes the registered function. On error, the error message is written to the provided buffer.
+//! * `dispatch(line, level, error_buffer)` parses the function name + arguments, checks the **privilege level**
+//!   and the **arity**, parses into a stack `CallCtx`, and invokes the registered function. On error, the error
+//!   message is written to the provided buffer; on success, the formatted return value of a command with a
+//!   `->` return type (empty otherwise).
 //! * No heap allocations are performed; buffers are compile-time sized from maximums inferred
 //!   across all descriptors.
 //! ## no_std
 //! - Uses `core` only; suitable for embedded/stack-only use.
 //!
 //! `DispatchError` reports: `Empty`, `UnknownFunction`, `WrongArity` and per-type parsing errors:
-//! `BadBool`, `BadChar`, `BadUnsigned`, `BadSigned`, `BadFloat`.
+//! `BadBool`, `BadChar`, `BadUnsigned`, `BadSigned`, `BadFloat`, and `OutOfRange` for values
+//! outside a range given in the descriptor (e.g. `D(0..=100)`), `PermissionDenied` for a command
+//! above the caller's level (e.g. `DD@admin`), and `BadName` for an unknown `--name` argument.
 //!
 use proc_macro::TokenStream;
 use proc_macro2::{Span, TokenStream as TokenStream2};
 use quote::{format_ident, quote};
 use syn::{Ident, LitStr, Result, Token, parse::Parse, parse_macro_input};
 
-/// A std-like alias used locally during macro input parsing.
-type StdResult<T, E> = std::result::Result<T, E>;
-
 /// Per-descriptor maximum counts of each primitive (used to size `CallCtx`).
 #[derive(Default, Clone, Copy)]
 struct HostCounts {
@@ -124,6 +130,11 @@ struct CommandMacroInput {
     body: LitStr,                   // Macro input body as string
     hexstr_size: Option<syn::Expr>, // Optional size for hexstr buffers
     error_buffer_size: Option<syn::Expr>, // Optional size for error buffers
+    compact_help: bool,             // Pack descriptors and help text into one static
+    compact_float: bool,            // Use the small fixed-point float parser instead of `FromStr`
+    emit_tests: bool,               // Generate a `#[cfg(test)]` module checking the command set
+    c_export: Option<LitStr>,       // Symbol of an `extern "C"` wrapper around `dispatch`
+    report_sizes: bool,             // Generate the `SIZE_REPORT` footprint estimates
 }
 
 /// Implementation for CommandMacroInput structure
@@ -134,52 +145,585 @@ impl Parse for CommandMacroInput {
         let mod_ident: Ident = input.parse()?;
         input.parse::<Token![;]>()?;
 
-        // Optionally parse hexstr_size = <expr>;
-        let hexstr_size = if input.peek(syn::Ident) && input.peek2(Token![=]) {
+        // Optional `key = <value>;` options, in any order
+        let mut hexstr_size = None;
+        let mut error_buffer_size = None;
+        let mut compact_help = false;
+        let mut compact_float = false;
+        let mut emit_tests = false;
+        let mut c_export = None;
+        let mut report_sizes = false;
+        while input.peek(syn::Ident) && input.peek2(Token![=]) {
             let key: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
             if key == "hexstr_size" {
-                input.parse::<Token![=]>()?;
-                let expr: syn::Expr = input.parse()?;
-                input.parse::<Token![;]>()?;
-                Some(expr)
+                hexstr_size = Some(input.parse::<syn::Expr>()?);
+            } else if key == "error_buffer_size" {
+                error_buffer_size = Some(input.parse::<syn::Expr>()?);
+            } else if key == "compact_help" {
+                compact_help = input.parse::<syn::LitBool>()?.value;
+            } else if key == "compact_float" {
+                compact_float = input.parse::<syn::LitBool>()?.value;
+            } else if key == "emit_tests" {
+                emit_tests = input.parse::<syn::LitBool>()?.value;
+            } else if key == "c_export" {
+                c_export = Some(input.parse::<LitStr>()?);
+            } else if key == "report_sizes" {
+                report_sizes = input.parse::<syn::LitBool>()?.value;
             } else {
                 return Err(syn::Error::new(
                     key.span(),
-                    "Unexpected identifier, expected 'hexstr_size' or 'error_buffer_size'",
+                    "Unexpected identifier, expected 'hexstr_size', 'error_buffer_size', 'compact_help', 'compact_float', 'emit_tests', 'c_export' or 'report_sizes'",
                 ));
             }
-        } else {
-            None
-        };
-
-        // Optionally parse error_buffer_size = <expr>;
-        let error_buffer_size = if input.peek(syn::Ident) && input.peek2(Token![=]) {
-            let key: Ident = input.parse()?;
-            if key == "error_buffer_size" {
-                input.parse::<Token![=]>()?;
-                let expr: syn::Expr = input.parse()?;
-                input.parse::<Token![;]>()?;
-                Some(expr)
-            } else {
-                return Err(syn::Error::new(
-                    key.span(),
-                    "Unexpected identifier, expected 'error_buffer_size'",
-                ));
-            }
-        } else {
-            None
-        };
+            input.parse::<Token![;]>()?;
+        }
 
         let body: LitStr = input.parse()?;
         Ok(CommandMacroInput {
             mod_ident,
             hexstr_size,
             error_buffer_size,
+            compact_help,
+            compact_float,
+            emit_tests,
+            c_export,
+            report_sizes,
             body,
         })
     }
 }
 
+/// Split `s` on `sep`, ignoring separators inside double-quoted help text and inside
+/// braces (`gpio { ... }` blocks).
+fn split_unquoted(s: &str, sep: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut in_quotes = false;
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, ch) in s.char_indices() {
+        if ch == '"' {
+            in_quotes = !in_quotes;
+        } else if in_quotes {
+            continue;
+        } else if ch == '{' {
+            depth += 1;
+        } else if ch == '}' {
+            depth = depth.saturating_sub(1);
+        } else if ch == sep && depth == 0 {
+            parts.push(&s[start..i]);
+            start = i + ch.len_utf8();
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+/// One function of a DSL group with its optional help text and attributes.
+struct DslFunc {
+    path: syn::Path,
+    help: String,
+    /// `@nohistory`: lines invoking this command are not stored in the shell history.
+    nohistory: bool,
+    /// `@rollback=path`: function undoing the command, called with the same arguments.
+    rollback: Option<syn::Path>,
+    /// `@group=name`: the command is invoked as `name <function>` (two-level command).
+    group: Option<String>,
+    /// Privilege level needed to run the command, from the group's `desc@level`.
+    level: u8,
+}
+
+/// Named privilege levels (`DD@admin`), lowest first; other levels are given as numbers.
+const LEVEL_NAMES: [&str; 3] = ["user", "operator", "admin"];
+
+/// Parse the level after a descriptor: a name of `LEVEL_NAMES` or a `u8`.
+fn parse_level(s: &str) -> Option<u8> {
+    match LEVEL_NAMES.iter().position(|&name| name == s) {
+        Some(level) => Some(level as u8),
+        None => s.parse().ok(),
+    }
+}
+
+/// Parse a function list `path ["help"] [@attr ...] path ...` into `DslFunc`s.
+/// Fails on an invalid path, a dangling help string or attribute, an unknown or
+/// repeated attribute or an unterminated quote.
+fn parse_function_list(names: &str) -> std::result::Result<Vec<DslFunc>, String> {
+    let mut funcs: Vec<DslFunc> = Vec::new();
+    let mut rest = names.trim_start();
+    while !rest.is_empty() {
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').ok_or("unterminated help string")?;
+            match funcs.last_mut() {
+                Some(f) if f.help.is_empty() => f.help = quoted[..end].to_string(),
+                _ => return Err(format!("help \"{}\" without a function", &quoted[..end])),
+            }
+            rest = quoted[end + 1..].trim_start();
+        } else {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || c == '"')
+                .unwrap_or(rest.len());
+            let token = &rest[..end];
+            if let Some(attr) = token.strip_prefix('@') {
+                let f = funcs
+                    .last_mut()
+                    .ok_or_else(|| format!("'{}' without a function", token))?;
+                let invalid = || format!("invalid attribute '{}'", token);
+                match attr.split_once('=') {
+                    None if attr == "nohistory" => f.nohistory = true,
+                    Some(("rollback", path)) if f.rollback.is_none() => {
+                        f.rollback = Some(syn::parse_str::<syn::Path>(path).map_err(|_| invalid())?)
+                    }
+                    Some(("group", name)) if f.group.is_none() => {
+                        f.group = Some(
+                            syn::parse_str::<Ident>(name)
+                                .map_err(|_| invalid())?
+                                .to_string(),
+                        )
+                    }
+                    Some(("rollback" | "group", _)) => {
+                        return Err(format!("repeated attribute '{}'", token));
+                    }
+                    _ => return Err(format!("unknown attribute '{}'", token)),
+                }
+            } else {
+                funcs.push(DslFunc {
+                    path: syn::parse_str::<syn::Path>(token)
+                        .map_err(|_| format!("invalid function path '{}'", token))?,
+                    help: String::new(),
+                    nohistory: false,
+                    rollback: None,
+                    group: None,
+                    level: 0,
+                });
+            }
+            rest = rest[end..].trim_start();
+        }
+    }
+    Ok(funcs)
+}
+
+/// Offset of the `:` between the descriptor and the functions of a group: the last single
+/// `:` outside quotes, as argument names (`D:addr`) come before it and paths only hold `::`.
+fn separator(group: &str) -> Option<usize> {
+    let bytes = group.as_bytes();
+    let mut in_quotes = false;
+    let mut found = None;
+    for (i, &b) in bytes.iter().enumerate() {
+        if b == b'"' {
+            in_quotes = !in_quotes;
+        } else if b == b':'
+            && !in_quotes
+            && (i == 0 || bytes[i - 1] != b':')
+            && bytes.get(i + 1) != Some(&b':')
+        {
+            found = Some(i);
+        }
+    }
+    found
+}
+
+/// Split a `name { groups }` block of subcommands into its name and groups.
+fn subcommand_block(grp: &str) -> Option<(String, &str)> {
+    let (name, body) = grp.strip_suffix('}')?.split_once('{')?;
+    let name = syn::parse_str::<Ident>(name.trim()).ok()?;
+    Some((name.to_string(), body))
+}
+
+/// Collect (descriptor, functions) groups from the DSL body. Groups without a descriptor
+/// or functions are skipped; an invalid privilege level or function list (unknown
+/// attribute, bad path, ...) fails with a message naming the group.
+fn parse_dsl_groups(s: &str) -> std::result::Result<Vec<(String, Vec<DslFunc>)>, String> {
+    let mut acc = Vec::new();
+    for group in split_unquoted(s, ',') {
+        let grp = group.trim();
+        if grp.is_empty() {
+            continue;
+        }
+        // `gpio { D: set, v: list }`: the groups of the block are subcommands of `gpio`,
+        // like `@group=gpio` on each function (which, or a nested block, is invalid)
+        if let Some((name, body)) = subcommand_block(grp) {
+            for (desc, mut funcs) in parse_dsl_groups(body)? {
+                if funcs.iter().any(|f| f.group.is_some()) {
+                    return Err(format!(
+                        "'{}': @group or a nested block inside a block",
+                        name
+                    ));
+                }
+                for f in &mut funcs {
+                    f.group = Some(name.clone());
+                }
+                acc.push((desc, funcs));
+            }
+            continue;
+        }
+        // A `S{path}` descriptor contains `::`, split after its closing brace
+        let split_from = match grp.strip_prefix("S{") {
+            Some(rest) => match rest.find('}') {
+                Some(end) => end + 3,
+                None => continue,
+            },
+            None => 0,
+        };
+        let (desc, names) = match separator(&grp[split_from..]) {
+            Some(at) => (
+                grp[..split_from + at].trim(),
+                grp[split_from + at + 1..].trim(),
+            ),
+            None => continue,
+        };
+        // `DD@admin`: all functions of the group need that privilege level
+        let (desc, level) = match desc.rsplit_once('@') {
+            Some((d, level)) => match parse_level(level.trim()) {
+                Some(level) => (d.trim(), level),
+                None => {
+                    return Err(format!(
+                        "'{}': unknown privilege level '@{}'",
+                        grp,
+                        level.trim()
+                    ));
+                }
+            },
+            None => (desc, 0),
+        };
+        if desc.is_empty() || names.is_empty() {
+            continue;
+        }
+        let mut funcs = parse_function_list(names).map_err(|e| format!("'{}': {}", grp, e))?;
+        for f in &mut funcs {
+            f.level = level;
+        }
+        acc.push((desc.to_string(), funcs));
+    }
+    Ok(acc)
+}
+
+/// Type characters, range constraints, defaults and names of the arguments of a
+/// descriptor, one entry per argument (see `parse_descriptor`).
+struct ParsedDescriptor {
+    types: String,
+    ranges: Vec<Option<TokenStream2>>,
+    /// Default of each optional argument (`[T=value]`, or `false` for a named `t`).
+    defaults: Vec<Option<syn::Expr>>,
+    /// Name of each named argument (`D:addr`), given as `--addr=value`.
+    names: Vec<Option<String>>,
+}
+
+/// Split a descriptor such as `D(0..=100):pct s[D=5]` into its type characters, the
+/// optional range constraint attached to each of them, the default of each optional
+/// argument and the name of each named argument. The rest-of-line type `r` may only come
+/// last and cannot be combined with names; optional arguments `[T=value]` and flags (named
+/// `t` arguments, `false` when left out) may only be followed by other optional arguments.
+fn parse_descriptor(desc: &str) -> Result<ParsedDescriptor> {
+    let error =
+        |msg: String| syn::Error::new(Span::call_site(), format!("descriptor '{}': {}", desc, msg));
+    let mut parsed = ParsedDescriptor {
+        types: String::new(),
+        ranges: Vec::new(),
+        defaults: Vec::new(),
+        names: Vec::new(),
+    };
+    let mut chars = desc.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch.is_whitespace() {
+            continue;
+        }
+        if parsed.types.ends_with('r') {
+            return Err(error("'r' must be the last argument".into()));
+        }
+        if ch == '[' {
+            let text: String = chars.by_ref().take_while(|&c| c != ']').collect();
+            let (ty, range, default, name) = parse_optional(desc, &text)?;
+            parsed.types.push(ty);
+            parsed.ranges.push(range);
+            parsed.defaults.push(Some(default));
+            parsed.names.push(name);
+            continue;
+        }
+        parsed.types.push(ch);
+        parsed.ranges.push(if chars.peek() == Some(&'(') {
+            chars.next();
+            let text: String = chars.by_ref().take_while(|&c| c != ')').collect();
+            Some(parse_range(desc, ch, &text)?)
+        } else {
+            None
+        });
+        let name = if chars.next_if_eq(&':').is_some() {
+            let mut name = String::new();
+            while let Some(c) =
+                chars.next_if(|&c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'))
+            {
+                name.push(c);
+            }
+            if name.is_empty() || matches!(ch, 'v' | 'r') {
+                return Err(error(format!("invalid argument name '{}:{}'", ch, name)));
+            }
+            Some(name)
+        } else {
+            None
+        };
+        // A named bool is a flag: `--verbose` sets it, leaving it out clears it
+        let default = match (ch, &name) {
+            ('t', Some(_)) => Some(syn::parse_quote! { false }),
+            _ => None,
+        };
+        if default.is_none() && parsed.defaults.last().is_some_and(Option::is_some) {
+            return Err(error(format!(
+                "mandatory argument '{}' after an optional one",
+                ch
+            )));
+        }
+        parsed.defaults.push(default);
+        parsed.names.push(name);
+    }
+    if parsed.types.ends_with('r') && parsed.names.iter().any(Option::is_some) {
+        return Err(error("'r' cannot be combined with named arguments".into()));
+    }
+    let names: Vec<&String> = parsed.names.iter().flatten().collect();
+    if let Some(name) = names
+        .iter()
+        .enumerate()
+        .find_map(|(i, name)| names[..i].contains(name).then_some(name))
+    {
+        return Err(error(format!("argument name '{}' used twice", name)));
+    }
+    Ok(parsed)
+}
+
+/// Parse the range `text` of a type character `ch` of `desc`, e.g. `0..=100`.
+fn parse_range(desc: &str, ch: char, text: &str) -> Result<TokenStream2> {
+    if !matches!(
+        ch,
+        'B' | 'W' | 'D' | 'Q' | 'X' | 'b' | 'w' | 'd' | 'q' | 'x' | 'Z' | 'z' | 'f' | 'F' | 'c'
+    ) {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            format!("descriptor '{}': '{}' does not accept a range", desc, ch),
+        ));
+    }
+    let invalid = || {
+        syn::Error::new(
+            Span::call_site(),
+            format!("descriptor '{}': invalid range '({})'", desc, text),
+        )
+    };
+    let (lo, hi, inclusive) = if let Some((lo, hi)) = text.split_once("..=") {
+        (lo.trim(), hi.trim(), true)
+    } else if let Some((lo, hi)) = text.split_once("..") {
+        (lo.trim(), hi.trim(), false)
+    } else {
+        return Err(invalid());
+    };
+    let bound = |b: &str| -> Result<Option<syn::Expr>> {
+        if b.is_empty() {
+            Ok(None)
+        } else {
+            syn::parse_str::<syn::Expr>(b)
+                .map(Some)
+                .map_err(|_| invalid())
+        }
+    };
+    let (lo, hi) = (bound(lo)?, bound(hi)?);
+    if inclusive && hi.is_none() {
+        return Err(invalid());
+    }
+    Ok(if inclusive {
+        quote! { #lo..=#hi }
+    } else {
+        quote! { #lo..#hi }
+    })
+}
+
+/// One optional argument: its type character, range, default and name.
+type OptionalArg = (char, Option<TokenStream2>, syn::Expr, Option<String>);
+
+/// Parse the inside of an optional argument `[T(range):name=default]` of `desc`.
+fn parse_optional(desc: &str, text: &str) -> Result<OptionalArg> {
+    let error = |what: &str| {
+        syn::Error::new(
+            Span::call_site(),
+            format!("descriptor '{}': {} '[{}]'", desc, what, text),
+        )
+    };
+    // The `=` of the default comes after the range, which may hold `..=`
+    let after_range = text.rfind(')').map_or(0, |end| end + 1);
+    let Some((arg, default)) = text[after_range..]
+        .split_once('=')
+        .map(|(range_end, default)| (&text[..after_range + range_end.len()], default.trim()))
+    else {
+        return Err(error("optional argument without a default"));
+    };
+    let mut parsed = parse_descriptor(arg)?;
+    let mut chars = parsed.types.chars();
+    let ty = match (chars.next(), chars.next()) {
+        (Some(ty), None) => ty,
+        _ => return Err(error("invalid optional argument")),
+    };
+    if matches!(ty, 'v' | 'r' | 'h') {
+        return Err(error("argument type cannot be optional"));
+    }
+    let default = syn::parse_str::<syn::Expr>(default).map_err(|_| error("invalid default in"))?;
+    Ok((
+        ty,
+        parsed.ranges.pop().flatten(),
+        default,
+        parsed.names.pop().flatten(),
+    ))
+}
+
+/// Keywords offered by Tab for one argument: its index and the words of its `s[a|b]` list.
+type ArgKeywords = (usize, Vec<String>);
+
+/// Take the keyword lists out of a descriptor: `s[on|off]D` becomes `sD` and the
+/// keywords `on`, `off` for argument 0. Keywords only guide completion, the command still
+/// gets any string. Optional arguments `[D=5]` and `S{path}` descriptors are kept as is.
+fn split_keywords(desc: &str) -> Result<(String, Vec<ArgKeywords>)> {
+    if desc.starts_with("S{") {
+        return Ok((desc.to_string(), Vec::new()));
+    }
+    let mut plain = String::new();
+    let mut lists = Vec::new();
+    let mut args = 0;
+    let mut chars = desc.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            // `D:addr`: the name of the argument
+            ':' => {
+                plain.push(ch);
+                while let Some(c) =
+                    chars.next_if(|&c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'))
+                {
+                    plain.push(c);
+                }
+            }
+            '(' => {
+                plain.push(ch);
+                for c in chars.by_ref() {
+                    plain.push(c);
+                    if c == ')' {
+                        break;
+                    }
+                }
+            }
+            '[' => {
+                let text: String = chars.by_ref().take_while(|&c| c != ']').collect();
+                // `[D=5]`: an optional argument, left for `parse_descriptor`
+                if text.contains('=') {
+                    plain.push('[');
+                    plain.push_str(&text);
+                    plain.push(']');
+                    args += 1;
+                    continue;
+                }
+                let words: Vec<String> = text.split('|').map(|w| w.trim().to_string()).collect();
+                let valid = |w: &String| {
+                    !w.is_empty()
+                        && w.chars()
+                            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+                };
+                if !plain.ends_with('s') || !words.iter().all(valid) {
+                    return Err(syn::Error::new(
+                        Span::call_site(),
+                        format!("descriptor '{}': invalid keyword list '[{}]'", desc, text),
+                    ));
+                }
+                lists.push((args - 1, words));
+            }
+            c if c.is_whitespace() => plain.push(c),
+            _ => {
+                plain.push(ch);
+                if ch != 'v' {
+                    args += 1;
+                }
+            }
+        }
+    }
+    Ok((plain, lists))
+}
+
+/// `CallCtx` field and parser index variable holding the values of a type character.
+fn ctx_slot(ch: char) -> Option<(&'static str, &'static str)> {
+    Some(match ch {
+        'B' => ("u8s", "idx_b"),
+        'W' => ("u16s", "idx_w"),
+        'D' => ("u32s", "idx_d"),
+        'Q' => ("u64s", "idx_q"),
+        'X' => ("u128s", "idx_x"),
+        'b' => ("i8s", "idx_B"),
+        'w' => ("i16s", "idx_W"),
+        'd' => ("i32s", "idx_D"),
+        'q' => ("i64s", "idx_Q"),
+        'x' => ("i128s", "idx_X"),
+        'Z' => ("usizes", "idx_z"),
+        'z' => ("isizes", "idx_Z"),
+        'f' => ("f32s", "idx_f"),
+        'F' => ("f64s", "idx_F"),
+        't' => ("bools", "idx_t"),
+        'c' => ("chars", "idx_c"),
+        's' => ("strs", "idx_s"),
+        _ => return None,
+    })
+}
+
+/// Take the return type off a descriptor: `DD->W` becomes `DD` and `'W'`.
+fn split_return(desc: &str) -> Result<(String, Option<char>)> {
+    let Some((args, ret)) = desc.rsplit_once("->") else {
+        return Ok((desc.to_string(), None));
+    };
+    let mut chars = ret.trim().chars();
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) if return_type(ch).is_some() => {
+            Ok((args.trim_end().to_string(), Some(ch)))
+        }
+        _ => Err(syn::Error::new(
+            Span::call_site(),
+            format!(
+                "descriptor '{}': invalid return type '{}'",
+                desc,
+                ret.trim()
+            ),
+        )),
+    }
+}
+
+/// Fractional digits of a returned float with `compact_float = true;`.
+const COMPACT_FLOAT_DECIMALS: u8 = 6;
+
+/// Rust type and format string of a return type character. Unsigned integers are shown
+/// in hex, padded to their width (`0x00FF` for a `W`), the others with `Display`.
+fn return_type(ch: char) -> Option<(TokenStream2, &'static str)> {
+    Some(match ch {
+        'B' => (quote! { u8 }, "{:#04X}"),
+        'W' => (quote! { u16 }, "{:#06X}"),
+        'D' => (quote! { u32 }, "{:#010X}"),
+        'Q' => (quote! { u64 }, "{:#018X}"),
+        'X' => (quote! { u128 }, "{:#034X}"),
+        'Z' => (quote! { usize }, "{:#X}"),
+        'b' => (quote! { i8 }, "{}"),
+        'w' => (quote! { i16 }, "{}"),
+        'd' => (quote! { i32 }, "{}"),
+        'q' => (quote! { i64 }, "{}"),
+        'x' => (quote! { i128 }, "{}"),
+        'z' => (quote! { isize }, "{}"),
+        'f' => (quote! { f32 }, "{}"),
+        'F' => (quote! { f64 }, "{}"),
+        't' => (quote! { bool }, "{}"),
+        'c' => (quote! { char }, "{}"),
+        's' => (quote! { &'static str }, "{}"),
+        _ => return None,
+    })
+}
+
+/// The struct path of a `S{path}` descriptor, `None` for a descriptor string.
+fn struct_descriptor(desc: &str) -> Option<Result<syn::Path>> {
+    let inner = desc.strip_prefix("S{")?.strip_suffix('}')?;
+    Some(syn::parse_str::<syn::Path>(inner.trim()).map_err(|_| {
+        syn::Error::new(
+            Span::call_site(),
+            format!("descriptor '{}': expected a struct path", desc),
+        )
+    }))
+}
+
 /// Generate a no-heap dispatcher module from a DSL mapping.
 pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
     let CommandMacroInput {
@@ -187,43 +731,31 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
         body,
         hexstr_size,
         error_buffer_size,
+        compact_help,
+        compact_float,
+        emit_tests,
+        c_export,
+        report_sizes,
     } = parse_macro_input!(input as CommandMacroInput);
 
-    // Collect (descriptor, [paths]) pairs from either the DSL
-
-    let mut pairs: Vec<(String, Vec<syn::Path>)> = {
-        let s = body.value();
-        let mut acc = Vec::new();
-        for group in s.split(',') {
-            let grp = group.trim();
-            if grp.is_empty() {
-                continue;
-            }
-            let (desc, names) = match grp.split_once(':') {
-                Some((d, r)) => (d.trim(), r.trim()),
-                None => continue,
-            };
-            if desc.is_empty() || names.is_empty() {
-                continue;
-            }
-            let desc_str = desc.to_string();
-            let funcs: StdResult<Vec<_>, _> = names
-                .split_whitespace()
-                .map(syn::parse_str::<syn::Path>)
-                .collect();
-            let funcs = match funcs {
-                Ok(v) => v,
-                Err(_) => continue,
-            };
-            acc.push((desc_str, funcs));
-        }
-        acc
+    // Collect (descriptor, functions) groups from the DSL
+    let mut pairs = match parse_dsl_groups(&body.value()) {
+        Ok(pairs) => pairs,
+        Err(e) => return syn::Error::new(body.span(), e).to_compile_error().into(),
     };
 
     // Deduplicate descriptors, assign indices, gather entries; stable sort by function name.
     let mut unique_desc: Vec<String> = Vec::new();
     let mut entries: Vec<FnEntry> = Vec::new();
     for (desc, funcs) in pairs.drain(..) {
+        let (desc, ret) = match split_return(&desc) {
+            Ok(split) => split,
+            Err(e) => return e.to_compile_error().into(),
+        };
+        let (desc, keywords) = match split_keywords(&desc) {
+            Ok(split) => split,
+            Err(e) => return e.to_compile_error().into(),
+        };
         let idx = match unique_desc.iter().position(|x| x == &desc) {
             Some(i) => i,
             None => {
@@ -231,13 +763,22 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
                 unique_desc.len() - 1
             }
         };
-        for p in funcs {
-            let name_str = path_last_ident(&p).unwrap_or_else(|| "unknown".into());
+        for f in funcs {
+            let mut name_str = path_last_ident(&f.path).unwrap_or_else(|| "unknown".into());
+            if let Some(group) = &f.group {
+                name_str = format!("{} {}", group, name_str);
+            }
             entries.push(FnEntry {
                 name_str,
-                path: p,
+                path: f.path,
                 spec: desc.clone(),
                 spec_idx: idx,
+                help: f.help,
+                nohistory: f.nohistory,
+                rollback: f.rollback,
+                level: f.level,
+                keywords: keywords.clone(),
+                ret,
             });
         }
     }
@@ -245,6 +786,88 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
     // Stable sort entries by function name
     entries.sort_by(|a, b| a.name_str.cmp(&b.name_str));
 
+    // Two functions of the same name: the lookup would only ever find one of them.
+    if let Err(e) = check_duplicate_names(&entries, body.span()) {
+        return e.to_compile_error().into();
+    }
+
+    // `gpio` cannot be both a command and the group of `gpio set`: the command would win.
+    for e in &entries {
+        if let Some((group, _)) = e.name_str.split_once(' ')
+            && entries.iter().any(|other| other.name_str == group)
+        {
+            return syn::Error::new(
+                Span::call_site(),
+                format!(
+                    "command '{}' clashes with the group of '{}'",
+                    group, e.name_str
+                ),
+            )
+            .to_compile_error()
+            .into();
+        }
+    }
+
+    let nohistory_fn = nohistory_fn(&entries);
+    let complete_arg_fn = complete_arg_fn(&entries);
+
+    // Split descriptors into plain type characters, per-argument range constraints,
+    // defaults of optional arguments and argument names. A `S{path}` descriptor has no type
+    // characters: its struct parses its own arguments.
+    let mut spec_types: Vec<String> = Vec::new();
+    let mut spec_ranges: Vec<Vec<Option<TokenStream2>>> = Vec::new();
+    let mut spec_defaults: Vec<Vec<Option<syn::Expr>>> = Vec::new();
+    let mut spec_names: Vec<Vec<Option<String>>> = Vec::new();
+    let mut struct_paths: Vec<Option<syn::Path>> = Vec::new();
+    for desc in &unique_desc {
+        if let Some(path) = struct_descriptor(desc) {
+            match path {
+                Ok(path) => struct_paths.push(Some(path)),
+                Err(e) => return e.to_compile_error().into(),
+            }
+            spec_types.push(String::new());
+            spec_ranges.push(Vec::new());
+            spec_defaults.push(Vec::new());
+            spec_names.push(Vec::new());
+            continue;
+        }
+        struct_paths.push(None);
+        match parse_descriptor(desc) {
+            Ok(parsed) => {
+                spec_types.push(parsed.types);
+                spec_ranges.push(parsed.ranges);
+                spec_defaults.push(parsed.defaults);
+                spec_names.push(parsed.names);
+            }
+            Err(e) => return e.to_compile_error().into(),
+        }
+    }
+    let has_ranges = spec_ranges.iter().flatten().any(Option::is_some);
+    // Keyword lists are gone from the descriptors: a `[` starts an optional argument
+    let has_defaults = unique_desc.iter().any(|desc| desc.contains('['));
+    let has_names = spec_names.iter().flatten().any(Option::is_some);
+    let c_export_fn = match &c_export {
+        Some(symbol) => match syn::parse_str::<Ident>(&symbol.value()) {
+            Ok(ident) => c_export_fn(&ident),
+            Err(_) => {
+                return syn::Error::new(symbol.span(), "c_export: expected a C identifier")
+                    .to_compile_error()
+                    .into();
+            }
+        },
+        None => quote! {},
+    };
+    let size_report = if report_sizes {
+        size_report(&entries, &unique_desc)
+    } else {
+        quote! {}
+    };
+    let tests_mod = if emit_tests {
+        generated_tests(&spec_types, &spec_ranges, &spec_defaults)
+    } else {
+        quote! {}
+    };
+
     // Get the largest name for a function
     let function_name_max_len = entries.iter().map(|e| e.name_str.len()).max().unwrap_or(0) + 1;
 
@@ -278,7 +901,7 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
     let mut max_counts = HostCounts::default();
     let mut max_arity: usize = 0;
 
-    for desc in &unique_desc {
+    for desc in &spec_types {
         let mut c = HostCounts::default();
         for ch in desc.chars() {
             match ch {
@@ -308,6 +931,7 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
                 't' => c.bool_c += 1,   // bool
                 'c' => c.char_c += 1,   // char
                 's' => c.str_c += 1,    // &str
+                'r' => c.str_c += 1,    // rest of line &str
                 'h' => c.hexstr_c += 1, // hex &str
 
                 // void
@@ -345,12 +969,273 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
         max_counts = host_counts_max(max_counts, c);
     }
 
-    // Keep raw descriptor strings for diagnostics in the generated module.
-    let param_specs: Vec<LitStr> = unique_desc
+    // `S{path}` descriptors are shown as the descriptor of their struct.
+    let struct_specs: Vec<Option<TokenStream2>> = struct_paths
         .iter()
-        .map(|s| LitStr::new(s, Span::call_site()))
+        .map(|p| {
+            p.as_ref()
+                .map(|p| quote! { <#p as ushell2::args::ShellArgs<'static>>::DESCRIPTOR })
+        })
         .collect();
-    let param_specs_len = param_specs.len();
+
+    // Descriptor and help text tables. With `compact_help = true;` all descriptors and help
+    // strings are packed into one static and addressed through `u16` offset tables instead
+    // of one `&str` (pointer + length) per string.
+    let param_specs_len = unique_desc.len();
+    let text_tables = if compact_help {
+        let mut packed = String::new();
+        let mut spec_offsets: Vec<usize> = vec![0];
+        for (desc, struct_spec) in unique_desc.iter().zip(&struct_specs) {
+            if struct_spec.is_none() {
+                packed.push_str(desc);
+            }
+            spec_offsets.push(packed.len());
+        }
+        let mut help_offsets: Vec<usize> = vec![packed.len()];
+        for e in &entries {
+            packed.push_str(&e.help);
+            help_offsets.push(packed.len());
+        }
+        if packed.len() > u16::MAX as usize {
+            return syn::Error::new(
+                Span::call_site(),
+                "compact_help: descriptors and help text exceed 65535 bytes",
+            )
+            .to_compile_error()
+            .into();
+        }
+        let packed_lit = LitStr::new(&packed, Span::call_site());
+        let spec_offsets_u16 = spec_offsets.iter().map(|&o| o as u16);
+        let help_offsets_u16 = help_offsets.iter().map(|&o| o as u16);
+        let num_offsets = help_offsets.len();
+        let name_spec_pairs = entries.iter().map(|e| {
+            let name_lit = LitStr::new(&e.name_str, Span::call_site());
+            if let Some(spec) = &struct_specs[e.spec_idx] {
+                return quote! { (#name_lit, #spec) };
+            }
+            let start = spec_offsets[e.spec_idx];
+            let end = spec_offsets[e.spec_idx + 1];
+            quote! { (#name_lit, packed_str(#start, #end)) }
+        });
+        let struct_spec_arms = struct_specs
+            .iter()
+            .enumerate()
+            .filter_map(|(i, spec)| spec.as_ref().map(|spec| quote! { #i => #spec, }));
+        quote! {
+            /// All descriptors followed by all help strings, concatenated.
+            const PACKED_TEXT: &str = #packed_lit;
+
+            /// Descriptor `i` spans `SPEC_OFFSETS[i]..SPEC_OFFSETS[i + 1]` in `PACKED_TEXT`.
+            static SPEC_OFFSETS: [u16; #param_specs_len + 1] = [ #( #spec_offsets_u16 ),* ];
+
+            /// Help of `ENTRIES[i]` spans `HELP_OFFSETS[i]..HELP_OFFSETS[i + 1]` in `PACKED_TEXT`.
+            static HELP_OFFSETS: [u16; #num_offsets] = [ #( #help_offsets_u16 ),* ];
+
+            /// Slice `PACKED_TEXT`; usable in const context. Offsets always fall on char boundaries.
+            const fn packed_str(start: usize, end: usize) -> &'static str {
+                let (_, tail) = PACKED_TEXT.as_bytes().split_at(start);
+                let (bytes, _) = tail.split_at(end - start);
+                match core::str::from_utf8(bytes) {
+                    Ok(s) => s,
+                    Err(_) => "",
+                }
+            }
+
+            /// Return the descriptor with index `spec_idx` (see `Entry::spec_idx`).
+            pub fn spec_text(spec_idx: usize) -> &'static str {
+                match spec_idx {
+                    #( #struct_spec_arms )*
+                    _ => packed_str(SPEC_OFFSETS[spec_idx] as usize, SPEC_OFFSETS[spec_idx + 1] as usize),
+                }
+            }
+
+            /// Return the help text of `ENTRIES[index]` (empty if none was given).
+            pub fn help_text(index: usize) -> &'static str {
+                packed_str(HELP_OFFSETS[index] as usize, HELP_OFFSETS[index + 1] as usize)
+            }
+
+            /// Static pairs of (function name, parameter descriptor).
+            pub static NAME_AND_SPEC: &[(&'static str, &'static str)] = &[
+                #( #name_spec_pairs ),*
+            ];
+        }
+    } else {
+        let param_specs = unique_desc
+            .iter()
+            .zip(&struct_specs)
+            .map(|(s, struct_spec)| match struct_spec {
+                Some(spec) => spec.clone(),
+                None => {
+                    let lit = LitStr::new(s, Span::call_site());
+                    quote! { #lit }
+                }
+            });
+        let help_lits = entries
+            .iter()
+            .map(|e| LitStr::new(&e.help, Span::call_site()));
+        let name_spec_pairs = entries.iter().map(|e| {
+            let name_lit = LitStr::new(&e.name_str, Span::call_site());
+            if let Some(spec) = &struct_specs[e.spec_idx] {
+                return quote! { (#name_lit, #spec) };
+            }
+            let spec_lit = LitStr::new(&e.spec, Span::call_site());
+            quote! { (#name_lit, #spec_lit) }
+        });
+        quote! {
+            /// All unique parameter descriptors encountered (for diagnostics/UIs).
+            pub static PARAM_SPECS: [&'static str; #param_specs_len] = [ #( #param_specs ),* ];
+
+            /// Help text per entry, in `ENTRIES` order (empty if none was given).
+            pub static COMMAND_HELP: &[&'static str] = &[ #( #help_lits ),* ];
+
+            /// Return the descriptor with index `spec_idx` (see `Entry::spec_idx`).
+            pub fn spec_text(spec_idx: usize) -> &'static str {
+                PARAM_SPECS[spec_idx]
+            }
+
+            /// Return the help text of `ENTRIES[index]` (empty if none was given).
+            pub fn help_text(index: usize) -> &'static str {
+                COMMAND_HELP[index]
+            }
+
+            /// Static pairs of (function name, parameter descriptor).
+            pub static NAME_AND_SPEC: &[(&'static str, &'static str)] = &[
+                #( #name_spec_pairs ),*
+            ];
+        }
+    };
+
+    // Descriptor character to type table, plus the range notation when any descriptor uses it.
+    let mut descriptor_help = String::from(
+        "B:u8   | W:u16  | D:u32 | Q:u64 | X:u128 | Z:usize | F:f64\nb:i8   | w:i16  | d:i32 | q:i64 | x:i128 | z:isize | f:f32\nv:void | c:char | s:str | t:bool | h:hexstr | r:rest of line\n",
+    );
+    if has_ranges {
+        descriptor_help.push_str("T(lo..=hi): argument of type T must be within lo..=hi\n");
+    }
+    if has_defaults {
+        descriptor_help.push_str("[T=value]: optional argument of type T, value if omitted\n");
+    }
+    if has_names {
+        descriptor_help
+            .push_str("T:name: argument also given as --name=value; t:name is a flag --name\n");
+    }
+    if struct_paths.iter().any(Option::is_some) {
+        descriptor_help.push_str("S{T}: the fields of struct T deriving ShellArgs\n");
+    }
+
+    // Commands with named arguments get them in descriptor order before the arity check.
+    let (order_named_fn, order_named_call) = if has_names {
+        (
+            quote! {
+                /// Put the arguments of a command with named arguments into `slots` in descriptor
+                /// order: `--name=value` (`--name` alone for `true`) goes to the slot of `name`, the
+                /// other arguments fill the free slots in order. Free slots before the last one used
+                /// hold `""`, which an optional argument's parser replaces by its default. Returns the
+                /// number of slots used.
+                fn order_named<'a>(ent: &Entry, args: &[&'a str], slots: &mut [&'a str]) -> Result<usize, DispatchError> {
+                    let mut given = [false; MAX_ARITY];
+                    let mut len = 0usize;
+                    for named in args.iter().filter_map(|arg| arg.strip_prefix("--")) {
+                        let (name, value) = named.split_once('=').unwrap_or((named, "true"));
+                        // `--name="a b"` is one token (see `ushell2::tokens::Tokenizer`)
+                        let value = value.strip_prefix('"').map_or(value, |v| v.strip_suffix('"').unwrap_or(v));
+                        let i = ent.names.iter().position(|&n| !n.is_empty() && n == name).ok_or(DispatchError::BadName)?;
+                        if given[i] {
+                            return Err(DispatchError::BadName);
+                        }
+                        given[i] = true;
+                        slots[i] = value;
+                        len = len.max(i + 1);
+                    }
+                    let mut free = 0usize;
+                    for &arg in args.iter().filter(|arg| !arg.starts_with("--")) {
+                        while free < ent.arity as usize && given[free] {
+                            free += 1;
+                        }
+                        if free == ent.arity as usize {
+                            return Err(DispatchError::WrongArity { expected: ent.arity });
+                        }
+                        given[free] = true;
+                        slots[free] = arg;
+                        len = len.max(free + 1);
+                    }
+                    if given[..ent.min_arity as usize].contains(&false) {
+                        return Err(DispatchError::WrongArity { expected: ent.min_arity });
+                    }
+                    Ok(len)
+                }
+            },
+            quote! {
+                // Named arguments are put in descriptor order first
+                let mut named: [&str; MAX_ARITY] = [""; MAX_ARITY];
+                let (toks, name_len, len) = if ent.names.is_empty() {
+                    (&toks[..], name_len, len)
+                } else {
+                    match order_named(ent, &toks[name_len..len], &mut named) {
+                        Ok(n) => (&named[..], 0, n),
+                        Err(e) => {
+                            format_error(e, error_buffer);
+                            return Err(error_buffer.as_str());
+                        }
+                    }
+                };
+            },
+        )
+    } else {
+        (quote! {}, quote! {})
+    };
+
+    // Float argument parsers. `core`'s `FromStr` for floats is exact but large; with
+    // `compact_float = true;` a plain decimal parser (no exponent, no inf/nan) is used.
+    let float_parsers = if compact_float {
+        quote! {
+            macro_rules! parse_float {
+                ($name:ident, $ty:ty) => {
+                    /// Parse `[+-]digits[.digits]`; the result may differ from `FromStr` in the last digit.
+                    fn $name(s: &str) -> Option<$ty> {
+                        let s = s.trim();
+                        let (negative, digits) = match s.as_bytes().first() {
+                            Some(b'-') => (true, &s[1..]),
+                            Some(b'+') => (false, &s[1..]),
+                            _ => (false, s),
+                        };
+                        let (int_part, frac_part) = digits.split_once('.').unwrap_or((digits, ""));
+                        if int_part.is_empty() && frac_part.is_empty() {
+                            return None;
+                        }
+                        let mut value: $ty = 0.0;
+                        for b in int_part.bytes() {
+                            if !b.is_ascii_digit() { return None; }
+                            value = value * 10.0 + (b - b'0') as $ty;
+                        }
+                        let mut frac: u64 = 0;
+                        let mut div: $ty = 1.0;
+                        for b in frac_part.bytes() {
+                            if !b.is_ascii_digit() { return None; }
+                            // Digits beyond u64 precision cannot change the result.
+                            if frac < u64::MAX / 10 - 9 {
+                                frac = frac * 10 + (b - b'0') as u64;
+                                div *= 10.0;
+                            }
+                        }
+                        value += frac as $ty / div;
+                        Some(if negative { -value } else { value })
+                    }
+                };
+            }
+
+            parse_float!(parse_f32, f32);
+            parse_float!(parse_f64, f64);
+        }
+    } else {
+        quote! {
+            #[inline(always)]
+            fn parse_f32(s: &str) -> Option<f32> { s.parse::<f32>().ok() }
+
+            #[inline(always)]
+            fn parse_f64(s: &str) -> Option<f64> { s.parse::<f64>().ok() }
+        }
+    };
 
     // Generate maximals as constants
     let max_u8 = max_counts.u8_c;
@@ -373,9 +1258,43 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
     let max_hexstr = max_counts.hexstr_c;
     let max_arity_num = max_arity;
 
+    // Structs of `S{path}` descriptors: their arity is only known to the compiler.
+    let args_paths: Vec<&syn::Path> = struct_paths.iter().flatten().collect();
+    let max_arity_expr = if args_paths.is_empty() {
+        quote! { #max_arity_num }
+    } else {
+        quote! {{
+            let mut max = #max_arity_num;
+            #(
+                if <#args_paths as ushell2::args::ShellArgs<'static>>::ARITY > max {
+                    max = <#args_paths as ushell2::args::ShellArgs<'static>>::ARITY;
+                }
+            )*
+            max
+        }}
+    };
+    let arg_error_fn = if args_paths.is_empty() {
+        quote! {}
+    } else {
+        quote! {
+            /// Map a `ShellArgs` parse error to the error of the same argument type.
+            fn arg_error(e: ushell2::args::ArgError) -> DispatchError {
+                use ushell2::args::ArgErrorKind;
+                match e.kind {
+                    ArgErrorKind::WrongArity => DispatchError::WrongArity { expected: e.index },
+                    ArgErrorKind::BadBool => DispatchError::BadBool,
+                    ArgErrorKind::BadChar => DispatchError::BadChar,
+                    ArgErrorKind::BadUnsigned => DispatchError::BadUnsigned,
+                    ArgErrorKind::BadSigned => DispatchError::BadSigned,
+                    ArgErrorKind::BadFloat => DispatchError::BadFloat,
+                }
+            }
+        }
+    };
+
     // Generate per-descriptor parsers that fill `CallCtx` from `&[&str]`.
     let mut parsers: Vec<TokenStream2> = Vec::new();
-    for (sid, spec) in unique_desc.iter().enumerate() {
+    for (sid, spec) in spec_types.iter().enumerate() {
         let fn_ident = format_ident!("__parse_spec_{}", sid);
         let header = quote! {
             // `k` indexes into the argument tokens slice; individual idx_* track per-type positions.
@@ -389,67 +1308,100 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
         };
 
         let mut stmts: Vec<TokenStream2> = Vec::new();
-        for ch in spec.chars() {
+        let args_with_defaults = spec.chars().zip(&spec_ranges[sid]).zip(&spec_defaults[sid]);
+        for ((ch, range), default) in args_with_defaults {
+            // Bounds check on the freshly parsed value, reporting the argument index.
+            let check = |slot: TokenStream2| match range {
+                Some(r) => quote! {
+                    if !(#r).contains(&#slot) { return Err(DispatchError::OutOfRange { index: k as u8 }); }
+                },
+                None => quote! {},
+            };
             let stmt = match ch {
                 // unsigned
                 'B' => {
-                    quote! { ctx.u8s   [idx_b] = parse_u8   (args[k]).ok_or(DispatchError::BadUnsigned)?; idx_b+=1; k+=1; }
+                    let chk = check(quote! { ctx.u8s[idx_b] });
+                    quote! { ctx.u8s   [idx_b] = parse_u8   (args[k]).ok_or(DispatchError::BadUnsigned)?; #chk idx_b+=1; k+=1; }
                 }
                 'W' => {
-                    quote! { ctx.u16s  [idx_w] = parse_u16  (args[k]).ok_or(DispatchError::BadUnsigned)?; idx_w+=1; k+=1; }
+                    let chk = check(quote! { ctx.u16s[idx_w] });
+                    quote! { ctx.u16s  [idx_w] = parse_u16  (args[k]).ok_or(DispatchError::BadUnsigned)?; #chk idx_w+=1; k+=1; }
                 }
                 'D' => {
-                    quote! { ctx.u32s  [idx_d] = parse_u32  (args[k]).ok_or(DispatchError::BadUnsigned)?; idx_d+=1; k+=1; }
+                    let chk = check(quote! { ctx.u32s[idx_d] });
+                    quote! { ctx.u32s  [idx_d] = parse_u32  (args[k]).ok_or(DispatchError::BadUnsigned)?; #chk idx_d+=1; k+=1; }
                 }
                 'Q' => {
-                    quote! { ctx.u64s  [idx_q] = parse_u64  (args[k]).ok_or(DispatchError::BadUnsigned)?; idx_q+=1; k+=1; }
+                    let chk = check(quote! { ctx.u64s[idx_q] });
+                    quote! { ctx.u64s  [idx_q] = parse_u64  (args[k]).ok_or(DispatchError::BadUnsigned)?; #chk idx_q+=1; k+=1; }
                 }
                 'X' => {
-                    quote! { ctx.u128s [idx_x] = parse_u128 (args[k]).ok_or(DispatchError::BadUnsigned)?; idx_x+=1; k+=1; }
+                    let chk = check(quote! { ctx.u128s[idx_x] });
+                    quote! { ctx.u128s [idx_x] = parse_u128 (args[k]).ok_or(DispatchError::BadUnsigned)?; #chk idx_x+=1; k+=1; }
                 }
                 // signed
                 'b' => {
-                    quote! { ctx.i8s   [idx_B] = parse_i8   (args[k]).ok_or(DispatchError::BadSigned  )?; idx_B+=1; k+=1; }
+                    let chk = check(quote! { ctx.i8s[idx_B] });
+                    quote! { ctx.i8s   [idx_B] = parse_i8   (args[k]).ok_or(DispatchError::BadSigned  )?; #chk idx_B+=1; k+=1; }
                 }
                 'w' => {
-                    quote! { ctx.i16s  [idx_W] = parse_i16  (args[k]).ok_or(DispatchError::BadSigned  )?; idx_W+=1; k+=1; }
+                    let chk = check(quote! { ctx.i16s[idx_W] });
+                    quote! { ctx.i16s  [idx_W] = parse_i16  (args[k]).ok_or(DispatchError::BadSigned  )?; #chk idx_W+=1; k+=1; }
                 }
                 'd' => {
-                    quote! { ctx.i32s  [idx_D] = parse_i32  (args[k]).ok_or(DispatchError::BadSigned  )?; idx_D+=1; k+=1; }
+                    let chk = check(quote! { ctx.i32s[idx_D] });
+                    quote! { ctx.i32s  [idx_D] = parse_i32  (args[k]).ok_or(DispatchError::BadSigned  )?; #chk idx_D+=1; k+=1; }
                 }
                 'q' => {
-                    quote! { ctx.i64s  [idx_Q] = parse_i64  (args[k]).ok_or(DispatchError::BadSigned  )?; idx_Q+=1; k+=1; }
+                    let chk = check(quote! { ctx.i64s[idx_Q] });
+                    quote! { ctx.i64s  [idx_Q] = parse_i64  (args[k]).ok_or(DispatchError::BadSigned  )?; #chk idx_Q+=1; k+=1; }
                 }
                 'x' => {
-                    quote! { ctx.i128s [idx_X] = parse_i128 (args[k]).ok_or(DispatchError::BadSigned  )?; idx_X+=1; k+=1; }
+                    let chk = check(quote! { ctx.i128s[idx_X] });
+                    quote! { ctx.i128s [idx_X] = parse_i128 (args[k]).ok_or(DispatchError::BadSigned  )?; #chk idx_X+=1; k+=1; }
                 }
                 // sized
                 'Z' => {
-                    quote! { ctx.usizes[idx_z] = parse_usize(args[k]).ok_or(DispatchError::BadUnsigned)?; idx_z+=1; k+=1; }
+                    let chk = check(quote! { ctx.usizes[idx_z] });
+                    quote! { ctx.usizes[idx_z] = parse_usize(args[k]).ok_or(DispatchError::BadUnsigned)?; #chk idx_z+=1; k+=1; }
                 }
                 'z' => {
-                    quote! { ctx.isizes[idx_Z] = parse_isize(args[k]).ok_or(DispatchError::BadSigned  )?; idx_Z+=1; k+=1; }
+                    let chk = check(quote! { ctx.isizes[idx_Z] });
+                    quote! { ctx.isizes[idx_Z] = parse_isize(args[k]).ok_or(DispatchError::BadSigned  )?; #chk idx_Z+=1; k+=1; }
                 }
                 // floats
                 'f' => {
-                    quote! { ctx.f32s  [idx_f] = parse_f::<f32  >(args[k]).ok_or(DispatchError::BadFloat)?; idx_f+=1; k+=1; }
+                    let chk = check(quote! { ctx.f32s[idx_f] });
+                    quote! { ctx.f32s  [idx_f] = parse_f32(args[k]).ok_or(DispatchError::BadFloat)?; #chk idx_f+=1; k+=1; }
                 }
                 'F' => {
-                    quote! { ctx.f64s  [idx_F] = parse_f::<f64  >(args[k]).ok_or(DispatchError::BadFloat)?; idx_F+=1; k+=1; }
+                    let chk = check(quote! { ctx.f64s[idx_F] });
+                    quote! { ctx.f64s  [idx_F] = parse_f64(args[k]).ok_or(DispatchError::BadFloat)?; #chk idx_F+=1; k+=1; }
                 }
                 //  bool, char, string, hexstring
                 't' => {
-                    quote! { ctx.bools [idx_t] = parse_bool(args[k]).ok_or(DispatchError::BadBool)?; idx_t+=1; k+=1; }
+                    let chk = check(quote! { ctx.bools[idx_t] });
+                    quote! { ctx.bools [idx_t] = parse_bool(args[k]).ok_or(DispatchError::BadBool)?; #chk idx_t+=1; k+=1; }
                 }
                 'c' => {
-                    quote! { ctx.chars [idx_c] = parse_char(args[k]).ok_or(DispatchError::BadChar)?; idx_c+=1; k+=1; }
+                    let chk = check(quote! { ctx.chars[idx_c] });
+                    quote! { ctx.chars [idx_c] = parse_char(args[k]).ok_or(DispatchError::BadChar)?; #chk idx_c+=1; k+=1; }
                 }
-                's' => quote! { ctx.strs  [idx_s] = args[k]; idx_s+=1; k+=1; },
+                's' | 'r' => quote! { ctx.strs  [idx_s] = args[k]; idx_s+=1; k+=1; },
                 'h' => {
                     quote! { ctx.hexstrs[idx_h]= parse_hexstr(args[k]).ok_or(DispatchError::BadHexStr)?; idx_h+=1; k+=1; }
                 }
                 _ => quote! {},
             };
+            // An omitted optional argument takes its default, used as is (no range check).
+            // Named arguments leave `""` in the slots they skip (see `order_named`).
+            let stmt = match (default, ctx_slot(ch)) {
+                (Some(value), Some((field, idx))) => {
+                    let (field, idx) = (format_ident!("{}", field), format_ident!("{}", idx));
+                    quote! { if k < args.len() && !args[k].is_empty() { #stmt } else { ctx.#field[#idx] = #value; #idx+=1; k+=1; } }
+                }
+                _ => stmt,
+            };
             stmts.push(stmt);
         }
         parsers.push(quote! {
@@ -468,27 +1420,40 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
     let mut wrappers: Vec<TokenStream2> = Vec::new();
     let mut entry_inits: Vec<TokenStream2> = Vec::new();
     let mut match_arms: Vec<TokenStream2> = Vec::new();
-
-    // Pairs of (function name, descriptor) for diagnostics / UI
-    let name_spec_pairs: Vec<TokenStream2> = entries
-        .iter()
-        .map(|e| {
-            let name_lit = LitStr::new(&e.name_str, Span::call_site());
-            let spec_lit = LitStr::new(&e.spec, Span::call_site());
-            quote! { (#name_lit, #spec_lit) }
-        })
-        .collect();
+    let mut sub_match_arms: Vec<TokenStream2> = Vec::new();
 
     for (pos, e) in entries.iter().enumerate() {
         let name_lit = LitStr::new(&e.name_str, Span::call_site());
-        let spec_str = &e.spec;
-        //let arity_u8 = (spec_str.chars().count()) as u8;
-        let arity_u8 = if spec_str == "v" {
-            0
+        let spec_str = &spec_types[e.spec_idx];
+        let mut arity_u8 = if spec_str == "v" {
+            quote! { 0u8 }
         } else {
-            spec_str.chars().count() as u8
+            let arity = spec_str.chars().count() as u8;
+            quote! { #arity }
         };
-        let wrapper_ident = format_ident!("__call_{}", sanitize_ident(&e.name_str));
+        // Optional arguments all come last
+        let mandatory = spec_defaults[e.spec_idx]
+            .iter()
+            .take_while(|default| default.is_none())
+            .count() as u8;
+        let mut min_arity_u8 = if spec_str == "v" {
+            quote! { 0u8 }
+        } else {
+            quote! { #mandatory }
+        };
+        let rest = spec_str.ends_with('r');
+        let names = &spec_names[e.spec_idx];
+        let name_lits: Vec<LitStr> = if names.iter().any(Option::is_some) {
+            names
+                .iter()
+                .map(|name| LitStr::new(name.as_deref().unwrap_or(""), Span::call_site()))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        // `gpio set` -> `gpio__set`, distinct from a `gpio_set` command
+        let ident_name = sanitize_ident(&e.name_str.replace(' ', "__"));
+        let wrapper_ident = format_ident!("__call_{}", ident_name);
         let path = &e.path;
         let spec_idx_u16 = e.spec_idx as u16;
         let parser_ident = format_ident!("__parse_spec_{}", e.spec_idx);
@@ -606,7 +1571,7 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
                     arg_exprs.push(quote! { ctx.chars  [#idx_c] });
                     idx_c += 1;
                 }
-                's' => {
+                's' | 'r' => {
                     arg_types.push(quote! { &str  });
                     arg_exprs.push(quote! { ctx.strs   [#idx_s] });
                     idx_s += 1;
@@ -620,15 +1585,58 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
             }
         }
 
-        // Compile-time signature check: ensures `path` has the expected arity/types.
-        let sig_check = {
-            let fn_type = quote! { fn(#(#arg_types),*) -> _ };
-            quote! {
-                const _: fn() = || {
-                    let _check: #fn_type = #path;
-                    let _ = _check;
-                };
+        // Arguments come from `CallCtx`, or for a `S{path}` descriptor the struct is
+        // parsed from the raw tokens.
+        let (fn_args, parse_args, call_args) = match &struct_paths[e.spec_idx] {
+            Some(args_path) => {
+                arity_u8 =
+                    quote! { <#args_path as ushell2::args::ShellArgs<'static>>::ARITY as u8 };
+                min_arity_u8 = arity_u8.clone();
+                (
+                    quote! { #args_path },
+                    quote! {
+                        let _ = ctx;
+                        let args = <#args_path as ushell2::args::ShellArgs<'__ctx>>::from_tokens(&_av.tokens[.._av.len]).map_err(arg_error)?;
+                    },
+                    quote! { args },
+                )
             }
+            None => (
+                quote! { #(#arg_types),* },
+                quote! {},
+                quote! { #(#arg_exprs),* },
+            ),
+        };
+        let fn_type = quote! { fn(#fn_args) -> _ };
+
+        // A `->T` return value is formatted into `ctx.result` for the shell to print.
+        // With `compact_float` floats go through `ushell2::output`, not `core::fmt`.
+        let ret_value = match e.ret {
+            Some('f') if compact_float => {
+                quote! { ushell2::output::format_f32(__ret, #COMPACT_FLOAT_DECIMALS) }
+            }
+            Some('F') if compact_float => {
+                quote! { ushell2::output::format_f64(__ret, #COMPACT_FLOAT_DECIMALS) }
+            }
+            _ => quote! { __ret },
+        };
+        let (ret_type, store_ret) = match e.ret.and_then(return_type) {
+            Some((ty, fmt)) => (
+                ty,
+                quote! {
+                    use core::fmt::Write;
+                    let _ = write!(ctx.result, #fmt, #ret_value);
+                },
+            ),
+            None => (quote! { _ }, quote! { let _ = __ret; }),
+        };
+
+        // Compile-time signature check: ensures `path` has the expected arity/types.
+        let sig_check = quote! {
+            const _: fn() = || {
+                let _check: fn(#fn_args) -> #ret_type = #path;
+                let _ = _check;
+            };
         };
 
         wrappers.push(quote! {
@@ -637,24 +1645,74 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
             /// Wrapper that extracts arguments from `CallCtx` and calls the target function.
             #[inline(always)]
             fn #wrapper_ident<'__ctx>(ctx: &mut CallCtx<'__ctx>, _av: ArgsView<'__ctx>) -> Result<(), DispatchError> {
-                let _ = #path( #(#arg_exprs),* );
+                #parse_args
+                let __ret = #path( #call_args );
+                #store_ret
                 Ok(())
             }
         });
 
+        // `@rollback` function: same arguments as the command, checked the same way.
+        let rollback_init = match &e.rollback {
+            Some(rollback_path) => {
+                let undo_ident = format_ident!("__undo_{}", ident_name);
+                wrappers.push(quote! {
+                    const _: fn() = || {
+                        let _check: #fn_type = #rollback_path;
+                        let _ = _check;
+                    };
+
+                    /// Wrapper that extracts arguments from `CallCtx` and calls the rollback function.
+                    #[inline(always)]
+                    fn #undo_ident<'__ctx>(ctx: &mut CallCtx<'__ctx>, _av: ArgsView<'__ctx>) -> Result<(), DispatchError> {
+                        #parse_args
+                        let _ = #rollback_path( #call_args );
+                        Ok(())
+                    }
+                });
+                quote! { Some(#undo_ident) }
+            }
+            None => quote! { None },
+        };
+
+        let level = e.level;
         entry_inits.push(quote! {
             Entry {
                 name: #name_lit,
                 arity: #arity_u8,
+                min_arity: #min_arity_u8,
+                names: &[ #( #name_lits ),* ],
                 parser: #parser_ident,
                 caller: #wrapper_ident,
+                rollback: #rollback_init,
                 spec_idx: #spec_idx_u16,
+                rest: #rest,
+                level: #level,
             }
         });
 
-        match_arms.push(quote! { #name_lit => Some(&ENTRIES[#pos]), });
+        match_arms.push(quote! { #name_lit => Some(#pos), });
+        if let Some((group, verb)) = e.name_str.split_once(' ') {
+            sub_match_arms.push(quote! { (#group, #verb) => Some(#pos), });
+        }
     }
 
+    // Subcommands (`@group=`) are named by two tokens, matched when the first is no command.
+    let max_name_tokens: usize = if sub_match_arms.is_empty() { 1 } else { 2 };
+    let sub_lookup = if sub_match_arms.is_empty() {
+        quote! { let _ = len; None }
+    } else {
+        quote! {
+            if len < 2 {
+                return None;
+            }
+            match (toks[0], toks[1]) {
+                #( #sub_match_arms )*
+                _ => None,
+            }.map(|i| (i, 2))
+        }
+    };
+
     let max_hexstr_len_expr = if let Some(expr) = &hexstr_size {
         quote! { #expr }
     } else {
@@ -720,11 +1778,10 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
             parse_int!(parse_usize, usize);
             parse_int!(parse_isize, isize);
 
-            /// All unique parameter descriptors encountered (for diagnostics/UIs).
-            pub static PARAM_SPECS: [&'static str; #param_specs_len] = [ #( #param_specs ),* ];
+            #text_tables
 
             /// Descriptor character to Rust type mapping (for help/diagnostics).
-            pub static DESCRIPTOR_HELP: &str = "B:u8   | W:u16  | D:u32 | Q:u64 | X:u128 | Z:usize | F:f64\nb:i8   | w:i16  | d:i32 | q:i64 | x:i128 | z:isize | f:f32\nv:void | c:char | s:str | t:bool | h:hexstr\n";
+            pub static DESCRIPTOR_HELP: &str = #descriptor_help;
 
             /// Maximum counts per primitive across all descriptors. These sizes define the
             pub const MAX_U8:    usize = #max_u8;
@@ -752,7 +1809,10 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
             pub const MAX_HEXSTR_LEN: usize = #max_hexstr_len_expr;
 
             /// Maximum arity across all functions; token buffers use `1 + MAX_ARITY`.
-            pub const MAX_ARITY: usize = #max_arity_num;
+            pub const MAX_ARITY: usize = #max_arity_expr;
+
+            /// Tokens naming a command: 2 if there are subcommands (`gpio set`), else 1.
+            pub const MAX_NAME_TOKENS: usize = #max_name_tokens;
 
             /// Maximum number of commands
             pub const NUM_COMMANDS: usize = ENTRIES.len();
@@ -766,23 +1826,46 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
             /// Error buffer size for dispatch error messages
             pub const ERROR_BUFFER_SIZE: usize = #error_buffer_size_expr;
 
+            /// Privilege level of commands without `@level`.
+            pub const LEVEL_USER: u8 = 0;
+            /// Privilege level `@operator`.
+            pub const LEVEL_OPERATOR: u8 = 1;
+            /// Privilege level `@admin`.
+            pub const LEVEL_ADMIN: u8 = 2;
+
             /// One entry per function available to the dispatcher.
             pub struct Entry {
 
                 /// Function name used in textual calls (first token).
                 pub name: &'static str,
 
-                /// Required positional arity.
+                /// Positional arity, optional arguments included.
                 pub arity: u8,
 
+                /// Number of mandatory arguments; the optional ones (`[T=value]`) follow.
+                pub min_arity: u8,
+
+                /// Name of each argument that can be given as `--name=value` (`D:name`), `""`
+                /// for the others; empty if the command has no named arguments.
+                pub names: &'static [&'static str],
+
                 /// Descriptor-specific parser filling `CallCtx` from `&[&str]`.
                 pub parser: for<'ctx> fn(&mut CallCtx<'ctx>, &[&'ctx str]) -> Result<(), DispatchError>,
 
                 /// Wrapper invoking the target function.
                 pub caller: for<'ctx> fn(&mut CallCtx<'ctx>, ArgsView<'ctx>) -> Result<(), DispatchError>,
 
-                /// Index into `PARAM_SPECS` (for diagnostics).
+                /// Wrapper invoking the `@rollback` function, if any.
+                pub rollback: Option<for<'ctx> fn(&mut CallCtx<'ctx>, ArgsView<'ctx>) -> Result<(), DispatchError>>,
+
+                /// Descriptor index (for diagnostics), resolved through `spec_text`.
                 pub spec_idx: u16,
+
+                /// The last argument (`r`) takes the rest of the line, unsplit.
+                pub rest: bool,
+
+                /// Privilege level needed to run the command (`desc@level`).
+                pub level: u8,
             }
 
             /// A lightweight view over the raw tokens for advanced callers.
@@ -821,6 +1904,18 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
 
                 /// Failed to parse a hexlified string.
                 BadHexStr,
+
+                /// Argument `index` (0-based) is outside the range given in the descriptor.
+                OutOfRange { index: u8 },
+
+                /// `rollback` was called for a command without `@rollback`.
+                NoRollback,
+
+                /// The command needs privilege level `required`, above the caller's.
+                PermissionDenied { required: u8 },
+
+                /// A `--name` argument the command does not declare, or given twice.
+                BadName,
             }
 
             /// Stack-only argument storage sized by the `MAX_*` constants.
@@ -847,6 +1942,9 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
                 pub chars:  [char;  MAX_CHAR],
                 pub strs:   [&'a str; MAX_STR],
                 pub hexstrs: [heapless::Vec<u8, MAX_HEXSTR_LEN>; MAX_HEXSTR],
+
+                /// Formatted return value of a command with a `->T` descriptor.
+                pub result: heapless::String<ERROR_BUFFER_SIZE>,
             }
 
             impl<'a> CallCtx<'a> {
@@ -876,6 +1974,7 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
                         chars:  ['\0'; MAX_CHAR],
                         strs:   ["";   MAX_STR],
                         hexstrs: core::array::from_fn(|_| heapless::Vec::new()),
+                        result: heapless::String::new(),
                     }
                 }
             }
@@ -883,6 +1982,8 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
             /// Generated per-spec parsers
             #( #parsers )*
 
+            #arg_error_fn
+
             /// Generated per-function wrappers
             #( #wrappers )*
 
@@ -894,19 +1995,45 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
                 #( #entry_inits ),*
             ];
 
-            /// Fast string-table lookup (match on string literal).
+            /// Fast string-table lookup (match on string literal), returns the `ENTRIES` index.
             #[inline(always)]
-            fn find_entry(name: &str) -> Option<&'static Entry> {
+            fn find_index(name: &str) -> Option<usize> {
                 match name {
                     #( #match_arms )*
                     _ => None,
                 }
             }
 
-            /// Static pairs of (function name, parameter descriptor).
-            pub static NAME_AND_SPEC: &[(&'static str, &'static str)] = &[
-                #( #name_spec_pairs ),*
-            ];
+            /// Look up the command named by the first token, or by the first two for a
+            /// subcommand (`gpio set`). Returns its `ENTRIES` index and the number of name tokens.
+            #[inline(always)]
+            fn lookup(toks: &[&str], len: usize) -> Option<(usize, usize)> {
+                if let Some(i) = find_index(toks[0]) {
+                    return Some((i, 1));
+                }
+                #sub_lookup
+            }
+
+            /// Return the help text of a command (empty if none was given), `None` if unknown.
+            pub fn get_help(name: &str) -> Option<&'static str> {
+                find_index(name).map(help_text)
+            }
+
+            /// Return `(name, descriptor, arity, help, level)` of the command at `index` in
+            /// `get_commands()` order, `None` past the last one (machine-readable listings).
+            pub fn describe_command(index: usize) -> Option<(&'static str, &'static str, u8, &'static str, u8)> {
+                let ent = ENTRIES.get(index)?;
+                Some((ent.name, spec_text(ent.spec_idx as usize), ent.arity, help_text(index), ent.level))
+            }
+
+            /// Return the privilege level a command needs (`desc@level`), `None` if unknown.
+            pub fn required_level(name: &str) -> Option<u8> {
+                find_index(name).map(|index| ENTRIES[index].level)
+            }
+
+            #nohistory_fn
+
+            #complete_arg_fn
 
             /// Return (function name, descriptor) pairs. No allocations.
             #[inline(always)]
@@ -920,6 +2047,12 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
                 DESCRIPTOR_HELP
             }
 
+            /// Write the command reference (usage, help, groups, argument types and the
+            /// given `get_shortcuts` list) as Markdown, from the tables above.
+            pub fn write_manual<W: core::fmt::Write>(out: &mut W, shortcuts: &str) -> core::fmt::Result {
+                ushell2::output::manual::write_manual(out, describe_command, DESCRIPTOR_HELP, shortcuts)
+            }
+
             /// Parse a hexlified string (even-length, non-empty, valid hex).
             #[inline(always)]
             pub fn parse_hexstr(s: &str) -> Option<heapless::Vec<u8, MAX_HEXSTR_LEN>> {
@@ -933,43 +2066,26 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
             }
 
             // Quotes-aware tokenizer (no heap). Caller provides the buffer.
-            /// Splits by ASCII space or tab. A pair of `"` quotes groups a token (quotes
+            /// Splits by ASCII space or tab with `ushell2::tokens::Tokenizer`. A pair of
+            /// `"` quotes groups a token (quotes are not included).
             /// Returns `Empty` if no tokens were produced.
             pub fn tokenize<'a>(line: &'a str, out: &mut [&'a str]) -> Result<usize, DispatchError> {
-                let bytes = line.as_bytes();
-                let mut i = 0usize;
-                let mut n = 0usize;
+                tokenize_until(line, out, usize::MAX).map(|(n, _)| n)
+            }
 
-                while i < bytes.len() {
-                    // Skip leading spaces
-                    while i < bytes.len() && is_space(bytes[i]) { i += 1; }
-                    if i >= bytes.len() { break; }
-
-                    if bytes[i] == b'"' {
-                        // Quoted token
-                        let start = i + 1;
-                        i = start;
-                        while i < bytes.len() && bytes[i] != b'"' { i += 1; }
-                        if n < out.len() { out[n] = &line[start..i]; n += 1; }
-                        if i < bytes.len() { i += 1; }
-                        // Consume trailing non-space until next whitespace to match original behavior.
-                        while i < bytes.len() && !is_space(bytes[i]) { i += 1; }
-                    } else {
-                        // Unquoted token
-                        let start = i;
-                        while i < bytes.len() && !is_space(bytes[i]) { i += 1; }
-                        if n < out.len() { out[n] = &line[start..i]; n += 1; }
-                    }
+            /// Like `tokenize`, but stops after `limit` tokens. Also returns the offset in
+            /// `line` where splitting stopped.
+            pub fn tokenize_until<'a>(line: &'a str, out: &mut [&'a str], limit: usize) -> Result<(usize, usize), DispatchError> {
+                let mut tokens = ushell2::tokens::Tokenizer::new(line);
+                let mut n = 0usize;
+                for token in tokens.by_ref().take(limit) {
+                    if n < out.len() { out[n] = token; n += 1; }
                 }
 
                 if n == 0 { return Err(DispatchError::Empty); }
-                Ok(n)
+                Ok((n, tokens.offset()))
             }
 
-            /// ASCII space or tab.
-            #[inline(always)]
-            const fn is_space(b: u8) -> bool { b == b' ' || b == b'\t' }
-
             /// Accepts `1|true|True|TRUE` as `true`, and `0|false|False|FALSE` as `false`.
             #[inline(always)]
             fn parse_bool(s: &str) -> Option<bool> {
@@ -988,8 +2104,7 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
                 if it.next().is_none() { Some(c) } else { None }
             }
 
-            #[inline(always)]
-            fn parse_f<T>(s: &str) -> Option<T> where T: core::str::FromStr { s.parse::<T>().ok() }
+            #float_parsers
 
             /// Format a DispatchError into a string buffer
             #[inline(always)]
@@ -1006,20 +2121,42 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
                     DispatchError::BadSigned => write!(buf, "BadSigned"),
                     DispatchError::BadFloat => write!(buf, "BadFloat"),
                     DispatchError::BadHexStr => write!(buf, "BadHexStr"),
+                    DispatchError::OutOfRange { index } => write!(buf, "OutOfRange(index={})", index),
+                    DispatchError::NoRollback => write!(buf, "NoRollback"),
+                    DispatchError::PermissionDenied { required } => write!(buf, "PermissionDenied(required={})", required),
+                    DispatchError::BadName => write!(buf, "BadName"),
                 };
             }
 
+            #order_named_fn
+
+            /// Run `line` for a caller with privilege level `level`; commands needing a
+            /// higher one fail with `PermissionDenied`.
             #[inline(always)]
-            pub fn dispatch<'a>(line: &'a str, error_buffer: &'a mut heapless::String<ERROR_BUFFER_SIZE>) -> Result<(), &'a str> {
-                // + 2 in order to detect if more args than expected are provided..
-                let mut toks: [&str; 2 + MAX_ARITY] = [""; 2 + MAX_ARITY];
-                dispatch_with_buf(line, &mut toks, error_buffer)
+            pub fn dispatch<'a>(line: &'a str, level: u8, error_buffer: &'a mut heapless::String<ERROR_BUFFER_SIZE>) -> Result<(), &'a str> {
+                // + 1 in order to detect if more args than expected are provided..
+                let mut toks: [&str; MAX_NAME_TOKENS + 1 + MAX_ARITY] = [""; MAX_NAME_TOKENS + 1 + MAX_ARITY];
+                dispatch_with_buf(line, &mut toks, level, error_buffer)
             }
 
             /// Embedded-friendly entry point: caller supplies the token buffer.
             #[inline(always)]
-            pub fn dispatch_with_buf<'a>(line: &'a str, toks: &mut [&'a str], error_buffer: &'a mut heapless::String<ERROR_BUFFER_SIZE>) -> Result<(), &'a str> {
-                let len = match tokenize(line, toks) {
+            pub fn dispatch_with_buf<'a>(line: &'a str, toks: &mut [&'a str], level: u8, error_buffer: &'a mut heapless::String<ERROR_BUFFER_SIZE>) -> Result<(), &'a str> {
+                run_line(line, toks, level, error_buffer, false)
+            }
+
+            /// Undo a command line: call the `@rollback` function of its command with the
+            /// line's arguments. Fails with `NoRollback` if the command has none. Undoing a
+            /// command that ran is not subject to its privilege level.
+            pub fn rollback<'a>(line: &'a str, error_buffer: &'a mut heapless::String<ERROR_BUFFER_SIZE>) -> Result<(), &'a str> {
+                let mut toks: [&str; MAX_NAME_TOKENS + 1 + MAX_ARITY] = [""; MAX_NAME_TOKENS + 1 + MAX_ARITY];
+                run_line(line, &mut toks, u8::MAX, error_buffer, true)
+            }
+
+            /// Tokenize, look up, check and parse `line`, then call the command (or its
+            /// rollback function).
+            fn run_line<'a>(line: &'a str, toks: &mut [&'a str], level: u8, error_buffer: &'a mut heapless::String<ERROR_BUFFER_SIZE>, rollback: bool) -> Result<(), &'a str> {
+                let mut len = match tokenize(line, toks) {
                     Ok(len) => len,
                     Err(e) => {
                         format_error(e, error_buffer);
@@ -1027,46 +2164,538 @@ pub fn generate_dispatcher_from_dsl(input: TokenStream) -> TokenStream {
                     }
                 };
 
-                let name = toks[0];
-                let got_arity = (len - 1) as u16;
+                let (ent, name_len) = match lookup(toks, len) {
+                    Some((index, name_len)) => (&ENTRIES[index], name_len),
+                    None => {
+                        format_error(DispatchError::UnknownFunction, error_buffer);
+                        return Err(error_buffer.as_str());
+                    }
+                };
+
+                if ent.level > level {
+                    format_error(DispatchError::PermissionDenied { required: ent.level }, error_buffer);
+                    return Err(error_buffer.as_str());
+                }
+
+                let caller = match (rollback, ent.rollback) {
+                    (false, _) => ent.caller,
+                    (true, Some(undo)) => undo,
+                    (true, None) => {
+                        format_error(DispatchError::NoRollback, error_buffer);
+                        return Err(error_buffer.as_str());
+                    }
+                };
+
+                if ent.rest {
+                    // Split only the name and the fixed arguments; the remainder is one token.
+                    let fixed = name_len + ent.arity as usize - 1;
+                    let (n, end) = match tokenize_until(line, toks, fixed) {
+                        Ok(split) => split,
+                        Err(e) => {
+                            format_error(e, error_buffer);
+                            return Err(error_buffer.as_str());
+                        }
+                    };
+                    let rest = line[end..].trim_start_matches([' ', '\t']);
+                    len = n;
+                    if !rest.is_empty() && n == fixed && n < toks.len() {
+                        toks[n] = rest;
+                        len = n + 1;
+                    }
+                }
+                #order_named_call
+                let got_arity = (len - name_len) as u16;
+
+                if got_arity < ent.min_arity as u16 || got_arity > ent.arity as u16 {
+                    let expected = if got_arity < ent.min_arity as u16 { ent.min_arity } else { ent.arity };
+                    format_error(DispatchError::WrongArity { expected }, error_buffer);
+                    return Err(error_buffer.as_str());
+                }
+
+                // Fill CallCtx from raw &str tokens (no heap).
+                let mut ctx = CallCtx::new();
+                let args_tokens: &[&str] = &toks[name_len..len];
+
+                if let Err(e) = (ent.parser)(&mut ctx, args_tokens) {
+                    format_error(e, error_buffer);
+                    if let DispatchError::OutOfRange { .. } = e {
+                        // Show the descriptor so the user sees the valid range.
+                        use core::fmt::Write;
+                        let _ = write!(error_buffer, " usage: {} {}", ent.name, spec_text(ent.spec_idx as usize));
+                    }
+                    return Err(error_buffer.as_str());
+                }
+
+                // Provide a view for advanced use (currently unused by wrappers).
+                let args = ArgsView { tokens: args_tokens, len: len - name_len };
+
+                match caller(&mut ctx, args) {
+                    Ok(()) => {
+                        // Success leaves the return value (if any) in the buffer
+                        error_buffer.clear();
+                        let _ = error_buffer.push_str(&ctx.result);
+                        Ok(())
+                    }
+                    Err(e) => {
+                        format_error(e, error_buffer);
+                        Err(error_buffer.as_str())
+                    }
+                }
+            }
+
+            #c_export_fn
+
+            #size_report
+
+            #tests_mod
+        }
+    };
+
+    out.into()
+}
+
+/// Generate the `report_sizes = true;` constant: what the command table adds to flash
+/// and RAM. String bytes are counted at expansion time, type sizes by the compiler.
+fn size_report(entries: &[FnEntry], unique_desc: &[String]) -> TokenStream2 {
+    let string_bytes: usize = entries
+        .iter()
+        .map(|e| e.name_str.len() + e.help.len())
+        .chain(
+            unique_desc
+                .iter()
+                .filter(|d| struct_descriptor(d).is_none())
+                .map(String::len),
+        )
+        .sum();
+    quote! {
+        /// Footprint estimates of the command table, see `SIZE_REPORT`.
+        #[derive(Debug, Clone, Copy)]
+        pub struct SizeReport {
+            /// Number of commands.
+            pub entries: usize,
+            /// Bytes of command names, descriptors and help text (flash).
+            pub string_bytes: usize,
+            /// Bytes of the `ENTRIES` table (flash).
+            pub table_bytes: usize,
+            /// Bytes of the `CallCtx` holding parsed arguments (stack, per dispatch).
+            pub call_ctx_bytes: usize,
+            /// Largest number of arguments of a command.
+            pub max_arity: usize,
+        }
+
+        /// Generated with `report_sizes = true;`. Check it in a `const` assertion or
+        /// print it once at startup to keep the shell footprint in view.
+        pub const SIZE_REPORT: SizeReport = SizeReport {
+            entries: NUM_COMMANDS,
+            string_bytes: #string_bytes,
+            table_bytes: core::mem::size_of::<[Entry; NUM_COMMANDS]>(),
+            call_ctx_bytes: core::mem::size_of::<CallCtx<'static>>(),
+            max_arity: MAX_ARITY,
+        };
+    }
+}
+
+/// Generate the `c_export = "symbol";` wrappers letting C code call `dispatch`: `symbol`
+/// at `LEVEL_ADMIN` and `symbol_level` with the caller's level.
+fn c_export_fn(symbol: &Ident) -> TokenStream2 {
+    let level_symbol = format_ident!("{}_level", symbol);
+    let prototype = format!(
+        " int32_t {}(const char *line, char *err_buf, size_t err_len);",
+        symbol
+    );
+    let level_prototype = format!(
+        " int32_t {}(const char *line, uint8_t level, char *err_buf, size_t err_len);",
+        level_symbol
+    );
+    let level_doc = format!(
+        " `{}` with the privilege level `LEVEL_ADMIN`.",
+        level_symbol
+    );
+    quote! {
+        /// C entry point to `dispatch`:
+        ///
+        /// ```c
+        #[doc = #prototype]
+        /// ```
+        ///
+        #[doc = #level_doc]
+        /// C callers are firmware code, trusted like an unprotected console.
+        ///
+        /// # Safety
+        /// `line` must be null or a NUL-terminated string; `err_buf` must be null or point
+        /// to `err_len` writable bytes.
+        #[unsafe(no_mangle)]
+        pub unsafe extern "C" fn #symbol(
+            line: *const core::ffi::c_char,
+            err_buf: *mut core::ffi::c_char,
+            err_len: usize,
+        ) -> i32 {
+            unsafe { #level_symbol(line, LEVEL_ADMIN, err_buf, err_len) }
+        }
+
+        /// C entry point to `dispatch` at a given privilege level:
+        ///
+        /// ```c
+        #[doc = #level_prototype]
+        /// ```
+        ///
+        /// `level` is the caller's privilege level, as for `dispatch`.
+        /// Returns 0 on success, 1 if the command failed (the message is copied to
+        /// `err_buf`, cut to `err_len - 1` bytes and NUL-terminated) and -1 if `line` is
+        /// null or not UTF-8. On success `err_buf` holds the formatted return value of a
+        /// `desc->T` command, an empty string for the others.
+        ///
+        /// # Safety
+        /// `line` must be null or a NUL-terminated string; `err_buf` must be null or point
+        /// to `err_len` writable bytes.
+        #[unsafe(no_mangle)]
+        pub unsafe extern "C" fn #level_symbol(
+            line: *const core::ffi::c_char,
+            level: u8,
+            err_buf: *mut core::ffi::c_char,
+            err_len: usize,
+        ) -> i32 {
+            let write_err = |msg: &str| {
+                if err_buf.is_null() || err_len == 0 {
+                    return;
+                }
+                let n = msg.len().min(err_len - 1);
+                unsafe {
+                    core::ptr::copy_nonoverlapping(msg.as_ptr(), err_buf.cast::<u8>(), n);
+                    *err_buf.add(n) = 0;
+                }
+            };
+            if line.is_null() {
+                write_err("");
+                return -1;
+            }
+            let Ok(line) = unsafe { core::ffi::CStr::from_ptr(line) }.to_str() else {
+                write_err("");
+                return -1;
+            };
+            let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
+            match dispatch(line, level, &mut error_buffer) {
+                Ok(()) => {
+                    write_err(&error_buffer);
+                    0
+                }
+                Err(msg) => {
+                    write_err(msg);
+                    1
+                }
+            }
+        }
+    }
+}
+
+/// Generate the `emit_tests = true;` test module: the tokenizer, the parser of every
+/// descriptor with valid arguments, without its optional arguments and with each argument
+/// made invalid in turn, and the arity check of every command. Command functions are never
+/// called.
+fn generated_tests(
+    spec_types: &[String],
+    spec_ranges: &[Vec<Option<TokenStream2>>],
+    spec_defaults: &[Vec<Option<syn::Expr>>],
+) -> TokenStream2 {
+    let mut spec_tests: Vec<TokenStream2> = Vec::new();
+    for (sid, spec) in spec_types.iter().enumerate() {
+        // `S{path}` descriptors: the struct parses its arguments
+        if spec.is_empty() {
+            continue;
+        }
+        let parser_ident = format_ident!("__parse_spec_{}", sid);
+        let test_ident = format_ident!("spec_{}_arguments", sid);
+        let spec_lit = LitStr::new(spec, Span::call_site());
+
+        let mut valid: Vec<TokenStream2> = Vec::new();
+        let mut invalid: Vec<TokenStream2> = Vec::new();
+        for (ch, range) in spec.chars().zip(&spec_ranges[sid]) {
+            // (type, in-range fallback, valid token, invalid token and its error)
+            let (ty, fallback, token, bad) = match ch {
+                'B' => (
+                    quote! { u8 },
+                    quote! { 1u8 },
+                    "1",
+                    Some(("x", quote! { BadUnsigned })),
+                ),
+                'W' => (
+                    quote! { u16 },
+                    quote! { 1u16 },
+                    "1",
+                    Some(("x", quote! { BadUnsigned })),
+                ),
+                'D' => (
+                    quote! { u32 },
+                    quote! { 1u32 },
+                    "1",
+                    Some(("x", quote! { BadUnsigned })),
+                ),
+                'Q' => (
+                    quote! { u64 },
+                    quote! { 1u64 },
+                    "1",
+                    Some(("x", quote! { BadUnsigned })),
+                ),
+                'X' => (
+                    quote! { u128 },
+                    quote! { 1u128 },
+                    "1",
+                    Some(("x", quote! { BadUnsigned })),
+                ),
+                'Z' => (
+                    quote! { usize },
+                    quote! { 1usize },
+                    "1",
+                    Some(("x", quote! { BadUnsigned })),
+                ),
+                'b' => (
+                    quote! { i8 },
+                    quote! { -1i8 },
+                    "-1",
+                    Some(("x", quote! { BadSigned })),
+                ),
+                'w' => (
+                    quote! { i16 },
+                    quote! { -1i16 },
+                    "-1",
+                    Some(("x", quote! { BadSigned })),
+                ),
+                'd' => (
+                    quote! { i32 },
+                    quote! { -1i32 },
+                    "-1",
+                    Some(("x", quote! { BadSigned })),
+                ),
+                'q' => (
+                    quote! { i64 },
+                    quote! { -1i64 },
+                    "-1",
+                    Some(("x", quote! { BadSigned })),
+                ),
+                'x' => (
+                    quote! { i128 },
+                    quote! { -1i128 },
+                    "-1",
+                    Some(("x", quote! { BadSigned })),
+                ),
+                'z' => (
+                    quote! { isize },
+                    quote! { -1isize },
+                    "-1",
+                    Some(("x", quote! { BadSigned })),
+                ),
+                'f' => (
+                    quote! { f32 },
+                    quote! { 0.5f32 },
+                    "0.5",
+                    Some(("x", quote! { BadFloat })),
+                ),
+                'F' => (
+                    quote! { f64 },
+                    quote! { 0.5f64 },
+                    "0.5",
+                    Some(("x", quote! { BadFloat })),
+                ),
+                't' => (
+                    quote! { bool },
+                    quote! { true },
+                    "true",
+                    Some(("maybe", quote! { BadBool })),
+                ),
+                'c' => (
+                    quote! { char },
+                    quote! { 'a' },
+                    "a",
+                    Some(("ab", quote! { BadChar })),
+                ),
+                'h' => (
+                    quote! { u8 },
+                    quote! { 0u8 },
+                    "0a",
+                    Some(("0", quote! { BadHexStr })),
+                ),
+                's' => (quote! { u8 }, quote! { 0u8 }, "text", None),
+                'r' => (quote! { u8 }, quote! { 0u8 }, "rest of line", None),
+                _ => continue,
+            };
+            let k = valid.len();
+            valid.push(match range {
+                // Ranges only exist on numeric and `c` types
+                Some(r) => quote! { pick::<#ty>(#r, #fallback)? },
+                None => quote! { arg(#token) },
+            });
+            if let Some((bad_token, err)) = bad {
+                invalid.push(quote! {
+                    let mut bad = args;
+                    bad[#k] = #bad_token;
+                    assert_eq!(#parser_ident(&mut CallCtx::new(), &bad), Err(DispatchError::#err), "{} argument {}", #spec_lit, #k);
+                });
+            }
+        }
+        let argc = valid.len();
+        let mandatory = spec_defaults[sid]
+            .iter()
+            .take_while(|default| default.is_none())
+            .count()
+            .min(argc);
+        let defaults_used = (mandatory < argc).then(|| {
+            quote! {
+                assert_eq!(#parser_ident(&mut CallCtx::new(), &args[..#mandatory]), Ok(()), "{} without optional arguments", #spec_lit);
+            }
+        });
+
+        spec_tests.push(quote! {
+            #[test]
+            fn #test_ident() {
+                // No in-range value could be picked for an argument: nothing to check
+                fn tokens() -> Option<[Arg; #argc]> {
+                    Some([ #( #valid ),* ])
+                }
+                let Some(tokens) = tokens() else {
+                    return;
+                };
+                let args: [&str; #argc] = core::array::from_fn(|i| tokens[i].as_str());
+                assert_eq!(#parser_ident(&mut CallCtx::new(), &args), Ok(()), "{}", #spec_lit);
+                #defaults_used
+                #( #invalid )*
+            }
+        });
+    }
+
+    quote! {
+        /// Generated with `emit_tests = true;`.
+        #[cfg(test)]
+        mod __generated_tests {
+            use super::*;
+            use core::fmt::Write;
 
-                let ent = match find_entry(name) {
-                    Some(ent) => ent,
-                    None => {
-                        format_error(DispatchError::UnknownFunction, error_buffer);
-                        return Err(error_buffer.as_str());
-                    }
+            type Arg = heapless::String<48>;
+
+            fn arg(token: &str) -> Arg {
+                let mut out = Arg::new();
+                let _ = out.push_str(token);
+                out
+            }
+
+            /// The lower bound of `range`, else its inclusive upper bound, else `fallback`
+            /// if it lies in the range, as an argument token.
+            fn pick<T: PartialOrd + Copy + core::fmt::Display>(range: impl core::ops::RangeBounds<T>, fallback: T) -> Option<Arg> {
+                use core::ops::Bound;
+                let value = match (range.start_bound(), range.end_bound()) {
+                    (Bound::Included(lo), _) => *lo,
+                    (Bound::Unbounded, Bound::Included(hi)) => *hi,
+                    _ if range.contains(&fallback) => fallback,
+                    _ => return None,
                 };
+                let mut out = Arg::new();
+                write!(out, "{}", value).ok()?;
+                Some(out)
+            }
 
-                if got_arity != ent.arity as u16 {
-                    format_error(DispatchError::WrongArity { expected: ent.arity }, error_buffer);
-                    return Err(error_buffer.as_str());
-                }
+            #[test]
+            fn tokenize_splits_and_groups_quotes() {
+                let mut out = [""; 4];
+                assert_eq!(tokenize("  a \"b c\"\td ", &mut out), Ok(3));
+                assert_eq!(out[..3], ["a", "b c", "d"]);
+                assert_eq!(tokenize(" \t ", &mut out), Err(DispatchError::Empty));
+                assert_eq!(tokenize_until("a b c d", &mut out, 2), Ok((2, 3)));
+            }
 
-                // Fill CallCtx from raw &str tokens (no heap).
-                let mut ctx = CallCtx::new();
-                let args_tokens: &[&str] = &toks[1..len];
+            #( #spec_tests )*
 
-                if let Err(e) = (ent.parser)(&mut ctx, args_tokens) {
-                    format_error(e, error_buffer);
-                    return Err(error_buffer.as_str());
+            #[test]
+            fn arity_checked() {
+                // Dispatches `line`, which must fail before its command is called.
+                fn assert_wrong_arity(line: &str) {
+                    let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
+                    let err = dispatch(line, u8::MAX, &mut error_buffer).unwrap_err();
+                    // A too small buffer holds no message
+                    assert!(ERROR_BUFFER_SIZE < 24 || err.starts_with("WrongArity"), "{}: {}", line, err);
                 }
 
-                // Provide a view for advanced use (currently unused by wrappers).
-                let args = ArgsView { tokens: args_tokens, len: len - 1 };
-
-                match (ent.caller)(&mut ctx, args) {
-                    Ok(()) => Ok(()),
-                    Err(e) => {
-                        format_error(e, error_buffer);
-                        Err(error_buffer.as_str())
+                for ent in ENTRIES.iter() {
+                    let mut line: heapless::String<{ MAX_FUNCTION_NAME_LEN + 2 * (MAX_ARITY + 1) }> = heapless::String::new();
+                    let _ = line.push_str(ent.name);
+                    // A missing argument; a command without mandatory ones would run, so it is left out
+                    if ent.min_arity > 0 {
+                        assert_wrong_arity(&line);
+                    }
+                    // One argument too many, unless the last argument takes the rest of the line
+                    if !ent.rest {
+                        for _ in 0..=ent.arity {
+                            let _ = line.push_str(" 0");
+                        }
+                        assert_wrong_arity(&line);
                     }
                 }
             }
         }
+    }
+}
+
+/// Generate `is_nohistory(line)`: true if the first token of `line` names a command
+/// tagged `@nohistory`.
+fn nohistory_fn(entries: &[FnEntry]) -> TokenStream2 {
+    let tagged = || entries.iter().filter(|e| e.nohistory);
+    let names: Vec<LitStr> = tagged()
+        .filter(|e| !e.name_str.contains(' '))
+        .map(|e| LitStr::new(&e.name_str, Span::call_site()))
+        .collect();
+    let subcommands: Vec<TokenStream2> = tagged()
+        .filter_map(|e| e.name_str.split_once(' '))
+        .map(|(group, verb)| quote! { (Some(#group), Some(#verb)) })
+        .collect();
+    let body = match (names.is_empty(), subcommands.is_empty()) {
+        (true, true) => quote! { let _ = line; false },
+        (false, true) => quote! {
+            matches!(line.split_whitespace().next(), Some(#( #names )|*))
+        },
+        (true, false) => quote! {
+            let mut tokens = line.split_whitespace();
+            matches!((tokens.next(), tokens.next()), #( #subcommands )|*)
+        },
+        (false, false) => quote! {
+            let mut tokens = line.split_whitespace();
+            let first = tokens.next();
+            matches!(first, Some(#( #names )|*))
+                || matches!((first, tokens.next()), #( #subcommands )|*)
+        },
     };
+    quote! {
+        /// Return true if `line` invokes a command tagged `@nohistory` (matched by its
+        /// first token, or first two for a subcommand); the shell does not store such
+        /// lines in its history.
+        pub fn is_nohistory(line: &str) -> bool {
+            #body
+        }
+    }
+}
 
-    out.into()
+/// Generate `complete_arg(command, arg_index, word, offer)`: offers the `s[a|b]` keywords
+/// of the argument; fits `ushell2::autocomplete::ArgCompleter`.
+fn complete_arg_fn(entries: &[FnEntry]) -> TokenStream2 {
+    let arms: Vec<TokenStream2> = entries
+        .iter()
+        .flat_map(|e| {
+            e.keywords.iter().map(move |(index, words)| {
+                let name = &e.name_str;
+                quote! { (#name, #index) => &[#( #words ),*], }
+            })
+        })
+        .collect();
+    quote! {
+        /// Offer the keywords of the `s[a|b]` argument `arg_index` of `command` (the
+        /// subcommand included, `gpio set`) to `offer`. `word` is not needed: the shell
+        /// drops candidates not starting with it. Fits `ShellConfig::complete_arg`.
+        pub fn complete_arg(command: &str, arg_index: usize, word: &str, offer: &mut dyn FnMut(&str)) {
+            let _ = word;
+            let keywords: &[&str] = match (command, arg_index) {
+                #( #arms )*
+                _ => &[],
+            };
+            keywords.iter().for_each(|keyword| offer(keyword));
+        }
+    }
 }
 
 /// Internal representation of one function to register (pre-codegen).
@@ -1075,6 +2704,38 @@ struct FnEntry {
     path: syn::Path,
     spec: String,
     spec_idx: usize,
+    help: String,
+    nohistory: bool,
+    rollback: Option<syn::Path>,
+    level: u8,
+    /// Keyword lists of the descriptor's `s[a|b]` arguments.
+    keywords: Vec<ArgKeywords>,
+    /// Return type character of a `desc->T` descriptor.
+    ret: Option<char>,
+}
+
+/// Full path of a function as written (`crate::uc::init`).
+fn path_string(p: &syn::Path) -> String {
+    quote!(#p).to_string().replace(' ', "")
+}
+
+/// Fail on two entries (sorted by name) with the same command name, naming both paths.
+fn check_duplicate_names(entries: &[FnEntry], span: Span) -> syn::Result<()> {
+    match entries
+        .windows(2)
+        .find(|pair| pair[0].name_str == pair[1].name_str)
+    {
+        Some(pair) => Err(syn::Error::new(
+            span,
+            format!(
+                "duplicate command '{}': {} and {}",
+                pair[0].name_str,
+                path_string(&pair[0].path),
+                path_string(&pair[1].path)
+            ),
+        )),
+        None => Ok(()),
+    }
 }
 
 /// Last path segment (function ident) as a `String`.
@@ -1093,49 +2754,41 @@ pub fn generate_commands_dispatcher_from_file(input: TokenStream) -> TokenStream
     use syn::{Expr, parse::ParseStream};
 
     struct FileMacroInput {
-        _mod_token: Token![mod],         // Token for `mod` keyword
-        mod_name: Ident,                 // Name of the module to generate
-        _semi1: Token![;],               // Semicolon after module declaration
-        _hexstr_size_token: Ident,       // Identifier for hexstr_size
-        _eq_token: Token![=],            // Equals token for hexstr_size assignment
-        hexstr_size: Expr,               // Expression for hexstr_size value
-        _semi2: Token![;],               // Semicolon after hexstr_size assignment
-        _error_buffer_size_token: Ident, // Identifier for error_buffer_size
-        _eq_token2: Token![=],           // Equals token for error_buffer_size assignment
-        error_buffer_size: Expr,         // Expression for error_buffer_size value
-        _semi3: Token![;],               // Semicolon after error_buffer_size assignment
-        _path_token: Ident,              // Identifier for path
-        _eq_token3: Token![=],           // Equals token for path assignment
-        path: LitStr,                    // Literal string for file path
+        mod_name: Ident,             // Name of the module to generate
+        options: Vec<(Ident, Expr)>, // `key = value;` options, forwarded to the DSL parser
+        path: LitStr,                // Literal string for file path (last option)
     }
 
     impl Parse for FileMacroInput {
         fn parse(input: ParseStream) -> syn::Result<Self> {
-            Ok(FileMacroInput {
-                _mod_token: input.parse()?,
-                mod_name: input.parse()?,
-                _semi1: input.parse()?,
-                _hexstr_size_token: input.parse()?,
-                _eq_token: input.parse()?,
-                hexstr_size: input.parse()?,
-                _semi2: input.parse()?,
-                _error_buffer_size_token: input.parse()?,
-                _eq_token2: input.parse()?,
-                error_buffer_size: input.parse()?,
-                _semi3: input.parse()?,
-                _path_token: input.parse()?,
-                _eq_token3: input.parse()?,
-                path: input.parse()?,
-            })
+            input.parse::<Token![mod]>()?;
+            let mod_name: Ident = input.parse()?;
+            input.parse::<Token![;]>()?;
+
+            let mut options = Vec::new();
+            loop {
+                let key: Ident = input.parse()?;
+                input.parse::<Token![=]>()?;
+                if key == "path" {
+                    let path: LitStr = input.parse()?;
+                    let _ = input.parse::<Option<Token![;]>>()?;
+                    return Ok(FileMacroInput {
+                        mod_name,
+                        options,
+                        path,
+                    });
+                }
+                let value: Expr = input.parse()?;
+                input.parse::<Token![;]>()?;
+                options.push((key, value));
+            }
         }
     }
 
     let FileMacroInput {
         mod_name,
-        hexstr_size,
-        error_buffer_size,
+        options,
         path,
-        ..
     } = parse_macro_input!(input as FileMacroInput);
 
     let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
@@ -1144,10 +2797,13 @@ pub fn generate_commands_dispatcher_from_file(input: TokenStream) -> TokenStream
     let raw_dsl = std::fs::read_to_string(&full_path)
         .unwrap_or_else(|_| panic!("Failed to read command descriptor file: {:?}", full_path));
 
+    // Errors about the file's contents point at its path
+    let raw_dsl = LitStr::new(&raw_dsl, path.span());
+    let option_keys = options.iter().map(|(k, _)| k);
+    let option_values = options.iter().map(|(_, v)| v);
     let macro_input = quote! {
         mod #mod_name;
-        hexstr_size = #hexstr_size;
-        error_buffer_size = #error_buffer_size;
+        #( #option_keys = #option_values; )*
         #raw_dsl
     };
 
@@ -1181,36 +2837,10 @@ mod tests {
                 mod_ident,
                 body,
                 hexstr_size,
+                ..
             } = parsed;
 
-            let pairs: Vec<(String, Vec<syn::Path>)> = {
-                let s = body.value();
-                let mut acc = Vec::new();
-                for group in s.split(',') {
-                    let grp = group.trim();
-                    if grp.is_empty() {
-                        continue;
-                    }
-                    let (desc, names) = match grp.split_once(':') {
-                        Some((d, r)) => (d.trim(), r.trim()),
-                        None => continue,
-                    };
-                    if desc.is_empty() || names.is_empty() {
-                        continue;
-                    }
-                    let desc_str = desc.to_string();
-                    let funcs: StdResult<Vec<_>, _> = names
-                        .split_whitespace()
-                        .map(syn::parse_str::<syn::Path>)
-                        .collect();
-                    let funcs = match funcs {
-                        Ok(v) => v,
-                        Err(_) => continue,
-                    };
-                    acc.push((desc_str, funcs));
-                }
-                acc
-            };
+            let pairs = parse_dsl_groups(&body.value()).unwrap();
 
             // Check we got at least some entries for non-empty, non-whitespace descriptors
             if !descriptor.trim().is_empty() && pairs.is_empty() {
@@ -1486,6 +3116,12 @@ mod tests {
             path: path.clone(),
             spec: "DD".to_string(),
             spec_idx: 0,
+            help: String::new(),
+            nohistory: false,
+            rollback: None,
+            level: 0,
+            keywords: Vec::new(),
+            ret: None,
         };
 
         assert_eq!(entry.name_str, "add");
@@ -1501,18 +3137,36 @@ mod tests {
                 path: syn::parse_str("test::zebra").unwrap(),
                 spec: "v".to_string(),
                 spec_idx: 0,
+                help: String::new(),
+                nohistory: false,
+                rollback: None,
+                level: 0,
+                keywords: Vec::new(),
+                ret: None,
             },
             FnEntry {
                 name_str: "apple".to_string(),
                 path: syn::parse_str("test::apple").unwrap(),
                 spec: "v".to_string(),
                 spec_idx: 0,
+                help: String::new(),
+                nohistory: false,
+                rollback: None,
+                level: 0,
+                keywords: Vec::new(),
+                ret: None,
             },
             FnEntry {
                 name_str: "middle".to_string(),
                 path: syn::parse_str("test::middle").unwrap(),
                 spec: "v".to_string(),
                 spec_idx: 0,
+                help: String::new(),
+                nohistory: false,
+                rollback: None,
+                level: 0,
+                keywords: Vec::new(),
+                ret: None,
             },
         ];
 
@@ -1653,6 +3307,626 @@ mod tests {
         assert!(parsed.is_ok());
     }
 
+    #[test]
+    fn test_parse_compact_help_option() {
+        let input = quote! {
+            mod test_dispatcher;
+            compact_help = true;
+            error_buffer_size = 32;
+            hexstr_size = 64;
+            "DD: test::add"
+        };
+
+        let cmd = syn::parse2::<CommandMacroInput>(input).expect("Failed to parse");
+        assert!(cmd.compact_help);
+        assert!(cmd.hexstr_size.is_some());
+        assert!(cmd.error_buffer_size.is_some());
+    }
+
+    #[test]
+    fn test_parse_compact_help_default_off() {
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            "DD: test::add"
+        };
+
+        let cmd = syn::parse2::<CommandMacroInput>(input).expect("Failed to parse");
+        assert!(!cmd.compact_help);
+    }
+
+    #[test]
+    fn test_parse_compact_float_option() {
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            compact_float = true;
+            "f: test::set"
+        };
+
+        let cmd = syn::parse2::<CommandMacroInput>(input).expect("Failed to parse");
+        assert!(cmd.compact_float);
+        assert!(!cmd.compact_help);
+    }
+
+    #[test]
+    fn test_parse_emit_tests_option() {
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            emit_tests = true;
+            "D: test::set"
+        };
+
+        let cmd = syn::parse2::<CommandMacroInput>(input).expect("Failed to parse");
+        assert!(cmd.emit_tests);
+        assert!(!cmd.compact_float);
+    }
+
+    #[test]
+    fn test_parse_c_export_option() {
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            c_export = "shell_dispatch";
+            "D: test::set"
+        };
+
+        let cmd = syn::parse2::<CommandMacroInput>(input).expect("Failed to parse");
+        assert_eq!(cmd.c_export.unwrap().value(), "shell_dispatch");
+
+        let code = c_export_fn(&format_ident!("shell_dispatch")).to_string();
+        assert_eq!(code.matches("no_mangle").count(), 2);
+        assert!(
+            code.contains(
+                "int32_t shell_dispatch(const char *line, char *err_buf, size_t err_len);"
+            )
+        );
+        assert!(code.contains(
+            "int32_t shell_dispatch_level(const char *line, uint8_t level, char *err_buf, size_t err_len);"
+        ));
+        // The plain entry point runs at the admin level
+        assert!(code.contains("extern \"C\" fn shell_dispatch (line"));
+        assert!(code.contains("shell_dispatch_level (line , LEVEL_ADMIN , err_buf , err_len)"));
+        assert!(code.contains("extern \"C\" fn shell_dispatch_level (line"));
+        assert!(code.contains("dispatch (line , level , & mut error_buffer)"));
+    }
+
+    #[test]
+    fn test_parse_report_sizes_option() {
+        let input = quote! {
+            mod test_dispatcher;
+            hexstr_size = 64;
+            report_sizes = true;
+            "Bt: test::led \"set a LED\", D: test::set"
+        };
+
+        let cmd = syn::parse2::<CommandMacroInput>(input).expect("Failed to parse");
+        assert!(cmd.report_sizes);
+
+        let entries: Vec<FnEntry> = parse_dsl_groups(&cmd.body.value())
+            .unwrap()
+            .into_iter()
+            .flat_map(|(desc, funcs)| {
+                funcs.into_iter().map(move |f| FnEntry {
+                    name_str: path_last_ident(&f.path).unwrap(),
+                    path: f.path,
+                    spec: desc.clone(),
+                    spec_idx: 0,
+                    help: f.help,
+                    nohistory: f.nohistory,
+                    rollback: f.rollback,
+                    level: 0,
+                    keywords: Vec::new(),
+                    ret: None,
+                })
+            })
+            .collect();
+        let code = size_report(&entries, &["Bt".into(), "D".into()]).to_string();
+        // "led" + "set a LED" + "set" + "Bt" + "D"
+        assert!(code.contains("string_bytes : 18usize"));
+        assert!(code.contains("pub const SIZE_REPORT"));
+    }
+
+    #[test]
+    fn test_generated_tests() {
+        let ranged = parse_descriptor("D(0..=100)s").unwrap();
+        let void = parse_descriptor("v").unwrap();
+        let code = generated_tests(
+            &[ranged.types, void.types],
+            &[ranged.ranges, void.ranges],
+            &[ranged.defaults, void.defaults],
+        )
+        .to_string();
+        assert!(code.contains("cfg (test)"));
+        assert!(code.contains("fn spec_0_arguments"));
+        assert!(code.contains("fn spec_1_arguments"));
+        assert!(code.contains("pick :: < u32 > (0 ..= 100 , 1u32)"));
+        assert!(code.contains("BadUnsigned"));
+        assert!(code.contains("fn arity_checked"));
+        assert!(!code.contains("without optional arguments"));
+
+        let optional = parse_descriptor("D[D=100]").unwrap();
+        let code = generated_tests(&[optional.types], &[optional.ranges], &[optional.defaults])
+            .to_string();
+        assert!(code.contains("& args [.. 1usize]"));
+    }
+
+    #[test]
+    fn test_parse_unknown_option_rejected() {
+        let input = quote! {
+            mod test_dispatcher;
+            compact = true;
+            "DD: test::add"
+        };
+
+        assert!(syn::parse2::<CommandMacroInput>(input).is_err());
+    }
+
+    #[test]
+    fn test_descriptor_rest_of_line() {
+        let ParsedDescriptor { types, ranges, .. } = parse_descriptor("Dr").unwrap();
+        assert_eq!(types, "Dr");
+        assert_eq!(ranges.len(), 2);
+        assert!(parse_descriptor("rD").is_err());
+        assert!(parse_descriptor("rr").is_err());
+        assert!(parse_descriptor("r(0..4)").is_err());
+    }
+
+    // ============================================================================
+    // Range Constraint Tests
+    // ============================================================================
+
+    #[test]
+    fn test_descriptor_without_ranges() {
+        let ParsedDescriptor { types, ranges, .. } = parse_descriptor("DDs").unwrap();
+        assert_eq!(types, "DDs");
+        assert_eq!(ranges.len(), 3);
+        assert!(ranges.iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn test_descriptor_with_ranges() {
+        let ParsedDescriptor { types, ranges, .. } =
+            parse_descriptor("D(0..=100)sd(-5..5)").unwrap();
+        assert_eq!(types, "Dsd");
+        assert!(ranges[0].is_some());
+        assert!(ranges[1].is_none());
+        assert!(ranges[2].is_some());
+    }
+
+    #[test]
+    fn test_descriptor_open_ranges() {
+        assert!(parse_descriptor("D(10..)").is_ok());
+        assert!(parse_descriptor("d(..=0)").is_ok());
+        assert!(parse_descriptor("f(0.0..1.0)").is_ok());
+        assert!(parse_descriptor("c('a'..='z')").is_ok());
+    }
+
+    #[test]
+    fn test_descriptor_invalid_ranges() {
+        assert!(parse_descriptor("D(100)").is_err());
+        assert!(parse_descriptor("D(0..=)").is_err());
+        assert!(parse_descriptor("D(0..=1 +)").is_err());
+        assert!(parse_descriptor("s(0..4)").is_err());
+        assert!(parse_descriptor("t(0..1)").is_err());
+    }
+
+    // ============================================================================
+    // Optional Argument Tests
+    // ============================================================================
+
+    #[test]
+    fn test_descriptor_optional_arguments() {
+        let ParsedDescriptor {
+            types,
+            ranges,
+            defaults,
+            ..
+        } = parse_descriptor("D[D(1..=1024)=100][s=\"on\"]").unwrap();
+        assert_eq!(types, "DDs");
+        assert!(ranges[0].is_none());
+        assert_eq!(
+            ranges[1].as_ref().unwrap().to_string(),
+            quote! { 1..=1024 }.to_string()
+        );
+        assert!(defaults[0].is_none());
+        assert!(matches!(&defaults[1], Some(syn::Expr::Lit(_))));
+        assert!(matches!(&defaults[2], Some(syn::Expr::Lit(_))));
+        assert!(parse_descriptor("[f=-0.5][t=true][c='x']").is_ok());
+    }
+
+    #[test]
+    fn test_descriptor_invalid_optional_arguments() {
+        // A mandatory argument after an optional one
+        assert!(parse_descriptor("[D=1]D").is_err());
+        // No default, not one argument, a bad default
+        assert!(parse_descriptor("D[D]").is_err());
+        assert!(parse_descriptor("D[D(0..=9)]").is_err());
+        assert!(parse_descriptor("[DD=1]").is_err());
+        assert!(parse_descriptor("[=1]").is_err());
+        assert!(parse_descriptor("[D=1 +]").is_err());
+        // Types that cannot be omitted
+        assert!(parse_descriptor("[r=\"x\"]").is_err());
+        assert!(parse_descriptor("[h=0]").is_err());
+        assert!(parse_descriptor("[v=0]").is_err());
+        assert!(parse_descriptor("[s(0..4)=0]").is_err());
+    }
+
+    // ============================================================================
+    // Named Argument Tests
+    // ============================================================================
+
+    #[test]
+    fn test_descriptor_named_arguments() {
+        let parsed = parse_descriptor("D:addr D(1..=64):len s [B:retries=3] t:verbose").unwrap();
+        assert_eq!(parsed.types, "DDsBt");
+        assert_eq!(
+            parsed.names,
+            vec![
+                Some("addr".to_string()),
+                Some("len".to_string()),
+                None,
+                Some("retries".to_string()),
+                Some("verbose".to_string()),
+            ]
+        );
+        assert!(parsed.ranges[1].is_some());
+        // A flag is an optional `false`
+        assert_eq!(parsed.defaults.iter().filter(|d| d.is_some()).count(), 2);
+        assert!(matches!(&parsed.defaults[4], Some(syn::Expr::Lit(_))));
+
+        assert!(parse_descriptor("t:verbose D").is_err());
+        assert!(parse_descriptor("D:a D:a").is_err());
+        assert!(parse_descriptor("D:a [D:a=1]").is_err());
+        assert!(parse_descriptor("D:").is_err());
+        assert!(parse_descriptor("v:none").is_err());
+        assert!(parse_descriptor("D:a r").is_err());
+    }
+
+    #[test]
+    fn test_dsl_separator() {
+        assert_eq!(separator("DD: test::add"), Some(2));
+        assert_eq!(separator("D:addr t:verbose : a::b \"x: y\""), Some(17));
+        assert_eq!(separator("v@admin : a::wipe @rollback=a::b"), Some(8));
+        assert_eq!(separator("D a::b"), None);
+
+        let groups = parse_dsl_groups("D:addr D:len t:verbose: mem::dump, v: mem::info").unwrap();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].0, "D:addr D:len t:verbose");
+        assert_eq!(groups[1].0, "v");
+    }
+
+    // ============================================================================
+    // Help Text Tests
+    // ============================================================================
+
+    #[test]
+    fn test_split_unquoted_ignores_quoted_separators() {
+        let parts = split_unquoted("DD: a \"x, y\", d: b", ',');
+        assert_eq!(parts, vec!["DD: a \"x, y\"", " d: b"]);
+    }
+
+    #[test]
+    fn test_function_list_with_help() {
+        let funcs = parse_function_list("test::add \"add two numbers\" test::sub").unwrap();
+        assert_eq!(funcs.len(), 2);
+        assert_eq!(path_last_ident(&funcs[0].path), Some("add".to_string()));
+        assert_eq!(funcs[0].help, "add two numbers");
+        assert_eq!(funcs[1].help, "");
+    }
+
+    #[test]
+    fn test_function_list_help_without_space() {
+        let funcs = parse_function_list("test::add\"sum\"").unwrap();
+        assert_eq!(funcs.len(), 1);
+        assert_eq!(funcs[0].help, "sum");
+    }
+
+    #[test]
+    fn test_function_list_dangling_help_rejected() {
+        assert!(parse_function_list("\"no function\" test::add").is_err());
+        assert!(parse_function_list("test::add \"one\" \"two\"").is_err());
+        assert!(parse_function_list("test::add \"unterminated").is_err());
+    }
+
+    #[test]
+    fn test_dsl_groups_with_help() {
+        let groups = parse_dsl_groups(
+            "DD: test::add \"a + b, wrapping\" test::sub, v: test::reset \"reset: all state\"",
+        )
+        .unwrap();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].0, "DD");
+        assert_eq!(groups[0].1.len(), 2);
+        assert_eq!(groups[0].1[0].help, "a + b, wrapping");
+        assert_eq!(groups[1].0, "v");
+        assert_eq!(groups[1].1[0].help, "reset: all state");
+    }
+
+    #[test]
+    fn test_function_list_nohistory() {
+        let funcs = parse_function_list(
+            "test::login \"log in\" @nohistory test::logout @nohistory test::who",
+        )
+        .unwrap();
+        assert_eq!(funcs.len(), 3);
+        assert!(funcs[0].nohistory);
+        assert_eq!(funcs[0].help, "log in");
+        assert!(funcs[1].nohistory);
+        assert!(!funcs[2].nohistory);
+
+        // Attribute before the help string
+        let funcs = parse_function_list("test::login @nohistory \"log in\"").unwrap();
+        assert!(funcs[0].nohistory);
+        assert_eq!(funcs[0].help, "log in");
+    }
+
+    #[test]
+    fn test_function_list_bad_attribute_rejected() {
+        assert!(parse_function_list("@nohistory test::login").is_err());
+        assert_eq!(
+            parse_function_list("test::login @nohistroy")
+                .err()
+                .as_deref(),
+            Some("unknown attribute '@nohistroy'")
+        );
+    }
+
+    #[test]
+    fn test_function_list_rollback() {
+        let funcs = parse_function_list(
+            "test::set \"set a key\" @rollback=test::unset @nohistory test::get",
+        )
+        .unwrap();
+        assert_eq!(funcs.len(), 2);
+        let rollback = funcs[0].rollback.as_ref().unwrap();
+        assert_eq!(path_last_ident(rollback).as_deref(), Some("unset"));
+        assert!(funcs[0].nohistory);
+        assert!(funcs[1].rollback.is_none());
+
+        // Missing or invalid path, or given twice
+        assert!(parse_function_list("test::set @rollback").is_err());
+        assert!(parse_function_list("test::set @rollback=").is_err());
+        assert!(parse_function_list("test::set @rollback=a::b @rollback=a::c").is_err());
+        assert!(parse_function_list("test::set @nohistory=a::b").is_err());
+    }
+
+    #[test]
+    fn test_struct_descriptor() {
+        let groups = parse_dsl_groups(
+            "S{crate::pwm::PwmArgs}: crate::pwm::set \"set a PWM\" @rollback=crate::pwm::off, B: test::led",
+        )
+        .unwrap();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].0, "S{crate::pwm::PwmArgs}");
+        assert_eq!(
+            path_last_ident(&groups[0].1[0].path).as_deref(),
+            Some("set")
+        );
+        assert_eq!(groups[1].0, "B");
+
+        let path = struct_descriptor(&groups[0].0).unwrap().unwrap();
+        assert_eq!(path_last_ident(&path).as_deref(), Some("PwmArgs"));
+        assert!(struct_descriptor("Bt").is_none());
+        assert!(struct_descriptor("S{not a path}").unwrap().is_err());
+
+        // Unterminated braces drop the group
+        assert!(
+            parse_dsl_groups("S{crate::Args: test::f")
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_nohistory_fn() {
+        let entry = |name: &str, nohistory: bool| FnEntry {
+            name_str: name.to_string(),
+            path: syn::parse_str(name).unwrap(),
+            spec: "s".to_string(),
+            spec_idx: 0,
+            help: String::new(),
+            nohistory,
+            rollback: None,
+            level: 0,
+            keywords: Vec::new(),
+            ret: None,
+        };
+        let code = nohistory_fn(&[entry("echo", false), entry("login", true)]).to_string();
+        assert!(code.contains("fn is_nohistory"));
+        assert!(code.contains("\"login\""));
+        assert!(!code.contains("\"echo\""));
+
+        // No tagged commands: still generated, always false
+        let code = nohistory_fn(&[entry("echo", false)]).to_string();
+        assert!(code.contains("fn is_nohistory"));
+        assert!(code.contains("false"));
+    }
+
+    #[test]
+    fn test_split_keywords() {
+        let (plain, lists) = split_keywords("D(0..=9)s[on|off]s t s[ a | b-c ]").unwrap();
+        assert_eq!(plain, "D(0..=9)ss t s");
+        assert_eq!(
+            lists,
+            vec![
+                (1, vec!["on".to_string(), "off".to_string()]),
+                (4, vec!["a".to_string(), "b-c".to_string()]),
+            ]
+        );
+        assert_eq!(split_keywords("S{a::Args}").unwrap().0, "S{a::Args}");
+
+        // Names are kept, not counted
+        let (plain, lists) = split_keywords("D:addr s[on|off]:mode").unwrap();
+        assert_eq!(plain, "D:addr s:mode");
+        assert_eq!(lists, vec![(1, vec!["on".to_string(), "off".to_string()])]);
+
+        // Optional arguments are kept and counted
+        let (plain, lists) = split_keywords("[D(0..=9)=5]s[on|off]").unwrap();
+        assert_eq!(plain, "[D(0..=9)=5]s");
+        assert_eq!(lists, vec![(1, vec!["on".to_string(), "off".to_string()])]);
+
+        // Only strings take keywords, and every keyword must be a word
+        assert!(split_keywords("D[1|2]").is_err());
+        assert!(split_keywords("[a]").is_err());
+        assert!(split_keywords("s[a||b]").is_err());
+        assert!(split_keywords("s[a b]").is_err());
+    }
+
+    #[test]
+    fn test_split_return() {
+        assert_eq!(
+            split_return("DD->W").unwrap(),
+            ("DD".to_string(), Some('W'))
+        );
+        assert_eq!(
+            split_return("v -> t").unwrap(),
+            ("v".to_string(), Some('t'))
+        );
+        assert_eq!(
+            split_return("d(-5..=-1)").unwrap(),
+            ("d(-5..=-1)".to_string(), None)
+        );
+        assert_eq!(
+            split_return("S{a::Args}->D").unwrap(),
+            ("S{a::Args}".to_string(), Some('D'))
+        );
+
+        // One type character, and one that can be returned
+        assert!(split_return("D->").is_err());
+        assert!(split_return("D->DD").is_err());
+        assert!(split_return("D->h").is_err());
+        assert!(split_return("D->r").is_err());
+    }
+
+    #[test]
+    fn test_return_type_sizes() {
+        let (ty, fmt) = return_type('Z').unwrap();
+        assert_eq!(ty.to_string(), "usize");
+        assert_eq!(fmt, "{:#X}");
+        let (ty, fmt) = return_type('z').unwrap();
+        assert_eq!(ty.to_string(), "isize");
+        assert_eq!(fmt, "{}");
+    }
+
+    #[test]
+    fn test_complete_arg_fn() {
+        let entry = |name: &str, keywords: Vec<ArgKeywords>| FnEntry {
+            name_str: name.to_string(),
+            path: syn::parse_str("test::f").unwrap(),
+            spec: "ss".to_string(),
+            spec_idx: 0,
+            help: String::new(),
+            nohistory: false,
+            rollback: None,
+            level: 0,
+            keywords,
+            ret: None,
+        };
+        let code = complete_arg_fn(&[
+            entry("echo", Vec::new()),
+            entry("gpio mode", vec![(1, vec!["in".into(), "out".into()])]),
+        ])
+        .to_string();
+        assert!(code.contains("pub fn complete_arg"));
+        assert!(code.contains("(\"gpio mode\" , 1usize) => & [\"in\" , \"out\"]"));
+        assert!(!code.contains("\"echo\""));
+    }
+
+    #[test]
+    fn test_function_list_group() {
+        let funcs = parse_function_list("gpio::set \"set a pin\" @group=gpio gpio::list").unwrap();
+        assert_eq!(funcs[0].group.as_deref(), Some("gpio"));
+        assert_eq!(funcs[0].help, "set a pin");
+        assert!(funcs[1].group.is_none());
+
+        // Missing or invalid name, or given twice
+        assert!(parse_function_list("gpio::set @group=").is_err());
+        assert!(parse_function_list("gpio::set @group=gp-io").is_err());
+        assert!(parse_function_list("gpio::set @group=a @group=b").is_err());
+
+        // Tagged subcommands are matched on their first two tokens
+        let entry = |name: &str| FnEntry {
+            name_str: name.to_string(),
+            path: syn::parse_str("gpio::set").unwrap(),
+            spec: "Bt".to_string(),
+            spec_idx: 0,
+            help: String::new(),
+            nohistory: true,
+            rollback: None,
+            level: 0,
+            keywords: Vec::new(),
+            ret: None,
+        };
+        let code = nohistory_fn(&[entry("gpio set"), entry("login")]).to_string();
+        assert!(code.contains("(Some (\"gpio\") , Some (\"set\"))"));
+        assert!(code.contains("Some (\"login\")"));
+    }
+
+    #[test]
+    fn test_dsl_subcommand_block() {
+        let groups = parse_dsl_groups(
+            "v: test::reset, gpio { D: set \"set a pin, high\", D: clear, v@admin: list }, \
+             pwm { S{a::PwmArgs}: pwm::set }, B: test::led",
+        )
+        .unwrap();
+        let listed: Vec<(&str, &str, Option<&str>)> = groups
+            .iter()
+            .flat_map(|(desc, funcs)| {
+                funcs
+                    .iter()
+                    .map(move |f| (desc.as_str(), f.help.as_str(), f.group.as_deref()))
+            })
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("v", "", None),
+                ("D", "set a pin, high", Some("gpio")),
+                ("D", "", Some("gpio")),
+                ("v", "", Some("gpio")),
+                ("S{a::PwmArgs}", "", Some("pwm")),
+                ("B", "", None),
+            ]
+        );
+        assert_eq!(groups[3].1[0].level, 2);
+
+        // A function of a block cannot name another group, blocks do not nest
+        assert!(parse_dsl_groups("gpio { D: set @group=io, v: list }").is_err());
+        assert!(parse_dsl_groups("a { b { v: c } }").is_err());
+        // Not a block: no name, or an invalid one
+        assert!(parse_dsl_groups("{ v: list }").is_err());
+        assert!(parse_dsl_groups("gp-io { v: list }").is_err());
+    }
+
+    #[test]
+    fn test_dsl_groups_with_level() {
+        let groups = parse_dsl_groups(
+            "DD@admin: test::poke test::peek, v @ 1: test::reset, S{a::Args}@operator: a::run, B: test::led",
+        )
+        .unwrap();
+        assert_eq!(groups.len(), 4);
+        assert_eq!(groups[0].0, "DD");
+        assert!(groups[0].1.iter().all(|f| f.level == 2));
+        assert_eq!(groups[1].0, "v");
+        assert_eq!(groups[1].1[0].level, 1);
+        assert_eq!(groups[2].0, "S{a::Args}");
+        assert_eq!(groups[2].1[0].level, 1);
+        assert_eq!(groups[3].1[0].level, 0);
+
+        // Unknown level names and levels past `u8` are errors naming the group
+        assert_eq!(
+            parse_dsl_groups("v: test::reset, DD@admn: test::poke")
+                .err()
+                .as_deref(),
+            Some("'DD@admn: test::poke': unknown privilege level '@admn'")
+        );
+        assert!(parse_dsl_groups("DD@256: test::poke").is_err());
+        // A level without a descriptor leaves the group empty
+        assert!(parse_dsl_groups("@admin: test::poke").unwrap().is_empty());
+    }
+
     // ============================================================================
     // Edge Cases
     // ============================================================================
@@ -1755,6 +4029,38 @@ mod tests {
         assert_eq!(unique[0], "DD");
     }
 
+    #[test]
+    fn test_duplicate_command_names() {
+        let entry = |name: &str, path: &str| FnEntry {
+            name_str: name.to_string(),
+            path: syn::parse_str(path).unwrap(),
+            spec: "v".to_string(),
+            spec_idx: 0,
+            help: String::new(),
+            nohistory: false,
+            rollback: None,
+            level: 0,
+            keywords: Vec::new(),
+            ret: None,
+        };
+        let unique = [
+            entry("gpio init", "gpio::init"),
+            entry("init", "crate::uc::init"),
+        ];
+        assert!(check_duplicate_names(&unique, Span::call_site()).is_ok());
+
+        let clashing = [
+            entry("init", "crate::uc::init"),
+            entry("init", "crate::board::init"),
+            entry("reset", "crate::uc::reset"),
+        ];
+        let err = check_duplicate_names(&clashing, Span::call_site()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "duplicate command 'init': crate::uc::init and crate::board::init"
+        );
+    }
+
     #[test]
     fn test_different_descriptors_no_dedup() {
         let descriptor = "DD: test::add, dd: test::sub, D: test::third";
@@ -1,8 +1,10 @@
 extern crate proc_macro;
 
+mod argsgen;
 mod commandsgen;
 mod shortcutsgen;
 
+use argsgen::generate_shell_args;
 use commandsgen::generate_commands_dispatcher_from_file;
 use proc_macro::TokenStream;
 use shortcutsgen::generate_shortcuts_dispatcher_from_file;
@@ -16,3 +18,8 @@ pub fn generate_commands_dispatcher(input: TokenStream) -> TokenStream {
 pub fn generate_shortcuts_dispatcher(input: TokenStream) -> TokenStream {
     generate_shortcuts_dispatcher_from_file(input)
 }
+
+#[proc_macro_derive(ShellArgs)]
+pub fn derive_shell_args(input: TokenStream) -> TokenStream {
+    generate_shell_args(input)
+}
@@ -16,11 +16,15 @@
 //! ```rust
 //! mod <module_name>;
 //! error_buffer_size = <expression>;
+//! command_dispatcher = <path>;        // optional
 //! path = "<file_path>"
 //! ```
 //!
 //! - `mod <module_name>`: Name of the generated module.
 //! - `error_buffer_size`: Maximum size of the error buffer (const expression).
+//! - `command_dispatcher`: A `(line, error_buffer)` command dispatcher, e.g. a wrapper passing
+//!   the session's privilege level to the generated `dispatch`, that runs shortcuts bound to
+//!   a command line (`s : "status --verbose"`); required only if the file has such entries.
 //! - `path`: Path to the file containing shortcut mappings (relative to CARGO_MANIFEST_DIR).
 //! - **Note**: No trailing semicolon after the path parameter.
 //!
@@ -28,6 +32,21 @@
 //! - `dispatch<'a>(input: &'a str, error_buffer: &'a mut heapless::String<ERROR_BUFFER_SIZE>) -> Result<(), &'a str>`
 //! - `is_supported_shortcut(input: &str) -> bool`
 //! - `get_shortcuts() -> &'static str`
+//! - `MAX_REPEAT: u32` - largest repeat count accepted by `dispatch`
+//!
+//! Handlers take the parameter as `&str` and return either `()` or `Result<(), &str>`.
+//! An `Err(msg)` from a handler is copied into the error buffer and returned by `dispatch`.
+//!
+//! A shortcut is its prefix followed by its key, both of any length (`+ : { l : .., led : .. }`
+//! declares `+l` and `+led`). A line runs the longest shortcut it starts with, the rest
+//! of the line being the parameter: `+led 1` runs `+led`, `+lx` runs `+l` with `x`.
+//!
+//! A decimal repeat count may precede a shortcut: `5!+ x` calls the `!+` handler five
+//! times with `x`, stopping at the first error. Counts above the generated `MAX_REPEAT`
+//! (1000) are rejected.
+//!
+//! A quoted entry binds a key to a command line instead of a function; the line is run
+//! through `command_dispatcher` and such shortcuts take no parameter.
 
 use proc_macro::TokenStream;
 use quote::quote;
@@ -38,18 +57,19 @@ use syn::{
 };
 
 /// Struct to parse macro input in the format:
-/// `mod <n>; error_buffer_size = <expr>; path = "<file_path>"`
+/// `mod <n>; error_buffer_size = <expr>; [command_dispatcher = <path>;] path = "<file_path>"`
 struct ShortcutMacroInput {
-    _mod_token: Token![mod],         // Token for the `mod` keyword
-    mod_name: Ident,                 // Identifier for the module name
-    _semi1: Token![;],               // Semicolon after module declaration
-    _error_buffer_size_token: Ident, // Identifier for `error_buffer_size` keyword
-    _eq_token: Token![=],            // Equals sign for error_buffer_size assignment
-    error_buffer_size: Expr,         // Expression representing the error buffer size
-    _semi2: Token![;],               // Semicolon after error_buffer_size declaration
-    _path_token: Ident,              // Identifier for `path` keyword
-    _eq_token2: Token![=],           // Equals sign for path assignment
-    path: LitStr,                    // Literal string representing the file path
+    _mod_token: Token![mod],               // Token for the `mod` keyword
+    mod_name: Ident,                       // Identifier for the module name
+    _semi1: Token![;],                     // Semicolon after module declaration
+    _error_buffer_size_token: Ident,       // Identifier for `error_buffer_size` keyword
+    _eq_token: Token![=],                  // Equals sign for error_buffer_size assignment
+    error_buffer_size: Expr,               // Expression representing the error buffer size
+    _semi2: Token![;],                     // Semicolon after error_buffer_size declaration
+    command_dispatcher: Option<syn::Path>, // Dispatcher running command line shortcuts
+    _path_token: Ident,                    // Identifier for `path` keyword
+    _eq_token2: Token![=],                 // Equals sign for path assignment
+    path: LitStr,                          // Literal string representing the file path
 }
 
 impl Parse for ShortcutMacroInput {
@@ -62,6 +82,17 @@ impl Parse for ShortcutMacroInput {
             _eq_token: input.parse()?,
             error_buffer_size: input.parse()?,
             _semi2: input.parse()?,
+            command_dispatcher: {
+                if input.fork().parse::<Ident>()? == "command_dispatcher" {
+                    input.parse::<Ident>()?;
+                    input.parse::<Token![=]>()?;
+                    let dispatcher = input.parse()?;
+                    input.parse::<Token![;]>()?;
+                    Some(dispatcher)
+                } else {
+                    None
+                }
+            },
             _path_token: input.parse()?,
             _eq_token2: input.parse()?,
             path: input.parse()?,
@@ -69,10 +100,33 @@ impl Parse for ShortcutMacroInput {
     }
 }
 
+/// Split the entries of a prefix group at the commas outside quoted command lines.
+fn split_entries(rest: &str) -> Vec<&str> {
+    let mut entries = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, ch) in rest.char_indices() {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                entries.push(&rest[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    entries.push(&rest[start..]);
+    entries
+}
+
 pub fn generate_shortcuts_dispatcher_from_file(input: TokenStream) -> TokenStream {
     let ShortcutMacroInput {
         mod_name,
         error_buffer_size,
+        command_dispatcher,
         path,
         ..
     } = parse_macro_input!(input as ShortcutMacroInput);
@@ -87,6 +141,8 @@ pub fn generate_shortcuts_dispatcher_from_file(input: TokenStream) -> TokenStrea
     let mut match_arms = vec![];
     let mut prefixes = std::collections::HashSet::new();
     let mut shortcut_keys = vec![];
+    // What each key of `shortcut_keys` is bound to, as written in the file
+    let mut shortcut_targets: Vec<String> = vec![];
     let mut buffer = String::new();
 
     for line in raw.lines() {
@@ -100,7 +156,7 @@ pub fn generate_shortcuts_dispatcher_from_file(input: TokenStream) -> TokenStrea
                 let prefix = prefix.trim();
                 prefixes.insert(prefix.to_string());
 
-                for entry in rest.split(',') {
+                for entry in split_entries(rest) {
                     let entry = entry.trim().trim_matches('{').trim_matches('}').trim();
                     if entry.is_empty() {
                         continue;
@@ -108,18 +164,65 @@ pub fn generate_shortcuts_dispatcher_from_file(input: TokenStream) -> TokenStrea
                     if let Some((key, func)) = entry.split_once(':') {
                         let key = key.trim();
                         let func = func.trim();
-                        if let Ok(path) = syn::parse_str::<syn::Path>(func) {
-                            let full_key = format!("{}{}", prefix, key);
-                            shortcut_keys.push(full_key.clone());
+                        if prefix.is_empty()
+                            || key.is_empty()
+                            || prefix.contains(char::is_whitespace)
+                            || key.contains(char::is_whitespace)
+                        {
+                            return syn::Error::new(
+                                path.span(),
+                                format!("invalid shortcut key '{}{}'", prefix, key),
+                            )
+                            .to_compile_error()
+                            .into();
+                        }
+                        let full_key = format!("{}{}", prefix, key);
+                        // A second arm for the key would never be reached
+                        if let Some(i) = shortcut_keys.iter().position(|k| *k == full_key) {
+                            return syn::Error::new(
+                                path.span(),
+                                format!(
+                                    "duplicate shortcut '{}': {} and {}",
+                                    full_key, shortcut_targets[i], func
+                                ),
+                            )
+                            .to_compile_error()
+                            .into();
+                        }
+                        if func.starts_with('"') {
+                            // Canned command line, run by the command dispatcher
+                            let Ok(line) = syn::parse_str::<LitStr>(func) else {
+                                panic!("Invalid command line: {}", func);
+                            };
+                            let Some(dispatcher) = &command_dispatcher else {
+                                panic!(
+                                    "Shortcut {} binds a command line: set `command_dispatcher = <path>;`",
+                                    full_key
+                                );
+                            };
                             match_arms.push(quote! {
                                 #full_key => {
-                                    #path(param);
+                                    if !param.is_empty() {
+                                        error_buffer.clear();
+                                        use core::fmt::Write;
+                                        let _ = write!(error_buffer, "Shortcut takes no parameter: {}", key);
+                                        return Err(error_buffer.as_str());
+                                    }
+                                    if #dispatcher(#line, error_buffer).is_err() {
+                                        return Err(error_buffer.as_str());
+                                    }
                                     Ok(())
-                                },
+                                }
+                            });
+                        } else if let Ok(path) = syn::parse_str::<syn::Path>(func) {
+                            match_arms.push(quote! {
+                                #full_key => ShortcutResult::into_shortcut_result(#path(param)),
                             });
                         } else {
                             panic!("Invalid function path: {}", func);
                         }
+                        shortcut_keys.push(full_key);
+                        shortcut_targets.push(func.to_string());
                     }
                 }
             }
@@ -127,8 +230,8 @@ pub fn generate_shortcuts_dispatcher_from_file(input: TokenStream) -> TokenStrea
         }
     }
 
-    // Check if all prefixes are ASCII for optimization
-    let all_ascii = prefixes.iter().all(|p| p.is_ascii());
+    // Check if all prefixes are single ASCII characters for optimization
+    let all_ascii = prefixes.iter().all(|p| p.len() == 1 && p.is_ascii());
 
     let support_fn = if all_ascii {
         // Optimized ASCII version using byte comparison
@@ -143,7 +246,8 @@ pub fn generate_shortcuts_dispatcher_from_file(input: TokenStream) -> TokenStrea
         quote! {
             #[inline]
             pub fn is_supported_shortcut(input: &str) -> bool {
-                let trimmed = input.trim();
+                // A repeat count may precede the shortcut
+                let trimmed = input.trim().trim_start_matches(|c: char| c.is_ascii_digit());
                 if trimmed.is_empty() {
                     return false;
                 }
@@ -152,24 +256,28 @@ pub fn generate_shortcuts_dispatcher_from_file(input: TokenStream) -> TokenStrea
             }
         }
     } else {
-        // Non-ASCII version using string comparison
+        // Non-ASCII or multi-character prefixes, compared as strings
         let supported_checks = prefixes.iter().map(|p| {
-            quote! { c == #p }
+            quote! { trimmed.starts_with(#p) }
         });
 
         quote! {
             #[inline]
             pub fn is_supported_shortcut(input: &str) -> bool {
-                let trimmed = input.trim();
+                // A repeat count may precede the shortcut
+                let trimmed = input.trim().trim_start_matches(|c: char| c.is_ascii_digit());
                 if trimmed.is_empty() {
                     return false;
                 }
-                let c = &trimmed[0..1];
                 #( #supported_checks )||*
             }
         }
     };
 
+    // Keys are matched longest first: `+led` wins over `+l` for `+led 1`
+    let mut match_order: Vec<&String> = shortcut_keys.iter().collect();
+    match_order.sort_by_key(|key| std::cmp::Reverse(key.len()));
+
     let shortcut_list = shortcut_keys.join(" | ");
     let list_fn = quote! {
         #[inline(always)]
@@ -182,449 +290,92 @@ pub fn generate_shortcuts_dispatcher_from_file(input: TokenStream) -> TokenStrea
         #[inline]
         pub fn dispatch<'a>(input: &'a str, error_buffer: &'a mut heapless::String<{ #error_buffer_size }>) -> Result<(), &'a str> {
             let trimmed = input.trim();
-            let (key, param) = if trimmed.len() >= 2 {
-                let key = &trimmed[..2];
-                let param = trimmed[2..].trim();
-                (key, param)
+            // Optional repeat count: `5!+` runs the `!+` handler five times
+            let digits = trimmed.bytes().take_while(u8::is_ascii_digit).count();
+            let (count, trimmed) = if digits == 0 {
+                (1, trimmed)
             } else {
-                (trimmed, "")
+                match trimmed[..digits].parse::<u32>() {
+                    Ok(count) if (1..=MAX_REPEAT).contains(&count) => (count, &trimmed[digits..]),
+                    Ok(0) => {
+                        error_buffer.clear();
+                        use core::fmt::Write;
+                        let _ = write!(error_buffer, "Bad repeat count: {}", &trimmed[..digits]);
+                        return Err(error_buffer.as_str());
+                    }
+                    // Too large, also when it does not fit in a `u32`
+                    _ => {
+                        error_buffer.clear();
+                        use core::fmt::Write;
+                        let _ = write!(error_buffer, "Repeat count above {}", MAX_REPEAT);
+                        return Err(error_buffer.as_str());
+                    }
+                }
+            };
+            // The longest key the line starts with; the rest is the parameter
+            const KEYS: &[&str] = &[#( #match_order ),*];
+            let (key, param) = match KEYS.iter().find(|key| trimmed.starts_with(**key)) {
+                Some(key) => (*key, trimmed[key.len()..].trim()),
+                // Unknown: the first word, for the error message
+                None => (trimmed.split_whitespace().next().unwrap_or(""), ""),
             };
-            match key {
-                #( #match_arms )*
-                _ => {
+            // Stops at the first failing run
+            for _ in 0..count {
+                let result = match key {
+                    #( #match_arms )*
+                    _ => {
+                        error_buffer.clear();
+                        use core::fmt::Write;
+                        let _ = write!(error_buffer, "Unknown shortcut: {}", key);
+                        return Err(error_buffer.as_str());
+                    },
+                };
+                if let Err(e) = result {
                     error_buffer.clear();
                     use core::fmt::Write;
-                    let _ = write!(error_buffer, "Unknown shortcut: {}", key);
-                    Err(error_buffer.as_str())
-                },
+                    let _ = write!(error_buffer, "{}", e);
+                    return Err(error_buffer.as_str());
+                }
             }
+            Ok(())
         }
     };
 
-    let expanded = quote! {
-        pub mod #mod_name {
-            #dispatch_fn
-            #support_fn
-            #list_fn
-        }
-    };
-
-    TokenStream::from(expanded)
-}
-
-// ==================================================
-// ================= TESTS ==========================
-// ==================================================
-
-#[cfg(test)]
-mod tests {
-    use std::collections::HashMap;
-    use std::sync::Mutex;
-
-    // Error buffer size for tests - configurable
-    const ERROR_BUFFER_SIZE: usize = 32;
-
-    // Global state to track function calls in tests
-    static CALL_LOG: Mutex<Option<HashMap<String, Vec<String>>>> = Mutex::new(None);
-
-    fn ensure_log_initialized() {
-        let mut log = CALL_LOG.lock().unwrap();
-        if log.is_none() {
-            *log = Some(HashMap::new());
+    // Handlers may return `()` or `Result<(), &str>`; the trait picks the right conversion,
+    // so the return type does not have to be declared in the mapping file.
+    let result_trait = quote! {
+        /// Conversion of a shortcut handler's return value into a dispatch result.
+        pub trait ShortcutResult<'a> {
+            fn into_shortcut_result(self) -> Result<(), &'a str>;
         }
-    }
 
-    fn record_call(func_name: &str, param: &str) {
-        ensure_log_initialized();
-        let mut log = CALL_LOG.lock().unwrap();
-        log.as_mut()
-            .unwrap()
-            .entry(func_name.to_string())
-            .or_insert_with(Vec::new)
-            .push(param.to_string());
-    }
-
-    fn get_calls(func_name: &str) -> Vec<String> {
-        ensure_log_initialized();
-        let log = CALL_LOG.lock().unwrap();
-        log.as_ref()
-            .unwrap()
-            .get(func_name)
-            .cloned()
-            .unwrap_or_default()
-    }
-
-    fn clear_log() {
-        ensure_log_initialized();
-        let mut log = CALL_LOG.lock().unwrap();
-        log.as_mut().unwrap().clear();
-    }
-
-    // Test handler functions
-    mod test_handlers {
-        use super::record_call;
-
-        pub fn bang_plus(param: &str) {
-            record_call("bang_plus", param);
-        }
-        pub fn bang_minus(param: &str) {
-            record_call("bang_minus", param);
-        }
-        pub fn bang_hash(param: &str) {
-            record_call("bang_hash", param);
-        }
-
-        pub fn plus_plus(param: &str) {
-            record_call("plus_plus", param);
-        }
-        pub fn plus_minus(param: &str) {
-            record_call("plus_minus", param);
-        }
-        pub fn plus_hash(param: &str) {
-            record_call("plus_hash", param);
+        impl<'a> ShortcutResult<'a> for () {
+            #[inline(always)]
+            fn into_shortcut_result(self) -> Result<(), &'a str> {
+                Ok(())
+            }
         }
 
-        pub fn minus_plus(param: &str) {
-            record_call("minus_plus", param);
-        }
-        pub fn minus_minus(param: &str) {
-            record_call("minus_minus", param);
-        }
-        pub fn minus_hash(param: &str) {
-            record_call("minus_hash", param);
+        impl<'a> ShortcutResult<'a> for Result<(), &'a str> {
+            #[inline(always)]
+            fn into_shortcut_result(self) -> Result<(), &'a str> {
+                self
+            }
         }
+    };
 
-        pub fn hash_bang(param: &str) {
-            record_call("hash_bang", param);
-        }
-        pub fn hash_plus(param: &str) {
-            record_call("hash_plus", param);
-        }
-        pub fn hash_question(param: &str) {
-            record_call("hash_question", param);
-        }
+    let expanded = quote! {
+        pub mod #mod_name {
+            /// Largest repeat count before a shortcut (`1000!+`); a typo must not
+            /// keep the shell busy for hours.
+            pub const MAX_REPEAT: u32 = 1000;
 
-        pub fn question_bang(param: &str) {
-            record_call("question_bang", param);
-        }
-        pub fn question_plus(param: &str) {
-            record_call("question_plus", param);
-        }
-        pub fn question_question(param: &str) {
-            record_call("question_question", param);
+            #result_trait
+            #dispatch_fn
+            #support_fn
+            #list_fn
         }
-    }
-
-    // Create a test shortcuts.txt file in the test directory
-    const TEST_SHORTCUTS: &str = r#"!: { +: test_handlers::bang_plus, -: test_handlers::bang_minus, #: test_handlers::bang_hash },
-+: { +: test_handlers::plus_plus, -: test_handlers::plus_minus, #: test_handlers::plus_hash },
--: { +: test_handlers::minus_plus, -: test_handlers::minus_minus, #: test_handlers::minus_hash },
-#: { !: test_handlers::hash_bang, +: test_handlers::hash_plus, ?: test_handlers::hash_question },
-?: { !: test_handlers::question_bang, +: test_handlers::question_plus, ?: test_handlers::question_question },
-"#;
-
-    // Write test shortcuts to a file before tests run
-    fn setup_test_shortcuts() {
-        use std::fs;
-        use std::path::Path;
-
-        let test_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
-        fs::create_dir_all(&test_dir).unwrap();
-        fs::write(test_dir.join("test_shortcuts.txt"), TEST_SHORTCUTS).unwrap();
-    }
-
-    // Use the macro to generate the dispatcher
-    use crate::generate_shortcuts_dispatcher_from_file;
-
-    macro_rules! define_shortcuts {
-        (mod $mod_name:ident; error_buffer_size = $size:expr; path = $path:expr) => {
-            generate_shortcuts_dispatcher_from_file(
-                quote::quote! {
-                    mod $mod_name;
-                    error_buffer_size = $size;
-                    path = $path
-                }
-                .into(),
-            )
-        };
-    }
-
-    // Generate the shortcuts module
-    setup_test_shortcuts!();
-    define_shortcuts! {
-        mod shortcuts;
-        error_buffer_size = ERROR_BUFFER_SIZE;
-        path = "tests/test_shortcuts.txt"
-    }
-
-    #[test]
-    fn test_is_supported_shortcut() {
-        assert!(shortcuts::is_supported_shortcut("!"));
-        assert!(shortcuts::is_supported_shortcut("+"));
-        assert!(shortcuts::is_supported_shortcut("-"));
-        assert!(shortcuts::is_supported_shortcut("#"));
-        assert!(shortcuts::is_supported_shortcut("?"));
-        assert!(!shortcuts::is_supported_shortcut("x"));
-        assert!(!shortcuts::is_supported_shortcut(""));
-    }
-
-    #[test]
-    fn test_get_shortcuts() {
-        let shortcuts_str = shortcuts::get_shortcuts();
-        assert!(shortcuts_str.contains("!+"));
-        assert!(shortcuts_str.contains("!-"));
-        assert!(shortcuts_str.contains("!#"));
-        assert!(shortcuts_str.contains("++"));
-        assert!(shortcuts_str.contains("+-"));
-        assert!(shortcuts_str.contains("+#"));
-        assert!(shortcuts_str.contains("-+"));
-        assert!(shortcuts_str.contains("--"));
-        assert!(shortcuts_str.contains("-#"));
-        assert!(shortcuts_str.contains("#!"));
-        assert!(shortcuts_str.contains("#+"));
-        assert!(shortcuts_str.contains("#?"));
-        assert!(shortcuts_str.contains("?!"));
-        assert!(shortcuts_str.contains("?+"));
-        assert!(shortcuts_str.contains("??"));
-    }
-
-    #[test]
-    fn test_invalid_shortcut() {
-        let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
-        let result = shortcuts::dispatch("xx", &mut error_buffer);
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Unknown shortcut"));
-    }
-
-    #[test]
-    fn test_all_bang_shortcuts() {
-        clear_log();
-        let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
-
-        assert!(shortcuts::dispatch("!+", &mut error_buffer).is_ok());
-        assert_eq!(get_calls("bang_plus").len(), 1);
-
-        clear_log();
-        assert!(shortcuts::dispatch("!-", &mut error_buffer).is_ok());
-        assert_eq!(get_calls("bang_minus").len(), 1);
-
-        clear_log();
-        assert!(shortcuts::dispatch("!#", &mut error_buffer).is_ok());
-        assert_eq!(get_calls("bang_hash").len(), 1);
-    }
-
-    #[test]
-    fn test_all_plus_shortcuts() {
-        clear_log();
-        let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
-
-        assert!(shortcuts::dispatch("++", &mut error_buffer).is_ok());
-        assert_eq!(get_calls("plus_plus").len(), 1);
-
-        clear_log();
-        assert!(shortcuts::dispatch("+-", &mut error_buffer).is_ok());
-        assert_eq!(get_calls("plus_minus").len(), 1);
-
-        clear_log();
-        assert!(shortcuts::dispatch("+#", &mut error_buffer).is_ok());
-        assert_eq!(get_calls("plus_hash").len(), 1);
-    }
-
-    #[test]
-    fn test_all_minus_shortcuts() {
-        clear_log();
-        let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
-
-        assert!(shortcuts::dispatch("-+", &mut error_buffer).is_ok());
-        assert_eq!(get_calls("minus_plus").len(), 1);
-
-        clear_log();
-        assert!(shortcuts::dispatch("--", &mut error_buffer).is_ok());
-        assert_eq!(get_calls("minus_minus").len(), 1);
-
-        clear_log();
-        assert!(shortcuts::dispatch("-#", &mut error_buffer).is_ok());
-        assert_eq!(get_calls("minus_hash").len(), 1);
-    }
-
-    #[test]
-    fn test_all_hash_shortcuts() {
-        clear_log();
-        let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
-
-        assert!(shortcuts::dispatch("#!", &mut error_buffer).is_ok());
-        assert_eq!(get_calls("hash_bang").len(), 1);
-
-        clear_log();
-        assert!(shortcuts::dispatch("#+", &mut error_buffer).is_ok());
-        assert_eq!(get_calls("hash_plus").len(), 1);
-
-        clear_log();
-        assert!(shortcuts::dispatch("#?", &mut error_buffer).is_ok());
-        assert_eq!(get_calls("hash_question").len(), 1);
-    }
-
-    #[test]
-    fn test_all_question_shortcuts() {
-        clear_log();
-        let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
-
-        assert!(shortcuts::dispatch("?!", &mut error_buffer).is_ok());
-        assert_eq!(get_calls("question_bang").len(), 1);
-
-        clear_log();
-        assert!(shortcuts::dispatch("?+", &mut error_buffer).is_ok());
-        assert_eq!(get_calls("question_plus").len(), 1);
-
-        clear_log();
-        assert!(shortcuts::dispatch("??", &mut error_buffer).is_ok());
-        assert_eq!(get_calls("question_question").len(), 1);
-    }
-
-    #[test]
-    fn test_parameter_passing() {
-        clear_log();
-        let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
-
-        shortcuts::dispatch("!+ first", &mut error_buffer).unwrap();
-        shortcuts::dispatch("!+ second", &mut error_buffer).unwrap();
-        shortcuts::dispatch("!+ third", &mut error_buffer).unwrap();
-
-        let calls = get_calls("bang_plus");
-        assert_eq!(calls.len(), 3);
-        assert_eq!(calls[0], "first");
-        assert_eq!(calls[1], "second");
-        assert_eq!(calls[2], "third");
-    }
-
-    #[test]
-    fn test_hash_question_debug() {
-        clear_log();
-        let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
-
-        // Test the exact failing case
-        let input = "#? /path/to/file";
-        let result = shortcuts::dispatch(input, &mut error_buffer);
-
-        // Check if dispatch succeeded
-        assert!(result.is_ok(), "Dispatch failed with: {:?}", result);
-
-        // Check if function was called
-        let calls = get_calls("hash_question");
-        assert!(!calls.is_empty(), "hash_question was not called at all");
-        assert_eq!(
-            calls.len(),
-            1,
-            "hash_question called {} times instead of 1",
-            calls.len()
-        );
-        assert_eq!(
-            calls[0], "/path/to/file",
-            "Wrong parameter: got '{}' expected '/path/to/file'",
-            calls[0]
-        );
-    }
-
-    #[test]
-    fn test_complex_parameters() {
-        clear_log();
-        let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
-
-        shortcuts::dispatch("++ key=value", &mut error_buffer).unwrap();
-        assert_eq!(get_calls("plus_plus"), vec!["key=value"]);
-
-        clear_log();
-        shortcuts::dispatch("-- --flag", &mut error_buffer).unwrap();
-        assert_eq!(get_calls("minus_minus"), vec!["--flag"]);
-
-        clear_log();
-        shortcuts::dispatch("#+ /path/to/file", &mut error_buffer).unwrap();
-        assert_eq!(get_calls("hash_plus"), vec!["/path/to/file"]);
-
-        clear_log();
-        shortcuts::dispatch("?! 123 456 789", &mut error_buffer).unwrap();
-        assert_eq!(get_calls("question_bang"), vec!["123 456 789"]);
-    }
-
-    #[test]
-    fn test_special_characters_in_parameters() {
-        clear_log();
-        let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
-
-        shortcuts::dispatch("!+ @#$%", &mut error_buffer).unwrap();
-        assert_eq!(get_calls("bang_plus"), vec!["@#$%"]);
-
-        clear_log();
-        shortcuts::dispatch("?? !@#$%^&*()", &mut error_buffer).unwrap();
-        assert_eq!(get_calls("question_question"), vec!["!@#$%^&*()"]);
-
-        clear_log();
-        shortcuts::dispatch("+- hello!world?", &mut error_buffer).unwrap();
-        assert_eq!(get_calls("plus_minus"), vec!["hello!world?"]);
-    }
-
-    #[test]
-    fn test_error_message_format() {
-        let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
-        let result = shortcuts::dispatch("xx", &mut error_buffer);
-        assert!(result.is_err());
-        let err = result.unwrap_err();
-        assert!(err.contains("Unknown shortcut"));
-        assert!(err.contains("xx"));
-    }
-
-    #[test]
-    fn test_sequential_dispatch() {
-        clear_log();
-        let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
-
-        assert!(shortcuts::dispatch("!+ one", &mut error_buffer).is_ok());
-        assert!(shortcuts::dispatch("++ two", &mut error_buffer).is_ok());
-        assert!(shortcuts::dispatch("-- three", &mut error_buffer).is_ok());
-        assert!(shortcuts::dispatch("#? four", &mut error_buffer).is_ok());
-
-        assert_eq!(get_calls("bang_plus"), vec!["one"]);
-        assert_eq!(get_calls("plus_plus"), vec!["two"]);
-        assert_eq!(get_calls("minus_minus"), vec!["three"]);
-        assert_eq!(get_calls("hash_question"), vec!["four"]);
-    }
-
-    #[test]
-    fn test_unicode_parameters() {
-        clear_log();
-        let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
-
-        shortcuts::dispatch("!+ 你好", &mut error_buffer).unwrap();
-        assert_eq!(get_calls("bang_plus"), vec!["你好"]);
-
-        clear_log();
-        shortcuts::dispatch("?? 🚀💻", &mut error_buffer).unwrap();
-        assert_eq!(get_calls("question_question"), vec!["🚀💻"]);
-    }
-
-    #[test]
-    fn test_empty_vs_no_parameter() {
-        clear_log();
-        let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
-
-        shortcuts::dispatch("!+", &mut error_buffer).unwrap();
-        assert_eq!(get_calls("bang_plus"), vec![""]);
-
-        clear_log();
-        shortcuts::dispatch("!+   ", &mut error_buffer).unwrap();
-        assert_eq!(get_calls("bang_plus"), vec![""]);
-    }
-
-    #[test]
-    fn test_shortcut_boundary_cases() {
-        let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
-        // Test exactly 2 characters
-        assert!(shortcuts::dispatch("!+", &mut error_buffer).is_ok());
-
-        // Test more than 2 characters (valid with param)
-        assert!(shortcuts::dispatch("!+x", &mut error_buffer).is_ok());
+    };
 
-        // Test 1 character (invalid)
-        assert!(shortcuts::dispatch("!", &mut error_buffer).is_err());
-    }
+    TokenStream::from(expanded)
 }
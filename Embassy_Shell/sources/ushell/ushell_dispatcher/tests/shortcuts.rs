@@ -0,0 +1,389 @@
+//! Tests of a shortcut dispatcher generated from `tests/shortcuts.cfg`.
+//!
+//! They live here rather than in `shortcutsgen`: a proc-macro crate cannot expand its own
+//! macros, but its integration tests can.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+// Error buffer size for tests - configurable
+const ERROR_BUFFER_SIZE: usize = 32;
+
+// Calls of each handler. Every test runs on its own thread, so its log is its own.
+thread_local! {
+    static CALL_LOG: RefCell<HashMap<String, Vec<String>>> = RefCell::new(HashMap::new());
+}
+
+fn record_call(func_name: &str, param: &str) {
+    CALL_LOG.with(|log| {
+        log.borrow_mut()
+            .entry(func_name.to_string())
+            .or_default()
+            .push(param.to_string())
+    });
+}
+
+fn get_calls(func_name: &str) -> Vec<String> {
+    CALL_LOG.with(|log| log.borrow().get(func_name).cloned().unwrap_or_default())
+}
+
+fn clear_log() {
+    CALL_LOG.with(|log| log.borrow_mut().clear());
+}
+
+// Test handler functions
+mod test_handlers {
+    use super::record_call;
+
+    pub fn bang_plus(param: &str) {
+        record_call("bang_plus", param);
+    }
+    pub fn bang_minus(param: &str) {
+        record_call("bang_minus", param);
+    }
+    pub fn bang_hash(param: &str) {
+        record_call("bang_hash", param);
+    }
+
+    pub fn plus_plus(param: &str) {
+        record_call("plus_plus", param);
+    }
+    pub fn plus_minus(param: &str) {
+        record_call("plus_minus", param);
+    }
+    pub fn plus_hash(param: &str) {
+        record_call("plus_hash", param);
+    }
+
+    pub fn minus_plus(param: &str) {
+        record_call("minus_plus", param);
+    }
+    pub fn minus_minus(param: &str) {
+        record_call("minus_minus", param);
+    }
+    pub fn minus_hash(param: &str) {
+        record_call("minus_hash", param);
+    }
+
+    pub fn hash_bang(param: &str) {
+        record_call("hash_bang", param);
+    }
+    pub fn hash_plus(param: &str) {
+        record_call("hash_plus", param);
+    }
+    pub fn hash_question(param: &str) {
+        record_call("hash_question", param);
+    }
+
+    pub fn question_bang(param: &str) {
+        record_call("question_bang", param);
+    }
+    pub fn question_plus(param: &str) {
+        record_call("question_plus", param);
+    }
+    pub fn question_question(param: &str) {
+        record_call("question_question", param);
+    }
+}
+
+ushell_dispatcher::generate_shortcuts_dispatcher! {
+    mod shortcuts;
+    error_buffer_size = super::ERROR_BUFFER_SIZE;
+    path = "tests/shortcuts.cfg"
+}
+
+#[test]
+fn test_is_supported_shortcut() {
+    assert!(shortcuts::is_supported_shortcut("!"));
+    assert!(shortcuts::is_supported_shortcut("+"));
+    assert!(shortcuts::is_supported_shortcut("-"));
+    assert!(shortcuts::is_supported_shortcut("#"));
+    assert!(shortcuts::is_supported_shortcut("?"));
+    assert!(!shortcuts::is_supported_shortcut("x"));
+    assert!(!shortcuts::is_supported_shortcut(""));
+}
+
+#[test]
+fn test_get_shortcuts() {
+    let shortcuts_str = shortcuts::get_shortcuts();
+    assert!(shortcuts_str.contains("!+"));
+    assert!(shortcuts_str.contains("!-"));
+    assert!(shortcuts_str.contains("!#"));
+    assert!(shortcuts_str.contains("++"));
+    assert!(shortcuts_str.contains("+-"));
+    assert!(shortcuts_str.contains("+#"));
+    assert!(shortcuts_str.contains("-+"));
+    assert!(shortcuts_str.contains("--"));
+    assert!(shortcuts_str.contains("-#"));
+    assert!(shortcuts_str.contains("#!"));
+    assert!(shortcuts_str.contains("#+"));
+    assert!(shortcuts_str.contains("#?"));
+    assert!(shortcuts_str.contains("?!"));
+    assert!(shortcuts_str.contains("?+"));
+    assert!(shortcuts_str.contains("??"));
+}
+
+#[test]
+fn test_invalid_shortcut() {
+    let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
+    let result = shortcuts::dispatch("xx", &mut error_buffer);
+    assert!(result.is_err());
+    assert!(result.unwrap_err().contains("Unknown shortcut"));
+}
+
+#[test]
+fn test_all_bang_shortcuts() {
+    clear_log();
+    let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
+
+    assert!(shortcuts::dispatch("!+", &mut error_buffer).is_ok());
+    assert_eq!(get_calls("bang_plus").len(), 1);
+
+    clear_log();
+    assert!(shortcuts::dispatch("!-", &mut error_buffer).is_ok());
+    assert_eq!(get_calls("bang_minus").len(), 1);
+
+    clear_log();
+    assert!(shortcuts::dispatch("!#", &mut error_buffer).is_ok());
+    assert_eq!(get_calls("bang_hash").len(), 1);
+}
+
+#[test]
+fn test_all_plus_shortcuts() {
+    clear_log();
+    let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
+
+    assert!(shortcuts::dispatch("++", &mut error_buffer).is_ok());
+    assert_eq!(get_calls("plus_plus").len(), 1);
+
+    clear_log();
+    assert!(shortcuts::dispatch("+-", &mut error_buffer).is_ok());
+    assert_eq!(get_calls("plus_minus").len(), 1);
+
+    clear_log();
+    assert!(shortcuts::dispatch("+#", &mut error_buffer).is_ok());
+    assert_eq!(get_calls("plus_hash").len(), 1);
+}
+
+#[test]
+fn test_all_minus_shortcuts() {
+    clear_log();
+    let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
+
+    assert!(shortcuts::dispatch("-+", &mut error_buffer).is_ok());
+    assert_eq!(get_calls("minus_plus").len(), 1);
+
+    clear_log();
+    assert!(shortcuts::dispatch("--", &mut error_buffer).is_ok());
+    assert_eq!(get_calls("minus_minus").len(), 1);
+
+    clear_log();
+    assert!(shortcuts::dispatch("-#", &mut error_buffer).is_ok());
+    assert_eq!(get_calls("minus_hash").len(), 1);
+}
+
+#[test]
+fn test_all_hash_shortcuts() {
+    clear_log();
+    let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
+
+    assert!(shortcuts::dispatch("#!", &mut error_buffer).is_ok());
+    assert_eq!(get_calls("hash_bang").len(), 1);
+
+    clear_log();
+    assert!(shortcuts::dispatch("#+", &mut error_buffer).is_ok());
+    assert_eq!(get_calls("hash_plus").len(), 1);
+
+    clear_log();
+    assert!(shortcuts::dispatch("#?", &mut error_buffer).is_ok());
+    assert_eq!(get_calls("hash_question").len(), 1);
+}
+
+#[test]
+fn test_all_question_shortcuts() {
+    clear_log();
+    let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
+
+    assert!(shortcuts::dispatch("?!", &mut error_buffer).is_ok());
+    assert_eq!(get_calls("question_bang").len(), 1);
+
+    clear_log();
+    assert!(shortcuts::dispatch("?+", &mut error_buffer).is_ok());
+    assert_eq!(get_calls("question_plus").len(), 1);
+
+    clear_log();
+    assert!(shortcuts::dispatch("??", &mut error_buffer).is_ok());
+    assert_eq!(get_calls("question_question").len(), 1);
+}
+
+#[test]
+fn test_parameter_passing() {
+    clear_log();
+    let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
+
+    shortcuts::dispatch("!+ first", &mut error_buffer).unwrap();
+    shortcuts::dispatch("!+ second", &mut error_buffer).unwrap();
+    shortcuts::dispatch("!+ third", &mut error_buffer).unwrap();
+
+    let calls = get_calls("bang_plus");
+    assert_eq!(calls.len(), 3);
+    assert_eq!(calls[0], "first");
+    assert_eq!(calls[1], "second");
+    assert_eq!(calls[2], "third");
+}
+
+#[test]
+fn test_hash_question_debug() {
+    clear_log();
+    let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
+
+    // Test the exact failing case
+    let input = "#? /path/to/file";
+    let result = shortcuts::dispatch(input, &mut error_buffer);
+
+    // Check if dispatch succeeded
+    assert!(result.is_ok(), "Dispatch failed with: {:?}", result);
+
+    // Check if function was called
+    let calls = get_calls("hash_question");
+    assert!(!calls.is_empty(), "hash_question was not called at all");
+    assert_eq!(
+        calls.len(),
+        1,
+        "hash_question called {} times instead of 1",
+        calls.len()
+    );
+    assert_eq!(
+        calls[0], "/path/to/file",
+        "Wrong parameter: got '{}' expected '/path/to/file'",
+        calls[0]
+    );
+}
+
+#[test]
+fn test_complex_parameters() {
+    clear_log();
+    let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
+
+    shortcuts::dispatch("++ key=value", &mut error_buffer).unwrap();
+    assert_eq!(get_calls("plus_plus"), vec!["key=value"]);
+
+    clear_log();
+    shortcuts::dispatch("-- --flag", &mut error_buffer).unwrap();
+    assert_eq!(get_calls("minus_minus"), vec!["--flag"]);
+
+    clear_log();
+    shortcuts::dispatch("#+ /path/to/file", &mut error_buffer).unwrap();
+    assert_eq!(get_calls("hash_plus"), vec!["/path/to/file"]);
+
+    clear_log();
+    shortcuts::dispatch("?! 123 456 789", &mut error_buffer).unwrap();
+    assert_eq!(get_calls("question_bang"), vec!["123 456 789"]);
+}
+
+#[test]
+fn test_special_characters_in_parameters() {
+    clear_log();
+    let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
+
+    shortcuts::dispatch("!+ @#$%", &mut error_buffer).unwrap();
+    assert_eq!(get_calls("bang_plus"), vec!["@#$%"]);
+
+    clear_log();
+    shortcuts::dispatch("?? !@#$%^&*()", &mut error_buffer).unwrap();
+    assert_eq!(get_calls("question_question"), vec!["!@#$%^&*()"]);
+
+    clear_log();
+    shortcuts::dispatch("+- hello!world?", &mut error_buffer).unwrap();
+    assert_eq!(get_calls("plus_minus"), vec!["hello!world?"]);
+}
+
+#[test]
+fn test_error_message_format() {
+    let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
+    let result = shortcuts::dispatch("xx", &mut error_buffer);
+    assert!(result.is_err());
+    let err = result.unwrap_err();
+    assert!(err.contains("Unknown shortcut"));
+    assert!(err.contains("xx"));
+}
+
+#[test]
+fn test_sequential_dispatch() {
+    clear_log();
+    let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
+
+    assert!(shortcuts::dispatch("!+ one", &mut error_buffer).is_ok());
+    assert!(shortcuts::dispatch("++ two", &mut error_buffer).is_ok());
+    assert!(shortcuts::dispatch("-- three", &mut error_buffer).is_ok());
+    assert!(shortcuts::dispatch("#? four", &mut error_buffer).is_ok());
+
+    assert_eq!(get_calls("bang_plus"), vec!["one"]);
+    assert_eq!(get_calls("plus_plus"), vec!["two"]);
+    assert_eq!(get_calls("minus_minus"), vec!["three"]);
+    assert_eq!(get_calls("hash_question"), vec!["four"]);
+}
+
+#[test]
+fn test_unicode_parameters() {
+    clear_log();
+    let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
+
+    shortcuts::dispatch("!+ 你好", &mut error_buffer).unwrap();
+    assert_eq!(get_calls("bang_plus"), vec!["你好"]);
+
+    clear_log();
+    shortcuts::dispatch("?? 🚀💻", &mut error_buffer).unwrap();
+    assert_eq!(get_calls("question_question"), vec!["🚀💻"]);
+}
+
+#[test]
+fn test_empty_vs_no_parameter() {
+    clear_log();
+    let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
+
+    shortcuts::dispatch("!+", &mut error_buffer).unwrap();
+    assert_eq!(get_calls("bang_plus"), vec![""]);
+
+    clear_log();
+    shortcuts::dispatch("!+   ", &mut error_buffer).unwrap();
+    assert_eq!(get_calls("bang_plus"), vec![""]);
+}
+
+#[test]
+fn test_repeat_count() {
+    clear_log();
+    let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
+
+    assert!(shortcuts::is_supported_shortcut("12!+"));
+    assert!(!shortcuts::is_supported_shortcut("12"));
+
+    shortcuts::dispatch("3!+ x", &mut error_buffer).unwrap();
+    assert_eq!(get_calls("bang_plus"), vec!["x", "x", "x"]);
+
+    let result = shortcuts::dispatch("0!+", &mut error_buffer);
+    assert_eq!(result, Err("Bad repeat count: 0"));
+    let line = format!("{}!+", shortcuts::MAX_REPEAT + 1);
+    let result = shortcuts::dispatch(&line, &mut error_buffer);
+    assert_eq!(result, Err("Repeat count above 1000"));
+    let result = shortcuts::dispatch("99999999999!+", &mut error_buffer);
+    assert_eq!(result, Err("Repeat count above 1000"));
+    assert_eq!(get_calls("bang_plus").len(), 3);
+
+    let line = format!("{}!+", shortcuts::MAX_REPEAT);
+    shortcuts::dispatch(&line, &mut error_buffer).unwrap();
+    assert_eq!(get_calls("bang_plus").len(), 3 + 1000);
+}
+
+#[test]
+fn test_shortcut_boundary_cases() {
+    let mut error_buffer = heapless::String::<ERROR_BUFFER_SIZE>::new();
+    // Test exactly 2 characters
+    assert!(shortcuts::dispatch("!+", &mut error_buffer).is_ok());
+
+    // Test more than 2 characters (valid with param)
+    assert!(shortcuts::dispatch("!+x", &mut error_buffer).is_ok());
+
+    // Test 1 character (invalid)
+    assert!(shortcuts::dispatch("!", &mut error_buffer).is_err());
+}
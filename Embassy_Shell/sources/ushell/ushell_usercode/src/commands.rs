@@ -1,6 +1,9 @@
 #![allow(non_snake_case)]
 
-use ushell2::log_info;
+use core::sync::atomic::{AtomicU32, Ordering};
+use ushell2::heapless::String;
+use ushell2::autocomplete::{complete_path, CompletionRegistry};
+use ushell2::{confirm, log_error, log_info, read_hidden_line};
 
 pub fn init() {
     log_info!("init | no-args");
@@ -49,6 +52,38 @@ pub fn led(onoff: bool) {
     }
 }
 
+/// Two-level commands: `gpio set` and `gpio read` (the `gpio { ... }` block in commands.cfg).
+pub mod gpio {
+    use core::fmt::Write;
+    use core::sync::atomic::{AtomicU32, Ordering};
+    use ushell2::heapless::String;
+    use ushell2::log_info;
+
+    /// Levels set with `gpio set`, one bit per pin (pins 0..=31).
+    static LEVELS: AtomicU32 = AtomicU32::new(0);
+
+    pub fn set(pin: u8, level: bool) {
+        log_info!("gpio set | pin: {} level: {}", pin, level as u8);
+        let bit = 1u32.checked_shl(pin as u32).unwrap_or(0);
+        if level {
+            LEVELS.fetch_or(bit, Ordering::Relaxed);
+        } else {
+            LEVELS.fetch_and(!bit, Ordering::Relaxed);
+        }
+        // Pins driven so far become Tab candidates for `gpio read`
+        let mut text: String<3> = String::new();
+        let _ = write!(text, "{}", pin);
+        super::COMPLETIONS.add("gpio read", 0, &text);
+    }
+
+    /// Returned to the shell, which prints it (`B->t` descriptor).
+    pub fn read(pin: u8) -> bool {
+        log_info!("gpio read | pin: {}", pin);
+        let bit = 1u32.checked_shl(pin as u32).unwrap_or(0);
+        LEVELS.load(Ordering::Relaxed) & bit != 0
+    }
+}
+
 pub fn greeting(s1: &str, s2: &str) {
     log_info!("greeting | [{}] : [{}]", s1, s2);
 }
@@ -68,3 +103,73 @@ pub fn bstring(s: &str) {
 pub fn cstring(s: &str) {
     log_info!("cstring | {}", s);
 }
+
+pub fn wipe() {
+    if !confirm("All settings will be erased.") {
+        return;
+    }
+    log_info!("wipe | settings erased");
+}
+
+pub fn join(ssid: &str) {
+    let mut password: String<32> = String::new();
+    if !read_hidden_line("Password: ", &mut password) {
+        return;
+    }
+    log_info!("join | ssid: {} | password: {} chars", ssid, password.len());
+}
+
+pub fn set(key: &str, value: &str) {
+    log_info!("set | {} = {}", key, value);
+}
+
+/// Rollback of `set` (run by `abort` inside a transaction).
+pub fn unset(key: &str, value: &str) {
+    log_info!("unset | {} = {} undone", key, value);
+}
+
+static ADC_SAMPLES: AtomicU32 = AtomicU32::new(0);
+
+/// One simulated ADC reading per call (a sawtooth).
+fn adc_sample() -> bool {
+    let n = ADC_SAMPLES.fetch_add(1, Ordering::Relaxed);
+    log_info!("adc | sample {}: {}", n, (n * 64) % 4096);
+    true
+}
+
+/// Streams ADC readings until `q` is pressed (Space pauses).
+pub fn adcwatch(interval_ms: u32) {
+    if let Err(e) = ushell2::stream::start(adc_sample, interval_ms) {
+        log_error!("adcwatch | {}", e.message());
+    }
+}
+
+/// Children of a node of the settings tree (`net.wifi.ssid`, ...).
+fn settings_children(parent: &str, emit: &mut dyn FnMut(&str)) {
+    let names: &[&str] = match parent {
+        "" => &["net", "log"],
+        "net" => &["wifi", "eth"],
+        "net.wifi" => &["ssid", "channel"],
+        "net.eth" => &["dhcp", "ip"],
+        "log" => &["level", "color"],
+        _ => &[],
+    };
+    names.iter().for_each(|name| emit(name));
+}
+
+/// Completion values registered by the commands at runtime (see `gpio::set`).
+pub static COMPLETIONS: CompletionRegistry<2, 8, 3> = CompletionRegistry::new();
+
+/// Argument completion: settings keys for `set`.
+pub fn complete_arg(command: &str, arg_index: usize, word: &str, offer: &mut dyn FnMut(&str)) {
+    if command == "set" && arg_index == 0 {
+        complete_path(settings_children, word, offer);
+    }
+}
+
+/// Snippets expanded with F2 after typing their name; `<cursor>` marks where the cursor goes.
+pub const SNIPPETS: &[(&str, &str)] = &[
+    ("jn", "join <cursor>"),
+    ("sw", "set net.wifi.<cursor> "),
+    ("ll", "set log.level <cursor>"),
+];
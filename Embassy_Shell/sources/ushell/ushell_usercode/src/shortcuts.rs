@@ -31,8 +31,12 @@ pub fn shortcut_dot_z(param: &str) {
     log_info!("Executing .z with param: '{}'", param);
 }
 
-pub fn shortcut_dot_k(param: &str) {
+pub fn shortcut_dot_k(param: &str) -> Result<(), &'static str> {
+    if param.is_empty() {
+        return Err(".k requires a parameter");
+    }
     log_info!("Executing .k with param: '{}'", param);
+    Ok(())
 }
 
 pub fn shortcut_minus_dot(param: &str) {
@@ -0,0 +1,187 @@
+//! # fault_capture
+//!
+//! Keeps the cause of the last HardFault across the reset that follows it.
+//!
+//! ## Responsibilities
+//! - [`capture`] — called from the HardFault handler, copies the fault status
+//!   registers (CFSR, HFSR, MMFAR, BFAR) and the stacked exception frame into
+//!   a [`FaultRecord`] in a reserved RAM region.
+//! - [`last_fault`] / [`clear`] — read back and forget the record after the
+//!   reset.
+//! - [`FaultRecord::causes`] — names and describes the status bits that are
+//!   set, for a report on the shell.
+//!
+//! The record lives in cortex-m-rt's `.uninit` section, which the startup
+//! code neither zeroes nor initializes, so it survives a system reset (not a
+//! power cycle).  A magic word and a checksum tell a record from the random
+//! content of RAM after power-up.
+//!
+//! ```ignore
+//! #[cortex_m_rt::exception]
+//! unsafe fn HardFault(frame: &cortex_m_rt::ExceptionFrame) -> ! {
+//!     fault_capture::capture(frame);
+//!     cortex_m::peripheral::SCB::sys_reset();
+//! }
+//! ```
+//!
+//! ## What this crate does NOT do
+//! - It does not install the HardFault handler (that stays in `main.rs`).
+//! - It does not print anything or know about the shell.
+
+#![no_std]
+
+use core::mem::MaybeUninit;
+use core::ptr::{addr_of, addr_of_mut};
+
+use cortex_m::peripheral::SCB;
+pub use cortex_m_rt::ExceptionFrame;
+
+/// Marks a written record ("FALT").
+const MAGIC: u32 = 0x4641_4C54;
+
+/// CFSR: MMFAR holds the address of the memory management fault.
+const CFSR_MMARVALID: u32 = 1 << 7;
+/// CFSR: BFAR holds the address of the bus fault.
+const CFSR_BFARVALID: u32 = 1 << 15;
+
+/// Status registers and stacked frame of a HardFault.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct FaultRecord {
+    /// Configurable Fault Status Register (MMFSR, BFSR, UFSR).
+    pub cfsr:  u32,
+    /// HardFault Status Register.
+    pub hfsr:  u32,
+    /// MemManage Fault Address Register, see [`FaultRecord::mmfar`].
+    pub mmfar: u32,
+    /// BusFault Address Register, see [`FaultRecord::bfar`].
+    pub bfar:  u32,
+    pub r0:    u32,
+    pub r1:    u32,
+    pub r2:    u32,
+    pub r3:    u32,
+    pub r12:   u32,
+    pub lr:    u32,
+    /// Address of the faulting instruction (or the next one, for imprecise
+    /// bus faults).
+    pub pc:    u32,
+    pub xpsr:  u32,
+}
+
+/// Status bits reported by [`FaultRecord::causes`]: register, bit, name and
+/// description.
+const CAUSES: [(Register, u32, &str, &str); 20] = [
+    (Register::Hfsr, 1,  "VECTTBL",     "vector table read fault"),
+    (Register::Hfsr, 30, "FORCED",      "escalated configurable fault"),
+    (Register::Hfsr, 31, "DEBUGEVT",    "debug event"),
+    (Register::Cfsr, 0,  "IACCVIOL",    "instruction access violation"),
+    (Register::Cfsr, 1,  "DACCVIOL",    "data access violation"),
+    (Register::Cfsr, 3,  "MUNSTKERR",   "MemManage fault on unstacking"),
+    (Register::Cfsr, 4,  "MSTKERR",     "MemManage fault on stacking"),
+    (Register::Cfsr, 5,  "MLSPERR",     "MemManage fault on FP lazy state"),
+    (Register::Cfsr, 8,  "IBUSERR",     "instruction bus error"),
+    (Register::Cfsr, 9,  "PRECISERR",   "precise data bus error"),
+    (Register::Cfsr, 10, "IMPRECISERR", "imprecise data bus error"),
+    (Register::Cfsr, 11, "UNSTKERR",    "bus fault on unstacking"),
+    (Register::Cfsr, 12, "STKERR",      "bus fault on stacking"),
+    (Register::Cfsr, 13, "LSPERR",      "bus fault on FP lazy state"),
+    (Register::Cfsr, 16, "UNDEFINSTR",  "undefined instruction"),
+    (Register::Cfsr, 17, "INVSTATE",    "invalid state (Thumb bit clear)"),
+    (Register::Cfsr, 18, "INVPC",       "invalid EXC_RETURN"),
+    (Register::Cfsr, 19, "NOCP",        "coprocessor access"),
+    (Register::Cfsr, 24, "UNALIGNED",   "unaligned access"),
+    (Register::Cfsr, 25, "DIVBYZERO",   "division by zero"),
+];
+
+#[derive(Clone, Copy)]
+enum Register {
+    Cfsr,
+    Hfsr,
+}
+
+impl FaultRecord {
+    /// Name and description of each status bit that is set, HardFault bits
+    /// first (the address-valid bits are reported by [`FaultRecord::mmfar`]
+    /// and [`FaultRecord::bfar`]).
+    pub fn causes(&self) -> impl Iterator<Item = (&'static str, &'static str)> + '_ {
+        CAUSES
+            .iter()
+            .filter(|&&(register, bit, _, _)| {
+                let value = match register {
+                    Register::Cfsr => self.cfsr,
+                    Register::Hfsr => self.hfsr,
+                };
+                value & (1 << bit) != 0
+            })
+            .map(|&(_, _, name, description)| (name, description))
+    }
+
+    /// Address of the MemManage fault, if MMFAR holds one.
+    pub fn mmfar(&self) -> Option<u32> {
+        (self.cfsr & CFSR_MMARVALID != 0).then_some(self.mmfar)
+    }
+
+    /// Address of the bus fault, if BFAR holds one.
+    pub fn bfar(&self) -> Option<u32> {
+        (self.cfsr & CFSR_BFARVALID != 0).then_some(self.bfar)
+    }
+
+    fn checksum(&self) -> u32 {
+        [
+            self.cfsr, self.hfsr, self.mmfar, self.bfar, self.r0, self.r1,
+            self.r2, self.r3, self.r12, self.lr, self.pc, self.xpsr,
+        ]
+        .iter()
+        .fold(MAGIC, |sum, &word| sum.rotate_left(5) ^ word)
+    }
+}
+
+/// The record with its validity marks, as stored in RAM.
+#[repr(C)]
+struct Slot {
+    magic:    u32,
+    record:   FaultRecord,
+    checksum: u32,
+}
+
+// Not zeroed by the startup code: survives a reset
+#[link_section = ".uninit.fault_capture"]
+static mut SLOT: MaybeUninit<Slot> = MaybeUninit::uninit();
+
+/// Records the fault status registers and the stacked `frame`, replacing a
+/// previous record.  Call it from the HardFault handler, before the reset.
+pub fn capture(frame: &ExceptionFrame) {
+    // Safety: read-only access to the SCB fault registers
+    let scb = unsafe { &*SCB::PTR };
+    let record = FaultRecord {
+        cfsr:  scb.cfsr.read(),
+        hfsr:  scb.hfsr.read(),
+        mmfar: scb.mmfar.read(),
+        bfar:  scb.bfar.read(),
+        r0:    frame.r0(),
+        r1:    frame.r1(),
+        r2:    frame.r2(),
+        r3:    frame.r3(),
+        r12:   frame.r12(),
+        lr:    frame.lr(),
+        pc:    frame.pc(),
+        xpsr:  frame.xpsr(),
+    };
+    let slot = Slot { magic: MAGIC, checksum: record.checksum(), record };
+    // Safety: the slot is plain words, only written here and by `clear`
+    unsafe { addr_of_mut!(SLOT).cast::<Slot>().write_volatile(slot) };
+}
+
+/// The record kept by [`capture`], if the RAM holds a valid one.
+pub fn last_fault() -> Option<FaultRecord> {
+    // Safety: every bit pattern is a valid `Slot` of plain words; the magic
+    // and the checksum reject what was not written by `capture`
+    let slot = unsafe { addr_of!(SLOT).cast::<Slot>().read_volatile() };
+    (slot.magic == MAGIC && slot.checksum == slot.record.checksum()).then_some(slot.record)
+}
+
+/// Forgets the record.
+pub fn clear() {
+    // Safety: see `capture`
+    unsafe { addr_of_mut!(SLOT).cast::<u32>().write_volatile(0) };
+}
@@ -0,0 +1,76 @@
+//! HardFault capture and the `fault` shell command.
+//!
+//! The HardFault handler records the fault status registers and the stacked
+//! frame with `fault_capture` and resets the MCU.  After the reset the shell
+//! task reports the fault once, and `fault show` / `fault clear` (registered
+//! in the runtime command registry) print or forget it:
+//!
+//! ```text
+//! >> fault show
+//! HardFault  HFSR 0x40000000  CFSR 0x00008200
+//!   FORCED       escalated configurable fault
+//!   PRECISERR    precise data bus error
+//!   BFAR 0x2003FFF0
+//! PC 0x08001A3C  LR 0x08001A21  xPSR 0x21000000
+//! R0 0x2003FFF0  R1 0x00000001  R2 0x00000000  R3 0x00000000  R12 0x00000000
+//! ```
+
+use cortex_m::peripheral::SCB;
+use cortex_m_rt::{exception, ExceptionFrame};
+use ushell2::registry::DynCommandRegistry;
+use ushell2::{log_error, log_simple};
+
+#[exception]
+unsafe fn HardFault(frame: &ExceptionFrame) -> ! {
+    fault_capture::capture(frame);
+    SCB::sys_reset();
+}
+
+/// Adds the `fault` command to `registry`.
+pub fn register<const N: usize>(registry: &DynCommandRegistry<N>) {
+    registry.register("fault", fault).ok();
+}
+
+/// Logs the fault recorded before the last reset, if any; called once at
+/// start-up.
+pub fn report_at_boot() {
+    if let Some(record) = fault_capture::last_fault() {
+        log_error!("Reset after a HardFault at PC 0x{:08X} (see 'fault show')", record.pc);
+    }
+}
+
+/// The `fault` command: `fault [show]` prints the recorded fault, `fault
+/// clear` forgets it.
+fn fault(args: &str) -> Result<(), &'static str> {
+    match args {
+        "" | "show" => show(),
+        "clear"     => {
+            fault_capture::clear();
+            Ok(())
+        }
+        _           => Err("usage: fault [show|clear]"),
+    }
+}
+
+fn show() -> Result<(), &'static str> {
+    let Some(record) = fault_capture::last_fault() else {
+        log_simple!("no fault recorded");
+        return Ok(());
+    };
+    log_simple!("HardFault  HFSR 0x{:08X}  CFSR 0x{:08X}", record.hfsr, record.cfsr);
+    for (name, description) in record.causes() {
+        log_simple!("  {:<12} {}", name, description);
+    }
+    if let Some(address) = record.mmfar() {
+        log_simple!("  MMFAR 0x{:08X}", address);
+    }
+    if let Some(address) = record.bfar() {
+        log_simple!("  BFAR 0x{:08X}", address);
+    }
+    log_simple!("PC 0x{:08X}  LR 0x{:08X}  xPSR 0x{:08X}", record.pc, record.lr, record.xpsr);
+    log_simple!(
+        "R0 0x{:08X}  R1 0x{:08X}  R2 0x{:08X}  R3 0x{:08X}  R12 0x{:08X}",
+        record.r0, record.r1, record.r2, record.r3, record.r12
+    );
+    Ok(())
+}
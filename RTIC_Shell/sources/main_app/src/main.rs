@@ -1,7 +1,7 @@
 #![no_std]
 #![no_main]
 
-use heapless::{Deque, spsc::Queue};
+use heapless::spsc::Queue;
 
 use panic_halt as _;
 use rtic::app;
@@ -9,14 +9,14 @@ use stm32f4xx_hal::{
     pac,
     prelude::*,
     gpio::{Output, PushPull, Pin},
-    serial::{Config as SerialConfig, Serial},
+    serial::{config::DmaConfig, Config as SerialConfig, Serial},
     timer::{Flag as TimerFlag, CounterHz, Timer},
 };
 use uart_hal::{
-    RX_QUEUE_SIZE, TX_BUFFER_SIZE,
-    UartTx, UartRx,
+    RX_QUEUE_SIZE,
+    UartTx, UartRx, TxBuffer,
+    handle_rx_byte,
     handle_tx_ready,
-    init_uart_globals,
     LOGGER_WRITER,
     RxQueueReader,
 };
@@ -27,6 +27,10 @@ use ushell_usercode::shortcuts as us;
 use ushell2::{log_info, log_simple};
 use ushell2::logger::{init_logger, LogLevel, LoggerConfig};
 use ushell_ctx::{ShellCtx, ShellConfig};
+use uart_hal::FrameFormat;
+use ushell2::registry::DynCommandRegistry;
+
+mod faultinfo;
 
 // Shell configuration constants
 pub const PROMPT:                &str  = ">> ";
@@ -59,9 +63,49 @@ type MyShell = ShellCtx<
     { MAX_ERROR_BUFFER_SIZE             }, // E   — error message buffer size
 >;
 
+/// Commands registered at runtime, next to the generated `commands` table;
+/// the shell's built-in names are refused.
+static DYN_COMMANDS: DynCommandRegistry<4> = DynCommandRegistry::reserving(ushell_ctx::BUILTINS);
+
 static LED_TOGGLE_COUNT: core::sync::atomic::AtomicU32 =
     core::sync::atomic::AtomicU32::new(0);
 
+/// Time passed since the shell task last called `ShellCtx::tick`, counted by
+/// the TIM2 interrupt (runs `watch` and the idle auto-lock).
+static SHELL_ELAPSED_MS: core::sync::atomic::AtomicU32 =
+    core::sync::atomic::AtomicU32::new(0);
+
+// Console TX: DMA1 stream 6 channel 4 (USART2_TX), or the byte-per-interrupt
+// fallback with the `irq-tx` feature
+#[cfg(not(feature = "irq-tx"))]
+type ConsoleTransport = uart_hal::UartDmaTransport;
+#[cfg(feature = "irq-tx")]
+type ConsoleTransport = uart_hal::UartTransport;
+
+/// Flash of the STM32F411RE, the only range `load` accepts.
+const FLASH: core::ops::Range<u32> = 0x0800_0000..0x0808_0000;
+
+/// `load ihex|srec`: the records must fall within the flash.
+fn load_range(addr: u32, len: usize) -> bool {
+    addr >= FLASH.start && addr.saturating_add(len as u32) <= FLASH.end
+}
+
+/// `load ihex|srec` data: only checked here, programming the flash is left to
+/// the application.
+fn load_write(_addr: u32, _data: &[u8]) -> Result<(), &'static str> {
+    Ok(())
+}
+
+/// Regions the `md` / `mw*` / `mfill` built-ins may touch: the SRAM, the GPIO
+/// ports and, read-only, the flash. Other peripherals are left out: reading
+/// the USART data register, for one, clears RXNE and loses shell input.
+fn mem_access(addr: usize, len: usize, write: bool) -> bool {
+    let within = |start: usize, size: usize| addr >= start && addr + len <= start + size;
+    within(0x2000_0000, 128 * 1024)
+        || within(0x4002_0000, 0x2000)
+        || (!write && within(FLASH.start as usize, FLASH.len()))
+}
+
 
 // ============================================================================
 // RTIC application
@@ -73,7 +117,7 @@ mod app {
     #[shared]
     struct Shared {
         uart_tx:       UartTx,
-        tx_buffer:     Deque<u8, TX_BUFFER_SIZE>,
+        tx_buffer:     TxBuffer,
         rx_queue:      Queue<u8, RX_QUEUE_SIZE>,
         shell_pending: bool,
     }
@@ -91,7 +135,12 @@ mod app {
     // -----------------------------------------------------------------------
     #[init]
     fn init(ctx: init::Context) -> (Shared, Local) {
-        let dp = ctx.device;
+        let dp     = ctx.device;
+        let mut cp = ctx.core;
+
+        // Cycle counter timing the commands for the `stats` built-in
+        cp.DCB.enable_trace();
+        cp.DWT.enable_cycle_counter();
 
         let rcc    = dp.RCC.constrain();
         let clocks = rcc.cfgr
@@ -103,11 +152,20 @@ mod app {
         let gpioc = dp.GPIOC.split();
         let led   = gpioc.pc13.into_push_pull_output();
 
+        // DMA1 feeds the console TX (see dma1_stream6_isr)
+        #[cfg(not(feature = "irq-tx"))]
+        let dma_config = {
+            unsafe { <pac::DMA1 as stm32f4xx_hal::rcc::Enable>::enable_unchecked() };
+            DmaConfig::Tx
+        };
+        #[cfg(feature = "irq-tx")]
+        let dma_config = DmaConfig::None;
+
         let gpioa  = dp.GPIOA.split();
         let serial = Serial::new(
             dp.USART2,
             (gpioa.pa2.into_alternate(), gpioa.pa3.into_alternate()),
-            SerialConfig::default().baudrate(115200.bps()),
+            SerialConfig::default().baudrate(115200.bps()).dma(dma_config),
             &clocks,
         ).unwrap();
 
@@ -118,7 +176,7 @@ mod app {
         blink_timer.start(1.Hz()).unwrap();
         blink_timer.listen(stm32f4xx_hal::timer::Event::Update);
 
-        let tx_buffer: Deque<u8, TX_BUFFER_SIZE> = Deque::new();
+        let tx_buffer: TxBuffer                 = TxBuffer::new();
         let rx_queue:  Queue<u8, RX_QUEUE_SIZE>  = Queue::new();
 
         // Wire logger — write_bytes is a no-op until init_uart_globals runs
@@ -139,9 +197,24 @@ mod app {
             is_shortcut:         shortcuts::is_supported_shortcut,
             command_dispatcher:  commands::dispatch,
             shortcut_dispatcher: shortcuts::dispatch,
+            dyn_dispatcher:      Some(|line| DYN_COMMANDS.dispatch(line)),
+            on_output:           None,
+            frame_format:        FrameFormat::Slip,
+            on_frame:            None,
+            check_password:      None,
+            auto_lock_ms:        None,
+            tick_source:         Some(cortex_m::peripheral::DWT::cycle_count),
+            load_range:          Some(load_range),
+            load_write:          Some(load_write),
+            mem_access:          Some(mem_access),
+            // Commands run in shell_task; a CommandQueue would need an
+            // executor task below it (shell_task is at the lowest priority)
+            submit:              None,
             prompt:              PROMPT,
         });
 
+        faultinfo::register(&DYN_COMMANDS);
+
         shell_task::spawn().ok();
 
         (
@@ -163,7 +236,7 @@ mod app {
         if ctx.local.uart_rx.is_rx_not_empty() {
             match ctx.local.uart_rx.read() {
                 Ok(byte) => {
-                    ctx.shared.rx_queue.lock(|q| { let _ = q.enqueue(byte); });
+                    ctx.shared.rx_queue.lock(|q| { handle_rx_byte(q, byte); });
                     ctx.shared.shell_pending.lock(|pending| {
                         if !*pending {
                             *pending = true;
@@ -182,6 +255,14 @@ mod app {
         });
     }
 
+    // -----------------------------------------------------------------------
+    // DMA1 stream 6 ISR — next console TX chunk (never fires with `irq-tx`)
+    // -----------------------------------------------------------------------
+    #[task(binds = DMA1_STREAM6, priority = 3)]
+    fn dma1_stream6_isr(_: dma1_stream6_isr::Context) {
+        uart_hal::handle_dma_tx_complete();
+    }
+
     // -----------------------------------------------------------------------
     // TIM2 ISR — LED blink (business logic)
     // -----------------------------------------------------------------------
@@ -202,6 +283,11 @@ mod app {
             log_info!("LED OFF");
         }
         *ctx.local.state = !*ctx.local.state;
+
+        // The timer ticks the shell too: 1 Hz, so `watch` intervals are
+        // rounded up to whole seconds
+        SHELL_ELAPSED_MS.fetch_add(1000, core::sync::atomic::Ordering::Relaxed);
+        shell_task::spawn().ok();
     }
 
     // -----------------------------------------------------------------------
@@ -209,27 +295,47 @@ mod app {
     // -----------------------------------------------------------------------
     #[task(
         shared = [uart_tx, tx_buffer, rx_queue, shell_pending],
-        local  = [shell, initialized: bool = false],
+        local  = [shell, transport: Option<ConsoleTransport> = None],
         priority = 1,
     )]
     async fn shell_task(mut ctx: shell_task::Context) {
-        if !*ctx.local.initialized {
-            // Safety: RTIC shared resources live for 'static. transmute is
-            // sound because this block runs exactly once and the resources are
-            // never moved or dropped afterwards.
+        if ctx.local.transport.is_none() {
+            // Safety: RTIC resources live for 'static. transmute is sound
+            // because this block runs exactly once and the resources are never
+            // moved or dropped afterwards.
             unsafe {
                 ctx.shared.tx_buffer.lock(|tx_buf| {
                     ctx.shared.uart_tx.lock(|uart_tx| {
-                        init_uart_globals(
-                            core::mem::transmute::<
-                                &mut Deque<u8, TX_BUFFER_SIZE>,
-                                &'static mut Deque<u8, TX_BUFFER_SIZE>,
-                            >(tx_buf),
+                        let tx_buf = core::mem::transmute::<
+                            &TxBuffer,
+                            &'static TxBuffer,
+                        >(tx_buf);
+
+                        #[cfg(not(feature = "irq-tx"))]
+                        let transport = {
+                            let _ = uart_tx;
+                            ctx.local.transport.insert(ConsoleTransport::new(
+                                tx_buf,
+                                uart_hal::DmaStream::dma1(6, 4),
+                            ))
+                        };
+                        #[cfg(feature = "irq-tx")]
+                        let transport = ctx.local.transport.insert(ConsoleTransport::new(
+                            tx_buf,
                             core::mem::transmute::<
                                 &mut UartTx,
                                 &'static mut UartTx,
                             >(uart_tx),
-                        );
+                        ));
+
+                        let transport = core::mem::transmute::<
+                            &mut ConsoleTransport,
+                            &'static mut ConsoleTransport,
+                        >(transport);
+                        #[cfg(not(feature = "irq-tx"))]
+                        uart_hal::init_uart_dma_globals(transport);
+                        #[cfg(feature = "irq-tx")]
+                        uart_hal::init_uart_globals(transport);
                     });
                 });
             }
@@ -238,8 +344,12 @@ mod app {
             log_simple!("UART configured with step-based shell");
             log_simple!("Starting step-based shell...");
             log_simple!("Type '##' for available commands");
+            faultinfo::report_at_boot();
+        }
 
-            *ctx.local.initialized = true;
+        let elapsed_ms = SHELL_ELAPSED_MS.swap(0, core::sync::atomic::Ordering::Relaxed);
+        if elapsed_ms > 0 {
+            ctx.local.shell.tick(elapsed_ms);
         }
 
         ctx.shared.rx_queue.lock(|rx_queue| {
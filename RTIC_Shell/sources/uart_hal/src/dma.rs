@@ -0,0 +1,289 @@
+//! DMA-driven TX: the [`TxRing`](crate::TxRing) is sent by a DMA stream in
+//! contiguous chunks, one transfer-complete interrupt per chunk instead of one
+//! TX interrupt per byte.
+//!
+//! [`DmaTransport::write_bytes`](crate::ShellTransport::write_bytes) copies
+//! into the ring and calls [`start_dma_transfer`], which starts the stream on
+//! the oldest contiguous chunk if it is idle. The stream's ISR calls
+//! [`handle_dma_tx_complete`], which releases the sent chunk and starts the
+//! next one, until the ring is empty.
+//!
+//! The USART must have DMA transmission enabled (`DMAT`, e.g. with
+//! `SerialConfig::default().dma(DmaConfig::Tx)`) and the DMA controller its
+//! clock. The stream and channel come from the reference manual's request
+//! mapping; for the STM32F411:
+//!
+//! | USART  | Controller | Stream | Channel |
+//! |--------|------------|--------|---------|
+//! | USART1 | DMA2       | 7      | 4       |
+//! | USART2 | DMA1       | 6      | 4       |
+//! | USART6 | DMA2       | 6      | 5       |
+//!
+//! ```ignore
+//! let stream    = unsafe { DmaStream::dma1(6, 4) };
+//! let transport = ctx.local.transport.insert(UartDmaTransport::new(tx_buf, stream));
+//! unsafe { init_uart_dma_globals(transport) };
+//!
+//! #[task(binds = DMA1_STREAM6, priority = 3)]
+//! fn dma1_stream6_isr(_: dma1_stream6_isr::Context) {
+//!     uart_hal::handle_dma_tx_complete();
+//! }
+//! ```
+
+use stm32f4xx_hal::{pac, serial::Instance};
+
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
+use crate::{flow, init_uart_globals, ShellTransport, TxBuffer};
+
+// ---------------------------------------------------------------------------
+// DMA stream
+// ---------------------------------------------------------------------------
+
+/// Bit offset of a stream's flags in `LISR`/`HISR` and `LIFCR`/`HIFCR`.
+const FLAG_SHIFT: [u32; 4] = [0, 6, 16, 22];
+
+/// Transfer error flag, relative to the stream's flag offset.
+const TEIF: u32 = 1 << 3;
+/// Transfer complete flag, relative to the stream's flag offset.
+const TCIF: u32 = 1 << 5;
+/// All of a stream's flags (FEIF, DMEIF, TEIF, HTIF, TCIF).
+const ALL_FLAGS: u32 = 0x3D;
+
+/// One stream of a DMA controller, set up for memory-to-peripheral transfers
+/// on a request channel.
+#[derive(Clone, Copy)]
+pub struct DmaStream {
+    regs:    *const pac::dma1::RegisterBlock,
+    stream:  usize,
+    channel: u8,
+}
+
+// Safety: the register block is a fixed MMIO address; `DmaStream::dma1` and
+// `DmaStream::dma2` make the caller promise the stream is used by one owner.
+unsafe impl Send for DmaStream {}
+unsafe impl Sync for DmaStream {}
+
+impl DmaStream {
+    /// Stream `stream` (0..=7) of DMA1, on request channel `channel` (0..=7).
+    ///
+    /// # Safety
+    /// Nothing else may use the stream, and the DMA1 clock must be enabled.
+    pub unsafe fn dma1(stream: usize, channel: u8) -> Self {
+        Self { regs: pac::DMA1::ptr(), stream, channel }
+    }
+
+    /// Stream `stream` (0..=7) of DMA2, on request channel `channel` (0..=7).
+    ///
+    /// # Safety
+    /// Nothing else may use the stream, and the DMA2 clock must be enabled.
+    pub unsafe fn dma2(stream: usize, channel: u8) -> Self {
+        Self { regs: pac::DMA2::ptr(), stream, channel }
+    }
+
+    fn regs(&self) -> &pac::dma1::RegisterBlock {
+        // Safety: a valid DMA register block, see the constructors.
+        unsafe { &*self.regs }
+    }
+
+    fn flag_shift(&self) -> u32 {
+        FLAG_SHIFT[self.stream % 4]
+    }
+
+    fn flags(&self) -> u32 {
+        let regs = self.regs();
+        let isr  = if self.stream < 4 { regs.lisr.read().bits() } else { regs.hisr.read().bits() };
+        (isr >> self.flag_shift()) & ALL_FLAGS
+    }
+
+    fn clear_flags(&self) {
+        let regs = self.regs();
+        let bits = ALL_FLAGS << self.flag_shift();
+        // Safety: only this stream's flag-clear bits are written.
+        if self.stream < 4 {
+            regs.lifcr.write(|w| unsafe { w.bits(bits) });
+        } else {
+            regs.hifcr.write(|w| unsafe { w.bits(bits) });
+        }
+    }
+
+    /// Clears the stream's flags; returns `true` if a transfer has ended
+    /// (completed, or stopped by a transfer error).
+    fn take_done(&self) -> bool {
+        let flags = self.flags();
+        self.clear_flags();
+        flags & (TCIF | TEIF) != 0
+    }
+
+    /// Starts sending `chunk` to the data register at `peripheral`. The
+    /// stream must be idle and `chunk` must stay put until the transfer ends.
+    fn start(&self, peripheral: u32, chunk: &[u8]) {
+        let st = &self.regs().st[self.stream];
+        self.clear_flags();
+        // Safety: plain addresses and a count the hardware accepts as is.
+        st.par.write(|w| unsafe { w.pa().bits(peripheral) });
+        st.m0ar.write(|w| unsafe { w.m0a().bits(chunk.as_ptr() as u32) });
+        st.ndtr.write(|w| w.ndt().bits(chunk.len() as u16));
+        st.cr.write(|w| {
+            w.chsel().bits(self.channel)
+                .minc().incremented()
+                .dir().memory_to_peripheral()
+                .tcie().enabled()
+                .teie().enabled()
+                .en().enabled()
+        });
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Transfer state
+// ---------------------------------------------------------------------------
+
+/// Length of the chunk the stream is sending; `0` while idle, [`CLAIMED`]
+/// while [`start_dma_transfer`] is setting up the next transfer.
+static DMA_IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+const CLAIMED: usize = usize::MAX;
+
+/// [`DMA_IN_FLIGHT`] while the stream sends a pending XON/XOFF: no ring bytes
+/// to release when it completes.
+const FLOW_CHUNK: usize = usize::MAX - 1;
+
+/// Source of the XON/XOFF transfer; must stay put while the stream reads it.
+static FLOW_BYTE: AtomicU8 = AtomicU8::new(0);
+
+/// What [`handle_dma_tx_complete`] needs: the ring, the stream and the data
+/// register address.
+struct GlobalDmaState {
+    tx: UnsafeCell<Option<(&'static TxBuffer, DmaStream, u32)>>,
+}
+
+// Safety: written exactly once (in init_uart_dma_globals) before the first
+// transfer, so before the ISR can read it.
+unsafe impl Sync for GlobalDmaState {}
+
+static GLOBAL_DMA: GlobalDmaState = GlobalDmaState { tx: UnsafeCell::new(None) };
+
+/// Start sending the oldest contiguous chunk of `tx_buf` through `stream` to
+/// the data register at `peripheral`, unless a transfer is already running
+/// or the ring is empty. Returns `true` if a transfer was started.
+///
+/// A running transfer picks up bytes pushed meanwhile when it completes (see
+/// [`handle_dma_tx_complete`]), so writers just call this after every push.
+/// A pending XON/XOFF (see [`handle_rx_byte`](crate::handle_rx_byte)) is sent
+/// first, in a transfer of its own.
+pub fn start_dma_transfer(tx_buf: &TxBuffer, stream: &DmaStream, peripheral: u32) -> bool {
+    if DMA_IN_FLIGHT
+        .compare_exchange(0, CLAIMED, Ordering::AcqRel, Ordering::Relaxed)
+        .is_err()
+    {
+        return false;
+    }
+    if let Some(byte) = flow::take_flow_byte() {
+        FLOW_BYTE.store(byte, Ordering::Release);
+        DMA_IN_FLIGHT.store(FLOW_CHUNK, Ordering::Release);
+        // Safety: a static byte, only written here while the stream is idle.
+        stream.start(peripheral, unsafe { core::slice::from_raw_parts(FLOW_BYTE.as_ptr(), 1) });
+        return true;
+    }
+    let chunk = tx_buf.peek_chunk();
+    let len   = chunk.len().min(u16::MAX as usize);
+    if len == 0 {
+        DMA_IN_FLIGHT.store(0, Ordering::Release);
+        return false;
+    }
+    DMA_IN_FLIGHT.store(len, Ordering::Release);
+    stream.start(peripheral, &chunk[..len]);
+    true
+}
+
+/// Drive the TX side from the DMA stream's interrupt.
+///
+/// Call this from the ISR of the stream registered with
+/// [`init_uart_dma_globals`]. Releases the chunk that was sent (or dropped on
+/// a transfer error) and starts the next one, if any.
+pub fn handle_dma_tx_complete() {
+    // Safety: see GlobalDmaState.
+    let Some((tx_buf, stream, peripheral)) = (unsafe { *GLOBAL_DMA.tx.get() }) else {
+        return;
+    };
+    if !stream.take_done() {
+        return;
+    }
+    let sent = DMA_IN_FLIGHT.load(Ordering::Acquire);
+    if sent != FLOW_CHUNK {
+        tx_buf.consume(sent);
+    }
+    DMA_IN_FLIGHT.store(0, Ordering::Release);
+    start_dma_transfer(tx_buf, &stream, peripheral);
+}
+
+/// Start the stream on a pending XON/XOFF if it is idle; no-op without a
+/// registered [`DmaTransport`]. Safe from the USART ISR: the transfer is
+/// claimed like any other (see [`start_dma_transfer`]).
+pub(crate) fn kick() {
+    // Safety: see GlobalDmaState.
+    if let Some((tx_buf, stream, peripheral)) = unsafe { *GLOBAL_DMA.tx.get() } {
+        start_dma_transfer(tx_buf, &stream, peripheral);
+    }
+}
+
+/// Whether the TX ring is sent by DMA, so the USART data register must be
+/// left alone.
+pub(crate) fn is_registered() -> bool {
+    // Safety: see GlobalDmaState.
+    unsafe { (*GLOBAL_DMA.tx.get()).is_some() }
+}
+
+/// Register a [`DmaTransport`] as the output transport (like
+/// [`init_uart_globals`]) and hand its ring and stream to
+/// [`handle_dma_tx_complete`].
+///
+/// # Safety
+/// Same as [`init_uart_globals`]; in addition, must run before the stream's
+/// interrupt is unmasked.
+pub unsafe fn init_uart_dma_globals<U: Instance + 'static>(transport: &'static mut DmaTransport<U>) {
+    *GLOBAL_DMA.tx.get() = Some((transport.tx_buf, transport.stream, U::peri_address()));
+    init_uart_globals(transport);
+}
+
+// ---------------------------------------------------------------------------
+// DMA transport
+// ---------------------------------------------------------------------------
+
+/// DMA-driven TX of a USART: bytes are copied into the TX ring and sent by
+/// `stream` in chunks (see the [module docs](self)).
+///
+/// Receives nothing itself — RX bytes arrive in the USART ISR's queue and are
+/// read through [`RxQueueReader`](crate::RxQueueReader).
+pub struct DmaTransport<U: Instance + 'static> {
+    tx_buf: &'static TxBuffer,
+    stream: DmaStream,
+    _usart: PhantomData<U>,
+}
+
+impl<U: Instance> DmaTransport<U> {
+    /// Wrap the TX ring and the DMA stream wired to the USART's TX request.
+    pub fn new(tx_buf: &'static TxBuffer, stream: DmaStream) -> Self {
+        Self { tx_buf, stream, _usart: PhantomData }
+    }
+}
+
+impl<U: Instance> ShellTransport for DmaTransport<U> {
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        // Whatever does not fit is dropped
+        if self.tx_buf.push_slice(bytes) == 0 {
+            return;
+        }
+        start_dma_transfer(self.tx_buf, &self.stream, U::peri_address());
+    }
+
+    /// No-op — TX draining is handled entirely by the DMA stream.
+    fn flush(&mut self) {}
+
+    fn read_byte(&mut self) -> Option<u8> {
+        None
+    }
+}
@@ -0,0 +1,253 @@
+//! RX accounting and flow control.
+//!
+//! The USART ISR hands every received byte to [`handle_rx_byte`] instead of
+//! enqueueing it itself: the byte is counted, and a byte that does not fit is
+//! counted as an overflow instead of vanishing unnoticed ([`rx_stats`]).
+//!
+//! With a [`FlowControl`] set, the sender is throttled once the queue holds
+//! [`RX_HIGH_WATER`] bytes and released once [`RxQueueReader`](crate::RxQueueReader)
+//! has drained it to [`RX_LOW_WATER`] — so a host pasting a long script waits
+//! instead of losing characters.
+//!
+//! XON/XOFF are not pushed into the [`TxRing`](crate::TxRing) from the RX ISR
+//! (it has a single producer, the shell task, which the ISR may preempt in
+//! the middle of a push). The ISR only marks the byte pending; the TX path
+//! sends it ahead of the ring: [`handle_tx_ready`](crate::handle_tx_ready),
+//! called by the same ISR, or the DMA stream (see
+//! [`start_dma_transfer`](crate::start_dma_transfer)). A full ring does not
+//! hold it back, and an idle one does not either: without DMA the TX
+//! interrupt is armed through [`ShellTransport::arm_tx`](crate::ShellTransport::arm_tx).
+
+use core::cell::Cell;
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering};
+
+use heapless::spsc::Queue;
+
+use crate::{dma, with_transport, RX_QUEUE_SIZE, TX_ARMED};
+
+/// Queue fill level at which the sender is throttled.
+pub const RX_HIGH_WATER: usize = RX_QUEUE_SIZE * 3 / 4;
+
+/// Queue fill level at which a throttled sender is released.
+pub const RX_LOW_WATER: usize = RX_QUEUE_SIZE / 4;
+
+/// Software flow control: stop the sender.
+pub const XOFF: u8 = 0x13;
+
+/// Software flow control: resume the sender.
+pub const XON: u8 = 0x11;
+
+/// How the sender is throttled when the RX queue fills up.
+#[derive(Clone, Copy, Debug)]
+pub enum FlowControl {
+    /// No throttling; bytes past a full queue are counted and dropped.
+    None,
+    /// Send [`XOFF`] at the high-water mark and [`XON`] at the low-water mark.
+    XonXoff,
+    /// Call the function with `true` to assert RTS (stop the sender) and with
+    /// `false` to release it; the application drives the GPIO.
+    Rts(fn(bool)),
+}
+
+/// RX counters since start-up or the last [`reset_rx_stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RxStats {
+    /// Bytes received, including dropped ones.
+    pub received: u32,
+    /// Bytes dropped because the RX queue was full.
+    pub overflows: u32,
+    /// Times the sender was throttled.
+    pub throttles: u32,
+}
+
+static RECEIVED:  AtomicU32  = AtomicU32::new(0);
+static OVERFLOWS: AtomicU32  = AtomicU32::new(0);
+static THROTTLES: AtomicU32  = AtomicU32::new(0);
+static THROTTLED: AtomicBool = AtomicBool::new(false);
+
+/// XON or XOFF waiting for the TX path, `0` if none. A newer one replaces an
+/// unsent older one: only the last state matters to the sender.
+static FLOW_PENDING: AtomicU8 = AtomicU8::new(0);
+
+static FLOW: critical_section::Mutex<Cell<FlowControl>> =
+    critical_section::Mutex::new(Cell::new(FlowControl::None));
+
+/// Select how the sender is throttled (default [`FlowControl::None`]).
+pub fn set_rx_flow_control(flow: FlowControl) {
+    critical_section::with(|cs| FLOW.borrow(cs).set(flow));
+}
+
+/// The RX counters.
+pub fn rx_stats() -> RxStats {
+    RxStats {
+        received:  RECEIVED.load(Ordering::Relaxed),
+        overflows: OVERFLOWS.load(Ordering::Relaxed),
+        throttles: THROTTLES.load(Ordering::Relaxed),
+    }
+}
+
+/// Zero the RX counters.
+pub fn reset_rx_stats() {
+    RECEIVED.store(0, Ordering::Relaxed);
+    OVERFLOWS.store(0, Ordering::Relaxed);
+    THROTTLES.store(0, Ordering::Relaxed);
+}
+
+/// Enqueue a received byte from the USART ISR; returns `false` if the queue
+/// was full and the byte was dropped (and counted).
+///
+/// # Example (inside `usart2_isr`)
+/// ```ignore
+/// ctx.shared.rx_queue.lock(|q| { uart_hal::handle_rx_byte(q, byte); });
+/// ```
+pub fn handle_rx_byte(queue: &mut Queue<u8, RX_QUEUE_SIZE>, byte: u8) -> bool {
+    RECEIVED.fetch_add(1, Ordering::Relaxed);
+    let stored = queue.enqueue(byte).is_ok();
+    if !stored {
+        OVERFLOWS.fetch_add(1, Ordering::Relaxed);
+    }
+    if queue.len() >= RX_HIGH_WATER && !THROTTLED.swap(true, Ordering::AcqRel) {
+        THROTTLES.fetch_add(1, Ordering::Relaxed);
+        signal(true);
+    }
+    stored
+}
+
+/// Release a throttled sender once the queue has drained to the low-water
+/// mark; called by the RX queue reader after each byte it takes.
+pub(crate) fn on_rx_dequeue(len: usize) {
+    if len <= RX_LOW_WATER && THROTTLED.swap(false, Ordering::AcqRel) {
+        signal(false);
+    }
+}
+
+/// The XON/XOFF to send before the next byte of the TX ring, if any.
+pub(crate) fn take_flow_byte() -> Option<u8> {
+    match FLOW_PENDING.swap(0, Ordering::AcqRel) {
+        0    => None,
+        byte => Some(byte),
+    }
+}
+
+/// Whether an XON/XOFF is waiting for the TX path.
+pub(crate) fn has_flow_byte() -> bool {
+    FLOW_PENDING.load(Ordering::Acquire) != 0
+}
+
+fn signal(stop: bool) {
+    match critical_section::with(|cs| FLOW.borrow(cs).get()) {
+        FlowControl::None        => {}
+        // Left to the TX path, never through the transport: see the module docs
+        FlowControl::XonXoff     => {
+            FLOW_PENDING.store(if stop { XOFF } else { XON }, Ordering::Release);
+            if dma::is_registered() {
+                dma::kick();
+            } else if !TX_ARMED.swap(true, Ordering::AcqRel) {
+                // Idle TX: nothing else would wake `handle_tx_ready` for it
+                with_transport(|transport| transport.arm_tx());
+            }
+        }
+        FlowControl::Rts(assert) => assert(stop),
+    }
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+pub(crate) mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::{init_uart_globals, RxQueueReader, ShellTransport};
+    use std::sync::{Mutex, Once};
+
+    /// The flow and TX state is global: one test at a time.
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    /// Times the registered transport was asked to arm the TX interrupt.
+    static ARMED: AtomicU32 = AtomicU32::new(0);
+
+    struct IdleTx;
+
+    impl ShellTransport for IdleTx {
+        fn write_bytes(&mut self, _: &[u8]) {}
+
+        fn flush(&mut self) {}
+
+        fn read_byte(&mut self) -> Option<u8> {
+            None
+        }
+
+        fn arm_tx(&mut self) {
+            ARMED.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn setup() -> std::sync::MutexGuard<'static, ()> {
+        static INIT: Once = Once::new();
+        let guard = LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        // Safety: registered once, and a leaked box lives for 'static
+        INIT.call_once(|| unsafe {
+            init_uart_globals(std::boxed::Box::leak(std::boxed::Box::new(IdleTx)))
+        });
+        reset_rx_stats();
+        guard
+    }
+
+    #[test]
+    fn test_xon_xoff_left_to_tx_path() {
+        let _guard = setup();
+        set_rx_flow_control(FlowControl::XonXoff);
+        let mut queue: Queue<u8, RX_QUEUE_SIZE> = Queue::new();
+
+        for byte in 0..RX_HIGH_WATER as u8 - 1 {
+            assert!(handle_rx_byte(&mut queue, byte));
+        }
+        assert_eq!(take_flow_byte(), None);
+        handle_rx_byte(&mut queue, 0);
+        assert_eq!(take_flow_byte(), Some(XOFF));
+        assert_eq!(take_flow_byte(), None);
+        assert_eq!(rx_stats().throttles, 1);
+
+        // Drained to the low-water mark: XON replaces an XOFF not sent yet
+        handle_rx_byte(&mut queue, 0);
+        FLOW_PENDING.store(XOFF, Ordering::Release);
+        let mut reader = RxQueueReader::new(&mut queue);
+        while reader.read_byte().is_some() {}
+        assert_eq!(take_flow_byte(), Some(XON));
+        assert_eq!(take_flow_byte(), None);
+        set_rx_flow_control(FlowControl::None);
+    }
+
+    #[test]
+    fn test_flow_byte_arms_idle_tx() {
+        let _guard = setup();
+        set_rx_flow_control(FlowControl::XonXoff);
+        let mut queue: Queue<u8, RX_QUEUE_SIZE> = Queue::new();
+        let tx_buf = crate::TxBuffer::new();
+        TX_ARMED.store(false, Ordering::Release);
+        ARMED.store(0, Ordering::Relaxed);
+
+        while handle_rx_byte(&mut queue, 0) && !has_flow_byte() {}
+        assert_eq!(take_flow_byte(), Some(XOFF));
+        assert!(TX_ARMED.load(Ordering::Acquire));
+        assert_eq!(ARMED.load(Ordering::Relaxed), 1);
+
+        // The ISR sent the XOFF and found the ring empty: TX disarmed
+        assert!(tx_buf.is_empty());
+        TX_ARMED.store(false, Ordering::Release);
+
+        // The XON has no ring byte to ride along with: TX is armed for it
+        let mut reader = RxQueueReader::new(&mut queue);
+        while reader.read_byte().is_some() {}
+        assert!(has_flow_byte());
+        assert!(TX_ARMED.load(Ordering::Acquire));
+        assert_eq!(ARMED.load(Ordering::Relaxed), 2);
+        assert_eq!(take_flow_byte(), Some(XON));
+
+        TX_ARMED.store(false, Ordering::Release);
+        set_rx_flow_control(FlowControl::None);
+    }
+}
@@ -0,0 +1,188 @@
+//! Binary frames multiplexed with the ASCII shell on one UART.
+//!
+//! [`FrameDetector`] sits between the RX bytes and the shell's key parser. A
+//! frame start byte switches it into frame mode; the bytes up to the end of
+//! the frame are decoded and handed out as one payload, everything else is
+//! passed through to the shell unchanged.
+//!
+//! | Format | Start / end byte | Encoding                                  |
+//! |--------|------------------|-------------------------------------------|
+//! | SLIP   | `0xC0` (`END`)   | `0xDB 0xDC` → `0xC0`, `0xDB 0xDD` → `0xDB` |
+//! | COBS   | `0x00`           | code byte + run, no `0x00` inside a frame |
+//!
+//! Neither start byte occurs in terminal input (`0xC0` is not valid UTF-8,
+//! terminals do not send NUL), so the two streams cannot be confused. A host
+//! sends a frame as `START payload END`; with both formats the end byte is the
+//! start byte again, and an empty frame between two delimiters is ignored.
+//!
+//! A frame longer than the buffer is dropped as a whole and reported as
+//! [`FrameEvent::Overflow`]; malformed COBS data as [`FrameEvent::Malformed`].
+//! A stray start byte (line noise) makes the following input frame data up to
+//! the next delimiter; call [`FrameDetector::reset`] after a period without
+//! RX traffic to get the shell back in that case.
+
+/// Framing used by the binary protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameFormat {
+    /// RFC 1055 SLIP, frames delimited by `0xC0`.
+    Slip,
+    /// Consistent Overhead Byte Stuffing, frames delimited by `0x00`.
+    Cobs,
+}
+
+impl FrameFormat {
+    /// The byte that starts and ends a frame.
+    pub const fn delimiter(&self) -> u8 {
+        match self {
+            FrameFormat::Slip => SLIP_END,
+            FrameFormat::Cobs => 0x00,
+        }
+    }
+}
+
+const SLIP_END:     u8 = 0xC0;
+const SLIP_ESC:     u8 = 0xDB;
+const SLIP_ESC_END: u8 = 0xDC;
+const SLIP_ESC_ESC: u8 = 0xDD;
+
+/// What a byte fed to [`FrameDetector::feed`] turned out to be.
+#[derive(Debug, PartialEq, Eq)]
+pub enum FrameEvent<'a> {
+    /// Not part of a frame: shell input.
+    Byte(u8),
+    /// Taken into the frame being received.
+    Pending,
+    /// The decoded payload of a complete frame.
+    Frame(&'a [u8]),
+    /// The frame did not fit into the buffer and was dropped.
+    Overflow,
+    /// The frame was not valid for the format and was dropped.
+    Malformed,
+}
+
+/// Splits an RX byte stream into shell input and binary frames of up to `N`
+/// decoded bytes.
+pub struct FrameDetector<const N: usize> {
+    format:   FrameFormat,
+    buf:      [u8; N],
+    len:      usize,
+    in_frame: bool,
+    /// SLIP: the previous byte was `ESC`.
+    escaped:  bool,
+    /// The frame exceeded `N` bytes; the rest of it is skipped.
+    overflow: bool,
+}
+
+impl<const N: usize> FrameDetector<N> {
+    pub const fn new(format: FrameFormat) -> Self {
+        Self {
+            format,
+            buf:      [0; N],
+            len:      0,
+            in_frame: false,
+            escaped:  false,
+            overflow: false,
+        }
+    }
+
+    pub fn format(&self) -> FrameFormat {
+        self.format
+    }
+
+    /// Returns `true` while a frame is being received.
+    pub fn in_frame(&self) -> bool {
+        self.in_frame
+    }
+
+    /// Drop a partly received frame and go back to passing bytes through.
+    pub fn reset(&mut self) {
+        self.start();
+        self.in_frame = false;
+    }
+
+    /// Classify one received byte.
+    pub fn feed(&mut self, byte: u8) -> FrameEvent<'_> {
+        let delimiter = self.format.delimiter();
+        if !self.in_frame {
+            if byte != delimiter {
+                return FrameEvent::Byte(byte);
+            }
+            self.start();
+            return FrameEvent::Pending;
+        }
+
+        if byte == delimiter {
+            // The end byte doubles as the start of the next frame
+            let len      = core::mem::replace(&mut self.len, 0);
+            let overflow = core::mem::replace(&mut self.overflow, false);
+            if len == 0 && !overflow {
+                return FrameEvent::Pending;
+            }
+            self.in_frame = false;
+            if overflow {
+                return FrameEvent::Overflow;
+            }
+            return match self.format {
+                FrameFormat::Slip if self.escaped => FrameEvent::Malformed,
+                FrameFormat::Slip => FrameEvent::Frame(&self.buf[..len]),
+                FrameFormat::Cobs => match cobs_decode(&mut self.buf[..len]) {
+                    Some(decoded) => FrameEvent::Frame(&self.buf[..decoded]),
+                    None => FrameEvent::Malformed,
+                },
+            };
+        }
+
+        let byte = match self.format {
+            FrameFormat::Slip if self.escaped => {
+                self.escaped = false;
+                match byte {
+                    SLIP_ESC_END => SLIP_END,
+                    SLIP_ESC_ESC => SLIP_ESC,
+                    other => other, // protocol violation, kept as is (RFC 1055)
+                }
+            }
+            FrameFormat::Slip if byte == SLIP_ESC => {
+                self.escaped = true;
+                return FrameEvent::Pending;
+            }
+            _ => byte,
+        };
+        if self.len < N {
+            self.buf[self.len] = byte;
+            self.len += 1;
+        } else {
+            self.overflow = true;
+        }
+        FrameEvent::Pending
+    }
+
+    fn start(&mut self) {
+        self.in_frame = true;
+        self.len      = 0;
+        self.escaped  = false;
+        self.overflow = false;
+    }
+}
+
+/// Decode a COBS frame (without its delimiter) in place; returns the payload
+/// length, or `None` if a code byte points past the end.
+fn cobs_decode(buf: &mut [u8]) -> Option<usize> {
+    let mut read  = 0;
+    let mut write = 0;
+    while read < buf.len() {
+        let code = buf[read] as usize;
+        if code == 0 || read + code > buf.len() {
+            return None;
+        }
+        read += 1;
+        // The payload is never longer than the encoding: `write < read`
+        buf.copy_within(read..read + code - 1, write);
+        write += code - 1;
+        read  += code - 1;
+        if code < 0xFF && read < buf.len() {
+            buf[write] = 0;
+            write += 1;
+        }
+    }
+    Some(write)
+}
@@ -10,9 +10,24 @@
 //! - Provides `RxQueueReader` so the shell can drain the RTIC-owned RX queue
 //!   without knowing about the queue internals.
 //! - Provides `handle_tx_ready`, a single-call ISR helper that drains one byte
-//!   from the TX buffer and manages the TX-interrupt arm/disarm logic.
-//! - Provides `init_uart_globals` for the one-time wiring of RTIC shared
-//!   resources into the global state.
+//!   from the TX buffer and disarms the TX interrupt once the buffer is empty.
+//! - Provides `TxRing`, the TX byte ring filled in chunks by `write_bytes`.
+//! - Provides `DmaTransport`, which sends the TX ring through a DMA stream in
+//!   contiguous chunks, with `init_uart_dma_globals`, `start_dma_transfer`
+//!   and the transfer-complete ISR helper `handle_dma_tx_complete`.
+//! - Provides `init_uart_globals` for the one-time registration of the
+//!   [`ShellTransport`] all output goes to.
+//! - Provides `UsartTransport`, the interrupt-driven transport for any USART;
+//!   other links (USB-CDC, I2C/SPI bridges) implement `ShellTransport` themselves.
+//! - Provides `with_capture`, which diverts [`write_bytes`] into a buffer
+//!   while a closure runs (command output capture).
+//! - Provides `handle_rx_byte`, the ISR helper that enqueues RX bytes, counts
+//!   overflows (`rx_stats`) and throttles the sender by XON/XOFF or RTS past a
+//!   high-water mark (`set_rx_flow_control`).
+//! - Provides `selftest_tx`, which sends a pattern through the TX ring and
+//!   the ISR path and checks the bytes and the TX interrupt state.
+//! - Provides `FrameDetector`, which separates SLIP or COBS framed binary
+//!   payloads from the shell's ASCII input on the same UART.
 //!
 //! ## What this crate does NOT do
 //! - It does not configure clocks, pins, or the USART peripheral.
@@ -21,15 +36,36 @@
 
 #![no_std]
 
-use stm32f4xx_hal::{pac, serial::{Tx, Rx}};
+use stm32f4xx_hal::{pac, serial::{Instance, Tx, Rx}};
 
 // These traits are not included in the blanket `prelude::*`; they must be
 // imported explicitly.  The compiler error messages name them precisely.
-use stm32f4xx_hal::prelude::_stm32f4xx_hal_serial_TxListen; // .listen() / .unlisten()
-use stm32f4xx_hal::prelude::_stm32f4xx_hal_serial_TxISR;    // .is_tx_empty()
-use stm32f4xx_hal::prelude::_embedded_hal_serial_nb_Write;   // .write(byte)
+use stm32f4xx_hal::serial::TxListen;                           // .unlisten()
+use stm32f4xx_hal::serial::TxISR;                              // .is_tx_empty()
+use stm32f4xx_hal::prelude::_embedded_hal_serial_nb_Write as SerialWrite; // .write(byte)
 
-use heapless::{Deque, spsc::Queue};
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use heapless::spsc::Queue;
+
+mod dma;
+mod flow;
+mod frame;
+mod selftest;
+mod transport;
+
+pub use dma::{
+    handle_dma_tx_complete, init_uart_dma_globals, start_dma_transfer, DmaStream,
+    DmaTransport,
+};
+pub use flow::{
+    handle_rx_byte, reset_rx_stats, rx_stats, set_rx_flow_control, FlowControl, RxStats,
+    RX_HIGH_WATER, RX_LOW_WATER, XOFF, XON,
+};
+pub use frame::{FrameDetector, FrameEvent, FrameFormat};
+pub use selftest::{selftest_tx, SelftestError};
+pub use transport::{ShellTransport, UsartTransport};
 
 // ---------------------------------------------------------------------------
 // Public size constants
@@ -41,35 +77,145 @@ pub const RX_QUEUE_SIZE: usize = 128;
 /// Capacity of the software TX ring buffer that feeds the USART TX interrupt.
 pub const TX_BUFFER_SIZE: usize = 512;
 
+/// Largest decoded binary frame the shell's [`FrameDetector`] accepts.
+pub const MAX_FRAME_SIZE: usize = 256;
+
 // ---------------------------------------------------------------------------
 // Concrete HAL type aliases (re-exported so main.rs stays free of hal details)
 // ---------------------------------------------------------------------------
 
-/// The USART2 TX half, as produced by `serial.split()`.
+/// The USART2 TX half, as produced by `serial.split()` (the default console).
 pub type UartTx = Tx<pac::USART2>;
 
 /// The USART2 RX half, as produced by `serial.split()`.
 pub type UartRx = Rx<pac::USART2>;
 
+/// The default console transport.
+pub type UartTransport = UsartTransport<pac::USART2>;
+
+/// The default console transport, sending through DMA1 stream 6 channel 4.
+pub type UartDmaTransport = DmaTransport<pac::USART2>;
+
+/// The TX ring shared between `write_bytes` and the USART ISR.
+pub type TxBuffer = TxRing<TX_BUFFER_SIZE>;
+
+// ---------------------------------------------------------------------------
+// TX ring buffer
+// ---------------------------------------------------------------------------
+
+/// Single-producer / single-consumer byte ring: `write_bytes` copies whole
+/// chunks in (at most two `memcpy`s, before and after the wrap point), the
+/// ISR takes bytes out one at a time ([`pop`](Self::pop)) or a DMA stream a
+/// contiguous chunk at a time ([`peek_chunk`](Self::peek_chunk)). Holds
+/// `N - 1` bytes.
+pub struct TxRing<const N: usize> {
+    buf:  UnsafeCell<[u8; N]>,
+    /// Next byte to send; written by the consumer only.
+    head: AtomicUsize,
+    /// Next free slot; written by the producer only.
+    tail: AtomicUsize,
+}
+
+// Safety: the producer only writes the free region and `tail`, the consumer
+// only reads the filled region and writes `head`.
+unsafe impl<const N: usize> Sync for TxRing<N> {}
+
+impl<const N: usize> Default for TxRing<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> TxRing<N> {
+    pub const fn new() -> Self {
+        Self {
+            buf:  UnsafeCell::new([0; N]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /// Copies as much of `bytes` as fits; returns how many bytes were taken.
+    pub fn push_slice(&self, bytes: &[u8]) -> usize {
+        let head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Relaxed);
+        let free = (head + N - tail - 1) % N;
+        let n    = bytes.len().min(free);
+        let first = n.min(N - tail);
+
+        let buf = self.buf.get() as *mut u8;
+        // Safety: `tail..tail + first` and `0..n - first` are free slots the
+        // consumer does not read until `tail` is published below.
+        unsafe {
+            core::ptr::copy_nonoverlapping(bytes.as_ptr(), buf.add(tail), first);
+            core::ptr::copy_nonoverlapping(bytes.as_ptr().add(first), buf, n - first);
+        }
+        self.tail.store((tail + n) % N, Ordering::Release);
+        n
+    }
+
+    /// Takes the oldest byte.
+    pub fn pop(&self) -> Option<u8> {
+        let head = self.head.load(Ordering::Relaxed);
+        if head == self.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        // Safety: `head` is in the filled region, published by the producer.
+        let byte = unsafe { (self.buf.get() as *const u8).add(head).read() };
+        self.head.store((head + 1) % N, Ordering::Release);
+        Some(byte)
+    }
+
+    /// The oldest waiting bytes, up to the wrap point — what a DMA stream can
+    /// send in one transfer. Release them with [`consume`](Self::consume).
+    pub fn peek_chunk(&self) -> &[u8] {
+        let head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Acquire);
+        let end  = if tail >= head { tail } else { N };
+        // Safety: `head..end` is in the filled region, published by the
+        // producer, which does not write it until `head` moves past it.
+        unsafe { core::slice::from_raw_parts((self.buf.get() as *const u8).add(head), end - head) }
+    }
+
+    /// Drops the first `n` bytes of [`peek_chunk`](Self::peek_chunk).
+    pub fn consume(&self, n: usize) {
+        let head = self.head.load(Ordering::Relaxed);
+        self.head.store((head + n) % N, Ordering::Release);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Acquire) == self.tail.load(Ordering::Acquire)
+    }
+
+    /// Bytes waiting to be sent.
+    pub fn len(&self) -> usize {
+        let head = self.head.load(Ordering::Acquire);
+        (self.tail.load(Ordering::Acquire) + N - head) % N
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Internal global state
 // ---------------------------------------------------------------------------
 
 struct GlobalUartState {
-    tx_buffer: core::cell::UnsafeCell<Option<&'static mut Deque<u8, TX_BUFFER_SIZE>>>,
-    uart_tx:   core::cell::UnsafeCell<Option<&'static mut UartTx>>,
+    transport: core::cell::UnsafeCell<Option<&'static mut dyn ShellTransport>>,
 }
 
 // Safety: accesses are coordinated by RTIC's priority-based interrupt masking.
-// The UnsafeCells are written exactly once (in init_uart_globals) before any
-// reader can observe them.
+// The UnsafeCell is written exactly once (in init_uart_globals) before any
+// reader can observe it.
 unsafe impl Sync for GlobalUartState {}
 
 static mut GLOBAL_UART: GlobalUartState = GlobalUartState {
-    tx_buffer: core::cell::UnsafeCell::new(None),
-    uart_tx:   core::cell::UnsafeCell::new(None),
+    transport: core::cell::UnsafeCell::new(None),
 };
 
+/// Set while the TX interrupt is enabled. `write_bytes` enables it only when
+/// this goes from `false` to `true`; the ISR clears it when it disables the
+/// interrupt on an empty buffer.
+pub(crate) static TX_ARMED: AtomicBool = AtomicBool::new(false);
+
 // ---------------------------------------------------------------------------
 // Global logger writer instance
 // ---------------------------------------------------------------------------
@@ -88,30 +234,28 @@ pub static mut LOGGER_WRITER: UartWriter = UartWriter;
 // One-time initialisation
 // ---------------------------------------------------------------------------
 
-/// Register the RTIC-owned `tx_buffer` and `uart_tx` with the global state.
+/// Register the transport that [`write_bytes`] and [`flush`] send to.
 ///
 /// Must be called **exactly once**, from the RTIC task that holds locks on
-/// both resources.  Use `core::mem::transmute` to extend lifetimes to
-/// `'static` — this is sound because RTIC shared resources live for the
-/// entire programme lifetime.
+/// the resources the transport wraps (for [`UsartTransport`]: `tx_buffer` and
+/// `uart_tx`).  Use `core::mem::transmute` to extend lifetimes to `'static` —
+/// this is sound because RTIC resources live for the entire programme
+/// lifetime.
 ///
 /// # Safety
-/// - Both references must remain valid for `'static`.
+/// - The reference must remain valid for `'static`.
 /// - Must be called before the first call to [`write_bytes`].
 /// - Must be called exactly once.
-pub unsafe fn init_uart_globals(
-    tx_buf:  &'static mut Deque<u8, TX_BUFFER_SIZE>,
-    uart_tx: &'static mut UartTx,
-) {
-    *(*core::ptr::addr_of_mut!(GLOBAL_UART.tx_buffer)).get() = Some(tx_buf);
-    *(*core::ptr::addr_of_mut!(GLOBAL_UART.uart_tx)).get()   = Some(uart_tx);
+pub unsafe fn init_uart_globals<T: ShellTransport + 'static>(transport: &'static mut T) {
+    *(*core::ptr::addr_of_mut!(GLOBAL_UART.transport)).get() = Some(transport);
 }
 
 // ---------------------------------------------------------------------------
 // Public write / flush — suitable as bare function pointers
 // ---------------------------------------------------------------------------
 
-/// Enqueue `bytes` into the TX ring buffer and arm the TX interrupt.
+/// Send `bytes` through the registered transport; for [`UsartTransport`],
+/// copy them into the TX ring buffer and arm the TX interrupt if it is idle.
 ///
 /// This is a plain `fn` (not a closure) so it can be stored in a
 /// `CallbackWriter<fn(&[u8]), fn()>` or any other function-pointer slot.
@@ -119,30 +263,15 @@ pub unsafe fn init_uart_globals(
 /// Silently drops bytes that exceed the buffer capacity.
 /// No-ops silently before [`init_uart_globals`] has been called.
 pub fn write_bytes(bytes: &[u8]) {
-    // Safety: write_bytes is called only from tasks at or below the USART ISR
-    // priority.  The ISR exclusively pops (pop_front) while we push
-    // (push_back), so there is no aliased mutable access to the Deque.
-    //
-    // Deref note: `Option<&'static mut T>::as_mut()` yields
-    // `Option<&mut &'static mut T>`, not `Option<&mut T>`.
-    // We therefore deref one extra level with `**uart_tx` to obtain the plain
-    // `&mut UartTx` that the HAL trait methods expect.
-    unsafe {
-        let tx_buf_ptr = core::ptr::addr_of!(GLOBAL_UART.tx_buffer);
-        let tx_ptr     = core::ptr::addr_of!(GLOBAL_UART.uart_tx);
-
-        if let Some(tx_buf) = (*(*tx_buf_ptr).get()).as_mut() {
-            if let Some(uart_tx) = (*(*tx_ptr).get()).as_mut() {
-                for &b in bytes {
-                    if tx_buf.push_back(b).is_err() {
-                        break; // buffer full — drop the remainder
-                    }
-                }
-                // uart_tx : &mut &'static mut UartTx  →  **  →  &mut UartTx
-                (**uart_tx).listen();
-            }
-        }
+    if capture(bytes) {
+        return;
     }
+    with_transport(|transport| transport.write_bytes(bytes));
+}
+
+/// Flush the registered transport.
+pub fn flush() {
+    with_transport(|transport| transport.flush());
 }
 
 /// No-op flush — TX draining is handled entirely by the USART TX interrupt.
@@ -151,6 +280,86 @@ pub fn write_bytes(bytes: &[u8]) {
 /// `fn()` flush pointer (e.g. `CallbackWriter`).
 pub fn flush_noop() {}
 
+pub(crate) fn with_transport(f: impl FnOnce(&mut dyn ShellTransport)) {
+    // Safety: called only from tasks at or below the USART ISR priority.
+    // The critical section keeps the writers of different priorities (the
+    // shell task, a command executor, the logger) from pushing at the same
+    // time: for `UsartTransport` the ring stays single-producer /
+    // single-consumer, the ISR only pops.
+    //
+    // Deref note: `Option<&'static mut T>::as_mut()` yields
+    // `Option<&mut &'static mut T>`; `**` gives the plain `&mut dyn` to call.
+    critical_section::with(|_| unsafe {
+        let transport_ptr = core::ptr::addr_of!(GLOBAL_UART.transport);
+        if let Some(transport) = (*(*transport_ptr).get()).as_mut() {
+            f(&mut **transport);
+        }
+    });
+}
+
+// ---------------------------------------------------------------------------
+// Output capture
+// ---------------------------------------------------------------------------
+
+/// A buffer [`with_capture`] collects output into.
+pub trait CaptureBuffer {
+    /// Append `bytes`; what does not fit is dropped.
+    fn push_bytes(&mut self, bytes: &[u8]);
+}
+
+impl<const N: usize> CaptureBuffer for heapless::Vec<u8, N> {
+    fn push_bytes(&mut self, bytes: &[u8]) {
+        let n = bytes.len().min(N - self.len());
+        let _ = self.extend_from_slice(&bytes[..n]);
+    }
+}
+
+/// The buffer of the running `with_capture`, lifetime erased.
+#[derive(Clone, Copy)]
+struct CapturePtr(*mut dyn CaptureBuffer);
+
+// Safety: only dereferenced inside a critical section (see `capture`).
+unsafe impl Send for CapturePtr {}
+
+static CAPTURE: critical_section::Mutex<core::cell::Cell<Option<CapturePtr>>> =
+    critical_section::Mutex::new(core::cell::Cell::new(None));
+
+/// Run `f` with all output of [`write_bytes`] (and so of the logger) appended
+/// to `buf` instead of being sent.
+///
+/// Output written by higher-priority tasks while `f` runs is captured as well.
+/// Calls do not nest: an inner `with_capture` writes to its own buffer and
+/// ends capturing for the rest of the outer one.
+///
+/// # Example
+/// ```ignore
+/// let mut out: heapless::Vec<u8, 128> = heapless::Vec::new();
+/// let result = uart_hal::with_capture(&mut out, || commands::dispatch(line, &mut err));
+/// ```
+pub fn with_capture<R>(buf: &mut dyn CaptureBuffer, f: impl FnOnce() -> R) -> R {
+    // Safety: the pointer is only dereferenced by `capture` while `f` runs,
+    // i.e. while `buf` is borrowed here, and is cleared before returning.
+    let buf: *mut (dyn CaptureBuffer + '_) = buf;
+    let buf: *mut (dyn CaptureBuffer + 'static) = unsafe { core::mem::transmute(buf) };
+    critical_section::with(|cs| CAPTURE.borrow(cs).set(Some(CapturePtr(buf))));
+    let result = f();
+    critical_section::with(|cs| CAPTURE.borrow(cs).set(None));
+    result
+}
+
+/// Append `bytes` to the capture buffer, if capturing; returns `true` then.
+fn capture(bytes: &[u8]) -> bool {
+    critical_section::with(|cs| match CAPTURE.borrow(cs).get() {
+        Some(CapturePtr(buf)) => {
+            // Safety: see `with_capture`; the critical section keeps a
+            // preempting writer out while this one appends.
+            unsafe { (*buf).push_bytes(bytes) };
+            true
+        }
+        None => false,
+    })
+}
+
 // ---------------------------------------------------------------------------
 // ISR TX helper
 // ---------------------------------------------------------------------------
@@ -158,9 +367,12 @@ pub fn flush_noop() {}
 /// Drive the TX side of the USART interrupt.
 ///
 /// Call this from your USART ISR whenever the TX data register is empty.
-/// Pops one byte from `tx_buf`, writes it to `uart_tx`, and keeps the TX
-/// interrupt armed.  When the buffer empties the interrupt is disarmed, so
-/// the ISR stops re-entering.
+/// Pops one byte from `tx_buf` and writes it to `uart_tx`; the TX interrupt
+/// stays armed as it is.  When the buffer empties the interrupt is disarmed,
+/// so the ISR stops re-entering until [`write_bytes`] arms it again.
+///
+/// A pending XON/XOFF from [`handle_rx_byte`] is written first, so call this
+/// after `handle_rx_byte` in the same ISR.
 ///
 /// # Example (inside `usart2_isr`)
 /// ```ignore
@@ -170,15 +382,63 @@ pub fn flush_noop() {}
 ///     });
 /// });
 /// ```
-pub fn handle_tx_ready(uart_tx: &mut UartTx, tx_buf: &mut Deque<u8, TX_BUFFER_SIZE>) {
-    if uart_tx.is_tx_empty() {
-        match tx_buf.pop_front() {
-            Some(byte) => {
-                let _ = uart_tx.write(byte);
-                uart_tx.listen();   // keep armed while data remains
+pub fn handle_tx_ready<U: Instance>(uart_tx: &mut Tx<U>, tx_buf: &mut TxBuffer)
+where
+    Tx<U>: TxISR + TxListen + SerialWrite<u8>,
+{
+    tx_ready(uart_tx, tx_buf);
+}
+
+/// The TX data register and interrupt of a USART, as the ISR path drives
+/// them; lets [`selftest_tx`] and the host tests run the same path.
+pub(crate) trait TxPort {
+    fn is_tx_empty(&self) -> bool;
+    fn write(&mut self, byte: u8);
+    fn listen(&mut self);
+    fn unlisten(&mut self);
+}
+
+impl<U: Instance> TxPort for Tx<U>
+where
+    Tx<U>: TxISR + TxListen + SerialWrite<u8>,
+{
+    fn is_tx_empty(&self) -> bool {
+        TxISR::is_tx_empty(self)
+    }
+
+    fn write(&mut self, byte: u8) {
+        let _ = SerialWrite::write(self, byte);
+    }
+
+    fn listen(&mut self) {
+        TxListen::listen(self)
+    }
+
+    fn unlisten(&mut self) {
+        TxListen::unlisten(self)
+    }
+}
+
+/// Body of [`handle_tx_ready`].
+pub(crate) fn tx_ready<const N: usize>(port: &mut impl TxPort, tx_buf: &TxRing<N>) {
+    // A pending XON/XOFF goes ahead of the ring (the DMA stream sends it itself)
+    if !dma::is_registered() {
+        if port.is_tx_empty() {
+            if let Some(byte) = flow::take_flow_byte() {
+                port.write(byte);
+                return;
             }
+        } else if flow::has_flow_byte() && !TX_ARMED.swap(true, Ordering::AcqRel) {
+            // Busy with a byte of its own: send it on the next TX interrupt
+            port.listen();
+        }
+    }
+    if port.is_tx_empty() && TX_ARMED.load(Ordering::Acquire) {
+        match tx_buf.pop() {
+            Some(byte) => port.write(byte),
             None => {
-                uart_tx.unlisten(); // buffer drained — silence the TX interrupt
+                port.unlisten(); // buffer drained — silence the TX interrupt
+                TX_ARMED.store(false, Ordering::Release);
             }
         }
     }
@@ -232,7 +492,9 @@ impl<'a> RxQueueReader<'a> {
 
     /// Dequeue and return the next byte, or `None` if empty.
     pub fn read_byte(&mut self) -> Option<u8> {
-        self.queue.dequeue()
+        let byte = self.queue.dequeue();
+        flow::on_rx_dequeue(self.queue.len());
+        byte
     }
 
     /// Returns `true` when no bytes are waiting.
@@ -240,3 +502,19 @@ impl<'a> RxQueueReader<'a> {
         self.queue.is_empty()
     }
 }
+
+/// Reads the RX queue; output goes to the transport registered with
+/// [`init_uart_globals`].
+impl ShellTransport for RxQueueReader<'_> {
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        write_bytes(bytes);
+    }
+
+    fn flush(&mut self) {
+        flush();
+    }
+
+    fn read_byte(&mut self) -> Option<u8> {
+        RxQueueReader::read_byte(self)
+    }
+}
@@ -0,0 +1,234 @@
+//! TX path self-test.
+//!
+//! [`selftest_tx`] pushes a pattern into the [`TxRing`] and drains it through
+//! the same code the USART ISR runs ([`handle_tx_ready`](crate::handle_tx_ready)),
+//! busy-waiting on the data register instead of taking the interrupt. Every
+//! byte the ISR path hands to the data register is compared against the
+//! pattern, and the TX interrupt must end up disarmed with the ring empty —
+//! so a ring that loses, duplicates or reorders bytes, or an ISR path that
+//! leaves the interrupt armed, fails the test.
+
+use core::sync::atomic::Ordering;
+
+use stm32f4xx_hal::prelude::_embedded_hal_serial_nb_Write as SerialWrite;
+use stm32f4xx_hal::serial::{Instance, Tx, TxISR, TxListen};
+
+use crate::{flow, tx_ready, TxBuffer, TxPort, TxRing, TX_ARMED};
+
+/// Data register polls per byte before the test gives up.
+const DRAIN_SPINS: u32 = 1_000_000;
+
+/// Why [`selftest_tx`] failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SelftestError {
+    /// The ring held bytes, TX was armed or a flow byte was pending.
+    Busy,
+    /// The ring took only `taken` bytes of the pattern.
+    RingFull { taken: usize },
+    /// Byte `index` reached the data register as `written`, not `expected`.
+    Mismatch { index: usize, expected: u8, written: u8 },
+    /// The data register stayed busy after `sent` bytes.
+    Stalled { sent: usize },
+    /// The ISR path wrote `sent` bytes of a `len`-byte pattern.
+    Length { sent: usize, len: usize },
+    /// The ring drained, but the TX interrupt was left armed.
+    StillArmed,
+}
+
+/// Checks the bytes written through it against the pattern.
+struct Checker<'a, P: TxPort> {
+    port:      &'a mut P,
+    pattern:   &'a [u8],
+    sent:      usize,
+    mismatch:  Option<SelftestError>,
+    listening: bool,
+}
+
+impl<P: TxPort> TxPort for Checker<'_, P> {
+    fn is_tx_empty(&self) -> bool {
+        self.port.is_tx_empty()
+    }
+
+    fn write(&mut self, byte: u8) {
+        if self.mismatch.is_none() {
+            match self.pattern.get(self.sent) {
+                Some(&expected) if expected == byte => {}
+                Some(&expected) => {
+                    self.mismatch = Some(SelftestError::Mismatch { index: self.sent, expected, written: byte });
+                }
+                None => {
+                    self.mismatch = Some(SelftestError::Length { sent: self.sent + 1, len: self.pattern.len() });
+                }
+            }
+        }
+        self.sent += 1;
+        self.port.write(byte);
+    }
+
+    fn listen(&mut self) {
+        self.listening = true;
+        self.port.listen();
+    }
+
+    fn unlisten(&mut self) {
+        self.listening = false;
+        self.port.unlisten();
+    }
+}
+
+/// Send `pattern` through `tx_buf` and the ISR path, checking every byte.
+///
+/// Call it with the USART interrupt masked — in RTIC, from inside the locks
+/// of the TX half and ring, the same ones the ISR takes — and with the link
+/// otherwise idle: the pattern goes out on the wire.
+///
+/// # Example (inside a task sharing `uart_tx` and `tx_buffer` with the ISR)
+/// ```ignore
+/// let pattern: [u8; 64] = core::array::from_fn(|i| b' ' + (i % 95) as u8);
+/// let result = ctx.shared.uart_tx.lock(|uart_tx| {
+///     ctx.shared.tx_buffer.lock(|tx_buf| uart_hal::selftest_tx(uart_tx, tx_buf, &pattern))
+/// });
+/// ```
+pub fn selftest_tx<U: Instance>(
+    uart_tx: &mut Tx<U>,
+    tx_buf:  &TxBuffer,
+    pattern: &[u8],
+) -> Result<(), SelftestError>
+where
+    Tx<U>: TxISR + TxListen + SerialWrite<u8>,
+{
+    run(uart_tx, tx_buf, pattern)
+}
+
+/// Body of [`selftest_tx`].
+fn run<const N: usize>(
+    uart_tx: &mut impl TxPort,
+    tx_buf:  &TxRing<N>,
+    pattern: &[u8],
+) -> Result<(), SelftestError> {
+    if !tx_buf.is_empty() || TX_ARMED.load(Ordering::Acquire) || flow::has_flow_byte() {
+        return Err(SelftestError::Busy);
+    }
+    let taken = tx_buf.push_slice(pattern);
+    if taken < pattern.len() {
+        tx_buf.consume(taken);
+        return Err(SelftestError::RingFull { taken });
+    }
+
+    let mut port = Checker { port: uart_tx, pattern, sent: 0, mismatch: None, listening: false };
+    TX_ARMED.store(true, Ordering::Release);
+    port.listen();
+
+    let mut spins = 0;
+    while TX_ARMED.load(Ordering::Acquire) {
+        if !port.is_tx_empty() {
+            spins += 1;
+            if spins > DRAIN_SPINS {
+                // Leave the ring and the interrupt as the ISR would find them
+                tx_buf.consume(tx_buf.len());
+                port.unlisten();
+                TX_ARMED.store(false, Ordering::Release);
+                return Err(SelftestError::Stalled { sent: port.sent });
+            }
+            continue;
+        }
+        spins = 0;
+        tx_ready(&mut port, tx_buf);
+    }
+
+    if let Some(error) = port.mismatch {
+        return Err(error);
+    }
+    if port.sent != pattern.len() || !tx_buf.is_empty() {
+        return Err(SelftestError::Length { sent: port.sent, len: pattern.len() });
+    }
+    if port.listening {
+        return Err(SelftestError::StillArmed);
+    }
+    Ok(())
+}
+
+// ===== TESTS =====
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::vec::Vec;
+
+    /// A data register that is always ready and records what it was given.
+    #[derive(Default)]
+    struct Wire {
+        bytes:     Vec<u8>,
+        listening: bool,
+    }
+
+    impl TxPort for Wire {
+        fn is_tx_empty(&self) -> bool {
+            true
+        }
+
+        fn write(&mut self, byte: u8) {
+            self.bytes.push(byte);
+        }
+
+        fn listen(&mut self) {
+            self.listening = true;
+        }
+
+        fn unlisten(&mut self) {
+            self.listening = false;
+        }
+    }
+
+    fn pattern() -> Vec<u8> {
+        (0..200).map(|i| b' ' + (i % 95) as u8).collect()
+    }
+
+    #[test]
+    fn test_selftest_tx() {
+        let _guard = flow::tests::setup();
+        TX_ARMED.store(false, Ordering::Release);
+        let tx_buf = TxRing::<256>::new();
+        let mut wire = Wire::default();
+
+        // Ends wrapped around the ring: the second run crosses the wrap point
+        for _ in 0..2 {
+            wire.bytes.clear();
+            assert_eq!(run(&mut wire, &tx_buf, &pattern()), Ok(()));
+            assert_eq!(wire.bytes, pattern());
+            assert!(!wire.listening);
+            assert!(!TX_ARMED.load(Ordering::Acquire));
+            assert!(tx_buf.is_empty());
+        }
+
+        // Does not fit the ring: nothing is left behind
+        let long = [b'x'; 256];
+        assert_eq!(run(&mut wire, &tx_buf, &long), Err(SelftestError::RingFull { taken: 255 }));
+        assert!(tx_buf.is_empty());
+
+        // Output already waiting: the pattern would not be alone on the wire
+        tx_buf.push_slice(b"> ");
+        assert_eq!(run(&mut wire, &tx_buf, &pattern()), Err(SelftestError::Busy));
+        tx_buf.consume(2);
+    }
+
+    #[test]
+    fn test_selftest_tx_catches_stray_byte() {
+        let _guard = flow::tests::setup();
+        TX_ARMED.store(false, Ordering::Release);
+        let tx_buf = TxRing::<256>::new();
+        let mut wire = Wire::default();
+
+        // A byte slipped into the ring behind the test's back shows up as a
+        // mismatch at the point the pattern should have continued
+        let mut port = Checker { port: &mut wire, pattern: b"abc", sent: 0, mismatch: None, listening: false };
+        tx_buf.push_slice(b"abXc");
+        TX_ARMED.store(true, Ordering::Release);
+        while TX_ARMED.load(Ordering::Acquire) {
+            tx_ready(&mut port, &tx_buf);
+        }
+        assert_eq!(port.mismatch, Some(SelftestError::Mismatch { index: 2, expected: b'c', written: b'X' }));
+        assert_eq!(port.sent, 4);
+    }
+}
@@ -0,0 +1,103 @@
+//! Byte transports the shell can run over.
+//!
+//! [`ShellTransport`] is what the shell needs from a link: send bytes, flush,
+//! and receive one byte at a time. The shell output (parser echo, logger) goes
+//! to the transport registered with [`init_uart_globals`](crate::init_uart_globals);
+//! `ShellCtx::step` reads from the transport it is given.
+//!
+//! Three implementations come with this crate:
+//! - [`UsartTransport`] — TX side of any USART, through the [`TxRing`](crate::TxRing)
+//!   and the TX interrupt (see [`handle_tx_ready`](crate::handle_tx_ready)).
+//! - [`DmaTransport`](crate::DmaTransport) — the same, but the ring is sent by a
+//!   DMA stream in chunks (see [`handle_dma_tx_complete`](crate::handle_dma_tx_complete)).
+//! - [`RxQueueReader`](crate::RxQueueReader) — RX side, draining the queue the
+//!   USART ISR fills; its output goes to the registered transport.
+//!
+//! Backing the shell with e.g. USB-CDC means implementing the trait for the
+//! CDC class, registering it, and passing it (or a reader) to `step`:
+//!
+//! ```ignore
+//! impl ShellTransport for CdcTransport {
+//!     fn write_bytes(&mut self, bytes: &[u8]) { let _ = self.serial.write(bytes); }
+//!     fn flush(&mut self)                     { let _ = self.serial.flush(); }
+//!     fn read_byte(&mut self) -> Option<u8>   { self.rx.dequeue() }
+//! }
+//! ```
+
+use stm32f4xx_hal::serial::{Instance, Tx, TxListen};
+
+use core::sync::atomic::Ordering;
+
+use crate::{TxBuffer, TX_ARMED};
+
+// ---------------------------------------------------------------------------
+// ShellTransport
+// ---------------------------------------------------------------------------
+
+/// A byte link the shell runs over.
+pub trait ShellTransport {
+    /// Queue `bytes` for sending. Bytes that do not fit may be dropped.
+    fn write_bytes(&mut self, bytes: &[u8]);
+
+    /// Push out queued bytes, if the transport buffers them on its own.
+    fn flush(&mut self);
+
+    /// The next received byte, or `None` if none is waiting.
+    fn read_byte(&mut self) -> Option<u8>;
+
+    /// Enable the TX interrupt that calls [`handle_tx_ready`](crate::handle_tx_ready),
+    /// so a pending XON/XOFF goes out while the TX ring is idle; may run in
+    /// the RX ISR. The default does nothing, for transports that do not send
+    /// through `handle_tx_ready`.
+    fn arm_tx(&mut self) {}
+}
+
+// ---------------------------------------------------------------------------
+// USART transport
+// ---------------------------------------------------------------------------
+
+/// Interrupt-driven TX of a USART: bytes are copied into the TX ring and sent
+/// by the USART ISR calling [`handle_tx_ready`](crate::handle_tx_ready).
+///
+/// Receives nothing itself — RX bytes arrive in the ISR's queue and are read
+/// through [`RxQueueReader`](crate::RxQueueReader).
+pub struct UsartTransport<U: Instance + 'static> {
+    tx_buf:  &'static TxBuffer,
+    uart_tx: &'static mut Tx<U>,
+}
+
+impl<U: Instance> UsartTransport<U> {
+    /// Wrap the TX ring and the TX half of the USART (from `serial.split()`).
+    pub fn new(tx_buf: &'static TxBuffer, uart_tx: &'static mut Tx<U>) -> Self {
+        Self { tx_buf, uart_tx }
+    }
+}
+
+impl<U: Instance> ShellTransport for UsartTransport<U>
+where
+    Tx<U>: TxListen,
+{
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        // Whatever does not fit is dropped
+        if self.tx_buf.push_slice(bytes) == 0 {
+            return;
+        }
+        // Only the idle -> busy transition touches CR1. The ISR clears
+        // TX_ARMED together with disarming, and it cannot run between its own
+        // check and clear, so no wake-up is lost.
+        if !TX_ARMED.swap(true, Ordering::AcqRel) {
+            self.uart_tx.listen();
+        }
+    }
+
+    /// No-op — TX draining is handled entirely by the USART TX interrupt.
+    fn flush(&mut self) {}
+
+    fn read_byte(&mut self) -> Option<u8> {
+        None
+    }
+
+    fn arm_tx(&mut self) {
+        self.uart_tx.listen();
+    }
+}
@@ -0,0 +1,211 @@
+//! Command aliases.
+//!
+//! `alias <name> <command...>` defines a short name for a command line; a typed line
+//! starting with the name runs the command line instead, with the rest of the typed
+//! line appended. `unalias <name>` removes the alias, `alias` alone lists them.
+//!
+//! ```text
+//! >> alias st "status 1"
+//! >> st
+//! (runs "status 1")
+//! >> alias g gpio set
+//! >> g 5 1
+//! (runs "gpio set 5 1")
+//! ```
+//!
+//! Only the first word is expanded, and only once: an alias naming another alias runs
+//! the command of that name, so definitions cannot loop. The table is bounded; aliases
+//! live in RAM and are lost on reset.
+//!
+//! This is the `alias` module of the Embassy tree's `ushell2`, copied like the rest of
+//! the crate: every shell tree (Embassy, RTIC) vendors its own `ushell2` so it builds
+//! and ships without the others. A fix to one copy goes into the other as well.
+
+use heapless::index_map::FnvIndexMap;
+use heapless::String;
+
+/// Number of aliases kept (a power of two, as the table requires).
+pub const ALIAS_MAX: usize = 8;
+
+/// Maximum length in bytes of an alias name.
+pub const ALIAS_NAME_MAX: usize = 12;
+
+/// Why an alias operation was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AliasError {
+    /// Empty, longer than `ALIAS_NAME_MAX`, containing whitespace or a quote, or one
+    /// of the `alias`/`unalias` built-ins.
+    BadName,
+    /// The alias would expand to nothing.
+    NoCommand,
+    /// The table holds `ALIAS_MAX` other aliases.
+    Full,
+    /// The command, or the expanded line, does not fit the input buffer.
+    TooLong,
+    /// No alias with that name.
+    Unknown,
+}
+
+impl AliasError {
+    pub const fn message(&self) -> &'static str {
+        match self {
+            AliasError::BadName => "bad alias name",
+            AliasError::NoCommand => "alias needs a command",
+            AliasError::Full => "alias table full (unalias one first)",
+            AliasError::TooLong => "alias too long",
+            AliasError::Unknown => "no such alias",
+        }
+    }
+}
+
+/// The aliases of one shell; `IML` bounds the commands and expanded lines, `N` the
+/// number of aliases (a power of two).
+pub struct AliasTable<const IML: usize, const N: usize> {
+    map: FnvIndexMap<String<ALIAS_NAME_MAX>, String<IML>, N>,
+}
+
+impl<const IML: usize, const N: usize> Default for AliasTable<IML, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const IML: usize, const N: usize> AliasTable<IML, N> {
+    pub const fn new() -> Self {
+        Self {
+            map: FnvIndexMap::new(),
+        }
+    }
+
+    /// Defines `name` as `command`, replacing an alias of the same name.
+    pub fn define(&mut self, name: &str, command: &str) -> Result<(), AliasError> {
+        if name.is_empty()
+            || name.contains(|ch: char| ch.is_whitespace() || ch == '"')
+            || matches!(name, "alias" | "unalias")
+        {
+            return Err(AliasError::BadName);
+        }
+        let key = String::try_from(name).map_err(|_| AliasError::BadName)?;
+        let command = command.trim();
+        if command.is_empty() {
+            return Err(AliasError::NoCommand);
+        }
+        let value = String::try_from(command).map_err(|_| AliasError::TooLong)?;
+        self.map
+            .insert(key, value)
+            .map(|_| ())
+            .map_err(|_| AliasError::Full)
+    }
+
+    /// Removes the alias `name`.
+    pub fn remove(&mut self, name: &str) -> Result<(), AliasError> {
+        self.map.remove(name).map(|_| ()).ok_or(AliasError::Unknown)
+    }
+
+    /// The command of the alias `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.map.get(name).map(|command| command.as_str())
+    }
+
+    /// The aliases as `(name, command)`, in definition order until one is removed.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.map
+            .iter()
+            .map(|(name, command)| (name.as_str(), command.as_str()))
+    }
+
+    /// The number of aliases.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns `true` if no alias is defined.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Expands the alias `line` starts with: `Ok(None)` if its first word is not an
+    /// alias, otherwise the alias command followed by the rest of `line`.
+    pub fn expand(&self, line: &str) -> Result<Option<String<IML>>, AliasError> {
+        let line = line.trim_start();
+        let (name, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let Some(command) = self.get(name) else {
+            return Ok(None);
+        };
+        let mut expanded = String::new();
+        expanded
+            .push_str(command)
+            .map_err(|_| AliasError::TooLong)?;
+        let rest = rest.trim();
+        if !rest.is_empty() {
+            expanded
+                .push(' ')
+                .and_then(|_| expanded.push_str(rest))
+                .map_err(|_| AliasError::TooLong)?;
+        }
+        Ok(Some(expanded))
+    }
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+
+    #[test]
+    fn test_define_expand_remove() {
+        let mut aliases = AliasTable::<32, 4>::new();
+        assert_eq!(aliases.expand("st"), Ok(None));
+
+        aliases.define("st", " status 1 ").unwrap();
+        aliases.define("g", "gpio set").unwrap();
+        assert_eq!(aliases.get("st"), Some("status 1"));
+        assert_eq!(aliases.expand("st").unwrap().unwrap(), "status 1");
+        assert_eq!(
+            aliases.expand("  g 5  1 ").unwrap().unwrap(),
+            "gpio set 5  1"
+        );
+        // Only whole first words
+        assert_eq!(aliases.expand("sta"), Ok(None));
+        assert_eq!(aliases.expand("led st"), Ok(None));
+
+        // Redefined in place; no recursion
+        aliases.define("st", "g 7").unwrap();
+        assert_eq!(aliases.len(), 2);
+        assert_eq!(aliases.expand("st").unwrap().unwrap(), "g 7");
+
+        assert_eq!(aliases.remove("st"), Ok(()));
+        assert_eq!(aliases.remove("st"), Err(AliasError::Unknown));
+        assert_eq!(aliases.expand("st"), Ok(None));
+        let listed: heapless::Vec<(&str, &str), 4> = aliases.iter().collect();
+        assert_eq!(listed, [("g", "gpio set")]);
+    }
+
+    #[test]
+    fn test_bounds() {
+        let mut aliases = AliasTable::<16, 2>::new();
+        assert_eq!(aliases.define("", "x"), Err(AliasError::BadName));
+        assert_eq!(aliases.define("a b", "x"), Err(AliasError::BadName));
+        assert_eq!(aliases.define("alias", "x"), Err(AliasError::BadName));
+        assert_eq!(
+            aliases.define(&"n".repeat(ALIAS_NAME_MAX + 1), "x"),
+            Err(AliasError::BadName)
+        );
+        assert_eq!(aliases.define("a", " "), Err(AliasError::NoCommand));
+        assert_eq!(
+            aliases.define("a", &"x".repeat(17)),
+            Err(AliasError::TooLong)
+        );
+
+        aliases.define("a", "0123456789").unwrap();
+        aliases.define("b", "x").unwrap();
+        assert_eq!(aliases.define("c", "x"), Err(AliasError::Full));
+        // Redefining needs no room
+        assert_eq!(aliases.define("b", "y"), Ok(()));
+        assert_eq!(aliases.expand("a 123456"), Err(AliasError::TooLong));
+    }
+}
@@ -240,6 +240,13 @@ impl<const HTC: usize> History<HTC> {
         self.entry_size
     }
 
+    /// Returns the entry the up/down arrow keys are at.
+    ///
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.current_index
+    }
+
     /// Returns the number of free bytes remaining in the buffer.
     ///
     pub fn get_free_space(&self) -> usize {
@@ -75,6 +75,10 @@ pub struct InputParser<
     buffer: InputBuffer<IML>,
 
     prompt: &'static str,
+
+    /// Shell lock (see `enable_lock`): `None` if the shell cannot be locked,
+    /// otherwise whether it is locked.
+    lock: Option<bool>,
 }
 
 impl<
@@ -135,9 +139,57 @@ impl<
             history,
             buffer,
             prompt,
+            lock: None,
+        }
+    }
+
+    /// Makes the shell lockable and sets its initial state.
+    ///
+    /// Once enabled, `#l` locks the shell (the history is listed with `#h`)
+    /// and `login ...` lines are never stored in history; their password is
+    /// echoed as `*`. While locked, the
+    /// history and the hashtag commands are unavailable and every line goes
+    /// to the command callback, which checks `login <password>` and unlocks
+    /// with `set_locked(false)`.
+    pub fn enable_lock(&mut self, locked: bool) {
+        self.lock = Some(locked);
+    }
+
+    /// Locks or unlocks the shell; ignored unless `enable_lock` was called.
+    pub fn set_locked(&mut self, locked: bool) {
+        if self.lock.is_some() {
+            self.lock = Some(locked);
         }
     }
 
+    /// Returns `true` while the shell is locked.
+    pub fn is_locked(&self) -> bool {
+        self.lock == Some(true)
+    }
+
+    /// Returns the cursor position in the input line, in characters.
+    pub fn cursor(&self) -> usize {
+        self.buffer.cursor()
+    }
+
+    /// Returns the number of characters in the input line.
+    pub fn input_len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Returns the command history.
+    pub fn history(&self) -> &History<HTC> {
+        &self.history
+    }
+
+    /// Whether `cmd` stays out of the history: hashtag commands, anything
+    /// typed while locked and, on a lockable shell, `login` lines.
+    fn skips_history(&self, cmd: &str) -> bool {
+        cmd.starts_with('#')
+            || self.is_locked()
+            || (self.lock.is_some() && cmd.split_whitespace().next() == Some("login"))
+    }
+
     /// Helper function: write a number directly to the writer without allocation
     fn write_number(writer: &mut W, mut num: usize) {
         let mut digits = [0u8; 20];
@@ -164,8 +216,18 @@ impl<
         self.buffer.chars().take(FNL).collect()
     }
 
+    /// Echoes the password of a `login <password>` line being typed as `*`.
+    fn mask_password(line: &mut String<IML>) {
+        if let Some(password) = line.strip_prefix("login ") {
+            *line = "login ".chars().chain(password.chars().map(|_| '*')).collect();
+        }
+    }
+
     fn render_buffer(&mut self) {
-        let buf_str = self.buffer.to_string();
+        let mut buf_str = self.buffer.to_string();
+        if self.lock.is_some() {
+            Self::mask_password(&mut buf_str);
+        }
         let cursor_pos = self.buffer.cursor().min(self.buffer.len());
         self.renderer.render(self.prompt, &buf_str, cursor_pos);
     }
@@ -305,6 +367,9 @@ impl<
     /// - Re-renders the prompt and buffer display to reflect the new input.
     ///
     pub fn handle_up(&mut self) {
+        if self.is_locked() {
+            return;
+        }
         self.buffer.clear();
         let found = self
             .history
@@ -322,6 +387,9 @@ impl<
     /// - Re-renders the prompt and buffer display to reflect the new input.
     ///
     pub fn handle_down(&mut self) {
+        if self.is_locked() {
+            return;
+        }
         self.buffer.clear();
         let found = self
             .history
@@ -392,8 +460,10 @@ impl<
     /// Hashtag Commands:
     /// - `#q` - Quit/exit the shell.
     /// - `#` - List available commands.
-    /// - `##` - List all (commands + shortcuts + arg types).
-    /// - `#l` - Show command history.
+    /// - `##` - List all (commands + shortcuts + arg types + these commands).
+    /// - `#l` - Show command history; locks the shell instead if it is lockable
+    ///   (see `enable_lock`).
+    /// - `#h` - Show command history.
     /// - `#c` - Clear command history.
     /// - `#N` - Execute command from history at index N.
     ///
@@ -426,9 +496,21 @@ impl<
                 writer.write_str(self.shell_datatypes);
                 writer.write_str("\n\r\n\rShortcuts:\n\r");
                 writer.write_str(self.shell_shortcuts);
-                writer.write_str("\n\r");
+                writer.write_str("\n\r\n\rHashtag commands:\n\r");
+                writer.write_str("  #: commands, ##: all, #q: quit\n\r");
+                // A lockable shell takes `#l` for the lock, the history stays on `#h`
+                if self.lock.is_some() {
+                    writer.write_str("  #l: lock the shell, #h: history");
+                } else {
+                    writer.write_str("  #l or #h: history");
+                }
+                writer.write_str(", #c: clear history, #N: run history entry N\n\r");
+            }
+            "l" if self.lock.is_some() => {
+                self.lock = Some(true);
+                writer.write_str("Shell locked.\n\r");
             }
-            "l" => {
+            "l" | "h" => {
                 if self.history.is_empty() {
                     writer.write_str("History is empty.\n\r");
                 } else {
@@ -499,14 +581,15 @@ impl<
     /// Processes the current input when the Enter key is pressed.
     ///
     /// Behavior:
-    /// - Commits the current buffer content to history (unless empty or starts with '#').
+    /// - Commits the current buffer content to history (unless empty, starting with '#'
+    ///   or kept out while locked, see `enable_lock`).
     /// - Clears the buffer.
     /// - Resets autocomplete state.
     /// - Returns the command string for execution.
     ///
     pub fn handle_enter(&mut self) -> String<IML> {
         let cmd = self.buffer.to_string();
-        if !cmd.is_empty() && !cmd.starts_with('#') {
+        if !cmd.is_empty() && !self.skips_history(&cmd) {
             self.history.push(cmd.as_str());
         }
         self.buffer.clear();
@@ -616,7 +699,10 @@ impl<
 
                     if !cmd.is_empty() {
                         // Handle hashtag commands
-                        if let Some(stripped) = cmd.strip_prefix('#') {
+                        if cmd.starts_with('#') && self.is_locked() {
+                            let writer = self.renderer.writer_mut();
+                            writer.write_str("Shell locked (login <password>).\n\r");
+                        } else if let Some(stripped) = cmd.strip_prefix('#') {
                             let (continue_running, maybe_history_command) =
                                 self.handle_hashtag(stripped);
                             if !continue_running {
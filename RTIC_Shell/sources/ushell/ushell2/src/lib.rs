@@ -10,12 +10,15 @@ pub extern crate heapless;
 ))]
 extern crate std;
 
+pub mod alias;
 pub mod autocomplete;
 pub mod history;
 pub mod input;
 pub mod logger;
+pub mod registry;
 pub mod runner;
 pub mod terminal;
+pub mod vars;
 
 // Re-export commonly used types for easier importing
 pub use input::parser::InputParser;
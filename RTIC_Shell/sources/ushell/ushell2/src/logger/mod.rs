@@ -69,6 +69,33 @@ impl LogLevel {
         }
     }
 
+    /// Parses a level name (`error`, `warn`, `info`, `debug`, `verbose`, `trace`),
+    /// ignoring ASCII case.
+    pub fn from_name(name: &str) -> Option<LogLevel> {
+        [
+            LogLevel::Error,
+            LogLevel::Warn,
+            LogLevel::Info,
+            LogLevel::Debug,
+            LogLevel::Verbose,
+            LogLevel::Trace,
+        ]
+        .into_iter()
+        .find(|level| level.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Lowercase name, as accepted by `from_name`.
+    pub const fn name(&self) -> &'static str {
+        match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Verbose => "verbose",
+            LogLevel::Trace => "trace",
+        }
+    }
+
     /// Allows early exit before string formatting
     #[inline]
     pub const fn is_enabled(&self, min_level: LogLevel) -> bool {
@@ -250,6 +277,24 @@ pub fn set_min_level(level: LogLevel) {
     }
 }
 
+/// Whether whole lines are colored by level, not only their label.
+#[cfg(feature = "hosted")]
+pub fn color_entire_line() -> bool {
+    match unsafe { &GLOBAL_LOGGER } {
+        Some(logger) => logger.lock().is_ok_and(|g| g.config.color_entire_line),
+        None => false,
+    }
+}
+
+/// The logger's minimum level (`Info` before `init_logger`).
+#[cfg(feature = "hosted")]
+pub fn min_level() -> LogLevel {
+    match unsafe { &GLOBAL_LOGGER } {
+        Some(logger) => logger.lock().map_or(LogLevel::Info, |g| g.config.min_level),
+        None => LogLevel::Info,
+    }
+}
+
 #[cfg(feature = "hosted")]
 pub fn log_with_level(level: LogLevel, message: &str) {
     if let Some(logger) = unsafe { &GLOBAL_LOGGER } {
@@ -328,6 +373,28 @@ pub fn set_min_level(level: LogLevel) {
     });
 }
 
+/// Whether whole lines are colored by level, not only their label.
+#[cfg(not(feature = "hosted"))]
+pub fn color_entire_line() -> bool {
+    critical_section::with(|cs| {
+        GLOBAL_LOGGER
+            .borrow_ref(cs)
+            .as_ref()
+            .is_some_and(|logger| logger.config.color_entire_line)
+    })
+}
+
+/// The logger's minimum level (`Info` before `init_logger`).
+#[cfg(not(feature = "hosted"))]
+pub fn min_level() -> LogLevel {
+    critical_section::with(|cs| {
+        GLOBAL_LOGGER
+            .borrow_ref(cs)
+            .as_ref()
+            .map_or(LogLevel::Info, |logger| logger.config.min_level)
+    })
+}
+
 #[cfg(not(feature = "hosted"))]
 pub fn log_with_level(level: LogLevel, message: &str) {
     critical_section::with(|cs| {
@@ -0,0 +1,205 @@
+//! Commands registered at runtime.
+//!
+//! The generated `commands::dispatch` table is fixed at compile time. A
+//! `DynCommandRegistry` holds up to `N` more commands that the application adds
+//! and removes while running, e.g. test commands enabled by a feature or by a
+//! board detected at start-up:
+//!
+//! ```ignore
+//! static DYN_COMMANDS: DynCommandRegistry<4> = DynCommandRegistry::new();
+//!
+//! fn selftest(args: &str) -> Result<(), &'static str> {
+//!     if !args.is_empty() {
+//!         return Err("no arguments expected");
+//!     }
+//!     run_selftest();
+//!     Ok(())
+//! }
+//!
+//! DYN_COMMANDS.register("selftest", selftest).ok();
+//! ```
+//!
+//! The shell consults the registry when the static dispatcher reports
+//! `UnknownFunction`. A handler receives the rest of the line after the command
+//! name, trimmed, and parses it itself.
+//!
+//! Names the shell handles itself never reach the registry; create it with
+//! `DynCommandRegistry::reserving(ushell_ctx::BUILTINS)` to refuse them.
+
+use core::cell::RefCell;
+use critical_section::Mutex;
+
+use crate::heapless::Vec;
+
+/// Handler of a runtime command; gets the arguments as typed.
+pub type DynCommandFn = fn(args: &str) -> Result<(), &'static str>;
+
+/// Why a command could not be registered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegistryError {
+    /// All `N` entries are in use.
+    Full,
+    /// A command with that name is registered already.
+    Duplicate,
+    /// Empty name or a name containing whitespace.
+    BadName,
+    /// The name of a shell built-in, which would run instead.
+    Reserved,
+}
+
+impl RegistryError {
+    pub const fn message(&self) -> &'static str {
+        match self {
+            RegistryError::Full => "command registry full",
+            RegistryError::Duplicate => "command already registered",
+            RegistryError::BadName => "bad command name",
+            RegistryError::Reserved => "command named like a built-in",
+        }
+    }
+}
+
+/// Up to `N` commands registered and unregistered at runtime.
+pub struct DynCommandRegistry<const N: usize> {
+    entries: Mutex<RefCell<Vec<(&'static str, DynCommandFn), N>>>,
+    reserved: &'static [&'static str],
+}
+
+impl<const N: usize> DynCommandRegistry<N> {
+    pub const fn new() -> Self {
+        Self::reserving(&[])
+    }
+
+    /// A registry that refuses the names in `reserved`, e.g. the shell's built-ins.
+    pub const fn reserving(reserved: &'static [&'static str]) -> Self {
+        Self {
+            entries: Mutex::new(RefCell::new(Vec::new())),
+            reserved,
+        }
+    }
+
+    /// Adds `name` (a single word) with its handler.
+    pub fn register(&self, name: &'static str, handler: DynCommandFn) -> Result<(), RegistryError> {
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(RegistryError::BadName);
+        }
+        if self.reserved.contains(&name) {
+            return Err(RegistryError::Reserved);
+        }
+        critical_section::with(|cs| {
+            let mut entries = self.entries.borrow_ref_mut(cs);
+            if entries.iter().any(|&(n, _)| n == name) {
+                return Err(RegistryError::Duplicate);
+            }
+            entries
+                .push((name, handler))
+                .map_err(|_| RegistryError::Full)
+        })
+    }
+
+    /// Removes `name`; returns `false` if it was not registered.
+    pub fn unregister(&self, name: &str) -> bool {
+        critical_section::with(|cs| {
+            let mut entries = self.entries.borrow_ref_mut(cs);
+            match entries.iter().position(|&(n, _)| n == name) {
+                Some(index) => {
+                    entries.swap_remove(index);
+                    true
+                }
+                None => false,
+            }
+        })
+    }
+
+    /// The handler registered for `name`.
+    pub fn find(&self, name: &str) -> Option<DynCommandFn> {
+        critical_section::with(|cs| {
+            self.entries
+                .borrow_ref(cs)
+                .iter()
+                .find(|&&(n, _)| n == name)
+                .map(|&(_, handler)| handler)
+        })
+    }
+
+    /// Runs `line` if its first word is a registered command; `None` otherwise.
+    /// The handler runs outside the critical section.
+    pub fn dispatch(&self, line: &str) -> Option<Result<(), &'static str>> {
+        let line = line.trim();
+        let (name, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        self.find(name).map(|handler| handler(args.trim()))
+    }
+
+    /// Calls `f` with the name of each registered command.
+    pub fn for_each(&self, mut f: impl FnMut(&str)) {
+        critical_section::with(|cs| {
+            for &(name, _) in self.entries.borrow_ref(cs).iter() {
+                f(name);
+            }
+        });
+    }
+}
+
+impl<const N: usize> Default for DynCommandRegistry<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ==================== TESTS =======================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn echo(args: &str) -> Result<(), &'static str> {
+        if args == "fail" {
+            Err("failed")
+        } else {
+            Ok(())
+        }
+    }
+
+    fn other(_: &str) -> Result<(), &'static str> {
+        Err("other")
+    }
+
+    #[test]
+    fn test_register_and_dispatch() {
+        let registry = DynCommandRegistry::<2>::new();
+        assert_eq!(registry.register("echo", echo), Ok(()));
+        assert_eq!(
+            registry.register("echo", other),
+            Err(RegistryError::Duplicate)
+        );
+        assert_eq!(
+            registry.register("two words", other),
+            Err(RegistryError::BadName)
+        );
+        assert_eq!(registry.register("", other), Err(RegistryError::BadName));
+        assert_eq!(registry.register("other", other), Ok(()));
+        assert_eq!(registry.register("third", other), Err(RegistryError::Full));
+
+        assert_eq!(registry.dispatch("echo hello world"), Some(Ok(())));
+        assert_eq!(registry.dispatch("  echo   fail "), Some(Err("failed")));
+        assert_eq!(registry.dispatch("other"), Some(Err("other")));
+        assert_eq!(registry.dispatch("missing 1"), None);
+        assert_eq!(registry.dispatch(""), None);
+
+        assert!(registry.unregister("echo"));
+        assert!(!registry.unregister("echo"));
+        assert_eq!(registry.dispatch("echo"), None);
+        assert_eq!(registry.register("third", other), Ok(()));
+
+        let mut count = 0;
+        registry.for_each(|_| count += 1);
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn test_reserved_names() {
+        let registry = DynCommandRegistry::<2>::reserving(&["set", "watch"]);
+        assert_eq!(registry.register("set", echo), Err(RegistryError::Reserved));
+        assert_eq!(registry.register("settings", echo), Ok(()));
+        assert_eq!(registry.dispatch("set"), None);
+    }
+}
@@ -0,0 +1,229 @@
+//! Shell variables.
+//!
+//! `set <NAME> <value>` stores a value under an upper-case name; `$NAME` anywhere in a
+//! command line is replaced by the value before the line is dispatched. `unset <NAME>`
+//! removes the variable, `set` alone lists them, `echo` prints its expanded arguments.
+//!
+//! ```text
+//! >> set EEPROM 0x50
+//! >> i2c write $EEPROM 0x00 0xAB
+//! (runs "i2c write 0x50 0x00 0xAB")
+//! >> echo $EEPROM
+//! 0x50
+//! ```
+//!
+//! Names are upper case (`A`-`Z`, `0`-`9`, `_`, not starting with a digit), so
+//! `set <key> <value>` commands of the application keep working for lower-case keys.
+//! `$$` stands for a literal `$`; a `$` not followed by a name (`$5`) is kept as is,
+//! an undefined name fails the line. The store is static and bounded; variables live in
+//! RAM and are lost on reset.
+//!
+//! Same source as `ushell2::vars` of the Embassy tree: each tree keeps a full copy of
+//! `ushell2` so that it is self-contained. Apart from this paragraph, keep the two files
+//! identical.
+
+use core::cell::RefCell;
+use critical_section::Mutex;
+use heapless::{String, Vec};
+
+/// Number of variables kept.
+pub const VAR_MAX: usize = 8;
+
+/// Maximum length in bytes of a variable name.
+pub const VAR_NAME_MAX: usize = 12;
+
+/// Maximum length in bytes of a variable value.
+pub const VAR_VALUE_MAX: usize = 32;
+
+/// Why a variable operation was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VarError {
+    /// Not an upper-case name, or longer than `VAR_NAME_MAX`.
+    BadName,
+    /// The value is longer than `VAR_VALUE_MAX`.
+    ValueTooLong,
+    /// All `VAR_MAX` variables are in use.
+    Full,
+    /// No variable with that name.
+    Unknown,
+    /// The expanded line does not fit the input buffer.
+    LineTooLong,
+}
+
+impl VarError {
+    pub const fn message(&self) -> &'static str {
+        match self {
+            VarError::BadName => "bad variable name",
+            VarError::ValueTooLong => "variable value too long",
+            VarError::Full => "too many variables (unset one first)",
+            VarError::Unknown => "undefined variable",
+            VarError::LineTooLong => "line too long after variable expansion",
+        }
+    }
+}
+
+struct Var {
+    name: String<VAR_NAME_MAX>,
+    value: String<VAR_VALUE_MAX>,
+}
+
+static VARS: Mutex<RefCell<Vec<Var, VAR_MAX>>> = Mutex::new(RefCell::new(Vec::new()));
+
+/// Returns `true` if `name` is a valid variable name (see the module docs).
+pub fn is_var_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= VAR_NAME_MAX
+        && !name.starts_with(|ch: char| ch.is_ascii_digit())
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_uppercase() || ch.is_ascii_digit() || ch == '_')
+}
+
+/// Sets `name` to `value`, replacing its previous value.
+pub fn set(name: &str, value: &str) -> Result<(), VarError> {
+    if !is_var_name(name) {
+        return Err(VarError::BadName);
+    }
+    let name = String::try_from(name).map_err(|_| VarError::BadName)?;
+    let value = String::try_from(value).map_err(|_| VarError::ValueTooLong)?;
+    critical_section::with(|cs| {
+        let mut vars = VARS.borrow_ref_mut(cs);
+        match vars.iter_mut().find(|var| var.name == name) {
+            Some(var) => var.value = value,
+            None => vars.push(Var { name, value }).map_err(|_| VarError::Full)?,
+        }
+        Ok(())
+    })
+}
+
+/// Removes the variable `name`.
+pub fn unset(name: &str) -> Result<(), VarError> {
+    critical_section::with(|cs| {
+        let mut vars = VARS.borrow_ref_mut(cs);
+        let index = vars
+            .iter()
+            .position(|var| var.name == name)
+            .ok_or(VarError::Unknown)?;
+        vars.remove(index);
+        Ok(())
+    })
+}
+
+/// Returns a copy of the value of `name`.
+pub fn get(name: &str) -> Option<String<VAR_VALUE_MAX>> {
+    critical_section::with(|cs| {
+        VARS.borrow_ref(cs)
+            .iter()
+            .find(|var| var.name == name)
+            .map(|var| var.value.clone())
+    })
+}
+
+/// Calls `f` with the name and value of each variable.
+pub fn for_each(mut f: impl FnMut(&str, &str)) {
+    critical_section::with(|cs| {
+        for var in VARS.borrow_ref(cs).iter() {
+            f(&var.name, &var.value);
+        }
+    });
+}
+
+/// Returns `line` with its `$NAME` references replaced, `None` if it has none.
+pub fn expand<const N: usize>(line: &str) -> Result<Option<String<N>>, VarError> {
+    if !line.contains('$') {
+        return Ok(None);
+    }
+    let mut expanded: String<N> = String::new();
+    let mut rest = line;
+    while let Some(dollar) = rest.find('$') {
+        push(&mut expanded, &rest[..dollar])?;
+        let after = &rest[dollar + 1..];
+        if let Some(after) = after.strip_prefix('$') {
+            push(&mut expanded, "$")?;
+            rest = after;
+            continue;
+        }
+        let len = after
+            .find(|ch: char| !(ch.is_ascii_uppercase() || ch.is_ascii_digit() || ch == '_'))
+            .unwrap_or(after.len());
+        let name = &after[..len];
+        if is_var_name(name) {
+            push(&mut expanded, &get(name).ok_or(VarError::Unknown)?)?;
+            rest = &after[len..];
+        } else {
+            push(&mut expanded, "$")?;
+            rest = after;
+        }
+    }
+    push(&mut expanded, rest)?;
+    Ok(Some(expanded))
+}
+
+fn push<const N: usize>(expanded: &mut String<N>, text: &str) -> Result<(), VarError> {
+    expanded.push_str(text).map_err(|_| VarError::LineTooLong)
+}
+
+// ==================================================
+// ================= TESTS ==========================
+// ==================================================
+
+#[cfg(test)]
+pub(crate) mod tests {
+    extern crate std;
+    use super::*;
+    use std::vec::Vec as StdVec;
+
+    // Tests share the global store
+    pub(crate) static TEST_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    pub(crate) fn reset() {
+        critical_section::with(|cs| VARS.borrow_ref_mut(cs).clear());
+    }
+
+    #[test]
+    fn test_set_expand_unset() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        reset();
+
+        assert_eq!(expand::<32>("led 1"), Ok(None));
+        set("ADDR", "0x50").unwrap();
+        set("PIN_2", "5").unwrap();
+        assert_eq!(
+            expand::<32>("i2c $ADDR $PIN_2,$ADDR").unwrap().unwrap(),
+            "i2c 0x50 5,0x50"
+        );
+        assert_eq!(
+            expand::<32>("cost $$5 $5 $ x$").unwrap().unwrap(),
+            "cost $5 $5 $ x$"
+        );
+        assert_eq!(expand::<32>("led $NOPE"), Err(VarError::Unknown));
+        assert_eq!(expand::<8>("i2c $ADDR 1"), Err(VarError::LineTooLong));
+
+        set("ADDR", "0x51").unwrap();
+        assert_eq!(get("ADDR").unwrap(), "0x51");
+        let mut listed = StdVec::new();
+        for_each(|name, value| listed.push(std::format!("{}={}", name, value)));
+        assert_eq!(listed, ["ADDR=0x51", "PIN_2=5"]);
+
+        assert_eq!(unset("ADDR"), Ok(()));
+        assert_eq!(unset("ADDR"), Err(VarError::Unknown));
+        assert_eq!(get("ADDR"), None);
+    }
+
+    #[test]
+    fn test_bounds() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        reset();
+
+        for name in ["", "baud", "1X", "A-B", "NAME_TOO_LONG_"] {
+            assert_eq!(set(name, "1"), Err(VarError::BadName), "{}", name);
+        }
+        let long = "x".repeat(VAR_VALUE_MAX + 1);
+        assert_eq!(set("X", &long), Err(VarError::ValueTooLong));
+        for n in 0..VAR_MAX {
+            set(&std::format!("V{}", n), "1").unwrap();
+        }
+        assert_eq!(set("EXTRA", "1"), Err(VarError::Full));
+        assert_eq!(set("V0", "2"), Ok(()));
+    }
+}
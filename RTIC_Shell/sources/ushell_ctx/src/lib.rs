@@ -8,6 +8,42 @@
 //!   single `step()` method that the RTIC shell task calls in a loop.
 //! - [`ShellConfig`] — plain struct of function pointers that the application
 //!   fills in from its generated dispatchers, then hands to `ShellCtx::new()`.
+//! - [`ShellCtx::run_script`] — batch execution of a multi-line buffer, e.g.
+//!   a command sequence received from a provisioning host.
+//! - Output capture — a command's output collected into a buffer and handed
+//!   to [`ShellConfig::on_output`] instead of going to the UART.
+//! - Binary frames — SLIP or COBS framed payloads on the shell UART routed to
+//!   [`ShellConfig::on_frame`], everything else to the key parser.
+//! - Aliases and variables — `alias <name> "command"` / `unalias <name>` and
+//!   `set <NAME> <value>` / `unset <NAME>` / `echo` lines are handled here; a
+//!   line starting with an alias runs its command (see `ushell2::alias`) and
+//!   `$NAME` is replaced by the variable's value (see `ushell2::vars`), typed
+//!   or in a [`ShellCtx::run_script`] script.
+//! - Watch — `watch <interval_ms> <command...>` (or [`ShellCtx::start_watch`])
+//!   runs a command line again every interval, timed by [`ShellCtx::tick`],
+//!   until any key is pressed or [`ShellCtx::stop_watch`] is called.
+//! - Command statistics — with [`ShellConfig::tick_source`] set, the run
+//!   time of each command of the table is measured and `stats` lists the
+//!   calls and min / avg / max ticks per command (`stats reset` clears them).
+//! - Login gate — with [`ShellConfig::check_password`] set the shell starts
+//!   locked and only accepts `login <password>`, whose password is echoed
+//!   as `*` and kept out of the history; binary frames are dropped until
+//!   then. `#l` or [`ShellCtx::lock`] lock it again, [`ShellCtx::tick`]
+//!   after an idle time.
+//!   `#l` then no longer lists the history; `#h` does (`##` shows both).
+//! - Record loader — with [`ShellConfig::load_write`] set, `load ihex` or
+//!   `load srec` reads the lines that follow as Intel HEX or S-records,
+//!   checked and written through the callback until the end-of-file record,
+//!   then the prompt comes back (see [`LoadFormat`]).
+//! - Memory tools — with the `mem-tools` feature, `md <addr> <len>`,
+//!   `mw8|mw16|mw32 <addr> <val>` and `mfill <addr> <len> <byte>` read and
+//!   write the regions [`ShellConfig::mem_access`] allows.
+//! - Deferred execution — with [`ShellConfig::submit`] set, `step` queues
+//!   the lines to dispatch and an [`Executor`] runs them from a [`CommandQueue`]
+//!   in a lower-priority task, so a slow command does not stall typing.
+//! - Logger controls — `loglevel [error|warn|info|debug|verbose|trace]` and
+//!   `logcolor [on|off]` show or change the logger's minimum level and
+//!   whole-line coloring at runtime.
 //!
 //! ## What this crate does NOT do
 //! - Hardware or UART configuration (that is `uart_hal`'s job).
@@ -16,7 +52,7 @@
 //!
 //! ## Const-generic parameters
 //!
-//! `ShellCtx` is generic over five `usize` constants — all sourced from the
+//! `ShellCtx` is generic over six `usize` constants — all sourced from the
 //! generated dispatcher modules or from application constants:
 //!
 //! | Parameter | Meaning                                      | Typical source                        |
@@ -26,6 +62,7 @@
 //! | `IML`     | Input line max length                        | app constant `MAX_INPUT_LEN`          |
 //! | `HTC`     | History ring-buffer total capacity           | app constant `MAX_HISTORY_CAPACITY`   |
 //! | `E`       | Error message buffer size (heapless String)  | app constant `MAX_ERROR_BUFFER_SIZE`  |
+//! | `C`       | Output capture buffer size (default 128)     | app constant, if `on_output` is used  |
 //!
 //! In `main.rs` create a type alias so you only write the numbers once:
 //!
@@ -44,14 +81,30 @@
 //use heapless::String;
 
 
+use core::cell::RefCell;
+
+use ushell2::alias::{AliasError, AliasTable, ALIAS_MAX};
 use ushell2::input::parser::InputParser;
 use ushell2::input::key_reader::embedded::AnsiKeyParser;
 use ushell2::input::key_reader::Key;
 use ushell2::input::renderer::CallbackWriter;
 
-use uart_hal::{write_bytes, flush_noop, RxQueueReader};
+use uart_hal::{write_bytes, flush, ShellTransport};
+use heapless::String;
+use uart_hal::{FrameDetector, FrameEvent, FrameFormat, MAX_FRAME_SIZE};
+
+use ushell2::logger::{self, LogLevel};
+use ushell2::vars;
+use ushell2::{log_info, log_error, log_simple};
+
+mod loader;
+#[cfg(feature = "mem-tools")]
+mod memtools;
+mod queue;
 
-use ushell2::{log_info, log_error};
+use loader::Loader;
+pub use loader::{LoadFormat, LoadRangeFn, LoadSummary, LoadWriteFn, LOAD_LINE_MAX};
+pub use queue::{CommandQueue, QueueError};
 
 // ---------------------------------------------------------------------------
 // Concrete function-pointer type aliases
@@ -82,6 +135,82 @@ pub type IsShortcutFn   = fn(input: &str) -> bool;
 pub type DispatchFn<const E: usize> =
     for<'a> fn(&'a str, &'a mut heapless::String<E>) -> Result<(), &'a str>;
 
+/// Runtime dispatcher: executes `input` if its command is registered, `None`
+/// otherwise — typically `|line| DYN_COMMANDS.dispatch(line)` over a static
+/// `ushell2::registry::DynCommandRegistry`.
+pub type DynDispatchFn = fn(input: &str) -> Option<Result<(), &'static str>>;
+
+/// Receives a command line, its dispatch result and the output it produced,
+/// when output capture is on (see [`ShellConfig::on_output`]).
+pub type OutputFn = fn(input: &str, result: Result<(), &str>, output: &[u8]);
+
+/// Receives the decoded payload of a binary frame (see [`ShellConfig::on_frame`]).
+pub type FrameFn = fn(payload: &[u8]);
+
+/// Queues a command line for an [`Executor`]; `false` if it could not
+/// (see [`ShellConfig::submit`]).
+pub type SubmitFn = fn(line: &str) -> bool;
+
+/// Returns `true` when `password` unlocks the shell (see [`ShellConfig::check_password`]).
+pub type PasswordFn = fn(password: &str) -> bool;
+
+/// Free-running tick counter timing the commands, e.g. the DWT cycle counter
+/// (see [`ShellConfig::tick_source`]); may wrap.
+pub type TickFn = fn() -> u32;
+
+/// Returns `true` when the `len` bytes at `addr` may be read (`write` false)
+/// or written by the memory built-ins (see [`ShellConfig::mem_access`]).
+pub type MemAccessFn = fn(addr: usize, len: usize, write: bool) -> bool;
+
+/// Error the generated dispatcher reports for a command it does not know.
+const UNKNOWN_FUNCTION: &str = "UnknownFunction";
+
+/// The only command accepted while the shell is locked.
+const LOGIN_COMMAND: &str = "login";
+
+/// Error reported for any other line while the shell is locked.
+const SHELL_LOCKED: &str = "Shell locked (login <password>)";
+
+/// Shortest interval accepted by `watch`.
+pub const WATCH_MIN_INTERVAL_MS: u32 = 10;
+
+/// Entries of the command table timed by [`ShellConfig::tick_source`]; the
+/// commands after them are not measured.
+pub const MAX_STATS_ENTRIES: usize = 64;
+
+/// Command names the shell handles itself, before any dispatcher: a command
+/// of the table or a runtime command named like one would never run.  `load`
+/// and `login` are built-ins too, with `load_write` / `check_password` set.
+#[cfg(not(feature = "mem-tools"))]
+pub const BUILTINS: &[&str] = &[
+    "stats", "loglevel", "logcolor", "alias", "unalias", "set", "unset", "echo", "watch",
+];
+
+/// Command names the shell handles itself, before any dispatcher: a command
+/// of the table or a runtime command named like one would never run.  `load`
+/// and `login` are built-ins too, with `load_write` / `check_password` set.
+#[cfg(feature = "mem-tools")]
+pub const BUILTINS: &[&str] = &[
+    "stats", "loglevel", "logcolor", "alias", "unalias", "set", "unset", "echo", "watch",
+    "md", "mw8", "mw16", "mw32", "mfill",
+];
+
+/// A problem [`ShellConfig::validate`] found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigError {
+    /// A command of the table is named like a built-in, which runs instead
+    /// (see [`BUILTINS`]).
+    BuiltinName(&'static str),
+}
+
+impl ConfigError {
+    pub const fn message(&self) -> &'static str {
+        match self {
+            ConfigError::BuiltinName(_) => "command named like a built-in",
+        }
+    }
+}
+
 // ---------------------------------------------------------------------------
 // ShellConfig — application-supplied wiring
 // ---------------------------------------------------------------------------
@@ -102,6 +231,17 @@ pub type DispatchFn<const E: usize> =
 ///     is_shortcut:         shortcuts::is_supported_shortcut,
 ///     command_dispatcher:  commands::dispatch,
 ///     shortcut_dispatcher: shortcuts::dispatch,
+///     dyn_dispatcher:      None,
+///     on_output:           None,
+///     frame_format:        FrameFormat::Slip,
+///     on_frame:            None,
+///     check_password:      None,
+///     auto_lock_ms:        None,
+///     tick_source:         None,
+///     load_range:          None,
+///     load_write:          None,
+///     mem_access:          None,
+///     submit:              None,
 ///     prompt:              PROMPT,
 /// };
 /// let shell: MyShell = ShellCtx::new(config);
@@ -119,10 +259,68 @@ pub struct ShellConfig<const E: usize> {
     pub command_dispatcher:  DispatchFn<E>,
     /// Dispatches a shortcut line; writes an error message into `error_buf` on failure.
     pub shortcut_dispatcher: DispatchFn<E>,
+    /// Consulted for commands the `command_dispatcher` does not know, before
+    /// `UnknownFunction` is reported; `None` for the static table only.
+    pub dyn_dispatcher:      Option<DynDispatchFn>,
+    /// Output capture: while a command runs, everything written through
+    /// `uart_hal::write_bytes` (logger included) is collected into a buffer of
+    /// `C` bytes and passed here afterwards, e.g. to frame or checksum the
+    /// response.  `None` sends the output straight to the UART.
+    pub on_output:           Option<OutputFn>,
+    /// Framing of the binary protocol sharing the UART with the shell.
+    pub frame_format:        FrameFormat,
+    /// Receives binary frames (firmware update, telemetry, ...) found in the
+    /// RX stream; bytes outside frames are shell input.  `None` passes every
+    /// byte to the shell.  Frames up to `uart_hal::MAX_FRAME_SIZE` bytes; they
+    /// are dropped while the login gate keeps the shell locked.
+    pub on_frame:            Option<FrameFn>,
+    /// Login gate: the shell starts locked and only accepts `login <password>`,
+    /// checked by this callback; `#l` locks it again (the history is then
+    /// listed with `#h` only).  `None` for an open shell.  Compare in
+    /// constant time if the UART is exposed.
+    pub check_password:      Option<PasswordFn>,
+    /// Lock the shell after this long without input, counted by
+    /// [`ShellCtx::tick`].  Only used together with `check_password`.
+    pub auto_lock_ms:        Option<u32>,
+    /// Timestamps each command of the table before and after its dispatch,
+    /// for the `stats` built-in, e.g. `cortex_m::peripheral::DWT::cycle_count`
+    /// with the cycle counter enabled.  `None` measures nothing.
+    pub tick_source:         Option<TickFn>,
+    /// Checks the address range of each record read by `load` (see
+    /// `load_write`); `None` accepts any address.
+    pub load_range:          Option<LoadRangeFn>,
+    /// Writes the data of the records read by `load ihex` / `load srec`,
+    /// e.g. to flash.  `None` leaves `load` to the application's commands.
+    pub load_write:          Option<LoadWriteFn>,
+    /// Regions the memory built-ins of the `mem-tools` feature (`md`, `mw8`,
+    /// ...) may read and write.  `None` refuses every access.
+    pub mem_access:          Option<MemAccessFn>,
+    /// Deferred execution: the lines to dispatch are passed here, e.g. to
+    /// [`CommandQueue::enqueue`], for an [`Executor`] to run in a lower-priority
+    /// task.  Built-ins still run in `step`.  `None` runs each command as
+    /// soon as it is entered.
+    pub submit:              Option<SubmitFn>,
     /// The prompt string displayed before each input line (e.g. `">> "`).
     pub prompt:              &'static str,
 }
 
+impl<const E: usize> ShellConfig<E> {
+    /// Checks the command table; returns the first problem found.  Called by
+    /// [`ShellCtx::try_new`] and [`ShellCtx::new`].
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        for &(name, _) in (self.get_commands)() {
+            let word = name.split_whitespace().next().unwrap_or(name);
+            if BUILTINS.contains(&word)
+                || (word == "load" && self.load_write.is_some())
+                || (word == LOGIN_COMMAND && self.check_password.is_some())
+            {
+                return Err(ConfigError::BuiltinName(name));
+            }
+        }
+        Ok(())
+    }
+}
+
 // ---------------------------------------------------------------------------
 // ShellCtx
 // ---------------------------------------------------------------------------
@@ -141,6 +339,7 @@ pub struct ShellCtx<
     const IML: usize, // input max length
     const HTC: usize, // history total capacity
     const E:   usize, // error buffer size
+    const C:   usize = 128, // output capture buffer size
 > {
     parser: InputParser<
         'static,
@@ -152,9 +351,224 @@ pub struct ShellCtx<
     >,
     key_parser:          AnsiKeyParser,
     pending_key:         Option<Key>,
+    dispatch:            Dispatch<E>,
+    on_output:           Option<OutputFn>,
+    submit:              Option<SubmitFn>,
+    frames:              FrameDetector<MAX_FRAME_SIZE>,
+    on_frame:            Option<FrameFn>,
+    check_password:      Option<PasswordFn>,
+    auto_lock_ms:        Option<u32>,
+    idle_ms:             u32,
+    aliases:             RefCell<AliasTable<IML, ALIAS_MAX>>,
+    watcher:             RefCell<Option<Watcher<IML>>>,
+    profiler:            Profiler,
+    load_range:          Option<LoadRangeFn>,
+    load_write:          Option<LoadWriteFn>,
+    loader:              RefCell<Option<Loader>>,
+}
+
+/// A command line run again every `interval_ms` (see [`ShellCtx::start_watch`]).
+struct Watcher<const IML: usize> {
+    line:        String<IML>,
+    interval_ms: u32,
+    elapsed_ms:  u32,
+}
+
+impl<const IML: usize> Watcher<IML> {
+    /// Parse `<interval_ms> <command...>`, the arguments of `watch`.
+    fn parse(args: &str) -> Result<Self, &'static str> {
+        const USAGE: &str = "usage: watch <interval_ms> <command...>";
+        let (interval, line) = args.trim().split_once(char::is_whitespace).ok_or(USAGE)?;
+        let interval_ms: u32 = interval.parse().map_err(|_| USAGE)?;
+        Self::new(interval_ms, line)
+    }
+
+    fn new(interval_ms: u32, line: &str) -> Result<Self, &'static str> {
+        let line = line.trim();
+        if interval_ms < WATCH_MIN_INTERVAL_MS {
+            return Err("watch interval too short");
+        }
+        if line.is_empty() || line.split_whitespace().next() == Some("watch") {
+            return Err("watch needs a command other than watch");
+        }
+        Ok(Self {
+            line:        String::try_from(line).map_err(|_| "watched command too long")?,
+            interval_ms,
+            elapsed_ms:  0,
+        })
+    }
+}
+
+/// Run time of one command, in ticks of [`ShellConfig::tick_source`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CommandStats {
+    /// Times the command was dispatched.
+    pub calls: u32,
+    /// Shortest run time.
+    pub min:   u32,
+    /// Longest run time.
+    pub max:   u32,
+    /// Sum of the run times.
+    pub total: u64,
+}
+
+impl CommandStats {
+    /// Average run time; 0 before the first call.
+    pub fn avg(&self) -> u32 {
+        (self.total / u64::from(self.calls.max(1))) as u32
+    }
+
+    fn add(&mut self, ticks: u32) {
+        self.min   = if self.calls == 0 { ticks } else { self.min.min(ticks) };
+        self.max   = self.max.max(ticks);
+        self.total = self.total.saturating_add(u64::from(ticks));
+        self.calls = self.calls.saturating_add(1);
+    }
+}
+
+/// Per-command run times, indexed like the command table.
+struct Profiler {
+    tick:     Option<TickFn>,
+    commands: &'static [(&'static str, &'static str)],
+    table:    RefCell<[CommandStats; MAX_STATS_ENTRIES]>,
+}
+
+impl Profiler {
+    /// Run `run`, timing it if `line` names a command of the table.
+    fn time<R>(&self, line: &str, run: impl FnOnce() -> R) -> R {
+        let Some(tick) = self.tick else {
+            return run();
+        };
+        let name = line.split_whitespace().next().unwrap_or("");
+        let Some(index) = self.commands.iter()
+            .position(|&(command, _)| command == name)
+            .filter(|&index| index < MAX_STATS_ENTRIES)
+        else {
+            return run();
+        };
+        let start  = tick();
+        let result = run();
+        let ticks  = tick().wrapping_sub(start);
+        self.table.borrow_mut()[index].add(ticks);
+        result
+    }
+
+    /// The `stats` built-in: `stats` lists the commands run so far, `stats
+    /// reset` clears them.
+    fn builtin(&self, args: &str) -> Result<(), &'static str> {
+        if self.tick.is_none() {
+            return Err("stats: no tick source");
+        }
+        match args {
+            ""      => {
+                log_simple!("{:<16} {:>8} {:>10} {:>10} {:>10}", "command", "calls", "min", "avg", "max");
+                self.for_each(|name, stats| log_simple!(
+                    "{:<16} {:>8} {:>10} {:>10} {:>10}",
+                    name, stats.calls, stats.min, stats.avg(), stats.max
+                ));
+                Ok(())
+            }
+            "reset" => {
+                self.reset();
+                Ok(())
+            }
+            _       => Err("usage: stats [reset]"),
+        }
+    }
+
+    fn for_each(&self, mut f: impl FnMut(&'static str, &CommandStats)) {
+        let table = self.table.borrow();
+        for (&(name, _), stats) in self.commands.iter().zip(table.iter()) {
+            if stats.calls > 0 {
+                f(name, stats);
+            }
+        }
+    }
+
+    fn reset(&self) {
+        *self.table.borrow_mut() = [CommandStats::default(); MAX_STATS_ENTRIES];
+    }
+}
+
+/// The dispatch function-pointers, copied out of `ShellCtx` where the parser
+/// is borrowed.
+#[derive(Clone, Copy)]
+struct Dispatch<const E: usize> {
     is_shortcut:         IsShortcutFn,
     command_dispatcher:  DispatchFn<E>,
     shortcut_dispatcher: DispatchFn<E>,
+    dyn_dispatcher:      Option<DynDispatchFn>,
+    #[cfg(feature = "mem-tools")]
+    mem_access:          Option<MemAccessFn>,
+}
+
+impl<const E: usize> Dispatch<E> {
+    /// Execute one trimmed line as a shortcut or a command; with `on_output`
+    /// set, its output is captured into `C` bytes and handed over afterwards.
+    fn exec<'a, const C: usize>(
+        &self,
+        line:      &'a str,
+        error_buf: &'a mut heapless::String<E>,
+        on_output: Option<OutputFn>,
+    ) -> Result<(), &'a str> {
+        let Some(on_output) = on_output else {
+            return self.run(line, error_buf);
+        };
+        let mut output: heapless::Vec<u8, C> = heapless::Vec::new();
+        let result = uart_hal::with_capture(&mut output, move || self.run(line, error_buf));
+        on_output(line, result, &output);
+        result
+    }
+
+    /// Execute one trimmed line as a memory built-in, a shortcut or a command.
+    fn run<'a>(&self, line: &'a str, error_buf: &'a mut heapless::String<E>) -> Result<(), &'a str> {
+        #[cfg(feature = "mem-tools")]
+        if let Some(result) = memtools::run(line, self.mem_access) {
+            return result;
+        }
+        if (self.is_shortcut)(line) {
+            return (self.shortcut_dispatcher)(line, error_buf);
+        }
+        match (self.command_dispatcher)(line, error_buf) {
+            // Not in the static table: try the runtime commands
+            Err(UNKNOWN_FUNCTION) => self.dyn_dispatcher
+                .and_then(|dispatch| dispatch(line))
+                .unwrap_or(Err(UNKNOWN_FUNCTION)),
+            result => result,
+        }
+    }
+}
+
+/// Snapshot of the line editor and shell state (see [`ShellCtx::debug_state`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebugState {
+    /// Cursor position in the input line, in characters.
+    pub cursor:        usize,
+    /// Characters in the input line.
+    pub input_len:     usize,
+    /// Entries in the history.
+    pub history_len:   usize,
+    /// History entry the up/down arrow keys are at.
+    pub history_index: usize,
+    /// Free bytes of the history.
+    pub history_free:  usize,
+    /// The login gate keeps the shell locked.
+    pub locked:        bool,
+    /// `load` reads records instead of command lines.
+    pub loading:       bool,
+    /// A `watch` is running.
+    pub watching:      bool,
+    /// A key was decoded but not handled yet.
+    pub pending_key:   bool,
+}
+
+/// Outcome of [`ShellCtx::run_script`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ScriptSummary {
+    /// Lines executed (empty lines are not counted).
+    pub lines:  usize,
+    /// Lines whose dispatch returned an error.
+    pub failed: usize,
 }
 
 impl<
@@ -163,37 +577,96 @@ impl<
     const IML: usize,
     const HTC: usize,
     const E:   usize,
-> ShellCtx<NAC, FNL, IML, HTC, E>
+    const C:   usize,
+> ShellCtx<NAC, FNL, IML, HTC, E, C>
 {
     /// Construct a new shell context from the application-supplied config.
     ///
-    /// Uses [`uart_hal::write_bytes`] and [`uart_hal::flush_noop`] as the
-    /// underlying writer — no UART reference is stored in this struct.
+    /// Uses [`uart_hal::write_bytes`] and [`uart_hal::flush`] as the
+    /// underlying writer — output goes to the transport registered with
+    /// `uart_hal::init_uart_globals`; no transport is stored in this struct.
+    ///
+    /// # Panics
+    /// If the config does not [`validate`](ShellConfig::validate), e.g. a
+    /// command is named like a built-in; see [`try_new`](Self::try_new).
     pub fn new(config: ShellConfig<E>) -> Self {
+        match Self::try_new(config) {
+            Ok(shell) => shell,
+            Err(ConfigError::BuiltinName(name)) => panic!("command named like a built-in: {}", name),
+        }
+    }
+
+    /// [`new`](Self::new), returning the problem instead of panicking if the
+    /// config does not [`validate`](ShellConfig::validate).
+    pub fn try_new(config: ShellConfig<E>) -> Result<Self, ConfigError> {
+        config.validate()?;
         let writer = CallbackWriter::new(
             write_bytes as fn(&[u8]),
-            flush_noop  as fn(),
+            flush       as fn(),
         );
 
-        let parser = InputParser::new(
+        let mut parser = InputParser::new(
             writer,
             (config.get_commands)(),    // &'static [(&'static str, &'static str)]
             (config.get_datatypes)(),   // &'static str
             (config.get_shortcuts)(),   // &'static str
             config.prompt,              // &'static str
         );
+        if config.check_password.is_some() {
+            parser.enable_lock(true);
+        }
 
-        Self {
+        Ok(Self {
             parser,
             key_parser:          AnsiKeyParser::new(),
             pending_key:         None,
-            is_shortcut:         config.is_shortcut,
-            command_dispatcher:  config.command_dispatcher,
-            shortcut_dispatcher: config.shortcut_dispatcher,
-        }
+            dispatch:            Dispatch {
+                is_shortcut:         config.is_shortcut,
+                command_dispatcher:  config.command_dispatcher,
+                shortcut_dispatcher: config.shortcut_dispatcher,
+                dyn_dispatcher:      config.dyn_dispatcher,
+                #[cfg(feature = "mem-tools")]
+                mem_access:          config.mem_access,
+            },
+            on_output:           config.on_output,
+            submit:              config.submit,
+            frames:              FrameDetector::new(config.frame_format),
+            on_frame:            config.on_frame,
+            check_password:      config.check_password,
+            auto_lock_ms:        config.auto_lock_ms,
+            idle_ms:             0,
+            aliases:             RefCell::new(AliasTable::new()),
+            watcher:             RefCell::new(None),
+            profiler:            Profiler {
+                tick:     config.tick_source,
+                commands: (config.get_commands)(),
+                table:    RefCell::new([CommandStats::default(); MAX_STATS_ENTRIES]),
+            },
+            load_range:          config.load_range,
+            load_write:          config.load_write,
+            loader:              RefCell::new(None),
+        })
     }
 
-    /// Process one byte from `reader` and advance the parser state machine.
+    /// The executor running the lines [`ShellConfig::submit`] queued, with
+    /// this shell's dispatchers and output capture; move it into the task
+    /// that drains the [`CommandQueue`].
+    pub fn executor(&self) -> Executor<E, C> {
+        Executor { dispatch: self.dispatch, on_output: self.on_output }
+    }
+
+    /// Switch output capture on (`Some`) or off (`None`) at runtime, e.g. when
+    /// a host enters or leaves a machine-readable session.
+    pub fn set_output_capture(&mut self, on_output: Option<OutputFn>) {
+        self.on_output = on_output;
+    }
+
+    /// Process one byte from `transport` and advance the parser state machine.
+    ///
+    /// Only the input side is per call: echo, prompt, command and logger
+    /// output all go through [`uart_hal::write_bytes`] to the transport
+    /// registered with `uart_hal::init_uart_globals`, whichever `transport`
+    /// the byte came from.
     ///
     /// Returns `false` when the shell signals it wants to stop (e.g. the user
     /// typed `#q`). The caller should break its processing loop in that case.
@@ -210,42 +683,876 @@ impl<
     ///     }
     /// });
     /// ```
-    pub fn step(&mut self, reader: &mut RxQueueReader) -> bool {
+    pub fn step<T: ShellTransport>(&mut self, transport: &mut T) -> bool {
+        let byte = transport.read_byte().and_then(|byte| self.demux(byte));
+        // Record lines of a running `load` bypass the line editor
+        if self.loader.get_mut().is_some() {
+            if let Some(byte) = byte {
+                self.idle_ms = 0;
+                self.feed_loader(byte);
+            }
+            return true;
+        }
+
         // Decode one raw byte into an ANSI key event (handles multi-byte sequences)
-        if let Some(byte) = reader.read_byte() {
+        if let Some(byte) = byte {
+            self.idle_ms = 0;
+            // Any key stops a running watch and is not input otherwise
+            if self.stop_watch() {
+                log_info!("Watch stopped");
+                self.parser.handle_clear();
+                return true;
+            }
             if let Some(key) = self.key_parser.parse_byte(byte) {
                 self.pending_key = Some(key);
             }
         }
 
-        // Copy fn-pointers to locals so the closure below can capture them
+        // Copy fn-pointers to a local so the closure below can capture them
         // without borrowing `self` (which is already mutably borrowed by `parser`).
-        let is_shortcut         = self.is_shortcut;
-        let command_dispatcher  = self.command_dispatcher;
-        let shortcut_dispatcher = self.shortcut_dispatcher;
+        let dispatch       = self.dispatch;
+        let on_output      = self.on_output;
+        let submit         = self.submit;
+        let check_password = self.check_password;
+        let locked         = self.parser.is_locked();
+        let unlocked       = core::cell::Cell::new(false);
+        let aliases        = &self.aliases;
+        let watcher        = &self.watcher;
+        let profiler       = &self.profiler;
+        let loader         = &self.loader;
+        let load_write     = self.load_write;
 
-        self.parser.parse_input(
+        let running = self.parser.parse_input(
             // Key source: take the pending key decoded above
             || self.pending_key.take(),
 
-            // Output sink: write directly via uart_hal
+            // Output sink: the registered transport, like the rest of the output
             |s: &str| write_bytes(s.as_bytes()),
 
             // Command execution: called with the complete, trimmed input line
             |input| {
-                let mut error_buf: heapless::String<E> = heapless::String::new();
-
-                let result = if (is_shortcut)(input.as_str()) {
-                    (shortcut_dispatcher)(input.as_str(), &mut error_buf)
-                } else {
-                    (command_dispatcher)(input.as_str(), &mut error_buf)
-                };
+                if let Some(check_password) = check_password {
+                    match login_password(input) {
+                        Some(_) if !locked => {
+                            log_info!("Already logged in");
+                            return;
+                        }
+                        Some(password) if check_password(password) => {
+                            unlocked.set(true);
+                            log_info!("Unlocked");
+                            return;
+                        }
+                        Some(_) => {
+                            log_error!("Error: Wrong password");
+                            return;
+                        }
+                        None if locked => {
+                            log_error!("Error: {}", SHELL_LOCKED);
+                            return;
+                        }
+                        None => {}
+                    }
+                }
 
-                match result {
-                    Ok(_)  => log_info!("Success"),
-                    Err(e) => log_error!("Error: {}", e), // e: &str — Display is fine
+                let (word, args) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+                if word == "load" && load_write.is_some() {
+                    match LoadFormat::parse(args.trim()) {
+                        Some(format) => {
+                            log_info!("Send the {} file (Ctrl+C aborts)", format.name());
+                            *loader.borrow_mut() = Some(Loader::new(format));
+                        }
+                        None => log_error!("Error: usage: load <ihex|srec>"),
+                    }
+                    return;
+                }
+                if word != "watch" {
+                    match submit {
+                        Some(submit) => submit_line(aliases, profiler, submit, input.as_str()),
+                        None         => exec_line::<IML, E, C>(&dispatch, aliases, profiler, on_output, input.as_str()),
+                    }
+                    return;
+                }
+                match Watcher::parse(args) {
+                    Ok(watch) => {
+                        // Runs once now, then from `tick`
+                        log_info!("Every {} ms (press any key to stop)", watch.interval_ms);
+                        let line = watch.line.clone();
+                        *watcher.borrow_mut() = Some(watch);
+                        exec_line::<IML, E, C>(&dispatch, aliases, profiler, on_output, &line);
+                    }
+                    Err(e) => log_error!("Error: {}", e),
                 }
             },
-        )
+        );
+
+        if unlocked.get() {
+            self.parser.set_locked(false);
+        }
+        running
+    }
+
+    /// Snapshot of the editor and shell state, for diagnosing a shell that
+    /// seems stuck, e.g. logged from a debugger or a fault handler.
+    pub fn debug_state(&self) -> DebugState {
+        let history = self.parser.history();
+        DebugState {
+            cursor:        self.parser.cursor(),
+            input_len:     self.parser.input_len(),
+            history_len:   history.len(),
+            history_index: history.index(),
+            history_free:  history.get_free_space(),
+            locked:        self.parser.is_locked(),
+            loading:       self.is_loading(),
+            watching:      self.is_watching(),
+            pending_key:   self.pending_key.is_some(),
+        }
+    }
+
+    /// Returns `true` while the login gate keeps the shell locked.
+    pub fn is_locked(&self) -> bool {
+        self.parser.is_locked()
+    }
+
+    /// Lock the shell, as `#l` does; no effect without
+    /// [`ShellConfig::check_password`].
+    pub fn lock(&mut self) {
+        self.parser.set_locked(true);
+    }
+
+    /// Run `line` every `interval_ms` from now on, as the `watch` built-in
+    /// does, replacing a running watch. The line runs at the next
+    /// [`tick`](Self::tick) that completes an interval.
+    pub fn start_watch(&mut self, interval_ms: u32, line: &str) -> Result<(), &'static str> {
+        *self.watcher.get_mut() = Some(Watcher::new(interval_ms, line)?);
+        Ok(())
+    }
+
+    /// Stop the running watch; returns `false` if there was none.
+    pub fn stop_watch(&mut self) -> bool {
+        self.watcher.get_mut().take().is_some()
+    }
+
+    /// Returns `true` while a watch is running.
+    pub fn is_watching(&self) -> bool {
+        self.watcher.borrow().is_some()
+    }
+
+    /// Call `f` with the name and run times of each command run since start-up
+    /// or the last [`reset_stats`](Self::reset_stats), in table order; nothing
+    /// without [`ShellConfig::tick_source`].
+    pub fn for_each_stats(&self, f: impl FnMut(&'static str, &CommandStats)) {
+        self.profiler.for_each(f);
+    }
+
+    /// Clear the command statistics, as `stats reset` does.
+    pub fn reset_stats(&self) {
+        self.profiler.reset();
+    }
+
+    /// Advance the idle timer and the running watch by `elapsed_ms`; input
+    /// read by `step` resets the idle timer.
+    ///
+    /// Runs the watched line once its interval is complete (at most once per
+    /// call). Locks the shell once [`ShellConfig::auto_lock_ms`] is reached,
+    /// which also stops a watch, and returns `true` if it locked in this call.
+    /// Call it periodically from the task that owns the shell, e.g. once per
+    /// second:
+    ///
+    /// ```ignore
+    /// ctx.local.shell.tick(1000);
+    /// ```
+    pub fn tick(&mut self, elapsed_ms: u32) -> bool {
+        let locked = self.tick_lock(elapsed_ms);
+        if locked {
+            self.stop_watch();
+        }
+        self.tick_watch(elapsed_ms);
+        locked
+    }
+
+    fn tick_watch(&mut self, elapsed_ms: u32) {
+        let Some(watch) = self.watcher.get_mut() else {
+            return;
+        };
+        watch.elapsed_ms = watch.elapsed_ms.saturating_add(elapsed_ms);
+        if watch.elapsed_ms < watch.interval_ms {
+            return;
+        }
+        watch.elapsed_ms %= watch.interval_ms;
+        let line = watch.line.clone();
+        // Over the prompt line, which comes back when the watch stops
+        write_bytes(b"\r\x1B[K");
+        exec_line::<IML, E, C>(&self.dispatch, &self.aliases, &self.profiler, self.on_output, &line);
+    }
+
+    fn tick_lock(&mut self, elapsed_ms: u32) -> bool {
+        let Some(limit) = self.auto_lock_ms else {
+            return false;
+        };
+        if self.check_password.is_none() || self.parser.is_locked() {
+            return false;
+        }
+        self.idle_ms = self.idle_ms.saturating_add(elapsed_ms);
+        if self.idle_ms < limit {
+            return false;
+        }
+        self.idle_ms = 0;
+        self.parser.set_locked(true);
+        log_info!("Shell locked after {} ms without input", limit);
+        true
+    }
+
+    /// Returns `true` while `load` reads records instead of command lines.
+    pub fn is_loading(&self) -> bool {
+        self.loader.borrow().is_some()
+    }
+
+    /// Pass `byte` to the running `load`; the prompt comes back once the
+    /// end-of-file record was written or a record failed.
+    fn feed_loader(&mut self, byte: u8) {
+        let (Some(loader), Some(write)) = (self.loader.get_mut(), self.load_write) else {
+            return;
+        };
+        let Some(result) = loader.feed(byte, self.load_range, write) else {
+            return;
+        };
+        let line = loader.lines();
+        *self.loader.get_mut() = None;
+        match result {
+            Ok(summary)         => log_info!("Loaded {} bytes in {} records", summary.bytes, summary.records),
+            Err(e) if line == 0 => log_error!("Error: load: {}", e),
+            Err(e)              => log_error!("Error: load: {} (line {})", e, line),
+        }
+        self.parser.handle_clear();
+    }
+
+    /// Route `byte` to the binary frame handler if it belongs to a frame;
+    /// returns it if it is shell input.
+    fn demux(&mut self, byte: u8) -> Option<u8> {
+        let Some(on_frame) = self.on_frame else {
+            return Some(byte);
+        };
+        match self.frames.feed(byte) {
+            FrameEvent::Byte(byte)     => Some(byte),
+            FrameEvent::Pending        => None,
+            // Binary commands need the login as much as typed ones
+            FrameEvent::Frame(_) if self.parser.is_locked() => {
+                log_error!("Binary frame dropped: {}", SHELL_LOCKED);
+                None
+            }
+            FrameEvent::Frame(payload) => {
+                on_frame(payload);
+                None
+            }
+            FrameEvent::Overflow       => {
+                log_error!("Binary frame dropped: longer than {} bytes", MAX_FRAME_SIZE);
+                None
+            }
+            FrameEvent::Malformed      => {
+                log_error!("Binary frame dropped: malformed");
+                None
+            }
+        }
+    }
+
+    /// Drop a partly received binary frame, e.g. after an RX idle timeout, so
+    /// a stray frame start byte cannot hold back shell input.
+    pub fn reset_frame(&mut self) {
+        self.frames.reset();
+    }
+
+    /// Execute a batch of command lines without simulating keystrokes.
+    ///
+    /// `script` is split into lines on `\n` and `;` (a `;` inside double
+    /// quotes belongs to the argument); each non-empty line goes through the
+    /// same shortcut / command dispatch as a line typed at the prompt. All
+    /// lines run, also after an error. One report line per executed line is
+    /// written to `report`:
+    ///
+    /// ```text
+    /// 1: led 1 -> OK
+    /// 2: baud x -> Error: BadUnsigned
+    /// ```
+    ///
+    /// Writing stops silently when `report` is full (e.g. a heapless `String`);
+    /// the returned summary still counts every line. While the shell is
+    /// locked every line fails with the locked error.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut report: heapless::String<256> = heapless::String::new();
+    /// let summary = shell.run_script("led 1; gpio set 5 1\nbaud 9600", &mut report);
+    /// if summary.failed > 0 {
+    ///     log_error!("{}", report);
+    /// }
+    /// ```
+    pub fn run_script<W: core::fmt::Write>(&self, script: &str, report: &mut W) -> ScriptSummary {
+        let mut summary = ScriptSummary::default();
+        let mut report_full = false;
+
+        for line in ScriptLines(script) {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            summary.lines += 1;
+
+            let mut error_buf: heapless::String<E> = heapless::String::new();
+            let expanded;
+            let result = if self.parser.is_locked() {
+                Err(SHELL_LOCKED)
+            } else {
+                match resolve_line(&self.aliases, &self.profiler, line) {
+                    LineOutcome::Done(result)  => result,
+                    LineOutcome::Run(line_out) => {
+                        expanded = line_out;
+                        let line = expanded.as_deref().unwrap_or(line);
+                        self.profiler.time(line, || {
+                            self.dispatch.exec::<C>(line, &mut error_buf, self.on_output)
+                        })
+                    }
+                }
+            };
+            if result.is_err() {
+                summary.failed += 1;
+            }
+
+            if !report_full {
+                report_full = match result {
+                    Ok(())  => writeln!(report, "{}: {} -> OK", summary.lines, line),
+                    Err(e)  => writeln!(report, "{}: {} -> Error: {}", summary.lines, line, e),
+                }.is_err();
+            }
+        }
+        summary
+    }
+}
+
+/// Resolve aliases and variables in `line` and dispatch it, logging the
+/// result as a line typed at the prompt.
+fn exec_line<const IML: usize, const E: usize, const C: usize>(
+    dispatch:  &Dispatch<E>,
+    aliases:   &RefCell<AliasTable<IML, ALIAS_MAX>>,
+    profiler:  &Profiler,
+    on_output: Option<OutputFn>,
+    line:      &str,
+) {
+    let expanded = match resolve_line(aliases, profiler, line) {
+        LineOutcome::Done(Ok(()))  => return,
+        LineOutcome::Done(Err(e))  => {
+            log_error!("Error: {}", e);
+            return;
+        }
+        LineOutcome::Run(expanded) => expanded,
+    };
+    let line = expanded.as_deref().unwrap_or(line);
+
+    let mut error_buf: heapless::String<E> = heapless::String::new();
+
+    match profiler.time(line, || dispatch.exec::<C>(line, &mut error_buf, on_output)) {
+        Ok(_)  => log_info!("Success"),
+        Err(e) => log_error!("Error: {}", e), // e: &str — Display is fine
+    }
+}
+
+/// Resolve aliases and variables in `line` and hand it to `submit` for an
+/// [`Executor`]; built-ins run here, as in [`exec_line`].
+fn submit_line<const IML: usize>(
+    aliases:  &RefCell<AliasTable<IML, ALIAS_MAX>>,
+    profiler: &Profiler,
+    submit:   SubmitFn,
+    line:     &str,
+) {
+    let expanded = match resolve_line(aliases, profiler, line) {
+        LineOutcome::Done(Ok(()))  => return,
+        LineOutcome::Done(Err(e))  => {
+            log_error!("Error: {}", e);
+            return;
+        }
+        LineOutcome::Run(expanded) => expanded,
+    };
+    if !submit(expanded.as_deref().unwrap_or(line)) {
+        log_error!("Error: command queue full, command dropped");
+    }
+}
+
+/// Runs the command lines queued through [`ShellConfig::submit`], in a task
+/// of lower priority than the shell's (see [`ShellCtx::executor`]).
+#[derive(Clone, Copy)]
+pub struct Executor<const E: usize, const C: usize = 128> {
+    dispatch:  Dispatch<E>,
+    on_output: Option<OutputFn>,
+}
+
+impl<const E: usize, const C: usize> Executor<E, C> {
+    /// Run the oldest line of `queue`, logging its result as `step` does;
+    /// returns `false` if the queue was empty.  Call it until it does.
+    pub fn run_queued<const IML: usize, const N: usize>(&self, queue: &CommandQueue<IML, N>) -> bool {
+        let Some(line) = queue.dequeue() else {
+            return false;
+        };
+        let mut error_buf: heapless::String<E> = heapless::String::new();
+        match self.dispatch.exec::<C>(&line, &mut error_buf, self.on_output) {
+            Ok(_)  => log_info!("Success"),
+            Err(e) => log_error!("Error: {}", e),
+        }
+        true
+    }
+}
+
+/// What [`resolve_line`] made of a line.
+enum LineOutcome<const IML: usize> {
+    /// An alias, variable, logger or `stats` built-in, already handled.
+    Done(Result<(), &'static str>),
+    /// A line to dispatch: its expansion, or `None` to dispatch it unchanged.
+    Run(Option<heapless::String<IML>>),
+}
+
+/// Handle the alias, variable, logger and `stats` built-ins, or expand the line's
+/// leading alias and then its variables.
+fn resolve_line<const IML: usize>(
+    aliases:  &RefCell<AliasTable<IML, ALIAS_MAX>>,
+    profiler: &Profiler,
+    line:     &str,
+) -> LineOutcome<IML> {
+    if let Some(args) = line.strip_prefix("stats") {
+        if args.is_empty() || args.starts_with(char::is_whitespace) {
+            return LineOutcome::Done(profiler.builtin(args.trim()));
+        }
+    }
+    if let Some(result) = resolve_log(line) {
+        return LineOutcome::Done(result);
+    }
+    let aliased = match resolve_alias(aliases, line) {
+        LineOutcome::Run(aliased) => aliased,
+        done                      => return done,
+    };
+    match resolve_vars(aliased.as_deref().unwrap_or(line)) {
+        LineOutcome::Run(None) => LineOutcome::Run(aliased),
+        outcome                => outcome,
+    }
+}
+
+/// Handle `loglevel [<level>]` and `logcolor [on|off]`: without an argument
+/// they show the current setting, otherwise change it. `None` for any other
+/// line.
+fn resolve_log(line: &str) -> Option<Result<(), &'static str>> {
+    let mut words = line.split_whitespace();
+    let name      = words.next()?;
+    let args      = (words.next(), words.next());
+    let result = match (name, args) {
+        ("loglevel", (None, _)) => {
+            log_info!("loglevel: {}", logger::min_level().name());
+            Ok(())
+        }
+        ("loglevel", (Some(level), None)) => match LogLevel::from_name(level) {
+            Some(level) => {
+                logger::set_min_level(level);
+                Ok(())
+            }
+            None        => Err(LOGLEVEL_USAGE),
+        },
+        ("loglevel", _) => Err(LOGLEVEL_USAGE),
+        ("logcolor", (None, _)) => {
+            log_info!("logcolor: {}", if logger::color_entire_line() { "on" } else { "off" });
+            Ok(())
+        }
+        ("logcolor", (Some("on"), None)) => {
+            logger::set_color_entire_line(true);
+            Ok(())
+        }
+        ("logcolor", (Some("off"), None)) => {
+            logger::set_color_entire_line(false);
+            Ok(())
+        }
+        ("logcolor", _) => Err("usage: logcolor [on|off]"),
+        _               => return None,
+    };
+    Some(result)
+}
+
+const LOGLEVEL_USAGE: &str = "usage: loglevel [error|warn|info|debug|verbose|trace]";
+
+/// Expand `$NAME` in `line`, then handle `set <NAME> <value>` (`set` alone
+/// lists the variables), `unset <NAME>` and `echo`. A `set` with a
+/// lower-case key is left to the application's command.
+fn resolve_vars<const IML: usize>(line: &str) -> LineOutcome<IML> {
+    let expanded = match vars::expand::<IML>(line) {
+        Ok(expanded) => expanded,
+        Err(e)       => return LineOutcome::Done(Err(e.message())),
+    };
+    let line         = expanded.as_deref().unwrap_or(line);
+    let (word, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let args         = args.trim();
+    let result = match (word, args.split_once(char::is_whitespace)) {
+        ("set", _) if args.is_empty() => {
+            vars::for_each(|name, value| log_info!("{}={}", name, value));
+            Ok(())
+        }
+        ("set", split) => {
+            let (name, value) = split.unwrap_or((args, ""));
+            if !vars::is_var_name(name) {
+                return LineOutcome::Run(expanded);
+            }
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|quoted| quoted.strip_suffix('"'))
+                .unwrap_or(value);
+            vars::set(name, value)
+        }
+        ("unset", _) => vars::unset(args),
+        ("echo", _)  => {
+            log_simple!("{}", args);
+            Ok(())
+        }
+        _ => return LineOutcome::Run(expanded),
+    };
+    LineOutcome::Done(result.map_err(|e| e.message()))
+}
+
+/// Handle an `alias` / `unalias` line, or expand the alias `line` starts
+/// with (see `ushell2::alias`).
+fn resolve_alias<const IML: usize>(
+    aliases: &RefCell<AliasTable<IML, ALIAS_MAX>>,
+    line:    &str,
+) -> LineOutcome<IML> {
+    let (name, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let args         = args.trim();
+    let mut aliases  = aliases.borrow_mut();
+    let result = match (name, args.split_once(char::is_whitespace)) {
+        ("alias", None) if args.is_empty() => {
+            for (name, command) in aliases.iter() {
+                log_info!("{} = \"{}\"", name, command);
+            }
+            Ok(())
+        }
+        ("alias", None) => match aliases.get(args) {
+            Some(command) => {
+                log_info!("{} = \"{}\"", args, command);
+                Ok(())
+            }
+            None => Err(AliasError::Unknown),
+        },
+        ("alias", Some((name, command))) => {
+            let command = command.trim();
+            let command = command
+                .strip_prefix('"')
+                .and_then(|quoted| quoted.strip_suffix('"'))
+                .unwrap_or(command);
+            aliases.define(name, command)
+        }
+        ("unalias", _) => aliases.remove(args),
+        _ => return match aliases.expand(line) {
+            Ok(expanded) => LineOutcome::Run(expanded),
+            Err(e)       => LineOutcome::Done(Err(e.message())),
+        },
+    };
+    LineOutcome::Done(result.map_err(|e| e.message()))
+}
+
+/// The password of a `login <password>` line; `None` for any other line.
+fn login_password(line: &str) -> Option<&str> {
+    match line.split_once(char::is_whitespace) {
+        Some((LOGIN_COMMAND, password)) => Some(password.trim()),
+        None if line == LOGIN_COMMAND   => Some(""),
+        _                               => None,
+    }
+}
+
+/// Lines of a script: split at `\n` and at `;` outside double quotes.
+struct ScriptLines<'a>(&'a str);
+
+impl<'a> Iterator for ScriptLines<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let mut quoted = false;
+        let end = self.0
+            .char_indices()
+            .find(|&(_, c)| {
+                if c == '"' {
+                    quoted = !quoted;
+                }
+                c == '\n' || (c == ';' && !quoted)
+            })
+            .map(|(i, _)| i);
+        let (line, rest) = match end {
+            Some(i) => (&self.0[..i], &self.0[i + 1..]),
+            None    => (self.0, ""),
+        };
+        self.0 = rest;
+        Some(line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::collections::VecDeque;
+    use std::string::String as StdString;
+    use std::sync::{Mutex, MutexGuard, Once};
+    use std::vec::Vec;
+
+    type TestShell = ShellCtx<4, 16, 64, 256, 64>;
+
+    const COMMANDS: &[(&str, &str)] = &[("gpio", "u"), ("reset", "")];
+
+    /// The shell output goes to one registered transport: one test at a time.
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    /// Everything written to the registered transport.
+    static OUTPUT: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+    /// Lines the command dispatcher ran.
+    static CALLS: Mutex<Vec<StdString>> = Mutex::new(Vec::new());
+
+    /// Payloads handed to `on_frame`.
+    static FRAMES: Mutex<Vec<Vec<u8>>> = Mutex::new(Vec::new());
+
+    /// The transport `init_uart_globals` registers, collecting into `OUTPUT`.
+    struct Sink;
+
+    impl ShellTransport for Sink {
+        fn write_bytes(&mut self, bytes: &[u8]) {
+            OUTPUT.lock().unwrap().extend_from_slice(bytes);
+        }
+
+        fn flush(&mut self) {}
+
+        fn read_byte(&mut self) -> Option<u8> {
+            None
+        }
+    }
+
+    /// Typed input for `step`; the output goes to the registered `Sink`.
+    struct Keys(VecDeque<u8>);
+
+    impl ShellTransport for Keys {
+        fn write_bytes(&mut self, _: &[u8]) {
+            panic!("step output bypassed the registered transport");
+        }
+
+        fn flush(&mut self) {}
+
+        fn read_byte(&mut self) -> Option<u8> {
+            self.0.pop_front()
+        }
+    }
+
+    fn commands() -> &'static [(&'static str, &'static str)] {
+        COMMANDS
+    }
+
+    fn no_text() -> &'static str {
+        ""
+    }
+
+    fn no_shortcut(_: &str) -> bool {
+        false
+    }
+
+    fn dispatch<'a>(line: &'a str, _: &'a mut heapless::String<64>) -> Result<(), &'a str> {
+        let name = line.split_whitespace().next().unwrap_or("");
+        if !COMMANDS.iter().any(|&(command, _)| command == name) {
+            return Err(UNKNOWN_FUNCTION);
+        }
+        CALLS.lock().unwrap().push(line.into());
+        Ok(())
+    }
+
+    fn record_frame(payload: &[u8]) {
+        FRAMES.lock().unwrap().push(payload.to_vec());
+    }
+
+    fn check_password(password: &str) -> bool {
+        password == "secret"
+    }
+
+    fn config() -> ShellConfig<64> {
+        ShellConfig {
+            get_commands:        commands,
+            get_datatypes:       no_text,
+            get_shortcuts:       no_text,
+            is_shortcut:         no_shortcut,
+            command_dispatcher:  dispatch,
+            shortcut_dispatcher: dispatch,
+            dyn_dispatcher:      None,
+            on_output:           None,
+            frame_format:        FrameFormat::Slip,
+            on_frame:            None,
+            check_password:      None,
+            auto_lock_ms:        None,
+            tick_source:         None,
+            load_range:          None,
+            load_write:          None,
+            mem_access:          None,
+            submit:              None,
+            prompt:              "> ",
+        }
+    }
+
+    /// Take the test lock and clear what the previous test left behind.
+    fn setup() -> MutexGuard<'static, ()> {
+        static INIT: Once = Once::new();
+        let guard = LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        // Safety: registered once, and a leaked box lives for 'static
+        INIT.call_once(|| unsafe {
+            uart_hal::init_uart_globals(std::boxed::Box::leak(std::boxed::Box::new(Sink)))
+        });
+        OUTPUT.lock().unwrap().clear();
+        CALLS.lock().unwrap().clear();
+        FRAMES.lock().unwrap().clear();
+        guard
+    }
+
+    /// Step `shell` through `input`; returns the output it produced.
+    fn type_in(shell: &mut TestShell, input: &[u8]) -> StdString {
+        let mut keys = Keys(input.iter().copied().collect());
+        while !keys.0.is_empty() {
+            shell.step(&mut keys);
+        }
+        StdString::from_utf8_lossy(&core::mem::take(&mut *OUTPUT.lock().unwrap())).into_owned()
+    }
+
+    #[test]
+    fn test_login_gate() {
+        let _guard = setup();
+        let mut shell = TestShell::new(ShellConfig {
+            on_frame:       Some(record_frame),
+            check_password: Some(check_password),
+            ..config()
+        });
+        assert!(shell.is_locked());
+
+        // Frames are dropped until the login
+        type_in(&mut shell, b"\xC0\x01\x02\xC0");
+        assert!(FRAMES.lock().unwrap().is_empty());
+
+        let output = type_in(&mut shell, b"login secret");
+        assert!(!output.contains("secret"), "{:?}", output);
+        assert!(output.contains("login ******"), "{:?}", output);
+        type_in(&mut shell, b"\r");
+        assert!(!shell.is_locked());
+
+        type_in(&mut shell, b"\xC0\x01\x02\xC0");
+        assert_eq!(*FRAMES.lock().unwrap(), [[1, 2]]);
+
+        // The login line is not in the history
+        type_in(&mut shell, b"x 1\r");
+        let output = type_in(&mut shell, b"\x1B[A\x1B[A");
+        assert!(output.contains("x 1"), "{:?}", output);
+        assert!(!output.contains("login"), "{:?}", output);
+    }
+
+    #[test]
+    fn test_step_output_goes_to_registered_transport() {
+        let _guard = setup();
+        let mut shell = TestShell::new(config());
+
+        // The echo, the line break of the parser and the next prompt
+        let output = type_in(&mut shell, b"x 1\r");
+        assert!(output.contains("> x 1"), "{:?}", output);
+        assert!(output.ends_with("\r\n\r\x1B[K> \x1B[3G"), "{:?}", output);
+    }
+
+    /// Lines `step` submitted, for `test_deferred_execution`.
+    static QUEUE: CommandQueue<64, 2> = CommandQueue::new();
+
+    fn submit(line: &str) -> bool {
+        QUEUE.enqueue(line).is_ok()
+    }
+
+    #[test]
+    fn test_deferred_execution() {
+        let _guard = setup();
+        while QUEUE.dequeue().is_some() {}
+        let mut shell = TestShell::new(ShellConfig { submit: Some(submit), ..config() });
+        let executor = shell.executor();
+
+        // Queued, not run: the prompt is back at once
+        let output = type_in(&mut shell, b"x 1\r");
+        assert!(output.ends_with("> \x1B[3G"), "{:?}", output);
+        assert_eq!(QUEUE.len(), 1);
+        assert!(CALLS.lock().unwrap().is_empty());
+
+        // Built-ins run in `step`; the variable is expanded before queueing
+        type_in(&mut shell, b"set PIN 5\r");
+        type_in(&mut shell, b"x $PIN\r");
+        assert_eq!(QUEUE.len(), 2);
+
+        // The queue is full: the line is dropped
+        type_in(&mut shell, b"x 3\r");
+        assert_eq!((QUEUE.len(), QUEUE.dropped()), (2, 1));
+
+        assert_eq!(QUEUE.dequeue().unwrap().as_str(), "x 1");
+        assert_eq!(QUEUE.dequeue().unwrap().as_str(), "x 5");
+
+        // The executor dispatches as `step` would have (autocompleted "r")
+        type_in(&mut shell, b"r\r");
+        assert!(CALLS.lock().unwrap().is_empty());
+        assert!(executor.run_queued(&QUEUE));
+        assert!(!executor.run_queued(&QUEUE));
+        assert_eq!(*CALLS.lock().unwrap(), ["reset "]);
+    }
+
+    #[test]
+    fn test_debug_state() {
+        let _guard = setup();
+        let mut shell = TestShell::new(ShellConfig { check_password: Some(check_password), ..config() });
+        let state = shell.debug_state();
+        assert!(state.locked);
+        assert_eq!((state.cursor, state.input_len, state.history_len), (0, 0, 0));
+
+        type_in(&mut shell, b"login secret\rx 12\rx 345\x1B[D\x1B[D");
+        let state = shell.debug_state();
+        assert!(!state.locked);
+        assert_eq!((state.cursor, state.input_len), (3, 5));
+        assert_eq!((state.history_len, state.history_index), (1, 0));
+        assert!(state.history_free < 256);
+        assert!(!state.loading && !state.watching && !state.pending_key);
+
+        // Entered: an empty line; up recalls an entry, cursor at its end
+        type_in(&mut shell, b"\r");
+        let state = shell.debug_state();
+        assert_eq!((state.cursor, state.input_len), (0, 0));
+        assert_eq!((state.history_len, state.history_index), (2, 1));
+        type_in(&mut shell, b"\x1B[A");
+        let state = shell.debug_state();
+        assert_eq!((state.cursor, state.input_len, state.history_index), (4, 4, 0));
+    }
+
+    fn builtin_commands() -> &'static [(&'static str, &'static str)] {
+        &[("gpio", "u"), ("echo", "s")]
+    }
+
+    fn login_commands() -> &'static [(&'static str, &'static str)] {
+        &[("gpio", "u"), ("login", "s")]
+    }
+
+    #[test]
+    fn test_builtin_names_rejected() {
+        let _guard = setup();
+        assert_eq!(config().validate(), Ok(()));
+        let shadowed = ShellConfig { get_commands: builtin_commands, ..config() };
+        assert_eq!(shadowed.validate(), Err(ConfigError::BuiltinName("echo")));
+        assert!(TestShell::try_new(shadowed).is_err());
+
+        // `login` is only taken with the login gate on
+        let login = ShellConfig { get_commands: login_commands, ..config() };
+        assert_eq!(login.validate(), Ok(()));
+        let gated = ShellConfig { check_password: Some(check_password), ..login };
+        assert_eq!(gated.validate(), Err(ConfigError::BuiltinName("login")));
+
+        let registry = ushell2::registry::DynCommandRegistry::<2>::reserving(BUILTINS);
+        for name in ["stats", "loglevel", "logcolor", "set", "unset", "echo", "alias"] {
+            assert_eq!(registry.register(name, |_| Ok(())), Err(ushell2::registry::RegistryError::Reserved));
+        }
+        assert_eq!(registry.register("settings", |_| Ok(())), Ok(()));
     }
 }
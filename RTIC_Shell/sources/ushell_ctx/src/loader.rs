@@ -0,0 +1,348 @@
+//! Intel HEX / Motorola S-record loader behind the `load` built-in.
+//!
+//! While a [`Loader`] is active, [`ShellCtx::step`](crate::ShellCtx::step)
+//! feeds it the raw input bytes instead of the key parser: each line is one
+//! record, checked (syntax, checksum, [`ShellConfig::load_range`](crate::ShellConfig::load_range))
+//! and its data handed to [`ShellConfig::load_write`](crate::ShellConfig::load_write),
+//! until the end-of-file record.
+
+/// Longest record line accepted: an Intel HEX record of 255 data bytes
+/// (`:` + 2 × 260 hex digits), S-records are shorter.
+pub const LOAD_LINE_MAX: usize = 521;
+
+/// Bytes of the longest decoded record (length, address, type, 255 data
+/// bytes, checksum).
+const RECORD_MAX: usize = 260;
+
+/// Ctrl+C: aborts a load.
+const ABORT: u8 = 0x03;
+
+/// Receives the data of each record at its absolute address; an error stops
+/// the load.
+pub type LoadWriteFn = fn(addr: u32, data: &[u8]) -> Result<(), &'static str>;
+
+/// Returns `true` when `len` bytes at `addr` may be written.
+pub type LoadRangeFn = fn(addr: u32, len: usize) -> bool;
+
+/// Record format read by `load`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadFormat {
+    /// `load ihex`: `:LLAAAATT<data>CC` records, up to `:00000001FF`.
+    IntelHex,
+    /// `load srec`: `S1`/`S2`/`S3` data records, up to `S7`/`S8`/`S9`.
+    Srec,
+}
+
+impl LoadFormat {
+    /// The format named by the argument of `load`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "ihex" => Some(LoadFormat::IntelHex),
+            "srec" => Some(LoadFormat::Srec),
+            _      => None,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            LoadFormat::IntelHex => "Intel HEX",
+            LoadFormat::Srec     => "S-record",
+        }
+    }
+}
+
+/// What one record holds.
+enum Record {
+    /// Data at an address relative to the current base (Intel HEX) or
+    /// absolute (S-record), in `buf[start..end]` of the decoded record.
+    Data { addr: u32, start: usize, end: usize },
+    /// New upper address bits (Intel HEX types 02 and 04).
+    Base(u32),
+    /// End of file.
+    End,
+    /// Header, count or start address: nothing to write.
+    Skip,
+}
+
+/// Outcome of a finished load.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadSummary {
+    /// Data records written.
+    pub records: u32,
+    /// Data bytes written.
+    pub bytes:   u32,
+}
+
+/// State of a running load: the record line being received and the totals.
+pub struct Loader {
+    format:   LoadFormat,
+    line:     heapless::Vec<u8, LOAD_LINE_MAX>,
+    /// The line being received is longer than `LOAD_LINE_MAX`.
+    overflow: bool,
+    /// Lines received, for error reports.
+    lines:    u32,
+    /// Upper address bits set by Intel HEX extended address records.
+    base:     u32,
+    summary:  LoadSummary,
+}
+
+impl Loader {
+    pub fn new(format: LoadFormat) -> Self {
+        Self {
+            format,
+            line:     heapless::Vec::new(),
+            overflow: false,
+            lines:    0,
+            base:     0,
+            summary:  LoadSummary { records: 0, bytes: 0 },
+        }
+    }
+
+    /// Lines received so far; the failing one when `feed` returns an error.
+    pub fn lines(&self) -> u32 {
+        self.lines
+    }
+
+    /// Take one input byte. Returns `None` while the load goes on, the totals
+    /// after the end-of-file record, or the error that stopped it.
+    pub fn feed(
+        &mut self,
+        byte:  u8,
+        range: Option<LoadRangeFn>,
+        write: LoadWriteFn,
+    ) -> Option<Result<LoadSummary, &'static str>> {
+        match byte {
+            ABORT         => Some(Err("aborted")),
+            b'\r' | b'\n' => {
+                if self.line.is_empty() && !self.overflow {
+                    return None;
+                }
+                self.lines += 1;
+                let result = if self.overflow {
+                    Err("record too long")
+                } else {
+                    self.record(range, write)
+                };
+                self.line.clear();
+                self.overflow = false;
+                match result {
+                    Ok(false) => None,
+                    Ok(true)  => Some(Ok(self.summary)),
+                    Err(e)    => Some(Err(e)),
+                }
+            }
+            _ => {
+                self.overflow |= self.line.push(byte).is_err();
+                None
+            }
+        }
+    }
+
+    /// Check and write the record line; returns `true` for the end-of-file
+    /// record.
+    fn record(&mut self, range: Option<LoadRangeFn>, write: LoadWriteFn) -> Result<bool, &'static str> {
+        let mut buf = [0u8; RECORD_MAX];
+        let record = match self.format {
+            LoadFormat::IntelHex => ihex_record(&self.line, &mut buf)?,
+            LoadFormat::Srec     => srec_record(&self.line, &mut buf)?,
+        };
+        match record {
+            Record::Data { addr, start, end } => {
+                let addr = self.base.wrapping_add(addr);
+                let data = &buf[start..end];
+                if range.is_some_and(|in_range| !in_range(addr, data.len())) {
+                    return Err("address out of range");
+                }
+                write(addr, data)?;
+                self.summary.records += 1;
+                self.summary.bytes   += data.len() as u32;
+                Ok(false)
+            }
+            Record::Base(base) => {
+                self.base = base;
+                Ok(false)
+            }
+            Record::End  => Ok(true),
+            Record::Skip => Ok(false),
+        }
+    }
+}
+
+/// Decode the hex digits of `text` into `buf`; returns the byte count.
+fn decode_hex(text: &[u8], buf: &mut [u8; RECORD_MAX]) -> Result<usize, &'static str> {
+    if !text.len().is_multiple_of(2) || text.len() / 2 > RECORD_MAX {
+        return Err("bad record length");
+    }
+    fn digit(c: u8) -> Result<u8, &'static str> {
+        (c as char).to_digit(16).map(|d| d as u8).ok_or("bad hex digit")
+    }
+    for (byte, pair) in buf.iter_mut().zip(text.chunks_exact(2)) {
+        *byte = (digit(pair[0])? << 4) | digit(pair[1])?;
+    }
+    Ok(text.len() / 2)
+}
+
+/// `:LLAAAATT<data>CC`: the bytes, checksum included, add up to 0.
+fn ihex_record(line: &[u8], buf: &mut [u8; RECORD_MAX]) -> Result<Record, &'static str> {
+    let text = line.strip_prefix(b":").ok_or("not an Intel HEX record")?;
+    let len = decode_hex(text, buf)?;
+    if len < 5 || len != buf[0] as usize + 5 {
+        return Err("bad record length");
+    }
+    if buf[..len].iter().fold(0u8, |sum, &b| sum.wrapping_add(b)) != 0 {
+        return Err("bad checksum");
+    }
+    let addr  = u32::from(u16::from_be_bytes([buf[1], buf[2]]));
+    let value = || u32::from(u16::from_be_bytes([buf[4], buf[5]]));
+    match (buf[3], buf[0]) {
+        (0x00, _) => Ok(Record::Data { addr, start: 4, end: len - 1 }),
+        (0x01, _) => Ok(Record::End),
+        (0x02, 2) => Ok(Record::Base(value() << 4)),
+        (0x04, 2) => Ok(Record::Base(value() << 16)),
+        (0x03 | 0x05, _) => Ok(Record::Skip),
+        _ => Err("bad record type"),
+    }
+}
+
+/// `St<count><address><data><checksum>`: the bytes from the count on,
+/// checksum included, add up to 0xFF.
+fn srec_record(line: &[u8], buf: &mut [u8; RECORD_MAX]) -> Result<Record, &'static str> {
+    let (kind, text) = match line {
+        [b'S', kind, text @ ..] => (*kind, text),
+        _                       => return Err("not an S-record"),
+    };
+    let len = decode_hex(text, buf)?;
+    if len < 3 || len != buf[0] as usize + 1 {
+        return Err("bad record length");
+    }
+    if buf[..len].iter().fold(0u8, |sum, &b| sum.wrapping_add(b)) != 0xFF {
+        return Err("bad checksum");
+    }
+    let addr_len = match kind {
+        b'1' | b'9'        => 2,
+        b'2' | b'8'        => 3,
+        b'3' | b'7'        => 4,
+        b'0' | b'5' | b'6' => return Ok(Record::Skip),
+        _                  => return Err("bad record type"),
+    };
+    if len < addr_len + 2 {
+        return Err("bad record length");
+    }
+    if matches!(kind, b'7' | b'8' | b'9') {
+        return Ok(Record::End);
+    }
+    let addr = buf[1..=addr_len].iter().fold(0u32, |addr, &b| (addr << 8) | u32::from(b));
+    Ok(Record::Data { addr, start: addr_len + 1, end: len - 1 })
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use core::cell::RefCell;
+    use std::vec::Vec;
+
+    /// `(addr, data)` of each write of a load.
+    type Writes = Vec<(u32, Vec<u8>)>;
+
+    std::thread_local! {
+        static WRITES: RefCell<Writes> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn record_write(addr: u32, data: &[u8]) -> Result<(), &'static str> {
+        WRITES.with(|writes| writes.borrow_mut().push((addr, data.to_vec())));
+        Ok(())
+    }
+
+    fn below_64k(addr: u32, len: usize) -> bool {
+        addr as usize + len <= 0x1_0000
+    }
+
+    /// Feed `lines` to a new loader; the outcome of the load, if it ended,
+    /// and the writes made.
+    fn load(
+        format: LoadFormat,
+        lines:  &[&str],
+        range:  Option<LoadRangeFn>,
+    ) -> (Option<Result<LoadSummary, &'static str>>, Writes) {
+        WRITES.with(|writes| writes.borrow_mut().clear());
+        let mut loader = Loader::new(format);
+        let outcome = lines
+            .iter()
+            .flat_map(|line| line.bytes().chain(*b"\r\n"))
+            .find_map(|byte| loader.feed(byte, range, record_write));
+        (outcome, WRITES.with(|writes| writes.take()))
+    }
+
+    #[test]
+    fn test_ihex_records() {
+        let lines = [":0400100001020304E2", ":020000040800F2", ":0400100001020304E2", ":00000001FF"];
+        let (outcome, writes) = load(LoadFormat::IntelHex, &lines, None);
+        assert_eq!(outcome, Some(Ok(LoadSummary { records: 2, bytes: 8 })));
+        assert_eq!(writes, [
+            (0x0000_0010, std::vec![1, 2, 3, 4]),
+            (0x0800_0010, std::vec![1, 2, 3, 4]),
+        ]);
+
+        // A segment address counts in 16-byte paragraphs
+        let lines = [":020000021000EC", ":0400100001020304E2"];
+        let (outcome, writes) = load(LoadFormat::IntelHex, &lines, None);
+        assert_eq!(outcome, None);
+        assert_eq!(writes[0].0, 0x0001_0010);
+    }
+
+    #[test]
+    fn test_srec_records() {
+        let lines = ["S00600004844521B", "S107001001020304DE", "S3090800001001020304D4", "S9030000FC"];
+        let (outcome, writes) = load(LoadFormat::Srec, &lines, None);
+        assert_eq!(outcome, Some(Ok(LoadSummary { records: 2, bytes: 8 })));
+        assert_eq!(writes, [
+            (0x0000_0010, std::vec![1, 2, 3, 4]),
+            (0x0800_0010, std::vec![1, 2, 3, 4]),
+        ]);
+    }
+
+    #[test]
+    fn test_bad_records() {
+        let ihex = |line| load(LoadFormat::IntelHex, &[line], None).0;
+        assert_eq!(ihex(":0400100001020304E3"), Some(Err("bad checksum")));
+        assert_eq!(ihex(":0500100001020304E1"), Some(Err("bad record length")));
+        assert_eq!(ihex(":0400100001020304E"), Some(Err("bad record length")));
+        assert_eq!(ihex(":04001000010203G4E2"), Some(Err("bad hex digit")));
+        assert_eq!(ihex(":00000006FA"), Some(Err("bad record type")));
+        assert_eq!(ihex("0400100001020304E2"), Some(Err("not an Intel HEX record")));
+
+        let srec = |line| load(LoadFormat::Srec, &[line], None).0;
+        assert_eq!(srec("S107001001020304DF"), Some(Err("bad checksum")));
+        assert_eq!(srec("S108001001020304DD"), Some(Err("bad record length")));
+        assert_eq!(srec("S4030000FC"), Some(Err("bad record type")));
+
+        // Nothing is written past the failing record
+        let lines = [":0400100001020304E2", ":0400100001020304E3", ":0400100001020304E2"];
+        let (outcome, writes) = load(LoadFormat::IntelHex, &lines, None);
+        assert_eq!(outcome, Some(Err("bad checksum")));
+        assert_eq!(writes.len(), 1);
+    }
+
+    #[test]
+    fn test_range_and_abort() {
+        let lines = [":020000040800F2", ":0400100001020304E2"];
+        let (outcome, writes) = load(LoadFormat::IntelHex, &lines, Some(below_64k));
+        assert_eq!(outcome, Some(Err("address out of range")));
+        assert!(writes.is_empty());
+
+        let mut loader = Loader::new(LoadFormat::Srec);
+        assert_eq!(loader.feed(b'S', None, record_write), None);
+        assert_eq!(loader.feed(ABORT, None, record_write), Some(Err("aborted")));
+
+        // Blank lines are not records; an overlong one fails when it ends
+        let mut loader = Loader::new(LoadFormat::IntelHex);
+        assert_eq!(loader.feed(b'\n', None, record_write), None);
+        for _ in 0..=LOAD_LINE_MAX {
+            assert_eq!(loader.feed(b'0', None, record_write), None);
+        }
+        assert_eq!(loader.feed(b'\r', None, record_write), Some(Err("record too long")));
+        assert_eq!(loader.lines(), 1);
+    }
+}
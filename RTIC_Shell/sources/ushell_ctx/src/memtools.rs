@@ -0,0 +1,203 @@
+//! Memory inspection built-ins, with the `mem-tools` feature.
+//!
+//! `md <addr> <len>` hexdumps a region, `mw8`, `mw16` and `mw32 <addr> <val>`
+//! write a value with an access of that width (peripheral registers often
+//! need one) and `mfill <addr> <len> <byte>` fills a region:
+//!
+//! ```text
+//! >> mw32 0x20000000 0xDEADBEEF
+//! >> md 0x20000000 8
+//! 20000000: EF BE AD DE 00 00 00 00                          |........|
+//! ```
+//!
+//! Every access is first passed to [`ShellConfig::mem_access`](crate::ShellConfig::mem_access),
+//! which allows the regions that are safe to touch; without it all accesses
+//! are refused. Numbers are decimal or `0x` hexadecimal. The built-ins are
+//! those of the Embassy shell (`ushell2::memtools` there); the dump goes
+//! through the logger, as all output of this crate.
+
+use core::fmt::Write;
+
+use heapless::String;
+use ushell2::log_simple;
+
+use crate::MemAccessFn;
+
+/// Largest region `md` dumps or `mfill` fills, in bytes.
+pub const MD_MAX_LEN: usize = 1024;
+
+/// Bytes per `md` row.
+const ROW_LEN: usize = 16;
+
+const BAD_NUMBER: &str = "mem: invalid number";
+const BAD_LENGTH: &str = "mem: length must be 1..=1024";
+const BAD_VALUE:  &str = "mem: value too large for the width";
+const UNALIGNED:  &str = "mem: unaligned address";
+const DENIED:     &str = "mem: access denied";
+
+/// Run `line` if it is a memory built-in; `None` for any other line.
+pub(crate) fn run(line: &str, access: Option<MemAccessFn>) -> Option<Result<(), &'static str>> {
+    let mut words = line.split_whitespace();
+    let name = words.next()?;
+    let args = [words.next(), words.next(), words.next(), words.next()];
+    let width = match name {
+        "md" | "mfill" => 0,
+        "mw8"          => 1,
+        "mw16"         => 2,
+        "mw32"         => 4,
+        _              => return None,
+    };
+    Some(exec(name, width, args, access))
+}
+
+/// Run the built-in `name`, writing with accesses of `width` bytes.
+fn exec(
+    name:   &str,
+    width:  usize,
+    args:   [Option<&str>; 4],
+    access: Option<MemAccessFn>,
+) -> Result<(), &'static str> {
+    match (name, args) {
+        ("md", [Some(addr), Some(len), None, None]) => {
+            let (addr, len) = (number(addr)?, number(len)?);
+            check(access, addr, len, false)?;
+            dump(addr, len);
+            Ok(())
+        }
+        ("md", _) => Err("usage: md <addr> <len>"),
+        ("mfill", [Some(addr), Some(len), Some(byte), None]) => {
+            let (addr, len, byte) = (number(addr)?, number(len)?, number(byte)?);
+            let byte = u8::try_from(byte).map_err(|_| BAD_VALUE)?;
+            check(access, addr, len, true)?;
+            for i in 0..len {
+                // The region was allowed by the application
+                unsafe { core::ptr::write_volatile((addr + i) as *mut u8, byte) };
+            }
+            Ok(())
+        }
+        ("mfill", _) => Err("usage: mfill <addr> <len> <byte>"),
+        (_, [Some(addr), Some(value), None, None]) => {
+            let (addr, value) = (number(addr)?, number(value)?);
+            if width < core::mem::size_of::<usize>() && value >> (width * 8) != 0 {
+                return Err(BAD_VALUE);
+            }
+            if addr % width != 0 {
+                return Err(UNALIGNED);
+            }
+            check(access, addr, width, true)?;
+            // As above, with the width asked for
+            unsafe {
+                match width {
+                    1 => core::ptr::write_volatile(addr as *mut u8, value as u8),
+                    2 => core::ptr::write_volatile(addr as *mut u16, value as u16),
+                    _ => core::ptr::write_volatile(addr as *mut u32, value as u32),
+                }
+            }
+            Ok(())
+        }
+        _ => Err("usage: mw8|mw16|mw32 <addr> <val>"),
+    }
+}
+
+fn number(arg: &str) -> Result<usize, &'static str> {
+    match arg.strip_prefix("0x").or_else(|| arg.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None      => arg.parse(),
+    }
+    .map_err(|_| BAD_NUMBER)
+}
+
+/// Check the length of a region and ask `access` for it.
+fn check(access: Option<MemAccessFn>, addr: usize, len: usize, write: bool) -> Result<(), &'static str> {
+    if len == 0 || len > MD_MAX_LEN || addr.checked_add(len).is_none() {
+        return Err(BAD_LENGTH);
+    }
+    match access {
+        Some(allowed) if allowed(addr, len, write) => Ok(()),
+        _                                          => Err(DENIED),
+    }
+}
+
+/// Log `len` bytes at `addr`, read byte by byte a row at a time.
+fn dump(addr: usize, len: usize) {
+    let mut row = [0u8; ROW_LEN];
+    for start in (0..len).step_by(ROW_LEN) {
+        let count = (len - start).min(ROW_LEN);
+        for (i, byte) in row[..count].iter_mut().enumerate() {
+            // Checked by `check`
+            *byte = unsafe { core::ptr::read_volatile((addr + start + i) as *const u8) };
+        }
+        log_simple!("{}", format_row(addr + start, &row[..count]));
+    }
+}
+
+/// One `md` row: address, the bytes in hex, then as ASCII (`.` for the
+/// others).
+fn format_row(addr: usize, bytes: &[u8]) -> String<80> {
+    let mut row: String<80> = String::new();
+    let _ = write!(row, "{:08X}: ", addr);
+    for byte in bytes {
+        let _ = write!(row, "{:02X} ", byte);
+    }
+    let _ = write!(row, "{:1$}|", "", (ROW_LEN - bytes.len()) * 3 + 1);
+    for &byte in bytes {
+        let _ = row.push(if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' });
+    }
+    let _ = row.push('|');
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allow_all(_: usize, _: usize, _: bool) -> bool {
+        true
+    }
+
+    #[test]
+    fn test_write_and_fill() {
+        let mut region = [0u32; 4];
+        let addr = region.as_mut_ptr() as usize;
+        let access = Some(allow_all as MemAccessFn);
+        let line = |name: &str, offset: usize, args: &str| {
+            let mut line: String<48> = String::new();
+            let _ = write!(line, "{} {:#x} {}", name, addr + offset, args);
+            line
+        };
+
+        assert_eq!(run(&line("mw32", 0, "0xDEADBEEF"), access), Some(Ok(())));
+        assert_eq!(run(&line("mw8", 5, "0x7F"), access), Some(Ok(())));
+        assert_eq!(run(&line("mw16", 6, "258"), access), Some(Ok(())));
+        assert_eq!(run(&line("mfill", 8, "8 0x11"), access), Some(Ok(())));
+        assert_eq!(region, [0xDEAD_BEEF, 0x0102_7F00, 0x1111_1111, 0x1111_1111]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let deny  = Some((|_, _, _| false) as MemAccessFn);
+        let allow = Some(allow_all as MemAccessFn);
+        assert_eq!(run("led 1", allow), None);
+        assert_eq!(run("md 0x1000", allow), Some(Err("usage: md <addr> <len>")));
+        assert_eq!(run("md 0x1000 4", None), Some(Err(DENIED)));
+        assert_eq!(run("md 0x1000 4", deny), Some(Err(DENIED)));
+        assert_eq!(run("md 0x1000 0", allow), Some(Err(BAD_LENGTH)));
+        assert_eq!(run("md 0x1000 2000", allow), Some(Err(BAD_LENGTH)));
+        assert_eq!(run("md 0xZZ 4", allow), Some(Err(BAD_NUMBER)));
+        assert_eq!(run("mw8 0x1000 0x100", allow), Some(Err(BAD_VALUE)));
+        assert_eq!(run("mw16 0x1001 1", allow), Some(Err(UNALIGNED)));
+        assert_eq!(run("mw32 0x1002 1", deny), Some(Err(UNALIGNED)));
+        assert_eq!(run("mw32 0x1000 1", deny), Some(Err(DENIED)));
+        assert_eq!(run("mfill 0x1000 4 256", allow), Some(Err(BAD_VALUE)));
+    }
+
+    #[test]
+    fn test_format_row() {
+        assert_eq!(
+            format_row(0x2000_0000, b"\xEF\xBE\xAD\xDE\x00\x7FA ").as_str(),
+            "20000000: EF BE AD DE 00 7F 41 20                          |......A |"
+        );
+        let row = format_row(0x10, &[0x31; ROW_LEN]);
+        assert!(row.ends_with("31 31  |1111111111111111|"));
+    }
+}
@@ -0,0 +1,129 @@
+//! Deferred command execution.
+//!
+//! By default [`ShellCtx::step`](crate::ShellCtx::step) runs a command inside
+//! the call that handles the Enter key, so a slow command freezes line editing
+//! until it returns. With [`ShellConfig::submit`](crate::ShellConfig::submit)
+//! set, `step` only edits lines, handles the built-ins and enqueues the lines
+//! to dispatch; an [`Executor`](crate::Executor) in a lower-priority task takes
+//! them from a [`CommandQueue`] and runs them.
+//!
+//! ```ignore
+//! static QUEUE: CommandQueue<MAX_INPUT_LEN, 4> = CommandQueue::new();
+//!
+//! fn submit(line: &str) -> bool {
+//!     let queued = QUEUE.enqueue(line).is_ok();
+//!     rtic::pend(Interrupt::SWI0);
+//!     queued
+//! }
+//!
+//! // The shell task runs above it, e.g. at priority 2
+//! #[task(binds = SWI0, local = [executor], priority = 1)]
+//! fn exec_task(ctx: exec_task::Context) {
+//!     while ctx.local.executor.run_queued(&QUEUE) {}
+//! }
+//! ```
+//!
+//! The executor is taken from the shell with [`ShellCtx::executor`](crate::ShellCtx::executor)
+//! in `init`. Lines entered while the queue is full are dropped with an error.
+//! `stats` does not time queued commands.
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use heapless::{Deque, String};
+
+/// Why a line was not queued.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueError {
+    /// The queue holds its maximum number of lines.
+    Full,
+    /// The line is longer than `IML` bytes.
+    TooLong,
+}
+
+struct Inner<const IML: usize, const N: usize> {
+    lines:   Deque<String<IML>, N>,
+    dropped: u32,
+}
+
+/// Bounded FIFO of command lines, shared between the shell task and the
+/// executor's.
+///
+/// - `IML`: maximum line length in bytes (normally the shell's input length).
+/// - `N`: maximum number of queued lines.
+pub struct CommandQueue<const IML: usize, const N: usize> {
+    inner: Mutex<RefCell<Inner<IML, N>>>,
+}
+
+impl<const IML: usize, const N: usize> Default for CommandQueue<IML, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const IML: usize, const N: usize> CommandQueue<IML, N> {
+    pub const fn new() -> Self {
+        Self {
+            inner: Mutex::new(RefCell::new(Inner { lines: Deque::new(), dropped: 0 })),
+        }
+    }
+
+    /// Appends `line`; fails without side effects (except the drop count)
+    /// if the queue is full or the line too long.
+    pub fn enqueue(&self, line: &str) -> Result<(), QueueError> {
+        let line = String::try_from(line).map_err(|_| QueueError::TooLong)?;
+        critical_section::with(|cs| {
+            let mut inner = self.inner.borrow_ref_mut(cs);
+            if inner.lines.push_back(line).is_err() {
+                inner.dropped = inner.dropped.wrapping_add(1);
+                return Err(QueueError::Full);
+            }
+            Ok(())
+        })
+    }
+
+    /// Removes and returns the oldest line.
+    pub fn dequeue(&self) -> Option<String<IML>> {
+        critical_section::with(|cs| self.inner.borrow_ref_mut(cs).lines.pop_front())
+    }
+
+    /// Number of queued lines.
+    pub fn len(&self) -> usize {
+        critical_section::with(|cs| self.inner.borrow_ref(cs).lines.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Maximum number of queued lines.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Lines rejected because the queue was full, since start-up.
+    pub fn dropped(&self) -> u32 {
+        critical_section::with(|cs| self.inner.borrow_ref(cs).dropped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fifo_order_and_limits() {
+        let queue: CommandQueue<8, 2> = CommandQueue::new();
+        assert!(queue.is_empty());
+        assert_eq!(queue.enqueue("led 1"), Ok(()));
+        assert_eq!(queue.enqueue("led 0"), Ok(()));
+        assert_eq!(queue.enqueue("reset"), Err(QueueError::Full));
+        assert_eq!((queue.len(), queue.capacity(), queue.dropped()), (2, 2, 1));
+
+        assert_eq!(queue.dequeue().unwrap().as_str(), "led 1");
+        assert_eq!(queue.enqueue("too long line"), Err(QueueError::TooLong));
+        assert_eq!(queue.dequeue().unwrap().as_str(), "led 0");
+        assert!(queue.dequeue().is_none());
+        assert_eq!(queue.dropped(), 1);
+    }
+}